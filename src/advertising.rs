@@ -7,7 +7,7 @@ use crate::link_layer::{
 use crate::{Error, Result};
 use std::fmt::{Display, Formatter};
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum AddressKind {
     Public,
     Random,
@@ -779,6 +779,22 @@ impl ConnectRequest {
         event_counter as u64 * self.interval_us() as u64
     }
 
+    /// Returns the 22 LLData octets carried after InitA and AdvA in a
+    /// CONNECT_IND, in over-the-air order.
+    pub fn ll_data(&self) -> [u8; 22] {
+        let mut data = [0u8; 22];
+        data[..4].copy_from_slice(&self.access_address.to_le_bytes());
+        data[4..7].copy_from_slice(&self.crc_init.to_le_bytes()[..3]);
+        data[7] = self.window_size;
+        data[8..10].copy_from_slice(&self.window_offset.to_le_bytes());
+        data[10..12].copy_from_slice(&self.interval.to_le_bytes());
+        data[12..14].copy_from_slice(&self.latency.to_le_bytes());
+        data[14..16].copy_from_slice(&self.supervision_timeout.to_le_bytes());
+        data[16..21].copy_from_slice(&self.channel_map);
+        data[21] = (self.sleep_clock_accuracy << 5) | (self.hop_increment & 0x1f);
+        data
+    }
+
     pub fn enabled_data_channels(&self) -> Vec<u8> {
         (0..=36)
             .filter(|channel| self.channel_map[*channel as usize / 8] & (1 << (*channel % 8)) != 0)
@@ -1791,10 +1807,11 @@ mod tests {
             0xff, 0xff, 0xff, 0xff, 0x1f, // all 37 channels
             10,   // hop 10, SCA 0
         ]);
-        let decoded = decode_advertising_pdu(&pdu(5, 0, payload)).unwrap();
+        let decoded = decode_advertising_pdu(&pdu(5, 0, payload.clone())).unwrap();
         let DecodedAdvertisingPdu::ConnectInd { request, .. } = decoded else {
             panic!("expected CONNECT_IND");
         };
+        assert_eq!(request.ll_data(), payload[12..]);
         assert_eq!(request.access_address, 0x8e89bed6);
         assert_eq!(request.crc_init, 0x563412);
         assert_eq!(request.interval_us(), 30_000);
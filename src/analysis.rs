@@ -0,0 +1,1712 @@
+//! Offline analysis of captured LE packets across every decoding layer.
+//!
+//! [`ConnectionAnalyzer`] takes CRC-checked packets in capture order and routes
+//! them through advertising decode, CONNECT_IND extraction, connection-event
+//! tracking, direction inference, LL encryption, L2CAP reassembly, and
+//! credit-based channel tracking. Encrypted Advertising Data is decrypted with
+//! keyring key material or key material read over a captured connection.
+//! Each packet's layers are returned as typed
+//! values; ATT, SMP, and signaling payloads stay inside the reassembled
+//! [`L2capPdu`] for the caller to decode.
+
+use crate::advertising::{
+    AddressKind, ConnectRequest, DecodedAdvertisingPdu, DeviceAddress, FirstCentralTransmission,
+    decode_advertising_pdu, parse_ad_structures,
+};
+use crate::att::{ATT_FIXED_CHANNEL_ID, AttSignatureStatus, AttSignatureVerifier, DecodedAttPdu};
+use crate::ble::{AdvertisingPdu, BleChannel, LE_ADV_ACCESS_ADDRESS, LePdu};
+use crate::encrypted_data::{
+    ENCRYPTED_DATA_AD_TYPE, EncryptedAdStructure, EncryptedDataKeyMaterialTracker,
+    EncryptedDataStatus, decrypt_encrypted_ad_structures,
+};
+use crate::keyring::{
+    EncryptedDataKeyEntry, IdentityResolvingKeyEntry, LeKeyring, LinkKeyEntry, LongTermKeyEntry,
+    SignatureKeyEntry,
+};
+use crate::l2cap::{L2capCreditBasedChannelTracker, L2capCreditBasedEvent};
+use crate::link_layer::{
+    ConnectionEventTiming, ConnectionTracker, DataChannelPdu, L2capPdu, L2capReassembler,
+    L2capReassemblyOutcome, LeAclDecryptionStatus, LePhy, LinkDirection, LogicalLinkId,
+    SampleTimingError, SleepClockAccuracy,
+};
+use crate::ll_control::LeEncryptionSessionTracker;
+use crate::pairing::{
+    LegacyPairingEvent, LegacyPairingTracker, PairingSessionEvent, PairingSessionSummary,
+    PairingSessionTracker, SecureConnectionsPairingEvent, SecureConnectionsPairingTracker,
+};
+use crate::privacy::{
+    AddressRole, IdentityResolver, ResolvedAddress, is_resolvable_private_address, resolves,
+};
+use crate::smp::{DecodedSmpPdu, LE_SMP_FIXED_CHANNEL_ID, SmpIdentityAddressType};
+use crate::{Error, Result};
+use std::collections::HashMap;
+
+const INTER_FRAME_SPACE_US: u64 = 150;
+/// Allowance past the inter-frame space for the reply's preamble and
+/// timestamp jitter before a gap is treated as the start of a new event.
+const REPLY_ALLOWANCE_US: u64 = 150;
+const LL_START_ENC_REQ: u8 = 0x05;
+const LL_TERMINATE_IND: u8 = 0x02;
+
+#[derive(Clone)]
+pub struct AnalyzerConfig {
+    /// Units per second of [`AnalyzerPacket::time`]: the sample rate for I/Q
+    /// input, or 1 GHz for nanosecond capture timestamps.
+    pub time_base_hz: u32,
+    /// Candidate LTKs in HCI/SMP field order, tried against every encrypted
+    /// connection.
+    pub long_term_keys: Vec<[u8; 16]>,
+    /// Imported LTKs, selected per connection by the EDIV and Rand of its
+    /// `LL_ENC_REQ` rather than tried exhaustively.
+    pub keyring: LeKeyring,
+    /// Searches the TK of LE legacy pairings observed on connections whose
+    /// CONNECT_IND was captured, then decrypts with the derived STK. LTKs
+    /// distributed during such a pairing join the keyring for later
+    /// connections.
+    pub crack_legacy_pairing: bool,
+    /// DHKeys in HCI field order, such as those a host log shows in LE
+    /// Generate DHKey Complete events, tried on every observed LE Secure
+    /// Connections pairing. Pairings using the debug key need none.
+    pub diffie_hellman_keys: Vec<[u8; 32]>,
+    pub receiver_clock_accuracy_ppm: u32,
+    pub maximum_event_advance: u16,
+    pub maximum_counter_skip: u64,
+    pub maximum_l2cap_payload_length: usize,
+}
+
+impl std::fmt::Debug for AnalyzerConfig {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        formatter
+            .debug_struct("AnalyzerConfig")
+            .field("time_base_hz", &self.time_base_hz)
+            .field("long_term_keys", &self.long_term_keys.len())
+            .field("keyring", &self.keyring)
+            .field("crack_legacy_pairing", &self.crack_legacy_pairing)
+            .field("diffie_hellman_keys", &self.diffie_hellman_keys.len())
+            .field(
+                "receiver_clock_accuracy_ppm",
+                &self.receiver_clock_accuracy_ppm,
+            )
+            .field("maximum_event_advance", &self.maximum_event_advance)
+            .field("maximum_counter_skip", &self.maximum_counter_skip)
+            .field(
+                "maximum_l2cap_payload_length",
+                &self.maximum_l2cap_payload_length,
+            )
+            .finish()
+    }
+}
+
+impl AnalyzerConfig {
+    pub fn new(time_base_hz: u32) -> Self {
+        Self {
+            time_base_hz,
+            long_term_keys: Vec::new(),
+            keyring: LeKeyring::new(),
+            crack_legacy_pairing: false,
+            diffie_hellman_keys: Vec::new(),
+            receiver_clock_accuracy_ppm: 20,
+            maximum_event_advance: 32,
+            maximum_counter_skip: 0,
+            maximum_l2cap_payload_length: 65_535,
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.time_base_hz == 0 {
+            return Err(Error::InvalidConfiguration(
+                "analysis time base must be greater than zero".to_owned(),
+            ));
+        }
+        LeEncryptionSessionTracker::new([0; 16], self.maximum_counter_skip)?;
+        L2capReassembler::new(self.maximum_l2cap_payload_length)?;
+        Ok(())
+    }
+}
+
+/// One CRC-checked packet in capture order.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AnalyzerPacket {
+    pub pdu: LePdu,
+    /// False when the capture did not record the RF channel; such packets
+    /// are decoded but not placed on the connection's event timeline.
+    pub channel_known: bool,
+    pub phy: LePhy,
+    /// Access-address time in units of [`AnalyzerConfig::time_base_hz`].
+    pub time: u64,
+    /// Transmitter direction recorded by the capture, when known.
+    pub direction: Option<LinkDirection>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DirectionSource {
+    Capture,
+    /// Inferred from the gap to the previous packet of the connection: a
+    /// packet within one inter-frame space of the previous packet's end
+    /// replies to it, and any other packet opens an event from the central.
+    Timing,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct AnalyzedDirection {
+    pub direction: LinkDirection,
+    pub source: DirectionSource,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct AnalyzedEvent {
+    pub event_counter: u16,
+    /// True for the central packet that opened the event.
+    pub anchor: bool,
+    /// Anchor offset from the tracker's prediction. `None` when the anchor
+    /// established timing itself, as for event 0 and after a connection
+    /// update.
+    pub timing_error: Option<SampleTimingError>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AnalysisLayer {
+    Advertising(AdvertisingPdu),
+    ConnectionRequest {
+        connection: usize,
+        initiator: DeviceAddress,
+        initiator_kind: AddressKind,
+        advertiser: DeviceAddress,
+        advertiser_kind: AddressKind,
+        request: ConnectRequest,
+    },
+    DataChannel {
+        plaintext: DataChannelPdu,
+        decryption: Option<LeAclDecryptionStatus>,
+        retransmission: bool,
+    },
+    /// An encrypted payload that no keyring LTK authenticates.
+    Ciphertext,
+    L2cap(L2capPdu),
+    L2capCredit(L2capCreditBasedEvent),
+    /// Follows the SMP PDU in the preceding [`AnalysisLayer::L2cap`] layer
+    /// when [`AnalyzerConfig::crack_legacy_pairing`] is set.
+    LegacyPairing(LegacyPairingEvent),
+    /// Follows the SMP PDU in the preceding [`AnalysisLayer::L2cap`] layer
+    /// of a connection whose CONNECT_IND was captured.
+    SecureConnectionsPairing(SecureConnectionsPairingEvent),
+    /// A pairing of the connection that completed or failed with the SMP PDU
+    /// in the preceding [`AnalysisLayer::L2cap`] layer.
+    PairingSession(PairingSessionEvent),
+    /// An AdvA, InitA, ScanA, or TargetA of the preceding
+    /// [`AnalysisLayer::Advertising`] layer that an IRK resolved, or the
+    /// connection address behind an Identity Address Information PDU in the
+    /// preceding [`AnalysisLayer::L2cap`] layer.
+    ResolvedAddress(ResolvedAddress),
+    /// The signature check of the Signed Write Command in the preceding
+    /// [`AnalysisLayer::L2cap`] layer. `device` is the signer's identity
+    /// address when known, otherwise its connection address.
+    AttSignature {
+        device: Option<(DeviceAddress, AddressKind)>,
+        sign_counter: u32,
+        status: AttSignatureStatus,
+    },
+    /// The BR/EDR link key cross-transport key derivation gives the LTK of
+    /// the connection's Secure Connections pairing, reported once the
+    /// peripheral's public address is known.
+    DerivedLinkKey(LinkKeyEntry),
+    /// An Encrypted Data AD structure of the preceding
+    /// [`AnalysisLayer::Advertising`] layer, or one nested in the plaintext
+    /// of another. `key` is the keyring entry that decrypted it.
+    EncryptedData {
+        structure: EncryptedAdStructure,
+        key: Option<EncryptedDataKeyEntry>,
+    },
+    /// EAD key material that the Read response in the preceding
+    /// [`AnalysisLayer::L2cap`] layer completed. The address is the GATT
+    /// server's identity or connection address.
+    EncryptedDataKey(EncryptedDataKeyEntry),
+    Note(String),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AnalyzedPacket {
+    pub connection: Option<usize>,
+    pub direction: Option<AnalyzedDirection>,
+    pub event: Option<AnalyzedEvent>,
+    pub layers: Vec<AnalysisLayer>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ConnectionTracking {
+    /// No CONNECT_IND was captured, so event timing is unknown.
+    Untracked,
+    AwaitingFirstEvent,
+    Tracked,
+    Lost,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ConnectionSummary {
+    pub access_address: u32,
+    pub request: Option<ConnectRequest>,
+    pub advertiser: Option<(DeviceAddress, AddressKind)>,
+    pub initiator: Option<(DeviceAddress, AddressKind)>,
+    /// Identity address of the advertiser, when its CONNECT_IND address
+    /// resolved or it distributed its identity during pairing.
+    pub advertiser_identity: Option<(DeviceAddress, AddressKind)>,
+    /// Identity address of the initiator, found the same way.
+    pub initiator_identity: Option<(DeviceAddress, AddressKind)>,
+    pub first_time: Option<u64>,
+    pub last_time: Option<u64>,
+    pub central_packets: u64,
+    pub peripheral_packets: u64,
+    pub first_event: Option<u16>,
+    pub last_event: Option<u16>,
+    pub tracking: ConnectionTracking,
+    pub encryption_started: bool,
+    /// Index into [`AnalyzerConfig::long_term_keys`] of the LTK that
+    /// authenticated the connection's encrypted packets.
+    pub key_index: Option<usize>,
+    /// Whether an LTK from [`AnalyzerConfig::keyring`] authenticated the
+    /// connection's encrypted packets.
+    pub keyring_key: bool,
+    /// Whether the STK recovered from the connection's legacy pairing
+    /// authenticated its encrypted packets.
+    pub short_term_key: bool,
+    /// Whether the LTK derived from the connection's Secure Connections
+    /// pairing authenticated its encrypted packets.
+    pub secure_connections_key: bool,
+    /// LTKs learned from the connection's pairing: distributed after a
+    /// cracked legacy pairing, or derived by Secure Connections.
+    pub pairing_long_term_keys: Vec<LongTermKeyEntry>,
+    /// IRKs distributed in the connection's Identity Information and
+    /// Identity Address Information PDUs.
+    pub pairing_identity_resolving_keys: Vec<IdentityResolvingKeyEntry>,
+    /// CSRKs distributed in the connection's Signing Information PDUs.
+    pub pairing_signature_keys: Vec<SignatureKeyEntry>,
+    /// BR/EDR link keys derived from the connection's Secure Connections LTK.
+    pub pairing_link_keys: Vec<LinkKeyEntry>,
+    /// Every pairing of the connection that completed or failed, in order.
+    pub pairing_sessions: Vec<PairingSessionSummary>,
+    /// EAD key material read from the connection's GATT server.
+    pub encrypted_data_keys: Vec<EncryptedDataKeyEntry>,
+    pub terminated: bool,
+    pub l2cap_pdus: u64,
+    pub att_pdus: u64,
+    pub smp_pdus: u64,
+    pub credit_sdus: u64,
+    pub errors: u64,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct LastTransmission {
+    time: u64,
+    end_time: u64,
+    channel: BleChannel,
+    direction: LinkDirection,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct PlaintextFingerprint {
+    sequence_number: bool,
+    header: [u8; 2],
+    payload: Vec<u8>,
+}
+
+struct Connection {
+    summary: ConnectionSummary,
+    connect_time: Option<u64>,
+    tracker: Option<ConnectionTracker>,
+    current_event: Option<u16>,
+    last: Option<LastTransmission>,
+    last_plaintext: [Option<PlaintextFingerprint>; 2],
+    /// Candidate sessions keyed by the source of their LTK.
+    sessions: Vec<(SessionKey, LeEncryptionSessionTracker)>,
+    /// Set when encryption started without a usable key; later non-empty
+    /// payloads are reported as ciphertext.
+    undecryptable: bool,
+    reassembler: L2capReassembler,
+    credits: L2capCreditBasedChannelTracker,
+    pairing: Option<LegacyPairingTracker>,
+    secure_pairing: Option<SecureConnectionsPairingTracker>,
+    pairing_session: PairingSessionTracker,
+    /// IRKs awaiting the Identity Address Information PDU from the same side.
+    identity_keys: [Option<[u8; 16]>; 2],
+    /// CSRKs each side distributed, which sign its later Signed Write
+    /// Commands.
+    signing_keys: [Option<[u8; 16]>; 2],
+    /// A derived link key waiting for the peripheral's identity address.
+    pending_link_key: Option<[u8; 16]>,
+    key_material: EncryptedDataKeyMaterialTracker,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum SessionKey {
+    /// Index into [`AnalyzerConfig::long_term_keys`].
+    Configured(usize),
+    /// Selected from [`AnalyzerConfig::keyring`] by LL_ENC_REQ.
+    Keyring,
+    /// Derived from the connection's cracked legacy pairing.
+    ShortTermKey,
+    /// Derived from the connection's Secure Connections pairing.
+    SecureConnections,
+}
+
+pub struct ConnectionAnalyzer {
+    config: AnalyzerConfig,
+    connections: Vec<Connection>,
+    by_access_address: HashMap<u32, usize>,
+    resolver: IdentityResolver,
+    signatures: AttSignatureVerifier,
+}
+
+impl std::fmt::Debug for ConnectionAnalyzer {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        formatter
+            .debug_struct("ConnectionAnalyzer")
+            .field("config", &self.config)
+            .field("connections", &self.connections.len())
+            .field("resolver", &self.resolver)
+            .field("signatures", &self.signatures)
+            .finish()
+    }
+}
+
+impl ConnectionAnalyzer {
+    pub fn new(config: AnalyzerConfig) -> Result<Self> {
+        config.validate()?;
+        Ok(Self {
+            resolver: IdentityResolver::from_keyring(&config.keyring),
+            config,
+            connections: Vec::new(),
+            by_access_address: HashMap::new(),
+            signatures: AttSignatureVerifier::new(),
+        })
+    }
+
+    pub fn connections(&self) -> impl Iterator<Item = &ConnectionSummary> {
+        self.connections
+            .iter()
+            .map(|connection| &connection.summary)
+    }
+
+    pub fn analyze(&mut self, packet: &AnalyzerPacket) -> Result<AnalyzedPacket> {
+        if packet.pdu.access_address == LE_ADV_ACCESS_ADDRESS {
+            return self.analyze_advertising(packet);
+        }
+        let connection = match self.by_access_address.get(&packet.pdu.access_address) {
+            Some(&connection) => connection,
+            None => self.open_connection(packet.pdu.access_address, None, None),
+        };
+        self.analyze_data(connection, packet)
+    }
+
+    fn open_connection(
+        &mut self,
+        access_address: u32,
+        connect: Option<(&DecodedAdvertisingPdu, u64)>,
+        first_time: Option<u64>,
+    ) -> usize {
+        let mut summary = ConnectionSummary {
+            access_address,
+            request: None,
+            advertiser: None,
+            initiator: None,
+            advertiser_identity: None,
+            initiator_identity: None,
+            first_time,
+            last_time: first_time,
+            central_packets: 0,
+            peripheral_packets: 0,
+            first_event: None,
+            last_event: None,
+            tracking: ConnectionTracking::Untracked,
+            encryption_started: false,
+            key_index: None,
+            keyring_key: false,
+            short_term_key: false,
+            secure_connections_key: false,
+            pairing_long_term_keys: Vec::new(),
+            pairing_identity_resolving_keys: Vec::new(),
+            pairing_signature_keys: Vec::new(),
+            pairing_link_keys: Vec::new(),
+            pairing_sessions: Vec::new(),
+            encrypted_data_keys: Vec::new(),
+            terminated: false,
+            l2cap_pdus: 0,
+            att_pdus: 0,
+            smp_pdus: 0,
+            credit_sdus: 0,
+            errors: 0,
+        };
+        let mut connect_time = None;
+        if let Some((
+            DecodedAdvertisingPdu::ConnectInd {
+                initiator,
+                initiator_kind,
+                advertiser,
+                advertiser_kind,
+                request,
+            },
+            time,
+        )) = connect
+        {
+            summary.request = Some(request.clone());
+            summary.advertiser = Some((*advertiser, *advertiser_kind));
+            summary.initiator = Some((*initiator, *initiator_kind));
+            summary.tracking = ConnectionTracking::AwaitingFirstEvent;
+            connect_time = Some(time);
+        }
+        let mut sessions: Vec<_> = self
+            .config
+            .long_term_keys
+            .iter()
+            .enumerate()
+            .map(|(index, key)| {
+                (
+                    SessionKey::Configured(index),
+                    LeEncryptionSessionTracker::new(*key, self.config.maximum_counter_skip)
+                        .expect("counter skip validated with the configuration"),
+                )
+            })
+            .collect();
+        if !self.config.keyring.is_empty() {
+            let peers: Vec<_> = summary
+                .initiator
+                .iter()
+                .chain(&summary.advertiser)
+                .map(|(address, _)| *address)
+                .collect();
+            sessions.push((
+                SessionKey::Keyring,
+                LeEncryptionSessionTracker::with_keyring(
+                    self.config.keyring.clone(),
+                    &peers,
+                    self.config.maximum_counter_skip,
+                )
+                .expect("counter skip validated with the configuration"),
+            ));
+        }
+        let addresses = summary.initiator.zip(summary.advertiser);
+        let pairing = addresses
+            .filter(|_| self.config.crack_legacy_pairing)
+            .map(|(initiator, advertiser)| LegacyPairingTracker::new(initiator, advertiser));
+        let secure_pairing = addresses.map(|(initiator, advertiser)| {
+            SecureConnectionsPairingTracker::with_diffie_hellman_keys(
+                initiator,
+                advertiser,
+                self.config.diffie_hellman_keys.clone(),
+            )
+        });
+        self.connections.push(Connection {
+            summary,
+            connect_time,
+            tracker: None,
+            current_event: None,
+            last: None,
+            last_plaintext: [None, None],
+            sessions,
+            undecryptable: false,
+            reassembler: L2capReassembler::new(self.config.maximum_l2cap_payload_length)
+                .expect("L2CAP payload limit validated with the configuration"),
+            credits: L2capCreditBasedChannelTracker::default(),
+            pairing,
+            secure_pairing,
+            pairing_session: PairingSessionTracker::new(),
+            identity_keys: [None; 2],
+            signing_keys: [None; 2],
+            pending_link_key: None,
+            key_material: EncryptedDataKeyMaterialTracker::new(),
+        });
+        let index = self.connections.len() - 1;
+        self.by_access_address.insert(access_address, index);
+        index
+    }
+
+    fn analyze_advertising(&mut self, packet: &AnalyzerPacket) -> Result<AnalyzedPacket> {
+        let mut analyzed = AnalyzedPacket {
+            connection: None,
+            direction: None,
+            event: None,
+            layers: Vec::new(),
+        };
+        let advertising = match AdvertisingPdu::try_from(packet.pdu.clone()) {
+            Ok(advertising) => advertising,
+            Err(error) => {
+                analyzed.layers.push(AnalysisLayer::Note(format!(
+                    "advertising PDU is malformed: {error}"
+                )));
+                return Ok(analyzed);
+            }
+        };
+        analyzed
+            .layers
+            .push(AnalysisLayer::Advertising(advertising.clone()));
+        let Ok(decoded) = decode_advertising_pdu(&advertising) else {
+            return Ok(analyzed);
+        };
+        let resolved = self.resolver.resolve_advertising(&decoded);
+        analyzed
+            .layers
+            .extend(resolved.iter().copied().map(AnalysisLayer::ResolvedAddress));
+        analyzed.layers.extend(decrypt_advertising_data(
+            &self.config.keyring,
+            &decoded,
+            &resolved,
+        ));
+        if !packet.pdu.channel.is_primary_advertising() {
+            return Ok(analyzed);
+        }
+        if let DecodedAdvertisingPdu::ConnectInd {
+            initiator,
+            initiator_kind,
+            advertiser,
+            advertiser_kind,
+            request,
+        } = &decoded
+        {
+            let connection =
+                self.open_connection(request.access_address, Some((&decoded, packet.time)), None);
+            let summary = &mut self.connections[connection].summary;
+            for resolved in &resolved {
+                match resolved.role {
+                    AddressRole::Advertiser => {
+                        summary.advertiser_identity = Some(resolved.identity)
+                    }
+                    AddressRole::Initiator => summary.initiator_identity = Some(resolved.identity),
+                    _ => {}
+                }
+            }
+            analyzed.connection = Some(connection);
+            analyzed.layers.push(AnalysisLayer::ConnectionRequest {
+                connection,
+                initiator: *initiator,
+                initiator_kind: *initiator_kind,
+                advertiser: *advertiser,
+                advertiser_kind: *advertiser_kind,
+                request: request.clone(),
+            });
+        }
+        Ok(analyzed)
+    }
+
+    fn analyze_data(&mut self, index: usize, packet: &AnalyzerPacket) -> Result<AnalyzedPacket> {
+        let config = self.config.clone();
+        let connection = &mut self.connections[index];
+        let mut layers = Vec::new();
+        let data = DataChannelPdu::from(packet.pdu.clone());
+        let end_time =
+            packet
+                .time
+                .saturating_add(airtime_units(&data, packet.phy, config.time_base_hz));
+
+        let reply_to = connection.last.filter(|last| {
+            (last.channel == packet.pdu.channel || !packet.channel_known)
+                && packet.time >= last.time
+                && packet.time
+                    <= last.end_time.saturating_add(microseconds_to_units(
+                        INTER_FRAME_SPACE_US + REPLY_ALLOWANCE_US,
+                        config.time_base_hz,
+                    ))
+        });
+        let direction = match packet.direction {
+            Some(direction) => AnalyzedDirection {
+                direction,
+                source: DirectionSource::Capture,
+            },
+            None => AnalyzedDirection {
+                direction: match reply_to {
+                    Some(last) => opposite(last.direction),
+                    None => LinkDirection::CentralToPeripheral,
+                },
+                source: DirectionSource::Timing,
+            },
+        };
+        connection.last = Some(LastTransmission {
+            time: packet.time,
+            end_time,
+            channel: packet.pdu.channel,
+            direction: direction.direction,
+        });
+        connection.summary.first_time.get_or_insert(packet.time);
+        connection.summary.last_time = Some(packet.time);
+        match direction.direction {
+            LinkDirection::CentralToPeripheral => connection.summary.central_packets += 1,
+            LinkDirection::PeripheralToCentral => connection.summary.peripheral_packets += 1,
+        }
+
+        let anchor =
+            direction.direction == LinkDirection::CentralToPeripheral && reply_to.is_none();
+        let event = if anchor {
+            match locate_event(connection, &config, packet) {
+                Ok(event) => event,
+                Err(error) => {
+                    connection.tracker = None;
+                    connection.current_event = None;
+                    connection.summary.tracking = ConnectionTracking::Lost;
+                    connection.summary.errors += 1;
+                    layers.push(AnalysisLayer::Note(format!(
+                        "connection tracking lost: {error}"
+                    )));
+                    None
+                }
+            }
+        } else {
+            connection.current_event.map(|event_counter| AnalyzedEvent {
+                event_counter,
+                anchor: false,
+                timing_error: None,
+            })
+        };
+        if let Some(event) = event {
+            connection
+                .summary
+                .first_event
+                .get_or_insert(event.event_counter);
+            connection.summary.last_event = Some(event.event_counter);
+        }
+
+        let learned_keys = connection.summary.pairing_long_term_keys.len();
+        let learned_identities = connection.summary.pairing_identity_resolving_keys.len();
+        let learned_signatures = connection.summary.pairing_signature_keys.len();
+        let learned_link_keys = connection.summary.pairing_link_keys.len();
+        let learned_key_material = connection.summary.encrypted_data_keys.len();
+        let plaintext = decrypt(connection, direction.direction, &data, &mut layers);
+        if let Some((plaintext, decryption)) = plaintext {
+            let retransmission = match decryption {
+                Some(LeAclDecryptionStatus::Retransmission { .. }) => true,
+                Some(_) => false,
+                None => {
+                    let fingerprint = PlaintextFingerprint {
+                        sequence_number: plaintext.sequence_number(),
+                        header: [plaintext.header[0] & 0xe3, plaintext.header[1]],
+                        payload: plaintext.payload.clone(),
+                    };
+                    let slot = &mut connection.last_plaintext[direction_slot(direction.direction)];
+                    let repeated = slot.as_ref() == Some(&fingerprint);
+                    *slot = Some(fingerprint);
+                    repeated
+                }
+            };
+            layers.push(AnalysisLayer::DataChannel {
+                plaintext: plaintext.clone(),
+                decryption,
+                retransmission,
+            });
+            if !retransmission {
+                deliver(
+                    connection,
+                    &config,
+                    &mut self.signatures,
+                    direction.direction,
+                    &plaintext,
+                    &mut layers,
+                );
+            }
+        }
+        let learned: Vec<_> = connection.summary.pairing_long_term_keys[learned_keys..].to_vec();
+        for key in learned {
+            self.config.keyring.add_long_term_key(key);
+        }
+        let learned: Vec<_> =
+            connection.summary.pairing_identity_resolving_keys[learned_identities..].to_vec();
+        for key in learned {
+            self.config.keyring.add_identity_resolving_key(key);
+            self.resolver.add(key);
+        }
+        let learned: Vec<_> =
+            connection.summary.pairing_signature_keys[learned_signatures..].to_vec();
+        for key in learned {
+            self.config.keyring.add_signature_key(key);
+        }
+        let learned: Vec<_> = connection.summary.pairing_link_keys[learned_link_keys..].to_vec();
+        for key in learned {
+            self.config.keyring.add_link_key(key);
+        }
+        let learned: Vec<_> =
+            connection.summary.encrypted_data_keys[learned_key_material..].to_vec();
+        for key in learned {
+            self.config.keyring.add_encrypted_data_key(key);
+        }
+
+        Ok(AnalyzedPacket {
+            connection: Some(index),
+            direction: Some(direction),
+            event,
+            layers,
+        })
+    }
+}
+
+/// Places a central packet that opened an event on the connection's event
+/// timeline, acquiring event 0 from the CONNECT_IND and re-anchoring after
+/// connection updates.
+fn locate_event(
+    connection: &mut Connection,
+    config: &AnalyzerConfig,
+    packet: &AnalyzerPacket,
+) -> Result<Option<AnalyzedEvent>> {
+    let channel = packet.pdu.channel;
+    let Some(request) = connection.summary.request.clone() else {
+        return Ok(None);
+    };
+    if !packet.channel_known {
+        return Ok(None);
+    }
+    let Some(tracker) = &mut connection.tracker else {
+        if connection.summary.tracking != ConnectionTracking::AwaitingFirstEvent {
+            return Ok(None);
+        }
+        let connect_time = connection
+            .connect_time
+            .expect("awaiting connections record their CONNECT_IND time");
+        let tracker = request.acquire_first_event_anchor(
+            connect_time,
+            config.time_base_hz,
+            config.receiver_clock_accuracy_ppm,
+            FirstCentralTransmission::new(channel, packet.time)?,
+        )?;
+        let event_counter = tracker.event_counter();
+        connection.tracker = Some(tracker);
+        connection.current_event = Some(event_counter);
+        connection.summary.tracking = ConnectionTracking::Tracked;
+        return Ok(Some(AnalyzedEvent {
+            event_counter,
+            anchor: true,
+            timing_error: None,
+        }));
+    };
+
+    let event = if matches!(
+        tracker.current_event()?.timing,
+        ConnectionEventTiming::AnchorObservationRequired { .. }
+    ) && tracker.current_event()?.channel == channel
+    {
+        AnalyzedEvent {
+            event_counter: tracker.observe_anchor(packet.time)?.event_counter,
+            anchor: true,
+            timing_error: None,
+        }
+    } else {
+        let peer_clock_accuracy = SleepClockAccuracy::new(request.sleep_clock_accuracy)?;
+        match tracker.synchronize_observation(
+            channel,
+            packet.time,
+            peer_clock_accuracy,
+            config.receiver_clock_accuracy_ppm,
+            config.maximum_event_advance,
+        ) {
+            Ok(observation) => AnalyzedEvent {
+                event_counter: observation.event.event_counter,
+                anchor: true,
+                timing_error: Some(observation.timing_error),
+            },
+            Err(error) => {
+                // The observation may be the first anchor after a connection
+                // update, whose instant stops synchronization's search.
+                let mut candidate = tracker.clone();
+                let mut anchored = None;
+                for _ in 0..config.maximum_event_advance {
+                    let event = match candidate.advance() {
+                        Ok(event) => event,
+                        Err(_) => break,
+                    };
+                    if let ConnectionEventTiming::AnchorObservationRequired { .. } = event.timing {
+                        if event.channel == channel {
+                            anchored = Some(candidate.observe_anchor(packet.time)?);
+                        }
+                        break;
+                    }
+                }
+                let Some(event) = anchored else {
+                    return Err(error);
+                };
+                *tracker = candidate;
+                AnalyzedEvent {
+                    event_counter: event.event_counter,
+                    anchor: true,
+                    timing_error: None,
+                }
+            }
+        }
+    };
+    connection.current_event = Some(event.event_counter);
+    Ok(Some(event))
+}
+
+/// Returns the plaintext of a data packet, or `None` when it is ciphertext
+/// without a usable key or every candidate session rejected it.
+fn decrypt(
+    connection: &mut Connection,
+    direction: LinkDirection,
+    data: &DataChannelPdu,
+    layers: &mut Vec<AnalysisLayer>,
+) -> Option<(DataChannelPdu, Option<LeAclDecryptionStatus>)> {
+    if connection.sessions.is_empty() {
+        if connection.undecryptable && !data.payload.is_empty() {
+            layers.push(AnalysisLayer::Ciphertext);
+            return None;
+        }
+        if data.llid() == LogicalLinkId::Control && data.payload.first() == Some(&LL_START_ENC_REQ)
+        {
+            connection.summary.encryption_started = true;
+            connection.undecryptable = true;
+        }
+        return Some((data.clone(), None));
+    }
+
+    let mut accepted = None;
+    let mut first_error = None;
+    let mut rejected = Vec::new();
+    for (position, (key, session)) in connection.sessions.iter_mut().enumerate() {
+        match session.observe(direction, data) {
+            Ok(observation) => {
+                if accepted.is_none() {
+                    accepted = Some((*key, observation));
+                }
+            }
+            Err(error) => {
+                first_error.get_or_insert(error);
+                rejected.push(position);
+            }
+        }
+    }
+    let Some((key, observation)) = accepted else {
+        let encrypted = connection
+            .sessions
+            .iter()
+            .any(|(_, session)| session.direction_encrypted(direction));
+        connection.summary.errors += 1;
+        if encrypted
+            && connection.summary.key_index.is_none()
+            && !connection.summary.keyring_key
+            && !connection.summary.short_term_key
+            && !connection.summary.secure_connections_key
+        {
+            connection.sessions.clear();
+            connection.undecryptable = true;
+            connection.summary.encryption_started = true;
+            layers.push(AnalysisLayer::Note(
+                "no keyring LTK authenticates the encrypted connection".to_owned(),
+            ));
+            layers.push(AnalysisLayer::Ciphertext);
+        } else {
+            layers.push(AnalysisLayer::Note(format!(
+                "LL encryption error: {}",
+                first_error.expect("every session rejected the packet")
+            )));
+            if encrypted {
+                layers.push(AnalysisLayer::Ciphertext);
+            }
+        }
+        return None;
+    };
+    // Sessions whose LTK failed where another succeeded cannot be the key.
+    for position in rejected.into_iter().rev() {
+        connection.sessions.remove(position);
+    }
+    if observation.decryption.is_some() {
+        connection.summary.encryption_started = true;
+        if matches!(
+            observation.decryption,
+            Some(LeAclDecryptionStatus::New { .. })
+        ) {
+            match key {
+                SessionKey::Configured(index) => connection.summary.key_index = Some(index),
+                SessionKey::Keyring => connection.summary.keyring_key = true,
+                SessionKey::ShortTermKey => connection.summary.short_term_key = true,
+                SessionKey::SecureConnections => {
+                    connection.summary.secure_connections_key = true;
+                }
+            }
+            connection
+                .sessions
+                .retain(|(candidate, _)| *candidate == key);
+        }
+    }
+    Some((observation.packet, observation.decryption))
+}
+
+fn deliver(
+    connection: &mut Connection,
+    config: &AnalyzerConfig,
+    signatures: &mut AttSignatureVerifier,
+    direction: LinkDirection,
+    plaintext: &DataChannelPdu,
+    layers: &mut Vec<AnalysisLayer>,
+) {
+    match plaintext.control() {
+        Ok(Some(control)) => {
+            if control.opcode == LL_TERMINATE_IND {
+                connection.summary.terminated = true;
+            }
+            if let Some(tracker) = &mut connection.tracker
+                && let Err(error) = tracker.schedule_control(control)
+            {
+                connection.summary.errors += 1;
+                layers.push(AnalysisLayer::Note(format!(
+                    "connection update was not scheduled: {error}"
+                )));
+            }
+            return;
+        }
+        Ok(None) => {}
+        Err(error) => {
+            connection.summary.errors += 1;
+            layers.push(AnalysisLayer::Note(format!(
+                "LL control PDU is malformed: {error}"
+            )));
+            return;
+        }
+    }
+
+    let update = match connection.reassembler.push(direction, plaintext) {
+        Ok(update) => update,
+        Err(error) => {
+            connection.summary.errors += 1;
+            layers.push(AnalysisLayer::Note(format!(
+                "L2CAP reassembly error: {error}"
+            )));
+            return;
+        }
+    };
+    if let Some(replaced) = update.replaced {
+        connection.summary.errors += 1;
+        layers.push(AnalysisLayer::Note(format!(
+            "replaced incomplete L2CAP PDU on CID 0x{:04x} after {} of {} octets",
+            replaced.channel_id, replaced.received_payload_length, replaced.expected_payload_length
+        )));
+    }
+    match update.outcome {
+        L2capReassemblyOutcome::Complete(pdu) => {
+            connection.summary.l2cap_pdus += 1;
+            match pdu.channel_id {
+                ATT_FIXED_CHANNEL_ID => connection.summary.att_pdus += 1,
+                LE_SMP_FIXED_CHANNEL_ID => connection.summary.smp_pdus += 1,
+                _ => {}
+            }
+            let credit = connection.credits.observe(&pdu);
+            let pairing = observe_pairing(connection, config.maximum_counter_skip, &pdu);
+            let identity = observe_identity(connection, &pdu);
+            let signature = verify_signed_write(connection, &config.keyring, signatures, &pdu);
+            let key_material = observe_key_material(connection, &pdu);
+            layers.push(AnalysisLayer::L2cap(pdu));
+            layers.extend(pairing);
+            layers.extend(identity.map(AnalysisLayer::ResolvedAddress));
+            layers.extend(settle_link_key(connection).map(AnalysisLayer::DerivedLinkKey));
+            layers.extend(signature);
+            layers.extend(key_material.map(AnalysisLayer::EncryptedDataKey));
+            match credit {
+                Ok(L2capCreditBasedEvent::Ignored) => {}
+                Ok(event) => {
+                    if matches!(event, L2capCreditBasedEvent::SduComplete(_)) {
+                        connection.summary.credit_sdus += 1;
+                    }
+                    layers.push(AnalysisLayer::L2capCredit(event));
+                }
+                Err(error) => {
+                    connection.summary.errors += 1;
+                    layers.push(AnalysisLayer::Note(format!(
+                        "credit-based channel error: {error}"
+                    )));
+                }
+            }
+        }
+        L2capReassemblyOutcome::OrphanedContinuation { fragment_octets } => {
+            layers.push(AnalysisLayer::Note(format!(
+                "orphaned L2CAP continuation of {fragment_octets} octets"
+            )));
+        }
+        L2capReassemblyOutcome::Ignored
+        | L2capReassemblyOutcome::InProgress(_)
+        | L2capReassemblyOutcome::Duplicate => {}
+    }
+}
+
+/// Feeds an SMP PDU to the pairing trackers and starts decrypting with the
+/// keys they recover.
+fn observe_pairing(
+    connection: &mut Connection,
+    maximum_counter_skip: u64,
+    pdu: &L2capPdu,
+) -> Vec<AnalysisLayer> {
+    let mut layers = Vec::new();
+    let mut session_key = None;
+    match connection.pairing_session.observe(pdu) {
+        Ok(Some(event)) => {
+            let (PairingSessionEvent::Completed(summary) | PairingSessionEvent::Failed(summary)) =
+                &event;
+            connection.summary.pairing_sessions.push(summary.clone());
+            layers.push(AnalysisLayer::PairingSession(event));
+        }
+        Ok(None) => {}
+        Err(error) => layers.push(AnalysisLayer::Note(format!(
+            "pairing session tracking skipped an SMP PDU: {error}"
+        ))),
+    }
+    if let Some(pairing) = &mut connection.pairing {
+        match pairing.observe(pdu) {
+            Ok(Some(event)) => {
+                match event {
+                    LegacyPairingEvent::KeysRecovered(keys) => {
+                        session_key = Some((SessionKey::ShortTermKey, keys.short_term_key));
+                    }
+                    LegacyPairingEvent::LongTermKeyDistributed { key, .. } => {
+                        connection.summary.pairing_long_term_keys.push(key);
+                    }
+                    LegacyPairingEvent::NotRecovered(_) => {}
+                }
+                layers.push(AnalysisLayer::LegacyPairing(event));
+            }
+            Ok(None) => {}
+            Err(error) => layers.push(AnalysisLayer::Note(format!(
+                "legacy pairing tracking skipped an SMP PDU: {error}"
+            ))),
+        }
+    }
+    if let Some(pairing) = &mut connection.secure_pairing {
+        match pairing.observe(pdu) {
+            Ok(Some(event)) => {
+                if let SecureConnectionsPairingEvent::KeysDerived(keys) = event {
+                    session_key = Some((SessionKey::SecureConnections, keys.long_term_key));
+                    connection.pending_link_key = keys.link_key;
+                    connection
+                        .summary
+                        .pairing_long_term_keys
+                        .push(LongTermKeyEntry {
+                            address: connection.summary.advertiser,
+                            encrypted_diversifier: 0,
+                            random_number: [0; 8],
+                            key: keys.long_term_key,
+                        });
+                }
+                layers.push(AnalysisLayer::SecureConnectionsPairing(event));
+            }
+            Ok(None) => {}
+            // Legacy tracking reports malformed SMP PDUs when enabled; the
+            // caller decodes the L2CAP layer either way.
+            Err(_) => {}
+        }
+    }
+    if let Some((source, key)) = session_key {
+        connection
+            .sessions
+            .retain(|(candidate, _)| *candidate != source);
+        connection.sessions.push((
+            source,
+            LeEncryptionSessionTracker::new(key, maximum_counter_skip)
+                .expect("counter skip validated with the configuration"),
+        ));
+    }
+    layers
+}
+
+/// Pairs the IRK and identity address an SMP key distribution carries and
+/// matches them against the sender's connection address. CSRKs are kept for
+/// the sender's later Signed Write Commands.
+fn observe_identity(connection: &mut Connection, pdu: &L2capPdu) -> Option<ResolvedAddress> {
+    let smp = pdu.smp_pdu().ok()??;
+    let slot = direction_slot(pdu.direction);
+    let identity = match smp.decode().ok()? {
+        DecodedSmpPdu::IdentityInformation(key) => {
+            connection.identity_keys[slot] = Some(key);
+            return None;
+        }
+        DecodedSmpPdu::SigningInformation(key) => {
+            connection.signing_keys[slot] = Some(key);
+            if let Some(address) = sender(&connection.summary, pdu.direction) {
+                connection
+                    .summary
+                    .pairing_signature_keys
+                    .push(SignatureKeyEntry { address, key });
+            }
+            return None;
+        }
+        DecodedSmpPdu::IdentityAddressInformation(identity) => (
+            DeviceAddress(identity.address),
+            match identity.address_type {
+                SmpIdentityAddressType::Public => AddressKind::Public,
+                SmpIdentityAddressType::StaticRandom => AddressKind::Random,
+            },
+        ),
+        _ => return None,
+    };
+    let key = connection.identity_keys[slot].take()?;
+    connection
+        .summary
+        .pairing_identity_resolving_keys
+        .push(IdentityResolvingKeyEntry {
+            address: identity,
+            key,
+        });
+    let (address, summary_identity) = match pdu.direction {
+        LinkDirection::CentralToPeripheral => (
+            connection.summary.initiator?,
+            &mut connection.summary.initiator_identity,
+        ),
+        LinkDirection::PeripheralToCentral => (
+            connection.summary.advertiser?,
+            &mut connection.summary.advertiser_identity,
+        ),
+    };
+    if address != identity
+        && !(is_resolvable_private_address(address.0, address.1) && resolves(key, address.0))
+    {
+        return None;
+    }
+    *summary_identity = Some(identity);
+    Some(ResolvedAddress {
+        role: AddressRole::SmpIdentityAddress,
+        address,
+        identity,
+    })
+}
+
+/// Stores a pending derived link key under the peripheral's BR/EDR address,
+/// its public identity or connection address. A static random identity has
+/// no BR/EDR counterpart, so the key stays pending.
+fn settle_link_key(connection: &mut Connection) -> Option<LinkKeyEntry> {
+    connection.pending_link_key?;
+    let summary = &mut connection.summary;
+    let (address, kind) = summary.advertiser_identity.or(summary.advertiser)?;
+    if kind != AddressKind::Public {
+        return None;
+    }
+    let entry = LinkKeyEntry {
+        address,
+        key: connection.pending_link_key.take()?,
+    };
+    summary.pairing_link_keys.push(entry);
+    Some(entry)
+}
+
+/// The identity address of the device sending in `direction`, or its
+/// connection address while the identity is unknown.
+fn sender(
+    summary: &ConnectionSummary,
+    direction: LinkDirection,
+) -> Option<(DeviceAddress, AddressKind)> {
+    match direction {
+        LinkDirection::CentralToPeripheral => summary.initiator_identity.or(summary.initiator),
+        LinkDirection::PeripheralToCentral => summary.advertiser_identity.or(summary.advertiser),
+    }
+}
+
+/// Records EAD key material completed by an ATT read response under the
+/// address of the server that sent it.
+fn observe_key_material(
+    connection: &mut Connection,
+    pdu: &L2capPdu,
+) -> Option<EncryptedDataKeyEntry> {
+    let entry = EncryptedDataKeyEntry {
+        address: sender(&connection.summary, pdu.direction),
+        key_material: connection.key_material.observe(pdu)?,
+    };
+    connection.summary.encrypted_data_keys.push(entry);
+    Some(entry)
+}
+
+/// Decrypts the Encrypted Data structures of an advertising PDU with the
+/// keyring's EAD key material, trying the advertiser's own entries first.
+fn decrypt_advertising_data(
+    keyring: &LeKeyring,
+    decoded: &DecodedAdvertisingPdu,
+    resolved: &[ResolvedAddress],
+) -> Vec<AnalysisLayer> {
+    let (advertiser, structures) = match decoded {
+        DecodedAdvertisingPdu::AdvInd {
+            advertiser,
+            advertiser_kind,
+            data,
+        }
+        | DecodedAdvertisingPdu::AdvNonconnInd {
+            advertiser,
+            advertiser_kind,
+            data,
+        }
+        | DecodedAdvertisingPdu::ScanRsp {
+            advertiser,
+            advertiser_kind,
+            data,
+        }
+        | DecodedAdvertisingPdu::AdvScanInd {
+            advertiser,
+            advertiser_kind,
+            data,
+        } => (Some((*advertiser, *advertiser_kind)), data.clone()),
+        DecodedAdvertisingPdu::AdvExtInd { header } => (
+            header
+                .advertiser_address
+                .zip(header.advertiser_address_kind),
+            parse_ad_structures(&header.advertising_data).unwrap_or_default(),
+        ),
+        _ => return Vec::new(),
+    };
+    if !structures
+        .iter()
+        .any(|structure| structure.ad_type == ENCRYPTED_DATA_AD_TYPE)
+    {
+        return Vec::new();
+    }
+    let identity = resolved
+        .iter()
+        .find(|resolved| resolved.role == AddressRole::Advertiser)
+        .map(|resolved| resolved.identity);
+    let (mut entries, others): (Vec<_>, Vec<_>) = keyring
+        .encrypted_data_keys()
+        .iter()
+        .copied()
+        .partition(|entry| {
+            entry.address.is_some() && [advertiser, identity].contains(&entry.address)
+        });
+    entries.extend(others);
+    let keys: Vec<_> = entries.iter().map(|entry| entry.key_material).collect();
+    decrypt_encrypted_ad_structures(&structures, &keys)
+        .into_iter()
+        .map(|structure| {
+            let key = match structure.status {
+                EncryptedDataStatus::Decrypted { key, .. }
+                | EncryptedDataStatus::MalformedPlaintext { key, .. } => Some(entries[key]),
+                _ => None,
+            };
+            AnalysisLayer::EncryptedData { structure, key }
+        })
+        .collect()
+}
+
+/// Checks a Signed Write Command against the CSRK the sender distributed on
+/// this connection and the keyring CSRKs of its address.
+fn verify_signed_write(
+    connection: &Connection,
+    keyring: &LeKeyring,
+    signatures: &mut AttSignatureVerifier,
+    pdu: &L2capPdu,
+) -> Option<AnalysisLayer> {
+    let Ok(DecodedAttPdu::SignedWriteCommand(write)) = pdu.att_pdu().ok()??.decode() else {
+        return None;
+    };
+    let device = sender(&connection.summary, pdu.direction);
+    let addresses = [
+        device,
+        match pdu.direction {
+            LinkDirection::CentralToPeripheral => connection.summary.initiator,
+            LinkDirection::PeripheralToCentral => connection.summary.advertiser,
+        },
+    ];
+    let csrks: Vec<_> = connection.signing_keys[direction_slot(pdu.direction)]
+        .into_iter()
+        .chain(
+            keyring
+                .signature_keys()
+                .iter()
+                .filter(|entry| addresses.contains(&Some(entry.address)))
+                .map(|entry| entry.key),
+        )
+        .collect();
+    Some(AnalysisLayer::AttSignature {
+        device,
+        sign_counter: write.sign_counter(),
+        status: signatures.verify(device, &write, &csrks),
+    })
+}
+
+const fn opposite(direction: LinkDirection) -> LinkDirection {
+    match direction {
+        LinkDirection::CentralToPeripheral => LinkDirection::PeripheralToCentral,
+        LinkDirection::PeripheralToCentral => LinkDirection::CentralToPeripheral,
+    }
+}
+
+const fn direction_slot(direction: LinkDirection) -> usize {
+    match direction {
+        LinkDirection::CentralToPeripheral => 0,
+        LinkDirection::PeripheralToCentral => 1,
+    }
+}
+
+fn microseconds_to_units(microseconds: u64, time_base_hz: u32) -> u64 {
+    (u128::from(microseconds) * u128::from(time_base_hz)).div_ceil(1_000_000) as u64
+}
+
+/// Time from the access address to the end of the CRC. LE Coded is timed at
+/// S=8, the longer coding, so replies are never mistaken for new events.
+fn airtime_units(data: &DataChannelPdu, phy: LePhy, time_base_hz: u32) -> u64 {
+    let octets = 4 + 2 + u64::from(data.cte_info.is_some()) + data.payload.len() as u64 + 3;
+    let microseconds = match phy {
+        LePhy::Le1M => octets * 8,
+        LePhy::Le2M => (octets * 8).div_ceil(2),
+        LePhy::LeCoded => octets * 8 * 8,
+    };
+    microseconds_to_units(microseconds, time_base_hz)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::link_layer::{
+        ChannelMapInd, ChannelSelectionAlgorithm, ConnectionUpdateInd, DataChannelMap,
+    };
+    use crate::ll_control::{EncryptionRequest, EncryptionResponse};
+    use crate::scenario::{
+        ConnectionScenario, ScenarioAction, ScenarioActionKind, ScenarioEncryption, ScenarioPacket,
+    };
+
+    const LONG_TERM_KEY: [u8; 16] = [
+        0xbf, 0x01, 0xfb, 0x9d, 0x4e, 0xf3, 0xbc, 0x36, 0xd8, 0x74, 0xf5, 0x39, 0x41, 0x38, 0x68,
+        0x4c,
+    ];
+
+    fn notification() -> Vec<u8> {
+        [0x1b, 0x2a, 0x00]
+            .into_iter()
+            .chain((0..40).map(|value| value as u8))
+            .collect()
+    }
+
+    fn scenario() -> ConnectionScenario {
+        ConnectionScenario {
+            sample_rate_hz: 4_000_000,
+            advertising_channel: BleChannel::new(37).unwrap(),
+            advertiser: DeviceAddress([0x06, 0x05, 0x04, 0x03, 0x02, 0x01]),
+            advertiser_kind: AddressKind::Public,
+            initiator: DeviceAddress([0x11, 0x22, 0x33, 0x44, 0x55, 0xc6]),
+            initiator_kind: AddressKind::Random,
+            advertising_data: vec![0x02, 0x01, 0x06],
+            advertising_sample: 1_000,
+            connect_request: ConnectRequest {
+                access_address: 0x5065_4b4f,
+                crc_init: 0x7a_4f21,
+                window_size: 2,
+                window_offset: 1,
+                interval: 8,
+                latency: 0,
+                supervision_timeout: 100,
+                channel_map: [0xff, 0xff, 0xff, 0xff, 0x1f],
+                hop_increment: 7,
+                sleep_clock_accuracy: 5,
+                channel_selection_algorithm: ChannelSelectionAlgorithm::Csa2,
+            },
+            event_count: 16,
+            actions: vec![
+                ScenarioAction {
+                    event_counter: 1,
+                    kind: ScenarioActionKind::L2cap {
+                        direction: LinkDirection::CentralToPeripheral,
+                        channel_id: ATT_FIXED_CHANNEL_ID,
+                        payload: vec![0x0a, 0x2a, 0x00],
+                    },
+                },
+                ScenarioAction {
+                    event_counter: 2,
+                    kind: ScenarioActionKind::StartEncryption(ScenarioEncryption {
+                        long_term_key: LONG_TERM_KEY,
+                        request: EncryptionRequest {
+                            random_number: [0x90, 0x78, 0x56, 0x34, 0x12, 0xef, 0xcd, 0xab],
+                            encrypted_diversifier: 0x2474,
+                            central_session_key_diversifier: [
+                                0x13, 0x02, 0xf1, 0xe0, 0xdf, 0xce, 0xbd, 0xac,
+                            ],
+                            central_initialization_vector: [0x24, 0xab, 0xdc, 0xba],
+                        },
+                        response: EncryptionResponse {
+                            peripheral_session_key_diversifier: [
+                                0x79, 0x68, 0x57, 0x46, 0x35, 0x24, 0x13, 0x02,
+                            ],
+                            peripheral_initialization_vector: [0xbe, 0xba, 0xaf, 0xde],
+                        },
+                    }),
+                },
+                ScenarioAction {
+                    event_counter: 2,
+                    kind: ScenarioActionKind::L2cap {
+                        direction: LinkDirection::PeripheralToCentral,
+                        channel_id: ATT_FIXED_CHANNEL_ID,
+                        payload: notification(),
+                    },
+                },
+                ScenarioAction {
+                    event_counter: 4,
+                    kind: ScenarioActionKind::ChannelMapUpdate(ChannelMapInd {
+                        channel_map: DataChannelMap::new([0x00, 0x00, 0xf0, 0x0f, 0x00]).unwrap(),
+                        instant: 8,
+                    }),
+                },
+                ScenarioAction {
+                    event_counter: 9,
+                    kind: ScenarioActionKind::ConnectionUpdate(
+                        ConnectionUpdateInd::new(1, 3, 12, 0, 100, 12).unwrap(),
+                    ),
+                },
+            ],
+        }
+    }
+
+    fn analyzer_packet(packet: &ScenarioPacket, captured_direction: bool) -> AnalyzerPacket {
+        AnalyzerPacket {
+            pdu: packet.transmission.pdu.clone(),
+            channel_known: true,
+            phy: packet.transmission.phy,
+            time: packet.access_address_sample,
+            direction: packet
+                .link
+                .as_ref()
+                .filter(|_| captured_direction)
+                .map(|link| link.direction),
+        }
+    }
+
+    #[test]
+    fn reconstructs_an_encrypted_connection_without_captured_directions() {
+        let packets = scenario().generate().unwrap();
+        let mut config = AnalyzerConfig::new(4_000_000);
+        config.long_term_keys = vec![[0x55; 16], LONG_TERM_KEY];
+        let mut analyzer = ConnectionAnalyzer::new(config).unwrap();
+
+        let mut att_payloads = Vec::new();
+        for packet in &packets {
+            let analyzed = analyzer.analyze(&analyzer_packet(packet, false)).unwrap();
+            let Some(link) = &packet.link else {
+                continue;
+            };
+            assert_eq!(
+                analyzed.direction,
+                Some(AnalyzedDirection {
+                    direction: link.direction,
+                    source: DirectionSource::Timing,
+                })
+            );
+            assert_eq!(
+                analyzed.event.map(|event| event.event_counter),
+                Some(link.event_counter)
+            );
+            for layer in analyzed.layers {
+                match layer {
+                    AnalysisLayer::DataChannel { plaintext, .. } => {
+                        assert_eq!(plaintext.payload, link.plaintext.payload);
+                    }
+                    AnalysisLayer::L2cap(pdu) => att_payloads.push(pdu.payload),
+                    AnalysisLayer::Note(note) => panic!("unexpected note: {note}"),
+                    _ => {}
+                }
+            }
+        }
+        assert_eq!(att_payloads, [vec![0x0a, 0x2a, 0x00], notification()]);
+
+        let summaries: Vec<_> = analyzer.connections().collect();
+        assert_eq!(summaries.len(), 1);
+        let summary = summaries[0];
+        assert_eq!(summary.tracking, ConnectionTracking::Tracked);
+        assert_eq!(summary.first_event, Some(0));
+        assert_eq!(summary.last_event, Some(15));
+        assert_eq!(summary.key_index, Some(1));
+        assert_eq!(summary.att_pdus, 2);
+        assert_eq!(summary.errors, 0);
+        assert_eq!(summary.central_packets, summary.peripheral_packets);
+    }
+
+    #[test]
+    fn reports_ciphertext_when_no_key_matches() {
+        let packets = scenario().generate().unwrap();
+        let mut analyzer = ConnectionAnalyzer::new(AnalyzerConfig::new(4_000_000)).unwrap();
+
+        let mut ciphertext = 0;
+        for packet in &packets {
+            let analyzed = analyzer.analyze(&analyzer_packet(packet, true)).unwrap();
+            let encrypted = packet
+                .link
+                .as_ref()
+                .is_some_and(|link| link.packet_counter.is_some());
+            let reported = analyzed.layers.contains(&AnalysisLayer::Ciphertext);
+            assert_eq!(
+                reported,
+                encrypted && !packet.transmission.pdu.payload.is_empty()
+            );
+            ciphertext += usize::from(reported);
+        }
+        assert!(ciphertext > 0);
+        let summary = analyzer.connections().next().unwrap();
+        assert!(summary.encryption_started);
+        assert_eq!(summary.key_index, None);
+        assert_eq!(summary.att_pdus, 1);
+        // The connection update arrives encrypted, so tracking stops at its
+        // instant instead of following the new interval.
+        assert_eq!(summary.tracking, ConnectionTracking::Lost);
+    }
+
+    #[test]
+    fn plaintext_retransmissions_skip_upper_layers() {
+        let packets = scenario().generate().unwrap();
+        let mut analyzer = ConnectionAnalyzer::new(AnalyzerConfig::new(4_000_000)).unwrap();
+        let request = packets
+            .iter()
+            .position(|packet| {
+                packet
+                    .link
+                    .as_ref()
+                    .is_some_and(|link| link.plaintext.payload.ends_with(&[0x0a, 0x2a, 0x00]))
+            })
+            .unwrap();
+        for packet in &packets[..=request] {
+            analyzer.analyze(&analyzer_packet(packet, true)).unwrap();
+        }
+
+        // Resend the request as the central's first packet of the next event.
+        let next_event = packets[request..]
+            .iter()
+            .find(|packet| {
+                packet.link.as_ref().is_some_and(|link| {
+                    link.direction == LinkDirection::CentralToPeripheral && link.event_counter == 2
+                })
+            })
+            .unwrap();
+        let mut repeated = analyzer_packet(&packets[request], true);
+        repeated.pdu.channel = next_event.transmission.pdu.channel;
+        repeated.time = next_event.access_address_sample;
+        let analyzed = analyzer.analyze(&repeated).unwrap();
+        assert!(matches!(
+            analyzed.layers.as_slice(),
+            [AnalysisLayer::DataChannel {
+                retransmission: true,
+                ..
+            }]
+        ));
+        assert_eq!(analyzer.connections().next().unwrap().att_pdus, 1);
+    }
+
+    #[test]
+    fn resolves_advertiser_rpas_with_an_irk_captured_during_pairing() {
+        // 70:81:94:0D:FB:AA, generated from the Core specification's sample
+        // IRK ec0234a357c8ad05341010a60a397d9b.
+        let rpa = DeviceAddress([0xaa, 0xfb, 0x0d, 0x94, 0x81, 0x70]);
+        let identity = (
+            DeviceAddress([0x06, 0x05, 0x04, 0x03, 0x02, 0x01]),
+            AddressKind::Public,
+        );
+        let smp = |payload: Vec<u8>| ScenarioActionKind::L2cap {
+            direction: LinkDirection::PeripheralToCentral,
+            channel_id: LE_SMP_FIXED_CHANNEL_ID,
+            payload,
+        };
+        let mut scenario = scenario();
+        scenario.advertiser = rpa;
+        scenario.advertiser_kind = AddressKind::Random;
+        scenario.actions = vec![
+            ScenarioAction {
+                event_counter: 1,
+                kind: smp([0x08]
+                    .into_iter()
+                    .chain([
+                        0x9b, 0x7d, 0x39, 0x0a, 0xa6, 0x10, 0x10, 0x34, 0x05, 0xad, 0xc8, 0x57,
+                        0xa3, 0x34, 0x02, 0xec,
+                    ])
+                    .collect()),
+            },
+            ScenarioAction {
+                event_counter: 2,
+                kind: smp(vec![0x09, 0x00, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01]),
+            },
+        ];
+        let packets = scenario.generate().unwrap();
+        let mut analyzer = ConnectionAnalyzer::new(AnalyzerConfig::new(4_000_000)).unwrap();
+
+        let mut resolved = Vec::new();
+        for pass in 0..2 {
+            for packet in &packets {
+                let analyzed = analyzer.analyze(&analyzer_packet(packet, true)).unwrap();
+                for layer in analyzed.layers {
+                    if let AnalysisLayer::ResolvedAddress(address) = layer {
+                        resolved.push((pass, address.role, address.address));
+                        assert_eq!(address.identity, identity);
+                    }
+                }
+            }
+        }
+        let rpa = (rpa, AddressKind::Random);
+        assert_eq!(
+            resolved,
+            [
+                (0, AddressRole::SmpIdentityAddress, rpa),
+                (1, AddressRole::Advertiser, rpa),
+                (1, AddressRole::Advertiser, rpa),
+                (1, AddressRole::SmpIdentityAddress, rpa),
+            ]
+        );
+        for summary in analyzer.connections() {
+            assert_eq!(summary.advertiser_identity, Some(identity));
+            assert_eq!(summary.initiator_identity, None);
+        }
+        assert_eq!(
+            analyzer
+                .connections()
+                .next()
+                .unwrap()
+                .pairing_identity_resolving_keys[0]
+                .address,
+            identity
+        );
+    }
+
+    #[test]
+    fn verifies_signed_writes_with_the_csrk_distributed_during_pairing() {
+        let csrk = [0x3c; 16];
+        let mut write = vec![0xd2, 0x03, 0x00, 0xaa];
+        write.extend(crate::att::att_signature(csrk, &write, 5));
+        let action = |event_counter, channel_id, payload: Vec<u8>| ScenarioAction {
+            event_counter,
+            kind: ScenarioActionKind::L2cap {
+                direction: LinkDirection::PeripheralToCentral,
+                channel_id,
+                payload,
+            },
+        };
+        let mut scenario = scenario();
+        scenario.actions = vec![
+            action(
+                1,
+                LE_SMP_FIXED_CHANNEL_ID,
+                [0x0a].into_iter().chain(csrk).collect(),
+            ),
+            action(2, ATT_FIXED_CHANNEL_ID, write.clone()),
+            action(3, ATT_FIXED_CHANNEL_ID, write),
+        ];
+        let packets = scenario.generate().unwrap();
+        let mut analyzer = ConnectionAnalyzer::new(AnalyzerConfig::new(4_000_000)).unwrap();
+
+        let mut statuses = Vec::new();
+        for packet in &packets {
+            let analyzed = analyzer.analyze(&analyzer_packet(packet, true)).unwrap();
+            for layer in analyzed.layers {
+                if let AnalysisLayer::AttSignature {
+                    device,
+                    sign_counter,
+                    status,
+                } = layer
+                {
+                    assert_eq!(
+                        device,
+                        Some((scenario.advertiser, scenario.advertiser_kind))
+                    );
+                    statuses.push((sign_counter, status));
+                }
+            }
+        }
+        assert_eq!(
+            statuses,
+            [
+                (5, AttSignatureStatus::Valid),
+                (
+                    5,
+                    AttSignatureStatus::Replayed {
+                        last_sign_counter: 5
+                    }
+                ),
+            ]
+        );
+        let summary = analyzer.connections().next().unwrap();
+        assert_eq!(summary.pairing_signature_keys[0].key, csrk);
+    }
+
+    #[test]
+    fn rejects_a_zero_time_base() {
+        assert!(matches!(
+            ConnectionAnalyzer::new(AnalyzerConfig::new(0)),
+            Err(Error::InvalidConfiguration(_))
+        ));
+    }
+}
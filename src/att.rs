@@ -1,10 +1,14 @@
+use crate::advertising::{AddressKind, DeviceAddress};
+use crate::crypto::aes_cmac;
 use crate::link_layer::L2capPdu;
 use crate::{Error, Result};
+use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
 
 pub const ATT_FIXED_CHANNEL_ID: u16 = 0x0004;
 pub const ATT_DEFAULT_LE_MTU: u16 = 23;
 pub const ATT_SIGNATURE_LENGTH: usize = 12;
+const ATT_SIGNED_WRITE_COMMAND: u8 = 0xd2;
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub struct AttPdu<'a> {
@@ -216,9 +220,9 @@ impl<'a> AttPdu<'a> {
             0x23 => Ok(DecodedAttPdu::MultipleHandleValueNotification(
                 decode_att_multiple_handle_values(self)?,
             )),
-            0xd2 => Ok(DecodedAttPdu::SignedWriteCommand(decode_att_signed_write(
-                self,
-            )?)),
+            ATT_SIGNED_WRITE_COMMAND => Ok(DecodedAttPdu::SignedWriteCommand(
+                decode_att_signed_write(self)?,
+            )),
             _ => Ok(DecodedAttPdu::Unknown {
                 opcode: self.opcode,
                 parameters: self.parameters,
@@ -445,6 +449,127 @@ pub struct AttSignedWrite<'a> {
     pub signature: [u8; ATT_SIGNATURE_LENGTH],
 }
 
+impl AttSignedWrite<'_> {
+    /// The SignCounter that opens the signature.
+    pub fn sign_counter(&self) -> u32 {
+        u32::from_le_bytes(
+            self.signature[..4]
+                .try_into()
+                .expect("signature opens with four counter octets"),
+        )
+    }
+
+    /// Whether the signature is the one `csrk` gives this command and its
+    /// SignCounter.
+    pub fn verify(&self, csrk: [u8; 16]) -> bool {
+        let message: Vec<u8> = [ATT_SIGNED_WRITE_COMMAND]
+            .into_iter()
+            .chain(self.handle.to_le_bytes())
+            .chain(self.value.iter().copied())
+            .collect();
+        att_signature(csrk, &message, self.sign_counter()) == self.signature
+    }
+}
+
+/// The ATT authentication signature of `message`, the signed PDU up to its
+/// signature: the SignCounter followed by the 64 most significant bits of
+/// the AES-CMAC over the message and counter, both least significant octet
+/// first. `csrk` is in SMP field order.
+pub fn att_signature(
+    csrk: [u8; 16],
+    message: &[u8],
+    sign_counter: u32,
+) -> [u8; ATT_SIGNATURE_LENGTH] {
+    let mut key = csrk;
+    key.reverse();
+    let operand: Vec<u8> = message
+        .iter()
+        .copied()
+        .chain(sign_counter.to_le_bytes())
+        .rev()
+        .collect();
+    let mac = aes_cmac(key, &operand);
+    let mut signature = [0u8; ATT_SIGNATURE_LENGTH];
+    signature[..4].copy_from_slice(&sign_counter.to_le_bytes());
+    for (octet, mac_octet) in signature[4..].iter_mut().zip(mac[..8].iter().rev()) {
+        *octet = *mac_octet;
+    }
+    signature
+}
+
+/// Outcome of checking a Signed Write Command.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AttSignatureStatus {
+    /// A candidate CSRK produced the signature and the SignCounter advanced.
+    Valid,
+    /// No candidate CSRK produced the signature.
+    Invalid,
+    /// The signature is genuine but its SignCounter did not exceed the last
+    /// one accepted from the device.
+    Replayed { last_sign_counter: u32 },
+    /// No CSRK is known for the signing device.
+    NoKey,
+}
+
+impl Display for AttSignatureStatus {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Valid => formatter.write_str("valid"),
+            Self::Invalid => formatter.write_str("invalid"),
+            Self::Replayed { .. } => formatter.write_str("replayed"),
+            Self::NoKey => formatter.write_str("no-key"),
+        }
+    }
+}
+
+/// Verifies Signed Write Commands and remembers the last SignCounter
+/// accepted from each signing device, so repeated counters are reported as
+/// replays.
+#[derive(Clone, Debug, Default)]
+pub struct AttSignatureVerifier {
+    last_sign_counters: HashMap<(DeviceAddress, AddressKind), u32>,
+}
+
+impl AttSignatureVerifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The last SignCounter accepted from `device`.
+    pub fn last_sign_counter(&self, device: (DeviceAddress, AddressKind)) -> Option<u32> {
+        self.last_sign_counters.get(&device).copied()
+    }
+
+    /// Checks `write` against each CSRK in `csrks` in turn. Counters are only
+    /// tracked for a known `device`, preferably its identity address.
+    pub fn verify(
+        &mut self,
+        device: Option<(DeviceAddress, AddressKind)>,
+        write: &AttSignedWrite<'_>,
+        csrks: &[[u8; 16]],
+    ) -> AttSignatureStatus {
+        if csrks.is_empty() {
+            return AttSignatureStatus::NoKey;
+        }
+        if !csrks.iter().any(|csrk| write.verify(*csrk)) {
+            return AttSignatureStatus::Invalid;
+        }
+        let Some(device) = device else {
+            return AttSignatureStatus::Valid;
+        };
+        let sign_counter = write.sign_counter();
+        match self.last_sign_counters.get(&device) {
+            Some(&last_sign_counter) if sign_counter <= last_sign_counter => {
+                AttSignatureStatus::Replayed { last_sign_counter }
+            }
+            _ => {
+                self.last_sign_counters.insert(device, sign_counter);
+                AttSignatureStatus::Valid
+            }
+        }
+    }
+}
+
 pub const fn att_error_name(error_code: u8) -> &'static str {
     match error_code {
         0x01 => "invalid-handle",
@@ -1096,6 +1221,65 @@ mod tests {
         assert_eq!(write.handle, 3);
         assert_eq!(write.value, [0x11, 0x22]);
         assert_eq!(write.signature, [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11]);
+        assert_eq!(write.sign_counter(), 0x0302_0100);
+    }
+
+    #[test]
+    fn att_signature_matches_the_rfc_4493_cmac_example() {
+        // RFC 4493 example 2 in SMP field order: the key and the 16-octet
+        // message reversed, the message's last four octets being the counter.
+        let csrk = [
+            0x3c, 0x4f, 0xcf, 0x09, 0x88, 0x15, 0xf7, 0xab, 0xa6, 0xd2, 0xae, 0x28, 0x16, 0x15,
+            0x7e, 0x2b,
+        ];
+        let message = [
+            0x2a, 0x17, 0x93, 0x73, 0x11, 0x7e, 0x3d, 0xe9, 0x96, 0x9f, 0x40, 0x2e,
+        ];
+        assert_eq!(
+            att_signature(csrk, &message, 0x6bc1_bee2),
+            [
+                0xe2, 0xbe, 0xc1, 0x6b, 0x44, 0x41, 0x4d, 0x6b, 0xb4, 0x16, 0x0a, 0x07,
+            ]
+        );
+    }
+
+    #[test]
+    fn verifies_signed_writes_and_reports_replayed_counters() {
+        let csrk = [0x42; 16];
+        let device = (DeviceAddress([1, 2, 3, 4, 5, 6]), AddressKind::Public);
+        let signed = |sign_counter: u32| {
+            let mut pdu = vec![0x03, 0x00, 0x11, 0x22];
+            pdu.extend(att_signature(
+                csrk,
+                &[0xd2, 0x03, 0x00, 0x11, 0x22],
+                sign_counter,
+            ));
+            pdu
+        };
+        let mut verifier = AttSignatureVerifier::new();
+        let mut check = |pdu: &[u8], csrks: &[[u8; 16]]| {
+            let DecodedAttPdu::SignedWriteCommand(write) = decode(0xd2, pdu).unwrap() else {
+                panic!("unexpected PDU");
+            };
+            verifier.verify(Some(device), &write, csrks)
+        };
+
+        assert_eq!(check(&signed(7), &[]), AttSignatureStatus::NoKey);
+        assert_eq!(
+            check(&signed(7), &[[0; 16], csrk]),
+            AttSignatureStatus::Valid
+        );
+        assert_eq!(
+            check(&signed(7), &[csrk]),
+            AttSignatureStatus::Replayed {
+                last_sign_counter: 7
+            }
+        );
+        assert_eq!(check(&signed(8), &[csrk]), AttSignatureStatus::Valid);
+        let mut tampered = signed(9);
+        tampered[2] ^= 1;
+        assert_eq!(check(&tampered, &[csrk]), AttSignatureStatus::Invalid);
+        assert_eq!(verifier.last_sign_counter(device), Some(8));
     }
 
     #[test]
@@ -1,8 +1,8 @@
 use crate::complex::Complex32;
-use crate::native::DynamicLibrary;
-use crate::sdr::{IqSource, ReadMetadata, SdrCapabilities, SdrConfig, SdrKind};
+use crate::native::{DynamicLibrary, c_char_array_to_string};
+use crate::sdr::{IqSource, ReadMetadata, SdrCapabilities, SdrConfig, SdrDevice, SdrKind};
 use crate::{Error, Result};
-use std::ffi::{CStr, CString, c_char, c_int, c_void};
+use std::ffi::{CStr, CString, c_char, c_int, c_uint, c_void};
 use std::ptr::NonNull;
 use std::sync::Arc;
 use std::time::Duration;
@@ -14,6 +14,9 @@ const BLADERF_FORMAT_SC16_Q11_META: c_int = 2;
 const BLADERF_META_STATUS_OVERRUN: u32 = 1;
 const BLADERF_META_FLAG_RX_NOW: u32 = 1 << 31;
 const BLADERF_ERR_TIMEOUT: c_int = -6;
+const BLADERF_ERR_NODEV: c_int = -7;
+const BLADERF_SERIAL_LENGTH: usize = 33;
+const BLADERF_DESCRIPTION_LENGTH: usize = 33;
 const Q11_SCALE: f32 = 1.0 / 2048.0;
 
 #[derive(Clone, Copy, Debug)]
@@ -82,6 +85,37 @@ struct BladeRfMetadata {
     reserved: [u8; 32],
 }
 
+#[repr(C)]
+struct BladeRfDeviceInfo {
+    backend: c_int,
+    serial: [c_char; BLADERF_SERIAL_LENGTH],
+    usb_bus: u8,
+    usb_address: u8,
+    instance: c_uint,
+    manufacturer: [c_char; BLADERF_DESCRIPTION_LENGTH],
+    product: [c_char; BLADERF_DESCRIPTION_LENGTH],
+}
+
+impl BladeRfDeviceInfo {
+    fn device(&self) -> SdrDevice {
+        let serial = c_char_array_to_string(&self.serial);
+        let product = c_char_array_to_string(&self.product);
+        SdrDevice {
+            kind: SdrKind::BladeRf,
+            identifier: format!("*:serial={serial}"),
+            description: format!(
+                "{} serial {serial}",
+                if product.is_empty() {
+                    "bladeRF"
+                } else {
+                    &product
+                }
+            ),
+            serial,
+        }
+    }
+}
+
 trait BladeRfApi {
     fn library_name(&self) -> &str;
     fn open(&self, identifier: Option<&CStr>) -> (c_int, *mut c_void);
@@ -126,6 +160,8 @@ type EnableModuleFn = unsafe extern "C" fn(*mut c_void, c_int, bool) -> c_int;
 type SyncRxFn =
     unsafe extern "C" fn(*mut c_void, *mut c_void, u32, *mut BladeRfMetadata, u32) -> c_int;
 type StrErrorFn = unsafe extern "C" fn(c_int) -> *const c_char;
+type GetDeviceListFn = unsafe extern "C" fn(*mut *mut BladeRfDeviceInfo) -> c_int;
+type FreeDeviceListFn = unsafe extern "C" fn(*mut BladeRfDeviceInfo);
 
 struct DynamicBladeRfApi {
     _library: DynamicLibrary,
@@ -140,6 +176,8 @@ struct DynamicBladeRfApi {
     enable_module: EnableModuleFn,
     sync_rx: SyncRxFn,
     strerror: StrErrorFn,
+    get_device_list: GetDeviceListFn,
+    free_device_list: FreeDeviceListFn,
 }
 
 impl DynamicBladeRfApi {
@@ -174,6 +212,8 @@ impl DynamicBladeRfApi {
                 enable_module: library.symbol("bladerf_enable_module")?,
                 sync_rx: library.symbol("bladerf_sync_rx")?,
                 strerror: library.symbol("bladerf_strerror")?,
+                get_device_list: library.symbol("bladerf_get_device_list")?,
+                free_device_list: library.symbol("bladerf_free_device_list")?,
                 _library: library,
                 library_name,
             })
@@ -181,6 +221,31 @@ impl DynamicBladeRfApi {
     }
 }
 
+impl DynamicBladeRfApi {
+    fn devices(&self) -> Result<Vec<SdrDevice>> {
+        let mut list = std::ptr::null_mut();
+        // SAFETY: Function pointer was loaded with the exact ABI and the
+        // output pointer is valid.
+        let count = unsafe { (self.get_device_list)(&mut list) };
+        if count == BLADERF_ERR_NODEV {
+            return Ok(Vec::new());
+        }
+        if count < 0 {
+            return Err(native_error(self, "bladerf_get_device_list", count));
+        }
+        if list.is_null() {
+            return Ok(Vec::new());
+        }
+        // SAFETY: libbladeRF allocated `count` initialized entries at `list`,
+        // which stay valid until bladerf_free_device_list.
+        let entries = unsafe { std::slice::from_raw_parts(list, count as usize) };
+        let devices = entries.iter().map(BladeRfDeviceInfo::device).collect();
+        // SAFETY: `list` came from bladerf_get_device_list and is freed once.
+        unsafe { (self.free_device_list)(list) };
+        Ok(devices)
+    }
+}
+
 impl BladeRfApi for DynamicBladeRfApi {
     fn library_name(&self) -> &str {
         &self.library_name
@@ -588,6 +653,11 @@ impl BladeRfSource {
         Ok(api.library_name().to_owned())
     }
 
+    /// Lists attached devices; each `identifier` selects one in [`Self::open`].
+    pub fn list_devices() -> Result<Vec<SdrDevice>> {
+        DynamicBladeRfApi::load()?.devices()
+    }
+
     pub fn applied_config(&self) -> Option<AppliedBladeRfConfig> {
         self.driver.applied_config()
     }
@@ -1068,4 +1138,34 @@ mod tests {
         assert_eq!(std::mem::size_of::<BladeRfMetadata>(), 56);
         assert_eq!(std::mem::align_of::<BladeRfMetadata>(), 8);
     }
+
+    #[test]
+    fn device_info_layout_and_identifier_match_vendor_abi() {
+        assert_eq!(std::mem::size_of::<BladeRfDeviceInfo>(), 112);
+        assert_eq!(std::mem::align_of::<BladeRfDeviceInfo>(), 4);
+        let mut info = BladeRfDeviceInfo {
+            backend: 0,
+            serial: [0; BLADERF_SERIAL_LENGTH],
+            usb_bus: 2,
+            usb_address: 5,
+            instance: 0,
+            manufacturer: [0; BLADERF_DESCRIPTION_LENGTH],
+            product: [0; BLADERF_DESCRIPTION_LENGTH],
+        };
+        for (field, value) in info.serial.iter_mut().zip(b"a1b2c3") {
+            *field = *value as c_char;
+        }
+        for (field, value) in info.product.iter_mut().zip(b"bladeRF 2.0") {
+            *field = *value as c_char;
+        }
+        assert_eq!(
+            info.device(),
+            SdrDevice {
+                kind: SdrKind::BladeRf,
+                identifier: "*:serial=a1b2c3".to_owned(),
+                serial: "a1b2c3".to_owned(),
+                description: "bladeRF 2.0 serial a1b2c3".to_owned(),
+            }
+        );
+    }
 }
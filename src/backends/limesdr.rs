@@ -1,6 +1,6 @@
 use crate::complex::Complex32;
-use crate::native::DynamicLibrary;
-use crate::sdr::{IqSource, ReadMetadata, SdrCapabilities, SdrConfig, SdrKind};
+use crate::native::{DynamicLibrary, c_char_array_to_string};
+use crate::sdr::{IqSource, ReadMetadata, SdrCapabilities, SdrConfig, SdrDevice, SdrKind};
 use crate::{Error, Result};
 use std::ffi::{CStr, CString, c_char, c_int, c_uint, c_void};
 use std::ptr::NonNull;
@@ -13,6 +13,9 @@ const LMS_FMT_F32: c_int = 0;
 const LMS_LINK_FMT_DEFAULT: c_int = 0;
 const MAX_GAIN_DB: f32 = 73.0;
 const MIN_CALIBRATION_BANDWIDTH_HZ: f64 = 2_500_000.0;
+/// `lms_info_str_t`
+const LMS_INFO_STRING_LENGTH: usize = 256;
+const LMS_DEVICE_LIST_HEADROOM: usize = 16;
 
 #[derive(Clone, Copy, Debug)]
 pub struct LimeSdrOptions {
@@ -168,6 +171,7 @@ type RecvStreamFn = unsafe extern "C" fn(
 ) -> c_int;
 type GetStreamStatusFn = unsafe extern "C" fn(*mut LmsStream, *mut LmsStreamStatus) -> c_int;
 type GetLastErrorFn = unsafe extern "C" fn() -> *const c_char;
+type GetDeviceListFn = unsafe extern "C" fn(*mut [c_char; LMS_INFO_STRING_LENGTH]) -> c_int;
 
 struct DynamicLimeApi {
     _library: DynamicLibrary,
@@ -194,6 +198,7 @@ struct DynamicLimeApi {
     recv_stream: RecvStreamFn,
     get_stream_status: GetStreamStatusFn,
     get_last_error: GetLastErrorFn,
+    get_device_list: GetDeviceListFn,
 }
 
 impl DynamicLimeApi {
@@ -240,6 +245,7 @@ impl DynamicLimeApi {
                 recv_stream: library.symbol("LMS_RecvStream")?,
                 get_stream_status: library.symbol("LMS_GetStreamStatus")?,
                 get_last_error: library.symbol("LMS_GetLastErrorMessage")?,
+                get_device_list: library.symbol("LMS_GetDeviceList")?,
                 _library: library,
                 library_name,
             })
@@ -247,6 +253,53 @@ impl DynamicLimeApi {
     }
 }
 
+impl DynamicLimeApi {
+    fn devices(&self) -> Result<Vec<SdrDevice>> {
+        // SAFETY: LMS_GetDeviceList accepts NULL and then only counts devices.
+        let count = unsafe { (self.get_device_list)(std::ptr::null_mut()) };
+        if count < 0 {
+            return Err(native_error(self, "LMS_GetDeviceList", count));
+        }
+        if count == 0 {
+            return Ok(Vec::new());
+        }
+        // LMS_GetDeviceList takes no buffer length and writes one entry per
+        // device it finds, so leave room for devices attached between calls.
+        let mut list = vec![[0; LMS_INFO_STRING_LENGTH]; count as usize + LMS_DEVICE_LIST_HEADROOM];
+        // SAFETY: `list` holds writable lms_info_str_t entries for the counted
+        // devices plus headroom.
+        let filled = unsafe { (self.get_device_list)(list.as_mut_ptr()) };
+        if filled < 0 {
+            return Err(native_error(self, "LMS_GetDeviceList", filled));
+        }
+        let filled = (filled as usize).min(list.len());
+        list.truncate(filled);
+        Ok(list
+            .iter()
+            .map(|info| lime_device(&c_char_array_to_string(info)))
+            .collect())
+    }
+}
+
+/// Reads a LimeSuite device string such as
+/// `LimeSDR Mini, media=USB 3.0, module=FTDI, addr=24607:1027, serial=1D3A`.
+fn lime_device(info: &str) -> SdrDevice {
+    let mut fields = info.split(", ");
+    let name = fields.next().unwrap_or_default();
+    let serial = fields
+        .find_map(|field| field.strip_prefix("serial="))
+        .unwrap_or(name);
+    SdrDevice {
+        kind: SdrKind::LimeSdr,
+        identifier: info.to_owned(),
+        serial: serial.to_owned(),
+        description: format!(
+            "{} serial {serial}",
+            if name.is_empty() { "LimeSDR" } else { name }
+        ),
+    }
+}
+
 impl LimeApi for DynamicLimeApi {
     fn library_name(&self) -> &str {
         &self.library_name
@@ -856,6 +909,11 @@ impl LimeSdrSource {
         Ok(api.library_name().to_owned())
     }
 
+    /// Lists attached devices; each `identifier` selects one in [`Self::open`].
+    pub fn list_devices() -> Result<Vec<SdrDevice>> {
+        DynamicLimeApi::load()?.devices()
+    }
+
     pub fn applied_config(&self) -> Option<AppliedLimeSdrConfig> {
         self.driver.applied_config()
     }
@@ -1709,4 +1767,17 @@ mod tests {
             ["stop_stream", "destroy_stream", "enable:0:false", "close"]
         );
     }
+
+    #[test]
+    fn reads_serial_and_model_from_device_strings() {
+        let device =
+            lime_device("LimeSDR Mini, media=USB 3.0, module=FTDI, addr=24607:1027, serial=1D3AC8");
+        assert_eq!(device.serial, "1D3AC8");
+        assert_eq!(device.description, "LimeSDR Mini serial 1D3AC8");
+        assert_eq!(
+            device.identifier,
+            "LimeSDR Mini, media=USB 3.0, module=FTDI, addr=24607:1027, serial=1D3AC8"
+        );
+        assert_eq!(lime_device("LimeSDR-USB").serial, "LimeSDR-USB");
+    }
 }
@@ -1,6 +1,6 @@
 use crate::complex::Complex32;
-use crate::native::DynamicLibrary;
-use crate::sdr::{IqSource, ReadMetadata, SdrCapabilities, SdrConfig, SdrKind};
+use crate::native::{DynamicLibrary, c_char_array_to_string};
+use crate::sdr::{IqSource, ReadMetadata, SdrCapabilities, SdrConfig, SdrDevice, SdrKind};
 use crate::{Error, Result};
 use std::ffi::{CStr, CString, c_char, c_int, c_uint, c_void};
 use std::ptr::NonNull;
@@ -25,6 +25,7 @@ const RCVEX_TIMOUT: u32 = 32;
 const RCVEX_EVENT_OVERFLOW: u32 = 1;
 const RCVEX_EVENT_FILLED_ZERO: u32 = 2;
 const Q11_SCALE: f32 = 1.0 / 2048.0;
+const MAX_DISCOVERED_DEVICES: usize = 32;
 const MIN_BANDWIDTH_HZ: u32 = 1_000_000;
 const MAX_BANDWIDTH_HZ: u32 = 60_000_000;
 const MIN_GAIN_DB: f32 = 0.0;
@@ -109,6 +110,45 @@ impl Default for XtrxRunParams {
     }
 }
 
+/// `xtrx_device_info_t`
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct XtrxDeviceInfo {
+    unique_name: [c_char; 64],
+    protocol: [c_char; 16],
+    address: [c_char; 16],
+    bus_speed: [c_char; 16],
+    serial: [c_char; 32],
+    device_id: [c_char; 64],
+}
+
+impl XtrxDeviceInfo {
+    const EMPTY: Self = Self {
+        unique_name: [0; 64],
+        protocol: [0; 16],
+        address: [0; 16],
+        bus_speed: [0; 16],
+        serial: [0; 32],
+        device_id: [0; 64],
+    };
+
+    fn device(&self) -> SdrDevice {
+        let unique_name = c_char_array_to_string(&self.unique_name);
+        let serial = c_char_array_to_string(&self.serial);
+        let serial = if serial.is_empty() {
+            unique_name.clone()
+        } else {
+            serial
+        };
+        SdrDevice {
+            kind: SdrKind::Xtrx,
+            description: format!("XTRX serial {serial} ({unique_name})"),
+            identifier: unique_name,
+            serial,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 #[repr(C)]
 struct XtrxRecvInfo {
@@ -204,6 +244,7 @@ type RunParamsInitFn = unsafe extern "C" fn(*mut XtrxRunParams);
 type RunFn = unsafe extern "C" fn(*mut c_void, *const XtrxRunParams) -> c_int;
 type RecvFn = unsafe extern "C" fn(*mut c_void, *mut XtrxRecvInfo) -> c_int;
 type StopFn = unsafe extern "C" fn(*mut c_void, c_int) -> c_int;
+type DiscoveryFn = unsafe extern "C" fn(*mut XtrxDeviceInfo, usize) -> c_int;
 
 struct DynamicXtrxApi {
     _library: DynamicLibrary,
@@ -219,6 +260,7 @@ struct DynamicXtrxApi {
     run: RunFn,
     recv: RecvFn,
     stop: StopFn,
+    discovery: DiscoveryFn,
 }
 
 impl DynamicXtrxApi {
@@ -254,6 +296,7 @@ impl DynamicXtrxApi {
                 run: library.symbol("xtrx_run_ex")?,
                 recv: library.symbol("xtrx_recv_sync_ex")?,
                 stop: library.symbol("xtrx_stop")?,
+                discovery: library.symbol("xtrx_discovery")?,
                 _library: library,
                 library_name,
             })
@@ -261,6 +304,22 @@ impl DynamicXtrxApi {
     }
 }
 
+impl DynamicXtrxApi {
+    fn devices(&self) -> Result<Vec<SdrDevice>> {
+        let mut list = [XtrxDeviceInfo::EMPTY; MAX_DISCOVERED_DEVICES];
+        // SAFETY: The function pointer has the reviewed ABI and libxtrx writes
+        // at most `maxbuf` entries.
+        let count = unsafe { (self.discovery)(list.as_mut_ptr(), list.len()) };
+        if count < 0 {
+            return Err(native_error(self, "xtrx_discovery", count));
+        }
+        Ok(list[..(count as usize).min(list.len())]
+            .iter()
+            .map(XtrxDeviceInfo::device)
+            .collect())
+    }
+}
+
 impl XtrxApi for DynamicXtrxApi {
     fn library_name(&self) -> &str {
         &self.library_name
@@ -760,6 +819,11 @@ impl XtrxSource {
         Ok(api.library_name().to_owned())
     }
 
+    /// Lists attached devices; each `identifier` selects one in [`Self::open`].
+    pub fn list_devices() -> Result<Vec<SdrDevice>> {
+        DynamicXtrxApi::load()?.devices()
+    }
+
     pub fn applied_config(&self) -> Option<AppliedXtrxConfig> {
         self.driver.applied_config()
     }
@@ -1460,6 +1524,7 @@ mod tests {
             assert_eq!(std::mem::size_of::<XtrxRecvInfo>(), 56);
             assert_eq!(std::mem::align_of::<XtrxRecvInfo>(), 8);
         }
+        assert_eq!(std::mem::size_of::<XtrxDeviceInfo>(), 208);
     }
 
     #[test]
@@ -1472,4 +1537,24 @@ mod tests {
         let calls = api.calls();
         assert_eq!(&calls[calls.len() - 2..], ["stop", "close"]);
     }
+
+    #[test]
+    fn discovered_devices_fall_back_to_unique_name_without_serial() {
+        let mut info = XtrxDeviceInfo::EMPTY;
+        for (field, value) in info.unique_name.iter_mut().zip(b"/dev/xtrx0") {
+            *field = *value as c_char;
+        }
+        let device = info.device();
+        assert_eq!(device.identifier, "/dev/xtrx0");
+        assert_eq!(device.serial, "/dev/xtrx0");
+        for (field, value) in info.serial.iter_mut().zip(b"12cc5241b88485c") {
+            *field = *value as c_char;
+        }
+        let device = info.device();
+        assert_eq!(device.serial, "12cc5241b88485c");
+        assert_eq!(
+            device.description,
+            "XTRX serial 12cc5241b88485c (/dev/xtrx0)"
+        );
+    }
 }
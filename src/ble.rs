@@ -37,6 +37,25 @@ impl BleChannel {
         };
         mhz * 1_000_000
     }
+
+    /// Physical RF channel `k` centered on 2402 + 2k MHz, as carried by
+    /// capture pseudo-headers.
+    pub const fn rf_channel(self) -> u8 {
+        ((self.center_frequency_hz() / 1_000_000 - 2402) / 2) as u8
+    }
+
+    pub fn from_rf_channel(rf_channel: u8) -> Result<Self> {
+        match rf_channel {
+            0 => Ok(Self(37)),
+            1..=11 => Ok(Self(rf_channel - 1)),
+            12 => Ok(Self(38)),
+            13..=38 => Ok(Self(rf_channel - 2)),
+            39 => Ok(Self(39)),
+            _ => Err(Error::InvalidInput(format!(
+                "RF channel {rf_channel} is outside 0..=39"
+            ))),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -158,6 +177,54 @@ impl LeFrameConfig {
     }
 }
 
+/// Whether frame decoders report access-address matches whose CRC failed.
+///
+/// CRC validation stays mandatory for [`decode_le_frames`] and every packet
+/// handed to semantic decoders. `Emit` is an RF-debugging aid: failed frames
+/// are returned separately and only when their Length octet is plausible.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum LeCrcFailurePolicy {
+    #[default]
+    Discard,
+    /// Report CRC-failed frames whose Length-counted payload is at most
+    /// `maximum_payload_length` octets and within the layout's own maximum.
+    Emit { maximum_payload_length: usize },
+}
+
+impl LeCrcFailurePolicy {
+    /// Emits CRC-failed frames up to the layout's maximum payload length.
+    pub const fn emit() -> Self {
+        Self::Emit {
+            maximum_payload_length: usize::MAX,
+        }
+    }
+
+    const fn maximum_payload_length(self, layout: LePduLayout) -> Option<usize> {
+        match self {
+            Self::Discard => None,
+            Self::Emit {
+                maximum_payload_length,
+            } => {
+                let layout_maximum = layout.maximum_payload_length();
+                Some(if maximum_payload_length < layout_maximum {
+                    maximum_payload_length
+                } else {
+                    layout_maximum
+                })
+            }
+        }
+    }
+}
+
+/// CRC-valid packets and, when requested, CRC-failed frames from one scan.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct LeFrameScan {
+    pub packets: Vec<LePdu>,
+    /// Frames whose received CRC does not match; `crc` holds the received
+    /// octets. These must not be passed to semantic decoders.
+    pub crc_failures: Vec<LePdu>,
+}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct LePdu {
     pub channel: BleChannel,
@@ -174,6 +241,50 @@ pub struct LePdu {
 }
 
 impl LePdu {
+    /// Builds a packet from a link-layer PDU without CRC: header, optional
+    /// data-channel CTEInfo, and payload. The CRC is calculated from the
+    /// frame's CRC initialization value.
+    pub fn from_pdu_bytes(
+        channel: BleChannel,
+        frame_config: LeFrameConfig,
+        pdu: &[u8],
+    ) -> Result<Self> {
+        frame_config.validate()?;
+        if pdu.len() < 2 {
+            return Err(Error::InvalidInput(
+                "LE PDU requires a two-octet header".to_owned(),
+            ));
+        }
+        let header = [pdu[0], pdu[1]];
+        let layout = frame_config.layout;
+        let payload_start = 2 + layout.additional_header_length(header);
+        let payload_length = layout.payload_length(header);
+        if payload_length > layout.maximum_payload_length() {
+            return Err(Error::InvalidInput(format!(
+                "LE PDU Length {payload_length} exceeds the {}-octet maximum",
+                layout.maximum_payload_length()
+            )));
+        }
+        if pdu.len() != payload_start + payload_length {
+            return Err(Error::InvalidInput(format!(
+                "LE PDU has {} octets, but its header requires {}",
+                pdu.len(),
+                payload_start + payload_length
+            )));
+        }
+        Ok(Self {
+            channel,
+            access_address: frame_config.access_address,
+            bit_offset: 0,
+            inverted: false,
+            access_address_errors: 0,
+            header,
+            cte_info: (payload_start == 3).then(|| pdu[2]),
+            payload: pdu[payload_start..].to_vec(),
+            crc: crc24_bytes(pdu, frame_config.crc_init),
+        })
+    }
+
     pub fn link_layer_bytes(&self) -> Vec<u8> {
         let mut bytes = Vec::with_capacity(
             4 + 2 + usize::from(self.cte_info.is_some()) + self.payload.len() + 3,
@@ -361,6 +472,28 @@ pub fn decode_le_frames(
     frame_config: LeFrameConfig,
     max_access_address_errors: u8,
 ) -> Result<Vec<LePdu>> {
+    Ok(scan_le_frames(
+        bits,
+        channel,
+        frame_config,
+        max_access_address_errors,
+        LeCrcFailurePolicy::Discard,
+    )?
+    .packets)
+}
+
+/// [`decode_le_frames`] with opt-in reporting of CRC-failed frames.
+///
+/// A failed frame is reported only when its Length octet is within the
+/// policy's bound and the whole frame fits in `bits`. A failed frame at the
+/// same bit offset and polarity as a CRC-valid packet is not reported.
+pub fn scan_le_frames(
+    bits: &[bool],
+    channel: BleChannel,
+    frame_config: LeFrameConfig,
+    max_access_address_errors: u8,
+    crc_failure_policy: LeCrcFailurePolicy,
+) -> Result<LeFrameScan> {
     frame_config.validate()?;
     if max_access_address_errors > 8 {
         return Err(Error::InvalidConfiguration(
@@ -371,10 +504,11 @@ pub fn decode_le_frames(
     let access_bits = bytes_to_bits_lsb(&frame_config.access_address.to_le_bytes());
     let minimum_body_bits = (2 + 3) * 8;
     if bits.len() < access_bits.len() + minimum_body_bits {
-        return Ok(Vec::new());
+        return Ok(LeFrameScan::default());
     }
 
-    let mut packets = Vec::new();
+    let failure_payload_limit = crc_failure_policy.maximum_payload_length(frame_config.layout);
+    let mut scan = LeFrameScan::default();
     for inverted in [false, true] {
         for offset in 0..=bits.len() - access_bits.len() - minimum_body_bits {
             let errors = access_bits
@@ -413,16 +547,18 @@ pub fn decode_le_frames(
             let payload_start = 2 + additional_header_length;
             let pdu_length = payload_start + payload_length;
             let total_length = pdu_length + 3;
-            if body.len() < total_length {
+            // A partial trailing octet is zero-padded by bits_to_bytes_lsb.
+            if body_bits.len() < total_length * 8 {
                 continue;
             }
 
             let received_crc = [body[pdu_length], body[pdu_length + 1], body[pdu_length + 2]];
-            if crc24_bytes(&body[..pdu_length], frame_config.crc_init) != received_crc {
+            let crc_valid = crc24_bytes(&body[..pdu_length], frame_config.crc_init) == received_crc;
+            if !crc_valid && failure_payload_limit.is_none_or(|limit| payload_length > limit) {
                 continue;
             }
 
-            packets.push(LePdu {
+            let packet = LePdu {
                 channel,
                 access_address: frame_config.access_address,
                 bit_offset: offset,
@@ -432,10 +568,21 @@ pub fn decode_le_frames(
                 cte_info: (additional_header_length != 0).then(|| body[2]),
                 payload: body[payload_start..pdu_length].to_vec(),
                 crc: received_crc,
-            });
+            };
+            if crc_valid {
+                scan.packets.push(packet);
+            } else {
+                scan.crc_failures.push(packet);
+            }
         }
     }
-    Ok(packets)
+    let packets = &scan.packets;
+    scan.crc_failures.retain(|failure| {
+        !packets.iter().any(|packet| {
+            packet.bit_offset == failure.bit_offset && packet.inverted == failure.inverted
+        })
+    });
+    Ok(scan)
 }
 
 /// Finds CRC-valid primary advertising packets in a hard-decision bit stream.
@@ -513,6 +660,24 @@ mod tests {
         );
     }
 
+    #[test]
+    fn rf_channel_numbers_round_trip_logical_channels() {
+        for (logical, rf) in [
+            (37, 0),
+            (0, 1),
+            (10, 11),
+            (38, 12),
+            (11, 13),
+            (36, 38),
+            (39, 39),
+        ] {
+            let channel = BleChannel::new(logical).unwrap();
+            assert_eq!(channel.rf_channel(), rf);
+            assert_eq!(BleChannel::from_rf_channel(rf).unwrap(), channel);
+        }
+        assert!(BleChannel::from_rf_channel(40).is_err());
+    }
+
     #[test]
     fn whitening_is_self_inverse() {
         let channel = BleChannel::new(38).unwrap();
@@ -787,6 +952,59 @@ mod tests {
         );
     }
 
+    #[test]
+    fn scan_reports_bounded_crc_failures_only_on_request() {
+        let channel = BleChannel::new(7).unwrap();
+        let config = LeFrameConfig::data(0x1020_3040, 0x0055_aa33).unwrap();
+        let frame = |payload: &[u8], corrupt: bool| {
+            let mut pdu = vec![0x02, payload.len() as u8];
+            pdu.extend_from_slice(payload);
+            pdu.extend_from_slice(&crc24_bytes(&pdu, config.crc_init));
+            if corrupt {
+                pdu[2] ^= 0x10;
+            }
+            let mut body = bytes_to_bits_lsb(&pdu);
+            whiten_bits(&mut body, channel);
+            let mut bits = bytes_to_bits_lsb(&config.access_address.to_le_bytes());
+            bits.extend(body);
+            bits
+        };
+        let mut bits = frame(&[0x11, 0x22, 0x33, 0x44], true);
+        bits.extend(frame(&[0x55; 12], true));
+        bits.extend(frame(&[0x66], false));
+
+        let discarded =
+            scan_le_frames(&bits, channel, config, 0, LeCrcFailurePolicy::Discard).unwrap();
+        assert_eq!(discarded.packets.len(), 1);
+        assert!(discarded.crc_failures.is_empty());
+        assert_eq!(
+            decode_le_frames(&bits, channel, config, 0).unwrap(),
+            discarded.packets
+        );
+
+        let emitted =
+            scan_le_frames(&bits, channel, config, 0, LeCrcFailurePolicy::emit()).unwrap();
+        assert_eq!(emitted.packets, discarded.packets);
+        assert_eq!(emitted.crc_failures.len(), 2);
+        assert_eq!(emitted.crc_failures[0].payload, [0x01, 0x22, 0x33, 0x44]);
+        assert_eq!(emitted.crc_failures[0].bit_offset, 0);
+        assert_eq!(emitted.crc_failures[1].payload[..2], [0x45, 0x55]);
+        assert_eq!(emitted.crc_failures[1].payload.len(), 12);
+
+        let bounded = scan_le_frames(
+            &bits,
+            channel,
+            config,
+            0,
+            LeCrcFailurePolicy::Emit {
+                maximum_payload_length: 8,
+            },
+        )
+        .unwrap();
+        assert_eq!(bounded.crc_failures.len(), 1);
+        assert_eq!(bounded.crc_failures[0].header, [0x02, 4]);
+    }
+
     #[test]
     fn data_decoder_rejects_missing_cte_info_octet() {
         let channel = BleChannel::new(16).unwrap();
@@ -805,8 +1023,68 @@ mod tests {
         );
     }
 
+    #[test]
+    fn decoder_rejects_frame_cut_inside_its_last_crc_octet() {
+        let channel = BleChannel::new(9).unwrap();
+        let config = LeFrameConfig::data(0x2468_ace0, 0x0013_5797).unwrap();
+        // A payload whose last CRC octet ends in a zero bit, so zero padding
+        // would reproduce the missing bit.
+        let pdu = (0u8..=255)
+            .map(|value| {
+                let mut pdu = vec![0x02, 2, 0x5a, value];
+                pdu.extend_from_slice(&crc24_bytes(&pdu, config.crc_init));
+                pdu
+            })
+            .find(|pdu| pdu[pdu.len() - 1] & 0x80 == 0)
+            .unwrap();
+        let mut body = bytes_to_bits_lsb(&pdu);
+        whiten_bits(&mut body, channel);
+        let mut bits = bytes_to_bits_lsb(&config.access_address.to_le_bytes());
+        bits.extend(body);
+        assert_eq!(
+            decode_le_frames(&bits, channel, config, 0).unwrap().len(),
+            1
+        );
+
+        bits.pop();
+        assert!(
+            decode_le_frames(&bits, channel, config, 0)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
     #[test]
     fn frame_config_rejects_wide_crc_init() {
         assert!(LeFrameConfig::data(1, 0x0100_0000).is_err());
     }
+
+    #[test]
+    fn builds_packets_from_pdu_octets_with_calculated_crc() {
+        let channel = BleChannel::new(12).unwrap();
+        let config = LeFrameConfig::data(0x1234_5678, 0x00ab_cdef).unwrap();
+        // Same Scapy-checked CTE-bearing body as the demodulator fixtures.
+        let pdu = LePdu::from_pdu_bytes(
+            channel,
+            config,
+            &[
+                0x3e, 0x09, 0x85, 0x05, 0x00, 0x04, 0x00, 0x0a, 0x01, 0x00, 0x02, 0x00,
+            ],
+        )
+        .unwrap();
+        assert_eq!(pdu.cte_info, Some(0x85));
+        assert_eq!(pdu.payload.len(), 9);
+        assert_eq!(pdu.crc, [0x42, 0x18, 0x93]);
+
+        assert!(LePdu::from_pdu_bytes(channel, config, &[0x02]).is_err());
+        assert!(LePdu::from_pdu_bytes(channel, config, &[0x02, 0x02, 0x00]).is_err());
+        assert!(
+            LePdu::from_pdu_bytes(
+                BleChannel::new(37).unwrap(),
+                LeFrameConfig::advertising(),
+                &[0x00, 0x26]
+            )
+            .is_err()
+        );
+    }
 }
@@ -0,0 +1,649 @@
+//! btsnoop HCI logs synthesized from reconstructed air traffic.
+//!
+//! [`BtSnoopWriter`] presents one side of each connection as if the log had
+//! been taken on that device's host: reassembled [`L2capPdu`]s become HCI ACL
+//! data packets on a per-connection handle, and a captured CONNECT_IND or
+//! LL_TERMINATE_IND becomes the matching HCI event. Records use the HCI UART
+//! (H4) datalink, so every packet starts with its H4 packet indicator.
+//!
+//! [`BtSnoopReader`] reads logs taken on a real host, such as Android's
+//! `btsnoop_hci.log` or `btmon -w` output, for the key importers in
+//! [`crate::keyring`].
+
+use crate::advertising::{AddressKind, ConnectRequest, DeviceAddress};
+use crate::link_layer::{L2capPdu, LinkDirection};
+use crate::{Error, Result};
+use std::fmt::{Display, Formatter};
+use std::io::{Read, Write};
+
+const BTSNOOP_MAGIC: &[u8; 8] = b"btsnoop\0";
+const BTSNOOP_VERSION: u32 = 1;
+pub const BTSNOOP_DATALINK_HCI_UNENCAPSULATED: u32 = 1001;
+pub const BTSNOOP_DATALINK_HCI_UART: u32 = 1002;
+/// Bounds allocations for corrupt length fields; HCI packets are far smaller.
+const MAXIMUM_RECORD_LENGTH: usize = 262_144;
+/// Microseconds from midnight, January 1st, 0 AD to the Unix epoch.
+const BTSNOOP_UNIX_EPOCH_US: u64 = 0x00dc_ddb3_0f2f_8000;
+const RECORD_FLAG_RECEIVED: u32 = 0x0000_0001;
+const RECORD_FLAG_COMMAND_OR_EVENT: u32 = 0x0000_0002;
+
+const H4_COMMAND: u8 = 0x01;
+const H4_ACL_DATA: u8 = 0x02;
+const H4_SYNCHRONOUS_DATA: u8 = 0x03;
+const H4_EVENT: u8 = 0x04;
+const H4_ISO_DATA: u8 = 0x05;
+const HCI_DISCONNECTION_COMPLETE: u8 = 0x05;
+const HCI_LE_META: u8 = 0x3e;
+const HCI_LE_CONNECTION_COMPLETE: u8 = 0x01;
+/// Packet Boundary flag of a host-to-controller first fragment: LE links are
+/// never automatically flushable.
+const ACL_FIRST_NON_FLUSHABLE: u16 = 0b00;
+const ACL_CONTINUATION: u16 = 0b01;
+/// Packet Boundary flag LE controllers use for a first fragment sent to the
+/// host.
+const ACL_FIRST_FLUSHABLE: u16 = 0b10;
+
+pub const HCI_MAXIMUM_CONNECTION_HANDLE: u16 = 0x0eff;
+/// LE ACL data length of a controller that supports LE Data Length Extension.
+pub const LE_ACL_DATA_PACKET_LENGTH: usize = 251;
+
+/// The device whose host the synthesized log appears to come from.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HciHost {
+    Central,
+    Peripheral,
+}
+
+impl HciHost {
+    /// True when `direction` carries data this host transmitted.
+    pub const fn sends(self, direction: LinkDirection) -> bool {
+        matches!(
+            (self, direction),
+            (Self::Central, LinkDirection::CentralToPeripheral)
+                | (Self::Peripheral, LinkDirection::PeripheralToCentral)
+        )
+    }
+}
+
+impl Display for HciHost {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Central => formatter.write_str("central"),
+            Self::Peripheral => formatter.write_str("peripheral"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HciPacketType {
+    Command,
+    AclData,
+    SynchronousData,
+    Event,
+    IsoData,
+}
+
+/// One HCI packet read from a btsnoop log, without its H4 packet indicator.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BtSnoopRecord {
+    /// Unix time in nanoseconds, at the log's microsecond resolution.
+    pub timestamp_ns: u64,
+    /// True for controller-to-host packets.
+    pub received: bool,
+    pub packet_type: HciPacketType,
+    /// Packet octets as logged. A record shorter than its original length
+    /// keeps only the logged prefix.
+    pub packet: Vec<u8>,
+}
+
+pub struct BtSnoopReader<R: Read> {
+    reader: R,
+    datalink: u32,
+    skipped_records: u64,
+}
+
+impl<R: Read> BtSnoopReader<R> {
+    /// Reads the file header. The HCI UART (H4) and unencapsulated HCI
+    /// datalinks are supported.
+    pub fn new(mut reader: R) -> Result<Self> {
+        let mut header = [0u8; 16];
+        reader.read_exact(&mut header)?;
+        if &header[..8] != BTSNOOP_MAGIC {
+            return Err(Error::InvalidInput("file is not a btsnoop log".to_owned()));
+        }
+        let version = u32::from_be_bytes(header[8..12].try_into().expect("four octets"));
+        if version != BTSNOOP_VERSION {
+            return Err(Error::InvalidInput(format!(
+                "btsnoop version {version} is not supported"
+            )));
+        }
+        let datalink = u32::from_be_bytes(header[12..16].try_into().expect("four octets"));
+        if !matches!(
+            datalink,
+            BTSNOOP_DATALINK_HCI_UNENCAPSULATED | BTSNOOP_DATALINK_HCI_UART
+        ) {
+            return Err(Error::InvalidInput(format!(
+                "btsnoop datalink {datalink} is not HCI UART (1002) or unencapsulated HCI (1001)"
+            )));
+        }
+        Ok(Self {
+            reader,
+            datalink,
+            skipped_records: 0,
+        })
+    }
+
+    pub const fn datalink(&self) -> u32 {
+        self.datalink
+    }
+
+    /// Records skipped because they were empty or carried an unknown H4
+    /// packet indicator.
+    pub const fn skipped_records(&self) -> u64 {
+        self.skipped_records
+    }
+
+    pub fn next_record(&mut self) -> Result<Option<BtSnoopRecord>> {
+        loop {
+            let mut header = [0u8; 24];
+            if !read_exact_or_end(&mut self.reader, &mut header)? {
+                return Ok(None);
+            }
+            let included_length =
+                u32::from_be_bytes(header[4..8].try_into().expect("four octets")) as usize;
+            if included_length > MAXIMUM_RECORD_LENGTH {
+                return Err(Error::InvalidInput(format!(
+                    "btsnoop record of {included_length} octets exceeds {MAXIMUM_RECORD_LENGTH}"
+                )));
+            }
+            let flags = u32::from_be_bytes(header[8..12].try_into().expect("four octets"));
+            let timestamp_us = u64::from_be_bytes(header[16..24].try_into().expect("eight octets"));
+            let mut packet = vec![0u8; included_length];
+            if !read_exact_or_end(&mut self.reader, &mut packet)? && included_length != 0 {
+                return Err(Error::InvalidInput(
+                    "btsnoop log ends inside a record".to_owned(),
+                ));
+            }
+            let received = flags & RECORD_FLAG_RECEIVED != 0;
+            let packet_type = if self.datalink == BTSNOOP_DATALINK_HCI_UART {
+                let packet_type = match packet.first() {
+                    Some(&H4_COMMAND) => HciPacketType::Command,
+                    Some(&H4_ACL_DATA) => HciPacketType::AclData,
+                    Some(&H4_SYNCHRONOUS_DATA) => HciPacketType::SynchronousData,
+                    Some(&H4_EVENT) => HciPacketType::Event,
+                    Some(&H4_ISO_DATA) => HciPacketType::IsoData,
+                    _ => {
+                        self.skipped_records += 1;
+                        continue;
+                    }
+                };
+                packet.remove(0);
+                packet_type
+            } else {
+                match (flags & RECORD_FLAG_COMMAND_OR_EVENT != 0, received) {
+                    (true, false) => HciPacketType::Command,
+                    (true, true) => HciPacketType::Event,
+                    (false, _) => HciPacketType::AclData,
+                }
+            };
+            return Ok(Some(BtSnoopRecord {
+                timestamp_ns: timestamp_us
+                    .saturating_sub(BTSNOOP_UNIX_EPOCH_US)
+                    .saturating_mul(1_000),
+                received,
+                packet_type,
+                packet,
+            }));
+        }
+    }
+}
+
+/// Splits a reassembled L2CAP PDU into HCI ACL data packets, without the H4
+/// packet indicator, as `host` would exchange them with its controller.
+///
+/// The first packet carries the L2CAP basic header. Each packet holds at most
+/// `maximum_data_length` octets of data.
+pub fn hci_acl_packets(
+    handle: u16,
+    host: HciHost,
+    pdu: &L2capPdu,
+    maximum_data_length: usize,
+) -> Result<Vec<Vec<u8>>> {
+    validate_handle(handle)?;
+    if maximum_data_length == 0 || maximum_data_length > usize::from(u16::MAX) {
+        return Err(Error::InvalidConfiguration(format!(
+            "HCI ACL data length must be 1..=65535, received {maximum_data_length}"
+        )));
+    }
+    let length = u16::try_from(pdu.payload.len()).map_err(|_| {
+        Error::InvalidInput(format!(
+            "L2CAP payload of {} octets exceeds the basic header length field",
+            pdu.payload.len()
+        ))
+    })?;
+    let mut frame = Vec::with_capacity(pdu.payload.len() + 4);
+    frame.extend_from_slice(&length.to_le_bytes());
+    frame.extend_from_slice(&pdu.channel_id.to_le_bytes());
+    frame.extend_from_slice(&pdu.payload);
+
+    let first_flag = if host.sends(pdu.direction) {
+        ACL_FIRST_NON_FLUSHABLE
+    } else {
+        ACL_FIRST_FLUSHABLE
+    };
+    Ok(frame
+        .chunks(maximum_data_length)
+        .enumerate()
+        .map(|(index, data)| {
+            let boundary = if index == 0 {
+                first_flag
+            } else {
+                ACL_CONTINUATION
+            };
+            let mut packet = Vec::with_capacity(data.len() + 4);
+            packet.extend_from_slice(&(handle | boundary << 12).to_le_bytes());
+            packet.extend_from_slice(&(data.len() as u16).to_le_bytes());
+            packet.extend_from_slice(data);
+            packet
+        })
+        .collect())
+}
+
+pub struct BtSnoopWriter<W: Write> {
+    writer: W,
+    host: HciHost,
+    records_written: u64,
+}
+
+impl<W: Write> BtSnoopWriter<W> {
+    /// Writes the btsnoop file header for the HCI UART datalink.
+    pub fn new(mut writer: W, host: HciHost) -> Result<Self> {
+        writer.write_all(BTSNOOP_MAGIC)?;
+        writer.write_all(&BTSNOOP_VERSION.to_be_bytes())?;
+        writer.write_all(&BTSNOOP_DATALINK_HCI_UART.to_be_bytes())?;
+        Ok(Self {
+            writer,
+            host,
+            records_written: 0,
+        })
+    }
+
+    pub const fn host(&self) -> HciHost {
+        self.host
+    }
+
+    pub const fn records_written(&self) -> u64 {
+        self.records_written
+    }
+
+    /// Writes the LE Connection Complete event the host receives when the
+    /// connection described by a CONNECT_IND is established. The peer is the
+    /// advertiser for a central host and the initiator for a peripheral host.
+    pub fn write_le_connection_complete(
+        &mut self,
+        timestamp_ns: u64,
+        handle: u16,
+        initiator: (DeviceAddress, AddressKind),
+        advertiser: (DeviceAddress, AddressKind),
+        request: &ConnectRequest,
+    ) -> Result<()> {
+        validate_handle(handle)?;
+        let (role, (peer, peer_kind)) = match self.host {
+            HciHost::Central => (0x00, advertiser),
+            HciHost::Peripheral => (0x01, initiator),
+        };
+        let mut parameters = Vec::with_capacity(19);
+        parameters.push(HCI_LE_CONNECTION_COMPLETE);
+        parameters.push(0x00);
+        parameters.extend_from_slice(&handle.to_le_bytes());
+        parameters.push(role);
+        parameters.push(match peer_kind {
+            AddressKind::Public => 0x00,
+            AddressKind::Random => 0x01,
+        });
+        parameters.extend_from_slice(&peer.0);
+        parameters.extend_from_slice(&request.interval.to_le_bytes());
+        parameters.extend_from_slice(&request.latency.to_le_bytes());
+        parameters.extend_from_slice(&request.supervision_timeout.to_le_bytes());
+        // The CONNECT_IND SCA field and Central_Clock_Accuracy share one
+        // encoding.
+        parameters.push(request.sleep_clock_accuracy);
+        self.write_event(timestamp_ns, HCI_LE_META, &parameters)
+    }
+
+    /// Writes the Disconnection Complete event for an LL_TERMINATE_IND
+    /// carrying `reason`.
+    pub fn write_disconnection_complete(
+        &mut self,
+        timestamp_ns: u64,
+        handle: u16,
+        reason: u8,
+    ) -> Result<()> {
+        validate_handle(handle)?;
+        let mut parameters = Vec::with_capacity(4);
+        parameters.push(0x00);
+        parameters.extend_from_slice(&handle.to_le_bytes());
+        parameters.push(reason);
+        self.write_event(timestamp_ns, HCI_DISCONNECTION_COMPLETE, &parameters)
+    }
+
+    /// Writes `pdu` as ACL data packets of at most
+    /// [`LE_ACL_DATA_PACKET_LENGTH`] octets and returns how many were written.
+    /// Data the host transmitted is recorded as sent, the rest as received.
+    pub fn write_l2cap(&mut self, timestamp_ns: u64, handle: u16, pdu: &L2capPdu) -> Result<usize> {
+        let packets = hci_acl_packets(handle, self.host, pdu, LE_ACL_DATA_PACKET_LENGTH)?;
+        let flags = if self.host.sends(pdu.direction) {
+            0
+        } else {
+            RECORD_FLAG_RECEIVED
+        };
+        for packet in &packets {
+            self.write_record(timestamp_ns, flags, H4_ACL_DATA, packet)?;
+        }
+        Ok(packets.len())
+    }
+
+    /// Writes one HCI packet given without its H4 packet indicator.
+    pub fn write_packet(
+        &mut self,
+        timestamp_ns: u64,
+        received: bool,
+        packet_type: HciPacketType,
+        packet: &[u8],
+    ) -> Result<()> {
+        let (indicator, command_or_event) = match packet_type {
+            HciPacketType::Command => (H4_COMMAND, true),
+            HciPacketType::AclData => (H4_ACL_DATA, false),
+            HciPacketType::SynchronousData => (H4_SYNCHRONOUS_DATA, false),
+            HciPacketType::Event => (H4_EVENT, true),
+            HciPacketType::IsoData => (H4_ISO_DATA, false),
+        };
+        let mut flags = 0;
+        if received {
+            flags |= RECORD_FLAG_RECEIVED;
+        }
+        if command_or_event {
+            flags |= RECORD_FLAG_COMMAND_OR_EVENT;
+        }
+        self.write_record(timestamp_ns, flags, indicator, packet)
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write_event(&mut self, timestamp_ns: u64, code: u8, parameters: &[u8]) -> Result<()> {
+        let mut event = Vec::with_capacity(parameters.len() + 2);
+        event.push(code);
+        event.push(parameters.len() as u8);
+        event.extend_from_slice(parameters);
+        self.write_packet(timestamp_ns, true, HciPacketType::Event, &event)
+    }
+
+    fn write_record(
+        &mut self,
+        timestamp_ns: u64,
+        flags: u32,
+        indicator: u8,
+        packet: &[u8],
+    ) -> Result<()> {
+        let length = u32::try_from(packet.len() + 1)
+            .map_err(|_| Error::InvalidInput("btsnoop record exceeds 4 GiB".to_owned()))?;
+        let timestamp_us = BTSNOOP_UNIX_EPOCH_US + timestamp_ns / 1_000;
+        self.writer.write_all(&length.to_be_bytes())?;
+        self.writer.write_all(&length.to_be_bytes())?;
+        self.writer.write_all(&flags.to_be_bytes())?;
+        self.writer.write_all(&0u32.to_be_bytes())?;
+        self.writer.write_all(&timestamp_us.to_be_bytes())?;
+        self.writer.write_all(&[indicator])?;
+        self.writer.write_all(packet)?;
+        self.records_written += 1;
+        Ok(())
+    }
+}
+
+fn read_exact_or_end(reader: &mut impl Read, buffer: &mut [u8]) -> Result<bool> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) if filled == 0 => return Ok(false),
+            Ok(0) => {
+                return Err(Error::InvalidInput(
+                    "btsnoop log ends inside a record".to_owned(),
+                ));
+            }
+            Ok(read) => filled += read,
+            Err(error) if error.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(error) => return Err(error.into()),
+        }
+    }
+    Ok(true)
+}
+
+fn validate_handle(handle: u16) -> Result<()> {
+    if handle > HCI_MAXIMUM_CONNECTION_HANDLE {
+        return Err(Error::InvalidInput(format!(
+            "HCI connection handle 0x{handle:04x} exceeds 0x{HCI_MAXIMUM_CONNECTION_HANDLE:04x}"
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::link_layer::ChannelSelectionAlgorithm;
+
+    fn pdu(direction: LinkDirection, payload: Vec<u8>) -> L2capPdu {
+        L2capPdu {
+            direction,
+            channel_id: 0x0004,
+            payload,
+            fragment_count: 1,
+        }
+    }
+
+    #[test]
+    fn splits_l2cap_pdus_into_acl_packets_with_host_boundary_flags() {
+        let sent = hci_acl_packets(
+            0x0001,
+            HciHost::Central,
+            &pdu(LinkDirection::CentralToPeripheral, vec![0x0a, 0x03, 0x00]),
+            251,
+        )
+        .unwrap();
+        assert_eq!(
+            sent,
+            [vec![
+                0x01, 0x00, 0x07, 0x00, 0x03, 0x00, 0x04, 0x00, 0x0a, 0x03, 0x00
+            ]]
+        );
+
+        let received = hci_acl_packets(
+            0x0eff,
+            HciHost::Central,
+            &pdu(LinkDirection::PeripheralToCentral, vec![1, 2, 3, 4, 5]),
+            4,
+        )
+        .unwrap();
+        assert_eq!(
+            received,
+            [
+                vec![0xff, 0x2e, 0x04, 0x00, 0x05, 0x00, 0x04, 0x00],
+                vec![0xff, 0x1e, 0x04, 0x00, 1, 2, 3, 4],
+                vec![0xff, 0x1e, 0x01, 0x00, 5],
+            ]
+        );
+
+        assert!(
+            hci_acl_packets(
+                0x0f00,
+                HciHost::Peripheral,
+                &pdu(LinkDirection::PeripheralToCentral, Vec::new()),
+                251,
+            )
+            .is_err()
+        );
+        assert!(
+            hci_acl_packets(
+                0,
+                HciHost::Peripheral,
+                &pdu(LinkDirection::PeripheralToCentral, Vec::new()),
+                0,
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn writes_big_endian_h4_records_from_the_host_view() {
+        let mut writer = BtSnoopWriter::new(Vec::new(), HciHost::Peripheral).unwrap();
+        let request = ConnectRequest {
+            access_address: 0x5065_4b4f,
+            crc_init: 0x7a_4f21,
+            window_size: 1,
+            window_offset: 0,
+            interval: 0x0018,
+            latency: 0x0002,
+            supervision_timeout: 0x0048,
+            channel_map: [0xff, 0xff, 0xff, 0xff, 0x1f],
+            hop_increment: 5,
+            sleep_clock_accuracy: 5,
+            channel_selection_algorithm: ChannelSelectionAlgorithm::Csa2,
+        };
+        writer
+            .write_le_connection_complete(
+                1_000_000_000,
+                0x0002,
+                (
+                    DeviceAddress([0x11, 0x22, 0x33, 0x44, 0x55, 0xc6]),
+                    AddressKind::Random,
+                ),
+                (DeviceAddress([6, 5, 4, 3, 2, 1]), AddressKind::Public),
+                &request,
+            )
+            .unwrap();
+        assert_eq!(
+            writer
+                .write_l2cap(
+                    1_000_002_999,
+                    0x0002,
+                    &pdu(LinkDirection::CentralToPeripheral, vec![0x0a, 0x03, 0x00]),
+                )
+                .unwrap(),
+            1
+        );
+        writer
+            .write_disconnection_complete(1_000_010_000, 0x0002, 0x13)
+            .unwrap();
+        assert_eq!(writer.records_written(), 3);
+        let bytes = writer.into_inner();
+
+        assert_eq!(&bytes[..8], b"btsnoop\0");
+        assert_eq!(&bytes[8..16], &[0, 0, 0, 1, 0, 0, 0x03, 0xea]);
+        let mut offset = 16;
+        let mut records = Vec::new();
+        while offset < bytes.len() {
+            let length = u32::from_be_bytes(bytes[offset..offset + 4].try_into().unwrap()) as usize;
+            assert_eq!(bytes[offset..offset + 4], bytes[offset + 4..offset + 8]);
+            let flags = u32::from_be_bytes(bytes[offset + 8..offset + 12].try_into().unwrap());
+            let timestamp = u64::from_be_bytes(bytes[offset + 16..offset + 24].try_into().unwrap());
+            records.push((
+                flags,
+                timestamp - BTSNOOP_UNIX_EPOCH_US,
+                bytes[offset + 24..offset + 24 + length].to_vec(),
+            ));
+            offset += 24 + length;
+        }
+        assert_eq!(offset, bytes.len());
+        assert_eq!(
+            records,
+            [
+                (
+                    3,
+                    1_000_000,
+                    vec![
+                        0x04, 0x3e, 0x13, 0x01, 0x00, 0x02, 0x00, 0x01, 0x01, 0x11, 0x22, 0x33,
+                        0x44, 0x55, 0xc6, 0x18, 0x00, 0x02, 0x00, 0x48, 0x00, 0x05,
+                    ],
+                ),
+                (
+                    1,
+                    1_000_002,
+                    vec![
+                        0x02, 0x02, 0x20, 0x07, 0x00, 0x03, 0x00, 0x04, 0x00, 0x0a, 0x03, 0x00,
+                    ],
+                ),
+                (3, 1_000_010, vec![0x04, 0x05, 0x04, 0x00, 0x02, 0x00, 0x13]),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_h4_and_unencapsulated_records_and_skips_unknown_indicators() {
+        let mut writer = BtSnoopWriter::new(Vec::new(), HciHost::Central).unwrap();
+        writer
+            .write_packet(
+                1_700_000_000_123_456_000,
+                false,
+                HciPacketType::Command,
+                &[0x1a, 0x20, 0x00],
+            )
+            .unwrap();
+        writer
+            .write_packet(5_000, true, HciPacketType::Event, &[0x05, 0x00])
+            .unwrap();
+        let mut bytes = writer.into_inner();
+        bytes.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0]);
+        bytes.extend_from_slice(&BTSNOOP_UNIX_EPOCH_US.to_be_bytes());
+        bytes.push(0x7f);
+
+        let mut reader = BtSnoopReader::new(bytes.as_slice()).unwrap();
+        assert_eq!(reader.datalink(), BTSNOOP_DATALINK_HCI_UART);
+        assert_eq!(
+            reader.next_record().unwrap(),
+            Some(BtSnoopRecord {
+                timestamp_ns: 1_700_000_000_123_456_000,
+                received: false,
+                packet_type: HciPacketType::Command,
+                packet: vec![0x1a, 0x20, 0x00],
+            })
+        );
+        assert_eq!(
+            reader.next_record().unwrap(),
+            Some(BtSnoopRecord {
+                timestamp_ns: 5_000,
+                received: true,
+                packet_type: HciPacketType::Event,
+                packet: vec![0x05, 0x00],
+            })
+        );
+        assert_eq!(reader.next_record().unwrap(), None);
+        assert_eq!(reader.skipped_records(), 1);
+
+        let mut unencapsulated = b"btsnoop\0\0\0\0\x01\0\0\x03\xe9".to_vec();
+        unencapsulated.extend_from_slice(&[0, 0, 0, 2, 0, 0, 0, 2, 0, 0, 0, 3, 0, 0, 0, 0]);
+        unencapsulated.extend_from_slice(&BTSNOOP_UNIX_EPOCH_US.to_be_bytes());
+        unencapsulated.extend_from_slice(&[0x05, 0x00]);
+        let mut reader = BtSnoopReader::new(unencapsulated.as_slice()).unwrap();
+        assert_eq!(
+            reader
+                .next_record()
+                .unwrap()
+                .map(|record| record.packet_type),
+            Some(HciPacketType::Event)
+        );
+
+        let truncated = &bytes[..bytes.len() - 3];
+        let mut reader = BtSnoopReader::new(truncated).unwrap();
+        reader.next_record().unwrap();
+        reader.next_record().unwrap();
+        assert!(reader.next_record().is_err());
+        assert!(BtSnoopReader::new(&b"btsnoop\0\0\0\0\x01\0\0\0\xc9"[..]).is_err());
+    }
+}
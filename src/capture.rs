@@ -1,4 +1,4 @@
-use crate::ble::{BleChannel, LeFrameConfig, LePduLayout};
+use crate::ble::{BleChannel, LeCrcFailurePolicy, LeFrameConfig, LePduLayout};
 use crate::complex::Complex32;
 use crate::demod::{
     Le1mDemodConfig, Le1mStreamDecoder, LeUncodedDemodConfig, LeUncodedPacketStreamDecoder,
@@ -63,10 +63,20 @@ pub struct CapturedDataChannelPdu {
     pub relative_sample_index: u64,
 }
 
+/// A frame whose CRC failed, reported only by the `_with_crc_failures`
+/// capture functions. It is never passed to the packet callback.
+#[derive(Clone, Debug)]
+pub struct CapturedCrcFailure {
+    pub observation: ReceivedLePdu,
+    pub relative_sample_index: u64,
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct CaptureStats {
     pub samples_received: u64,
     pub packets_decoded: u64,
+    /// CRC-failed frames reported; not included in `packets_decoded`.
+    pub crc_failures: u64,
     pub dropped_samples: u64,
     pub overruns: u64,
     pub discontinuities: u64,
@@ -179,10 +189,17 @@ impl FixedChannelCentralObservationTracker {
     }
 }
 
-trait CaptureObservation {
+/// A received packet placed by the sample where its access address begins.
+pub trait CaptureObservation {
     fn access_address_sample(&self) -> u64;
 }
 
+impl<T: CaptureObservation + ?Sized> CaptureObservation for &T {
+    fn access_address_sample(&self) -> u64 {
+        (**self).access_address_sample()
+    }
+}
+
 impl CaptureObservation for ReceivedAdvertisingPdu {
     fn access_address_sample(&self) -> u64 {
         self.access_address_sample
@@ -195,8 +212,40 @@ impl CaptureObservation for ReceivedLePdu {
     }
 }
 
+/// One item of [`in_sample_order`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SampleOrdered<P, F> {
+    Packet(P),
+    CrcFailure(F),
+}
+
+/// Merges the packets and CRC failures of one decode batch, each already in
+/// sample order, by the sample where their access address begins. A packet
+/// comes before a CRC failure at the same sample.
+pub fn in_sample_order<P, F>(
+    packets: impl IntoIterator<Item = P>,
+    crc_failures: impl IntoIterator<Item = F>,
+) -> impl Iterator<Item = SampleOrdered<P, F>>
+where
+    P: CaptureObservation,
+    F: CaptureObservation,
+{
+    let mut packets = packets.into_iter().peekable();
+    let mut crc_failures = crc_failures.into_iter().peekable();
+    std::iter::from_fn(move || match (packets.peek(), crc_failures.peek()) {
+        (Some(packet), Some(failure))
+            if failure.access_address_sample() < packet.access_address_sample() =>
+        {
+            crc_failures.next().map(SampleOrdered::CrcFailure)
+        }
+        (Some(_), _) => packets.next().map(SampleOrdered::Packet),
+        (None, _) => crc_failures.next().map(SampleOrdered::CrcFailure),
+    })
+}
+
 struct CaptureDecodeBatch<T> {
     packets: Vec<T>,
+    crc_failures: Vec<ReceivedLePdu>,
     discontinuity: Option<SampleDiscontinuity>,
 }
 
@@ -221,6 +270,7 @@ impl CaptureStreamDecoder for Le1mStreamDecoder {
         let batch = self.push(first_sample_index, input)?;
         Ok(CaptureDecodeBatch {
             packets: batch.packets,
+            crc_failures: batch.crc_failures,
             discontinuity: batch.discontinuity,
         })
     }
@@ -237,6 +287,7 @@ impl CaptureStreamDecoder for LeUncodedPacketStreamDecoder {
         let batch = self.push(first_sample_index, input)?;
         Ok(CaptureDecodeBatch {
             packets: batch.packets,
+            crc_failures: batch.crc_failures,
             discontinuity: batch.discontinuity,
         })
     }
@@ -248,13 +299,44 @@ pub fn capture_primary_advertising<S, F>(
     ble_channel: BleChannel,
     demod_config: Le1mDemodConfig,
     limits: CaptureLimits,
+    on_packet: F,
+) -> Result<CaptureStats>
+where
+    S: IqSource,
+    F: FnMut(&CapturedAdvertisingPdu) -> Result<()>,
+{
+    capture_primary_advertising_with_crc_failures(
+        source,
+        radio_config,
+        ble_channel,
+        demod_config,
+        LeCrcFailurePolicy::Discard,
+        limits,
+        on_packet,
+        |_| Ok(()),
+    )
+}
+
+/// Like [`capture_primary_advertising`], and also reports CRC-failed frames
+/// to `on_crc_failure` under `crc_failure_policy`.
+#[allow(clippy::too_many_arguments)]
+pub fn capture_primary_advertising_with_crc_failures<S, F, G>(
+    source: &mut S,
+    radio_config: &SdrConfig,
+    ble_channel: BleChannel,
+    demod_config: Le1mDemodConfig,
+    crc_failure_policy: LeCrcFailurePolicy,
+    limits: CaptureLimits,
     mut on_packet: F,
+    on_crc_failure: G,
 ) -> Result<CaptureStats>
 where
     S: IqSource,
     F: FnMut(&CapturedAdvertisingPdu) -> Result<()>,
+    G: FnMut(&CapturedCrcFailure) -> Result<()>,
 {
-    let decoder = Le1mStreamDecoder::new(ble_channel, demod_config)?;
+    let decoder =
+        Le1mStreamDecoder::with_crc_failure_policy(ble_channel, demod_config, crc_failure_policy)?;
     capture_with_decoder(
         source,
         radio_config,
@@ -267,6 +349,7 @@ where
                 relative_sample_index,
             })
         },
+        on_crc_failure,
     )
 }
 
@@ -277,11 +360,43 @@ pub fn capture_data_channel<S, F>(
     frame_config: LeFrameConfig,
     demod_config: LeUncodedDemodConfig,
     limits: CaptureLimits,
+    on_packet: F,
+) -> Result<CaptureStats>
+where
+    S: IqSource,
+    F: FnMut(&CapturedDataChannelPdu) -> Result<()>,
+{
+    capture_data_channel_with_crc_failures(
+        source,
+        radio_config,
+        ble_channel,
+        frame_config,
+        demod_config,
+        LeCrcFailurePolicy::Discard,
+        limits,
+        on_packet,
+        |_| Ok(()),
+    )
+}
+
+/// Like [`capture_data_channel`], and also reports CRC-failed frames to
+/// `on_crc_failure` under `crc_failure_policy`.
+#[allow(clippy::too_many_arguments)]
+pub fn capture_data_channel_with_crc_failures<S, F, G>(
+    source: &mut S,
+    radio_config: &SdrConfig,
+    ble_channel: BleChannel,
+    frame_config: LeFrameConfig,
+    demod_config: LeUncodedDemodConfig,
+    crc_failure_policy: LeCrcFailurePolicy,
+    limits: CaptureLimits,
     mut on_packet: F,
+    on_crc_failure: G,
 ) -> Result<CaptureStats>
 where
     S: IqSource,
     F: FnMut(&CapturedDataChannelPdu) -> Result<()>,
+    G: FnMut(&CapturedCrcFailure) -> Result<()>,
 {
     if ble_channel.index() > 36 {
         return Err(Error::InvalidConfiguration(format!(
@@ -294,7 +409,12 @@ where
             "data capture requires LE data-channel frame configuration".to_owned(),
         ));
     }
-    let decoder = LeUncodedPacketStreamDecoder::new(ble_channel, frame_config, demod_config)?;
+    let decoder = LeUncodedPacketStreamDecoder::with_crc_failure_policy(
+        ble_channel,
+        frame_config,
+        demod_config,
+        crc_failure_policy,
+    )?;
     capture_with_decoder(
         source,
         radio_config,
@@ -307,21 +427,24 @@ where
                 relative_sample_index,
             })
         },
+        on_crc_failure,
     )
 }
 
-fn capture_with_decoder<S, D, F>(
+fn capture_with_decoder<S, D, F, G>(
     source: &mut S,
     radio_config: &SdrConfig,
     demodulator_sample_rate_hz: u32,
     decoder: D,
     limits: CaptureLimits,
     mut on_packet: F,
+    mut on_crc_failure: G,
 ) -> Result<CaptureStats>
 where
     S: IqSource,
     D: CaptureStreamDecoder,
     F: FnMut(D::Observation, u64) -> Result<()>,
+    G: FnMut(&CapturedCrcFailure) -> Result<()>,
 {
     limits.validate()?;
     if radio_config.sample_rate_hz != demodulator_sample_rate_hz {
@@ -340,7 +463,7 @@ where
     }
     source.start()?;
 
-    let capture_result = capture_loop(source, decoder, limits, &mut on_packet);
+    let capture_result = capture_loop(source, decoder, limits, &mut on_packet, &mut on_crc_failure);
     let stop_result = source.stop();
     match (capture_result, stop_result) {
         (Ok(stats), Ok(())) => Ok(stats),
@@ -349,16 +472,18 @@ where
     }
 }
 
-fn capture_loop<S, D, F>(
+fn capture_loop<S, D, F, G>(
     source: &mut S,
     mut decoder: D,
     limits: CaptureLimits,
     on_packet: &mut F,
+    on_crc_failure: &mut G,
 ) -> Result<CaptureStats>
 where
     S: IqSource,
     D: CaptureStreamDecoder,
     F: FnMut(D::Observation, u64) -> Result<()>,
+    G: FnMut(&CapturedCrcFailure) -> Result<()>,
 {
     let mut buffer = vec![Complex32::ZERO; limits.block_samples];
     let started = Instant::now();
@@ -386,6 +511,9 @@ where
             )));
         }
         if count == 0 {
+            if source.end_of_stream() {
+                break;
+            }
             continue;
         }
 
@@ -414,23 +542,45 @@ where
         if batch.discontinuity.is_some() {
             stats.discontinuities += 1;
         }
-        for observation in batch.packets {
-            let access_address_sample = observation.access_address_sample();
-            let relative_sample_index = access_address_sample
-                .checked_sub(first_hardware_sample)
-                .ok_or_else(|| {
-                    Error::InvalidInput(format!(
-                        "hardware sample counter moved before capture origin: {} < {}",
-                        access_address_sample, first_hardware_sample
-                    ))
-                })?;
-            on_packet(observation, relative_sample_index)?;
-            stats.packets_decoded += 1;
+        // Callbacks see packets and CRC failures in sample order.
+        for item in in_sample_order(batch.packets, batch.crc_failures) {
+            match item {
+                SampleOrdered::Packet(observation) => {
+                    let relative_sample_index = relative_sample_index(
+                        observation.access_address_sample(),
+                        first_hardware_sample,
+                    )?;
+                    on_packet(observation, relative_sample_index)?;
+                    stats.packets_decoded += 1;
+                }
+                SampleOrdered::CrcFailure(observation) => {
+                    let relative_sample_index = relative_sample_index(
+                        observation.access_address_sample,
+                        first_hardware_sample,
+                    )?;
+                    on_crc_failure(&CapturedCrcFailure {
+                        observation,
+                        relative_sample_index,
+                    })?;
+                    stats.crc_failures += 1;
+                }
+            }
         }
     }
     Ok(stats)
 }
 
+fn relative_sample_index(access_address_sample: u64, first_hardware_sample: u64) -> Result<u64> {
+    access_address_sample
+        .checked_sub(first_hardware_sample)
+        .ok_or_else(|| {
+            Error::InvalidInput(format!(
+                "hardware sample counter moved before capture origin: {} < {}",
+                access_address_sample, first_hardware_sample
+            ))
+        })
+}
+
 pub fn describe_discontinuity(discontinuity: SampleDiscontinuity) -> String {
     if discontinuity.observed_first_sample >= discontinuity.expected_first_sample {
         format!(
@@ -592,6 +742,36 @@ mod tests {
         }
     }
 
+    #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+    struct Sampled(u64);
+
+    impl CaptureObservation for Sampled {
+        fn access_address_sample(&self) -> u64 {
+            self.0
+        }
+    }
+
+    #[test]
+    fn merges_packets_and_crc_failures_by_sample() {
+        use SampleOrdered::{CrcFailure, Packet};
+        let packets = [Sampled(10), Sampled(20)];
+        let failures = [Sampled(5), Sampled(20), Sampled(30)];
+        assert_eq!(
+            in_sample_order(&packets, &failures).collect::<Vec<_>>(),
+            [
+                CrcFailure(&failures[0]),
+                Packet(&packets[0]),
+                Packet(&packets[1]),
+                CrcFailure(&failures[1]),
+                CrcFailure(&failures[2]),
+            ]
+        );
+        assert_eq!(
+            in_sample_order(packets, []).collect::<Vec<SampleOrdered<_, Sampled>>>(),
+            [Packet(Sampled(10)), Packet(Sampled(20))]
+        );
+    }
+
     #[test]
     fn fixed_channel_central_observations_anchor_and_recover_missed_events() {
         let mut tracker = FixedChannelCentralObservationTracker::new(
@@ -768,6 +948,77 @@ mod tests {
         assert!(packets[0].relative_sample_index < total_samples);
     }
 
+    #[test]
+    fn reports_crc_failures_separately_from_packets() {
+        let channel = BleChannel::new(12).unwrap();
+        let mut pdu = vec![0x02, 0x03, 0x11, 0x22, 0x33];
+        let mut crc = crc24_bytes(&pdu, 0x00ab_cdef);
+        crc[1] ^= 0x40;
+        pdu.extend_from_slice(&crc);
+        let mut body = bytes_to_bits_lsb(&pdu);
+        whiten_bits(&mut body, channel);
+        let mut bits = bytes_to_bits_lsb(&[0xaa]);
+        bits.extend(bytes_to_bits_lsb(&0x1234_5678u32.to_le_bytes()));
+        bits.extend(body);
+        bits.extend([false, true].repeat(16));
+        let samples = modulate_uncoded(&bits, 4, 250_000.0, 4_000_000.0);
+        let metadata = ReadMetadata {
+            first_sample_index: 5_000,
+            dropped_samples_before: 0,
+            overrun: false,
+        };
+        let mut source = MockSource {
+            blocks: VecDeque::from([(5_000, samples.clone(), metadata)]),
+            configured: false,
+            running: false,
+            stopped: false,
+            applied_sample_rate_hz: None,
+        };
+        let mut packets = 0usize;
+        let mut failures = Vec::new();
+        let stats = capture_data_channel_with_crc_failures(
+            &mut source,
+            &SdrConfig {
+                center_frequency_hz: 2_430_000_000,
+                sample_rate_hz: 4_000_000,
+                bandwidth_hz: 2_000_000,
+                gain_db: 20.0,
+                channel: 0,
+            },
+            channel,
+            LeFrameConfig::data(0x1234_5678, 0x00ab_cdef).unwrap(),
+            LeUncodedDemodConfig {
+                phy: crate::demod::LeUncodedPhy::Le1M,
+                sample_rate_hz: 4_000_000,
+                max_access_address_errors: 0,
+            },
+            LeCrcFailurePolicy::emit(),
+            CaptureLimits {
+                maximum_samples: Some(samples.len() as u64),
+                maximum_duration: None,
+                read_timeout: Duration::from_millis(100),
+                block_samples: samples.len(),
+            },
+            |_| {
+                packets += 1;
+                Ok(())
+            },
+            |failure| {
+                failures.push(failure.clone());
+                Ok(())
+            },
+        )
+        .unwrap();
+
+        assert!(source.stopped);
+        assert_eq!(packets, 0);
+        assert_eq!(stats.packets_decoded, 0);
+        assert_eq!(stats.crc_failures, 1);
+        assert_eq!(failures[0].observation.pdu.payload, [0x11, 0x22, 0x33]);
+        assert_eq!(failures[0].observation.pdu.crc, crc);
+        assert!(failures[0].relative_sample_index < samples.len() as u64);
+    }
+
     #[test]
     fn captures_independent_le_2m_data_vector() {
         let samples = modulated_le_2m_data_channel_packet();
@@ -831,6 +1082,254 @@ mod tests {
         assert_eq!(packets[0].observation.pdu.crc, [0xf2, 0x83, 0x8c]);
     }
 
+    #[test]
+    fn finite_stream_ends_duration_limited_capture() {
+        let samples = modulated_advertisement();
+        let bytes: Vec<u8> = samples
+            .iter()
+            .flat_map(|sample| {
+                sample
+                    .re
+                    .to_le_bytes()
+                    .into_iter()
+                    .chain(sample.im.to_le_bytes())
+            })
+            .collect();
+        let mut source = crate::stream::StreamSource::from_reader(
+            Box::new(std::io::Cursor::new(bytes)),
+            crate::iq::IqFormat::F32Le,
+            None,
+        )
+        .unwrap();
+        let mut packets = 0;
+        let stats = capture_primary_advertising(
+            &mut source,
+            &SdrConfig {
+                center_frequency_hz: 2_402_000_000,
+                sample_rate_hz: 4_000_000,
+                bandwidth_hz: 2_000_000,
+                gain_db: 20.0,
+                channel: 0,
+            },
+            BleChannel::new(37).unwrap(),
+            Le1mDemodConfig {
+                sample_rate_hz: 4_000_000,
+                max_access_address_errors: 0,
+            },
+            CaptureLimits {
+                maximum_samples: None,
+                maximum_duration: Some(Duration::from_secs(60)),
+                read_timeout: Duration::from_millis(100),
+                block_samples: 97,
+            },
+            |_| {
+                packets += 1;
+                Ok(())
+            },
+        )
+        .unwrap();
+        assert!(source.end_of_stream());
+        assert_eq!(stats.samples_received, samples.len() as u64);
+        assert_eq!(stats.discontinuities, 0);
+        assert_eq!(packets, 1);
+    }
+
+    /// Low-level receiver noise; a noiseless idle carrier gives the slicer no
+    /// decision threshold.
+    fn receiver_noise(count: usize) -> impl Iterator<Item = Complex32> {
+        let mut state = 0x2545_f491u32;
+        (0..count).map(move |_| {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            let phase = (state >> 8) as f32 / (1u32 << 24) as f32 * TAU;
+            Complex32::new(0.05 * phase.cos(), 0.05 * phase.sin())
+        })
+    }
+
+    fn replay_source(
+        samples: &[Complex32],
+        channel: BleChannel,
+        impairments: &[&str],
+    ) -> crate::replay::ReplaySource {
+        let bytes: Vec<u8> = samples
+            .iter()
+            .flat_map(|sample| {
+                sample
+                    .re
+                    .to_le_bytes()
+                    .into_iter()
+                    .chain(sample.im.to_le_bytes())
+            })
+            .collect();
+        crate::replay::ReplaySource::new(
+            Box::new(std::io::Cursor::new(bytes)),
+            crate::replay::ReplayRecording {
+                format: crate::iq::IqFormat::F32Le,
+                sample_rate_hz: Some(4_000_000),
+                center_frequency_hz: Some(channel.center_frequency_hz()),
+                sample_count: Some(samples.len() as u64),
+            },
+            crate::replay::ReplayOptions {
+                pacing: crate::replay::ReplayPacing::Unpaced,
+                impairments: impairments
+                    .iter()
+                    .map(|impairment| crate::replay::ReplayImpairment::parse(impairment).unwrap())
+                    .collect(),
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn replayed_advertising_reports_injected_receive_faults() {
+        let channel = BleChannel::new(37).unwrap();
+        let packet = modulated_advertisement();
+        let mut samples = packet.clone();
+        samples.extend(receiver_noise(4_000));
+        let second_start = samples.len();
+        samples.extend_from_slice(&packet);
+        samples.extend(receiver_noise(200));
+        let gap_start = packet.len() + 1_000;
+        let mut source = replay_source(
+            &samples,
+            channel,
+            &[
+                "timeout:64",
+                &format!("overrun:{gap_start}:1500"),
+                &format!("timeout:{}", gap_start + 1_500),
+            ],
+        );
+        let mut packets = Vec::new();
+        let stats = capture_primary_advertising(
+            &mut source,
+            &SdrConfig {
+                center_frequency_hz: channel.center_frequency_hz(),
+                sample_rate_hz: 4_000_000,
+                bandwidth_hz: 2_000_000,
+                gain_db: 20.0,
+                channel: 0,
+            },
+            channel,
+            Le1mDemodConfig {
+                sample_rate_hz: 4_000_000,
+                max_access_address_errors: 0,
+            },
+            CaptureLimits {
+                maximum_samples: None,
+                maximum_duration: Some(Duration::from_secs(60)),
+                read_timeout: Duration::from_millis(100),
+                block_samples: 97,
+            },
+            |packet| {
+                packets.push(packet.relative_sample_index);
+                Ok(())
+            },
+        )
+        .unwrap();
+
+        assert!(source.end_of_stream());
+        assert_eq!(stats.samples_received, samples.len() as u64 - 1_500);
+        assert_eq!(stats.dropped_samples, 1_500);
+        assert_eq!(stats.overruns, 1);
+        assert_eq!(stats.discontinuities, 1);
+        assert_eq!(stats.first_hardware_sample, Some(0));
+        assert_eq!(stats.last_hardware_sample, Some(samples.len() as u64 - 1));
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[1] - packets[0], second_start as u64);
+    }
+
+    #[test]
+    fn replayed_data_channel_recording_drives_central_observation_tracker() {
+        let channel = BleChannel::new(31).unwrap();
+        let mut pdu = vec![0x01, 0x00];
+        pdu.extend_from_slice(&crc24_bytes(&pdu, 0x00ab_cdef));
+        let mut body = bytes_to_bits_lsb(&pdu);
+        whiten_bits(&mut body, channel);
+        let mut bits = bytes_to_bits_lsb(&[0xaa]);
+        bits.extend(bytes_to_bits_lsb(&0x1234_5678u32.to_le_bytes()));
+        bits.extend(body);
+        let packet = modulate_uncoded(&bits, 4, 250_000.0, 4_000_000.0);
+
+        // Event 7 of a 30 ms interval begins 840,000 samples after event 0.
+        let mut samples: Vec<_> = receiver_noise(1_000).collect();
+        samples.extend_from_slice(&packet);
+        samples.extend(receiver_noise(841_000 - samples.len()));
+        samples.extend_from_slice(&packet);
+        samples.extend(receiver_noise(200));
+        let mut source = replay_source(&samples, channel, &["gap:300000:120000"]);
+        let mut tracker =
+            FixedChannelCentralObservationTracker::new(channel, fixed_channel_tracking_config())
+                .unwrap();
+        let mut observations = Vec::new();
+        let stats = capture_data_channel(
+            &mut source,
+            &SdrConfig {
+                center_frequency_hz: channel.center_frequency_hz(),
+                sample_rate_hz: 4_000_000,
+                bandwidth_hz: 2_000_000,
+                gain_db: 20.0,
+                channel: 0,
+            },
+            channel,
+            LeFrameConfig::data(0x1234_5678, 0x00ab_cdef).unwrap(),
+            LeUncodedDemodConfig {
+                phy: crate::demod::LeUncodedPhy::Le1M,
+                sample_rate_hz: 4_000_000,
+                max_access_address_errors: 0,
+            },
+            CaptureLimits {
+                maximum_samples: None,
+                maximum_duration: Some(Duration::from_secs(60)),
+                read_timeout: Duration::from_millis(100),
+                block_samples: 8_192,
+            },
+            |packet| {
+                observations
+                    .push(tracker.observe_central(packet.observation.access_address_sample)?);
+                Ok(())
+            },
+        )
+        .unwrap();
+
+        assert_eq!(stats.packets_decoded, 2);
+        assert_eq!(stats.dropped_samples, 120_000);
+        assert_eq!(stats.overruns, 0);
+        assert_eq!(stats.discontinuities, 1);
+        assert_eq!(observations[0].event.event_counter, 0);
+        assert_eq!(observations[1].event.event_counter, 7);
+        assert_eq!(observations[1].advanced_events, 7);
+        assert_eq!(observations[1].timing_error, SampleTimingError::OnTime);
+    }
+
+    #[test]
+    fn replay_rejects_capture_on_a_different_channel() {
+        let channel = BleChannel::new(37).unwrap();
+        let mut source = replay_source(&modulated_advertisement(), channel, &[]);
+        let error = capture_primary_advertising(
+            &mut source,
+            &SdrConfig {
+                center_frequency_hz: BleChannel::new(38).unwrap().center_frequency_hz(),
+                sample_rate_hz: 4_000_000,
+                bandwidth_hz: 2_000_000,
+                gain_db: 20.0,
+                channel: 0,
+            },
+            BleChannel::new(38).unwrap(),
+            Le1mDemodConfig {
+                sample_rate_hz: 4_000_000,
+                max_access_address_errors: 0,
+            },
+            CaptureLimits {
+                maximum_samples: None,
+                maximum_duration: Some(Duration::from_secs(60)),
+                read_timeout: Duration::from_millis(100),
+                block_samples: 97,
+            },
+            |_| Ok(()),
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("center frequency"));
+    }
+
     #[test]
     fn stops_source_when_packet_callback_fails() {
         let samples = modulated_advertisement();
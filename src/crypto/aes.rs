@@ -0,0 +1,922 @@
+//! AES-128 block encryption with runtime-selected backends.
+//!
+//! AES-NI on x86-64 and the ARMv8 Cryptography Extension on AArch64 are used
+//! when the CPU reports them. Elsewhere a constant-time software backend keeps
+//! the state as eight bit planes, bit `i` of every octet in plane `i`, and runs
+//! SubBytes as the Boyar-Peralta circuit on the planes, so no table is indexed
+//! by key- or data-dependent values. [`Aes128Batch`] encrypts eight blocks per
+//! call, under one key or eight: the hardware backends interleave the blocks,
+//! and the software backend gives each block one bit of every plane octet.
+//! Every backend of [`Aes128`] shares one key schedule, which uses the same
+//! circuit.
+
+use std::ops::{BitAnd, BitOr, BitXor, Not};
+
+const AES_BLOCK_OCTETS: usize = 16;
+const AES_128_ROUND_KEY_OCTETS: usize = 176;
+const AES_128_ROUND_KEYS: usize = 11;
+/// Blocks an [`Aes128Batch`] encrypts per call.
+pub(crate) const AES_BATCH_BLOCKS: usize = 8;
+
+const ROUND_CONSTANTS: [u8; 10] = [0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x1b, 0x36];
+
+/// The implementation an [`Aes128`] encrypts with.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum AesBackend {
+    #[cfg(target_arch = "x86_64")]
+    AesNi,
+    #[cfg(target_arch = "aarch64")]
+    ArmV8,
+    Bitsliced,
+}
+
+impl AesBackend {
+    /// The fastest backend the running CPU supports.
+    pub(crate) fn detect() -> Self {
+        #[cfg(target_arch = "x86_64")]
+        if std::arch::is_x86_feature_detected!("aes") {
+            return Self::AesNi;
+        }
+        #[cfg(target_arch = "aarch64")]
+        if std::arch::is_aarch64_feature_detected!("aes") {
+            return Self::ArmV8;
+        }
+        Self::Bitsliced
+    }
+
+    /// Every backend the running CPU supports, fastest first.
+    #[cfg(test)]
+    pub(crate) fn available() -> Vec<Self> {
+        let mut backends = Vec::new();
+        if Self::detect() != Self::Bitsliced {
+            backends.push(Self::detect());
+        }
+        backends.push(Self::Bitsliced);
+        backends
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct Aes128 {
+    round_keys: [u8; AES_128_ROUND_KEY_OCTETS],
+    /// The round keys as bit planes, used only by the bitsliced backend.
+    round_key_planes: [[u16; 8]; AES_128_ROUND_KEYS],
+    backend: AesBackend,
+}
+
+impl Aes128 {
+    pub(crate) fn new(key: [u8; AES_BLOCK_OCTETS]) -> Self {
+        Self::with_backend(key, AesBackend::detect())
+    }
+
+    /// Expands `key` for `backend`, which must be supported by the CPU; only
+    /// [`AesBackend::detect`] and [`AesBackend::available`] produce those.
+    pub(crate) fn with_backend(key: [u8; AES_BLOCK_OCTETS], backend: AesBackend) -> Self {
+        let mut round_keys = [0u8; AES_128_ROUND_KEY_OCTETS];
+        round_keys[..AES_BLOCK_OCTETS].copy_from_slice(&key);
+        let mut generated = AES_BLOCK_OCTETS;
+        let mut round_constant = 0usize;
+        let mut temporary = [0u8; 4];
+
+        while generated < AES_128_ROUND_KEY_OCTETS {
+            temporary.copy_from_slice(&round_keys[generated - 4..generated]);
+            if generated.is_multiple_of(AES_BLOCK_OCTETS) {
+                temporary.rotate_left(1);
+                let mut word = [0u8; AES_BLOCK_OCTETS];
+                word[..4].copy_from_slice(&temporary);
+                substitute_bytes(&mut word);
+                temporary.copy_from_slice(&word[..4]);
+                temporary[0] ^= ROUND_CONSTANTS[round_constant];
+                round_constant += 1;
+            }
+            for byte in temporary {
+                round_keys[generated] = round_keys[generated - AES_BLOCK_OCTETS] ^ byte;
+                generated += 1;
+            }
+        }
+        let round_key_planes = if backend == AesBackend::Bitsliced {
+            std::array::from_fn(|round| block_planes(round_key(&round_keys, round)))
+        } else {
+            [[0; 8]; AES_128_ROUND_KEYS]
+        };
+        Self {
+            round_keys,
+            round_key_planes,
+            backend,
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) const fn backend(&self) -> AesBackend {
+        self.backend
+    }
+
+    pub(crate) fn encrypt(&self, state: [u8; AES_BLOCK_OCTETS]) -> [u8; AES_BLOCK_OCTETS] {
+        match self.backend {
+            #[cfg(target_arch = "x86_64")]
+            // SAFETY: AesBackend::AesNi is only selected after the CPU reported
+            // the AES feature.
+            AesBackend::AesNi => unsafe { encrypt_aes_ni(&self.round_keys, state) },
+            #[cfg(target_arch = "aarch64")]
+            // SAFETY: AesBackend::ArmV8 is only selected after the CPU reported
+            // the AES feature.
+            AesBackend::ArmV8 => unsafe { encrypt_armv8(&self.round_keys, state) },
+            AesBackend::Bitsliced => {
+                planes_block(encrypt_planes(block_planes(state), &self.round_key_planes))
+            }
+        }
+    }
+
+    /// A batch with this key in every lane.
+    pub(crate) fn batch(&self) -> Aes128Batch {
+        let lanes = match self.backend {
+            #[cfg(target_arch = "x86_64")]
+            AesBackend::AesNi => BatchLanes::AesNi([self.round_keys; AES_BATCH_BLOCKS]),
+            #[cfg(target_arch = "aarch64")]
+            AesBackend::ArmV8 => BatchLanes::ArmV8([self.round_keys; AES_BATCH_BLOCKS]),
+            AesBackend::Bitsliced => BatchLanes::Bitsliced(std::array::from_fn(|round| {
+                lane_planes(&[round_key(&self.round_keys, round); AES_BATCH_BLOCKS])
+            })),
+        };
+        Aes128Batch { lanes }
+    }
+}
+
+/// Eight AES-128 lanes, each with its own key, that encrypt one block each
+/// per call. Key and counter searches use it so that the bitsliced backend
+/// fills every bit of its planes.
+#[derive(Clone)]
+pub(crate) struct Aes128Batch {
+    lanes: BatchLanes,
+}
+
+#[derive(Clone)]
+enum BatchLanes {
+    #[cfg(target_arch = "x86_64")]
+    AesNi([[u8; AES_128_ROUND_KEY_OCTETS]; AES_BATCH_BLOCKS]),
+    #[cfg(target_arch = "aarch64")]
+    ArmV8([[u8; AES_128_ROUND_KEY_OCTETS]; AES_BATCH_BLOCKS]),
+    Bitsliced([[u128; 8]; AES_128_ROUND_KEYS]),
+}
+
+impl Aes128Batch {
+    pub(crate) fn new(keys: [[u8; AES_BLOCK_OCTETS]; AES_BATCH_BLOCKS]) -> Self {
+        Self::with_backend(keys, AesBackend::detect())
+    }
+
+    /// Expands `keys` for `backend`, as [`Aes128::with_backend`] does.
+    pub(crate) fn with_backend(
+        keys: [[u8; AES_BLOCK_OCTETS]; AES_BATCH_BLOCKS],
+        backend: AesBackend,
+    ) -> Self {
+        let lanes = match backend {
+            #[cfg(target_arch = "x86_64")]
+            AesBackend::AesNi => BatchLanes::AesNi(lane_round_keys(&keys)),
+            #[cfg(target_arch = "aarch64")]
+            AesBackend::ArmV8 => BatchLanes::ArmV8(lane_round_keys(&keys)),
+            AesBackend::Bitsliced => BatchLanes::Bitsliced(expand_lane_keys(&keys)),
+        };
+        Self { lanes }
+    }
+
+    /// Encrypts `blocks[lane]` under the key of `lane`.
+    pub(crate) fn encrypt(
+        &self,
+        blocks: [[u8; AES_BLOCK_OCTETS]; AES_BATCH_BLOCKS],
+    ) -> [[u8; AES_BLOCK_OCTETS]; AES_BATCH_BLOCKS] {
+        match &self.lanes {
+            #[cfg(target_arch = "x86_64")]
+            // SAFETY: BatchLanes::AesNi is only built for AesBackend::AesNi.
+            BatchLanes::AesNi(round_keys) => unsafe { encrypt_aes_ni_lanes(round_keys, blocks) },
+            #[cfg(target_arch = "aarch64")]
+            // SAFETY: BatchLanes::ArmV8 is only built for AesBackend::ArmV8.
+            BatchLanes::ArmV8(round_keys) => unsafe { encrypt_armv8_lanes(round_keys, blocks) },
+            BatchLanes::Bitsliced(round_keys) => {
+                lane_blocks(encrypt_planes(lane_planes(&blocks), round_keys))
+            }
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "aes")]
+fn encrypt_aes_ni(
+    round_keys: &[u8; AES_128_ROUND_KEY_OCTETS],
+    block: [u8; AES_BLOCK_OCTETS],
+) -> [u8; AES_BLOCK_OCTETS] {
+    use std::arch::x86_64::{
+        __m128i, _mm_aesenc_si128, _mm_aesenclast_si128, _mm_loadu_si128, _mm_storeu_si128,
+        _mm_xor_si128,
+    };
+    // SAFETY: Every load reads 16 octets inside `round_keys` or `block`;
+    // unaligned loads accept any address.
+    let load = |octets: &[u8]| unsafe { _mm_loadu_si128(octets.as_ptr().cast::<__m128i>()) };
+    let mut state = _mm_xor_si128(load(&block), load(&round_keys[..AES_BLOCK_OCTETS]));
+    for round_key in
+        round_keys[AES_BLOCK_OCTETS..10 * AES_BLOCK_OCTETS].chunks_exact(AES_BLOCK_OCTETS)
+    {
+        state = _mm_aesenc_si128(state, load(round_key));
+    }
+    state = _mm_aesenclast_si128(state, load(&round_keys[10 * AES_BLOCK_OCTETS..]));
+    let mut output = [0u8; AES_BLOCK_OCTETS];
+    // SAFETY: `output` holds exactly the 16 stored octets.
+    unsafe { _mm_storeu_si128(output.as_mut_ptr().cast::<__m128i>(), state) };
+    output
+}
+
+/// [`encrypt_aes_ni`] for every lane, a round at a time so that the AESENC
+/// latencies overlap.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "aes")]
+fn encrypt_aes_ni_lanes(
+    round_keys: &[[u8; AES_128_ROUND_KEY_OCTETS]; AES_BATCH_BLOCKS],
+    blocks: [[u8; AES_BLOCK_OCTETS]; AES_BATCH_BLOCKS],
+) -> [[u8; AES_BLOCK_OCTETS]; AES_BATCH_BLOCKS] {
+    use std::arch::x86_64::{
+        __m128i, _mm_aesenc_si128, _mm_aesenclast_si128, _mm_loadu_si128, _mm_storeu_si128,
+        _mm_xor_si128,
+    };
+    // SAFETY: Every load reads 16 octets inside `round_keys` or `blocks`;
+    // unaligned loads accept any address.
+    let load = |octets: &[u8]| unsafe { _mm_loadu_si128(octets.as_ptr().cast::<__m128i>()) };
+    let mut states: [__m128i; AES_BATCH_BLOCKS] = std::array::from_fn(|lane| {
+        _mm_xor_si128(
+            load(&blocks[lane]),
+            load(&round_keys[lane][..AES_BLOCK_OCTETS]),
+        )
+    });
+    for round in 1..10 {
+        let offset = round * AES_BLOCK_OCTETS;
+        for (state, round_keys) in states.iter_mut().zip(round_keys) {
+            *state = _mm_aesenc_si128(*state, load(&round_keys[offset..offset + AES_BLOCK_OCTETS]));
+        }
+    }
+    let mut output = [[0u8; AES_BLOCK_OCTETS]; AES_BATCH_BLOCKS];
+    for ((state, round_keys), output) in states.into_iter().zip(round_keys).zip(&mut output) {
+        let state = _mm_aesenclast_si128(state, load(&round_keys[10 * AES_BLOCK_OCTETS..]));
+        // SAFETY: `output` holds exactly the 16 stored octets.
+        unsafe { _mm_storeu_si128(output.as_mut_ptr().cast::<__m128i>(), state) };
+    }
+    output
+}
+
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "aes")]
+fn encrypt_armv8(
+    round_keys: &[u8; AES_128_ROUND_KEY_OCTETS],
+    block: [u8; AES_BLOCK_OCTETS],
+) -> [u8; AES_BLOCK_OCTETS] {
+    use std::arch::aarch64::{vaeseq_u8, vaesmcq_u8, veorq_u8, vld1q_u8, vst1q_u8};
+    // SAFETY: Every load reads 16 octets inside `round_keys` or `block`.
+    let load = |octets: &[u8]| unsafe { vld1q_u8(octets.as_ptr()) };
+    // AESE adds the round key before SubBytes and ShiftRows, so the rounds
+    // start from the plaintext and the last key is added separately.
+    let mut state = load(&block);
+    for round_key in round_keys[..9 * AES_BLOCK_OCTETS].chunks_exact(AES_BLOCK_OCTETS) {
+        state = vaesmcq_u8(vaeseq_u8(state, load(round_key)));
+    }
+    state = vaeseq_u8(
+        state,
+        load(&round_keys[9 * AES_BLOCK_OCTETS..10 * AES_BLOCK_OCTETS]),
+    );
+    state = veorq_u8(state, load(&round_keys[10 * AES_BLOCK_OCTETS..]));
+    let mut output = [0u8; AES_BLOCK_OCTETS];
+    // SAFETY: `output` holds exactly the 16 stored octets.
+    unsafe { vst1q_u8(output.as_mut_ptr(), state) };
+    output
+}
+
+/// [`encrypt_armv8`] for every lane, a round at a time so that the AESE
+/// latencies overlap.
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "aes")]
+fn encrypt_armv8_lanes(
+    round_keys: &[[u8; AES_128_ROUND_KEY_OCTETS]; AES_BATCH_BLOCKS],
+    blocks: [[u8; AES_BLOCK_OCTETS]; AES_BATCH_BLOCKS],
+) -> [[u8; AES_BLOCK_OCTETS]; AES_BATCH_BLOCKS] {
+    use std::arch::aarch64::{uint8x16_t, vaeseq_u8, vaesmcq_u8, veorq_u8, vld1q_u8, vst1q_u8};
+    // SAFETY: Every load reads 16 octets inside `round_keys` or `blocks`.
+    let load = |octets: &[u8]| unsafe { vld1q_u8(octets.as_ptr()) };
+    let mut states: [uint8x16_t; AES_BATCH_BLOCKS] =
+        std::array::from_fn(|lane| load(&blocks[lane]));
+    for round in 0..9 {
+        let offset = round * AES_BLOCK_OCTETS;
+        for (state, round_keys) in states.iter_mut().zip(round_keys) {
+            *state = vaesmcq_u8(vaeseq_u8(
+                *state,
+                load(&round_keys[offset..offset + AES_BLOCK_OCTETS]),
+            ));
+        }
+    }
+    let mut output = [[0u8; AES_BLOCK_OCTETS]; AES_BATCH_BLOCKS];
+    for ((state, round_keys), output) in states.into_iter().zip(round_keys).zip(&mut output) {
+        let state = veorq_u8(
+            vaeseq_u8(
+                state,
+                load(&round_keys[9 * AES_BLOCK_OCTETS..10 * AES_BLOCK_OCTETS]),
+            ),
+            load(&round_keys[10 * AES_BLOCK_OCTETS..]),
+        );
+        // SAFETY: `output` holds exactly the 16 stored octets.
+        unsafe { vst1q_u8(output.as_mut_ptr(), state) };
+    }
+    output
+}
+
+fn round_key(round_keys: &[u8; AES_128_ROUND_KEY_OCTETS], round: usize) -> [u8; AES_BLOCK_OCTETS] {
+    round_keys[round * AES_BLOCK_OCTETS..(round + 1) * AES_BLOCK_OCTETS]
+        .try_into()
+        .expect("16 octets")
+}
+
+/// One bit of every state octet, rows first: in a `u16` bit `4 * r + c` is
+/// row `r` of column `c` of one block, and in a `u128` bit
+/// `8 * (4 * r + c) + lane` is that octet of each batch lane.
+trait BitPlane:
+    Copy + BitAnd<Output = Self> + BitOr<Output = Self> + BitXor<Output = Self> + Not<Output = Self>
+{
+    /// ShiftRows: row `r` of column `c` takes row `r` of column `c + r`.
+    fn shift_rows(self) -> Self;
+
+    /// Row `r` of every column takes row `r + rows` of the same column.
+    fn rotate_rows(self, rows: u32) -> Self;
+}
+
+impl BitPlane for u16 {
+    fn shift_rows(self) -> Self {
+        (self & 0x000f)
+            | ((self >> 1) & 0x0070)
+            | ((self << 3) & 0x0080)
+            | ((self >> 2) & 0x0300)
+            | ((self << 2) & 0x0c00)
+            | ((self >> 3) & 0x1000)
+            | ((self << 1) & 0xe000)
+    }
+
+    fn rotate_rows(self, rows: u32) -> Self {
+        self.rotate_right(4 * rows)
+    }
+}
+
+impl BitPlane for u128 {
+    fn shift_rows(self) -> Self {
+        (self & 0xffff_ffff)
+            | ((self >> 8) & 0x00ff_ffff << 32)
+            | ((self << 24) & 0xff00_0000 << 32)
+            | ((self >> 16) & 0x0000_ffff << 64)
+            | ((self << 16) & 0xffff_0000 << 64)
+            | ((self >> 24) & 0x0000_00ff << 96)
+            | ((self << 8) & 0xffff_ff00 << 96)
+    }
+
+    fn rotate_rows(self, rows: u32) -> Self {
+        self.rotate_right(32 * rows)
+    }
+}
+
+fn encrypt_planes<P: BitPlane>(
+    mut state: [P; 8],
+    round_keys: &[[P; 8]; AES_128_ROUND_KEYS],
+) -> [P; 8] {
+    add_round_key_planes(&mut state, &round_keys[0]);
+    for round_key in &round_keys[1..AES_128_ROUND_KEYS - 1] {
+        substitute_bit_planes(&mut state);
+        state = state.map(BitPlane::shift_rows);
+        mix_column_planes(&mut state);
+        add_round_key_planes(&mut state, round_key);
+    }
+    substitute_bit_planes(&mut state);
+    state = state.map(BitPlane::shift_rows);
+    add_round_key_planes(&mut state, &round_keys[AES_128_ROUND_KEYS - 1]);
+    state
+}
+
+fn add_round_key_planes<P: BitPlane>(state: &mut [P; 8], round_key: &[P; 8]) {
+    for (state, key) in state.iter_mut().zip(round_key) {
+        *state = *state ^ *key;
+    }
+}
+
+/// MixColumns as 2(a_r ^ a_r+1) ^ a_r+1 ^ (a_r+2 ^ a_r+3), where doubling in
+/// GF(2^8) moves each plane up one bit and folds the carry in as 0x1b.
+fn mix_column_planes<P: BitPlane>(planes: &mut [P; 8]) {
+    let next = planes.map(|plane| plane.rotate_rows(1));
+    let pairs: [P; 8] = std::array::from_fn(|bit| planes[bit] ^ next[bit]);
+    let carry = pairs[7];
+    let doubled = [
+        carry,
+        pairs[0] ^ carry,
+        pairs[1],
+        pairs[2] ^ carry,
+        pairs[3] ^ carry,
+        pairs[4],
+        pairs[5],
+        pairs[6],
+    ];
+    *planes = std::array::from_fn(|bit| doubled[bit] ^ next[bit] ^ pairs[bit].rotate_rows(2));
+}
+
+/// The key schedule of every lane at once, on the planes of
+/// [`Aes128Batch`]. Each round key's first column takes SubWord and RotWord
+/// of the previous last column.
+fn expand_lane_keys(
+    keys: &[[u8; AES_BLOCK_OCTETS]; AES_BATCH_BLOCKS],
+) -> [[u128; 8]; AES_128_ROUND_KEYS] {
+    // Octet 0 of each row, which holds column 0.
+    const FIRST_COLUMN: u128 = 0x0000_00ff_0000_00ff_0000_00ff_0000_00ff;
+    let mut round_keys = [[0u128; 8]; AES_128_ROUND_KEYS];
+    round_keys[0] = lane_planes(keys);
+    for round in 1..AES_128_ROUND_KEYS {
+        let previous = round_keys[round - 1];
+        let mut substituted = previous;
+        substitute_bit_planes(&mut substituted);
+        round_keys[round] = std::array::from_fn(|bit| {
+            let mut word = (substituted[bit].rotate_rows(1) >> 24) & FIRST_COLUMN;
+            if ROUND_CONSTANTS[round - 1] >> bit & 1 == 1 {
+                word ^= 0xff;
+            }
+            // Column c becomes the XOR of columns 0 through c, then of word.
+            let mut columns = previous[bit];
+            columns ^= (columns << 8) & !FIRST_COLUMN;
+            columns ^= (columns << 16) & !(FIRST_COLUMN | FIRST_COLUMN << 8);
+            columns ^ word ^ word << 8 ^ word << 16 ^ word << 24
+        });
+    }
+    round_keys
+}
+
+/// The round keys of every lane in octet order, for the hardware backends.
+/// The bitsliced schedule expands eight keys faster than eight byte-wise
+/// schedules do.
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+fn lane_round_keys(
+    keys: &[[u8; AES_BLOCK_OCTETS]; AES_BATCH_BLOCKS],
+) -> [[u8; AES_128_ROUND_KEY_OCTETS]; AES_BATCH_BLOCKS] {
+    let mut round_keys = [[0u8; AES_128_ROUND_KEY_OCTETS]; AES_BATCH_BLOCKS];
+    for (round, planes) in expand_lane_keys(keys).into_iter().enumerate() {
+        for (round_keys, round_key) in round_keys.iter_mut().zip(lane_blocks(planes)) {
+            round_keys[round * AES_BLOCK_OCTETS..(round + 1) * AES_BLOCK_OCTETS]
+                .copy_from_slice(&round_key);
+        }
+    }
+    round_keys
+}
+
+/// Applies the S-box to every octet of one block.
+fn substitute_bytes(state: &mut [u8; AES_BLOCK_OCTETS]) {
+    let mut planes = block_planes(*state);
+    substitute_bit_planes(&mut planes);
+    *state = planes_block(planes);
+}
+
+/// Splits `block` into bit planes: plane `i` holds bit `i` of every octet.
+fn block_planes(block: [u8; AES_BLOCK_OCTETS]) -> [u16; 8] {
+    let block = rows_first(block);
+    let low = transpose_bits(u64::from_le_bytes(block[..8].try_into().expect("8 octets")));
+    let high = transpose_bits(u64::from_le_bytes(block[8..].try_into().expect("8 octets")));
+    std::array::from_fn(|bit| {
+        (low >> (8 * bit)) as u8 as u16 | ((high >> (8 * bit)) as u8 as u16) << 8
+    })
+}
+
+fn planes_block(planes: [u16; 8]) -> [u8; AES_BLOCK_OCTETS] {
+    let (low, high) = planes
+        .iter()
+        .enumerate()
+        .fold((0u64, 0u64), |(low, high), (bit, plane)| {
+            (
+                low | u64::from(*plane as u8) << (8 * bit),
+                high | u64::from((*plane >> 8) as u8) << (8 * bit),
+            )
+        });
+    let mut block = [0u8; AES_BLOCK_OCTETS];
+    block[..8].copy_from_slice(&transpose_bits(low).to_le_bytes());
+    block[8..].copy_from_slice(&transpose_bits(high).to_le_bytes());
+    rows_first(block)
+}
+
+/// Reorders a block's octets from column order to row order, or back.
+fn rows_first(block: [u8; AES_BLOCK_OCTETS]) -> [u8; AES_BLOCK_OCTETS] {
+    std::array::from_fn(|index| block[4 * (index % 4) + index / 4])
+}
+
+/// Splits one block per lane into bit planes: octet `4 * r + c` of plane `i`
+/// holds bit `i` of row `r` of column `c` of every lane, lane 0 least
+/// significant.
+fn lane_planes(blocks: &[[u8; AES_BLOCK_OCTETS]; AES_BATCH_BLOCKS]) -> [u128; 8] {
+    let octets: [[u8; 8]; AES_BLOCK_OCTETS] = std::array::from_fn(|octet| {
+        let position = 4 * (octet % 4) + octet / 4;
+        transpose_bits(u64::from_le_bytes(blocks.map(|block| block[position]))).to_le_bytes()
+    });
+    std::array::from_fn(|bit| u128::from_le_bytes(octets.map(|octet| octet[bit])))
+}
+
+fn lane_blocks(planes: [u128; 8]) -> [[u8; AES_BLOCK_OCTETS]; AES_BATCH_BLOCKS] {
+    let planes = planes.map(u128::to_le_bytes);
+    let lanes: [[u8; AES_BATCH_BLOCKS]; AES_BLOCK_OCTETS] = std::array::from_fn(|octet| {
+        transpose_bits(u64::from_le_bytes(planes.map(|plane| plane[octet]))).to_le_bytes()
+    });
+    std::array::from_fn(|lane| {
+        std::array::from_fn(|position| lanes[4 * (position % 4) + position / 4][lane])
+    })
+}
+
+/// Transposes the 8x8 bit matrix whose rows are the octets of `value`.
+const fn transpose_bits(mut value: u64) -> u64 {
+    let mut swapped = (value ^ (value >> 7)) & 0x00aa_00aa_00aa_00aa;
+    value ^= swapped ^ (swapped << 7);
+    swapped = (value ^ (value >> 14)) & 0x0000_cccc_0000_cccc;
+    value ^= swapped ^ (swapped << 14);
+    swapped = (value ^ (value >> 28)) & 0x0000_0000_f0f0_f0f0;
+    value ^ swapped ^ (swapped << 28)
+}
+
+/// The AES S-box as the 113-gate circuit of Boyar and Peralta. `planes[0]`
+/// holds the least significant bit of every input.
+fn substitute_bit_planes<P: BitPlane>(planes: &mut [P; 8]) {
+    let [x7, x6, x5, x4, x3, x2, x1, x0] = *planes;
+
+    // Top linear transformation.
+    let y14 = x3 ^ x5;
+    let y13 = x0 ^ x6;
+    let y9 = x0 ^ x3;
+    let y8 = x0 ^ x5;
+    let t0 = x1 ^ x2;
+    let y1 = t0 ^ x7;
+    let y4 = y1 ^ x3;
+    let y12 = y13 ^ y14;
+    let y2 = y1 ^ x0;
+    let y5 = y1 ^ x6;
+    let y3 = y5 ^ y8;
+    let t1 = x4 ^ y12;
+    let y15 = t1 ^ x5;
+    let y20 = t1 ^ x1;
+    let y6 = y15 ^ x7;
+    let y10 = y15 ^ t0;
+    let y11 = y20 ^ y9;
+    let y7 = x7 ^ y11;
+    let y17 = y10 ^ y11;
+    let y19 = y10 ^ y8;
+    let y16 = t0 ^ y11;
+    let y21 = y13 ^ y16;
+    let y18 = x0 ^ y16;
+
+    // Shared non-linear middle section, inversion in GF(2^4)^2.
+    let t2 = y12 & y15;
+    let t3 = y3 & y6;
+    let t4 = t3 ^ t2;
+    let t5 = y4 & x7;
+    let t6 = t5 ^ t2;
+    let t7 = y13 & y16;
+    let t8 = y5 & y1;
+    let t9 = t8 ^ t7;
+    let t10 = y2 & y7;
+    let t11 = t10 ^ t7;
+    let t12 = y9 & y11;
+    let t13 = y14 & y17;
+    let t14 = t13 ^ t12;
+    let t15 = y8 & y10;
+    let t16 = t15 ^ t12;
+    let t17 = t4 ^ t14;
+    let t18 = t6 ^ t16;
+    let t19 = t9 ^ t14;
+    let t20 = t11 ^ t16;
+    let t21 = t17 ^ y20;
+    let t22 = t18 ^ y19;
+    let t23 = t19 ^ y21;
+    let t24 = t20 ^ y18;
+
+    let t25 = t21 ^ t22;
+    let t26 = t21 & t23;
+    let t27 = t24 ^ t26;
+    let t28 = t25 & t27;
+    let t29 = t28 ^ t22;
+    let t30 = t23 ^ t24;
+    let t31 = t22 ^ t26;
+    let t32 = t31 & t30;
+    let t33 = t32 ^ t24;
+    let t34 = t23 ^ t33;
+    let t35 = t27 ^ t33;
+    let t36 = t24 & t35;
+    let t37 = t36 ^ t34;
+    let t38 = t27 ^ t36;
+    let t39 = t29 & t38;
+    let t40 = t25 ^ t39;
+
+    let t41 = t40 ^ t37;
+    let t42 = t29 ^ t33;
+    let t43 = t29 ^ t40;
+    let t44 = t33 ^ t37;
+    let t45 = t42 ^ t41;
+    let z0 = t44 & y15;
+    let z1 = t37 & y6;
+    let z2 = t33 & x7;
+    let z3 = t43 & y16;
+    let z4 = t40 & y1;
+    let z5 = t29 & y7;
+    let z6 = t42 & y11;
+    let z7 = t45 & y17;
+    let z8 = t41 & y10;
+    let z9 = t44 & y12;
+    let z10 = t37 & y3;
+    let z11 = t33 & y4;
+    let z12 = t43 & y13;
+    let z13 = t40 & y5;
+    let z14 = t29 & y2;
+    let z15 = t42 & y9;
+    let z16 = t45 & y14;
+    let z17 = t41 & y8;
+
+    // Bottom linear transformation, including the affine constant.
+    let t46 = z15 ^ z16;
+    let t47 = z10 ^ z11;
+    let t48 = z5 ^ z13;
+    let t49 = z9 ^ z10;
+    let t50 = z2 ^ z12;
+    let t51 = z2 ^ z5;
+    let t52 = z7 ^ z8;
+    let t53 = z0 ^ z3;
+    let t54 = z6 ^ z7;
+    let t55 = z16 ^ z17;
+    let t56 = z12 ^ t48;
+    let t57 = t50 ^ t53;
+    let t58 = z4 ^ t46;
+    let t59 = z3 ^ t54;
+    let t60 = t46 ^ t57;
+    let t61 = z14 ^ t57;
+    let t62 = t52 ^ t58;
+    let t63 = t49 ^ t58;
+    let t64 = z4 ^ t59;
+    let t65 = t61 ^ t62;
+    let t66 = z1 ^ t63;
+    let s0 = t59 ^ t63;
+    let s6 = t56 ^ !t62;
+    let s7 = t48 ^ !t60;
+    let t67 = t64 ^ t65;
+    let s3 = t53 ^ t66;
+    let s4 = t51 ^ t66;
+    let s5 = t47 ^ t65;
+    let s1 = t64 ^ !s3;
+    let s2 = t55 ^ !t67;
+
+    *planes = [s7, s6, s5, s4, s3, s2, s1, s0];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    /// The FIPS-197 S-box table the circuit replaces.
+    const S_BOX: [u8; 256] = [
+        0x63, 0x7c, 0x77, 0x7b, 0xf2, 0x6b, 0x6f, 0xc5, 0x30, 0x01, 0x67, 0x2b, 0xfe, 0xd7, 0xab,
+        0x76, 0xca, 0x82, 0xc9, 0x7d, 0xfa, 0x59, 0x47, 0xf0, 0xad, 0xd4, 0xa2, 0xaf, 0x9c, 0xa4,
+        0x72, 0xc0, 0xb7, 0xfd, 0x93, 0x26, 0x36, 0x3f, 0xf7, 0xcc, 0x34, 0xa5, 0xe5, 0xf1, 0x71,
+        0xd8, 0x31, 0x15, 0x04, 0xc7, 0x23, 0xc3, 0x18, 0x96, 0x05, 0x9a, 0x07, 0x12, 0x80, 0xe2,
+        0xeb, 0x27, 0xb2, 0x75, 0x09, 0x83, 0x2c, 0x1a, 0x1b, 0x6e, 0x5a, 0xa0, 0x52, 0x3b, 0xd6,
+        0xb3, 0x29, 0xe3, 0x2f, 0x84, 0x53, 0xd1, 0x00, 0xed, 0x20, 0xfc, 0xb1, 0x5b, 0x6a, 0xcb,
+        0xbe, 0x39, 0x4a, 0x4c, 0x58, 0xcf, 0xd0, 0xef, 0xaa, 0xfb, 0x43, 0x4d, 0x33, 0x85, 0x45,
+        0xf9, 0x02, 0x7f, 0x50, 0x3c, 0x9f, 0xa8, 0x51, 0xa3, 0x40, 0x8f, 0x92, 0x9d, 0x38, 0xf5,
+        0xbc, 0xb6, 0xda, 0x21, 0x10, 0xff, 0xf3, 0xd2, 0xcd, 0x0c, 0x13, 0xec, 0x5f, 0x97, 0x44,
+        0x17, 0xc4, 0xa7, 0x7e, 0x3d, 0x64, 0x5d, 0x19, 0x73, 0x60, 0x81, 0x4f, 0xdc, 0x22, 0x2a,
+        0x90, 0x88, 0x46, 0xee, 0xb8, 0x14, 0xde, 0x5e, 0x0b, 0xdb, 0xe0, 0x32, 0x3a, 0x0a, 0x49,
+        0x06, 0x24, 0x5c, 0xc2, 0xd3, 0xac, 0x62, 0x91, 0x95, 0xe4, 0x79, 0xe7, 0xc8, 0x37, 0x6d,
+        0x8d, 0xd5, 0x4e, 0xa9, 0x6c, 0x56, 0xf4, 0xea, 0x65, 0x7a, 0xae, 0x08, 0xba, 0x78, 0x25,
+        0x2e, 0x1c, 0xa6, 0xb4, 0xc6, 0xe8, 0xdd, 0x74, 0x1f, 0x4b, 0xbd, 0x8b, 0x8a, 0x70, 0x3e,
+        0xb5, 0x66, 0x48, 0x03, 0xf6, 0x0e, 0x61, 0x35, 0x57, 0xb9, 0x86, 0xc1, 0x1d, 0x9e, 0xe1,
+        0xf8, 0x98, 0x11, 0x69, 0xd9, 0x8e, 0x94, 0x9b, 0x1e, 0x87, 0xe9, 0xce, 0x55, 0x28, 0xdf,
+        0x8c, 0xa1, 0x89, 0x0d, 0xbf, 0xe6, 0x42, 0x68, 0x41, 0x99, 0x2d, 0x0f, 0xb0, 0x54, 0xbb,
+        0x16,
+    ];
+
+    /// The table-lookup cipher the backends replaced, kept as the
+    /// benchmark's baseline. It indexes `S_BOX` with secret octets.
+    fn encrypt_table(
+        round_keys: &[u8; AES_128_ROUND_KEY_OCTETS],
+        mut state: [u8; AES_BLOCK_OCTETS],
+    ) -> [u8; AES_BLOCK_OCTETS] {
+        let substitute = |state: &mut [u8; AES_BLOCK_OCTETS]| {
+            state
+                .iter_mut()
+                .for_each(|byte| *byte = S_BOX[*byte as usize]);
+        };
+        add_round_key(&mut state, &round_keys[..AES_BLOCK_OCTETS]);
+        for round in 1..10 {
+            substitute(&mut state);
+            shift_rows(&mut state);
+            mix_columns(&mut state);
+            add_round_key(
+                &mut state,
+                &round_keys[round * AES_BLOCK_OCTETS..(round + 1) * AES_BLOCK_OCTETS],
+            );
+        }
+        substitute(&mut state);
+        shift_rows(&mut state);
+        add_round_key(&mut state, &round_keys[10 * AES_BLOCK_OCTETS..]);
+        state
+    }
+
+    fn add_round_key(state: &mut [u8; AES_BLOCK_OCTETS], round_key: &[u8]) {
+        for (state, key) in state.iter_mut().zip(round_key) {
+            *state ^= key;
+        }
+    }
+
+    fn shift_rows(state: &mut [u8; AES_BLOCK_OCTETS]) {
+        let before = *state;
+        state[1] = before[5];
+        state[5] = before[9];
+        state[9] = before[13];
+        state[13] = before[1];
+        state[2] = before[10];
+        state[6] = before[14];
+        state[10] = before[2];
+        state[14] = before[6];
+        state[3] = before[15];
+        state[7] = before[3];
+        state[11] = before[7];
+        state[15] = before[11];
+    }
+
+    fn mix_columns(state: &mut [u8; AES_BLOCK_OCTETS]) {
+        for column in state.chunks_exact_mut(4) {
+            let before = [column[0], column[1], column[2], column[3]];
+            column[0] =
+                multiply_by_two(before[0]) ^ multiply_by_three(before[1]) ^ before[2] ^ before[3];
+            column[1] =
+                before[0] ^ multiply_by_two(before[1]) ^ multiply_by_three(before[2]) ^ before[3];
+            column[2] =
+                before[0] ^ before[1] ^ multiply_by_two(before[2]) ^ multiply_by_three(before[3]);
+            column[3] =
+                multiply_by_three(before[0]) ^ before[1] ^ before[2] ^ multiply_by_two(before[3]);
+        }
+    }
+
+    /// Doubles in GF(2^8), reducing with a mask rather than a branch.
+    const fn multiply_by_two(value: u8) -> u8 {
+        (value << 1) ^ (0u8.wrapping_sub(value >> 7) & 0x1b)
+    }
+
+    const fn multiply_by_three(value: u8) -> u8 {
+        multiply_by_two(value) ^ value
+    }
+
+    /// The key schedule of the table cipher, with the same lookups.
+    fn table_round_keys(key: [u8; AES_BLOCK_OCTETS]) -> [u8; AES_128_ROUND_KEY_OCTETS] {
+        let mut round_keys = [0u8; AES_128_ROUND_KEY_OCTETS];
+        round_keys[..AES_BLOCK_OCTETS].copy_from_slice(&key);
+        for generated in (AES_BLOCK_OCTETS..AES_128_ROUND_KEY_OCTETS).step_by(4) {
+            let mut temporary: [u8; 4] = round_keys[generated - 4..generated]
+                .try_into()
+                .expect("4 octets");
+            if generated.is_multiple_of(AES_BLOCK_OCTETS) {
+                temporary.rotate_left(1);
+                temporary = temporary.map(|byte| S_BOX[usize::from(byte)]);
+                temporary[0] ^= ROUND_CONSTANTS[generated / AES_BLOCK_OCTETS - 1];
+            }
+            for (offset, byte) in temporary.into_iter().enumerate() {
+                round_keys[generated + offset] =
+                    round_keys[generated + offset - AES_BLOCK_OCTETS] ^ byte;
+            }
+        }
+        round_keys
+    }
+
+    const FIPS_197_KEY: [u8; 16] = [
+        0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e,
+        0x0f,
+    ];
+
+    #[test]
+    fn bitsliced_s_box_matches_the_fips_197_table() {
+        for first in (0..=255u8).step_by(16) {
+            let mut state: [u8; 16] = std::array::from_fn(|index| first + index as u8);
+            substitute_bytes(&mut state);
+            for (index, output) in state.into_iter().enumerate() {
+                assert_eq!(output, S_BOX[usize::from(first) + index]);
+            }
+        }
+    }
+
+    #[test]
+    fn every_available_backend_matches_the_fips_197_example() {
+        for backend in AesBackend::available() {
+            let aes = Aes128::with_backend(FIPS_197_KEY, backend);
+            assert_eq!(aes.backend(), backend);
+            assert_eq!(
+                aes.encrypt([
+                    0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc,
+                    0xdd, 0xee, 0xff,
+                ]),
+                [
+                    0x69, 0xc4, 0xe0, 0xd8, 0x6a, 0x7b, 0x04, 0x30, 0xd8, 0xcd, 0xb7, 0x80, 0x70,
+                    0xb4, 0xc5, 0x5a,
+                ],
+                "{backend:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn backends_agree_on_chained_blocks() {
+        let backends = AesBackend::available();
+        let mut key = FIPS_197_KEY;
+        let mut block = [0u8; 16];
+        for _ in 0..64 {
+            let outputs: Vec<_> = backends
+                .iter()
+                .map(|backend| Aes128::with_backend(key, *backend).encrypt(block))
+                .collect();
+            assert!(outputs.iter().all(|output| *output == outputs[0]));
+            assert_eq!(encrypt_table(&table_round_keys(key), block), outputs[0]);
+            key = block;
+            block = outputs[0];
+        }
+    }
+
+    #[test]
+    fn batches_match_single_blocks_on_every_backend() {
+        let keys: [[u8; 16]; AES_BATCH_BLOCKS] = std::array::from_fn(|lane| {
+            std::array::from_fn(|index| (lane * 37 + index * 11) as u8 ^ FIPS_197_KEY[index])
+        });
+        let blocks: [[u8; 16]; AES_BATCH_BLOCKS] =
+            std::array::from_fn(|lane| std::array::from_fn(|index| (lane * 5 + index * 29) as u8));
+        let expected: [[u8; 16]; AES_BATCH_BLOCKS] = std::array::from_fn(|lane| {
+            encrypt_table(&Aes128::new(keys[lane]).round_keys, blocks[lane])
+        });
+        for backend in AesBackend::available() {
+            assert_eq!(
+                Aes128Batch::with_backend(keys, backend).encrypt(blocks),
+                expected,
+                "{backend:?}"
+            );
+            let aes = Aes128::with_backend(keys[3], backend);
+            assert_eq!(
+                aes.batch().encrypt(blocks),
+                blocks.map(|block| encrypt_table(&aes.round_keys, block)),
+                "{backend:?}"
+            );
+        }
+    }
+
+    /// Prints the best block throughput of five runs for each backend, alone
+    /// and in batches, and for the table cipher they replaced, then the same
+    /// with a fresh key for every two blocks as the legacy TK search runs
+    /// `c1`:
+    /// `cargo test --release --lib aes_backend_throughput -- --ignored --nocapture`.
+    #[test]
+    #[ignore = "benchmark"]
+    fn aes_backend_throughput() {
+        const BLOCKS: u32 = 2_000_000;
+        type Blocks = [[u8; 16]; AES_BATCH_BLOCKS];
+        let measure = |name: &str, encrypt: &dyn Fn(Blocks) -> Blocks| {
+            let mut blocks = [[0u8; 16]; AES_BATCH_BLOCKS];
+            let elapsed = (0..5)
+                .map(|_| {
+                    let started = Instant::now();
+                    for _ in 0..BLOCKS / AES_BATCH_BLOCKS as u32 {
+                        blocks = encrypt(std::hint::black_box(blocks));
+                    }
+                    started.elapsed()
+                })
+                .min()
+                .expect("five runs");
+            println!(
+                "aes_backend={name} blocks={BLOCKS} seconds={:.3} blocks_per_second={:.0} last={:02x?}",
+                elapsed.as_secs_f64(),
+                f64::from(BLOCKS) / elapsed.as_secs_f64(),
+                blocks[0]
+            );
+        };
+        for backend in AesBackend::available() {
+            let aes = Aes128::with_backend(FIPS_197_KEY, backend);
+            let batch = aes.batch();
+            measure(&format!("{backend:?}"), &|blocks| {
+                blocks.map(|block| aes.encrypt(block))
+            });
+            measure(&format!("{backend:?}Batch"), &|blocks| {
+                batch.encrypt(blocks)
+            });
+            measure(&format!("{backend:?}Keyed"), &|blocks| {
+                blocks.map(|block| {
+                    let aes = Aes128::with_backend(block, backend);
+                    aes.encrypt(aes.encrypt(block))
+                })
+            });
+            measure(&format!("{backend:?}BatchKeyed"), &|blocks| {
+                let batch = Aes128Batch::with_backend(blocks, backend);
+                batch.encrypt(batch.encrypt(blocks))
+            });
+        }
+        let round_keys = Aes128::new(FIPS_197_KEY).round_keys;
+        measure("TableBaseline", &|blocks| {
+            blocks.map(|block| encrypt_table(&round_keys, block))
+        });
+        measure("TableBaselineKeyed", &|blocks| {
+            blocks.map(|block| {
+                let round_keys = table_round_keys(block);
+                encrypt_table(&round_keys, encrypt_table(&round_keys, block))
+            })
+        });
+    }
+}
@@ -0,0 +1,387 @@
+//! NIST P-256 elliptic-curve Diffie-Hellman for LE Secure Connections.
+//!
+//! Field elements are four little-endian 64-bit limbs in Montgomery form.
+//! Scalar multiplication is a plain double-and-add and is not constant time:
+//! the analyzer only multiplies by published or already disclosed private
+//! keys. Byte strings are big-endian, as in SEC 1.
+
+const P256_OCTETS: usize = 32;
+
+type Limbs = [u64; 4];
+
+/// p = 2^256 - 2^224 + 2^192 + 2^96 - 1.
+const PRIME: Limbs = [
+    0xffff_ffff_ffff_ffff,
+    0x0000_0000_ffff_ffff,
+    0x0000_0000_0000_0000,
+    0xffff_ffff_0000_0001,
+];
+/// 2^512 mod p, which moves a value into Montgomery form.
+const MONTGOMERY_R_SQUARED: Limbs = [
+    0x0000_0000_0000_0003,
+    0xffff_fffb_ffff_ffff,
+    0xffff_ffff_ffff_fffe,
+    0x0000_0004_ffff_fffd,
+];
+const CURVE_B: [u8; P256_OCTETS] = [
+    0x5a, 0xc6, 0x35, 0xd8, 0xaa, 0x3a, 0x93, 0xe7, 0xb3, 0xeb, 0xbd, 0x55, 0x76, 0x98, 0x86, 0xbc,
+    0x65, 0x1d, 0x06, 0xb0, 0xcc, 0x53, 0xb0, 0xf6, 0x3b, 0xce, 0x3c, 0x3e, 0x27, 0xd2, 0x60, 0x4b,
+];
+const GENERATOR_X: [u8; P256_OCTETS] = [
+    0x6b, 0x17, 0xd1, 0xf2, 0xe1, 0x2c, 0x42, 0x47, 0xf8, 0xbc, 0xe6, 0xe5, 0x63, 0xa4, 0x40, 0xf2,
+    0x77, 0x03, 0x7d, 0x81, 0x2d, 0xeb, 0x33, 0xa0, 0xf4, 0xa1, 0x39, 0x45, 0xd8, 0x98, 0xc2, 0x96,
+];
+const GENERATOR_Y: [u8; P256_OCTETS] = [
+    0x4f, 0xe3, 0x42, 0xe2, 0xfe, 0x1a, 0x7f, 0x9b, 0x8e, 0xe7, 0xeb, 0x4a, 0x7c, 0x0f, 0x9e, 0x16,
+    0x2b, 0xce, 0x33, 0x57, 0x6b, 0x31, 0x5e, 0xce, 0xcb, 0xb6, 0x40, 0x68, 0x37, 0xbf, 0x51, 0xf5,
+];
+
+/// The public key `private_key * G` as affine `(x, y)`, or `None` for a
+/// zero private key.
+pub(crate) fn p256_public_key(
+    private_key: [u8; P256_OCTETS],
+) -> Option<([u8; P256_OCTETS], [u8; P256_OCTETS])> {
+    let generator = AffinePoint::from_bytes(GENERATOR_X, GENERATOR_Y)?;
+    multiply(private_key, &generator).to_affine()
+}
+
+/// The shared x-coordinate of `private_key * public`, or `None` when the
+/// public key is not on the curve or the product is the point at infinity.
+pub(crate) fn p256_diffie_hellman(
+    private_key: [u8; P256_OCTETS],
+    public_x: [u8; P256_OCTETS],
+    public_y: [u8; P256_OCTETS],
+) -> Option<[u8; P256_OCTETS]> {
+    let public = AffinePoint::from_bytes(public_x, public_y)?;
+    multiply(private_key, &public).to_affine().map(|(x, _)| x)
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct FieldElement(Limbs);
+
+impl FieldElement {
+    const ZERO: Self = Self([0; 4]);
+
+    fn one() -> Self {
+        Self::from_limbs([1, 0, 0, 0])
+    }
+
+    /// Converts big-endian octets, rejecting values not below p.
+    fn from_bytes(bytes: [u8; P256_OCTETS]) -> Option<Self> {
+        let limbs: Limbs = std::array::from_fn(|index| {
+            let start = P256_OCTETS - (index + 1) * 8;
+            u64::from_be_bytes(bytes[start..start + 8].try_into().expect("eight octets"))
+        });
+        (!greater_or_equal(&limbs, &PRIME)).then(|| Self::from_limbs(limbs))
+    }
+
+    fn from_limbs(limbs: Limbs) -> Self {
+        Self(montgomery_multiply(&limbs, &MONTGOMERY_R_SQUARED))
+    }
+
+    fn to_bytes(self) -> [u8; P256_OCTETS] {
+        let limbs = montgomery_multiply(&self.0, &[1, 0, 0, 0]);
+        let mut bytes = [0u8; P256_OCTETS];
+        for (index, limb) in limbs.iter().enumerate() {
+            let start = P256_OCTETS - (index + 1) * 8;
+            bytes[start..start + 8].copy_from_slice(&limb.to_be_bytes());
+        }
+        bytes
+    }
+
+    fn is_zero(self) -> bool {
+        self.0 == [0; 4]
+    }
+
+    fn add(self, other: Self) -> Self {
+        let (sum, carry) = add_limbs(&self.0, &other.0);
+        if carry || greater_or_equal(&sum, &PRIME) {
+            Self(subtract_limbs(&sum, &PRIME).0)
+        } else {
+            Self(sum)
+        }
+    }
+
+    fn subtract(self, other: Self) -> Self {
+        let (difference, borrow) = subtract_limbs(&self.0, &other.0);
+        if borrow {
+            Self(add_limbs(&difference, &PRIME).0)
+        } else {
+            Self(difference)
+        }
+    }
+
+    fn multiply(self, other: Self) -> Self {
+        Self(montgomery_multiply(&self.0, &other.0))
+    }
+
+    fn square(self) -> Self {
+        self.multiply(self)
+    }
+
+    fn double(self) -> Self {
+        self.add(self)
+    }
+
+    /// Inverts by Fermat's little theorem, raising to p - 2.
+    fn invert(self) -> Self {
+        let exponent = subtract_limbs(&PRIME, &[2, 0, 0, 0]).0;
+        let mut result = Self::one();
+        for bit in (0..256).rev() {
+            result = result.square();
+            if exponent[bit / 64] >> (bit % 64) & 1 == 1 {
+                result = result.multiply(self);
+            }
+        }
+        result
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct AffinePoint {
+    x: FieldElement,
+    y: FieldElement,
+}
+
+impl AffinePoint {
+    /// Accepts only points satisfying y^2 = x^3 - 3x + b.
+    fn from_bytes(x: [u8; P256_OCTETS], y: [u8; P256_OCTETS]) -> Option<Self> {
+        let x = FieldElement::from_bytes(x)?;
+        let y = FieldElement::from_bytes(y)?;
+        let b = FieldElement::from_bytes(CURVE_B)?;
+        let three_x = x.double().add(x);
+        let right = x.square().multiply(x).subtract(three_x).add(b);
+        (y.square() == right).then_some(Self { x, y })
+    }
+}
+
+/// Jacobian coordinates; Z = 0 is the point at infinity.
+#[derive(Clone, Copy, Debug)]
+struct JacobianPoint {
+    x: FieldElement,
+    y: FieldElement,
+    z: FieldElement,
+}
+
+impl JacobianPoint {
+    const INFINITY: Self = Self {
+        x: FieldElement::ZERO,
+        y: FieldElement::ZERO,
+        z: FieldElement::ZERO,
+    };
+
+    fn to_affine(self) -> Option<([u8; P256_OCTETS], [u8; P256_OCTETS])> {
+        if self.z.is_zero() {
+            return None;
+        }
+        let inverse = self.z.invert();
+        let inverse_squared = inverse.square();
+        Some((
+            self.x.multiply(inverse_squared).to_bytes(),
+            self.y
+                .multiply(inverse_squared)
+                .multiply(inverse)
+                .to_bytes(),
+        ))
+    }
+
+    /// dbl-2001-b, which relies on a = -3.
+    fn double(self) -> Self {
+        if self.z.is_zero() || self.y.is_zero() {
+            return Self::INFINITY;
+        }
+        let delta = self.z.square();
+        let gamma = self.y.square();
+        let beta = self.x.multiply(gamma);
+        let alpha = {
+            let product = self.x.subtract(delta).multiply(self.x.add(delta));
+            product.double().add(product)
+        };
+        let four_beta = beta.double().double();
+        let x = alpha.square().subtract(four_beta.double());
+        let z = self.y.add(self.z).square().subtract(gamma).subtract(delta);
+        let eight_gamma_squared = gamma.square().double().double().double();
+        let y = alpha
+            .multiply(four_beta.subtract(x))
+            .subtract(eight_gamma_squared);
+        Self { x, y, z }
+    }
+
+    /// madd-2007-bl, adding an affine point.
+    fn add_affine(self, other: &AffinePoint) -> Self {
+        if self.z.is_zero() {
+            return Self {
+                x: other.x,
+                y: other.y,
+                z: FieldElement::one(),
+            };
+        }
+        let z1z1 = self.z.square();
+        let u2 = other.x.multiply(z1z1);
+        let s2 = other.y.multiply(self.z).multiply(z1z1);
+        let h = u2.subtract(self.x);
+        let r = s2.subtract(self.y).double();
+        if h.is_zero() {
+            return if r.is_zero() {
+                self.double()
+            } else {
+                Self::INFINITY
+            };
+        }
+        let hh = h.square();
+        let i = hh.double().double();
+        let j = h.multiply(i);
+        let v = self.x.multiply(i);
+        let x = r.square().subtract(j).subtract(v.double());
+        let y = r
+            .multiply(v.subtract(x))
+            .subtract(self.y.multiply(j).double());
+        let z = self.z.add(h).square().subtract(z1z1).subtract(hh);
+        Self { x, y, z }
+    }
+}
+
+fn multiply(scalar: [u8; P256_OCTETS], point: &AffinePoint) -> JacobianPoint {
+    let mut result = JacobianPoint::INFINITY;
+    for octet in scalar {
+        for bit in (0..8).rev() {
+            result = result.double();
+            if octet >> bit & 1 == 1 {
+                result = result.add_affine(point);
+            }
+        }
+    }
+    result
+}
+
+fn add_limbs(left: &Limbs, right: &Limbs) -> (Limbs, bool) {
+    let mut sum = [0u64; 4];
+    let mut carry = false;
+    for index in 0..4 {
+        let (partial, first) = left[index].overflowing_add(right[index]);
+        let (partial, second) = partial.overflowing_add(u64::from(carry));
+        sum[index] = partial;
+        carry = first || second;
+    }
+    (sum, carry)
+}
+
+fn subtract_limbs(left: &Limbs, right: &Limbs) -> (Limbs, bool) {
+    let mut difference = [0u64; 4];
+    let mut borrow = false;
+    for index in 0..4 {
+        let (partial, first) = left[index].overflowing_sub(right[index]);
+        let (partial, second) = partial.overflowing_sub(u64::from(borrow));
+        difference[index] = partial;
+        borrow = first || second;
+    }
+    (difference, borrow)
+}
+
+fn greater_or_equal(left: &Limbs, right: &Limbs) -> bool {
+    !subtract_limbs(left, right).1
+}
+
+/// CIOS Montgomery multiplication, `left * right / 2^256 mod p`. The lowest
+/// limb of p is all ones, so -p^-1 mod 2^64 is 1.
+fn montgomery_multiply(left: &Limbs, right: &Limbs) -> Limbs {
+    let mut accumulator = [0u64; 6];
+    for &multiplier in right {
+        let mut carry = 0u128;
+        for index in 0..4 {
+            let value = u128::from(accumulator[index])
+                + u128::from(left[index]) * u128::from(multiplier)
+                + carry;
+            accumulator[index] = value as u64;
+            carry = value >> 64;
+        }
+        let value = u128::from(accumulator[4]) + carry;
+        accumulator[4] = value as u64;
+        accumulator[5] = (value >> 64) as u64;
+
+        let reduction = accumulator[0];
+        let mut carry = 0u128;
+        for index in 0..4 {
+            let value = u128::from(accumulator[index])
+                + u128::from(reduction) * u128::from(PRIME[index])
+                + carry;
+            accumulator[index] = value as u64;
+            carry = value >> 64;
+        }
+        let value = u128::from(accumulator[4]) + carry;
+        accumulator[4] = value as u64;
+        accumulator[5] += (value >> 64) as u64;
+        accumulator = [
+            accumulator[1],
+            accumulator[2],
+            accumulator[3],
+            accumulator[4],
+            accumulator[5],
+            0,
+        ];
+    }
+    let result = [
+        accumulator[0],
+        accumulator[1],
+        accumulator[2],
+        accumulator[3],
+    ];
+    if accumulator[4] != 0 || greater_or_equal(&result, &PRIME) {
+        subtract_limbs(&result, &PRIME).0
+    } else {
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(hex: &str) -> [u8; P256_OCTETS] {
+        std::array::from_fn(|index| u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16).unwrap())
+    }
+
+    #[test]
+    fn derives_the_secure_connections_debug_public_key() {
+        assert_eq!(
+            p256_public_key(hex(
+                "3f49f6d4a3c55f3874c9b3e3d2103f504aff607beb40b7995899b8a6cd3c1abd"
+            )),
+            Some((
+                hex("20b003d2f297be2c5e2c83a7e9f9a5b9eff49111acf4fddbcc0301480e359de6"),
+                hex("dc809c49652aeb6d63329abf5a52155c766345c28fed3024741c8ed01589d28b"),
+            ))
+        );
+    }
+
+    #[test]
+    fn agrees_on_the_rfc_5903_shared_secret() {
+        let initiator = hex("c88f01f510d9ac3f70a292daa2316de544e9aab8afe84049c62a9c57862d1433");
+        let responder = hex("c6ef9c5d78ae012a011164acb397ce2088685d8f06bf9be0b283ab46476bee53");
+        let (responder_x, responder_y) = p256_public_key(responder).unwrap();
+        assert_eq!(
+            responder_x,
+            hex("d12dfb5289c8d4f81208b70270398c342296970a0bccb74c736fc7554494bf63")
+        );
+        let shared = p256_diffie_hellman(initiator, responder_x, responder_y).unwrap();
+        assert_eq!(
+            shared,
+            hex("d6840f6b42f6edafd13116e0e12565202fef8e9ece7dce03812464d04b9442de")
+        );
+        let (initiator_x, initiator_y) = p256_public_key(initiator).unwrap();
+        assert_eq!(
+            p256_diffie_hellman(responder, initiator_x, initiator_y),
+            Some(shared)
+        );
+    }
+
+    #[test]
+    fn rejects_public_keys_off_the_curve() {
+        let (x, mut y) = p256_public_key(hex(
+            "3f49f6d4a3c55f3874c9b3e3d2103f504aff607beb40b7995899b8a6cd3c1abd",
+        ))
+        .unwrap();
+        y[31] ^= 1;
+        assert_eq!(p256_diffie_hellman([1; 32], x, y), None);
+    }
+}
@@ -1,4 +1,6 @@
-use crate::ble::{AdvertisingPdu, BleChannel, LeFrameConfig, LePdu, decode_le_frames};
+use crate::ble::{
+    AdvertisingPdu, BleChannel, LeCrcFailurePolicy, LeFrameConfig, LePdu, scan_le_frames,
+};
 use crate::complex::Complex32;
 use crate::{Error, Result};
 use std::fmt::{Display, Formatter};
@@ -88,15 +90,27 @@ pub struct SampleDiscontinuity {
 #[derive(Clone, Debug, Default)]
 pub struct StreamDecodeBatch {
     pub packets: Vec<ReceivedAdvertisingPdu>,
+    /// CRC-failed frames, reported only under [`LeCrcFailurePolicy::Emit`].
+    pub crc_failures: Vec<ReceivedLePdu>,
     pub discontinuity: Option<SampleDiscontinuity>,
 }
 
 #[derive(Clone, Debug, Default)]
 pub struct LeStreamDecodeBatch {
     pub packets: Vec<ReceivedLePdu>,
+    /// CRC-failed frames, reported only under [`LeCrcFailurePolicy::Emit`].
+    pub crc_failures: Vec<ReceivedLePdu>,
     pub discontinuity: Option<SampleDiscontinuity>,
 }
 
+/// CRC-valid observations and, when requested, CRC-failed frames from one
+/// block of samples.
+#[derive(Clone, Debug, Default)]
+pub struct LeUncodedScan {
+    pub packets: Vec<ReceivedLePdu>,
+    pub crc_failures: Vec<ReceivedLePdu>,
+}
+
 impl Le1mDemodConfig {
     pub fn validate(self) -> Result<usize> {
         LeUncodedDemodConfig::from(self).validate()
@@ -183,6 +197,20 @@ fn robust_threshold(symbols: &[f32]) -> Option<SliceLevels> {
     }
 }
 
+/// Bit errors make CRC-failed copies of one frame differ between symbol
+/// phases and buffers, so they are matched by position alone.
+fn same_le_frame_position(
+    left: &ReceivedLePdu,
+    right: &ReceivedLePdu,
+    samples_per_symbol: u64,
+) -> bool {
+    left.access_address_sample
+        .abs_diff(right.access_address_sample)
+        <= samples_per_symbol
+        && left.phy == right.phy
+        && left.pdu.access_address == right.pdu.access_address
+}
+
 fn same_le_observation(
     left: &ReceivedLePdu,
     right: &ReceivedLePdu,
@@ -248,14 +276,37 @@ pub fn decode_le_uncoded_detailed(
     frame_config: LeFrameConfig,
     config: LeUncodedDemodConfig,
 ) -> Result<Vec<ReceivedLePdu>> {
+    Ok(scan_le_uncoded(
+        samples,
+        channel,
+        frame_config,
+        config,
+        LeCrcFailurePolicy::Discard,
+    )?
+    .packets)
+}
+
+/// [`decode_le_uncoded_detailed`] with opt-in reporting of CRC-failed frames.
+///
+/// A CRC-failed frame seen at several symbol phases is reported once, at the
+/// phase with the widest discriminator separation, and never when a CRC-valid
+/// packet was recovered at the same position.
+pub fn scan_le_uncoded(
+    samples: &[Complex32],
+    channel: BleChannel,
+    frame_config: LeFrameConfig,
+    config: LeUncodedDemodConfig,
+    crc_failure_policy: LeCrcFailurePolicy,
+) -> Result<LeUncodedScan> {
     let samples_per_symbol = config.validate()?;
     frame_config.validate()?;
     if samples.len() < samples_per_symbol * 10 {
-        return Ok(Vec::new());
+        return Ok(LeUncodedScan::default());
     }
 
     let discriminator = quadrature_discriminator(samples);
     let mut packets = Vec::new();
+    let mut crc_failures: Vec<ReceivedLePdu> = Vec::new();
 
     for phase in 0..samples_per_symbol {
         if phase >= discriminator.len() {
@@ -269,17 +320,19 @@ pub fn decode_le_uncoded_detailed(
             .iter()
             .map(|value| *value >= levels.threshold)
             .collect();
-        for packet in decode_le_frames(
+        let scan = scan_le_frames(
             &bits,
             channel,
             frame_config,
             config.max_access_address_errors,
-        )? {
+            crc_failure_policy,
+        )?;
+        let observe = |packet: LePdu| {
             let packet_bits = packet.frame_bit_length();
             let packet_levels =
                 packet_slice_levels(&symbols, &bits, packet.bit_offset, packet_bits)
                     .unwrap_or(levels);
-            let observation = ReceivedLePdu {
+            ReceivedLePdu {
                 phy: config.phy,
                 access_address_sample: (phase + 1 + packet.bit_offset * samples_per_symbol) as u64,
                 symbol_phase: phase,
@@ -290,7 +343,10 @@ pub fn decode_le_uncoded_detailed(
                     / (2.0 * std::f32::consts::TAU),
                 discriminator_separation: packet_levels.high - packet_levels.low,
                 pdu: packet,
-            };
+            }
+        };
+        for packet in scan.packets {
+            let observation = observe(packet);
             if let Some(existing) = packets.iter_mut().find(|existing| {
                 same_le_observation(existing, &observation, samples_per_symbol as u64)
             }) {
@@ -301,9 +357,30 @@ pub fn decode_le_uncoded_detailed(
                 packets.push(observation);
             }
         }
+        for packet in scan.crc_failures {
+            let observation = observe(packet);
+            if let Some(existing) = crc_failures.iter_mut().find(|existing| {
+                same_le_frame_position(existing, &observation, samples_per_symbol as u64)
+            }) {
+                if observation.discriminator_separation > existing.discriminator_separation {
+                    *existing = observation;
+                }
+            } else {
+                crc_failures.push(observation);
+            }
+        }
     }
+    crc_failures.retain(|failure| {
+        !packets
+            .iter()
+            .any(|packet| same_le_frame_position(packet, failure, samples_per_symbol as u64))
+    });
     packets.sort_unstable_by_key(|packet| packet.access_address_sample);
-    Ok(packets)
+    crc_failures.sort_unstable_by_key(|packet| packet.access_address_sample);
+    Ok(LeUncodedScan {
+        packets,
+        crc_failures,
+    })
 }
 
 /// Compatibility wrapper for detailed CRC-valid LE 1M observations.
@@ -422,11 +499,13 @@ pub struct LeUncodedPacketStreamDecoder {
     channel: BleChannel,
     frame_config: LeFrameConfig,
     config: LeUncodedDemodConfig,
+    crc_failure_policy: LeCrcFailurePolicy,
     samples_per_symbol: usize,
     samples: Vec<Complex32>,
     buffer_first_sample: Option<u64>,
     expected_next_sample: Option<u64>,
     recent_packets: Vec<ReceivedLePdu>,
+    recent_crc_failures: Vec<ReceivedLePdu>,
     maximum_buffer_samples: usize,
 }
 
@@ -435,6 +514,17 @@ impl LeUncodedPacketStreamDecoder {
         channel: BleChannel,
         frame_config: LeFrameConfig,
         config: LeUncodedDemodConfig,
+    ) -> Result<Self> {
+        Self::with_crc_failure_policy(channel, frame_config, config, LeCrcFailurePolicy::Discard)
+    }
+
+    /// Creates a decoder that also reports CRC-failed frames in
+    /// [`LeStreamDecodeBatch::crc_failures`] under [`LeCrcFailurePolicy::Emit`].
+    pub fn with_crc_failure_policy(
+        channel: BleChannel,
+        frame_config: LeFrameConfig,
+        config: LeUncodedDemodConfig,
+        crc_failure_policy: LeCrcFailurePolicy,
     ) -> Result<Self> {
         let samples_per_symbol = config.validate()?;
         frame_config.validate()?;
@@ -445,11 +535,13 @@ impl LeUncodedPacketStreamDecoder {
             channel,
             frame_config,
             config,
+            crc_failure_policy,
             samples_per_symbol,
             samples: Vec::with_capacity(maximum_buffer_samples),
             buffer_first_sample: None,
             expected_next_sample: None,
             recent_packets: Vec::new(),
+            recent_crc_failures: Vec::new(),
             maximum_buffer_samples,
         })
     }
@@ -459,6 +551,7 @@ impl LeUncodedPacketStreamDecoder {
         self.buffer_first_sample = None;
         self.expected_next_sample = None;
         self.recent_packets.clear();
+        self.recent_crc_failures.clear();
     }
 
     pub fn push(
@@ -480,6 +573,7 @@ impl LeUncodedPacketStreamDecoder {
             });
             self.samples.clear();
             self.recent_packets.clear();
+            self.recent_crc_failures.clear();
             self.buffer_first_sample = None;
         }
 
@@ -495,7 +589,7 @@ impl LeUncodedPacketStreamDecoder {
                 self.buffer_first_sample = Some(chunk_first);
             }
             self.samples.extend_from_slice(chunk);
-            self.decode_buffer(&mut batch.packets)?;
+            self.decode_buffer(&mut batch)?;
             self.trim_buffer();
             consumed += chunk.len();
         }
@@ -503,24 +597,48 @@ impl LeUncodedPacketStreamDecoder {
         Ok(batch)
     }
 
-    fn decode_buffer(&mut self, output: &mut Vec<ReceivedLePdu>) -> Result<()> {
+    fn decode_buffer(&mut self, batch: &mut LeStreamDecodeBatch) -> Result<()> {
         let buffer_first = self.buffer_first_sample.ok_or_else(|| {
             Error::InvalidInput("stream decoder lost its buffer sample index".to_owned())
         })?;
-        for mut observation in
-            decode_le_uncoded_detailed(&self.samples, self.channel, self.frame_config, self.config)?
-        {
+        let scan = scan_le_uncoded(
+            &self.samples,
+            self.channel,
+            self.frame_config,
+            self.config,
+            self.crc_failure_policy,
+        )?;
+        let samples_per_symbol = self.samples_per_symbol as u64;
+        for mut observation in scan.packets {
             observation.access_address_sample = observation
                 .access_address_sample
                 .checked_add(buffer_first)
                 .ok_or_else(|| Error::InvalidInput("sample index overflow".to_owned()))?;
-            if self.recent_packets.iter().any(|existing| {
-                same_le_observation(existing, &observation, self.samples_per_symbol as u64)
-            }) {
+            if self
+                .recent_packets
+                .iter()
+                .any(|existing| same_le_observation(existing, &observation, samples_per_symbol))
+            {
                 continue;
             }
             self.recent_packets.push(observation.clone());
-            output.push(observation);
+            batch.packets.push(observation);
+        }
+        for mut observation in scan.crc_failures {
+            observation.access_address_sample = observation
+                .access_address_sample
+                .checked_add(buffer_first)
+                .ok_or_else(|| Error::InvalidInput("sample index overflow".to_owned()))?;
+            if self
+                .recent_packets
+                .iter()
+                .chain(&self.recent_crc_failures)
+                .any(|existing| same_le_frame_position(existing, &observation, samples_per_symbol))
+            {
+                continue;
+            }
+            self.recent_crc_failures.push(observation.clone());
+            batch.crc_failures.push(observation);
         }
         Ok(())
     }
@@ -534,12 +652,15 @@ impl LeUncodedPacketStreamDecoder {
         if let Some(first) = &mut self.buffer_first_sample {
             *first += remove as u64;
             let retained_from = *first;
-            self.recent_packets.retain(|packet| {
+            let maximum_buffer_samples = self.maximum_buffer_samples as u64;
+            let retained = |packet: &ReceivedLePdu| {
                 packet
                     .access_address_sample
-                    .saturating_add(self.maximum_buffer_samples as u64)
+                    .saturating_add(maximum_buffer_samples)
                     >= retained_from
-            });
+            };
+            self.recent_packets.retain(retained);
+            self.recent_crc_failures.retain(retained);
         }
     }
 }
@@ -554,9 +675,23 @@ impl Le1mPacketStreamDecoder {
         channel: BleChannel,
         frame_config: LeFrameConfig,
         config: Le1mDemodConfig,
+    ) -> Result<Self> {
+        Self::with_crc_failure_policy(channel, frame_config, config, LeCrcFailurePolicy::Discard)
+    }
+
+    pub fn with_crc_failure_policy(
+        channel: BleChannel,
+        frame_config: LeFrameConfig,
+        config: Le1mDemodConfig,
+        crc_failure_policy: LeCrcFailurePolicy,
     ) -> Result<Self> {
         Ok(Self {
-            inner: LeUncodedPacketStreamDecoder::new(channel, frame_config, config.into())?,
+            inner: LeUncodedPacketStreamDecoder::with_crc_failure_policy(
+                channel,
+                frame_config,
+                config.into(),
+                crc_failure_policy,
+            )?,
         })
     }
 
@@ -584,6 +719,16 @@ pub struct Le1mStreamDecoder {
 
 impl Le1mStreamDecoder {
     pub fn new(channel: BleChannel, config: Le1mDemodConfig) -> Result<Self> {
+        Self::with_crc_failure_policy(channel, config, LeCrcFailurePolicy::Discard)
+    }
+
+    /// CRC-failed frames are reported as raw [`ReceivedLePdu`] values, since
+    /// their advertising header cannot be trusted.
+    pub fn with_crc_failure_policy(
+        channel: BleChannel,
+        config: Le1mDemodConfig,
+        crc_failure_policy: LeCrcFailurePolicy,
+    ) -> Result<Self> {
         if !channel.is_primary_advertising() {
             return Err(Error::InvalidConfiguration(format!(
                 "LE 1M stream decoder requires channel 37, 38, or 39; got {}",
@@ -591,7 +736,12 @@ impl Le1mStreamDecoder {
             )));
         }
         Ok(Self {
-            inner: Le1mPacketStreamDecoder::new(channel, LeFrameConfig::advertising(), config)?,
+            inner: Le1mPacketStreamDecoder::with_crc_failure_policy(
+                channel,
+                LeFrameConfig::advertising(),
+                config,
+                crc_failure_policy,
+            )?,
         })
     }
 
@@ -611,6 +761,7 @@ impl Le1mStreamDecoder {
                 .into_iter()
                 .map(received_advertising_pdu)
                 .collect::<Result<Vec<_>>>()?,
+            crc_failures: batch.crc_failures,
             discontinuity: batch.discontinuity,
         })
     }
@@ -632,6 +783,22 @@ impl LePeriodicAdvertisingStreamDecoder {
         access_address: u32,
         crc_init: u32,
         config: LeUncodedDemodConfig,
+    ) -> Result<Self> {
+        Self::with_crc_failure_policy(
+            channel,
+            access_address,
+            crc_init,
+            config,
+            LeCrcFailurePolicy::Discard,
+        )
+    }
+
+    pub fn with_crc_failure_policy(
+        channel: BleChannel,
+        access_address: u32,
+        crc_init: u32,
+        config: LeUncodedDemodConfig,
+        crc_failure_policy: LeCrcFailurePolicy,
     ) -> Result<Self> {
         if channel.is_primary_advertising() {
             return Err(Error::InvalidConfiguration(format!(
@@ -640,10 +807,11 @@ impl LePeriodicAdvertisingStreamDecoder {
             )));
         }
         Ok(Self {
-            inner: LeUncodedPacketStreamDecoder::new(
+            inner: LeUncodedPacketStreamDecoder::with_crc_failure_policy(
                 channel,
                 LeFrameConfig::periodic_advertising(access_address, crc_init)?,
                 config,
+                crc_failure_policy,
             )?,
         })
     }
@@ -664,6 +832,7 @@ impl LePeriodicAdvertisingStreamDecoder {
                 .into_iter()
                 .map(received_advertising_layout_pdu)
                 .collect::<Result<Vec<_>>>()?,
+            crc_failures: batch.crc_failures,
             discontinuity: batch.discontinuity,
         })
     }
@@ -674,6 +843,14 @@ impl LeSecondaryAdvertisingStreamDecoder {
         channel: BleChannel,
         config: LeUncodedDemodConfig,
     ) -> Result<LeSecondaryAdvertisingStreamDecoder> {
+        Self::with_crc_failure_policy(channel, config, LeCrcFailurePolicy::Discard)
+    }
+
+    pub fn with_crc_failure_policy(
+        channel: BleChannel,
+        config: LeUncodedDemodConfig,
+        crc_failure_policy: LeCrcFailurePolicy,
+    ) -> Result<Self> {
         if channel.is_primary_advertising() {
             return Err(Error::InvalidConfiguration(format!(
                 "secondary advertising stream decoder requires channel 0 through 36; got {}",
@@ -681,10 +858,11 @@ impl LeSecondaryAdvertisingStreamDecoder {
             )));
         }
         Ok(Self {
-            inner: LeUncodedPacketStreamDecoder::new(
+            inner: LeUncodedPacketStreamDecoder::with_crc_failure_policy(
                 channel,
                 LeFrameConfig::secondary_advertising(),
                 config,
+                crc_failure_policy,
             )?,
         })
     }
@@ -705,6 +883,7 @@ impl LeSecondaryAdvertisingStreamDecoder {
                 .into_iter()
                 .map(received_advertising_pdu)
                 .collect::<Result<Vec<_>>>()?,
+            crc_failures: batch.crc_failures,
             discontinuity: batch.discontinuity,
         })
     }
@@ -941,6 +1120,62 @@ mod tests {
         assert!((packets[0].estimated_carrier_offset_hz - 40_000.0).abs() < 10_000.0);
     }
 
+    #[test]
+    fn stream_decoder_reports_crc_failures_once_and_only_on_request() {
+        let channel = BleChannel::new(5).unwrap();
+        let frame_config = LeFrameConfig::data(0x1234_5678, 0x00ab_cdef).unwrap();
+        let mut bits = Vec::new();
+        for (payload, corrupt) in [([0x11u8; 20], true), ([0x22; 20], false)] {
+            let mut pdu = vec![0x02, payload.len() as u8];
+            pdu.extend_from_slice(&payload);
+            pdu.extend_from_slice(&crc24_bytes(&pdu, frame_config.crc_init));
+            if corrupt {
+                pdu[10] ^= 0x01;
+            }
+            let mut body = bytes_to_bits_lsb(&pdu);
+            whiten_bits(&mut body, channel);
+            bits.extend(bytes_to_bits_lsb(&[0xaa]));
+            bits.extend(bytes_to_bits_lsb(
+                &frame_config.access_address.to_le_bytes(),
+            ));
+            bits.extend(body);
+            bits.extend([false, true].repeat(40));
+        }
+        let samples = modulate(&bits, 4, 20_000.0);
+        let config = LeUncodedDemodConfig {
+            phy: LeUncodedPhy::Le1M,
+            sample_rate_hz: 4_000_000,
+            max_access_address_errors: 0,
+        };
+
+        for policy in [LeCrcFailurePolicy::Discard, LeCrcFailurePolicy::emit()] {
+            let mut decoder = LeUncodedPacketStreamDecoder::with_crc_failure_policy(
+                channel,
+                frame_config,
+                config,
+                policy,
+            )
+            .unwrap();
+            let mut packets = Vec::new();
+            let mut crc_failures = Vec::new();
+            for (index, chunk) in samples.chunks(101).enumerate() {
+                let batch = decoder.push((index * 101) as u64, chunk).unwrap();
+                packets.extend(batch.packets);
+                crc_failures.extend(batch.crc_failures);
+            }
+            assert_eq!(packets.len(), 1);
+            assert_eq!(packets[0].pdu.payload, [0x22; 20]);
+            if policy == LeCrcFailurePolicy::Discard {
+                assert!(crc_failures.is_empty());
+            } else {
+                assert_eq!(crc_failures.len(), 1);
+                assert_eq!(crc_failures[0].pdu.header, [0x02, 20]);
+                assert_eq!(crc_failures[0].pdu.payload[8], 0x10);
+                assert!(crc_failures[0].access_address_sample < packets[0].access_address_sample);
+            }
+        }
+    }
+
     #[test]
     fn generic_stream_decoder_recovers_maximum_data_pdu_with_cte_info() {
         let channel = BleChannel::new(26).unwrap();
@@ -1,7 +1,7 @@
 use crate::complex::Complex32;
 use crate::{Error, Result};
 use std::fs::File;
-use std::io::{BufReader, Read};
+use std::io::{BufReader, Read, Write};
 use std::path::Path;
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -21,7 +21,14 @@ impl IqFormat {
         }
     }
 
-    const fn bytes_per_sample(self) -> usize {
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::F32Le => "f32le",
+            Self::S16Le => "s16le",
+        }
+    }
+
+    pub const fn bytes_per_sample(self) -> usize {
         match self {
             Self::F32Le => 8,
             Self::S16Le => 4,
@@ -100,6 +107,13 @@ pub fn open_iq_file(
     format: IqFormat,
 ) -> Result<(IqReader<BufReader<File>>, usize)> {
     let file = File::open(path.as_ref())?;
+    let sample_count = iq_file_sample_count(&file, format)?;
+    Ok((IqReader::new(BufReader::new(file), format), sample_count))
+}
+
+/// Returns the number of complete samples in a regular I/Q file, rejecting
+/// lengths that end inside a sample.
+pub fn iq_file_sample_count(file: &File, format: IqFormat) -> Result<usize> {
     let length_u64 = file.metadata()?.len();
     let length = usize::try_from(length_u64).map_err(|_| {
         Error::InvalidInput(format!(
@@ -112,10 +126,7 @@ pub fn open_iq_file(
             "I/Q file length {length} is not divisible by the {bytes_per_sample}-byte sample size"
         )));
     }
-    Ok((
-        IqReader::new(BufReader::new(file), format),
-        length / bytes_per_sample,
-    ))
+    Ok(length / bytes_per_sample)
 }
 
 pub fn read_iq_file(
@@ -138,7 +149,36 @@ pub fn read_iq_file(
     reader.read_block(sample_count.max(1))
 }
 
-fn parse_iq_bytes(bytes: &[u8], format: IqFormat) -> Result<Vec<Complex32>> {
+/// Writes interleaved little-endian samples. Signed 16-bit output is scaled by
+/// `i16::MAX`, rounded, and saturated.
+pub fn write_iq_samples<W: Write>(
+    writer: &mut W,
+    samples: &[Complex32],
+    format: IqFormat,
+) -> Result<()> {
+    let mut bytes = Vec::with_capacity(samples.len() * format.bytes_per_sample());
+    for sample in samples {
+        match format {
+            IqFormat::F32Le => {
+                bytes.extend_from_slice(&sample.re.to_le_bytes());
+                bytes.extend_from_slice(&sample.im.to_le_bytes());
+            }
+            IqFormat::S16Le => {
+                for value in [sample.re, sample.im] {
+                    let scaled = (value * i16::MAX as f32)
+                        .round()
+                        .clamp(i16::MIN as f32, i16::MAX as f32)
+                        as i16;
+                    bytes.extend_from_slice(&scaled.to_le_bytes());
+                }
+            }
+        }
+    }
+    writer.write_all(&bytes)?;
+    Ok(())
+}
+
+pub(crate) fn parse_iq_bytes(bytes: &[u8], format: IqFormat) -> Result<Vec<Complex32>> {
     let mut samples = Vec::with_capacity(bytes.len() / format.bytes_per_sample());
     match format {
         IqFormat::F32Le => {
@@ -232,4 +272,23 @@ mod tests {
         assert_eq!(reader.next_sample_index(), 3);
         assert!(reader.read_block(2).unwrap().is_empty());
     }
+
+    #[test]
+    fn written_samples_read_back_in_both_formats() {
+        let samples = [
+            Complex32::new(0.5, -0.25),
+            Complex32::new(1.5, -1.5),
+            Complex32::new(0.0, 1.0),
+        ];
+        let mut bytes = Vec::new();
+        write_iq_samples(&mut bytes, &samples, IqFormat::F32Le).unwrap();
+        assert_eq!(parse_iq_bytes(&bytes, IqFormat::F32Le).unwrap(), samples);
+
+        let mut bytes = Vec::new();
+        write_iq_samples(&mut bytes, &samples, IqFormat::S16Le).unwrap();
+        assert_eq!(&bytes[4..8], &[0xff, 0x7f, 0x00, 0x80]);
+        let read = parse_iq_bytes(&bytes, IqFormat::S16Le).unwrap();
+        assert!((read[0].re - 0.5).abs() < 1e-4 && (read[0].im + 0.25).abs() < 1e-4);
+        assert_eq!(read[2], Complex32::new(0.0, 1.0));
+    }
 }
@@ -0,0 +1,529 @@
+//! Minimal in-tree JSON support: a reader for recording metadata and a writer
+//! for the CLI's JSON Lines output.
+
+use crate::{Error, Result};
+use std::fmt::{Display, Formatter, Write};
+
+const MAXIMUM_DEPTH: usize = 64;
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    pub(crate) fn parse(text: &str) -> Result<Self> {
+        let mut parser = Parser {
+            bytes: text.as_bytes(),
+            offset: 0,
+        };
+        let value = parser.value(0)?;
+        parser.skip_whitespace();
+        if parser.offset != parser.bytes.len() {
+            return Err(parser.error("trailing characters after JSON value"));
+        }
+        Ok(value)
+    }
+
+    /// Returns the first member named `key` when this value is an object.
+    pub(crate) fn get(&self, key: &str) -> Option<&Self> {
+        match self {
+            Self::Object(members) => members
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Returns a number that is an exact non-negative integer below 2^53.
+    pub(crate) fn as_u64(&self) -> Option<u64> {
+        let value = self.as_f64()?;
+        (value >= 0.0 && value.fract() == 0.0 && value < 9_007_199_254_740_992.0)
+            .then_some(value as u64)
+    }
+
+    pub(crate) fn as_array(&self) -> Option<&[Self]> {
+        match self {
+            Self::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+/// A JSON value built for output.
+///
+/// Integers keep their exact value instead of passing through `f64`, so
+/// 64-bit sample indices and counters survive serialization. Non-finite floats
+/// serialize as `null`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Unsigned(u64),
+    Signed(i64),
+    Float(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(JsonObject),
+}
+
+impl Json {
+    /// Lowercase hexadecimal string for an octet sequence, in wire order.
+    pub fn hex(bytes: &[u8]) -> Self {
+        let mut text = String::with_capacity(bytes.len() * 2);
+        for byte in bytes {
+            let _ = write!(text, "{byte:02x}");
+        }
+        Self::String(text)
+    }
+
+    /// String from any `Display` value, for enums whose text form is their
+    /// documented name.
+    pub fn display(value: impl Display) -> Self {
+        Self::String(value.to_string())
+    }
+}
+
+/// JSON object members in insertion order. Keys are not deduplicated.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct JsonObject {
+    members: Vec<(String, Json)>,
+}
+
+impl JsonObject {
+    pub const fn new() -> Self {
+        Self {
+            members: Vec::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn field(mut self, key: &str, value: impl Into<Json>) -> Self {
+        self.push(key, value);
+        self
+    }
+
+    pub fn push(&mut self, key: &str, value: impl Into<Json>) {
+        self.members.push((key.to_owned(), value.into()));
+    }
+
+    /// Appends every member of `other` after the existing ones.
+    #[must_use]
+    pub fn extend(mut self, other: Self) -> Self {
+        self.members.extend(other.members);
+        self
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        self.members
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value)
+    }
+
+    pub fn members(&self) -> &[(String, Json)] {
+        &self.members
+    }
+}
+
+impl From<JsonObject> for Json {
+    fn from(value: JsonObject) -> Self {
+        Self::Object(value)
+    }
+}
+
+impl From<bool> for Json {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+macro_rules! json_from_integer {
+    ($variant:ident, $wide:ty, $($type:ty),+) => {
+        $(impl From<$type> for Json {
+            fn from(value: $type) -> Self {
+                Self::$variant(<$wide>::from(value))
+            }
+        })+
+    };
+}
+
+json_from_integer!(Unsigned, u64, u8, u16, u32, u64);
+json_from_integer!(Signed, i64, i8, i16, i32, i64);
+
+impl From<usize> for Json {
+    fn from(value: usize) -> Self {
+        Self::Unsigned(value as u64)
+    }
+}
+
+impl From<f32> for Json {
+    /// Goes through the shortest decimal form of the `f32`, so `0.1f32`
+    /// serializes as `0.1` rather than its widened binary value.
+    fn from(value: f32) -> Self {
+        Self::Float(value.to_string().parse().unwrap_or(f64::NAN))
+    }
+}
+
+impl From<f64> for Json {
+    fn from(value: f64) -> Self {
+        Self::Float(value)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_owned())
+    }
+}
+
+impl From<String> for Json {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Self::Null, Into::into)
+    }
+}
+
+impl<T: Into<Json>> From<Vec<T>> for Json {
+    fn from(value: Vec<T>) -> Self {
+        Self::Array(value.into_iter().map(Into::into).collect())
+    }
+}
+
+/// Compact serialization with no whitespace, suitable for one JSON Lines
+/// record per `Display`.
+impl Display for Json {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Null => formatter.write_str("null"),
+            Self::Bool(value) => write!(formatter, "{value}"),
+            Self::Unsigned(value) => write!(formatter, "{value}"),
+            Self::Signed(value) => write!(formatter, "{value}"),
+            Self::Float(value) if value.is_finite() => write!(formatter, "{value}"),
+            Self::Float(_) => formatter.write_str("null"),
+            Self::String(value) => write_json_string(formatter, value),
+            Self::Array(values) => {
+                formatter.write_char('[')?;
+                for (index, value) in values.iter().enumerate() {
+                    if index != 0 {
+                        formatter.write_char(',')?;
+                    }
+                    write!(formatter, "{value}")?;
+                }
+                formatter.write_char(']')
+            }
+            Self::Object(object) => {
+                formatter.write_char('{')?;
+                for (index, (key, value)) in object.members.iter().enumerate() {
+                    if index != 0 {
+                        formatter.write_char(',')?;
+                    }
+                    write_json_string(formatter, key)?;
+                    write!(formatter, ":{value}")?;
+                }
+                formatter.write_char('}')
+            }
+        }
+    }
+}
+
+fn write_json_string(formatter: &mut Formatter<'_>, value: &str) -> std::fmt::Result {
+    formatter.write_char('"')?;
+    for character in value.chars() {
+        match character {
+            '"' => formatter.write_str("\\\"")?,
+            '\\' => formatter.write_str("\\\\")?,
+            '\n' => formatter.write_str("\\n")?,
+            '\r' => formatter.write_str("\\r")?,
+            '\t' => formatter.write_str("\\t")?,
+            '\u{08}' => formatter.write_str("\\b")?,
+            '\u{0c}' => formatter.write_str("\\f")?,
+            character if u32::from(character) < 0x20 => {
+                write!(formatter, "\\u{:04x}", u32::from(character))?;
+            }
+            character => formatter.write_char(character)?,
+        }
+    }
+    formatter.write_char('"')
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> Error {
+        Error::InvalidInput(format!("JSON {message} at octet {}", self.offset))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self
+            .bytes
+            .get(self.offset)
+            .is_some_and(|byte| matches!(byte, b' ' | b'\t' | b'\n' | b'\r'))
+        {
+            self.offset += 1;
+        }
+    }
+
+    fn expect(&mut self, literal: &str) -> Result<()> {
+        if self.bytes[self.offset..].starts_with(literal.as_bytes()) {
+            self.offset += literal.len();
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected {literal:?}")))
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<JsonValue> {
+        if depth > MAXIMUM_DEPTH {
+            return Err(self.error("nesting exceeds 64 levels"));
+        }
+        self.skip_whitespace();
+        match self.bytes.get(self.offset) {
+            Some(b'n') => self.expect("null").map(|()| JsonValue::Null),
+            Some(b't') => self.expect("true").map(|()| JsonValue::Bool(true)),
+            Some(b'f') => self.expect("false").map(|()| JsonValue::Bool(false)),
+            Some(b'"') => self.string().map(JsonValue::String),
+            Some(b'[') => {
+                self.offset += 1;
+                let mut values = Vec::new();
+                self.skip_whitespace();
+                if self.bytes.get(self.offset) == Some(&b']') {
+                    self.offset += 1;
+                    return Ok(JsonValue::Array(values));
+                }
+                loop {
+                    values.push(self.value(depth + 1)?);
+                    self.skip_whitespace();
+                    match self.bytes.get(self.offset) {
+                        Some(b',') => self.offset += 1,
+                        Some(b']') => {
+                            self.offset += 1;
+                            return Ok(JsonValue::Array(values));
+                        }
+                        _ => return Err(self.error("expected ',' or ']'")),
+                    }
+                }
+            }
+            Some(b'{') => {
+                self.offset += 1;
+                let mut members = Vec::new();
+                self.skip_whitespace();
+                if self.bytes.get(self.offset) == Some(&b'}') {
+                    self.offset += 1;
+                    return Ok(JsonValue::Object(members));
+                }
+                loop {
+                    self.skip_whitespace();
+                    if self.bytes.get(self.offset) != Some(&b'"') {
+                        return Err(self.error("expected object member name"));
+                    }
+                    let name = self.string()?;
+                    self.skip_whitespace();
+                    self.expect(":")?;
+                    members.push((name, self.value(depth + 1)?));
+                    self.skip_whitespace();
+                    match self.bytes.get(self.offset) {
+                        Some(b',') => self.offset += 1,
+                        Some(b'}') => {
+                            self.offset += 1;
+                            return Ok(JsonValue::Object(members));
+                        }
+                        _ => return Err(self.error("expected ',' or '}'")),
+                    }
+                }
+            }
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn number(&mut self) -> Result<JsonValue> {
+        let start = self.offset;
+        while self
+            .bytes
+            .get(self.offset)
+            .is_some_and(|byte| matches!(byte, b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9'))
+        {
+            self.offset += 1;
+        }
+        let text = std::str::from_utf8(&self.bytes[start..self.offset])
+            .map_err(|_| self.error("invalid number"))?;
+        let value: f64 = text.parse().map_err(|_| self.error("invalid number"))?;
+        if !value.is_finite() {
+            return Err(self.error("number is not finite"));
+        }
+        Ok(JsonValue::Number(value))
+    }
+
+    fn hex_escape(&mut self) -> Result<u32> {
+        let digits = self
+            .bytes
+            .get(self.offset..self.offset + 4)
+            .ok_or_else(|| self.error("truncated unicode escape"))?;
+        let text = std::str::from_utf8(digits).map_err(|_| self.error("invalid unicode escape"))?;
+        let value =
+            u32::from_str_radix(text, 16).map_err(|_| self.error("invalid unicode escape"))?;
+        self.offset += 4;
+        Ok(value)
+    }
+
+    fn string(&mut self) -> Result<String> {
+        self.offset += 1;
+        let mut output = Vec::new();
+        loop {
+            let byte = *self
+                .bytes
+                .get(self.offset)
+                .ok_or_else(|| self.error("unterminated string"))?;
+            self.offset += 1;
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let escape = *self
+                        .bytes
+                        .get(self.offset)
+                        .ok_or_else(|| self.error("unterminated escape"))?;
+                    self.offset += 1;
+                    let character = match escape {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let high = self.hex_escape()?;
+                            let code = if (0xd800..0xdc00).contains(&high) {
+                                self.expect("\\u")?;
+                                let low = self.hex_escape()?;
+                                if !(0xdc00..0xe000).contains(&low) {
+                                    return Err(self.error("unpaired surrogate escape"));
+                                }
+                                0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+                            } else {
+                                high
+                            };
+                            char::from_u32(code)
+                                .ok_or_else(|| self.error("invalid unicode escape"))?
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    let mut encoded = [0u8; 4];
+                    output.extend_from_slice(character.encode_utf8(&mut encoded).as_bytes());
+                }
+                0x00..=0x1f => return Err(self.error("control character in string")),
+                _ => output.push(byte),
+            }
+        }
+        String::from_utf8(output).map_err(|_| self.error("string is not UTF-8"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_exact_integers_escaped_strings_and_null_non_finite_floats() {
+        let value = Json::from(
+            JsonObject::new()
+                .field("sample", u64::MAX)
+                .field("delta_db", -3i8)
+                .field("offset_hz", 0.1f32)
+                .field("nan", f64::NAN)
+                .field("text", "a\"b\\c\n\u{01}é")
+                .field("bytes", Json::hex(&[0x00, 0xab]))
+                .field("list", vec![1u8, 2])
+                .field("missing", None::<u8>)
+                .field("empty", JsonObject::new()),
+        );
+        let text = value.to_string();
+        assert_eq!(
+            text,
+            r#"{"sample":18446744073709551615,"delta_db":-3,"offset_hz":0.1,"nan":null,"text":"a\"b\\c\n\u0001é","bytes":"00ab","list":[1,2],"missing":null,"empty":{}}"#
+        );
+        let parsed = JsonValue::parse(&text).unwrap();
+        assert_eq!(
+            parsed.get("text").unwrap().as_str(),
+            Some("a\"b\\c\n\u{01}é")
+        );
+        assert_eq!(parsed.get("offset_hz").unwrap().as_f64(), Some(0.1));
+    }
+
+    #[test]
+    fn parses_nested_values_and_escapes() {
+        let value = JsonValue::parse(
+            r#"{"global":{"core:sample_rate":4e6,"core:datatype":"cf32_le"},
+                "captures":[{"core:frequency":2402000000}],"note":"\u00e9\ud83d\ude00\n","ok":true,"none":null}"#,
+        )
+        .unwrap();
+        let global = value.get("global").unwrap();
+        assert_eq!(
+            global.get("core:sample_rate").unwrap().as_u64(),
+            Some(4_000_000)
+        );
+        assert_eq!(
+            global.get("core:datatype").unwrap().as_str(),
+            Some("cf32_le")
+        );
+        let captures = value.get("captures").unwrap().as_array().unwrap();
+        assert_eq!(
+            captures[0].get("core:frequency").unwrap().as_u64(),
+            Some(2_402_000_000)
+        );
+        assert_eq!(value.get("note").unwrap().as_str(), Some("é😀\n"));
+        assert_eq!(value.get("ok"), Some(&JsonValue::Bool(true)));
+        assert_eq!(value.get("none"), Some(&JsonValue::Null));
+    }
+
+    #[test]
+    fn rejects_malformed_documents() {
+        for text in [
+            "",
+            "{",
+            "[1,]",
+            "{\"a\" 1}",
+            "\"unterminated",
+            "\"\\ud800\"",
+            "1 2",
+            "nul",
+            "1e999",
+        ] {
+            assert!(JsonValue::parse(text).is_err(), "{text:?}");
+        }
+        let deep = "[".repeat(100) + &"]".repeat(100);
+        assert!(JsonValue::parse(&deep).is_err());
+        assert_eq!(JsonValue::Number(1.5).as_u64(), None);
+    }
+}
@@ -0,0 +1,1342 @@
+//! Bonding keys gathered from host-side sources.
+//!
+//! [`LeKeyring`] holds LTKs keyed by EDIV/Rand and device address, IRKs and
+//! CSRKs keyed by identity address, BR/EDR link keys, and Encrypted Advertising
+//! Data key material. Importers read them from btsnoop
+//! HCI logs, where the host hands LTKs to its controller, and from BlueZ
+//! `/var/lib/bluetooth/<adapter>/<device>/info` files. Every key uses the
+//! octet order of HCI and SMP key fields, as `--ltk` does, except EAD key
+//! material, which keeps the order of its GATT characteristic value.
+//!
+//! [`LeKeyring::load`] and [`LeKeyring::save`] keep a keyring in a text file
+//! with one entry per line, such as
+//! `ltk address=C6:55:44:33:22:11/random ediv=9332 rand=9078563412efcdab
+//! key=...`. EDIV is decimal and Rand is hexadecimal in LL_ENC_REQ order.
+//! [`crate::ll_control::LeEncryptionSessionTracker::with_keyring`] selects
+//! an LTK from the keyring when it observes an LL_ENC_REQ.
+
+use crate::advertising::{AddressKind, DeviceAddress};
+use crate::btsnoop::{BtSnoopReader, HciPacketType};
+use crate::encrypted_data::EncryptedDataKeyMaterial;
+use crate::ll_control::EncryptionRequest;
+use crate::pairing::{link_key_from_long_term_key, long_term_key_from_link_key};
+use crate::{Error, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+
+const HCI_LINK_KEY_REQUEST_REPLY: u16 = 0x040b;
+const HCI_LE_ENABLE_ENCRYPTION: u16 = 0x2019;
+const HCI_LE_LONG_TERM_KEY_REQUEST_REPLY: u16 = 0x201a;
+const HCI_LE_ADD_DEVICE_TO_RESOLVING_LIST: u16 = 0x2027;
+const HCI_DISCONNECTION_COMPLETE: u8 = 0x05;
+const HCI_LINK_KEY_NOTIFICATION: u8 = 0x18;
+const HCI_LE_META: u8 = 0x3e;
+const HCI_LE_CONNECTION_COMPLETE: u8 = 0x01;
+const HCI_LE_LONG_TERM_KEY_REQUEST: u8 = 0x05;
+const HCI_LE_ENHANCED_CONNECTION_COMPLETE: u8 = 0x0a;
+const HCI_LE_ENHANCED_CONNECTION_COMPLETE_V2: u8 = 0x29;
+/// BlueZ groups holding an LTK. `SlaveLongTermKey` is the name used before
+/// BlueZ 5.57.
+const BLUEZ_LONG_TERM_KEY_GROUPS: [&str; 3] =
+    ["LongTermKey", "PeripheralLongTermKey", "SlaveLongTermKey"];
+/// First line of a keyring file; the number is the format version.
+const KEYRING_FILE_HEADER: &str = "# blueoxide keyring 1";
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub struct LongTermKeyEntry {
+    /// Peer device, when the source names it.
+    pub address: Option<(DeviceAddress, AddressKind)>,
+    /// EDIV and Rand as carried by LL_ENC_REQ; both are zero for LE Secure
+    /// Connections keys.
+    pub encrypted_diversifier: u16,
+    pub random_number: [u8; 8],
+    pub key: [u8; 16],
+}
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub struct IdentityResolvingKeyEntry {
+    pub address: (DeviceAddress, AddressKind),
+    pub key: [u8; 16],
+}
+
+/// A peer's CSRK, which verifies the signatures of its ATT Signed Write
+/// Commands.
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub struct SignatureKeyEntry {
+    pub address: (DeviceAddress, AddressKind),
+    pub key: [u8; 16],
+}
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub struct LinkKeyEntry {
+    pub address: DeviceAddress,
+    pub key: [u8; 16],
+}
+
+macro_rules! redacted_debug {
+    ($name:ident, $($field:ident),*) => {
+        impl std::fmt::Debug for $name {
+            fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                formatter
+                    .debug_struct(stringify!($name))
+                    $(.field(stringify!($field), &self.$field))*
+                    .field("key", &"<redacted>")
+                    .finish()
+            }
+        }
+    };
+}
+
+redacted_debug!(
+    LongTermKeyEntry,
+    address,
+    encrypted_diversifier,
+    random_number
+);
+redacted_debug!(IdentityResolvingKeyEntry, address);
+redacted_debug!(SignatureKeyEntry, address);
+redacted_debug!(LinkKeyEntry, address);
+
+/// Key material of a device's Encrypted Advertising Data.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct EncryptedDataKeyEntry {
+    /// The advertiser, when known. Advertisers using EAD usually rotate
+    /// private addresses, so this is their identity address.
+    pub address: Option<(DeviceAddress, AddressKind)>,
+    pub key_material: EncryptedDataKeyMaterial,
+}
+
+/// Keys added by one import; duplicates of keys already held are not counted.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct KeyImport {
+    pub long_term_keys: usize,
+    pub identity_resolving_keys: usize,
+    pub signature_keys: usize,
+    pub link_keys: usize,
+    pub encrypted_data_keys: usize,
+}
+
+impl KeyImport {
+    fn add(&mut self, other: Self) {
+        self.long_term_keys += other.long_term_keys;
+        self.identity_resolving_keys += other.identity_resolving_keys;
+        self.signature_keys += other.signature_keys;
+        self.link_keys += other.link_keys;
+        self.encrypted_data_keys += other.encrypted_data_keys;
+    }
+}
+
+/// The kinds of entry a keyring holds, as named in keyring files.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum KeyringEntryKind {
+    LongTermKey,
+    IdentityResolvingKey,
+    SignatureKey,
+    LinkKey,
+    EncryptedDataKey,
+}
+
+impl KeyringEntryKind {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "ltk" => Ok(Self::LongTermKey),
+            "irk" => Ok(Self::IdentityResolvingKey),
+            "csrk" => Ok(Self::SignatureKey),
+            "link-key" => Ok(Self::LinkKey),
+            "ead-key" => Ok(Self::EncryptedDataKey),
+            _ => Err(Error::InvalidInput(format!(
+                "unknown keyring entry type {value:?}; expected ltk, irk, csrk, link-key, or ead-key"
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for KeyringEntryKind {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        formatter.write_str(match self {
+            Self::LongTermKey => "ltk",
+            Self::IdentityResolvingKey => "irk",
+            Self::SignatureKey => "csrk",
+            Self::LinkKey => "link-key",
+            Self::EncryptedDataKey => "ead-key",
+        })
+    }
+}
+
+#[derive(Clone, Default, Eq, PartialEq)]
+pub struct LeKeyring {
+    long_term_keys: Vec<LongTermKeyEntry>,
+    identity_resolving_keys: Vec<IdentityResolvingKeyEntry>,
+    signature_keys: Vec<SignatureKeyEntry>,
+    link_keys: Vec<LinkKeyEntry>,
+    encrypted_data_keys: Vec<EncryptedDataKeyEntry>,
+}
+
+impl std::fmt::Debug for LeKeyring {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        formatter
+            .debug_struct("LeKeyring")
+            .field("long_term_keys", &self.long_term_keys.len())
+            .field(
+                "identity_resolving_keys",
+                &self.identity_resolving_keys.len(),
+            )
+            .field("signature_keys", &self.signature_keys.len())
+            .field("link_keys", &self.link_keys.len())
+            .field("encrypted_data_keys", &self.encrypted_data_keys.len())
+            .finish()
+    }
+}
+
+impl LeKeyring {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.long_term_keys.is_empty()
+            && self.identity_resolving_keys.is_empty()
+            && self.signature_keys.is_empty()
+            && self.link_keys.is_empty()
+            && self.encrypted_data_keys.is_empty()
+    }
+
+    /// Reads a keyring file written by [`Self::save`].
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        Self::parse_file(&contents).map_err(|error| with_path(path, error))
+    }
+
+    /// Writes the keyring to `path` through a temporary file in the same
+    /// directory, so an interrupted write leaves the previous file intact.
+    /// On Unix the file is readable by its owner only.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        let temporary = Path::new(&temporary);
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(temporary)?;
+        file.write_all(self.file_contents().as_bytes())?;
+        file.sync_all()?;
+        fs::rename(temporary, path)?;
+        Ok(())
+    }
+
+    fn file_contents(&self) -> String {
+        let mut contents = format!("{KEYRING_FILE_HEADER}\n");
+        for entry in &self.long_term_keys {
+            let address = entry
+                .address
+                .map_or_else(|| "none".to_owned(), file_address);
+            contents.push_str(&format!(
+                "ltk address={address} ediv={} rand={} key={}\n",
+                entry.encrypted_diversifier,
+                hex(&entry.random_number),
+                hex(&entry.key)
+            ));
+        }
+        for entry in &self.identity_resolving_keys {
+            contents.push_str(&format!(
+                "irk address={} key={}\n",
+                file_address(entry.address),
+                hex(&entry.key)
+            ));
+        }
+        for entry in &self.signature_keys {
+            contents.push_str(&format!(
+                "csrk address={} key={}\n",
+                file_address(entry.address),
+                hex(&entry.key)
+            ));
+        }
+        for entry in &self.link_keys {
+            // BR/EDR addresses are always public.
+            contents.push_str(&format!(
+                "link-key address={} key={}\n",
+                file_address((entry.address, AddressKind::Public)),
+                hex(&entry.key)
+            ));
+        }
+        for entry in &self.encrypted_data_keys {
+            let address = entry
+                .address
+                .map_or_else(|| "none".to_owned(), file_address);
+            contents.push_str(&format!(
+                "ead-key address={address} key={} iv={}\n",
+                hex(&entry.key_material.session_key),
+                hex(&entry.key_material.initialization_vector)
+            ));
+        }
+        contents
+    }
+
+    fn parse_file(contents: &str) -> Result<Self> {
+        let mut lines = contents.lines().enumerate();
+        if lines.next().map(|(_, line)| line.trim()) != Some(KEYRING_FILE_HEADER) {
+            return Err(Error::InvalidInput(format!(
+                "keyring file must begin with {KEYRING_FILE_HEADER:?}"
+            )));
+        }
+        let mut keyring = Self::new();
+        for (index, line) in lines {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            keyring.parse_file_line(line).map_err(|error| match error {
+                Error::InvalidInput(message) => {
+                    Error::InvalidInput(format!("line {}: {message}", index + 1))
+                }
+                error => error,
+            })?;
+        }
+        Ok(keyring)
+    }
+
+    fn parse_file_line(&mut self, line: &str) -> Result<()> {
+        let mut words = line.split_whitespace();
+        let kind = KeyringEntryKind::parse(words.next().unwrap_or_default())?;
+        let mut fields = HashMap::new();
+        for word in words {
+            let (name, value) = word.split_once('=').ok_or_else(|| {
+                Error::InvalidInput(format!("expected NAME=VALUE, received {word:?}"))
+            })?;
+            fields.insert(name, value);
+        }
+        let field = |name: &str| {
+            fields
+                .get(name)
+                .copied()
+                .ok_or_else(|| Error::InvalidInput(format!("{kind} entry requires {name}=")))
+        };
+        let address = field("address")?;
+        let key = parse_hex(field("key")?, "key")?;
+        let optional_address = || match address {
+            "none" => Ok(None),
+            address => parse_file_address(address).map(Some),
+        };
+        match kind {
+            KeyringEntryKind::LongTermKey => {
+                let address = optional_address()?;
+                let encrypted_diversifier = field("ediv")?.parse().map_err(|_| {
+                    Error::InvalidInput("ediv must be a decimal number in 0..=65535".to_owned())
+                })?;
+                self.add_long_term_key(LongTermKeyEntry {
+                    address,
+                    encrypted_diversifier,
+                    random_number: parse_hex(field("rand")?, "rand")?,
+                    key,
+                });
+            }
+            KeyringEntryKind::IdentityResolvingKey => {
+                self.add_identity_resolving_key(IdentityResolvingKeyEntry {
+                    address: parse_file_address(address)?,
+                    key,
+                });
+            }
+            KeyringEntryKind::SignatureKey => {
+                self.add_signature_key(SignatureKeyEntry {
+                    address: parse_file_address(address)?,
+                    key,
+                });
+            }
+            KeyringEntryKind::LinkKey => {
+                self.add_link_key(LinkKeyEntry {
+                    address: parse_file_address(address)?.0,
+                    key,
+                });
+            }
+            KeyringEntryKind::EncryptedDataKey => {
+                self.add_encrypted_data_key(EncryptedDataKeyEntry {
+                    address: optional_address()?,
+                    key_material: EncryptedDataKeyMaterial {
+                        session_key: key,
+                        initialization_vector: parse_hex(field("iv")?, "iv")?,
+                    },
+                });
+            }
+        }
+        Ok(())
+    }
+
+    pub fn long_term_keys(&self) -> &[LongTermKeyEntry] {
+        &self.long_term_keys
+    }
+
+    pub fn identity_resolving_keys(&self) -> &[IdentityResolvingKeyEntry] {
+        &self.identity_resolving_keys
+    }
+
+    pub fn signature_keys(&self) -> &[SignatureKeyEntry] {
+        &self.signature_keys
+    }
+
+    pub fn link_keys(&self) -> &[LinkKeyEntry] {
+        &self.link_keys
+    }
+
+    pub fn encrypted_data_keys(&self) -> &[EncryptedDataKeyEntry] {
+        &self.encrypted_data_keys
+    }
+
+    /// Adds an LTK and returns false when an identical entry is already held.
+    /// An entry with an address replaces an otherwise identical one without.
+    pub fn add_long_term_key(&mut self, entry: LongTermKeyEntry) -> bool {
+        let same_key = |existing: &LongTermKeyEntry| {
+            existing.key == entry.key
+                && existing.encrypted_diversifier == entry.encrypted_diversifier
+                && existing.random_number == entry.random_number
+        };
+        if let Some(existing) = self
+            .long_term_keys
+            .iter_mut()
+            .find(|existing| same_key(existing))
+        {
+            if existing.address.is_none() && entry.address.is_some() {
+                existing.address = entry.address;
+            }
+            return false;
+        }
+        self.long_term_keys.push(entry);
+        true
+    }
+
+    pub fn add_identity_resolving_key(&mut self, entry: IdentityResolvingKeyEntry) -> bool {
+        if self.identity_resolving_keys.contains(&entry) {
+            return false;
+        }
+        self.identity_resolving_keys.push(entry);
+        true
+    }
+
+    pub fn add_signature_key(&mut self, entry: SignatureKeyEntry) -> bool {
+        if self.signature_keys.contains(&entry) {
+            return false;
+        }
+        self.signature_keys.push(entry);
+        true
+    }
+
+    pub fn add_link_key(&mut self, entry: LinkKeyEntry) -> bool {
+        if self.link_keys.contains(&entry) {
+            return false;
+        }
+        self.link_keys.push(entry);
+        true
+    }
+
+    /// Adds EAD key material and returns false when it is already held. As
+    /// for LTKs, an entry with an address replaces one without.
+    pub fn add_encrypted_data_key(&mut self, entry: EncryptedDataKeyEntry) -> bool {
+        if let Some(existing) = self
+            .encrypted_data_keys
+            .iter_mut()
+            .find(|existing| existing.key_material == entry.key_material)
+        {
+            if existing.address.is_none() && entry.address.is_some() {
+                existing.address = entry.address;
+            }
+            return false;
+        }
+        self.encrypted_data_keys.push(entry);
+        true
+    }
+
+    /// Adds a Secure Connections LTK of `address`, used with EDIV 0 and Rand 0,
+    /// and the BR/EDR link key cross-transport key derivation gives it. `ct2`
+    /// is set when both devices set CT2 during pairing.
+    pub fn add_long_term_key_with_link_key(
+        &mut self,
+        address: (DeviceAddress, AddressKind),
+        long_term_key: [u8; 16],
+        ct2: bool,
+    ) -> KeyImport {
+        KeyImport {
+            long_term_keys: usize::from(self.add_long_term_key(LongTermKeyEntry {
+                address: Some(address),
+                encrypted_diversifier: 0,
+                random_number: [0; 8],
+                key: long_term_key,
+            })),
+            link_keys: usize::from(self.add_link_key(LinkKeyEntry {
+                address: address.0,
+                key: link_key_from_long_term_key(long_term_key, ct2),
+            })),
+            ..KeyImport::default()
+        }
+    }
+
+    /// Adds a BR/EDR Secure Connections link key of `address` and the LE LTK
+    /// cross-transport key derivation gives it.
+    pub fn add_link_key_with_long_term_key(
+        &mut self,
+        address: (DeviceAddress, AddressKind),
+        link_key: [u8; 16],
+        ct2: bool,
+    ) -> KeyImport {
+        KeyImport {
+            long_term_keys: usize::from(self.add_long_term_key(LongTermKeyEntry {
+                address: Some(address),
+                encrypted_diversifier: 0,
+                random_number: [0; 8],
+                key: long_term_key_from_link_key(link_key, ct2),
+            })),
+            link_keys: usize::from(self.add_link_key(LinkKeyEntry {
+                address: address.0,
+                key: link_key,
+            })),
+            ..KeyImport::default()
+        }
+    }
+
+    /// Adds every entry of `other` that this keyring does not already hold.
+    pub fn merge(&mut self, other: &Self) -> KeyImport {
+        let mut imported = KeyImport::default();
+        for entry in &other.long_term_keys {
+            imported.long_term_keys += usize::from(self.add_long_term_key(*entry));
+        }
+        for entry in &other.identity_resolving_keys {
+            imported.identity_resolving_keys +=
+                usize::from(self.add_identity_resolving_key(*entry));
+        }
+        for entry in &other.signature_keys {
+            imported.signature_keys += usize::from(self.add_signature_key(*entry));
+        }
+        for entry in &other.link_keys {
+            imported.link_keys += usize::from(self.add_link_key(*entry));
+        }
+        for entry in &other.encrypted_data_keys {
+            imported.encrypted_data_keys += usize::from(self.add_encrypted_data_key(*entry));
+        }
+        imported
+    }
+
+    /// Removes the entries stored for `address`, of one kind or of every
+    /// kind, and returns how many were removed.
+    pub fn remove_device(
+        &mut self,
+        address: DeviceAddress,
+        kind: Option<KeyringEntryKind>,
+    ) -> usize {
+        let selected = |candidate| kind.is_none_or(|kind| kind == candidate);
+        let before = self.entry_count();
+        if selected(KeyringEntryKind::LongTermKey) {
+            self.long_term_keys
+                .retain(|entry| entry.address.is_none_or(|(stored, _)| stored != address));
+        }
+        if selected(KeyringEntryKind::IdentityResolvingKey) {
+            self.identity_resolving_keys
+                .retain(|entry| entry.address.0 != address);
+        }
+        if selected(KeyringEntryKind::SignatureKey) {
+            self.signature_keys
+                .retain(|entry| entry.address.0 != address);
+        }
+        if selected(KeyringEntryKind::LinkKey) {
+            self.link_keys.retain(|entry| entry.address != address);
+        }
+        if selected(KeyringEntryKind::EncryptedDataKey) {
+            self.encrypted_data_keys
+                .retain(|entry| entry.address.is_none_or(|(stored, _)| stored != address));
+        }
+        before - self.entry_count()
+    }
+
+    fn entry_count(&self) -> usize {
+        self.long_term_keys.len()
+            + self.identity_resolving_keys.len()
+            + self.signature_keys.len()
+            + self.link_keys.len()
+            + self.encrypted_data_keys.len()
+    }
+
+    /// Returns the LTKs whose EDIV and Rand match an LL_ENC_REQ, most
+    /// recently added first. When any of them belongs to one of `peers`, only
+    /// those are returned, so LE Secure Connections keys, which all carry
+    /// zero EDIV and Rand, are told apart by the connection's addresses.
+    pub fn long_term_keys_for(
+        &self,
+        request: &EncryptionRequest,
+        peers: &[DeviceAddress],
+    ) -> Vec<[u8; 16]> {
+        let matching = self.long_term_keys.iter().rev().filter(|entry| {
+            entry.encrypted_diversifier == request.encrypted_diversifier
+                && entry.random_number == request.random_number
+        });
+        let addressed = matching
+            .clone()
+            .filter(|entry| {
+                entry
+                    .address
+                    .is_some_and(|(address, _)| peers.contains(&address))
+            })
+            .map(|entry| entry.key)
+            .collect::<Vec<_>>();
+        if addressed.is_empty() {
+            matching.map(|entry| entry.key).collect()
+        } else {
+            addressed
+        }
+    }
+
+    /// Imports the keys a host passed to its controller in a btsnoop log:
+    /// `HCI_LE_Enable_Encryption`, `HCI_LE_Long_Term_Key_Request_Reply` with
+    /// the EDIV/Rand of the preceding LE Long Term Key Request event,
+    /// `HCI_LE_Add_Device_To_Resolving_List`, and BR/EDR link keys from
+    /// `HCI_Link_Key_Request_Reply` and Link Key Notification. LTKs take the
+    /// peer address of their connection handle from the LE connection
+    /// complete events.
+    pub fn import_btsnoop(&mut self, reader: impl Read) -> Result<KeyImport> {
+        let mut reader = BtSnoopReader::new(reader)?;
+        let mut imported = KeyImport::default();
+        let mut peers = HashMap::<u16, (DeviceAddress, AddressKind)>::new();
+        let mut requests = HashMap::<u16, (u16, [u8; 8])>::new();
+        while let Some(record) = reader.next_record()? {
+            let packet = record.packet.as_slice();
+            match record.packet_type {
+                HciPacketType::Command if packet.len() >= 3 => {
+                    let opcode = u16::from_le_bytes([packet[0], packet[1]]);
+                    let parameters = &packet[3..];
+                    match opcode {
+                        HCI_LE_ENABLE_ENCRYPTION if parameters.len() >= 28 => {
+                            let handle = connection_handle(parameters);
+                            if self.add_long_term_key(LongTermKeyEntry {
+                                address: peers.get(&handle).copied(),
+                                encrypted_diversifier: u16::from_le_bytes([
+                                    parameters[10],
+                                    parameters[11],
+                                ]),
+                                random_number: array(&parameters[2..10]),
+                                key: array(&parameters[12..28]),
+                            }) {
+                                imported.long_term_keys += 1;
+                            }
+                        }
+                        HCI_LE_LONG_TERM_KEY_REQUEST_REPLY if parameters.len() >= 18 => {
+                            let handle = connection_handle(parameters);
+                            let (encrypted_diversifier, random_number) =
+                                requests.get(&handle).copied().unwrap_or_default();
+                            if self.add_long_term_key(LongTermKeyEntry {
+                                address: peers.get(&handle).copied(),
+                                encrypted_diversifier,
+                                random_number,
+                                key: array(&parameters[2..18]),
+                            }) {
+                                imported.long_term_keys += 1;
+                            }
+                        }
+                        HCI_LE_ADD_DEVICE_TO_RESOLVING_LIST if parameters.len() >= 23 => {
+                            let key = array(&parameters[7..23]);
+                            if key != [0; 16]
+                                && self.add_identity_resolving_key(IdentityResolvingKeyEntry {
+                                    address: (
+                                        DeviceAddress(array(&parameters[1..7])),
+                                        hci_address_kind(parameters[0]),
+                                    ),
+                                    key,
+                                })
+                            {
+                                imported.identity_resolving_keys += 1;
+                            }
+                        }
+                        HCI_LINK_KEY_REQUEST_REPLY if parameters.len() >= 22 => {
+                            imported.link_keys += usize::from(self.add_link_key(LinkKeyEntry {
+                                address: DeviceAddress(array(&parameters[..6])),
+                                key: array(&parameters[6..22]),
+                            }));
+                        }
+                        _ => {}
+                    }
+                }
+                HciPacketType::Event if packet.len() >= 2 => {
+                    let parameters = &packet[2..];
+                    match packet[0] {
+                        HCI_DISCONNECTION_COMPLETE if parameters.len() >= 3 => {
+                            let handle = connection_handle(&parameters[1..]);
+                            peers.remove(&handle);
+                            requests.remove(&handle);
+                        }
+                        HCI_LINK_KEY_NOTIFICATION if parameters.len() >= 22 => {
+                            imported.link_keys += usize::from(self.add_link_key(LinkKeyEntry {
+                                address: DeviceAddress(array(&parameters[..6])),
+                                key: array(&parameters[6..22]),
+                            }));
+                        }
+                        HCI_LE_META if !parameters.is_empty() => {
+                            let subevent = &parameters[1..];
+                            match parameters[0] {
+                                HCI_LE_CONNECTION_COMPLETE
+                                | HCI_LE_ENHANCED_CONNECTION_COMPLETE
+                                | HCI_LE_ENHANCED_CONNECTION_COMPLETE_V2
+                                    if subevent.len() >= 11 && subevent[0] == 0 =>
+                                {
+                                    peers.insert(
+                                        connection_handle(&subevent[1..]),
+                                        (
+                                            DeviceAddress(array(&subevent[5..11])),
+                                            hci_address_kind(subevent[4]),
+                                        ),
+                                    );
+                                }
+                                HCI_LE_LONG_TERM_KEY_REQUEST if subevent.len() >= 12 => {
+                                    requests.insert(
+                                        connection_handle(subevent),
+                                        (
+                                            u16::from_le_bytes([subevent[10], subevent[11]]),
+                                            array(&subevent[2..10]),
+                                        ),
+                                    );
+                                }
+                                _ => {}
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(imported)
+    }
+
+    /// Imports one BlueZ device `info` file. `address` is the device
+    /// directory's name; the file's `[General] AddressType` supplies its kind.
+    pub fn import_bluez_info(&mut self, address: DeviceAddress, info: &str) -> Result<KeyImport> {
+        let groups = parse_key_file(info);
+        let value = |group: &str, key: &str| {
+            groups
+                .get(group)
+                .and_then(|entries| entries.get(key))
+                .map(String::as_str)
+        };
+        let kind = match value("General", "AddressType") {
+            Some("static" | "random") => AddressKind::Random,
+            _ => AddressKind::Public,
+        };
+        let mut imported = KeyImport::default();
+        for group in BLUEZ_LONG_TERM_KEY_GROUPS {
+            let Some(key) = value(group, "Key") else {
+                continue;
+            };
+            let encrypted_diversifier = value(group, "EDiv")
+                .map(|ediv| bluez_number(group, "EDiv", ediv))
+                .transpose()?
+                .unwrap_or(0);
+            let random_number: u64 = value(group, "Rand")
+                .map(|rand| bluez_number(group, "Rand", rand))
+                .transpose()?
+                .unwrap_or(0);
+            if self.add_long_term_key(LongTermKeyEntry {
+                address: Some((address, kind)),
+                encrypted_diversifier,
+                random_number: random_number.to_le_bytes(),
+                key: bluez_key(group, key)?,
+            }) {
+                imported.long_term_keys += 1;
+            }
+        }
+        if let Some(key) = value("IdentityResolvingKey", "Key")
+            && self.add_identity_resolving_key(IdentityResolvingKeyEntry {
+                address: (address, kind),
+                key: bluez_key("IdentityResolvingKey", key)?,
+            })
+        {
+            imported.identity_resolving_keys += 1;
+        }
+        if let Some(key) = value("RemoteSignatureKey", "Key")
+            && self.add_signature_key(SignatureKeyEntry {
+                address: (address, kind),
+                key: bluez_key("RemoteSignatureKey", key)?,
+            })
+        {
+            imported.signature_keys += 1;
+        }
+        if let Some(key) = value("LinkKey", "Key")
+            && self.add_link_key(LinkKeyEntry {
+                address,
+                key: bluez_key("LinkKey", key)?,
+            })
+        {
+            imported.link_keys += 1;
+        }
+        Ok(imported)
+    }
+
+    /// Imports every device under a BlueZ storage path: `/var/lib/bluetooth`,
+    /// one adapter directory, one device directory, or a device's `info`
+    /// file. Directories not named by a device address are skipped.
+    pub fn import_bluez(&mut self, path: &Path) -> Result<KeyImport> {
+        if path.is_file() {
+            let address = path
+                .parent()
+                .and_then(Path::file_name)
+                .and_then(|name| name.to_str())
+                .and_then(parse_bluez_address)
+                .ok_or_else(|| {
+                    Error::InvalidInput(format!(
+                        "BlueZ info file {} is not inside a device address directory",
+                        path.display()
+                    ))
+                })?;
+            let info = fs::read_to_string(path)?;
+            return self
+                .import_bluez_info(address, &info)
+                .map_err(|error| with_path(path, error));
+        }
+        let mut imported = KeyImport::default();
+        let info = path.join("info");
+        if info.is_file()
+            && path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(parse_bluez_address)
+                .is_some()
+        {
+            return self.import_bluez(&info);
+        }
+        let mut entries = fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        entries.sort();
+        for entry in entries {
+            let addressed = entry
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(parse_bluez_address)
+                .is_some();
+            if addressed && entry.is_dir() {
+                imported.add(self.import_bluez(&entry)?);
+            }
+        }
+        Ok(imported)
+    }
+}
+
+fn connection_handle(parameters: &[u8]) -> u16 {
+    u16::from_le_bytes([parameters[0], parameters[1]]) & 0x0fff
+}
+
+fn array<const N: usize>(bytes: &[u8]) -> [u8; N] {
+    bytes[..N].try_into().expect("length checked by the caller")
+}
+
+/// Maps an HCI address type, including the resolved identity types, to its
+/// address kind.
+fn hci_address_kind(address_type: u8) -> AddressKind {
+    if address_type & 0x01 == 0 {
+        AddressKind::Public
+    } else {
+        AddressKind::Random
+    }
+}
+
+/// Parses a GLib key file into groups of `key=value` entries. Comments and
+/// lines outside a group are ignored.
+fn parse_key_file(contents: &str) -> HashMap<String, HashMap<String, String>> {
+    let mut groups = HashMap::<String, HashMap<String, String>>::new();
+    let mut group = None;
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            group = Some(name.to_owned());
+            continue;
+        }
+        if let (Some(group), Some((key, value))) = (&group, line.split_once('=')) {
+            groups
+                .entry(group.clone())
+                .or_default()
+                .insert(key.trim().to_owned(), value.trim().to_owned());
+        }
+    }
+    groups
+}
+
+/// BlueZ writes a key's octets in HCI order as 32 hexadecimal digits.
+fn bluez_key(group: &str, value: &str) -> Result<[u8; 16]> {
+    parse_hex(value, &format!("BlueZ [{group}] Key"))
+}
+
+fn bluez_number<T: std::str::FromStr>(group: &str, key: &str, value: &str) -> Result<T> {
+    value.parse().map_err(|_| {
+        Error::InvalidInput(format!(
+            "BlueZ [{group}] {key} must be a decimal number, received {value:?}"
+        ))
+    })
+}
+
+/// Parses a BlueZ directory name such as `C6:55:44:33:22:11`, which shows the
+/// most significant octet first.
+fn parse_bluez_address(name: &str) -> Option<DeviceAddress> {
+    let mut address = [0u8; 6];
+    let mut octets = name.split(':');
+    for octet in address.iter_mut().rev() {
+        let digits = octets.next()?;
+        if digits.len() != 2 {
+            return None;
+        }
+        *octet = u8::from_str_radix(digits, 16).ok()?;
+    }
+    octets.next().is_none().then_some(DeviceAddress(address))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn parse_hex<const N: usize>(value: &str, name: &str) -> Result<[u8; N]> {
+    let invalid = || {
+        Error::InvalidInput(format!(
+            "{name} must be {} hexadecimal digits, received {value:?}",
+            N * 2
+        ))
+    };
+    if value.len() != N * 2 || !value.is_ascii() {
+        return Err(invalid());
+    }
+    let mut bytes = [0u8; N];
+    for (octet, digits) in bytes.iter_mut().zip(value.as_bytes().chunks(2)) {
+        let digits = std::str::from_utf8(digits).map_err(|_| invalid())?;
+        *octet = u8::from_str_radix(digits, 16).map_err(|_| invalid())?;
+    }
+    Ok(bytes)
+}
+
+fn file_address((address, kind): (DeviceAddress, AddressKind)) -> String {
+    format!("{address}/{kind}")
+}
+
+fn parse_file_address(value: &str) -> Result<(DeviceAddress, AddressKind)> {
+    let invalid = || {
+        Error::InvalidInput(format!(
+            "address must be XX:XX:XX:XX:XX:XX/public or /random, received {value:?}"
+        ))
+    };
+    let (address, kind) = value.split_once('/').ok_or_else(invalid)?;
+    let kind = match kind {
+        "public" => AddressKind::Public,
+        "random" => AddressKind::Random,
+        _ => return Err(invalid()),
+    };
+    Ok((parse_bluez_address(address).ok_or_else(invalid)?, kind))
+}
+
+fn with_path(path: &Path, error: Error) -> Error {
+    match error {
+        Error::InvalidInput(message) => {
+            Error::InvalidInput(format!("{}: {message}", path.display()))
+        }
+        error => error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::btsnoop::{BtSnoopWriter, HciHost};
+
+    const PEER: DeviceAddress = DeviceAddress([0x11, 0x22, 0x33, 0x44, 0x55, 0xc6]);
+
+    fn request(encrypted_diversifier: u16, random_number: [u8; 8]) -> EncryptionRequest {
+        EncryptionRequest {
+            random_number,
+            encrypted_diversifier,
+            central_session_key_diversifier: [0; 8],
+            central_initialization_vector: [0; 4],
+        }
+    }
+
+    fn command(opcode: u16, parameters: &[u8]) -> Vec<u8> {
+        let mut packet = opcode.to_le_bytes().to_vec();
+        packet.push(parameters.len() as u8);
+        packet.extend_from_slice(parameters);
+        packet
+    }
+
+    fn event(code: u8, parameters: &[u8]) -> Vec<u8> {
+        let mut packet = vec![code, parameters.len() as u8];
+        packet.extend_from_slice(parameters);
+        packet
+    }
+
+    #[test]
+    fn imports_ltks_irks_and_link_keys_from_btsnoop_host_traffic() {
+        let ltk = core::array::from_fn::<u8, 16, _>(|index| index as u8);
+        let secure_ltk = [0xa5; 16];
+        let irk = [0x3c; 16];
+        let mut log = BtSnoopWriter::new(Vec::new(), HciHost::Central).unwrap();
+        let mut connection_complete = vec![HCI_LE_CONNECTION_COMPLETE, 0x00, 0x40, 0x00, 0x00];
+        connection_complete.push(0x01);
+        connection_complete.extend_from_slice(&PEER.0);
+        connection_complete.extend_from_slice(&[0x18, 0, 0, 0, 0x48, 0, 0]);
+        log.write_packet(
+            0,
+            true,
+            HciPacketType::Event,
+            &event(HCI_LE_META, &connection_complete),
+        )
+        .unwrap();
+        let mut enable = vec![0x40, 0x00];
+        enable.extend_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        enable.extend_from_slice(&0x1234u16.to_le_bytes());
+        enable.extend_from_slice(&ltk);
+        log.write_packet(
+            1,
+            false,
+            HciPacketType::Command,
+            &command(HCI_LE_ENABLE_ENCRYPTION, &enable),
+        )
+        .unwrap();
+        log.write_packet(
+            2,
+            true,
+            HciPacketType::Event,
+            &event(HCI_DISCONNECTION_COMPLETE, &[0x00, 0x40, 0x00, 0x13]),
+        )
+        .unwrap();
+        let mut ltk_request = vec![HCI_LE_LONG_TERM_KEY_REQUEST, 0x41, 0x00];
+        ltk_request.extend_from_slice(&[0; 10]);
+        log.write_packet(
+            3,
+            true,
+            HciPacketType::Event,
+            &event(HCI_LE_META, &ltk_request),
+        )
+        .unwrap();
+        let mut reply = vec![0x41, 0x00];
+        reply.extend_from_slice(&secure_ltk);
+        log.write_packet(
+            4,
+            false,
+            HciPacketType::Command,
+            &command(HCI_LE_LONG_TERM_KEY_REQUEST_REPLY, &reply),
+        )
+        .unwrap();
+        let mut resolving = vec![0x01];
+        resolving.extend_from_slice(&PEER.0);
+        resolving.extend_from_slice(&irk);
+        resolving.extend_from_slice(&[0; 16]);
+        log.write_packet(
+            5,
+            false,
+            HciPacketType::Command,
+            &command(HCI_LE_ADD_DEVICE_TO_RESOLVING_LIST, &resolving),
+        )
+        .unwrap();
+        let mut notification = vec![6, 5, 4, 3, 2, 1];
+        notification.extend_from_slice(&[0x77; 16]);
+        notification.push(0x04);
+        log.write_packet(
+            6,
+            true,
+            HciPacketType::Event,
+            &event(HCI_LINK_KEY_NOTIFICATION, &notification),
+        )
+        .unwrap();
+        let bytes = log.into_inner();
+
+        let mut keyring = LeKeyring::new();
+        assert_eq!(
+            keyring.import_btsnoop(bytes.as_slice()).unwrap(),
+            KeyImport {
+                long_term_keys: 2,
+                identity_resolving_keys: 1,
+                signature_keys: 0,
+                link_keys: 1,
+                encrypted_data_keys: 0,
+            }
+        );
+        assert_eq!(
+            keyring.long_term_keys(),
+            [
+                LongTermKeyEntry {
+                    address: Some((PEER, AddressKind::Random)),
+                    encrypted_diversifier: 0x1234,
+                    random_number: [1, 2, 3, 4, 5, 6, 7, 8],
+                    key: ltk,
+                },
+                LongTermKeyEntry {
+                    address: None,
+                    encrypted_diversifier: 0,
+                    random_number: [0; 8],
+                    key: secure_ltk,
+                },
+            ]
+        );
+        assert_eq!(
+            keyring.identity_resolving_keys(),
+            [IdentityResolvingKeyEntry {
+                address: (PEER, AddressKind::Random),
+                key: irk,
+            }]
+        );
+        assert_eq!(
+            keyring.link_keys()[0].address,
+            DeviceAddress([6, 5, 4, 3, 2, 1])
+        );
+        assert_eq!(
+            keyring.import_btsnoop(bytes.as_slice()).unwrap(),
+            KeyImport::default()
+        );
+        assert_eq!(
+            keyring.long_term_keys_for(&request(0x1234, [1, 2, 3, 4, 5, 6, 7, 8]), &[]),
+            [ltk]
+        );
+        assert!(
+            keyring
+                .long_term_keys_for(&request(0x1235, [1, 2, 3, 4, 5, 6, 7, 8]), &[])
+                .is_empty()
+        );
+        assert!(format!("{:?}", keyring.long_term_keys()[0]).contains("<redacted>"));
+    }
+
+    #[test]
+    fn imports_bluez_info_files_and_prefers_addressed_secure_connections_keys() {
+        let info = "\
+[General]
+Name=Sensor
+AddressType=static
+SupportedTechnologies=LE;
+
+[IdentityResolvingKey]
+Key=00112233445566778899AABBCCDDEEFF
+
+[RemoteSignatureKey]
+Key=5A5A5A5A5A5A5A5A5A5A5A5A5A5A5A5A
+Counter=3
+Authenticated=false
+
+[LongTermKey]
+Key=0F0E0D0C0B0A09080706050403020100
+Authenticated=2
+EncSize=16
+EDiv=0
+Rand=0
+
+[SlaveLongTermKey]
+Key=A0A1A2A3A4A5A6A7A8A9AAABACADAEAF
+EDiv=4660
+Rand=578437695752307201
+
+[LinkKey]
+Key=FFEEDDCCBBAA99887766554433221100
+Type=4
+";
+        let mut keyring = LeKeyring::new();
+        keyring.add_long_term_key(LongTermKeyEntry {
+            address: Some((DeviceAddress([1; 6]), AddressKind::Public)),
+            encrypted_diversifier: 0,
+            random_number: [0; 8],
+            key: [0xee; 16],
+        });
+        assert_eq!(
+            keyring.import_bluez_info(PEER, info).unwrap(),
+            KeyImport {
+                long_term_keys: 2,
+                identity_resolving_keys: 1,
+                signature_keys: 1,
+                link_keys: 1,
+                encrypted_data_keys: 0,
+            }
+        );
+        let secure = core::array::from_fn::<u8, 16, _>(|index| 15 - index as u8);
+        assert_eq!(
+            keyring.long_term_keys_for(&request(0, [0; 8]), &[PEER]),
+            [secure]
+        );
+        assert_eq!(
+            keyring.long_term_keys_for(&request(0, [0; 8]), &[]),
+            [secure, [0xee; 16]]
+        );
+        assert_eq!(
+            keyring.long_term_keys_for(&request(0x1234, [1, 2, 3, 4, 5, 6, 7, 8]), &[]),
+            [core::array::from_fn(|index| 0xa0 + index as u8)]
+        );
+        assert_eq!(
+            keyring.identity_resolving_keys()[0].address,
+            (PEER, AddressKind::Random)
+        );
+        assert_eq!(keyring.identity_resolving_keys()[0].key[..2], [0x00, 0x11]);
+
+        assert!(
+            LeKeyring::new()
+                .import_bluez_info(PEER, "[LongTermKey]\nKey=0011\n")
+                .is_err()
+        );
+        assert_eq!(parse_bluez_address("C6:55:44:33:22:11"), Some(PEER));
+        assert_eq!(parse_bluez_address("cache"), None);
+        assert_eq!(parse_bluez_address("C6:55:44:33:22:11:00"), None);
+    }
+
+    #[test]
+    fn imports_a_bluez_storage_tree() {
+        let nonce = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let root = std::env::temp_dir().join(format!("blueoxide-bluez-{nonce}"));
+        let adapter = root.join("00:1A:7D:DA:71:13");
+        let device = adapter.join("C6:55:44:33:22:11");
+        fs::create_dir_all(&device).unwrap();
+        fs::create_dir_all(adapter.join("cache")).unwrap();
+        fs::write(adapter.join("settings"), "[General]\nDiscoverable=false\n").unwrap();
+        fs::write(
+            device.join("info"),
+            "[LongTermKey]\nKey=0F0E0D0C0B0A09080706050403020100\nEDiv=0\nRand=0\n",
+        )
+        .unwrap();
+
+        let mut keyring = LeKeyring::new();
+        let imported = keyring.import_bluez(&root);
+        let from_device = LeKeyring::new().import_bluez(&device);
+        let from_info = LeKeyring::new().import_bluez(&device.join("info"));
+        fs::remove_dir_all(&root).ok();
+        assert_eq!(imported.unwrap().long_term_keys, 1);
+        assert_eq!(
+            keyring.long_term_keys()[0].address,
+            Some((PEER, AddressKind::Public))
+        );
+        assert_eq!(from_device.unwrap().long_term_keys, 1);
+        assert_eq!(from_info.unwrap().long_term_keys, 1);
+    }
+
+    #[test]
+    fn stores_the_cross_transport_counterpart_of_secure_connections_keys() {
+        let address = (DeviceAddress([1, 2, 3, 4, 5, 6]), AddressKind::Public);
+        let long_term_key = [0x5c; 16];
+        let mut keyring = LeKeyring::new();
+        assert_eq!(
+            keyring.add_long_term_key_with_link_key(address, long_term_key, true),
+            KeyImport {
+                long_term_keys: 1,
+                link_keys: 1,
+                ..KeyImport::default()
+            }
+        );
+        let link_key = keyring.link_keys()[0].key;
+        assert_eq!(
+            link_key,
+            crate::pairing::link_key_from_long_term_key(long_term_key, true)
+        );
+        assert_eq!(keyring.long_term_keys()[0].address, Some(address));
+
+        let mut reverse = LeKeyring::new();
+        reverse.add_link_key_with_long_term_key(address, link_key, true);
+        assert_eq!(reverse.link_keys(), keyring.link_keys());
+        assert_eq!(
+            reverse.long_term_keys()[0].key,
+            crate::pairing::long_term_key_from_link_key(link_key, true)
+        );
+        assert_eq!(
+            keyring.add_long_term_key_with_link_key(address, long_term_key, true),
+            KeyImport::default()
+        );
+    }
+
+    #[test]
+    fn saves_loads_merges_and_removes_keyring_file_entries() {
+        let mut keyring = LeKeyring::new();
+        keyring.add_long_term_key(LongTermKeyEntry {
+            address: Some((PEER, AddressKind::Random)),
+            encrypted_diversifier: 9332,
+            random_number: [0x90, 0x78, 0x56, 0x34, 0x12, 0xef, 0xcd, 0xab],
+            key: [0x11; 16],
+        });
+        keyring.add_long_term_key(LongTermKeyEntry {
+            address: None,
+            encrypted_diversifier: 0,
+            random_number: [0; 8],
+            key: [0x22; 16],
+        });
+        keyring.add_identity_resolving_key(IdentityResolvingKeyEntry {
+            address: (PEER, AddressKind::Random),
+            key: [0x33; 16],
+        });
+        keyring.add_signature_key(SignatureKeyEntry {
+            address: (PEER, AddressKind::Random),
+            key: [0x44; 16],
+        });
+        keyring.add_link_key(LinkKeyEntry {
+            address: DeviceAddress([1, 2, 3, 4, 5, 6]),
+            key: [0x55; 16],
+        });
+        let key_material = EncryptedDataKeyMaterial {
+            session_key: [0x66; 16],
+            initialization_vector: [0x77; 8],
+        };
+        keyring.add_encrypted_data_key(EncryptedDataKeyEntry {
+            address: None,
+            key_material,
+        });
+        assert!(!keyring.add_encrypted_data_key(EncryptedDataKeyEntry {
+            address: Some((PEER, AddressKind::Random)),
+            key_material,
+        }));
+
+        let contents = keyring.file_contents();
+        assert!(contents.starts_with("# blueoxide keyring 1\n"));
+        assert!(contents.contains(
+            "ltk address=C6:55:44:33:22:11/random ediv=9332 rand=9078563412efcdab key=11111111111111111111111111111111\n"
+        ));
+        assert!(contents.contains("ltk address=none ediv=0 rand=0000000000000000"));
+        assert!(contents.contains("link-key address=06:05:04:03:02:01/public"));
+        assert!(contents.contains(
+            "ead-key address=C6:55:44:33:22:11/random key=66666666666666666666666666666666 iv=7777777777777777\n"
+        ));
+        assert_eq!(LeKeyring::parse_file(&contents).unwrap(), keyring);
+
+        let path = std::env::temp_dir().join(format!(
+            "blueoxide-keyring-{}",
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        keyring.save(&path).unwrap();
+        let loaded = LeKeyring::load(&path);
+        fs::remove_file(&path).ok();
+        assert_eq!(loaded.unwrap(), keyring);
+
+        let mut merged = LeKeyring::new();
+        assert_eq!(
+            merged.merge(&keyring),
+            KeyImport {
+                long_term_keys: 2,
+                identity_resolving_keys: 1,
+                signature_keys: 1,
+                link_keys: 1,
+                encrypted_data_keys: 1,
+            }
+        );
+        assert_eq!(merged.merge(&keyring), KeyImport::default());
+        assert_eq!(
+            merged.remove_device(PEER, Some(KeyringEntryKind::SignatureKey)),
+            1
+        );
+        assert_eq!(merged.remove_device(PEER, None), 3);
+        assert_eq!(merged.long_term_keys().len(), 1);
+        assert_eq!(merged.link_keys().len(), 1);
+        assert!(merged.encrypted_data_keys().is_empty());
+
+        let error = LeKeyring::parse_file("# blueoxide keyring 1\nltk address=none key=00\n")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("line 2"), "{error}");
+        assert!(LeKeyring::parse_file("ltk address=none\n").is_err());
+        assert!(
+            LeKeyring::parse_file(
+                "# blueoxide keyring 1\nirk address=C6:55:44:33:22:11 key=33333333333333333333333333333333\n"
+            )
+            .is_err()
+        );
+    }
+}
@@ -5,22 +5,34 @@
 //! core remains testable without attached hardware.
 
 pub mod advertising;
+pub mod analysis;
 pub mod att;
 pub mod backends;
 pub mod ble;
+pub mod btsnoop;
 pub mod capture;
 pub mod complex;
 mod crypto;
 pub mod demod;
+pub mod encrypted_data;
 pub mod error;
 pub mod iq;
+pub mod json;
+pub mod keyring;
 pub mod l2cap;
 pub mod link_layer;
 pub mod ll_control;
 pub mod native;
+pub mod pairing;
 pub mod pcapng;
 pub mod periodic;
+pub mod privacy;
+pub mod remote;
+pub mod replay;
+pub mod scenario;
 pub mod sdr;
 pub mod smp;
+pub mod stream;
+pub mod synth;
 
 pub use error::{Error, Result};
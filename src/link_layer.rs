@@ -407,10 +407,20 @@ impl LeAclDecryptor {
         let final_counter = first_counter
             .saturating_add(self.maximum_counter_skip)
             .min(LE_ACL_PACKET_COUNTER_LIMIT - 1);
-        for packet_counter in first_counter..=final_counter {
-            let Some(plaintext) = self.decrypt_at_counter(packet, packet_counter) else {
-                continue;
-            };
+        // The expected counter is tried alone; skipped counters are searched
+        // eight at a time.
+        let authenticated = self
+            .decrypt_at_counter(packet, first_counter)
+            .map(|plaintext| (first_counter, plaintext))
+            .or_else(|| {
+                let nonces = (first_counter + 1..=final_counter).map(|packet_counter| {
+                    encryption_nonce(packet_counter, self.direction, self.initialization_vector)
+                });
+                self.ccm
+                    .decrypt_first(nonces, packet.header[0] & 0xe3, &packet.payload)
+                    .map(|(index, plaintext)| (first_counter + 1 + index as u64, plaintext))
+            });
+        if let Some((packet_counter, plaintext)) = authenticated {
             let skipped_counters = packet_counter - first_counter;
             self.next_packet_counter = packet_counter + 1;
             self.last_authenticated = Some((sequence_number, packet_counter));
@@ -438,6 +448,117 @@ impl LeAclDecryptor {
     }
 }
 
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LeAclEncryption {
+    pub packet: DataChannelPdu,
+    /// Counter the payload was encrypted under, or `None` for an empty PDU,
+    /// which is sent unencrypted.
+    pub packet_counter: Option<u64>,
+}
+
+/// Encrypts one explicitly directed LE ACL packet stream, the transmit-side
+/// counterpart of [`LeAclDecryptor`].
+///
+/// Every nonempty payload consumes the next 39-bit packet counter and gains a
+/// MIC; zero-length data PDUs pass through without one. The Length octet is
+/// updated, but the CRC is left as given because it depends on the
+/// connection's CRCInit; recompute it with [`crate::ble::crc24_bytes`] before
+/// transmitting or writing a capture.
+pub struct LeAclEncryptor {
+    ccm: Aes128Ccm,
+    initialization_vector: [u8; 8],
+    direction: LinkDirection,
+    next_packet_counter: u64,
+}
+
+impl LeAclEncryptor {
+    /// Creates explicit state for one transmitter direction, with the key and
+    /// IV conventions of [`LeAclDecryptor::new`].
+    pub fn new(
+        session_key: [u8; 16],
+        initialization_vector: [u8; 8],
+        direction: LinkDirection,
+        initial_packet_counter: u64,
+    ) -> Result<Self> {
+        if initial_packet_counter >= LE_ACL_PACKET_COUNTER_LIMIT {
+            return Err(Error::InvalidConfiguration(format!(
+                "LE ACL packet counter {initial_packet_counter} exceeds the 39-bit range"
+            )));
+        }
+        Ok(Self {
+            ccm: Aes128Ccm::new(session_key),
+            initialization_vector,
+            direction,
+            next_packet_counter: initial_packet_counter,
+        })
+    }
+
+    pub const fn direction(&self) -> LinkDirection {
+        self.direction
+    }
+
+    pub const fn next_packet_counter(&self) -> u64 {
+        self.next_packet_counter
+    }
+
+    /// Encrypts `packet` under the next packet counter.
+    pub fn encrypt(&mut self, packet: &DataChannelPdu) -> Result<LeAclEncryption> {
+        let packet_counter = self.next_packet_counter;
+        let encrypted = self.encrypt_at_counter(packet, packet_counter)?;
+        if packet.payload.is_empty() {
+            return Ok(LeAclEncryption {
+                packet: encrypted,
+                packet_counter: None,
+            });
+        }
+        self.next_packet_counter = packet_counter + 1;
+        Ok(LeAclEncryption {
+            packet: encrypted,
+            packet_counter: Some(packet_counter),
+        })
+    }
+
+    /// Encrypts `packet` under an explicit counter without advancing state,
+    /// as a retransmission repeats the counter of the original.
+    pub fn encrypt_at_counter(
+        &self,
+        packet: &DataChannelPdu,
+        packet_counter: u64,
+    ) -> Result<DataChannelPdu> {
+        if usize::from(packet.declared_payload_length()) != packet.payload.len() {
+            return Err(Error::InvalidInput(format!(
+                "data-channel Length declares {} octets but the packet retains {}",
+                packet.declared_payload_length(),
+                packet.payload.len()
+            )));
+        }
+        if packet.payload.len() > usize::from(u8::MAX) - LE_ACL_MIC_OCTETS {
+            return Err(Error::InvalidInput(format!(
+                "LE ACL plaintext of {} octets leaves no room for the {LE_ACL_MIC_OCTETS}-octet MIC",
+                packet.payload.len()
+            )));
+        }
+        if packet.payload.is_empty() {
+            return Ok(packet.clone());
+        }
+        if packet_counter >= LE_ACL_PACKET_COUNTER_LIMIT {
+            return Err(Error::InvalidState(
+                "LE ACL packet counter is exhausted".to_owned(),
+            ));
+        }
+        let ciphertext = self.ccm.encrypt(
+            encryption_nonce(packet_counter, self.direction, self.initialization_vector),
+            packet.header[0] & 0xe3,
+            &packet.payload,
+        );
+        let mut encrypted = packet.clone();
+        encrypted.header[1] =
+            u8::try_from(ciphertext.len()).expect("plaintext length checked above");
+        encrypted.payload = ciphertext;
+        Ok(encrypted)
+    }
+}
+
 fn encryption_nonce(
     packet_counter: u64,
     direction: LinkDirection,
@@ -2428,6 +2549,18 @@ mod tests {
             ],
         );
         let decrypted = central.decrypt(&central_packet).unwrap();
+        assert_eq!(
+            LeAclEncryptor::new(
+                core_session_key(),
+                core_initialization_vector(),
+                LinkDirection::CentralToPeripheral,
+                1,
+            )
+            .unwrap()
+            .encrypt_at_counter(&decrypted.packet, 1)
+            .unwrap(),
+            central_packet
+        );
         assert_eq!(
             decrypted.status,
             LeAclDecryptionStatus::New {
@@ -2473,6 +2606,149 @@ mod tests {
         );
     }
 
+    #[test]
+    fn encryptor_reproduces_the_bluetooth_core_sample_data() {
+        let mut central = LeAclEncryptor::new(
+            core_session_key(),
+            core_initialization_vector(),
+            LinkDirection::CentralToPeripheral,
+            0,
+        )
+        .unwrap();
+        let start_encryption = central.encrypt(&data_packet(0x13, &[0x06])).unwrap();
+        assert_eq!(start_encryption.packet_counter, Some(0));
+        assert_eq!(start_encryption.packet.header, [0x13, 5]);
+        assert_eq!(
+            start_encryption.packet.payload,
+            [0x9f, 0xcd, 0xa7, 0xf4, 0x48]
+        );
+        let encrypted = central
+            .encrypt(&data_packet(
+                0x0e,
+                &[
+                    0x17, 0x00, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69, 0x6a, 0x6b, 0x6c, 0x6d,
+                    0x6e, 0x6f, 0x70, 0x71, 0x31, 0x32, 0x33, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39,
+                    0x30,
+                ],
+            ))
+            .unwrap();
+        assert_eq!(encrypted.packet_counter, Some(1));
+        assert_eq!(encrypted.packet.header, [0x0e, 31]);
+        assert_eq!(
+            encrypted.packet.payload,
+            [
+                0x7a, 0x70, 0xd6, 0x64, 0x15, 0x22, 0x6d, 0xf2, 0x6b, 0x17, 0x83, 0x9a, 0x06, 0x04,
+                0x05, 0x59, 0x6b, 0xd6, 0x56, 0x4f, 0x79, 0x6b, 0x5b, 0x9c, 0xe6, 0xff, 0x32, 0xf7,
+                0x5a, 0x6d, 0x33,
+            ]
+        );
+        assert_eq!(central.next_packet_counter(), 2);
+
+        let peripheral = LeAclEncryptor::new(
+            core_session_key(),
+            core_initialization_vector(),
+            LinkDirection::PeripheralToCentral,
+            0,
+        )
+        .unwrap();
+        assert_eq!(
+            peripheral
+                .encrypt_at_counter(&data_packet(0x07, &[0x06]), 0)
+                .unwrap()
+                .payload,
+            [0xa3, 0x4c, 0x13, 0xa4, 0x15]
+        );
+        assert_eq!(peripheral.next_packet_counter(), 0);
+    }
+
+    #[test]
+    fn encryptor_round_trips_through_the_decryptor() {
+        let mut encryptor = LeAclEncryptor::new(
+            core_session_key(),
+            core_initialization_vector(),
+            LinkDirection::PeripheralToCentral,
+            5,
+        )
+        .unwrap();
+        let mut decryptor = LeAclDecryptor::new(
+            core_session_key(),
+            core_initialization_vector(),
+            LinkDirection::PeripheralToCentral,
+            5,
+            1,
+        )
+        .unwrap();
+
+        let empty = encryptor.encrypt(&data_packet(0x05, &[])).unwrap();
+        assert_eq!(empty.packet_counter, None);
+        assert_eq!(empty.packet, data_packet(0x05, &[]));
+        assert_eq!(encryptor.next_packet_counter(), 5);
+
+        let plaintext = data_packet(0x02, &[5, 0, 4, 0, 0x0c, 1, 0, 2, 0]);
+        let first = encryptor.encrypt(&plaintext).unwrap();
+        let decrypted = decryptor.decrypt(&first.packet).unwrap();
+        assert_eq!(decrypted.packet, plaintext);
+        assert_eq!(
+            decrypted.status,
+            LeAclDecryptionStatus::New {
+                packet_counter: 5,
+                skipped_counters: 0,
+            }
+        );
+
+        // A lost packet consumes a counter the decryptor skips over, and a
+        // retransmission repeats its original counter.
+        encryptor.encrypt(&plaintext).unwrap();
+        let mut third = plaintext.clone();
+        third.header[0] = 0x0a;
+        let third = encryptor.encrypt(&third).unwrap();
+        assert_eq!(third.packet_counter, Some(7));
+        assert_eq!(
+            decryptor
+                .decrypt(&third.packet)
+                .unwrap()
+                .status
+                .skipped_counters(),
+            1
+        );
+        let mut retransmission = data_packet(0x0e, &[5, 0, 4, 0, 0x0c, 1, 0, 2, 0]);
+        retransmission = encryptor.encrypt_at_counter(&retransmission, 7).unwrap();
+        assert_eq!(
+            decryptor.decrypt(&retransmission).unwrap().status,
+            LeAclDecryptionStatus::Retransmission { packet_counter: 7 }
+        );
+    }
+
+    #[test]
+    fn encryptor_rejects_oversized_and_inconsistent_packets() {
+        let mut encryptor = LeAclEncryptor::new(
+            core_session_key(),
+            core_initialization_vector(),
+            LinkDirection::CentralToPeripheral,
+            LE_ACL_PACKET_COUNTER_LIMIT - 1,
+        )
+        .unwrap();
+        assert!(encryptor.encrypt(&data_packet(0x02, &[0; 252])).is_err());
+        let mut inconsistent = data_packet(0x02, &[1, 2, 3]);
+        inconsistent.header[1] = 4;
+        assert!(encryptor.encrypt(&inconsistent).is_err());
+        assert!(encryptor.encrypt(&data_packet(0x02, &[0; 251])).is_ok());
+        assert!(matches!(
+            encryptor.encrypt(&data_packet(0x02, &[1])),
+            Err(Error::InvalidState(_))
+        ));
+        assert!(encryptor.encrypt(&data_packet(0x01, &[])).is_ok());
+        assert!(
+            LeAclEncryptor::new(
+                core_session_key(),
+                core_initialization_vector(),
+                LinkDirection::CentralToPeripheral,
+                LE_ACL_PACKET_COUNTER_LIMIT,
+            )
+            .is_err()
+        );
+    }
+
     #[test]
     fn decrypts_first_encrypted_control_packets_and_masks_sequence_bits() {
         let mut decryptor = LeAclDecryptor::new(
@@ -1,7 +1,9 @@
+use crate::advertising::DeviceAddress;
+use crate::keyring::LeKeyring;
 use crate::link_layer::{
     ChannelMapInd, ConnectionParameters, ConnectionUpdateInd, ControlPdu, DataChannelMap,
-    DataChannelPdu, LE_ACL_MAXIMUM_COUNTER_SKIP, LeAclDecryptionStatus, LeAclDecryptor,
-    LinkDirection, SleepClockAccuracy,
+    DataChannelPdu, LE_ACL_MAXIMUM_COUNTER_SKIP, LeAclDecryption, LeAclDecryptionStatus,
+    LeAclDecryptor, LinkDirection, SleepClockAccuracy,
 };
 use crate::{Error, Result};
 
@@ -344,6 +346,84 @@ pub struct LeEncryptionObservation {
     pub state_after: LeEncryptionSessionState,
 }
 
+/// Further LTK candidates for one session, tried in order when the current
+/// material fails to authenticate a packet.
+///
+/// A keyring can hold several LTKs for one LL_ENC_REQ. Until a packet
+/// authenticates as new, any of them may be the session's key; after that
+/// the key is settled and the remaining candidates are dropped. Each
+/// candidate is anything that yields session material, such as an
+/// [`LeEncryptionMaterialTracker`] still waiting for its exchange or a
+/// derived [`LeEncryptionMaterial`].
+#[derive(Clone, Debug)]
+pub struct LeKeyCandidates<T> {
+    candidates: Vec<T>,
+}
+
+impl<T> Default for LeKeyCandidates<T> {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl<T> LeKeyCandidates<T> {
+    pub const fn new(candidates: Vec<T>) -> Self {
+        Self { candidates }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.candidates.is_empty()
+    }
+
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, T> {
+        self.candidates.iter_mut()
+    }
+
+    /// Tries each candidate with material, in order, on a packet the current
+    /// material failed to authenticate. The first authenticated decryption is
+    /// returned with the candidate's position and its advanced decryptor.
+    /// Nothing is committed; call [`Self::take`] once the caller accepts it.
+    pub fn authenticate(
+        &self,
+        material: impl Fn(&T) -> Option<LeEncryptionMaterial>,
+        direction: LinkDirection,
+        packet_counter: u64,
+        maximum_counter_skip: u64,
+        packet: &DataChannelPdu,
+    ) -> Result<Option<(usize, LeAclDecryptor, LeAclDecryption)>> {
+        for (index, candidate) in self.candidates.iter().enumerate() {
+            let Some(material) = material(candidate) else {
+                continue;
+            };
+            let mut decryptor = LeAclDecryptor::new(
+                material.session_key(),
+                material.initialization_vector(),
+                direction,
+                packet_counter,
+                maximum_counter_skip,
+            )?;
+            if let Ok(decryption) = decryptor.decrypt(packet) {
+                return Ok(Some((index, decryptor, decryption)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Removes the candidate at `index`, as returned by
+    /// [`Self::authenticate`], keeping the others in order.
+    pub fn take(&mut self, index: usize) -> T {
+        self.candidates.remove(index)
+    }
+
+    /// Drops every candidate once `status` shows a packet authenticated as
+    /// new under the current material.
+    pub fn settle(&mut self, status: Option<LeAclDecryptionStatus>) {
+        if matches!(status, Some(LeAclDecryptionStatus::New { .. })) {
+            self.candidates.clear();
+        }
+    }
+}
+
 /// Reconstructs a bidirectional LE encryption session from directed packets.
 ///
 /// Every packet direction must come from capture context supplied by the
@@ -358,6 +438,13 @@ pub struct LeEncryptionObservation {
 /// counters, is committed only after the complete observation is accepted.
 pub struct LeEncryptionSessionTracker {
     long_term_key: [u8; 16],
+    /// Source of the LTK for each LL_ENC_REQ, with the connection's device
+    /// addresses, and the request the current LTK was selected for.
+    keyring: Option<(LeKeyring, Vec<DeviceAddress>)>,
+    selected_request: Option<EncryptionRequest>,
+    /// Material exchanges for the other keyring candidates of the selected
+    /// request, tried in order when the first encrypted packet fails its MIC.
+    alternative_material_trackers: LeKeyCandidates<LeEncryptionMaterialTracker>,
     maximum_counter_skip: u64,
     material_tracker: LeEncryptionMaterialTracker,
     pending_material: Option<LeEncryptionMaterial>,
@@ -404,6 +491,9 @@ impl LeEncryptionSessionTracker {
         }
         Ok(Self {
             long_term_key,
+            keyring: None,
+            selected_request: None,
+            alternative_material_trackers: LeKeyCandidates::default(),
             maximum_counter_skip,
             material_tracker: LeEncryptionMaterialTracker::new(long_term_key),
             pending_material: None,
@@ -416,6 +506,23 @@ impl LeEncryptionSessionTracker {
         })
     }
 
+    /// Creates a tracker that selects its LTK from `keyring` on every
+    /// LL_ENC_REQ by EDIV and Rand, preferring keys stored for one of
+    /// `peers`. See [`LeKeyring::long_term_keys_for`]. The first candidate is
+    /// used, and when the first encrypted packet fails its MIC the others are
+    /// tried in order until one authenticates. When none matches the request,
+    /// the previous LTK stays selected and the first encrypted packet fails
+    /// authentication.
+    pub fn with_keyring(
+        keyring: LeKeyring,
+        peers: &[DeviceAddress],
+        maximum_counter_skip: u64,
+    ) -> Result<Self> {
+        let mut tracker = Self::new([0; 16], maximum_counter_skip)?;
+        tracker.keyring = Some((keyring, peers.to_vec()));
+        Ok(tracker)
+    }
+
     pub const fn state(&self) -> LeEncryptionSessionState {
         self.state
     }
@@ -451,7 +558,7 @@ impl LeEncryptionSessionTracker {
     /// Drops all procedure, material, and packet-counter state after a capture
     /// discontinuity while retaining the configured LTK and skip bound.
     pub fn reset(&mut self) {
-        self.material_tracker = LeEncryptionMaterialTracker::new(self.long_term_key);
+        self.restart_material_exchange();
         self.pending_material = None;
         self.active_material = None;
         self.central_to_peripheral = None;
@@ -475,6 +582,7 @@ impl LeEncryptionSessionTracker {
         }
 
         let state_before = self.state;
+        let mut alternative = None;
         let (observed, decryption, candidate_decryptor) = if self.direction_encrypted(direction) {
             let mut candidate = self
                 .decryptor(direction)
@@ -485,7 +593,25 @@ impl LeEncryptionSessionTracker {
                     ))
                 })?
                 .duplicate_state();
-            let decryption = candidate.decrypt(packet)?;
+            let decryption = match candidate.decrypt(packet) {
+                Ok(decryption) => decryption,
+                Err(error) => {
+                    let Some((index, decryptor, decryption)) =
+                        self.alternative_material_trackers.authenticate(
+                            LeEncryptionMaterialTracker::material,
+                            direction,
+                            0,
+                            self.maximum_counter_skip,
+                            packet,
+                        )?
+                    else {
+                        return Err(error);
+                    };
+                    candidate = decryptor;
+                    alternative = Some(index);
+                    decryption
+                }
+            };
             (decryption.packet, Some(decryption.status), Some(candidate))
         } else {
             (packet.clone(), None, None)
@@ -514,9 +640,13 @@ impl LeEncryptionSessionTracker {
             self.process_observed(direction, &observed)?;
         }
 
+        if let Some(index) = alternative {
+            self.switch_to_alternative(index, direction)?;
+        }
         if let Some(candidate) = candidate_decryptor {
             *self.decryptor_mut(direction) = Some(candidate);
         }
+        self.alternative_material_trackers.settle(decryption);
         if decryption.is_none() && !plaintext_retransmission && observed.control()?.is_some() {
             self.last_plaintext_control = Some(PlaintextControlFingerprint {
                 direction,
@@ -576,7 +706,8 @@ impl LeEncryptionSessionTracker {
 
         match self.state {
             State::AwaitingInitialEncryptionRequest => match decoded {
-                Control::EncryptionRequest(_) => {
+                Control::EncryptionRequest(request) => {
+                    self.select_long_term_key(direction, request);
                     self.observe_material(direction, control)?;
                     self.state = State::AwaitingEncryptionResponse;
                     Ok(())
@@ -589,7 +720,10 @@ impl LeEncryptionSessionTracker {
                 _ => Ok(()),
             },
             State::AwaitingEncryptionResponse => match decoded {
-                Control::EncryptionRequest(_) => self.observe_material(direction, control),
+                Control::EncryptionRequest(request) => {
+                    self.select_long_term_key(direction, request);
+                    self.observe_material(direction, control)
+                }
                 Control::EncryptionResponse(_) => {
                     if let Some(material) = self.observe_material_value(direction, control)? {
                         self.pending_material = Some(material);
@@ -701,7 +835,7 @@ impl LeEncryptionSessionTracker {
                 {
                     self.central_to_peripheral = None;
                     self.peripheral_to_central = None;
-                    self.material_tracker = LeEncryptionMaterialTracker::new(self.long_term_key);
+                    self.restart_material_exchange();
                     self.pending_material = None;
                     self.active_material = None;
                     self.state = State::AwaitingRefreshEncryptionRequest;
@@ -716,7 +850,8 @@ impl LeEncryptionSessionTracker {
                 {
                     Ok(())
                 }
-                Control::EncryptionRequest(_) => {
+                Control::EncryptionRequest(request) => {
+                    self.select_long_term_key(direction, request);
                     self.observe_material(direction, control)?;
                     self.state = State::AwaitingEncryptionResponse;
                     Ok(())
@@ -726,6 +861,65 @@ impl LeEncryptionSessionTracker {
         }
     }
 
+    /// Switches to the keyring's LTK for a new central LL_ENC_REQ. Material
+    /// derived from the previous request is discarded with the old key.
+    fn select_long_term_key(&mut self, direction: LinkDirection, request: EncryptionRequest) {
+        if direction != LinkDirection::CentralToPeripheral || self.selected_request == Some(request)
+        {
+            return;
+        }
+        let Some((keyring, peers)) = &self.keyring else {
+            return;
+        };
+        let mut candidates = keyring.long_term_keys_for(&request, peers).into_iter();
+        if let Some(key) = candidates.next() {
+            self.long_term_key = key;
+            self.material_tracker = LeEncryptionMaterialTracker::new(key);
+            self.alternative_material_trackers =
+                LeKeyCandidates::new(candidates.map(LeEncryptionMaterialTracker::new).collect());
+        }
+        self.selected_request = Some(request);
+    }
+
+    /// Starts the material exchange again under the current LTK and its
+    /// remaining alternatives.
+    fn restart_material_exchange(&mut self) {
+        self.material_tracker = LeEncryptionMaterialTracker::new(self.long_term_key);
+        for alternative in self.alternative_material_trackers.iter_mut() {
+            *alternative = LeEncryptionMaterialTracker::new(alternative.long_term_key);
+        }
+    }
+
+    /// Makes an authenticated alternative the session's LTK. The decryptor
+    /// for `direction` is installed by the caller; the other direction
+    /// restarts at counter zero because it has authenticated nothing yet.
+    fn switch_to_alternative(&mut self, index: usize, direction: LinkDirection) -> Result<()> {
+        let tracker = self.alternative_material_trackers.take(index);
+        let material = tracker
+            .material()
+            .expect("alternative authenticated with derived material");
+        self.long_term_key = tracker.long_term_key;
+        self.material_tracker = tracker;
+        if self.pending_material.is_some() {
+            self.pending_material = Some(material);
+        }
+        if self.active_material.is_some() {
+            self.active_material = Some(material);
+        }
+        let other = match direction {
+            LinkDirection::CentralToPeripheral => LinkDirection::PeripheralToCentral,
+            LinkDirection::PeripheralToCentral => LinkDirection::CentralToPeripheral,
+        };
+        *self.decryptor_mut(other) = Some(LeAclDecryptor::new(
+            material.session_key(),
+            material.initialization_vector(),
+            other,
+            0,
+            self.maximum_counter_skip,
+        )?);
+        Ok(())
+    }
+
     fn observe_material(
         &mut self,
         direction: LinkDirection,
@@ -739,7 +933,11 @@ impl LeEncryptionSessionTracker {
         direction: LinkDirection,
         control: ControlPdu<'_>,
     ) -> Result<Option<LeEncryptionMaterial>> {
-        self.material_tracker.observe(direction, control)
+        let material = self.material_tracker.observe(direction, control)?;
+        for alternative in self.alternative_material_trackers.iter_mut() {
+            alternative.observe(direction, control)?;
+        }
+        Ok(material)
     }
 
     fn install_pending_decryptors(&mut self) -> Result<()> {
@@ -766,7 +964,7 @@ impl LeEncryptionSessionTracker {
     }
 
     fn reject_material_exchange(&mut self) {
-        self.material_tracker = LeEncryptionMaterialTracker::new(self.long_term_key);
+        self.restart_material_exchange();
         self.pending_material = None;
         self.central_to_peripheral = None;
         self.peripheral_to_central = None;
@@ -2035,6 +2233,125 @@ mod tests {
         tracker
     }
 
+    #[test]
+    fn key_candidates_authenticate_in_order_until_settled() {
+        let DecodedControlPdu::EncryptionRequest(request) =
+            control(0x03, &core_request_parameters()).decode().unwrap()
+        else {
+            unreachable!();
+        };
+        let DecodedControlPdu::EncryptionResponse(response) =
+            control(0x04, &core_response_parameters()).decode().unwrap()
+        else {
+            unreachable!();
+        };
+        let material = |key| LeEncryptionMaterial::derive(key, request, response);
+        let mut candidates = LeKeyCandidates::new(vec![
+            material([0x55; 16]),
+            material(core_ltk()),
+            material([0x66; 16]),
+        ]);
+        let start = data_packet(0x13, &[0x9f, 0xcd, 0xa7, 0xf4, 0x48]);
+
+        let (index, decryptor, decryption) = candidates
+            .authenticate(
+                |material| Some(*material),
+                LinkDirection::CentralToPeripheral,
+                0,
+                0,
+                &start,
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(index, 1);
+        assert_eq!(decryptor.next_packet_counter(), 1);
+        assert_eq!(decryption.packet.payload, [0x06]);
+        assert_eq!(candidates.take(index), material(core_ltk()));
+        assert!(
+            candidates
+                .authenticate(
+                    |material| Some(*material),
+                    LinkDirection::CentralToPeripheral,
+                    0,
+                    0,
+                    &start,
+                )
+                .unwrap()
+                .is_none()
+        );
+
+        candidates.settle(None);
+        candidates.settle(Some(LeAclDecryptionStatus::UnencryptedEmpty));
+        assert_eq!(
+            candidates
+                .iter_mut()
+                .map(|material| *material)
+                .collect::<Vec<_>>(),
+            [material([0x55; 16]), material([0x66; 16])]
+        );
+        candidates.settle(Some(decryption.status));
+        assert!(candidates.is_empty());
+    }
+
+    #[test]
+    fn selects_the_long_term_key_from_a_keyring_on_ll_enc_req() {
+        use crate::advertising::AddressKind;
+        use crate::keyring::LongTermKeyEntry;
+
+        let peer = DeviceAddress([1, 2, 3, 4, 5, 6]);
+        let stored = LongTermKeyEntry {
+            address: Some((peer, AddressKind::Public)),
+            encrypted_diversifier: 0x2474,
+            random_number: core_request_parameters()[..8].try_into().unwrap(),
+            key: core_ltk(),
+        };
+        let wrong = LongTermKeyEntry {
+            address: None,
+            key: [0x55; 16],
+            ..stored
+        };
+        let mut keyring = LeKeyring::new();
+        keyring.add_long_term_key(stored);
+        keyring.add_long_term_key(wrong);
+        let mut wrong_only = LeKeyring::new();
+        wrong_only.add_long_term_key(wrong);
+
+        // Without the peer address the unaddressed key is tried first, and
+        // the stored key takes over when it fails the first MIC.
+        for (keyring, peers, authenticated) in [
+            (keyring.clone(), vec![peer], true),
+            (keyring, Vec::new(), true),
+            (wrong_only, vec![peer], false),
+        ] {
+            let mut tracker = LeEncryptionSessionTracker::with_keyring(keyring, &peers, 0).unwrap();
+            tracker
+                .observe(
+                    LinkDirection::CentralToPeripheral,
+                    &control_packet(0x03, 0x03, &core_request_parameters()),
+                )
+                .unwrap();
+            tracker
+                .observe(
+                    LinkDirection::PeripheralToCentral,
+                    &control_packet(0x07, 0x04, &core_response_parameters()),
+                )
+                .unwrap();
+            tracker
+                .observe(
+                    LinkDirection::PeripheralToCentral,
+                    &control_packet(0x03, 0x05, &[]),
+                )
+                .unwrap();
+            let start = tracker.observe(
+                LinkDirection::CentralToPeripheral,
+                &data_packet(0x13, &[0x9f, 0xcd, 0xa7, 0xf4, 0x48]),
+            );
+            assert_eq!(start.is_ok(), authenticated);
+            assert_eq!(tracker.long_term_key == core_ltk(), authenticated);
+            assert!(tracker.alternative_material_trackers.is_empty());
+        }
+    }
+
     #[test]
     fn decodes_fixed_and_cryptographic_control_pdus() {
         assert_eq!(
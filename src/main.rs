@@ -1,87 +1,133 @@
 use blueoxide::advertising::{
-    ConnectRequest, ExtendedAdvertisingChainConfig, ExtendedAdvertisingChainProgress,
-    ExtendedAdvertisingChainTracker, ExtendedAdvertisingPduKind, FirstCentralTransmission,
-    decode_advertising_pdu, decode_contextual_extended_advertising_pdu,
+    AdStructure, AddressKind, ConnectRequest, DecodedAdvertisingPdu, DeviceAddress,
+    ExtendedAdvertisingChainConfig, ExtendedAdvertisingChainProgress,
+    ExtendedAdvertisingChainTracker, ExtendedAdvertisingHeader, ExtendedAdvertisingPduKind,
+    FirstCentralTransmission, PeriodicAdvertisingSyncInfo, decode_advertising_pdu,
+    decode_contextual_extended_advertising_pdu,
 };
-use blueoxide::att::{AttPdu, AttUuid, DecodedAttPdu};
+use blueoxide::analysis::{
+    AnalysisLayer, AnalyzedEvent, AnalyzedPacket, AnalyzerConfig, AnalyzerPacket,
+    ConnectionAnalyzer, ConnectionSummary, ConnectionTracking, DirectionSource,
+};
+use blueoxide::att::{ATT_FIXED_CHANNEL_ID, AttPdu, AttSignatureStatus, AttUuid, DecodedAttPdu};
 use blueoxide::backends::bladerf::{BladeRfOptions, BladeRfSource};
 use blueoxide::backends::limesdr::{LimeSdrOptions, LimeSdrSource};
 use blueoxide::backends::xtrx::{XtrxOptions, XtrxSource};
-use blueoxide::ble::{BleChannel, LeFrameConfig};
+use blueoxide::ble::{
+    BleChannel, LE_ADV_ACCESS_ADDRESS, LE_ADV_CRC_INIT, LeCrcFailurePolicy, LeFrameConfig, LePdu,
+};
+use blueoxide::btsnoop::{BtSnoopWriter, HciHost};
 use blueoxide::capture::{
-    CaptureLimits, CaptureStats, CapturedAdvertisingPdu, CapturedDataChannelPdu,
-    FixedChannelCentralObservationConfig, FixedChannelCentralObservationTracker,
-    capture_data_channel, capture_primary_advertising,
+    CaptureLimits, CaptureObservation, CaptureStats, CapturedAdvertisingPdu, CapturedCrcFailure,
+    CapturedDataChannelPdu, FixedChannelCentralObservationConfig,
+    FixedChannelCentralObservationTracker, SampleOrdered, capture_data_channel_with_crc_failures,
+    capture_primary_advertising_with_crc_failures, in_sample_order,
 };
+use blueoxide::complex::Complex32;
 use blueoxide::demod::{
     Le1mDemodConfig, Le1mStreamDecoder, LePeriodicAdvertisingStreamDecoder,
     LeSecondaryAdvertisingStreamDecoder, LeUncodedDemodConfig, LeUncodedPacketStreamDecoder,
     LeUncodedPhy, ReceivedAdvertisingPdu, ReceivedLePdu,
 };
-use blueoxide::iq::{IqFormat, open_iq_file};
+use blueoxide::encrypted_data::{EncryptedDataKeyMaterial, EncryptedDataStatus};
+use blueoxide::iq::{IqFormat, IqReader, iq_file_sample_count, write_iq_samples};
+use blueoxide::json::{Json, JsonObject};
+use blueoxide::keyring::{
+    EncryptedDataKeyEntry, IdentityResolvingKeyEntry, KeyImport, KeyringEntryKind, LeKeyring,
+    LinkKeyEntry, LongTermKeyEntry, SignatureKeyEntry,
+};
 use blueoxide::l2cap::{
     IncompleteL2capCreditBasedSdu, L2capCreditBasedChannel, L2capCreditBasedChannelTracker,
     L2capCreditBasedEvent, L2capCreditBasedSdu,
 };
 use blueoxide::link_layer::{
-    ChannelSelectionAlgorithm, ConnectionEventTiming, ConnectionParameters, ConnectionPhyState,
-    ConnectionTracker, ConnectionTrackerConfig, ControlPdu, DataChannelMap, DataChannelPdu,
+    ChannelMapInd, ChannelSelectionAlgorithm, ConnectionEventTiming, ConnectionParameters,
+    ConnectionPhyState, ConnectionTracker, ConnectionTrackerConfig, ConnectionUpdateInd,
+    ConstantToneExtensionInfo, ControlPdu, DataChannelMap, DataChannelPdu,
     DecodedL2capSignalingCommand, IncompleteL2capPdu, L2capPdu, L2capReassembler,
     L2capReassemblyOutcome, L2capSignalingCommand, LE_ACL_MAXIMUM_COUNTER_SKIP, LeAclDecryption,
     LeAclDecryptionStatus, LeAclDecryptor, LePhy, LinkDirection, LogicalLinkId, PhyUpdateInd,
     SampleTimingError, SleepClockAccuracy,
 };
 use blueoxide::ll_control::{
-    ChannelClassification, CsConfigAction, DecodedControlPdu, LeEncryptionMaterialTracker,
-    LeEncryptionSessionTracker,
+    ChannelClassification, CsConfigAction, DecodedControlPdu, LeEncryptionMaterial,
+    LeEncryptionMaterialTracker, LeEncryptionSessionTracker, LeKeyCandidates,
+};
+use blueoxide::pairing::{LegacyPairingEvent, PairingSessionEvent, SecureConnectionsPairingEvent};
+use blueoxide::pcapng::{
+    CaptureCrcStatus, CaptureFileReader, PcapNgInterface, PcapNgPacketOptions, PcapNgRotation,
+    PcapNgSection, PcapNgWriter, RotatingPcapNgWriter, encryption_material_comment,
+    sample_timestamp_ns,
 };
-use blueoxide::pcapng::{PcapNgWriter, sample_timestamp_ns};
 use blueoxide::periodic::{
     PeriodicAdvertisingEvent, PeriodicAdvertisingTracker, PeriodicAdvertisingTrackerConfig,
 };
-use blueoxide::sdr::{IqSource, SdrConfig};
+use blueoxide::remote::{RemoteSource, serve_iq_connection};
+use blueoxide::replay::{
+    ReplayImpairment, ReplayOptions, ReplayPacing, ReplaySource, is_sigmf_path,
+    sigmf_metadata_text, sigmf_paths,
+};
+use blueoxide::scenario::{
+    ConnectionScenario, ScenarioAction, ScenarioActionKind, ScenarioEncryption,
+};
+use blueoxide::sdr::{IqSource, SdrConfig, SdrDevice, SdrKind};
 use blueoxide::smp::{DecodedSmpPdu, SmpAuthenticationRequirements, SmpKeyDistribution, SmpPdu};
+use blueoxide::stream::{IqStreamFraming, IqStreamLocation, StreamSource, open_iq_stream};
+use blueoxide::synth::{
+    ChannelImpairments, GfskShape, MultipathTap, SynthesisConfig, SynthesizedPacket, synthesize,
+};
 use blueoxide::{Error, Result};
 use std::env;
 use std::fs::File;
-use std::io::{BufWriter, Write};
-use std::path::PathBuf;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 const DEFAULT_MAX_SAMPLES: usize = 16_000_000;
 const DEFAULT_BLOCK_SAMPLES: usize = 262_144;
 
 #[derive(Debug)]
 struct DecodeArgs {
-    input: PathBuf,
+    input: IqStreamLocation,
     format: IqFormat,
+    framing: IqStreamFraming,
     channel: BleChannel,
     sample_rate_hz: u32,
     max_samples: usize,
     block_samples: usize,
     max_access_address_errors: u8,
+    crc_failure_policy: LeCrcFailurePolicy,
     output_pcap: Option<PathBuf>,
     capture_start_ns: u64,
+    output_format: OutputFormat,
 }
 
 #[derive(Debug)]
 struct DecodeSecondaryArgs {
-    input: PathBuf,
+    input: IqStreamLocation,
     format: IqFormat,
+    framing: IqStreamFraming,
     channel: BleChannel,
     phy: LeUncodedPhy,
     sample_rate_hz: u32,
     max_samples: usize,
     block_samples: usize,
     max_access_address_errors: u8,
+    crc_failure_policy: LeCrcFailurePolicy,
     output_pcap: Option<PathBuf>,
     capture_start_ns: u64,
+    output_format: OutputFormat,
 }
 
 #[derive(Debug)]
 struct DecodePeriodicArgs {
-    input: PathBuf,
+    input: IqStreamLocation,
     format: IqFormat,
+    framing: IqStreamFraming,
     channel: BleChannel,
     phy: LeUncodedPhy,
     sample_rate_hz: u32,
@@ -90,13 +136,16 @@ struct DecodePeriodicArgs {
     max_samples: usize,
     block_samples: usize,
     max_access_address_errors: u8,
+    crc_failure_policy: LeCrcFailurePolicy,
     output_pcap: Option<PathBuf>,
     capture_start_ns: u64,
+    output_format: OutputFormat,
 }
 
 struct DecodeDataArgs {
-    input: PathBuf,
+    input: IqStreamLocation,
     format: IqFormat,
+    framing: IqStreamFraming,
     channel: BleChannel,
     phy: LeUncodedPhy,
     sample_rate_hz: u32,
@@ -105,31 +154,141 @@ struct DecodeDataArgs {
     max_samples: usize,
     block_samples: usize,
     max_access_address_errors: u8,
+    crc_failure_policy: LeCrcFailurePolicy,
     output_pcap: Option<PathBuf>,
     capture_start_ns: u64,
     plaintext_l2cap_direction: Option<LinkDirection>,
     maximum_l2cap_payload_length: usize,
     decryption: Option<DecodeDataDecryptionArgs>,
+    pcap_payload: PcapPayload,
+    output_format: OutputFormat,
+}
+
+/// Which form of each encrypted data PDU `decode-data` writes to PCAPNG.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum PcapPayload {
+    Ciphertext,
+    Plaintext,
+    Both,
+}
+
+/// How commands print packets and events on standard output.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum OutputFormat {
+    Text,
+    /// One JSON object per line; see [`JSON_SCHEMA_VERSION`].
+    JsonLines,
 }
 
+/// Version of the `--output-format jsonl` record schema documented in the
+/// README. Bump it when a field is renamed, removed, or changes type.
+const JSON_SCHEMA_VERSION: u32 = 1;
+
 struct DecodeDataDecryptionArgs {
     session_key: [u8; 16],
     initialization_vector: [u8; 8],
+    /// Session material of each further keyring LTK matching `--enc-req`,
+    /// tried in order when the first encrypted packet fails its MIC.
+    alternatives: Vec<LeEncryptionMaterial>,
     direction: LinkDirection,
     initial_packet_counter: u64,
     maximum_counter_skip: u64,
 }
 
+/// Key stores named by `--keyring`, `--keys-btsnoop`, and `--keys-bluez`,
+/// loaded into an [`LeKeyring`] once option parsing has succeeded.
+#[derive(Debug, Default)]
+struct KeySourceArgs {
+    keyrings: Vec<PathBuf>,
+    btsnoop: Vec<PathBuf>,
+    bluez: Vec<PathBuf>,
+}
+
+impl KeySourceArgs {
+    fn push(&mut self, option: &str, path: String) {
+        let paths = match option {
+            "--keyring" => &mut self.keyrings,
+            "--keys-btsnoop" => &mut self.btsnoop,
+            "--keys-bluez" => &mut self.bluez,
+            _ => unreachable!("caller matched a key source option"),
+        };
+        paths.push(PathBuf::from(path));
+    }
+
+    fn is_empty(&self) -> bool {
+        self.keyrings.is_empty() && self.btsnoop.is_empty() && self.bluez.is_empty()
+    }
+
+    fn load(&self) -> Result<LeKeyring> {
+        let mut keyring = LeKeyring::new();
+        for path in &self.keyrings {
+            let imported = keyring.merge(&LeKeyring::load(path)?);
+            print_key_import(path, imported);
+        }
+        for path in &self.btsnoop {
+            let imported = keyring.import_btsnoop(BufReader::new(File::open(path)?))?;
+            print_key_import(path, imported);
+        }
+        for path in &self.bluez {
+            let imported = keyring.import_bluez(path)?;
+            print_key_import(path, imported);
+        }
+        Ok(keyring)
+    }
+}
+
+fn describe_key_import(imported: KeyImport) -> String {
+    format!(
+        "{} LTK(s), {} IRK(s), {} CSRK(s), {} link key(s), and {} EAD key(s)",
+        imported.long_term_keys,
+        imported.identity_resolving_keys,
+        imported.signature_keys,
+        imported.link_keys,
+        imported.encrypted_data_keys
+    )
+}
+
+fn print_key_import(path: &Path, imported: KeyImport) {
+    eprintln!(
+        "imported {} from {}",
+        describe_key_import(imported),
+        path.display()
+    );
+}
+
+#[derive(Debug)]
+struct KeyringArgs {
+    path: PathBuf,
+    action: KeyringAction,
+    output_format: OutputFormat,
+}
+
+#[derive(Debug)]
+enum KeyringAction {
+    Add(Box<LeKeyring>),
+    List {
+        show_keys: bool,
+    },
+    Remove {
+        address: DeviceAddress,
+        kind: Option<KeyringEntryKind>,
+    },
+    Import(KeySourceArgs),
+}
+
 #[derive(Debug)]
 struct EncryptionTraceArgs {
-    long_term_key: [u8; 16],
+    long_term_key: Option<[u8; 16]>,
+    key_sources: KeySourceArgs,
     maximum_counter_skip: u64,
     packets: Vec<DirectionalDataPacketArg>,
+    output_format: OutputFormat,
 }
 
 #[derive(Debug)]
 struct L2capTraceArgs {
     pdus: Vec<L2capPdu>,
+    output_format: OutputFormat,
 }
 
 #[derive(Clone, Debug)]
@@ -151,10 +310,99 @@ struct CaptureArgs {
     block_samples: usize,
     read_timeout_ms: u64,
     max_access_address_errors: u8,
+    crc_failure_policy: LeCrcFailurePolicy,
     output_pcap: Option<PathBuf>,
+    pcap_rotation: PcapNgRotation,
     capture_start_ns: Option<u64>,
+    stream_format: IqFormat,
+    stream_framing: IqStreamFraming,
+    replay: ReplayOptions,
     frame: CaptureFrame,
     central_observation_tracking: Option<FixedChannelCentralObservationConfig>,
+    output_format: OutputFormat,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum ExtcapAction {
+    Interfaces,
+    Dlts,
+    Config,
+    Capture,
+}
+
+#[derive(Debug)]
+struct ExtcapArgs {
+    action: ExtcapAction,
+    interface: Option<String>,
+    fifo: Option<PathBuf>,
+    /// Interface options from `--extcap-config`, in the order Wireshark
+    /// passed them; flags have no value.
+    options: Vec<(String, Option<String>)>,
+}
+
+#[derive(Debug)]
+struct ServeArgs {
+    device: String,
+    identifier: Option<String>,
+    listen: String,
+    stream_format: IqFormat,
+    stream_framing: IqStreamFraming,
+    max_connections: Option<u64>,
+}
+
+#[derive(Debug)]
+struct SynthesizeArgs {
+    output: PathBuf,
+    format: IqFormat,
+    channel: BleChannel,
+    sample_rate_hz: u32,
+    total_samples: Option<usize>,
+    frame_config: LeFrameConfig,
+    packets: Vec<SynthesizePacketArg>,
+    shape: GfskShape,
+    impairments: ChannelImpairments,
+}
+
+#[derive(Debug)]
+struct SynthesizePacketArg {
+    phy: LePhy,
+    start_sample: u64,
+    pdu: Vec<u8>,
+}
+
+struct ScenarioArgs {
+    scenario: ConnectionScenario,
+    output: Option<PathBuf>,
+    format: IqFormat,
+    iq_channel: Option<BleChannel>,
+    output_pcap: Option<PathBuf>,
+    capture_start_ns: u64,
+    impairments: ChannelImpairments,
+}
+
+/// Capture timestamps are rescaled to this clock so connection-event windows
+/// keep the sample-per-symbol range the tracker supports.
+const ANALYZE_CAPTURE_TIME_BASE_HZ: u32 = 16_000_000;
+
+struct AnalyzeArgs {
+    input: PathBuf,
+    iq: Option<AnalyzeIqArgs>,
+    config: AnalyzerConfig,
+    key_sources: KeySourceArgs,
+    output_btsnoop: Option<PathBuf>,
+    btsnoop_host: HciHost,
+    output_keyring: Option<PathBuf>,
+}
+
+struct AnalyzeIqArgs {
+    format: IqFormat,
+    channel: BleChannel,
+    phy: LeUncodedPhy,
+    frame_config: LeFrameConfig,
+    max_samples: usize,
+    block_samples: usize,
+    max_access_address_errors: u8,
+    capture_start_ns: u64,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -203,6 +451,7 @@ struct ConnectionPlanArgs {
     connect_ind_access_address_sample: Option<u64>,
     initial_phy: ConnectionPhyState,
     phy_update: Option<PhyUpdateInd>,
+    output_format: OutputFormat,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -217,6 +466,7 @@ struct ExtendedAdvertisingPlanArgs {
     receiver_clock_accuracy_ppm: u32,
     maximum_advertising_data_length: usize,
     packets: Vec<ExtendedAdvertisingPacketArg>,
+    output_format: OutputFormat,
 }
 
 #[derive(Clone, Debug)]
@@ -241,6 +491,7 @@ struct PeriodicAdvertisingPlanArgs {
     event_count: usize,
     maximum_event_advance: u16,
     observations: Vec<PeriodicAdvertisingObservationArg>,
+    output_format: OutputFormat,
 }
 
 fn usage() -> &'static str {
@@ -255,8 +506,9 @@ USAGE:
     --access-address 0xNNNNNNNN --crc-init 0xNNNNNN [OPTIONS]
   blueoxide decode-data --input FILE --channel 0..36 --sample-rate HZ \
     --access-address 0xNNNNNNNN --crc-init 0xNNNNNN [OPTIONS]
-  blueoxide encryption-trace --ltk HEX \
+  blueoxide encryption-trace --ltk HEX|KEY SOURCE \
     --packet DIRECTION:HEADERPAYLOADHEX [--packet ...] [OPTIONS]
+  blueoxide keyring add|list|remove|import --keyring FILE [KEYRING OPTIONS]
   blueoxide l2cap-trace --pdu DIRECTION:CID:PAYLOADHEX [--pdu ...]
   blueoxide connection-plan --access-address 0xNNNNNNNN \
     --channel-map HEX --csa 1|2 --interval N --sample-rate HZ [OPTIONS]
@@ -270,16 +522,37 @@ USAGE:
     --packet CHANNEL:PHY:SAMPLE:PDUHEX [--packet ...]
   blueoxide periodic-advertising-plan --sample-rate HZ \
     --sync-packet CHANNEL:PHY:SAMPLE:PDUHEX [OPTIONS]
-  blueoxide capture --device bladerf|limesdr|xtrx --channel 37|38|39 [OPTIONS]
-  blueoxide capture-data --device bladerf|limesdr|xtrx --channel 0..36 \
+  blueoxide capture --device bladerf|limesdr|xtrx|stream|remote|replay --channel 37|38|39 \
+    [OPTIONS]
+  blueoxide capture-data --device bladerf|limesdr|xtrx|stream|remote|replay --channel 0..36 \
     --access-address 0xNNNNNNNN --crc-init 0xNNNNNN [OPTIONS]
+  blueoxide serve --device bladerf|limesdr|xtrx|stream --listen HOST:PORT [OPTIONS]
+  blueoxide synthesize --output FILE --channel 0..39 \
+    --packet PHY:SAMPLE:PDUHEX [--packet ...] [OPTIONS]
+  blueoxide scenario [--output FILE --iq-channel 0..39] [--output-pcap FILE] \
+    [ACTIONS] [OPTIONS]
+  blueoxide analyze --input FILE [--ltk HEX ...] [OPTIONS]
+  blueoxide --extcap-interfaces | --extcap-dlts | --extcap-config | --capture \
+    --extcap-interface NAME [--fifo PATH] [EXTCAP OPTIONS]
+
+OUTPUT OPTIONS:
+  --output-format text|jsonl
+                          Decode, capture, trace, and planner record format;
+                          jsonl prints one schema_version 1 JSON object per
+                          packet or event (default: text)
 
 DECODE OPTIONS:
+  --input -|PATH|tcp://HOST:PORT
+                          I/Q file, FIFO, standard input, or TCP stream
   --format f32le|s16le    Interleaved little-endian I/Q (default: f32le)
+  --framing raw|header    Stream framing; header expects a 20-octet BOIQ header
+                          carrying format, rate, and frequency (default: raw)
   --max-samples N         Maximum samples accepted from the file (default: 16000000)
   --block-samples N       Streaming decode block size (default: 262144)
   --aa-errors N           Access-address bit errors, 0..=8 (default: 1)
   --output-pcap FILE      Write CRC-valid packets as BLE PCAPNG
+  --emit-crc-failures     Also report CRC-failed frames of plausible length;
+                          PCAPNG marks them CRC-checked and not valid
   --capture-start-ns N    Unix capture start in nanoseconds (default: 0)
   -h, --help              Show this help
 
@@ -303,19 +576,53 @@ DECODE-DATA OPTIONS:
   --session-key HEX       16 AES session-key octets, left to right
   --iv HEX                Eight combined LL initialization-vector octets
   --ltk HEX               16 LTK octets in HCI/SMP field order
+  --keyring FILE          Instead of --ltk, try the keyring LTKs matching the
+                          EDIV/Rand of --enc-req; also --keys-btsnoop and
+                          --keys-bluez as for encryption-trace (repeatable)
   --enc-req HEX           Complete 23-octet LL_ENC_REQ control payload
   --enc-rsp HEX           Complete 13-octet LL_ENC_RSP control payload
   --decrypt-direction central-to-peripheral|peripheral-to-central
                           Assert the transmitter direction for AES-CCM
   --packet-counter N      Initial 39-bit direction-specific packet counter
   --max-counter-skip N    MIC-search skipped counters, 0..=65535 (default: 0)
+  --pcap-payload ciphertext|plaintext|both
+                          Encrypted PDUs written to --output-pcap; plaintext
+                          copies carry the session key (default: ciphertext)
 
 ENCRYPTION-TRACE OPTIONS:
   --ltk HEX               16 LTK octets in HCI/SMP field order
+  KEY SOURCE options select the LTK by EDIV/Rand and try each match against
+  the first encrypted packet's MIC; each may be repeated:
+  --keyring FILE          A keyring file kept by the keyring command
+  --keys-btsnoop FILE     Keys a host sent to its controller in a btsnoop log
+  --keys-bluez PATH       A BlueZ key store: /var/lib/bluetooth, an adapter or
+                          device directory, or an info file
   --packet DIRECTION:HEX  Directed two-octet data header plus Length-counted
                           payload/MIC; repeat in observed wire order
   --max-counter-skip N    MIC-search skipped counters, 0..=65535 (default: 0)
 
+KEYRING OPTIONS:
+  --keyring FILE          Keyring file; add and import create it if missing
+  --address XX:XX:XX:XX:XX:XX[/public|/random]
+                          Device the entry belongs to; remove drops its entries
+  --ltk HEX               add: LTK in HCI/SMP field order; --address optional
+  --ediv N                add: EDIV stored with --ltk (default: 0)
+  --rand HEX              add: eight Rand octets in LL_ENC_REQ order (default: 0)
+  --irk HEX               add: IRK of the identity --address
+  --csrk HEX              add: CSRK that signs the device's ATT signed writes
+  --link-key HEX          add: BR/EDR link key
+  --ctkd h6|h7            add: also store the cross-transport counterpart of a
+                          Secure Connections --ltk or --link-key; h7 when both
+                          devices set CT2. Requires --address
+  --ead-key HEX           add: Encrypted Advertising Data session key in
+                          characteristic order; --address optional
+  --ead-iv HEX            add: eight EAD IV octets stored with --ead-key
+  --type ltk|irk|csrk|link-key|ead-key
+                          remove: only entries of this type
+  --show-keys             list: print key material instead of redacted
+  --keys-btsnoop FILE     import: keys from a btsnoop HCI log (repeatable)
+  --keys-bluez PATH       import: keys from a BlueZ key store (repeatable)
+
 L2CAP-TRACE OPTIONS:
   --pdu DIRECTION:CID:HEX Complete plaintext L2CAP PDU after the basic header;
                           CID accepts decimal or 0x-prefixed hexadecimal
@@ -364,7 +671,15 @@ PERIODIC-ADVERTISING-PLAN OPTIONS:
                           PHY is 1m, 2m, or coded
 
 CAPTURE OPTIONS:
-  --identifier STRING     Native backend device identifier
+  --identifier STRING     Native backend device identifier; for --device stream,
+                          -, a file or FIFO path, or tcp://HOST:PORT; for
+                          --device remote, the HOST:PORT of a serve process;
+                          for --device replay, an I/Q file or SigMF recording
+  --format f32le|s16le    Stream or replay file sample format (default: f32le)
+  --framing raw|header    Stream framing (default: raw)
+  --replay-speed X|max    Replay pacing factor, 1 for real time (default: max)
+  --impair SPEC           Inject a replay fault: overrun:SAMPLE:COUNT,
+                          gap:SAMPLE:COUNT, or timeout:SAMPLE (repeatable)
   --sample-rate HZ        Complex sample rate (default: 4000000)
   --bandwidth HZ          RX bandwidth (default: 2000000)
   --gain DB               RX gain in dB (default: 30)
@@ -374,6 +689,15 @@ CAPTURE OPTIONS:
   --read-timeout-ms N     Native read timeout (default: 1000)
   --aa-errors N           Access-address bit errors, 0..=8 (default: 1)
   --output-pcap FILE      Write CRC-valid packets as BLE PCAPNG
+  --emit-crc-failures     Also report CRC-failed frames of plausible length;
+                          PCAPNG marks them CRC-checked and not valid
+  --pcap-max-bytes N      Start a new PCAPNG file after N octets
+  --pcap-max-seconds N    Start a new PCAPNG file after N seconds of capture
+  --pcap-max-packets N    Start a new PCAPNG file after N packets
+                          Rotated files are FILE's STEM_NNNNN_YYYYMMDDhhmmss.EXT
+                          (UTC file start), each with its own section header
+  --pcap-ring-files N     Keep only the newest N rotated files
+  --pcap-flush-ms N       Flush PCAPNG to disk at least every N milliseconds
   --capture-start-ns N    Override Unix capture start in nanoseconds
 
 CAPTURE-DATA OPTIONS:
@@ -393,6 +717,124 @@ CAPTURE-DATA OPTIONS:
   --peer-sca N            Peer sleep-clock accuracy, 0..=7 (default: 0)
   --receiver-ppm N        Receiver sample-clock error bound (default: 20)
   --max-event-advance N   Maximum event advancement searched (default: 32)
+
+EXTCAP OPTIONS:
+  Wireshark extcap protocol. Interfaces are blueoxide-bladerf-SERIAL,
+  blueoxide-limesdr-SERIAL, blueoxide-xtrx-SERIAL for attached radios, and
+  blueoxide-replay. --capture runs capture, or capture-data when
+  --access-address is set, until Wireshark closes the FIFO.
+  --channel, --access-address, --crc-init, --phy, --sample-rate, --bandwidth,
+  --gain, --rx-channel, --aa-errors, --emit-crc-failures, --format,
+  --replay-speed
+                          As for capture and capture-data
+  --recording FILE        Recording for blueoxide-replay
+
+SERVE OPTIONS:
+  --listen HOST:PORT      TCP address for remote capture clients
+  --identifier STRING     Native backend device identifier or stream location
+  --format f32le|s16le    Stream sample format for --device stream
+  --framing raw|header    Stream framing for --device stream
+  --max-connections N     Exit after serving N clients (default: unlimited)
+
+SYNTHESIZE OPTIONS:
+  --packet P:S:HEX        PHY 1m|2m, first preamble sample, and PDU header,
+                          CTEInfo, and payload octets without CRC (repeatable)
+  --output FILE           Headerless I/Q file, or NAME.sigmf-data with metadata
+  --format f32le|s16le    Output sample format (default: f32le)
+  --sample-rate HZ        Complex sample rate (default: 4000000)
+  --samples N             Output length (default: last packet plus a short tail)
+  --access-address HEX    Access address with --crc-init (default: advertising)
+  --crc-init HEX          24-bit CRC initialization value
+  --modulation-index H    GFSK modulation index (default: 0.5)
+  --bt X                  Gaussian bandwidth-time product (default: 0.5)
+  --snr DB                Add white Gaussian noise at this carrier SNR
+  --cfo HZ                Carrier frequency offset
+  --drift-ppm PPM         Transmitter symbol-clock error
+  --multipath D:G[:DEG]   Echo delayed D samples with gain G and phase (repeatable)
+  --seed N                Noise generator seed (default: 1)
+
+SCENARIO OPTIONS:
+  Generates an advertiser, a CONNECT_IND, and a perfect LE 1M connection.
+  --output FILE           I/Q of the transmissions on --iq-channel, or
+                          NAME.sigmf-data with metadata
+  --iq-channel N          Fixed receiver channel for --output
+  --output-pcap FILE      Every transmission as BLE PCAPNG with directions
+  --capture-start-ns N    Unix time of sample 0 for PCAPNG (default: 0)
+  --format f32le|s16le    Output sample format (default: f32le)
+  --sample-rate HZ        Whole-MHz complex sample rate (default: 4000000)
+  --advertising-channel N Primary advertising channel (default: 37)
+  --advertiser ADDR[/public|/random]
+                          AdvA (default: 01:02:03:04:05:06/public)
+  --initiator ADDR[/public|/random]
+                          InitA (default: c6:55:44:33:22:11/random)
+  --adv-data HEX          ADV_IND advertising data (default: 020106)
+  --advertising-sample N  First ADV_IND preamble sample (default: 1000)
+  --access-address HEX    Connection access address (default: 0x50654b4f)
+  --crc-init HEX          24-bit CRC initialization value (default: 0x7a4f21)
+  --csa 1|2               Channel Selection Algorithm (default: 2)
+  --hop N                 CSA#1 hop increment, 5..=16 (default: 7)
+  --channel-map HEX       Five map octets in over-the-air order
+  --interval N            Connection interval in 1.25 ms units (default: 24)
+  --latency N             Peripheral latency (default: 0)
+  --timeout N             Supervision timeout in 10 ms units (default: 100)
+  --window-size N         CONNECT_IND WinSize (default: 2)
+  --window-offset N       CONNECT_IND WinOffset (default: 1)
+  --sca N                 CONNECT_IND sleep-clock accuracy, 0..=7 (default: 5)
+  --events N              Connection events to generate (default: 16)
+  --snr DB                Add white Gaussian noise at this carrier SNR
+  --cfo HZ                Carrier frequency offset
+  --seed N                Noise generator seed (default: 1)
+
+SCENARIO ACTIONS:
+  --att EVENT:DIRECTION:HEX
+                          Queue an ATT PDU on CID 0x0004 (repeatable)
+  --l2cap EVENT:DIRECTION:CID:HEX
+                          Queue an L2CAP payload, fragmented to 27 octets
+  --channel-map-update EVENT:INSTANT:MAPHEX
+                          Queue LL_CHANNEL_MAP_IND
+  --connection-update EVENT:INSTANT:WINSIZE:WINOFFSET:INTERVAL:LATENCY:TIMEOUT
+                          Queue LL_CONNECTION_UPDATE_IND
+  --encrypt EVENT         Start LL encryption; requires --ltk
+  --ltk HEX               16 LTK octets in HCI/SMP field order
+  --enc-req HEX           23-octet LL_ENC_REQ payload (default: Core sample data)
+  --enc-rsp HEX           13-octet LL_ENC_RSP payload (default: Core sample data)
+
+ANALYZE OPTIONS:
+  Runs advertising, CONNECT_IND, connection-event, encryption, L2CAP, ATT, and
+  SMP decoding over a PCAPNG or pcap capture, or over I/Q when --channel is set.
+  --input FILE            PCAPNG, classic pcap, or raw I/Q file
+  --ltk HEX               Candidate LTK in HCI/SMP field order (repeatable)
+  --keyring FILE          Keyring file kept by the keyring command (repeatable)
+  --keys-btsnoop FILE     Import LTKs and IRKs from a btsnoop HCI log; the LTK
+                          is chosen by the EDIV/Rand of LL_ENC_REQ (repeatable)
+  --keys-bluez PATH       Import LTKs and IRKs from a BlueZ key store directory
+                          or info file (repeatable)
+  --crack-legacy-pairing  Search the TK of observed LE legacy pairings, decrypt
+                          with the derived STK, and capture distributed LTKs
+  --dhkey HEX             Candidate Secure Connections DHKey in HCI field
+                          order (repeatable); debug-key pairings need none
+  --output-keyring FILE   Add LTKs, IRKs, and CSRKs captured or derived from
+                          pairings to a keyring file
+  --receiver-ppm N        Receiver clock accuracy for event windows (default: 20)
+  --max-event-advance N   Events searched when re-synchronizing (default: 32)
+  --max-counter-skip N    Encrypted packet-counter gap accepted (default: 0)
+  --max-l2cap-payload N   Largest reassembled L2CAP payload (default: 65535)
+  --channel N             Decode I/Q captured on this channel
+  --sample-rate HZ        I/Q complex sample rate; required with --channel
+  --format f32le|s16le    I/Q sample format (default: f32le)
+  --phy 1m|2m             I/Q PHY (default: 1m)
+  --access-address HEX    Data-channel access address for I/Q input
+  --crc-init HEX          Data-channel CRC initialization value for I/Q input
+  --max-samples N         Maximum I/Q samples accepted (default: 16000000)
+  --block-samples N       I/Q samples processed per block (default: 262144)
+  --aa-errors N           Maximum access-address bit errors (default: 1)
+  --capture-start-ns N    Unix time of the first I/Q sample for btsnoop
+                          timestamps (default: 0)
+  --output-btsnoop FILE   Write reassembled L2CAP PDUs as HCI ACL data, with
+                          one connection handle per connection, to a btsnoop log
+  --btsnoop-host central|peripheral
+                          Device whose host the btsnoop log is taken on
+                          (default: central)
 "
 }
 
@@ -475,6 +917,27 @@ fn parse_link_direction(value: &str, option: &str) -> Result<LinkDirection> {
     }
 }
 
+fn parse_pcap_payload(value: &str) -> Result<PcapPayload> {
+    match value {
+        "ciphertext" => Ok(PcapPayload::Ciphertext),
+        "plaintext" => Ok(PcapPayload::Plaintext),
+        "both" => Ok(PcapPayload::Both),
+        _ => Err(Error::InvalidConfiguration(format!(
+            "invalid value {value:?} for --pcap-payload; expected ciphertext, plaintext, or both"
+        ))),
+    }
+}
+
+fn parse_output_format(value: &str) -> Result<OutputFormat> {
+    match value {
+        "text" => Ok(OutputFormat::Text),
+        "jsonl" => Ok(OutputFormat::JsonLines),
+        _ => Err(Error::InvalidConfiguration(format!(
+            "invalid value {value:?} for --output-format; expected text or jsonl"
+        ))),
+    }
+}
+
 fn parse_uncoded_phy(value: &str, option: &str) -> Result<LeUncodedPhy> {
     match value.to_ascii_lowercase().as_str() {
         "1m" | "le-1m" => Ok(LeUncodedPhy::Le1M),
@@ -648,6 +1111,7 @@ fn parse_extended_advertising_plan_args(args: &[String]) -> Result<ExtendedAdver
     let mut receiver_clock_accuracy_ppm = 20u32;
     let mut maximum_advertising_data_length = 1_650usize;
     let mut packets = Vec::new();
+    let mut output_format = OutputFormat::Text;
     let mut index = 0;
     while index < args.len() {
         match args[index].as_str() {
@@ -670,6 +1134,10 @@ fn parse_extended_advertising_plan_args(args: &[String]) -> Result<ExtendedAdver
             "--packet" => packets.push(parse_extended_advertising_packet(&value_after(
                 args, &mut index, "--packet",
             )?)?),
+            "--output-format" => {
+                output_format =
+                    parse_output_format(&value_after(args, &mut index, "--output-format")?)?;
+            }
             "-h" | "--help" => {
                 print!("{}", usage());
                 std::process::exit(0);
@@ -696,6 +1164,7 @@ fn parse_extended_advertising_plan_args(args: &[String]) -> Result<ExtendedAdver
         receiver_clock_accuracy_ppm,
         maximum_advertising_data_length,
         packets,
+        output_format,
     };
     ExtendedAdvertisingChainConfig {
         sample_rate_hz: plan.sample_rate_hz,
@@ -745,6 +1214,7 @@ fn parse_periodic_advertising_plan_args(args: &[String]) -> Result<PeriodicAdver
     let mut event_count = 10usize;
     let mut maximum_event_advance = 32u16;
     let mut observations = Vec::new();
+    let mut output_format = OutputFormat::Text;
     let mut index = 0;
 
     while index < args.len() {
@@ -785,6 +1255,10 @@ fn parse_periodic_advertising_plan_args(args: &[String]) -> Result<PeriodicAdver
             "--observe" => observations.push(parse_periodic_advertising_observation(
                 &value_after(args, &mut index, "--observe")?,
             )?),
+            "--output-format" => {
+                output_format =
+                    parse_output_format(&value_after(args, &mut index, "--output-format")?)?;
+            }
             "-h" | "--help" => {
                 print!("{}", usage());
                 std::process::exit(0);
@@ -831,6 +1305,7 @@ fn parse_periodic_advertising_plan_args(args: &[String]) -> Result<PeriodicAdver
         event_count,
         maximum_event_advance,
         observations,
+        output_format,
     })
 }
 
@@ -856,6 +1331,7 @@ fn parse_connection_plan_args(args: &[String]) -> Result<ConnectionPlanArgs> {
     let mut connect_ind_access_address_sample = None;
     let mut initial_phy = ConnectionPhyState::default();
     let mut phy_update = None;
+    let mut output_format = OutputFormat::Text;
     let mut index = 0;
 
     while index < args.len() {
@@ -964,6 +1440,10 @@ fn parse_connection_plan_args(args: &[String]) -> Result<ConnectionPlanArgs> {
                 let value = value_after(args, &mut index, "--connect-sample")?;
                 connect_ind_access_address_sample = Some(parse_number(&value, "--connect-sample")?);
             }
+            "--output-format" => {
+                output_format =
+                    parse_output_format(&value_after(args, &mut index, "--output-format")?)?;
+            }
             "-h" | "--help" => {
                 print!("{}", usage());
                 std::process::exit(0);
@@ -1016,25 +1496,36 @@ fn parse_connection_plan_args(args: &[String]) -> Result<ConnectionPlanArgs> {
         connect_ind_access_address_sample,
         initial_phy,
         phy_update,
+        output_format,
     })
 }
 
 fn parse_decode_args(args: &[String]) -> Result<DecodeArgs> {
     let mut input = None;
     let mut format = IqFormat::F32Le;
+    let mut framing = IqStreamFraming::Raw;
     let mut channel = None;
     let mut sample_rate_hz = None;
     let mut max_samples = DEFAULT_MAX_SAMPLES;
     let mut block_samples = DEFAULT_BLOCK_SAMPLES;
     let mut max_access_address_errors = 1u8;
+    let mut crc_failure_policy = LeCrcFailurePolicy::Discard;
     let mut output_pcap = None;
     let mut capture_start_ns = 0u64;
+    let mut output_format = OutputFormat::Text;
     let mut index = 0;
 
     while index < args.len() {
         match args[index].as_str() {
-            "--input" => input = Some(PathBuf::from(value_after(args, &mut index, "--input")?)),
+            "--input" => {
+                input = Some(IqStreamLocation::parse(&value_after(
+                    args, &mut index, "--input",
+                )?)?);
+            }
             "--format" => format = IqFormat::parse(&value_after(args, &mut index, "--format")?)?,
+            "--framing" => {
+                framing = IqStreamFraming::parse(&value_after(args, &mut index, "--framing")?)?;
+            }
             "--channel" => {
                 let value = value_after(args, &mut index, "--channel")?;
                 channel = Some(BleChannel::new(parse_number(&value, "--channel")?)?);
@@ -1051,6 +1542,7 @@ fn parse_decode_args(args: &[String]) -> Result<DecodeArgs> {
                 let value = value_after(args, &mut index, "--block-samples")?;
                 block_samples = parse_number(&value, "--block-samples")?;
             }
+            "--emit-crc-failures" => crc_failure_policy = LeCrcFailurePolicy::emit(),
             "--aa-errors" => {
                 let value = value_after(args, &mut index, "--aa-errors")?;
                 max_access_address_errors = parse_number(&value, "--aa-errors")?;
@@ -1066,6 +1558,10 @@ fn parse_decode_args(args: &[String]) -> Result<DecodeArgs> {
                 let value = value_after(args, &mut index, "--capture-start-ns")?;
                 capture_start_ns = parse_number(&value, "--capture-start-ns")?;
             }
+            "--output-format" => {
+                output_format =
+                    parse_output_format(&value_after(args, &mut index, "--output-format")?)?;
+            }
             "-h" | "--help" => {
                 print!("{}", usage());
                 std::process::exit(0);
@@ -1084,6 +1580,7 @@ fn parse_decode_args(args: &[String]) -> Result<DecodeArgs> {
             Error::InvalidConfiguration("decode requires --input FILE".to_owned())
         })?,
         format,
+        framing,
         channel: channel.ok_or_else(|| {
             Error::InvalidConfiguration("decode requires --channel 37|38|39".to_owned())
         })?,
@@ -1093,28 +1590,40 @@ fn parse_decode_args(args: &[String]) -> Result<DecodeArgs> {
         max_samples,
         block_samples,
         max_access_address_errors,
+        crc_failure_policy,
         output_pcap,
         capture_start_ns,
+        output_format,
     })
 }
 
 fn parse_decode_secondary_args(args: &[String]) -> Result<DecodeSecondaryArgs> {
     let mut input = None;
     let mut format = IqFormat::F32Le;
+    let mut framing = IqStreamFraming::Raw;
     let mut channel = None;
     let mut phy = LeUncodedPhy::Le1M;
     let mut sample_rate_hz = None;
     let mut max_samples = DEFAULT_MAX_SAMPLES;
     let mut block_samples = DEFAULT_BLOCK_SAMPLES;
     let mut max_access_address_errors = 1u8;
+    let mut crc_failure_policy = LeCrcFailurePolicy::Discard;
     let mut output_pcap = None;
     let mut capture_start_ns = 0u64;
+    let mut output_format = OutputFormat::Text;
     let mut index = 0;
 
     while index < args.len() {
         match args[index].as_str() {
-            "--input" => input = Some(PathBuf::from(value_after(args, &mut index, "--input")?)),
+            "--input" => {
+                input = Some(IqStreamLocation::parse(&value_after(
+                    args, &mut index, "--input",
+                )?)?);
+            }
             "--format" => format = IqFormat::parse(&value_after(args, &mut index, "--format")?)?,
+            "--framing" => {
+                framing = IqStreamFraming::parse(&value_after(args, &mut index, "--framing")?)?;
+            }
             "--channel" => {
                 let value = value_after(args, &mut index, "--channel")?;
                 channel = Some(BleChannel::new(parse_number(&value, "--channel")?)?);
@@ -1135,6 +1644,7 @@ fn parse_decode_secondary_args(args: &[String]) -> Result<DecodeSecondaryArgs> {
                 let value = value_after(args, &mut index, "--block-samples")?;
                 block_samples = parse_number(&value, "--block-samples")?;
             }
+            "--emit-crc-failures" => crc_failure_policy = LeCrcFailurePolicy::emit(),
             "--aa-errors" => {
                 let value = value_after(args, &mut index, "--aa-errors")?;
                 max_access_address_errors = parse_number(&value, "--aa-errors")?;
@@ -1150,6 +1660,10 @@ fn parse_decode_secondary_args(args: &[String]) -> Result<DecodeSecondaryArgs> {
                 let value = value_after(args, &mut index, "--capture-start-ns")?;
                 capture_start_ns = parse_number(&value, "--capture-start-ns")?;
             }
+            "--output-format" => {
+                output_format =
+                    parse_output_format(&value_after(args, &mut index, "--output-format")?)?;
+            }
             "-h" | "--help" => {
                 print!("{}", usage());
                 std::process::exit(0);
@@ -1192,20 +1706,24 @@ fn parse_decode_secondary_args(args: &[String]) -> Result<DecodeSecondaryArgs> {
             Error::InvalidConfiguration("decode-secondary requires --input FILE".to_owned())
         })?,
         format,
+        framing,
         channel,
         phy,
         sample_rate_hz,
         max_samples,
         block_samples,
         max_access_address_errors,
+        crc_failure_policy,
         output_pcap,
         capture_start_ns,
+        output_format,
     })
 }
 
 fn parse_decode_periodic_args(args: &[String]) -> Result<DecodePeriodicArgs> {
     let mut input = None;
     let mut format = IqFormat::F32Le;
+    let mut framing = IqStreamFraming::Raw;
     let mut channel = None;
     let mut phy = LeUncodedPhy::Le1M;
     let mut sample_rate_hz = None;
@@ -1214,14 +1732,23 @@ fn parse_decode_periodic_args(args: &[String]) -> Result<DecodePeriodicArgs> {
     let mut max_samples = DEFAULT_MAX_SAMPLES;
     let mut block_samples = DEFAULT_BLOCK_SAMPLES;
     let mut max_access_address_errors = 1u8;
+    let mut crc_failure_policy = LeCrcFailurePolicy::Discard;
     let mut output_pcap = None;
     let mut capture_start_ns = 0u64;
+    let mut output_format = OutputFormat::Text;
     let mut index = 0;
 
     while index < args.len() {
         match args[index].as_str() {
-            "--input" => input = Some(PathBuf::from(value_after(args, &mut index, "--input")?)),
+            "--input" => {
+                input = Some(IqStreamLocation::parse(&value_after(
+                    args, &mut index, "--input",
+                )?)?);
+            }
             "--format" => format = IqFormat::parse(&value_after(args, &mut index, "--format")?)?,
+            "--framing" => {
+                framing = IqStreamFraming::parse(&value_after(args, &mut index, "--framing")?)?;
+            }
             "--channel" => {
                 channel = Some(BleChannel::new(parse_number(
                     &value_after(args, &mut index, "--channel")?,
@@ -1261,6 +1788,7 @@ fn parse_decode_periodic_args(args: &[String]) -> Result<DecodePeriodicArgs> {
                     "--block-samples",
                 )?;
             }
+            "--emit-crc-failures" => crc_failure_policy = LeCrcFailurePolicy::emit(),
             "--aa-errors" => {
                 max_access_address_errors = parse_number(
                     &value_after(args, &mut index, "--aa-errors")?,
@@ -1280,6 +1808,10 @@ fn parse_decode_periodic_args(args: &[String]) -> Result<DecodePeriodicArgs> {
                     "--capture-start-ns",
                 )?;
             }
+            "--output-format" => {
+                output_format =
+                    parse_output_format(&value_after(args, &mut index, "--output-format")?)?;
+            }
             "-h" | "--help" => {
                 print!("{}", usage());
                 std::process::exit(0);
@@ -1331,6 +1863,7 @@ fn parse_decode_periodic_args(args: &[String]) -> Result<DecodePeriodicArgs> {
             Error::InvalidConfiguration("decode-periodic requires --input FILE".to_owned())
         })?,
         format,
+        framing,
         channel,
         phy,
         sample_rate_hz,
@@ -1339,14 +1872,17 @@ fn parse_decode_periodic_args(args: &[String]) -> Result<DecodePeriodicArgs> {
         max_samples,
         block_samples,
         max_access_address_errors,
+        crc_failure_policy,
         output_pcap,
         capture_start_ns,
+        output_format,
     })
 }
 
 fn parse_decode_data_args(args: &[String]) -> Result<DecodeDataArgs> {
     let mut input = None;
     let mut format = IqFormat::F32Le;
+    let mut framing = IqStreamFraming::Raw;
     let mut channel = None;
     let mut phy = LeUncodedPhy::Le1M;
     let mut sample_rate_hz = None;
@@ -1355,6 +1891,7 @@ fn parse_decode_data_args(args: &[String]) -> Result<DecodeDataArgs> {
     let mut max_samples = DEFAULT_MAX_SAMPLES;
     let mut block_samples = DEFAULT_BLOCK_SAMPLES;
     let mut max_access_address_errors = 1u8;
+    let mut crc_failure_policy = LeCrcFailurePolicy::Discard;
     let mut output_pcap = None;
     let mut capture_start_ns = 0u64;
     let mut plaintext_l2cap_direction = None;
@@ -1363,18 +1900,28 @@ fn parse_decode_data_args(args: &[String]) -> Result<DecodeDataArgs> {
     let mut session_key = None;
     let mut initialization_vector = None;
     let mut long_term_key = None;
+    let mut key_sources = KeySourceArgs::default();
     let mut encryption_request: Option<[u8; 23]> = None;
     let mut encryption_response: Option<[u8; 13]> = None;
     let mut decryption_direction = None;
     let mut initial_packet_counter = None;
     let mut maximum_counter_skip = 0u64;
     let mut maximum_counter_skip_supplied = false;
+    let mut pcap_payload = None;
+    let mut output_format = OutputFormat::Text;
     let mut index = 0;
 
     while index < args.len() {
         match args[index].as_str() {
-            "--input" => input = Some(PathBuf::from(value_after(args, &mut index, "--input")?)),
+            "--input" => {
+                input = Some(IqStreamLocation::parse(&value_after(
+                    args, &mut index, "--input",
+                )?)?);
+            }
             "--format" => format = IqFormat::parse(&value_after(args, &mut index, "--format")?)?,
+            "--framing" => {
+                framing = IqStreamFraming::parse(&value_after(args, &mut index, "--framing")?)?;
+            }
             "--channel" => {
                 let value = value_after(args, &mut index, "--channel")?;
                 channel = Some(BleChannel::new(parse_number(&value, "--channel")?)?);
@@ -1403,6 +1950,7 @@ fn parse_decode_data_args(args: &[String]) -> Result<DecodeDataArgs> {
                 let value = value_after(args, &mut index, "--block-samples")?;
                 block_samples = parse_number(&value, "--block-samples")?;
             }
+            "--emit-crc-failures" => crc_failure_policy = LeCrcFailurePolicy::emit(),
             "--aa-errors" => {
                 let value = value_after(args, &mut index, "--aa-errors")?;
                 max_access_address_errors = parse_number(&value, "--aa-errors")?;
@@ -1440,6 +1988,10 @@ fn parse_decode_data_args(args: &[String]) -> Result<DecodeDataArgs> {
                 let value = value_after(args, &mut index, "--ltk")?;
                 long_term_key = Some(parse_fixed_hex(&value, "--ltk")?);
             }
+            option @ ("--keyring" | "--keys-btsnoop" | "--keys-bluez") => {
+                let value = value_after(args, &mut index, option)?;
+                key_sources.push(option, value);
+            }
             "--enc-req" => {
                 let value = value_after(args, &mut index, "--enc-req")?;
                 encryption_request = Some(parse_fixed_hex(&value, "--enc-req")?);
@@ -1461,6 +2013,17 @@ fn parse_decode_data_args(args: &[String]) -> Result<DecodeDataArgs> {
                 maximum_counter_skip = parse_u64(&value, "--max-counter-skip")?;
                 maximum_counter_skip_supplied = true;
             }
+            "--pcap-payload" => {
+                pcap_payload = Some(parse_pcap_payload(&value_after(
+                    args,
+                    &mut index,
+                    "--pcap-payload",
+                )?)?);
+            }
+            "--output-format" => {
+                output_format =
+                    parse_output_format(&value_after(args, &mut index, "--output-format")?)?;
+            }
             "-h" | "--help" => {
                 print!("{}", usage());
                 std::process::exit(0);
@@ -1520,8 +2083,13 @@ fn parse_decode_data_args(args: &[String]) -> Result<DecodeDataArgs> {
             "--session-key and --iv must be supplied together".to_owned(),
         ));
     }
+    if long_term_key.is_some() && !key_sources.is_empty() {
+        return Err(Error::InvalidConfiguration(
+            "--ltk and --keyring/--keys-btsnoop/--keys-bluez are mutually exclusive".to_owned(),
+        ));
+    }
     let exchange_material_option_count = [
-        long_term_key.is_some(),
+        long_term_key.is_some() || !key_sources.is_empty(),
         encryption_request.is_some(),
         encryption_response.is_some(),
     ]
@@ -1529,9 +2097,11 @@ fn parse_decode_data_args(args: &[String]) -> Result<DecodeDataArgs> {
     .filter(|supplied| *supplied)
     .count();
     if exchange_material_option_count != 0 && exchange_material_option_count != 3 {
-        return Err(Error::InvalidConfiguration(
-            "--ltk, --enc-req, and --enc-rsp must be supplied together".to_owned(),
-        ));
+        return Err(Error::InvalidConfiguration(if key_sources.is_empty() {
+            "--ltk, --enc-req, and --enc-rsp must be supplied together".to_owned()
+        } else {
+            "--keyring, --keys-btsnoop, and --keys-bluez require --enc-req and --enc-rsp".to_owned()
+        }));
     }
     if direct_material_option_count != 0 && exchange_material_option_count != 0 {
         return Err(Error::InvalidConfiguration(
@@ -1570,17 +2140,16 @@ fn parse_decode_data_args(args: &[String]) -> Result<DecodeDataArgs> {
             "--plaintext-l2cap-direction must match --decrypt-direction".to_owned(),
         ));
     }
-    let material = match (
+    let (material, alternatives) = match (
         session_key,
         initialization_vector,
-        long_term_key,
         encryption_request,
         encryption_response,
     ) {
-        (Some(session_key), Some(initialization_vector), None, None, None) => {
-            Some((session_key, initialization_vector))
+        (Some(session_key), Some(initialization_vector), None, None) => {
+            (Some((session_key, initialization_vector)), Vec::new())
         }
-        (None, None, Some(long_term_key), Some(request), Some(response)) => {
+        (None, None, Some(request), Some(response)) => {
             if request[0] != 0x03 {
                 return Err(Error::InvalidConfiguration(format!(
                     "--enc-req must begin with LL_ENC_REQ opcode 03, received {:02x}",
@@ -1593,30 +2162,57 @@ fn parse_decode_data_args(args: &[String]) -> Result<DecodeDataArgs> {
                     response[0]
                 )));
             }
-            let mut tracker = LeEncryptionMaterialTracker::new(long_term_key);
-            tracker.observe(
-                LinkDirection::CentralToPeripheral,
-                ControlPdu {
-                    opcode: request[0],
-                    parameters: &request[1..],
-                },
-            )?;
-            let material = tracker
-                .observe(
-                    LinkDirection::PeripheralToCentral,
-                    ControlPdu {
-                        opcode: response[0],
-                        parameters: &response[1..],
-                    },
-                )?
-                .ok_or_else(|| {
-                    Error::InvalidState(
-                        "LL encryption exchange did not produce session material".to_owned(),
-                    )
-                })?;
-            Some((material.session_key(), material.initialization_vector()))
+            let request_pdu = ControlPdu {
+                opcode: request[0],
+                parameters: &request[1..],
+            };
+            let long_term_keys = match long_term_key {
+                Some(long_term_key) => vec![long_term_key],
+                None => {
+                    let DecodedControlPdu::EncryptionRequest(request) = request_pdu.decode()?
+                    else {
+                        unreachable!("opcode 03 decodes as LL_ENC_REQ");
+                    };
+                    let long_term_keys = key_sources.load()?.long_term_keys_for(&request, &[]);
+                    if long_term_keys.is_empty() {
+                        return Err(Error::InvalidConfiguration(format!(
+                            "no keyring LTK matches --enc-req EDIV {} and Rand {}",
+                            request.encrypted_diversifier,
+                            print_hex(&request.random_number)
+                        )));
+                    }
+                    long_term_keys
+                }
+            };
+            let mut materials = Vec::with_capacity(long_term_keys.len());
+            for long_term_key in long_term_keys {
+                let mut tracker = LeEncryptionMaterialTracker::new(long_term_key);
+                tracker.observe(LinkDirection::CentralToPeripheral, request_pdu)?;
+                let material = tracker
+                    .observe(
+                        LinkDirection::PeripheralToCentral,
+                        ControlPdu {
+                            opcode: response[0],
+                            parameters: &response[1..],
+                        },
+                    )?
+                    .ok_or_else(|| {
+                        Error::InvalidState(
+                            "LL encryption exchange did not produce session material".to_owned(),
+                        )
+                    })?;
+                materials.push(material);
+            }
+            let alternatives = materials.split_off(1);
+            (
+                Some((
+                    materials[0].session_key(),
+                    materials[0].initialization_vector(),
+                )),
+                alternatives,
+            )
         }
-        (None, None, None, None, None) => None,
+        (None, None, None, None) => (None, Vec::new()),
         _ => unreachable!("partial or conflicting key-material options rejected above"),
     };
     let decryption = match (material, decryption_direction, initial_packet_counter) {
@@ -1631,6 +2227,7 @@ fn parse_decode_data_args(args: &[String]) -> Result<DecodeDataArgs> {
             Some(DecodeDataDecryptionArgs {
                 session_key,
                 initialization_vector,
+                alternatives,
                 direction,
                 initial_packet_counter: packet_counter,
                 maximum_counter_skip,
@@ -1639,12 +2236,25 @@ fn parse_decode_data_args(args: &[String]) -> Result<DecodeDataArgs> {
         (None, None, None) => None,
         _ => unreachable!("partial decryption state options rejected above"),
     };
+    if pcap_payload.is_some() && output_pcap.is_none() {
+        return Err(Error::InvalidConfiguration(
+            "--pcap-payload requires --output-pcap".to_owned(),
+        ));
+    }
+    let pcap_payload = pcap_payload.unwrap_or(PcapPayload::Ciphertext);
+    if pcap_payload != PcapPayload::Ciphertext && decryption.is_none() {
+        return Err(Error::InvalidConfiguration(
+            "--pcap-payload plaintext or both requires the complete decryption option set"
+                .to_owned(),
+        ));
+    }
 
     Ok(DecodeDataArgs {
         input: input.ok_or_else(|| {
             Error::InvalidConfiguration("decode-data requires --input FILE".to_owned())
         })?,
         format,
+        framing,
         channel,
         phy,
         sample_rate_hz,
@@ -1653,11 +2263,14 @@ fn parse_decode_data_args(args: &[String]) -> Result<DecodeDataArgs> {
         max_samples,
         block_samples,
         max_access_address_errors,
+        crc_failure_policy,
         output_pcap,
         capture_start_ns,
         plaintext_l2cap_direction,
         maximum_l2cap_payload_length,
         decryption,
+        pcap_payload,
+        output_format,
     })
 }
 
@@ -1704,8 +2317,10 @@ fn parse_encryption_trace_packet(value: &str) -> Result<DirectionalDataPacketArg
 
 fn parse_encryption_trace_args(args: &[String]) -> Result<EncryptionTraceArgs> {
     let mut long_term_key = None;
+    let mut key_sources = KeySourceArgs::default();
     let mut maximum_counter_skip = 0;
     let mut packets = Vec::new();
+    let mut output_format = OutputFormat::Text;
     let mut index = 0;
     while index < args.len() {
         match args[index].as_str() {
@@ -1715,6 +2330,10 @@ fn parse_encryption_trace_args(args: &[String]) -> Result<EncryptionTraceArgs> {
                     "--ltk",
                 )?);
             }
+            option @ ("--keyring" | "--keys-btsnoop" | "--keys-bluez") => {
+                let value = value_after(args, &mut index, option)?;
+                key_sources.push(option, value);
+            }
             "--max-counter-skip" => {
                 maximum_counter_skip = parse_u64(
                     &value_after(args, &mut index, "--max-counter-skip")?,
@@ -1726,6 +2345,10 @@ fn parse_encryption_trace_args(args: &[String]) -> Result<EncryptionTraceArgs> {
                     args, &mut index, "--packet",
                 )?)?);
             }
+            "--output-format" => {
+                output_format =
+                    parse_output_format(&value_after(args, &mut index, "--output-format")?)?;
+            }
             unknown => {
                 return Err(Error::InvalidConfiguration(format!(
                     "unknown encryption-trace option {unknown:?}"
@@ -1734,8 +2357,20 @@ fn parse_encryption_trace_args(args: &[String]) -> Result<EncryptionTraceArgs> {
         }
         index += 1;
     }
-    let long_term_key = long_term_key
-        .ok_or_else(|| Error::InvalidConfiguration("encryption-trace requires --ltk".to_owned()))?;
+    match (long_term_key.is_some(), key_sources.is_empty()) {
+        (false, true) => {
+            return Err(Error::InvalidConfiguration(
+                "encryption-trace requires --ltk or a --keyring, --keys-btsnoop, or --keys-bluez key source"
+                    .to_owned(),
+            ));
+        }
+        (true, false) => {
+            return Err(Error::InvalidConfiguration(
+                "--ltk and --keyring/--keys-btsnoop/--keys-bluez are mutually exclusive".to_owned(),
+            ));
+        }
+        _ => {}
+    }
     if packets.is_empty() {
         return Err(Error::InvalidConfiguration(
             "encryption-trace requires at least one --packet".to_owned(),
@@ -1748,8 +2383,291 @@ fn parse_encryption_trace_args(args: &[String]) -> Result<EncryptionTraceArgs> {
     }
     Ok(EncryptionTraceArgs {
         long_term_key,
+        key_sources,
         maximum_counter_skip,
         packets,
+        output_format,
+    })
+}
+
+fn parse_keyring_args(args: &[String]) -> Result<KeyringArgs> {
+    let action = args.first().map(String::as_str);
+    if !matches!(action, Some("add" | "list" | "remove" | "import")) {
+        return Err(Error::InvalidConfiguration(
+            "keyring requires an action: add, list, remove, or import".to_owned(),
+        ));
+    }
+    let mut path = None;
+    let mut address = None;
+    let mut long_term_key = None;
+    let mut encrypted_diversifier = None;
+    let mut random_number = None;
+    let mut identity_resolving_key = None;
+    let mut signature_key = None;
+    let mut link_key = None;
+    let mut encrypted_data_key = None;
+    let mut encrypted_data_iv = None;
+    let mut cross_transport = None;
+    let mut kind = None;
+    let mut show_keys = false;
+    let mut key_sources = KeySourceArgs::default();
+    let mut output_format = OutputFormat::Text;
+    let mut index = 1;
+    while index < args.len() {
+        match args[index].as_str() {
+            "--keyring" => path = Some(PathBuf::from(value_after(args, &mut index, "--keyring")?)),
+            "--address" => {
+                let value = value_after(args, &mut index, "--address")?;
+                address = Some(parse_device_address(&value, "--address")?);
+            }
+            "--ltk" => {
+                let value = value_after(args, &mut index, "--ltk")?;
+                long_term_key = Some(parse_fixed_hex(&value, "--ltk")?);
+            }
+            "--ediv" => {
+                let value = value_after(args, &mut index, "--ediv")?;
+                let ediv = parse_u32(&value, "--ediv")?;
+                encrypted_diversifier = Some(u16::try_from(ediv).map_err(|_| {
+                    Error::InvalidConfiguration(format!("--ediv {ediv} exceeds 16 bits"))
+                })?);
+            }
+            "--rand" => {
+                let value = value_after(args, &mut index, "--rand")?;
+                random_number = Some(parse_fixed_hex(&value, "--rand")?);
+            }
+            "--irk" => {
+                let value = value_after(args, &mut index, "--irk")?;
+                identity_resolving_key = Some(parse_fixed_hex(&value, "--irk")?);
+            }
+            "--csrk" => {
+                let value = value_after(args, &mut index, "--csrk")?;
+                signature_key = Some(parse_fixed_hex(&value, "--csrk")?);
+            }
+            "--link-key" => {
+                let value = value_after(args, &mut index, "--link-key")?;
+                link_key = Some(parse_fixed_hex(&value, "--link-key")?);
+            }
+            "--ead-key" => {
+                let value = value_after(args, &mut index, "--ead-key")?;
+                encrypted_data_key = Some(parse_fixed_hex(&value, "--ead-key")?);
+            }
+            "--ead-iv" => {
+                let value = value_after(args, &mut index, "--ead-iv")?;
+                encrypted_data_iv = Some(parse_fixed_hex(&value, "--ead-iv")?);
+            }
+            "--ctkd" => {
+                cross_transport = Some(match value_after(args, &mut index, "--ctkd")?.as_str() {
+                    "h6" => false,
+                    "h7" => true,
+                    value => {
+                        return Err(Error::InvalidConfiguration(format!(
+                            "invalid value {value:?} for --ctkd; expected h6 or h7"
+                        )));
+                    }
+                });
+            }
+            "--type" => {
+                let value = value_after(args, &mut index, "--type")?;
+                kind = Some(KeyringEntryKind::parse(&value).map_err(|_| {
+                    Error::InvalidConfiguration(format!(
+                        "invalid value {value:?} for --type; expected ltk, irk, csrk, link-key, or ead-key"
+                    ))
+                })?);
+            }
+            "--show-keys" => show_keys = true,
+            option @ ("--keys-btsnoop" | "--keys-bluez") => {
+                let value = value_after(args, &mut index, option)?;
+                key_sources.push(option, value);
+            }
+            "--output-format" => {
+                output_format =
+                    parse_output_format(&value_after(args, &mut index, "--output-format")?)?;
+            }
+            unknown => {
+                return Err(Error::InvalidConfiguration(format!(
+                    "unknown keyring option {unknown:?}"
+                )));
+            }
+        }
+        index += 1;
+    }
+    let path = path
+        .ok_or_else(|| Error::InvalidConfiguration("keyring requires --keyring FILE".to_owned()))?;
+    let supplied = |options: &[(bool, &'static str)]| {
+        options
+            .iter()
+            .find(|(supplied, _)| *supplied)
+            .map(|(_, option)| *option)
+    };
+    let misplaced = match action {
+        Some("add") => supplied(&[
+            (kind.is_some(), "--type"),
+            (show_keys, "--show-keys"),
+            (!key_sources.is_empty(), "--keys-btsnoop/--keys-bluez"),
+        ]),
+        Some("list") => supplied(&[
+            (address.is_some(), "--address"),
+            (long_term_key.is_some(), "--ltk"),
+            (identity_resolving_key.is_some(), "--irk"),
+            (signature_key.is_some(), "--csrk"),
+            (link_key.is_some(), "--link-key"),
+            (encrypted_data_key.is_some(), "--ead-key"),
+            (encrypted_data_iv.is_some(), "--ead-iv"),
+            (cross_transport.is_some(), "--ctkd"),
+            (kind.is_some(), "--type"),
+            (!key_sources.is_empty(), "--keys-btsnoop/--keys-bluez"),
+        ]),
+        Some("remove") => supplied(&[
+            (long_term_key.is_some(), "--ltk"),
+            (identity_resolving_key.is_some(), "--irk"),
+            (signature_key.is_some(), "--csrk"),
+            (link_key.is_some(), "--link-key"),
+            (encrypted_data_key.is_some(), "--ead-key"),
+            (encrypted_data_iv.is_some(), "--ead-iv"),
+            (cross_transport.is_some(), "--ctkd"),
+            (show_keys, "--show-keys"),
+            (!key_sources.is_empty(), "--keys-btsnoop/--keys-bluez"),
+        ]),
+        _ => supplied(&[
+            (address.is_some(), "--address"),
+            (long_term_key.is_some(), "--ltk"),
+            (identity_resolving_key.is_some(), "--irk"),
+            (signature_key.is_some(), "--csrk"),
+            (link_key.is_some(), "--link-key"),
+            (encrypted_data_key.is_some(), "--ead-key"),
+            (encrypted_data_iv.is_some(), "--ead-iv"),
+            (cross_transport.is_some(), "--ctkd"),
+            (kind.is_some(), "--type"),
+            (show_keys, "--show-keys"),
+        ]),
+    };
+    if let Some(option) = misplaced {
+        return Err(Error::InvalidConfiguration(format!(
+            "keyring {} does not accept {option}",
+            action.expect("action checked above")
+        )));
+    }
+    if (encrypted_diversifier.is_some() || random_number.is_some()) && long_term_key.is_none() {
+        return Err(Error::InvalidConfiguration(
+            "--ediv and --rand require --ltk".to_owned(),
+        ));
+    }
+    if encrypted_data_key.is_some() != encrypted_data_iv.is_some() {
+        return Err(Error::InvalidConfiguration(
+            "--ead-key and --ead-iv must be given together".to_owned(),
+        ));
+    }
+    let action = match action {
+        Some("add") if cross_transport.is_some() => {
+            let ct2 = cross_transport.expect("guard checked --ctkd");
+            let address = address.ok_or_else(|| {
+                Error::InvalidConfiguration("--ctkd requires --address".to_owned())
+            })?;
+            if encrypted_diversifier.is_some() || random_number.is_some() {
+                return Err(Error::InvalidConfiguration(
+                    "--ctkd LTKs use EDIV 0 and Rand 0; drop --ediv and --rand".to_owned(),
+                ));
+            }
+            let mut keyring = LeKeyring::new();
+            match (long_term_key, link_key) {
+                (Some(key), None) => keyring.add_long_term_key_with_link_key(address, key, ct2),
+                (None, Some(key)) => keyring.add_link_key_with_long_term_key(address, key, ct2),
+                _ => {
+                    return Err(Error::InvalidConfiguration(
+                        "--ctkd requires exactly one of --ltk or --link-key".to_owned(),
+                    ));
+                }
+            };
+            if let Some(key) = identity_resolving_key {
+                keyring.add_identity_resolving_key(IdentityResolvingKeyEntry { address, key });
+            }
+            if let Some(key) = signature_key {
+                keyring.add_signature_key(SignatureKeyEntry { address, key });
+            }
+            if let Some((session_key, initialization_vector)) =
+                encrypted_data_key.zip(encrypted_data_iv)
+            {
+                keyring.add_encrypted_data_key(EncryptedDataKeyEntry {
+                    address: Some(address),
+                    key_material: EncryptedDataKeyMaterial {
+                        session_key,
+                        initialization_vector,
+                    },
+                });
+            }
+            KeyringAction::Add(Box::new(keyring))
+        }
+        Some("add") => {
+            let mut keyring = LeKeyring::new();
+            if let Some(key) = long_term_key {
+                keyring.add_long_term_key(LongTermKeyEntry {
+                    address,
+                    encrypted_diversifier: encrypted_diversifier.unwrap_or(0),
+                    random_number: random_number.unwrap_or([0; 8]),
+                    key,
+                });
+            }
+            if let Some((session_key, initialization_vector)) =
+                encrypted_data_key.zip(encrypted_data_iv)
+            {
+                keyring.add_encrypted_data_key(EncryptedDataKeyEntry {
+                    address,
+                    key_material: EncryptedDataKeyMaterial {
+                        session_key,
+                        initialization_vector,
+                    },
+                });
+            }
+            let addressed_keys = [identity_resolving_key, signature_key, link_key];
+            if addressed_keys.iter().any(Option::is_some) {
+                let address = address.ok_or_else(|| {
+                    Error::InvalidConfiguration(
+                        "--irk, --csrk, and --link-key require --address".to_owned(),
+                    )
+                })?;
+                if let Some(key) = identity_resolving_key {
+                    keyring.add_identity_resolving_key(IdentityResolvingKeyEntry { address, key });
+                }
+                if let Some(key) = signature_key {
+                    keyring.add_signature_key(SignatureKeyEntry { address, key });
+                }
+                if let Some(key) = link_key {
+                    keyring.add_link_key(LinkKeyEntry {
+                        address: address.0,
+                        key,
+                    });
+                }
+            }
+            if keyring.is_empty() {
+                return Err(Error::InvalidConfiguration(
+                    "keyring add requires --ltk, --irk, --csrk, --link-key, or --ead-key"
+                        .to_owned(),
+                ));
+            }
+            KeyringAction::Add(Box::new(keyring))
+        }
+        Some("list") => KeyringAction::List { show_keys },
+        Some("remove") => KeyringAction::Remove {
+            address: address
+                .ok_or_else(|| {
+                    Error::InvalidConfiguration("keyring remove requires --address".to_owned())
+                })?
+                .0,
+            kind,
+        },
+        _ => {
+            if key_sources.is_empty() {
+                return Err(Error::InvalidConfiguration(
+                    "keyring import requires --keys-btsnoop or --keys-bluez".to_owned(),
+                ));
+            }
+            KeyringAction::Import(key_sources)
+        }
+    };
+    Ok(KeyringArgs {
+        path,
+        action,
+        output_format,
     })
 }
 
@@ -1785,12 +2703,17 @@ fn parse_l2cap_trace_pdu(value: &str) -> Result<L2capPdu> {
 
 fn parse_l2cap_trace_args(args: &[String]) -> Result<L2capTraceArgs> {
     let mut pdus = Vec::new();
+    let mut output_format = OutputFormat::Text;
     let mut index = 0;
     while index < args.len() {
         match args[index].as_str() {
             "--pdu" => pdus.push(parse_l2cap_trace_pdu(&value_after(
                 args, &mut index, "--pdu",
             )?)?),
+            "--output-format" => {
+                output_format =
+                    parse_output_format(&value_after(args, &mut index, "--output-format")?)?;
+            }
             unknown => {
                 return Err(Error::InvalidConfiguration(format!(
                     "unknown l2cap-trace option {unknown:?}"
@@ -1804,172 +2727,575 @@ fn parse_l2cap_trace_args(args: &[String]) -> Result<L2capTraceArgs> {
             "l2cap-trace requires at least one --pdu".to_owned(),
         ));
     }
-    Ok(L2capTraceArgs { pdus })
+    Ok(L2capTraceArgs {
+        pdus,
+        output_format,
+    })
 }
 
-fn parse_capture_args(args: &[String], command: CaptureCommand) -> Result<CaptureArgs> {
+fn parse_serve_args(args: &[String]) -> Result<ServeArgs> {
     let mut device = None;
     let mut identifier = None;
-    let mut channel = None;
-    let mut access_address = None;
-    let mut crc_init = None;
-    let mut phy = LeUncodedPhy::Le1M;
-    let mut sample_rate_hz = 4_000_000u32;
-    let mut bandwidth_hz = 2_000_000u32;
-    let mut gain_db = 30.0f32;
-    let mut rx_channel = 0u8;
-    let mut duration = Duration::from_secs(10);
-    let mut block_samples = 8_192usize;
-    let mut read_timeout_ms = 1_000u64;
-    let mut max_access_address_errors = 1u8;
-    let mut output_pcap = None;
-    let mut capture_start_ns = None;
-    let mut assert_central_observations = false;
-    let mut tracking_options_supplied = false;
-    let mut tracking_first_event_counter = None;
-    let mut tracking_channel_map = None;
-    let mut tracking_channel_selection_algorithm = None;
-    let mut tracking_hop_increment = 5u8;
-    let mut tracking_interval = None;
-    let mut tracking_latency = 0u16;
-    let mut tracking_supervision_timeout = 3_200u16;
-    let mut tracking_peer_clock_accuracy = SleepClockAccuracy::new(0)?;
-    let mut tracking_receiver_clock_accuracy_ppm = 20u32;
-    let mut tracking_maximum_event_advance = 32u16;
+    let mut listen = None;
+    let mut stream_format = IqFormat::F32Le;
+    let mut stream_framing = IqStreamFraming::Raw;
+    let mut stream_options_supplied = false;
+    let mut max_connections = None;
     let mut index = 0;
-
     while index < args.len() {
         match args[index].as_str() {
             "--device" => device = Some(value_after(args, &mut index, "--device")?),
             "--identifier" => identifier = Some(value_after(args, &mut index, "--identifier")?),
+            "--listen" => listen = Some(value_after(args, &mut index, "--listen")?),
+            "--format" => {
+                stream_format = IqFormat::parse(&value_after(args, &mut index, "--format")?)?;
+                stream_options_supplied = true;
+            }
+            "--framing" => {
+                stream_framing =
+                    IqStreamFraming::parse(&value_after(args, &mut index, "--framing")?)?;
+                stream_options_supplied = true;
+            }
+            "--max-connections" => {
+                let value = value_after(args, &mut index, "--max-connections")?;
+                let value: u64 = parse_number(&value, "--max-connections")?;
+                if value == 0 {
+                    return Err(Error::InvalidConfiguration(
+                        "--max-connections must be greater than zero".to_owned(),
+                    ));
+                }
+                max_connections = Some(value);
+            }
+            "-h" | "--help" => {
+                print!("{}", usage());
+                std::process::exit(0);
+            }
+            unknown => {
+                return Err(Error::InvalidConfiguration(format!(
+                    "unknown serve option {unknown:?}"
+                )));
+            }
+        }
+        index += 1;
+    }
+    let device = device.ok_or_else(|| {
+        Error::InvalidConfiguration(
+            "serve requires --device bladerf|limesdr|xtrx|stream".to_owned(),
+        )
+    })?;
+    if stream_options_supplied && !device.eq_ignore_ascii_case("stream") {
+        return Err(Error::InvalidConfiguration(
+            "--format and --framing apply only to --device stream".to_owned(),
+        ));
+    }
+    Ok(ServeArgs {
+        device,
+        identifier,
+        listen: listen.ok_or_else(|| {
+            Error::InvalidConfiguration("serve requires --listen HOST:PORT".to_owned())
+        })?,
+        stream_format,
+        stream_framing,
+        max_connections,
+    })
+}
+
+fn parse_synthesize_packet(value: &str) -> Result<SynthesizePacketArg> {
+    let invalid = || {
+        Error::InvalidConfiguration(format!(
+            "invalid --packet {value:?}; expected PHY:SAMPLE:PDUHEX"
+        ))
+    };
+    let mut fields = value.split(':');
+    let (Some(phy), Some(sample), Some(pdu), None) =
+        (fields.next(), fields.next(), fields.next(), fields.next())
+    else {
+        return Err(invalid());
+    };
+    Ok(SynthesizePacketArg {
+        phy: parse_connection_phy(phy, "--packet PHY")?,
+        start_sample: parse_number(sample, "--packet SAMPLE")?,
+        pdu: parse_hex_bytes(pdu, "--packet PDUHEX")?,
+    })
+}
+
+fn parse_multipath_tap(value: &str) -> Result<MultipathTap> {
+    let invalid = || {
+        Error::InvalidConfiguration(format!(
+            "invalid --multipath {value:?}; expected DELAY:GAIN[:DEGREES]"
+        ))
+    };
+    let mut fields = value.split(':');
+    let delay_samples = fields
+        .next()
+        .and_then(|field| field.parse().ok())
+        .ok_or_else(invalid)?;
+    let gain: f32 = fields
+        .next()
+        .and_then(|field| field.parse().ok())
+        .ok_or_else(invalid)?;
+    let degrees: f32 = match fields.next() {
+        Some(field) => field.parse().map_err(|_| invalid())?,
+        None => 0.0,
+    };
+    if fields.next().is_some() {
+        return Err(invalid());
+    }
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    Ok(MultipathTap {
+        delay_samples,
+        gain: Complex32::new(gain * cos, gain * sin),
+    })
+}
+
+fn parse_synthesize_args(args: &[String]) -> Result<SynthesizeArgs> {
+    let mut output = None;
+    let mut format = IqFormat::F32Le;
+    let mut channel = None;
+    let mut sample_rate_hz = 4_000_000u32;
+    let mut total_samples = None;
+    let mut access_address = None;
+    let mut crc_init = None;
+    let mut packets = Vec::new();
+    let mut shape = GfskShape::default();
+    let mut impairments = ChannelImpairments::default();
+    let mut index = 0;
+    while index < args.len() {
+        match args[index].as_str() {
+            "--output" => output = Some(PathBuf::from(value_after(args, &mut index, "--output")?)),
+            "--format" => format = IqFormat::parse(&value_after(args, &mut index, "--format")?)?,
             "--channel" => {
                 let value = value_after(args, &mut index, "--channel")?;
                 channel = Some(BleChannel::new(parse_number(&value, "--channel")?)?);
             }
-            "--access-address" if command == CaptureCommand::Data => {
+            "--sample-rate" => {
+                let value = value_after(args, &mut index, "--sample-rate")?;
+                sample_rate_hz = parse_number(&value, "--sample-rate")?;
+            }
+            "--samples" => {
+                let value = value_after(args, &mut index, "--samples")?;
+                total_samples = Some(parse_number(&value, "--samples")?);
+            }
+            "--access-address" => {
                 let value = value_after(args, &mut index, "--access-address")?;
                 access_address = Some(parse_u32(&value, "--access-address")?);
             }
-            "--crc-init" if command == CaptureCommand::Data => {
+            "--crc-init" => {
                 let value = value_after(args, &mut index, "--crc-init")?;
                 crc_init = Some(parse_u32(&value, "--crc-init")?);
             }
-            "--phy" if command == CaptureCommand::Data => {
-                let value = value_after(args, &mut index, "--phy")?;
-                phy = parse_uncoded_phy(&value, "--phy")?;
+            "--packet" => packets.push(parse_synthesize_packet(&value_after(
+                args, &mut index, "--packet",
+            )?)?),
+            "--modulation-index" => {
+                let value = value_after(args, &mut index, "--modulation-index")?;
+                shape.modulation_index = parse_number(&value, "--modulation-index")?;
+            }
+            "--bt" => {
+                let value = value_after(args, &mut index, "--bt")?;
+                shape.bandwidth_time = parse_number(&value, "--bt")?;
+            }
+            "--snr" => {
+                let value = value_after(args, &mut index, "--snr")?;
+                impairments.snr_db = Some(parse_number(&value, "--snr")?);
+            }
+            "--cfo" => {
+                let value = value_after(args, &mut index, "--cfo")?;
+                impairments.carrier_offset_hz = parse_number(&value, "--cfo")?;
+            }
+            "--drift-ppm" => {
+                let value = value_after(args, &mut index, "--drift-ppm")?;
+                impairments.clock_drift_ppm = parse_number(&value, "--drift-ppm")?;
+            }
+            "--multipath" => impairments.multipath.push(parse_multipath_tap(&value_after(
+                args,
+                &mut index,
+                "--multipath",
+            )?)?),
+            "--seed" => {
+                let value = value_after(args, &mut index, "--seed")?;
+                impairments.noise_seed = parse_u64(&value, "--seed")?;
             }
-            "--assert-central-observations" if command == CaptureCommand::Data => {
-                assert_central_observations = true;
-                tracking_options_supplied = true;
+            "-h" | "--help" => {
+                print!("{}", usage());
+                std::process::exit(0);
             }
-            "--first-event" if command == CaptureCommand::Data => {
-                let value = value_after(args, &mut index, "--first-event")?;
-                tracking_first_event_counter = Some(parse_number(&value, "--first-event")?);
-                tracking_options_supplied = true;
+            unknown => {
+                return Err(Error::InvalidConfiguration(format!(
+                    "unknown synthesize option {unknown:?}"
+                )));
             }
-            "--channel-map" if command == CaptureCommand::Data => {
-                tracking_channel_map = Some(parse_channel_map(&value_after(
+        }
+        index += 1;
+    }
+
+    let channel = channel
+        .ok_or_else(|| Error::InvalidConfiguration("synthesize requires --channel".to_owned()))?;
+    let frame_config = match (access_address, crc_init) {
+        (None, None) if channel.is_primary_advertising() => LeFrameConfig::advertising(),
+        (None, None) => LeFrameConfig::secondary_advertising(),
+        (Some(access_address), Some(crc_init)) if channel.is_primary_advertising() => {
+            if access_address != LE_ADV_ACCESS_ADDRESS || crc_init != LE_ADV_CRC_INIT {
+                return Err(Error::InvalidConfiguration(
+                    "primary advertising channels use the advertising access address and CRC initialization".to_owned(),
+                ));
+            }
+            LeFrameConfig::advertising()
+        }
+        (Some(LE_ADV_ACCESS_ADDRESS), Some(crc_init)) => {
+            LeFrameConfig::periodic_advertising(LE_ADV_ACCESS_ADDRESS, crc_init)?
+        }
+        (Some(access_address), Some(crc_init)) => LeFrameConfig::data(access_address, crc_init)?,
+        _ => {
+            return Err(Error::InvalidConfiguration(
+                "--access-address and --crc-init must be supplied together".to_owned(),
+            ));
+        }
+    };
+    if packets.is_empty() {
+        return Err(Error::InvalidConfiguration(
+            "synthesize requires at least one --packet PHY:SAMPLE:PDUHEX".to_owned(),
+        ));
+    }
+    Ok(SynthesizeArgs {
+        output: output.ok_or_else(|| {
+            Error::InvalidConfiguration("synthesize requires --output FILE".to_owned())
+        })?,
+        format,
+        channel,
+        sample_rate_hz,
+        total_samples,
+        frame_config,
+        packets,
+        shape,
+        impairments,
+    })
+}
+
+/// Core specification sample LL_ENC_REQ and LL_ENC_RSP control payloads.
+const SCENARIO_DEFAULT_ENC_REQ: [u8; 23] = [
+    0x03, 0x90, 0x78, 0x56, 0x34, 0x12, 0xef, 0xcd, 0xab, 0x74, 0x24, 0x13, 0x02, 0xf1, 0xe0, 0xdf,
+    0xce, 0xbd, 0xac, 0x24, 0xab, 0xdc, 0xba,
+];
+const SCENARIO_DEFAULT_ENC_RSP: [u8; 13] = [
+    0x04, 0x79, 0x68, 0x57, 0x46, 0x35, 0x24, 0x13, 0x02, 0xbe, 0xba, 0xaf, 0xde,
+];
+
+fn parse_device_address(value: &str, option: &str) -> Result<(DeviceAddress, AddressKind)> {
+    let invalid = || {
+        Error::InvalidConfiguration(format!(
+            "invalid value {value:?} for {option}; expected XX:XX:XX:XX:XX:XX[/public|/random]"
+        ))
+    };
+    let (address, kind) = match value.split_once('/') {
+        Some((address, "public")) => (address, AddressKind::Public),
+        Some((address, "random")) => (address, AddressKind::Random),
+        Some(_) => return Err(invalid()),
+        None => (value, AddressKind::Public),
+    };
+    let mut octets = [0u8; 6];
+    let mut fields = address.split(':');
+    // Displayed addresses are most significant octet first; the air order is reversed.
+    for octet in octets.iter_mut().rev() {
+        let field = fields
+            .next()
+            .filter(|field| field.len() == 2)
+            .ok_or_else(invalid)?;
+        *octet = u8::from_str_radix(field, 16).map_err(|_| invalid())?;
+    }
+    if fields.next().is_some() {
+        return Err(invalid());
+    }
+    Ok((DeviceAddress(octets), kind))
+}
+
+fn scenario_fields<'a, const N: usize>(
+    value: &'a str,
+    option: &str,
+    expected: &str,
+) -> Result<[&'a str; N]> {
+    let fields: Vec<&str> = value.split(':').collect();
+    fields.try_into().map_err(|_| {
+        Error::InvalidConfiguration(format!("invalid {option} {value:?}; expected {expected}"))
+    })
+}
+
+fn parse_scenario_action(option: &str, value: &str) -> Result<ScenarioAction> {
+    match option {
+        "--channel-map-update" => {
+            let [event, instant, map] = scenario_fields(value, option, "EVENT:INSTANT:MAPHEX")?;
+            Ok(ScenarioAction {
+                event_counter: parse_number(event, "--channel-map-update EVENT")?,
+                kind: ScenarioActionKind::ChannelMapUpdate(ChannelMapInd {
+                    channel_map: parse_channel_map(map)?,
+                    instant: parse_number(instant, "--channel-map-update INSTANT")?,
+                }),
+            })
+        }
+        "--connection-update" => {
+            let [
+                event,
+                instant,
+                window_size,
+                window_offset,
+                interval,
+                latency,
+                timeout,
+            ] = scenario_fields(
+                value,
+                option,
+                "EVENT:INSTANT:WINSIZE:WINOFFSET:INTERVAL:LATENCY:TIMEOUT",
+            )?;
+            Ok(ScenarioAction {
+                event_counter: parse_number(event, "--connection-update EVENT")?,
+                kind: ScenarioActionKind::ConnectionUpdate(ConnectionUpdateInd::new(
+                    parse_number(window_size, "--connection-update WINSIZE")?,
+                    parse_number(window_offset, "--connection-update WINOFFSET")?,
+                    parse_number(interval, "--connection-update INTERVAL")?,
+                    parse_number(latency, "--connection-update LATENCY")?,
+                    parse_number(timeout, "--connection-update TIMEOUT")?,
+                    parse_number(instant, "--connection-update INSTANT")?,
+                )?),
+            })
+        }
+        "--l2cap" => {
+            let [event, direction, channel_id, payload] =
+                scenario_fields(value, option, "EVENT:DIRECTION:CID:HEX")?;
+            Ok(ScenarioAction {
+                event_counter: parse_number(event, "--l2cap EVENT")?,
+                kind: ScenarioActionKind::L2cap {
+                    direction: parse_link_direction(direction, "--l2cap DIRECTION")?,
+                    channel_id: u16::try_from(parse_u32(channel_id, "--l2cap CID")?).map_err(
+                        |_| {
+                            Error::InvalidConfiguration(format!(
+                                "--l2cap CID {channel_id:?} exceeds 16 bits"
+                            ))
+                        },
+                    )?,
+                    payload: parse_hex_bytes(payload, "--l2cap HEX")?,
+                },
+            })
+        }
+        "--att" => {
+            let [event, direction, payload] =
+                scenario_fields(value, option, "EVENT:DIRECTION:HEX")?;
+            Ok(ScenarioAction {
+                event_counter: parse_number(event, "--att EVENT")?,
+                kind: ScenarioActionKind::L2cap {
+                    direction: parse_link_direction(direction, "--att DIRECTION")?,
+                    channel_id: ATT_FIXED_CHANNEL_ID,
+                    payload: parse_hex_bytes(payload, "--att HEX")?,
+                },
+            })
+        }
+        _ => unreachable!("only scenario action options are dispatched here"),
+    }
+}
+
+fn parse_scenario_encryption(
+    long_term_key: [u8; 16],
+    request: [u8; 23],
+    response: [u8; 13],
+) -> Result<ScenarioEncryption> {
+    let request = match (ControlPdu {
+        opcode: request[0],
+        parameters: &request[1..],
+    })
+    .decode()?
+    {
+        DecodedControlPdu::EncryptionRequest(request) => request,
+        _ => {
+            return Err(Error::InvalidConfiguration(format!(
+                "--enc-req must begin with LL_ENC_REQ opcode 03, received {:02x}",
+                request[0]
+            )));
+        }
+    };
+    let response = match (ControlPdu {
+        opcode: response[0],
+        parameters: &response[1..],
+    })
+    .decode()?
+    {
+        DecodedControlPdu::EncryptionResponse(response) => response,
+        _ => {
+            return Err(Error::InvalidConfiguration(format!(
+                "--enc-rsp must begin with LL_ENC_RSP opcode 04, received {:02x}",
+                response[0]
+            )));
+        }
+    };
+    Ok(ScenarioEncryption {
+        long_term_key,
+        request,
+        response,
+    })
+}
+
+fn parse_scenario_args(args: &[String]) -> Result<ScenarioArgs> {
+    let mut output = None;
+    let mut format = IqFormat::F32Le;
+    let mut iq_channel = None;
+    let mut output_pcap = None;
+    let mut capture_start_ns = 0u64;
+    let mut impairments = ChannelImpairments::default();
+    let mut sample_rate_hz = 4_000_000u32;
+    let mut advertising_channel = BleChannel::new(37)?;
+    let mut advertiser = (
+        DeviceAddress([0x06, 0x05, 0x04, 0x03, 0x02, 0x01]),
+        AddressKind::Public,
+    );
+    let mut initiator = (
+        DeviceAddress([0x11, 0x22, 0x33, 0x44, 0x55, 0xc6]),
+        AddressKind::Random,
+    );
+    let mut advertising_data = vec![0x02, 0x01, 0x06];
+    let mut advertising_sample = 1_000u64;
+    let mut connect_request = ConnectRequest {
+        access_address: 0x5065_4b4f,
+        crc_init: 0x7a_4f21,
+        window_size: 2,
+        window_offset: 1,
+        interval: 24,
+        latency: 0,
+        supervision_timeout: 100,
+        channel_map: [0xff, 0xff, 0xff, 0xff, 0x1f],
+        hop_increment: 7,
+        sleep_clock_accuracy: 5,
+        channel_selection_algorithm: ChannelSelectionAlgorithm::Csa2,
+    };
+    let mut event_count = 16u16;
+    let mut actions = Vec::new();
+    let mut encrypt_event = None;
+    let mut long_term_key = None;
+    let mut encryption_request = SCENARIO_DEFAULT_ENC_REQ;
+    let mut encryption_response = SCENARIO_DEFAULT_ENC_RSP;
+    let mut index = 0;
+    while index < args.len() {
+        match args[index].as_str() {
+            "--output" => output = Some(PathBuf::from(value_after(args, &mut index, "--output")?)),
+            "--format" => format = IqFormat::parse(&value_after(args, &mut index, "--format")?)?,
+            "--iq-channel" => {
+                let value = value_after(args, &mut index, "--iq-channel")?;
+                iq_channel = Some(BleChannel::new(parse_number(&value, "--iq-channel")?)?);
+            }
+            "--output-pcap" => {
+                output_pcap = Some(PathBuf::from(value_after(
                     args,
                     &mut index,
-                    "--channel-map",
-                )?)?);
-                tracking_options_supplied = true;
+                    "--output-pcap",
+                )?));
             }
-            "--csa" if command == CaptureCommand::Data => {
+            "--capture-start-ns" => {
+                let value = value_after(args, &mut index, "--capture-start-ns")?;
+                capture_start_ns = parse_u64(&value, "--capture-start-ns")?;
+            }
+            "--sample-rate" => {
+                let value = value_after(args, &mut index, "--sample-rate")?;
+                sample_rate_hz = parse_number(&value, "--sample-rate")?;
+            }
+            "--advertising-channel" => {
+                let value = value_after(args, &mut index, "--advertising-channel")?;
+                advertising_channel =
+                    BleChannel::new(parse_number(&value, "--advertising-channel")?)?;
+            }
+            "--advertiser" => {
+                let value = value_after(args, &mut index, "--advertiser")?;
+                advertiser = parse_device_address(&value, "--advertiser")?;
+            }
+            "--initiator" => {
+                let value = value_after(args, &mut index, "--initiator")?;
+                initiator = parse_device_address(&value, "--initiator")?;
+            }
+            "--adv-data" => {
+                let value = value_after(args, &mut index, "--adv-data")?;
+                advertising_data = parse_hex_bytes(&value, "--adv-data")?;
+            }
+            "--advertising-sample" => {
+                let value = value_after(args, &mut index, "--advertising-sample")?;
+                advertising_sample = parse_u64(&value, "--advertising-sample")?;
+            }
+            "--access-address" => {
+                let value = value_after(args, &mut index, "--access-address")?;
+                connect_request.access_address = parse_u32(&value, "--access-address")?;
+            }
+            "--crc-init" => {
+                let value = value_after(args, &mut index, "--crc-init")?;
+                connect_request.crc_init = parse_u32(&value, "--crc-init")?;
+            }
+            "--csa" => {
                 let value = value_after(args, &mut index, "--csa")?;
-                tracking_channel_selection_algorithm =
-                    Some(parse_channel_selection_algorithm(&value, "--csa")?);
-                tracking_options_supplied = true;
+                connect_request.channel_selection_algorithm =
+                    parse_channel_selection_algorithm(&value, "--csa")?;
             }
-            "--hop" if command == CaptureCommand::Data => {
+            "--hop" => {
                 let value = value_after(args, &mut index, "--hop")?;
-                tracking_hop_increment = parse_number(&value, "--hop")?;
-                tracking_options_supplied = true;
+                connect_request.hop_increment = parse_number(&value, "--hop")?;
             }
-            "--interval" if command == CaptureCommand::Data => {
+            "--channel-map" => {
+                let value = value_after(args, &mut index, "--channel-map")?;
+                connect_request.channel_map = parse_channel_map(&value)?.bytes();
+            }
+            "--interval" => {
                 let value = value_after(args, &mut index, "--interval")?;
-                tracking_interval = Some(parse_number(&value, "--interval")?);
-                tracking_options_supplied = true;
+                connect_request.interval = parse_number(&value, "--interval")?;
             }
-            "--latency" if command == CaptureCommand::Data => {
+            "--latency" => {
                 let value = value_after(args, &mut index, "--latency")?;
-                tracking_latency = parse_number(&value, "--latency")?;
-                tracking_options_supplied = true;
+                connect_request.latency = parse_number(&value, "--latency")?;
             }
-            "--timeout" if command == CaptureCommand::Data => {
+            "--timeout" => {
                 let value = value_after(args, &mut index, "--timeout")?;
-                tracking_supervision_timeout = parse_number(&value, "--timeout")?;
-                tracking_options_supplied = true;
+                connect_request.supervision_timeout = parse_number(&value, "--timeout")?;
             }
-            "--peer-sca" if command == CaptureCommand::Data => {
-                let value = value_after(args, &mut index, "--peer-sca")?;
-                tracking_peer_clock_accuracy =
-                    SleepClockAccuracy::new(parse_number(&value, "--peer-sca")?)?;
-                tracking_options_supplied = true;
+            "--window-size" => {
+                let value = value_after(args, &mut index, "--window-size")?;
+                connect_request.window_size = parse_number(&value, "--window-size")?;
             }
-            "--receiver-ppm" if command == CaptureCommand::Data => {
-                let value = value_after(args, &mut index, "--receiver-ppm")?;
-                tracking_receiver_clock_accuracy_ppm = parse_number(&value, "--receiver-ppm")?;
-                tracking_options_supplied = true;
+            "--window-offset" => {
+                let value = value_after(args, &mut index, "--window-offset")?;
+                connect_request.window_offset = parse_number(&value, "--window-offset")?;
             }
-            "--max-event-advance" if command == CaptureCommand::Data => {
-                let value = value_after(args, &mut index, "--max-event-advance")?;
-                tracking_maximum_event_advance = parse_number(&value, "--max-event-advance")?;
-                tracking_options_supplied = true;
+            "--sca" => {
+                let value = value_after(args, &mut index, "--sca")?;
+                connect_request.sleep_clock_accuracy = parse_number(&value, "--sca")?;
             }
-            "--sample-rate" => {
-                let value = value_after(args, &mut index, "--sample-rate")?;
-                sample_rate_hz = parse_number(&value, "--sample-rate")?;
+            "--events" => {
+                let value = value_after(args, &mut index, "--events")?;
+                event_count = parse_number(&value, "--events")?;
             }
-            "--bandwidth" => {
-                let value = value_after(args, &mut index, "--bandwidth")?;
-                bandwidth_hz = parse_number(&value, "--bandwidth")?;
+            option @ ("--channel-map-update" | "--connection-update" | "--l2cap" | "--att") => {
+                let option = option.to_owned();
+                let value = value_after(args, &mut index, &option)?;
+                actions.push(parse_scenario_action(&option, &value)?);
             }
-            "--gain" => {
-                let value = value_after(args, &mut index, "--gain")?;
-                gain_db = parse_number(&value, "--gain")?;
+            "--encrypt" => {
+                let value = value_after(args, &mut index, "--encrypt")?;
+                encrypt_event = Some(parse_number(&value, "--encrypt")?);
             }
-            "--rx-channel" => {
-                let value = value_after(args, &mut index, "--rx-channel")?;
-                rx_channel = parse_number(&value, "--rx-channel")?;
+            "--ltk" => {
+                let value = value_after(args, &mut index, "--ltk")?;
+                long_term_key = Some(parse_fixed_hex(&value, "--ltk")?);
             }
-            "--seconds" => {
-                let value = value_after(args, &mut index, "--seconds")?;
-                let seconds: f64 = parse_number(&value, "--seconds")?;
-                duration = Duration::try_from_secs_f64(seconds).map_err(|_| {
-                    Error::InvalidConfiguration(
-                        "--seconds must be finite, greater than zero, and representable as a duration"
-                            .to_owned(),
-                    )
-                })?;
-                if duration == Duration::ZERO {
-                    return Err(Error::InvalidConfiguration(
-                        "--seconds must be greater than zero".to_owned(),
-                    ));
-                }
-            }
-            "--block-samples" => {
-                let value = value_after(args, &mut index, "--block-samples")?;
-                block_samples = parse_number(&value, "--block-samples")?;
+            "--enc-req" => {
+                let value = value_after(args, &mut index, "--enc-req")?;
+                encryption_request = parse_fixed_hex(&value, "--enc-req")?;
             }
-            "--read-timeout-ms" => {
-                let value = value_after(args, &mut index, "--read-timeout-ms")?;
-                read_timeout_ms = parse_number(&value, "--read-timeout-ms")?;
+            "--enc-rsp" => {
+                let value = value_after(args, &mut index, "--enc-rsp")?;
+                encryption_response = parse_fixed_hex(&value, "--enc-rsp")?;
             }
-            "--aa-errors" => {
-                let value = value_after(args, &mut index, "--aa-errors")?;
-                max_access_address_errors = parse_number(&value, "--aa-errors")?;
+            "--snr" => {
+                let value = value_after(args, &mut index, "--snr")?;
+                impairments.snr_db = Some(parse_number(&value, "--snr")?);
             }
-            "--output-pcap" => {
-                output_pcap = Some(PathBuf::from(value_after(
-                    args,
-                    &mut index,
-                    "--output-pcap",
-                )?));
+            "--cfo" => {
+                let value = value_after(args, &mut index, "--cfo")?;
+                impairments.carrier_offset_hz = parse_number(&value, "--cfo")?;
             }
-            "--capture-start-ns" => {
-                let value = value_after(args, &mut index, "--capture-start-ns")?;
-                capture_start_ns = Some(parse_number(&value, "--capture-start-ns")?);
+            "--seed" => {
+                let value = value_after(args, &mut index, "--seed")?;
+                impairments.noise_seed = parse_u64(&value, "--seed")?;
             }
             "-h" | "--help" => {
                 print!("{}", usage());
@@ -1977,2176 +3303,5728 @@ fn parse_capture_args(args: &[String], command: CaptureCommand) -> Result<Captur
             }
             unknown => {
                 return Err(Error::InvalidConfiguration(format!(
-                    "unknown {} option {unknown:?}",
-                    match command {
-                        CaptureCommand::Advertising => "capture",
-                        CaptureCommand::Data => "capture-data",
-                    }
+                    "unknown scenario option {unknown:?}"
                 )));
             }
         }
         index += 1;
     }
 
-    if block_samples == 0 || block_samples > c_int_max_as_usize() {
-        return Err(Error::InvalidConfiguration(
-            "--block-samples must be in 1..=2147483647 for current live backends".to_owned(),
-        ));
+    match (encrypt_event, long_term_key) {
+        (Some(event_counter), Some(long_term_key)) => actions.push(ScenarioAction {
+            event_counter,
+            kind: ScenarioActionKind::StartEncryption(parse_scenario_encryption(
+                long_term_key,
+                encryption_request,
+                encryption_response,
+            )?),
+        }),
+        (None, None) => {}
+        _ => {
+            return Err(Error::InvalidConfiguration(
+                "--encrypt and --ltk must be supplied together".to_owned(),
+            ));
+        }
     }
-    if read_timeout_ms == 0 || read_timeout_ms > u32::MAX as u64 {
+    if output.is_some() != iq_channel.is_some() {
         return Err(Error::InvalidConfiguration(
-            "--read-timeout-ms must be in 1..=4294967295 for current live backends".to_owned(),
+            "--output and --iq-channel must be supplied together".to_owned(),
         ));
     }
-    if !gain_db.is_finite() {
+    if output.is_none() && output_pcap.is_none() {
         return Err(Error::InvalidConfiguration(
-            "--gain must be finite".to_owned(),
+            "scenario requires --output FILE --iq-channel N, --output-pcap FILE, or both"
+                .to_owned(),
         ));
     }
-    let command_name = match command {
-        CaptureCommand::Advertising => "capture",
-        CaptureCommand::Data => "capture-data",
+    let scenario = ConnectionScenario {
+        sample_rate_hz,
+        advertising_channel,
+        advertiser: advertiser.0,
+        advertiser_kind: advertiser.1,
+        initiator: initiator.0,
+        initiator_kind: initiator.1,
+        advertising_data,
+        advertising_sample,
+        connect_request,
+        event_count,
+        actions,
     };
-    let channel = channel
-        .ok_or_else(|| Error::InvalidConfiguration(format!("{command_name} requires --channel")))?;
-    let (frame, central_observation_tracking) = match command {
-        CaptureCommand::Advertising => {
-            if !channel.is_primary_advertising() {
-                return Err(Error::InvalidConfiguration(format!(
-                    "capture requires BLE advertising channel 37, 38, or 39; got {}",
-                    channel.index()
-                )));
+    scenario.validate()?;
+    Ok(ScenarioArgs {
+        scenario,
+        output,
+        format,
+        iq_channel,
+        output_pcap,
+        capture_start_ns,
+        impairments,
+    })
+}
+
+fn parse_analyze_args(args: &[String]) -> Result<AnalyzeArgs> {
+    let mut input = None;
+    let mut format = IqFormat::F32Le;
+    let mut channel = None;
+    let mut phy = LeUncodedPhy::Le1M;
+    let mut sample_rate_hz = None;
+    let mut access_address = None;
+    let mut crc_init = None;
+    let mut max_samples = DEFAULT_MAX_SAMPLES;
+    let mut block_samples = DEFAULT_BLOCK_SAMPLES;
+    let mut max_access_address_errors = 1u8;
+    let mut capture_start_ns = None;
+    let mut config = AnalyzerConfig::new(ANALYZE_CAPTURE_TIME_BASE_HZ);
+    let mut key_sources = KeySourceArgs::default();
+    let mut output_btsnoop = None;
+    let mut btsnoop_host = HciHost::Central;
+    let mut output_keyring = None;
+    let mut index = 0;
+
+    while index < args.len() {
+        match args[index].as_str() {
+            "--input" => input = Some(PathBuf::from(value_after(args, &mut index, "--input")?)),
+            "--format" => format = IqFormat::parse(&value_after(args, &mut index, "--format")?)?,
+            "--channel" => {
+                let value = value_after(args, &mut index, "--channel")?;
+                channel = Some(BleChannel::new(parse_number(&value, "--channel")?)?);
             }
-            Le1mDemodConfig {
-                sample_rate_hz,
-                max_access_address_errors,
+            "--phy" => {
+                let value = value_after(args, &mut index, "--phy")?;
+                phy = parse_uncoded_phy(&value, "--phy")?;
             }
-            .validate()?;
-            (CaptureFrame::Advertising, None)
-        }
-        CaptureCommand::Data => {
-            if channel.index() > 36 {
+            "--sample-rate" => {
+                let value = value_after(args, &mut index, "--sample-rate")?;
+                sample_rate_hz = Some(parse_number(&value, "--sample-rate")?);
+            }
+            "--access-address" => {
+                let value = value_after(args, &mut index, "--access-address")?;
+                access_address = Some(parse_u32(&value, "--access-address")?);
+            }
+            "--crc-init" => {
+                let value = value_after(args, &mut index, "--crc-init")?;
+                crc_init = Some(parse_u32(&value, "--crc-init")?);
+            }
+            "--max-samples" => {
+                let value = value_after(args, &mut index, "--max-samples")?;
+                max_samples = parse_number(&value, "--max-samples")?;
+            }
+            "--block-samples" => {
+                let value = value_after(args, &mut index, "--block-samples")?;
+                block_samples = parse_number(&value, "--block-samples")?;
+            }
+            "--aa-errors" => {
+                let value = value_after(args, &mut index, "--aa-errors")?;
+                max_access_address_errors = parse_number(&value, "--aa-errors")?;
+            }
+            "--ltk" => {
+                let value = value_after(args, &mut index, "--ltk")?;
+                config
+                    .long_term_keys
+                    .push(parse_fixed_hex(&value, "--ltk")?);
+            }
+            option @ ("--keyring" | "--keys-btsnoop" | "--keys-bluez") => {
+                let value = value_after(args, &mut index, option)?;
+                key_sources.push(option, value);
+            }
+            "--crack-legacy-pairing" => config.crack_legacy_pairing = true,
+            "--dhkey" => {
+                let value = value_after(args, &mut index, "--dhkey")?;
+                config
+                    .diffie_hellman_keys
+                    .push(parse_fixed_hex(&value, "--dhkey")?);
+            }
+            "--output-keyring" => {
+                output_keyring = Some(PathBuf::from(value_after(
+                    args,
+                    &mut index,
+                    "--output-keyring",
+                )?));
+            }
+            "--receiver-ppm" => {
+                let value = value_after(args, &mut index, "--receiver-ppm")?;
+                config.receiver_clock_accuracy_ppm = parse_number(&value, "--receiver-ppm")?;
+            }
+            "--max-event-advance" => {
+                let value = value_after(args, &mut index, "--max-event-advance")?;
+                config.maximum_event_advance = parse_number(&value, "--max-event-advance")?;
+            }
+            "--max-counter-skip" => {
+                let value = value_after(args, &mut index, "--max-counter-skip")?;
+                config.maximum_counter_skip = parse_number(&value, "--max-counter-skip")?;
+            }
+            "--max-l2cap-payload" => {
+                let value = value_after(args, &mut index, "--max-l2cap-payload")?;
+                config.maximum_l2cap_payload_length = parse_number(&value, "--max-l2cap-payload")?;
+            }
+            "--capture-start-ns" => {
+                let value = value_after(args, &mut index, "--capture-start-ns")?;
+                capture_start_ns = Some(parse_number(&value, "--capture-start-ns")?);
+            }
+            "--output-btsnoop" => {
+                output_btsnoop = Some(PathBuf::from(value_after(
+                    args,
+                    &mut index,
+                    "--output-btsnoop",
+                )?));
+            }
+            "--btsnoop-host" => {
+                btsnoop_host = match value_after(args, &mut index, "--btsnoop-host")?.as_str() {
+                    "central" => HciHost::Central,
+                    "peripheral" => HciHost::Peripheral,
+                    value => {
+                        return Err(Error::InvalidConfiguration(format!(
+                            "invalid value {value:?} for --btsnoop-host; expected central or peripheral"
+                        )));
+                    }
+                };
+            }
+            "-h" | "--help" => {
+                print!("{}", usage());
+                std::process::exit(0);
+            }
+            unknown => {
                 return Err(Error::InvalidConfiguration(format!(
-                    "capture-data requires a data channel in 0..=36; got {}",
-                    channel.index()
+                    "unknown analyze option {unknown:?}"
                 )));
             }
-            let access_address = access_address.ok_or_else(|| {
+        }
+        index += 1;
+    }
+
+    let input = input
+        .ok_or_else(|| Error::InvalidConfiguration("analyze requires --input FILE".to_owned()))?;
+    let iq = match channel {
+        Some(channel) => {
+            let sample_rate_hz = sample_rate_hz.ok_or_else(|| {
                 Error::InvalidConfiguration(
-                    "capture-data requires --access-address 0xNNNNNNNN".to_owned(),
+                    "analyze requires --sample-rate HZ for I/Q input".to_owned(),
                 )
             })?;
-            let crc_init = crc_init.ok_or_else(|| {
-                Error::InvalidConfiguration("capture-data requires --crc-init 0xNNNNNN".to_owned())
-            })?;
-            LeFrameConfig::data(access_address, crc_init)?;
             LeUncodedDemodConfig {
                 phy,
                 sample_rate_hz,
                 max_access_address_errors,
             }
             .validate()?;
-            let central_observation_tracking = if tracking_options_supplied {
-                if !assert_central_observations {
+            if block_samples == 0 {
+                return Err(Error::InvalidConfiguration(
+                    "--block-samples must be greater than zero".to_owned(),
+                ));
+            }
+            let frame_config = match (access_address, crc_init) {
+                (Some(access_address), Some(crc_init)) => {
+                    LeFrameConfig::data(access_address, crc_init)?
+                }
+                (None, None) if channel.is_primary_advertising() => LeFrameConfig::advertising(),
+                (None, None) => {
                     return Err(Error::InvalidConfiguration(
-                        "connection-event tracking options require --assert-central-observations"
+                        "analyze requires --access-address and --crc-init on data channels"
                             .to_owned(),
                     ));
                 }
-                let config = FixedChannelCentralObservationConfig {
-                    tracker: ConnectionTrackerConfig {
-                        access_address,
-                        channel_selection_algorithm: tracking_channel_selection_algorithm
-                            .ok_or_else(|| {
-                                Error::InvalidConfiguration(
-                                    "--assert-central-observations requires --csa 1|2".to_owned(),
-                                )
-                            })?,
-                        hop_increment: tracking_hop_increment,
-                        channel_map: tracking_channel_map.ok_or_else(|| {
-                            Error::InvalidConfiguration(
-                                "--assert-central-observations requires --channel-map HEX"
-                                    .to_owned(),
-                            )
-                        })?,
-                        parameters: ConnectionParameters::new(
-                            tracking_interval.ok_or_else(|| {
-                                Error::InvalidConfiguration(
-                                    "--assert-central-observations requires --interval N"
-                                        .to_owned(),
-                                )
-                            })?,
-                            tracking_latency,
-                            tracking_supervision_timeout,
-                        )?,
-                        sample_rate_hz,
-                    },
-                    first_event_counter: tracking_first_event_counter.ok_or_else(|| {
-                        Error::InvalidConfiguration(
-                            "--assert-central-observations requires --first-event N".to_owned(),
-                        )
-                    })?,
-                    peer_clock_accuracy: tracking_peer_clock_accuracy,
-                    receiver_clock_accuracy_ppm: tracking_receiver_clock_accuracy_ppm,
-                    maximum_event_advance: tracking_maximum_event_advance,
-                };
-                config.validate(channel)?;
-                Some(config)
-            } else {
-                None
+                _ => {
+                    return Err(Error::InvalidConfiguration(
+                        "--access-address and --crc-init must be supplied together".to_owned(),
+                    ));
+                }
             };
-            (
-                CaptureFrame::Data {
-                    access_address,
-                    crc_init,
-                    phy,
-                },
-                central_observation_tracking,
-            )
+            config.time_base_hz = sample_rate_hz;
+            Some(AnalyzeIqArgs {
+                format,
+                channel,
+                phy,
+                frame_config,
+                max_samples,
+                block_samples,
+                max_access_address_errors,
+                capture_start_ns: capture_start_ns.unwrap_or(0),
+            })
+        }
+        None => {
+            if sample_rate_hz.is_some()
+                || access_address.is_some()
+                || crc_init.is_some()
+                || capture_start_ns.is_some()
+            {
+                return Err(Error::InvalidConfiguration(
+                    "I/Q options require --channel; capture files carry their own framing"
+                        .to_owned(),
+                ));
+            }
+            None
         }
     };
-    Ok(CaptureArgs {
-        device: device.ok_or_else(|| {
-            Error::InvalidConfiguration(format!(
-                "{command_name} requires --device bladerf|limesdr|xtrx"
-            ))
-        })?,
-        identifier,
-        channel,
-        sample_rate_hz,
-        bandwidth_hz,
-        gain_db,
-        rx_channel,
-        duration,
-        block_samples,
-        read_timeout_ms,
-        max_access_address_errors,
-        output_pcap,
-        capture_start_ns,
-        frame,
-        central_observation_tracking,
-    })
-}
-
-const fn c_int_max_as_usize() -> usize {
-    i32::MAX as usize
-}
-
-fn print_hex(bytes: &[u8]) -> String {
-    let mut output = String::with_capacity(bytes.len() * 2);
-    for byte in bytes {
-        use std::fmt::Write;
-        let _ = write!(output, "{byte:02x}");
+    if config.maximum_counter_skip > LE_ACL_MAXIMUM_COUNTER_SKIP {
+        return Err(Error::InvalidConfiguration(format!(
+            "--max-counter-skip must be in 0..={LE_ACL_MAXIMUM_COUNTER_SKIP}"
+        )));
     }
-    output
+    config.validate()?;
+    Ok(AnalyzeArgs {
+        input,
+        iq,
+        config,
+        key_sources,
+        output_btsnoop,
+        btsnoop_host,
+        output_keyring,
+    })
 }
 
-fn print_signed_hex(bytes: &[i8]) -> String {
-    let mut output = String::with_capacity(bytes.len() * 2);
-    for byte in bytes {
-        use std::fmt::Write;
-        let _ = write!(output, "{:02x}", *byte as u8);
+/// Interface options offered by `--extcap-config` that take a value. They
+/// keep their `capture` spelling so a capture can reuse that parser.
+const EXTCAP_VALUE_OPTIONS: &[&str] = &[
+    "--channel",
+    "--access-address",
+    "--crc-init",
+    "--phy",
+    "--sample-rate",
+    "--bandwidth",
+    "--gain",
+    "--rx-channel",
+    "--aa-errors",
+    "--recording",
+    "--format",
+    "--replay-speed",
+];
+
+fn parse_extcap_args(args: &[String]) -> Result<ExtcapArgs> {
+    // Wireshark passes some options as `--name=value`.
+    let args: Vec<String> = args
+        .iter()
+        .flat_map(|arg| match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => {
+                vec![name.to_owned(), value.to_owned()]
+            }
+            _ => vec![arg.clone()],
+        })
+        .collect();
+    let mut action = None;
+    let mut interface = None;
+    let mut fifo = None;
+    let mut options = Vec::new();
+    let mut index = 0;
+
+    while index < args.len() {
+        match args[index].as_str() {
+            "--extcap-interfaces" => action = Some(ExtcapAction::Interfaces),
+            "--extcap-dlts" => action = Some(ExtcapAction::Dlts),
+            "--extcap-config" => action = Some(ExtcapAction::Config),
+            "--capture" => action = Some(ExtcapAction::Capture),
+            "--extcap-interface" => {
+                interface = Some(value_after(&args, &mut index, "--extcap-interface")?);
+            }
+            "--fifo" => fifo = Some(PathBuf::from(value_after(&args, &mut index, "--fifo")?)),
+            "--extcap-capture-filter" => {
+                let filter = value_after(&args, &mut index, "--extcap-capture-filter")?;
+                if !filter.trim().is_empty() {
+                    return Err(Error::InvalidConfiguration(
+                        "blueoxide extcap does not support capture filters; use a display filter"
+                            .to_owned(),
+                    ));
+                }
+            }
+            "--extcap-version"
+            | "--extcap-control-in"
+            | "--extcap-control-out"
+            | "--debug-file" => {
+                let option = args[index].clone();
+                value_after(&args, &mut index, &option)?;
+            }
+            "--debug" => {}
+            "--emit-crc-failures" => options.push(("--emit-crc-failures".to_owned(), None)),
+            option if EXTCAP_VALUE_OPTIONS.contains(&option) => {
+                let option = option.to_owned();
+                let value = value_after(&args, &mut index, &option)?;
+                options.push((option, Some(value)));
+            }
+            unknown => {
+                return Err(Error::InvalidConfiguration(format!(
+                    "unknown extcap option {unknown:?}"
+                )));
+            }
+        }
+        index += 1;
     }
-    output
-}
 
-fn print_packet(packet: &ReceivedAdvertisingPdu) {
-    let semantic = decode_advertising_pdu(&packet.pdu)
-        .map(|decoded| decoded.to_string())
-        .unwrap_or_else(|error| format!("decode_error={error}"));
-    println!(
-        "channel={} phy={} sample={} phase={} inverted={} aa_errors={} pdu_type={} carrier_offset_hz={:.1} deviation_hz={:.1} header={} payload={} crc={} semantic=\"{}\"",
-        packet.pdu.channel.index(),
-        packet.phy,
-        packet.access_address_sample,
-        packet.symbol_phase,
-        packet.pdu.inverted,
-        packet.pdu.access_address_errors,
-        packet.pdu.pdu_type(),
-        packet.estimated_carrier_offset_hz,
-        packet.estimated_deviation_hz,
-        print_hex(&packet.pdu.header),
-        print_hex(&packet.pdu.payload),
-        print_hex(&packet.pdu.crc),
-        semantic.replace('"', "'"),
-    );
+    let action = action.ok_or_else(|| {
+        Error::InvalidConfiguration(
+            "extcap requires --extcap-interfaces, --extcap-dlts, --extcap-config, or --capture"
+                .to_owned(),
+        )
+    })?;
+    if action != ExtcapAction::Interfaces && interface.is_none() {
+        return Err(Error::InvalidConfiguration(
+            "extcap requires --extcap-interface NAME".to_owned(),
+        ));
+    }
+    if action == ExtcapAction::Capture && fifo.is_none() {
+        return Err(Error::InvalidConfiguration(
+            "extcap --capture requires --fifo PATH".to_owned(),
+        ));
+    }
+    Ok(ExtcapArgs {
+        action,
+        interface,
+        fifo,
+        options,
+    })
 }
 
-fn print_periodic_packet(packet: &ReceivedAdvertisingPdu) {
-    let semantic = decode_contextual_extended_advertising_pdu(
-        &packet.pdu,
-        ExtendedAdvertisingPduKind::AuxSyncInd,
-    )
-    .map(|decoded| decoded.to_string())
-    .unwrap_or_else(|error| format!("decode_error={error}"));
-    println!(
-        "channel={} phy={} sample={} phase={} access_address={:08x} inverted={} aa_errors={} pdu_type={} carrier_offset_hz={:.1} deviation_hz={:.1} header={} payload={} crc={} semantic=\"{}\"",
-        packet.pdu.channel.index(),
-        packet.phy,
-        packet.access_address_sample,
-        packet.symbol_phase,
-        packet.pdu.access_address,
-        packet.pdu.inverted,
-        packet.pdu.access_address_errors,
-        packet.pdu.pdu_type(),
-        packet.estimated_carrier_offset_hz,
-        packet.estimated_deviation_hz,
-        print_hex(&packet.pdu.header),
-        print_hex(&packet.pdu.payload),
-        print_hex(&packet.pdu.crc),
-        semantic.replace('"', "'"),
-    );
-}
+fn parse_capture_args(args: &[String], command: CaptureCommand) -> Result<CaptureArgs> {
+    let mut device = None;
+    let mut identifier = None;
+    let mut channel = None;
+    let mut access_address = None;
+    let mut crc_init = None;
+    let mut phy = LeUncodedPhy::Le1M;
+    let mut sample_rate_hz = 4_000_000u32;
+    let mut bandwidth_hz = 2_000_000u32;
+    let mut gain_db = 30.0f32;
+    let mut rx_channel = 0u8;
+    let mut duration = Duration::from_secs(10);
+    let mut block_samples = 8_192usize;
+    let mut read_timeout_ms = 1_000u64;
+    let mut max_access_address_errors = 1u8;
+    let mut crc_failure_policy = LeCrcFailurePolicy::Discard;
+    let mut output_pcap = None;
+    let mut pcap_rotation = PcapNgRotation::default();
+    let mut capture_start_ns = None;
+    let mut stream_format = IqFormat::F32Le;
+    let mut stream_framing = IqStreamFraming::Raw;
+    let mut format_supplied = false;
+    let mut framing_supplied = false;
+    let mut replay = ReplayOptions::default();
+    let mut replay_options_supplied = false;
+    let mut assert_central_observations = false;
+    let mut tracking_options_supplied = false;
+    let mut tracking_first_event_counter = None;
+    let mut tracking_channel_map = None;
+    let mut tracking_channel_selection_algorithm = None;
+    let mut tracking_hop_increment = 5u8;
+    let mut tracking_interval = None;
+    let mut tracking_latency = 0u16;
+    let mut tracking_supervision_timeout = 3_200u16;
+    let mut tracking_peer_clock_accuracy = SleepClockAccuracy::new(0)?;
+    let mut tracking_receiver_clock_accuracy_ppm = 20u32;
+    let mut tracking_maximum_event_advance = 32u16;
+    let mut output_format = OutputFormat::Text;
+    let mut index = 0;
 
-fn describe_control_pdu(control: ControlPdu<'_>) -> Result<String> {
-    Ok(match control.decode()? {
-        DecodedControlPdu::ConnectionUpdateInd(update) => format!(
-            "{} opcode=0x{:02x} window_offset={} window_size={} interval={} latency={} timeout={} instant={}",
-            control.opcode_name(),
-            control.opcode,
-            update.window_offset,
-            update.window_size,
-            update.parameters.interval,
-            update.parameters.latency,
-            update.parameters.supervision_timeout,
-            update.instant
-        ),
-        DecodedControlPdu::ChannelMapInd(update) => format!(
-            "{} opcode=0x{:02x} channel_map={} channels={} instant={}",
-            control.opcode_name(),
-            control.opcode,
-            print_hex(&update.channel_map.bytes()),
-            update.channel_map.used_count(),
-            update.instant
-        ),
-        DecodedControlPdu::TerminateInd(value) | DecodedControlPdu::RejectInd(value) => format!(
-            "{} opcode=0x{:02x} error_code=0x{:02x}",
-            control.opcode_name(),
-            control.opcode,
-            value.error_code
-        ),
-        DecodedControlPdu::EncryptionRequest(value) => format!(
-            "{} opcode=0x{:02x} rand={} ediv=0x{:04x} skd_c={} iv_c={}",
-            control.opcode_name(),
-            control.opcode,
-            print_hex(&value.random_number),
-            value.encrypted_diversifier,
-            print_hex(&value.central_session_key_diversifier),
-            print_hex(&value.central_initialization_vector)
-        ),
-        DecodedControlPdu::EncryptionResponse(value) => format!(
-            "{} opcode=0x{:02x} skd_p={} iv_p={}",
-            control.opcode_name(),
-            control.opcode,
-            print_hex(&value.peripheral_session_key_diversifier),
-            print_hex(&value.peripheral_initialization_vector)
-        ),
-        DecodedControlPdu::StartEncryptionRequest
-        | DecodedControlPdu::StartEncryptionResponse
-        | DecodedControlPdu::PauseEncryptionRequest
-        | DecodedControlPdu::PauseEncryptionResponse
-        | DecodedControlPdu::PingRequest
-        | DecodedControlPdu::PingResponse
-        | DecodedControlPdu::CteResponse
-        | DecodedControlPdu::CsFaeRequest => {
-            format!("{} opcode=0x{:02x}", control.opcode_name(), control.opcode)
-        }
-        DecodedControlPdu::UnknownResponse(value) => format!(
-            "{} opcode=0x{:02x} unknown_type=0x{:02x} unknown_name={}",
-            control.opcode_name(),
-            control.opcode,
-            value.unknown_type,
-            ControlPdu {
-                opcode: value.unknown_type,
-                parameters: &[]
+    while index < args.len() {
+        match args[index].as_str() {
+            "--device" => device = Some(value_after(args, &mut index, "--device")?),
+            "--identifier" => identifier = Some(value_after(args, &mut index, "--identifier")?),
+            "--channel" => {
+                let value = value_after(args, &mut index, "--channel")?;
+                channel = Some(BleChannel::new(parse_number(&value, "--channel")?)?);
             }
-            .opcode_name()
-        ),
-        DecodedControlPdu::FeatureRequest(value)
-        | DecodedControlPdu::FeatureResponse(value)
-        | DecodedControlPdu::PeripheralFeatureRequest(value) => format!(
-            "{} opcode=0x{:02x} features={}",
-            control.opcode_name(),
-            control.opcode,
-            print_hex(&value.bytes)
-        ),
-        DecodedControlPdu::VersionInd(value) => format!(
-            "{} opcode=0x{:02x} version=0x{:02x} company=0x{:04x} subversion=0x{:04x}",
-            control.opcode_name(),
-            control.opcode,
-            value.version,
-            value.company_identifier,
-            value.subversion
-        ),
-        DecodedControlPdu::ConnectionParameterRequest(value)
-        | DecodedControlPdu::ConnectionParameterResponse(value) => format!(
-            "{} opcode=0x{:02x} interval_min={} interval_max={} latency={} timeout={} preferred_periodicity={} reference_event={} offsets={:04x},{:04x},{:04x},{:04x},{:04x},{:04x}",
-            control.opcode_name(),
-            control.opcode,
-            value.interval_min,
-            value.interval_max,
-            value.latency,
-            value.supervision_timeout,
-            value.preferred_periodicity,
-            value.reference_connection_event_count,
-            value.offsets[0],
-            value.offsets[1],
-            value.offsets[2],
-            value.offsets[3],
-            value.offsets[4],
-            value.offsets[5]
-        ),
-        DecodedControlPdu::RejectExtendedInd(value) => format!(
-            "{} opcode=0x{:02x} rejected_opcode=0x{:02x} rejected_name={} error_code=0x{:02x}",
-            control.opcode_name(),
-            control.opcode,
-            value.rejected_opcode,
-            ControlPdu {
-                opcode: value.rejected_opcode,
-                parameters: &[]
+            "--access-address" if command == CaptureCommand::Data => {
+                let value = value_after(args, &mut index, "--access-address")?;
+                access_address = Some(parse_u32(&value, "--access-address")?);
             }
-            .opcode_name(),
-            value.error_code
-        ),
-        DecodedControlPdu::LengthRequest(value) | DecodedControlPdu::LengthResponse(value) => {
-            format!(
-                "{} opcode=0x{:02x} max_rx_octets={} max_rx_time_us={} max_tx_octets={} max_tx_time_us={}",
-                control.opcode_name(),
-                control.opcode,
-                value.maximum_receive_octets,
-                value.maximum_receive_time_us,
-                value.maximum_transmit_octets,
-                value.maximum_transmit_time_us
-            )
-        }
-        DecodedControlPdu::PhyRequest(value) | DecodedControlPdu::PhyResponse(value) => format!(
-            "{} opcode=0x{:02x} tx_phys=0x{:02x} rx_phys=0x{:02x}",
+            "--crc-init" if command == CaptureCommand::Data => {
+                let value = value_after(args, &mut index, "--crc-init")?;
+                crc_init = Some(parse_u32(&value, "--crc-init")?);
+            }
+            "--phy" if command == CaptureCommand::Data => {
+                let value = value_after(args, &mut index, "--phy")?;
+                phy = parse_uncoded_phy(&value, "--phy")?;
+            }
+            "--assert-central-observations" if command == CaptureCommand::Data => {
+                assert_central_observations = true;
+                tracking_options_supplied = true;
+            }
+            "--first-event" if command == CaptureCommand::Data => {
+                let value = value_after(args, &mut index, "--first-event")?;
+                tracking_first_event_counter = Some(parse_number(&value, "--first-event")?);
+                tracking_options_supplied = true;
+            }
+            "--channel-map" if command == CaptureCommand::Data => {
+                tracking_channel_map = Some(parse_channel_map(&value_after(
+                    args,
+                    &mut index,
+                    "--channel-map",
+                )?)?);
+                tracking_options_supplied = true;
+            }
+            "--csa" if command == CaptureCommand::Data => {
+                let value = value_after(args, &mut index, "--csa")?;
+                tracking_channel_selection_algorithm =
+                    Some(parse_channel_selection_algorithm(&value, "--csa")?);
+                tracking_options_supplied = true;
+            }
+            "--hop" if command == CaptureCommand::Data => {
+                let value = value_after(args, &mut index, "--hop")?;
+                tracking_hop_increment = parse_number(&value, "--hop")?;
+                tracking_options_supplied = true;
+            }
+            "--interval" if command == CaptureCommand::Data => {
+                let value = value_after(args, &mut index, "--interval")?;
+                tracking_interval = Some(parse_number(&value, "--interval")?);
+                tracking_options_supplied = true;
+            }
+            "--latency" if command == CaptureCommand::Data => {
+                let value = value_after(args, &mut index, "--latency")?;
+                tracking_latency = parse_number(&value, "--latency")?;
+                tracking_options_supplied = true;
+            }
+            "--timeout" if command == CaptureCommand::Data => {
+                let value = value_after(args, &mut index, "--timeout")?;
+                tracking_supervision_timeout = parse_number(&value, "--timeout")?;
+                tracking_options_supplied = true;
+            }
+            "--peer-sca" if command == CaptureCommand::Data => {
+                let value = value_after(args, &mut index, "--peer-sca")?;
+                tracking_peer_clock_accuracy =
+                    SleepClockAccuracy::new(parse_number(&value, "--peer-sca")?)?;
+                tracking_options_supplied = true;
+            }
+            "--receiver-ppm" if command == CaptureCommand::Data => {
+                let value = value_after(args, &mut index, "--receiver-ppm")?;
+                tracking_receiver_clock_accuracy_ppm = parse_number(&value, "--receiver-ppm")?;
+                tracking_options_supplied = true;
+            }
+            "--max-event-advance" if command == CaptureCommand::Data => {
+                let value = value_after(args, &mut index, "--max-event-advance")?;
+                tracking_maximum_event_advance = parse_number(&value, "--max-event-advance")?;
+                tracking_options_supplied = true;
+            }
+            "--sample-rate" => {
+                let value = value_after(args, &mut index, "--sample-rate")?;
+                sample_rate_hz = parse_number(&value, "--sample-rate")?;
+            }
+            "--bandwidth" => {
+                let value = value_after(args, &mut index, "--bandwidth")?;
+                bandwidth_hz = parse_number(&value, "--bandwidth")?;
+            }
+            "--gain" => {
+                let value = value_after(args, &mut index, "--gain")?;
+                gain_db = parse_number(&value, "--gain")?;
+            }
+            "--rx-channel" => {
+                let value = value_after(args, &mut index, "--rx-channel")?;
+                rx_channel = parse_number(&value, "--rx-channel")?;
+            }
+            "--seconds" => {
+                let value = value_after(args, &mut index, "--seconds")?;
+                let seconds: f64 = parse_number(&value, "--seconds")?;
+                duration = Duration::try_from_secs_f64(seconds).map_err(|_| {
+                    Error::InvalidConfiguration(
+                        "--seconds must be finite, greater than zero, and representable as a duration"
+                            .to_owned(),
+                    )
+                })?;
+                if duration == Duration::ZERO {
+                    return Err(Error::InvalidConfiguration(
+                        "--seconds must be greater than zero".to_owned(),
+                    ));
+                }
+            }
+            "--block-samples" => {
+                let value = value_after(args, &mut index, "--block-samples")?;
+                block_samples = parse_number(&value, "--block-samples")?;
+            }
+            "--read-timeout-ms" => {
+                let value = value_after(args, &mut index, "--read-timeout-ms")?;
+                read_timeout_ms = parse_number(&value, "--read-timeout-ms")?;
+            }
+            "--emit-crc-failures" => crc_failure_policy = LeCrcFailurePolicy::emit(),
+            "--aa-errors" => {
+                let value = value_after(args, &mut index, "--aa-errors")?;
+                max_access_address_errors = parse_number(&value, "--aa-errors")?;
+            }
+            "--output-pcap" => {
+                output_pcap = Some(PathBuf::from(value_after(
+                    args,
+                    &mut index,
+                    "--output-pcap",
+                )?));
+            }
+            "--pcap-max-bytes" => {
+                let value = value_after(args, &mut index, "--pcap-max-bytes")?;
+                pcap_rotation.maximum_file_bytes = Some(parse_number(&value, "--pcap-max-bytes")?);
+            }
+            "--pcap-max-seconds" => {
+                let value = value_after(args, &mut index, "--pcap-max-seconds")?;
+                pcap_rotation.maximum_file_duration = Some(Duration::from_secs(parse_number(
+                    &value,
+                    "--pcap-max-seconds",
+                )?));
+            }
+            "--pcap-max-packets" => {
+                let value = value_after(args, &mut index, "--pcap-max-packets")?;
+                pcap_rotation.maximum_file_packets =
+                    Some(parse_number(&value, "--pcap-max-packets")?);
+            }
+            "--pcap-ring-files" => {
+                let value = value_after(args, &mut index, "--pcap-ring-files")?;
+                pcap_rotation.ring_files = Some(parse_number(&value, "--pcap-ring-files")?);
+            }
+            "--pcap-flush-ms" => {
+                let value = value_after(args, &mut index, "--pcap-flush-ms")?;
+                pcap_rotation.flush_interval = Some(Duration::from_millis(parse_number(
+                    &value,
+                    "--pcap-flush-ms",
+                )?));
+            }
+            "--capture-start-ns" => {
+                let value = value_after(args, &mut index, "--capture-start-ns")?;
+                capture_start_ns = Some(parse_number(&value, "--capture-start-ns")?);
+            }
+            "--format" => {
+                stream_format = IqFormat::parse(&value_after(args, &mut index, "--format")?)?;
+                format_supplied = true;
+            }
+            "--framing" => {
+                stream_framing =
+                    IqStreamFraming::parse(&value_after(args, &mut index, "--framing")?)?;
+                framing_supplied = true;
+            }
+            "--replay-speed" => {
+                replay.pacing =
+                    ReplayPacing::parse(&value_after(args, &mut index, "--replay-speed")?)?;
+                replay_options_supplied = true;
+            }
+            "--impair" => {
+                replay
+                    .impairments
+                    .push(ReplayImpairment::parse(&value_after(
+                        args, &mut index, "--impair",
+                    )?)?);
+                replay_options_supplied = true;
+            }
+            "--output-format" => {
+                output_format =
+                    parse_output_format(&value_after(args, &mut index, "--output-format")?)?;
+            }
+            "-h" | "--help" => {
+                print!("{}", usage());
+                std::process::exit(0);
+            }
+            unknown => {
+                return Err(Error::InvalidConfiguration(format!(
+                    "unknown {} option {unknown:?}",
+                    match command {
+                        CaptureCommand::Advertising => "capture",
+                        CaptureCommand::Data => "capture-data",
+                    }
+                )));
+            }
+        }
+        index += 1;
+    }
+
+    if block_samples == 0 || block_samples > c_int_max_as_usize() {
+        return Err(Error::InvalidConfiguration(
+            "--block-samples must be in 1..=2147483647 for current live backends".to_owned(),
+        ));
+    }
+    if read_timeout_ms == 0 || read_timeout_ms > u32::MAX as u64 {
+        return Err(Error::InvalidConfiguration(
+            "--read-timeout-ms must be in 1..=4294967295 for current live backends".to_owned(),
+        ));
+    }
+    if !gain_db.is_finite() {
+        return Err(Error::InvalidConfiguration(
+            "--gain must be finite".to_owned(),
+        ));
+    }
+    let command_name = match command {
+        CaptureCommand::Advertising => "capture",
+        CaptureCommand::Data => "capture-data",
+    };
+    let device_is = |name: &str| {
+        device
+            .as_deref()
+            .is_some_and(|device| device.eq_ignore_ascii_case(name))
+    };
+    if framing_supplied && !device_is("stream") {
+        return Err(Error::InvalidConfiguration(
+            "--framing applies only to --device stream".to_owned(),
+        ));
+    }
+    if format_supplied && !device_is("stream") && !device_is("replay") {
+        return Err(Error::InvalidConfiguration(
+            "--format applies only to --device stream or replay".to_owned(),
+        ));
+    }
+    if replay_options_supplied && !device_is("replay") {
+        return Err(Error::InvalidConfiguration(
+            "--replay-speed and --impair apply only to --device replay".to_owned(),
+        ));
+    }
+    if pcap_rotation != PcapNgRotation::default() && output_pcap.is_none() {
+        return Err(Error::InvalidConfiguration(
+            "--pcap-max-bytes, --pcap-max-seconds, --pcap-max-packets, --pcap-ring-files and --pcap-flush-ms require --output-pcap"
+                .to_owned(),
+        ));
+    }
+    pcap_rotation.validate()?;
+    let channel = channel
+        .ok_or_else(|| Error::InvalidConfiguration(format!("{command_name} requires --channel")))?;
+    let (frame, central_observation_tracking) = match command {
+        CaptureCommand::Advertising => {
+            if !channel.is_primary_advertising() {
+                return Err(Error::InvalidConfiguration(format!(
+                    "capture requires BLE advertising channel 37, 38, or 39; got {}",
+                    channel.index()
+                )));
+            }
+            Le1mDemodConfig {
+                sample_rate_hz,
+                max_access_address_errors,
+            }
+            .validate()?;
+            (CaptureFrame::Advertising, None)
+        }
+        CaptureCommand::Data => {
+            if channel.index() > 36 {
+                return Err(Error::InvalidConfiguration(format!(
+                    "capture-data requires a data channel in 0..=36; got {}",
+                    channel.index()
+                )));
+            }
+            let access_address = access_address.ok_or_else(|| {
+                Error::InvalidConfiguration(
+                    "capture-data requires --access-address 0xNNNNNNNN".to_owned(),
+                )
+            })?;
+            let crc_init = crc_init.ok_or_else(|| {
+                Error::InvalidConfiguration("capture-data requires --crc-init 0xNNNNNN".to_owned())
+            })?;
+            LeFrameConfig::data(access_address, crc_init)?;
+            LeUncodedDemodConfig {
+                phy,
+                sample_rate_hz,
+                max_access_address_errors,
+            }
+            .validate()?;
+            let central_observation_tracking = if tracking_options_supplied {
+                if !assert_central_observations {
+                    return Err(Error::InvalidConfiguration(
+                        "connection-event tracking options require --assert-central-observations"
+                            .to_owned(),
+                    ));
+                }
+                let config = FixedChannelCentralObservationConfig {
+                    tracker: ConnectionTrackerConfig {
+                        access_address,
+                        channel_selection_algorithm: tracking_channel_selection_algorithm
+                            .ok_or_else(|| {
+                                Error::InvalidConfiguration(
+                                    "--assert-central-observations requires --csa 1|2".to_owned(),
+                                )
+                            })?,
+                        hop_increment: tracking_hop_increment,
+                        channel_map: tracking_channel_map.ok_or_else(|| {
+                            Error::InvalidConfiguration(
+                                "--assert-central-observations requires --channel-map HEX"
+                                    .to_owned(),
+                            )
+                        })?,
+                        parameters: ConnectionParameters::new(
+                            tracking_interval.ok_or_else(|| {
+                                Error::InvalidConfiguration(
+                                    "--assert-central-observations requires --interval N"
+                                        .to_owned(),
+                                )
+                            })?,
+                            tracking_latency,
+                            tracking_supervision_timeout,
+                        )?,
+                        sample_rate_hz,
+                    },
+                    first_event_counter: tracking_first_event_counter.ok_or_else(|| {
+                        Error::InvalidConfiguration(
+                            "--assert-central-observations requires --first-event N".to_owned(),
+                        )
+                    })?,
+                    peer_clock_accuracy: tracking_peer_clock_accuracy,
+                    receiver_clock_accuracy_ppm: tracking_receiver_clock_accuracy_ppm,
+                    maximum_event_advance: tracking_maximum_event_advance,
+                };
+                config.validate(channel)?;
+                Some(config)
+            } else {
+                None
+            };
+            (
+                CaptureFrame::Data {
+                    access_address,
+                    crc_init,
+                    phy,
+                },
+                central_observation_tracking,
+            )
+        }
+    };
+    Ok(CaptureArgs {
+        device: device.ok_or_else(|| {
+            Error::InvalidConfiguration(format!(
+                "{command_name} requires --device bladerf|limesdr|xtrx"
+            ))
+        })?,
+        identifier,
+        channel,
+        sample_rate_hz,
+        bandwidth_hz,
+        gain_db,
+        rx_channel,
+        duration,
+        block_samples,
+        read_timeout_ms,
+        max_access_address_errors,
+        crc_failure_policy,
+        output_pcap,
+        pcap_rotation,
+        capture_start_ns,
+        stream_format,
+        stream_framing,
+        replay,
+        frame,
+        central_observation_tracking,
+        output_format,
+    })
+}
+
+const fn c_int_max_as_usize() -> usize {
+    i32::MAX as usize
+}
+
+fn print_hex(bytes: &[u8]) -> String {
+    let mut output = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        use std::fmt::Write;
+        let _ = write!(output, "{byte:02x}");
+    }
+    output
+}
+
+fn print_signed_hex(bytes: &[i8]) -> String {
+    let mut output = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        use std::fmt::Write;
+        let _ = write!(output, "{:02x}", *byte as u8);
+    }
+    output
+}
+
+fn print_packet(packet: &ReceivedAdvertisingPdu, output_format: OutputFormat) {
+    if output_format == OutputFormat::JsonLines {
+        print_json(json_advertising_packet(
+            "advertising_packet",
+            packet,
+            decode_advertising_pdu(&packet.pdu).map(|decoded| json_advertising_pdu(&decoded)),
+        ));
+        return;
+    }
+    let semantic = decode_advertising_pdu(&packet.pdu)
+        .map(|decoded| decoded.to_string())
+        .unwrap_or_else(|error| format!("decode_error={error}"));
+    println!(
+        "channel={} phy={} sample={} phase={} inverted={} aa_errors={} pdu_type={} carrier_offset_hz={:.1} deviation_hz={:.1} header={} payload={} crc={} semantic=\"{}\"",
+        packet.pdu.channel.index(),
+        packet.phy,
+        packet.access_address_sample,
+        packet.symbol_phase,
+        packet.pdu.inverted,
+        packet.pdu.access_address_errors,
+        packet.pdu.pdu_type(),
+        packet.estimated_carrier_offset_hz,
+        packet.estimated_deviation_hz,
+        print_hex(&packet.pdu.header),
+        print_hex(&packet.pdu.payload),
+        print_hex(&packet.pdu.crc),
+        semantic.replace('"', "'"),
+    );
+}
+
+fn print_periodic_packet(packet: &ReceivedAdvertisingPdu, output_format: OutputFormat) {
+    if output_format == OutputFormat::JsonLines {
+        let decoded = decode_contextual_extended_advertising_pdu(
+            &packet.pdu,
+            ExtendedAdvertisingPduKind::AuxSyncInd,
+        );
+        print_json(json_advertising_packet(
+            "periodic_advertising_packet",
+            packet,
+            decoded.map(|decoded| json_extended_advertising(decoded.kind, &decoded.header)),
+        ));
+        return;
+    }
+    let semantic = decode_contextual_extended_advertising_pdu(
+        &packet.pdu,
+        ExtendedAdvertisingPduKind::AuxSyncInd,
+    )
+    .map(|decoded| decoded.to_string())
+    .unwrap_or_else(|error| format!("decode_error={error}"));
+    println!(
+        "channel={} phy={} sample={} phase={} access_address={:08x} inverted={} aa_errors={} pdu_type={} carrier_offset_hz={:.1} deviation_hz={:.1} header={} payload={} crc={} semantic=\"{}\"",
+        packet.pdu.channel.index(),
+        packet.phy,
+        packet.access_address_sample,
+        packet.symbol_phase,
+        packet.pdu.access_address,
+        packet.pdu.inverted,
+        packet.pdu.access_address_errors,
+        packet.pdu.pdu_type(),
+        packet.estimated_carrier_offset_hz,
+        packet.estimated_deviation_hz,
+        print_hex(&packet.pdu.header),
+        print_hex(&packet.pdu.payload),
+        print_hex(&packet.pdu.crc),
+        semantic.replace('"', "'"),
+    );
+}
+
+fn describe_control_pdu(control: ControlPdu<'_>) -> Result<String> {
+    Ok(match control.decode()? {
+        DecodedControlPdu::ConnectionUpdateInd(update) => format!(
+            "{} opcode=0x{:02x} window_offset={} window_size={} interval={} latency={} timeout={} instant={}",
+            control.opcode_name(),
+            control.opcode,
+            update.window_offset,
+            update.window_size,
+            update.parameters.interval,
+            update.parameters.latency,
+            update.parameters.supervision_timeout,
+            update.instant
+        ),
+        DecodedControlPdu::ChannelMapInd(update) => format!(
+            "{} opcode=0x{:02x} channel_map={} channels={} instant={}",
+            control.opcode_name(),
+            control.opcode,
+            print_hex(&update.channel_map.bytes()),
+            update.channel_map.used_count(),
+            update.instant
+        ),
+        DecodedControlPdu::TerminateInd(value) | DecodedControlPdu::RejectInd(value) => format!(
+            "{} opcode=0x{:02x} error_code=0x{:02x}",
+            control.opcode_name(),
+            control.opcode,
+            value.error_code
+        ),
+        DecodedControlPdu::EncryptionRequest(value) => format!(
+            "{} opcode=0x{:02x} rand={} ediv=0x{:04x} skd_c={} iv_c={}",
+            control.opcode_name(),
+            control.opcode,
+            print_hex(&value.random_number),
+            value.encrypted_diversifier,
+            print_hex(&value.central_session_key_diversifier),
+            print_hex(&value.central_initialization_vector)
+        ),
+        DecodedControlPdu::EncryptionResponse(value) => format!(
+            "{} opcode=0x{:02x} skd_p={} iv_p={}",
+            control.opcode_name(),
+            control.opcode,
+            print_hex(&value.peripheral_session_key_diversifier),
+            print_hex(&value.peripheral_initialization_vector)
+        ),
+        DecodedControlPdu::StartEncryptionRequest
+        | DecodedControlPdu::StartEncryptionResponse
+        | DecodedControlPdu::PauseEncryptionRequest
+        | DecodedControlPdu::PauseEncryptionResponse
+        | DecodedControlPdu::PingRequest
+        | DecodedControlPdu::PingResponse
+        | DecodedControlPdu::CteResponse
+        | DecodedControlPdu::CsFaeRequest => {
+            format!("{} opcode=0x{:02x}", control.opcode_name(), control.opcode)
+        }
+        DecodedControlPdu::UnknownResponse(value) => format!(
+            "{} opcode=0x{:02x} unknown_type=0x{:02x} unknown_name={}",
+            control.opcode_name(),
+            control.opcode,
+            value.unknown_type,
+            ControlPdu {
+                opcode: value.unknown_type,
+                parameters: &[]
+            }
+            .opcode_name()
+        ),
+        DecodedControlPdu::FeatureRequest(value)
+        | DecodedControlPdu::FeatureResponse(value)
+        | DecodedControlPdu::PeripheralFeatureRequest(value) => format!(
+            "{} opcode=0x{:02x} features={}",
+            control.opcode_name(),
+            control.opcode,
+            print_hex(&value.bytes)
+        ),
+        DecodedControlPdu::VersionInd(value) => format!(
+            "{} opcode=0x{:02x} version=0x{:02x} company=0x{:04x} subversion=0x{:04x}",
+            control.opcode_name(),
+            control.opcode,
+            value.version,
+            value.company_identifier,
+            value.subversion
+        ),
+        DecodedControlPdu::ConnectionParameterRequest(value)
+        | DecodedControlPdu::ConnectionParameterResponse(value) => format!(
+            "{} opcode=0x{:02x} interval_min={} interval_max={} latency={} timeout={} preferred_periodicity={} reference_event={} offsets={:04x},{:04x},{:04x},{:04x},{:04x},{:04x}",
+            control.opcode_name(),
+            control.opcode,
+            value.interval_min,
+            value.interval_max,
+            value.latency,
+            value.supervision_timeout,
+            value.preferred_periodicity,
+            value.reference_connection_event_count,
+            value.offsets[0],
+            value.offsets[1],
+            value.offsets[2],
+            value.offsets[3],
+            value.offsets[4],
+            value.offsets[5]
+        ),
+        DecodedControlPdu::RejectExtendedInd(value) => format!(
+            "{} opcode=0x{:02x} rejected_opcode=0x{:02x} rejected_name={} error_code=0x{:02x}",
+            control.opcode_name(),
+            control.opcode,
+            value.rejected_opcode,
+            ControlPdu {
+                opcode: value.rejected_opcode,
+                parameters: &[]
+            }
+            .opcode_name(),
+            value.error_code
+        ),
+        DecodedControlPdu::LengthRequest(value) | DecodedControlPdu::LengthResponse(value) => {
+            format!(
+                "{} opcode=0x{:02x} max_rx_octets={} max_rx_time_us={} max_tx_octets={} max_tx_time_us={}",
+                control.opcode_name(),
+                control.opcode,
+                value.maximum_receive_octets,
+                value.maximum_receive_time_us,
+                value.maximum_transmit_octets,
+                value.maximum_transmit_time_us
+            )
+        }
+        DecodedControlPdu::PhyRequest(value) | DecodedControlPdu::PhyResponse(value) => format!(
+            "{} opcode=0x{:02x} tx_phys=0x{:02x} rx_phys=0x{:02x}",
+            control.opcode_name(),
+            control.opcode,
+            value.transmit_phys,
+            value.receive_phys
+        ),
+        DecodedControlPdu::PhyUpdateInd(value) => format!(
+            "{} opcode=0x{:02x} central_to_peripheral_phy={} peripheral_to_central_phy={} instant={}",
+            control.opcode_name(),
+            control.opcode,
+            value
+                .central_to_peripheral_phy
+                .map_or_else(|| "unchanged".to_owned(), |phy| phy.to_string()),
+            value
+                .peripheral_to_central_phy
+                .map_or_else(|| "unchanged".to_owned(), |phy| phy.to_string()),
+            value.instant
+        ),
+        DecodedControlPdu::MinimumUsedChannelsInd(value) => format!(
+            "{} opcode=0x{:02x} phys=0x{:02x} minimum_used_channels={}",
+            control.opcode_name(),
+            control.opcode,
+            value.phys,
+            value.minimum_used_channels
+        ),
+        DecodedControlPdu::CteRequest(value) => format!(
+            "{} opcode=0x{:02x} minimum_cte_length={} minimum_cte_us={} cte_type={} cte_type_name={}",
+            control.opcode_name(),
+            control.opcode,
+            value.minimum_length_units,
+            value.minimum_duration_us(),
+            value.cte_type,
+            value.cte_type_name()
+        ),
+        DecodedControlPdu::PeriodicSyncInd(value) => format!(
+            "{} opcode=0x{:02x} id=0x{:04x} sync_offset_us={} interval={} channel_map={} access_address={:08x} crc_init={:06x} periodic_event={} connection_event={} last_periodic_event={} sid={} address_type={} sender_sca={} phy=0x{:02x} advertiser={} sync_connection_event={}",
+            control.opcode_name(),
+            control.opcode,
+            value.identifier,
+            value.sync_info.packet_window_offset_us(),
+            value.sync_info.interval,
+            print_hex(&value.sync_info.channel_map.bytes()),
+            value.sync_info.access_address,
+            value.sync_info.crc_init,
+            value.sync_info.periodic_event_counter,
+            value.connection_event_count,
+            value.last_periodic_event_counter,
+            value.advertising_sid,
+            if value.advertiser_address_random {
+                "random"
+            } else {
+                "public"
+            },
+            value.sender_sleep_clock_accuracy.raw(),
+            value.phy,
+            print_hex(&value.advertiser_address),
+            value.sync_connection_event_count
+        ),
+        DecodedControlPdu::ClockAccuracyRequest(value)
+        | DecodedControlPdu::ClockAccuracyResponse(value) => format!(
+            "{} opcode=0x{:02x} sca={} maximum_ppm={}",
+            control.opcode_name(),
+            control.opcode,
+            value.raw(),
+            value.maximum_ppm()
+        ),
+        DecodedControlPdu::CisRequest(value) => format!(
+            "{} opcode=0x{:02x} cig={} cis={} central_phy=0x{:02x} peripheral_phy=0x{:02x} central_max_sdu={} peripheral_max_sdu={} framed={} framing_mode={} central_sdu_interval_us={} peripheral_sdu_interval_us={} central_max_pdu={} peripheral_max_pdu={} nse={} sub_interval_us={} central_bn={} peripheral_bn={} central_ft={} peripheral_ft={} iso_interval={} offset_min_us={} offset_max_us={} connection_event={}",
+            control.opcode_name(),
+            control.opcode,
+            value.cig_identifier,
+            value.cis_identifier,
+            value.central_to_peripheral_phy,
+            value.peripheral_to_central_phy,
+            value.maximum_central_sdu,
+            value.maximum_peripheral_sdu,
+            value.framed,
+            if value.framing_mode_unsegmented {
+                "unsegmented"
+            } else {
+                "segmentable"
+            },
+            value.central_sdu_interval_us,
+            value.peripheral_sdu_interval_us,
+            value.maximum_central_pdu,
+            value.maximum_peripheral_pdu,
+            value.subevents,
+            value.subevent_interval_us,
+            value.central_burst_number,
+            value.peripheral_burst_number,
+            value.central_flush_timeout,
+            value.peripheral_flush_timeout,
+            value.iso_interval,
+            value.cis_offset_min_us,
+            value.cis_offset_max_us,
+            value.connection_event_count
+        ),
+        DecodedControlPdu::CisResponse(value) => format!(
+            "{} opcode=0x{:02x} offset_min_us={} offset_max_us={} connection_event={}",
+            control.opcode_name(),
+            control.opcode,
+            value.cis_offset_min_us,
+            value.cis_offset_max_us,
+            value.connection_event_count
+        ),
+        DecodedControlPdu::CisInd(value) => format!(
+            "{} opcode=0x{:02x} access_address={:08x} cis_offset_us={} cig_sync_delay_us={} cis_sync_delay_us={} connection_event={}",
+            control.opcode_name(),
+            control.opcode,
+            value.access_address,
+            value.cis_offset_us,
+            value.cig_sync_delay_us,
+            value.cis_sync_delay_us,
+            value.connection_event_count
+        ),
+        DecodedControlPdu::CisTerminateInd(value) => format!(
+            "{} opcode=0x{:02x} cig={} cis={} error_code=0x{:02x}",
+            control.opcode_name(),
+            control.opcode,
+            value.cig_identifier,
+            value.cis_identifier,
+            value.error_code
+        ),
+        DecodedControlPdu::PowerControlRequest(value) => format!(
+            "{} opcode=0x{:02x} phy=0x{:02x} delta_db={} tx_power_dbm={}",
+            control.opcode_name(),
+            control.opcode,
+            value.phy,
+            value.delta_db,
+            value.transmit_power_dbm
+        ),
+        DecodedControlPdu::PowerControlResponse(value) => format!(
+            "{} opcode=0x{:02x} minimum={} maximum={} delta_db={} tx_power_dbm={} acceptable_reduction_db={}",
+            control.opcode_name(),
+            control.opcode,
+            value.at_minimum,
+            value.at_maximum,
+            value.delta_db,
+            value.transmit_power_dbm,
+            value.acceptable_power_reduction_db
+        ),
+        DecodedControlPdu::PowerChangeInd(value) => format!(
+            "{} opcode=0x{:02x} phys=0x{:02x} minimum={} maximum={} delta_db={} tx_power_dbm={}",
+            control.opcode_name(),
+            control.opcode,
+            value.phys,
+            value.at_minimum,
+            value.at_maximum,
+            value.delta_db,
+            value.transmit_power_dbm
+        ),
+        DecodedControlPdu::SubrateRequest(value) => format!(
+            "{} opcode=0x{:02x} factor_min={} factor_max={} maximum_latency={} continuation_number={} timeout={}",
+            control.opcode_name(),
+            control.opcode,
+            value.factor_min,
+            value.factor_max,
+            value.maximum_latency,
+            value.continuation_number,
+            value.supervision_timeout
+        ),
+        DecodedControlPdu::SubrateInd(value) => format!(
+            "{} opcode=0x{:02x} factor={} base_event={} latency={} continuation_number={} timeout={}",
+            control.opcode_name(),
+            control.opcode,
+            value.factor,
+            value.base_event,
+            value.latency,
+            value.continuation_number,
+            value.supervision_timeout
+        ),
+        DecodedControlPdu::ChannelReportingInd(value) => format!(
+            "{} opcode=0x{:02x} enabled={} minimum_spacing={} maximum_delay={}",
+            control.opcode_name(),
+            control.opcode,
+            value.enabled,
+            value.minimum_spacing,
+            value.maximum_delay
+        ),
+        DecodedControlPdu::ChannelStatusInd(value) => {
+            let good = value
+                .classifications
+                .iter()
+                .filter(|classification| **classification == ChannelClassification::Good)
+                .count();
+            let bad = value
+                .classifications
+                .iter()
+                .filter(|classification| **classification == ChannelClassification::Bad)
+                .count();
+            format!(
+                "{} opcode=0x{:02x} good_channels={} bad_channels={} unknown_channels={}",
+                control.opcode_name(),
+                control.opcode,
+                good,
+                bad,
+                value.classifications.len() - good - bad
+            )
+        }
+        DecodedControlPdu::PeriodicSyncWrInd(value) => format!(
+            "{} opcode=0x{:02x} id=0x{:04x} sync_offset_us={} interval={} channel_map={} periodic_access_address={:08x} response_access_address={:08x} subevents={} subevent_interval={} response_slot_delay={} response_slot_spacing={}",
+            control.opcode_name(),
+            control.opcode,
+            value.periodic_sync.identifier,
+            value.periodic_sync.sync_info.packet_window_offset_us(),
+            value.periodic_sync.sync_info.interval,
+            print_hex(&value.periodic_sync.sync_info.channel_map.bytes()),
+            value.periodic_sync.sync_info.access_address,
+            value.response_access_address,
+            value.subevent_count,
+            value.subevent_interval,
+            value.response_slot_delay,
+            value.response_slot_spacing
+        ),
+        DecodedControlPdu::FeatureExtendedRequest(value)
+        | DecodedControlPdu::FeatureExtendedResponse(value) => format!(
+            "{} opcode=0x{:02x} maximum_page={} page={} features={}",
+            control.opcode_name(),
+            control.opcode,
+            value.maximum_page,
+            value.page_number,
+            print_hex(&value.feature_page)
+        ),
+        DecodedControlPdu::CsSecurityRequest(value)
+        | DecodedControlPdu::CsSecurityResponse(value) => format!(
+            "{} opcode=0x{:02x} iv={} nonce={} personalization_vector={}",
+            control.opcode_name(),
+            control.opcode,
+            print_hex(&value.initialization_vector),
+            print_hex(&value.nonce),
+            print_hex(&value.personalization_vector)
+        ),
+        DecodedControlPdu::CsCapabilitiesRequest(value)
+        | DecodedControlPdu::CsCapabilitiesResponse(value) => format!(
+            "{} opcode=0x{:02x} mode_types=0x{:02x} rtt_capability=0x{:02x} rtt_aa_only_n={} rtt_sounding_n={} rtt_random_sequence_n={} nadm_sounding=0x{:04x} nadm_random=0x{:04x} cs_sync_phys=0x{:02x} antennas={} maximum_antenna_paths={} roles=0x{:02x} no_fae={} channel_selection_3c={} sounding_pct_estimate={} configurations={} maximum_procedures={} t_sw_us={} t_ip1_capability=0x{:04x} t_ip2_capability=0x{:04x} t_fcs_capability=0x{:04x} t_pm_capability=0x{:04x} tx_snr_capability=0x{:02x}",
+            control.opcode_name(),
+            control.opcode,
+            value.mode_types,
+            value.rtt_capability,
+            value.rtt_aa_only_n,
+            value.rtt_sounding_n,
+            value.rtt_random_sequence_n,
+            value.nadm_sounding_capability,
+            value.nadm_random_capability,
+            value.cs_sync_phy_capability,
+            value.antenna_count,
+            value.maximum_antenna_paths,
+            value.roles,
+            value.no_fae,
+            value.channel_selection_3c,
+            value.sounding_pct_estimate,
+            value.configuration_count,
+            value.maximum_procedures_supported,
+            value.antenna_switch_time_us,
+            value.t_ip1_capability,
+            value.t_ip2_capability,
+            value.t_fcs_capability,
+            value.t_pm_capability,
+            value.tx_snr_capability
+        ),
+        DecodedControlPdu::CsConfigRequest(value) => format!(
+            "{} opcode=0x{:02x} config={} action={} channel_map={} channels={} channel_map_repetition={} main_mode={} sub_mode={} main_mode_min_steps={} main_mode_max_steps={} main_mode_repetition={} mode_0_steps={} cs_sync_phy=0x{:02x} rtt_type={} role={} channel_selection={} channel_selection_3c_shape={} channel_selection_3c_jump={} t_ip1={} t_ip2={} t_fcs={} t_pm={}",
+            control.opcode_name(),
+            control.opcode,
+            value.config_id,
+            match value.action {
+                CsConfigAction::Remove => "remove",
+                CsConfigAction::Create => "create",
+            },
+            print_hex(&value.channel_map.bytes()),
+            value.channel_map.used_count(),
+            value.channel_map_repetition,
+            value.main_mode,
+            value.sub_mode,
+            value.main_mode_min_steps,
+            value.main_mode_max_steps,
+            value.main_mode_repetition,
+            value.mode_0_steps,
+            value.cs_sync_phy,
+            value.rtt_type,
+            value.role,
+            value.channel_selection,
+            value.channel_selection_3c_shape,
+            value.channel_selection_3c_jump,
+            value.t_ip1,
+            value.t_ip2,
+            value.t_fcs,
+            value.t_pm
+        ),
+        DecodedControlPdu::CsConfigResponse(value) => format!(
+            "{} opcode=0x{:02x} config={}",
+            control.opcode_name(),
+            control.opcode,
+            value.config_id
+        ),
+        DecodedControlPdu::CsProcedureRequest(value) => format!(
+            "{} opcode=0x{:02x} config={} connection_event={} offset_min_us={} offset_max_us={} maximum_procedure_length_units={} maximum_procedure_length_us={} event_interval={} subevents_per_event={} subevent_interval_units={} subevent_interval_us={} subevent_length_us={} procedure_interval={} procedure_count={} aci={} preferred_peer_antennas=0x{:02x} phy=0x{:02x} power_delta_db={} initiator_snr_index={} reflector_snr_index={}",
+            control.opcode_name(),
+            control.opcode,
+            value.config_id,
+            value.connection_event_count,
+            value.offset_min_us,
+            value.offset_max_us,
+            value.maximum_procedure_length_units,
+            value.maximum_procedure_length_us(),
+            value.event_interval_connection_events,
+            value.subevents_per_event,
+            value.subevent_interval_units,
+            value.subevent_interval_us(),
+            value.subevent_length_us,
+            value.procedure_interval_connection_events,
+            value.procedure_count,
+            value.antenna_configuration_index,
+            value.preferred_peer_antennas,
+            value.phy,
+            value.power_delta_db,
+            value.initiator_snr_index,
+            value.reflector_snr_index
+        ),
+        DecodedControlPdu::CsProcedureResponse(value) => format!(
+            "{} opcode=0x{:02x} config={} connection_event={} offset_min_us={} offset_max_us={} event_interval={} subevents_per_event={} subevent_interval_units={} subevent_interval_us={} subevent_length_us={} aci={} phy=0x{:02x} power_delta_db={}",
+            control.opcode_name(),
+            control.opcode,
+            value.config_id,
+            value.connection_event_count,
+            value.offset_min_us,
+            value.offset_max_us,
+            value.event_interval_connection_events,
+            value.subevents_per_event,
+            value.subevent_interval_units,
+            value.subevent_interval_us(),
+            value.subevent_length_us,
+            value.antenna_configuration_index,
+            value.phy,
+            value.power_delta_db
+        ),
+        DecodedControlPdu::CsProcedureIndication(value) => format!(
+            "{} opcode=0x{:02x} config={} connection_event={} offset_us={} event_interval={} subevents_per_event={} subevent_interval_units={} subevent_interval_us={} subevent_length_us={} aci={} phy=0x{:02x} power_delta_db={}",
+            control.opcode_name(),
+            control.opcode,
+            value.config_id,
+            value.connection_event_count,
+            value.offset_us,
+            value.event_interval_connection_events,
+            value.subevents_per_event,
+            value.subevent_interval_units,
+            value.subevent_interval_us(),
+            value.subevent_length_us,
+            value.antenna_configuration_index,
+            value.phy,
+            value.power_delta_db
+        ),
+        DecodedControlPdu::CsTerminateRequest(value)
+        | DecodedControlPdu::CsTerminateResponse(value) => format!(
+            "{} opcode=0x{:02x} config={} procedure_count={} error_code=0x{:02x}",
             control.opcode_name(),
             control.opcode,
-            value.transmit_phys,
-            value.receive_phys
+            value.config_id,
+            value.procedure_count,
+            value.error_code
         ),
-        DecodedControlPdu::PhyUpdateInd(value) => format!(
-            "{} opcode=0x{:02x} central_to_peripheral_phy={} peripheral_to_central_phy={} instant={}",
+        DecodedControlPdu::CsFaeResponse(value) => format!(
+            "{} opcode=0x{:02x} fae={}",
             control.opcode_name(),
             control.opcode,
-            value
-                .central_to_peripheral_phy
-                .map_or_else(|| "unchanged".to_owned(), |phy| phy.to_string()),
-            value
-                .peripheral_to_central_phy
-                .map_or_else(|| "unchanged".to_owned(), |phy| phy.to_string()),
+            print_signed_hex(&value.values)
+        ),
+        DecodedControlPdu::CsChannelMapInd(value) => format!(
+            "{} opcode=0x{:02x} channel_map={} channels={} instant={}",
+            control.opcode_name(),
+            control.opcode,
+            print_hex(&value.channel_map.bytes()),
+            value.channel_map.used_count(),
             value.instant
         ),
-        DecodedControlPdu::MinimumUsedChannelsInd(value) => format!(
-            "{} opcode=0x{:02x} phys=0x{:02x} minimum_used_channels={}",
+        DecodedControlPdu::FrameSpaceRequest(value) => format!(
+            "{} opcode=0x{:02x} minimum_us={} maximum_us={} phys=0x{:02x} spacing_types=0x{:04x}",
             control.opcode_name(),
             control.opcode,
+            value.minimum_us,
+            value.maximum_us,
             value.phys,
-            value.minimum_used_channels
+            value.spacing_types
         ),
-        DecodedControlPdu::CteRequest(value) => format!(
-            "{} opcode=0x{:02x} minimum_cte_length={} minimum_cte_us={} cte_type={} cte_type_name={}",
+        DecodedControlPdu::FrameSpaceResponse(value) => format!(
+            "{} opcode=0x{:02x} frame_space_us={} phys=0x{:02x} spacing_types=0x{:04x}",
             control.opcode_name(),
             control.opcode,
-            value.minimum_length_units,
-            value.minimum_duration_us(),
-            value.cte_type,
-            value.cte_type_name()
+            value.frame_space_us,
+            value.phys,
+            value.spacing_types
+        ),
+        DecodedControlPdu::Raw { parameters, .. } => format!(
+            "{} opcode=0x{:02x} raw_parameters={}",
+            control.opcode_name(),
+            control.opcode,
+            print_hex(parameters)
+        ),
+    })
+}
+
+fn describe_data_pdu(packet: &DataChannelPdu) -> Result<String> {
+    match packet.llid() {
+        LogicalLinkId::StartOrComplete => {
+            let start = packet
+                .l2cap_start()?
+                .expect("LLID checked before L2CAP start decode");
+            Ok(format!(
+                "L2CAP length={} cid=0x{:04x} fragment_octets={}",
+                start.payload_length,
+                start.channel_id,
+                start.fragment.len()
+            ))
+        }
+        LogicalLinkId::Control => describe_control_pdu(
+            packet
+                .control()?
+                .expect("LLID checked before LL control decode"),
+        ),
+        LogicalLinkId::ContinuationOrEmpty if packet.payload.is_empty() => Ok("empty".to_owned()),
+        LogicalLinkId::ContinuationOrEmpty => Ok(format!(
+            "L2CAP continuation_octets={}",
+            packet.payload.len()
+        )),
+        LogicalLinkId::Reserved => Ok(format!(
+            "reserved_llid payload_octets={}",
+            packet.payload.len()
+        )),
+    }
+}
+
+/// Hands each packet of a decode batch to `write_packet` and reports the
+/// batch's CRC failures between them by sample, so the PCAPNG stays in time
+/// order.
+fn write_interleaved_crc_failures<P: CaptureObservation>(
+    packets: &[P],
+    crc_failures: &[ReceivedLePdu],
+    pcap: &mut Option<PcapNgWriter<BufWriter<File>>>,
+    capture_start_ns: u64,
+    sample_rate_hz: u32,
+    output_format: OutputFormat,
+    mut write_packet: impl FnMut(&P, Option<&mut PcapNgWriter<BufWriter<File>>>) -> Result<()>,
+) -> Result<()> {
+    for item in in_sample_order(packets, crc_failures) {
+        match item {
+            SampleOrdered::Packet(packet) => write_packet(packet, pcap.as_mut())?,
+            SampleOrdered::CrcFailure(failure) => report_crc_failure(
+                failure,
+                failure.access_address_sample,
+                pcap.as_mut(),
+                capture_start_ns,
+                sample_rate_hz,
+                output_format,
+            )?,
+        }
+    }
+    Ok(())
+}
+
+/// Prints a CRC-failed frame and writes it to PCAPNG with the CRC marked
+/// checked and invalid. None of its octets can be trusted, so it gets no
+/// semantic decoding.
+fn report_crc_failure(
+    failure: &ReceivedLePdu,
+    sample: u64,
+    pcap: Option<&mut PcapNgWriter<BufWriter<File>>>,
+    capture_start_ns: u64,
+    sample_rate_hz: u32,
+    output_format: OutputFormat,
+) -> Result<()> {
+    if output_format == OutputFormat::JsonLines {
+        print_json(
+            json_received(
+                "crc_failure",
+                failure.pdu.channel,
+                failure.phy,
+                sample,
+                failure,
+            )
+            .field("header", Json::hex(&failure.pdu.header))
+            .field("cte_info", failure.pdu.cte_info)
+            .field("payload", Json::hex(&failure.pdu.payload))
+            .field("crc", Json::hex(&failure.pdu.crc)),
+        );
+    } else {
+        println!(
+            "crc_failure channel={} phy={} sample={} phase={} access_address={:08x} inverted={} aa_errors={} carrier_offset_hz={:.1} deviation_hz={:.1} header={} cte_info={} payload={} crc={}",
+            failure.pdu.channel.index(),
+            failure.phy,
+            sample,
+            failure.symbol_phase,
+            failure.pdu.access_address,
+            failure.pdu.inverted,
+            failure.pdu.access_address_errors,
+            failure.estimated_carrier_offset_hz,
+            failure.estimated_deviation_hz,
+            print_hex(&failure.pdu.header),
+            failure
+                .pdu
+                .cte_info
+                .map(|value| format!("0x{value:02x}"))
+                .unwrap_or_else(|| "none".to_owned()),
+            print_hex(&failure.pdu.payload),
+            print_hex(&failure.pdu.crc),
+        );
+    }
+    if let Some(writer) = pcap {
+        let timestamp = sample_timestamp_ns(capture_start_ns, sample, sample_rate_hz)?;
+        writer.write_crc_failure(
+            failure,
+            timestamp,
+            Some(&PcapNgPacketOptions::received(0, failure)),
+        )?;
+    }
+    Ok(())
+}
+
+fn print_data_packet(
+    packet: &ReceivedLePdu,
+    data: &DataChannelPdu,
+    payload_is_plaintext: bool,
+    output_format: OutputFormat,
+) -> Result<()> {
+    if output_format == OutputFormat::JsonLines {
+        let (pdu, result) = json_decode_result(
+            payload_is_plaintext
+                .then(|| json_data_pdu(data))
+                .transpose(),
+        );
+        print_json(
+            json_received(
+                "data_packet",
+                data.channel,
+                packet.phy,
+                packet.access_address_sample,
+                packet,
+            )
+            .field("llid", Json::display(data.llid()))
+            .field("nesn", data.next_expected_sequence_number())
+            .field("sn", data.sequence_number())
+            .field("md", data.more_data())
+            .field("cp", data.constant_tone_extension_present())
+            .field(
+                "cte_info",
+                data.constant_tone_extension_info().map(json_cte_info),
+            )
+            .field("rfu", data.reserved_header_bits())
+            .field("header", Json::hex(&data.header))
+            .field("payload", Json::hex(&data.payload))
+            .field("crc", Json::hex(&data.crc))
+            .field("encrypted", !payload_is_plaintext)
+            .extend(pdu),
+        );
+        return result;
+    }
+    let cte = data
+        .constant_tone_extension_info()
+        .map(|info| {
+            format!(
+                "0x{:02x}:{}:{}us:rfu={}:reserved={}",
+                info.raw(),
+                info.cte_type_name(),
+                info.duration_us(),
+                info.rfu(),
+                info.has_reserved_value()
+            )
+        })
+        .unwrap_or_else(|| "none".to_owned());
+    let description = payload_is_plaintext.then(|| describe_data_pdu(data));
+    let plaintext_hint = match &description {
+        Some(Ok(description)) => description.clone(),
+        Some(Err(error)) => format!("decode_error={error}"),
+        None => "encrypted".to_owned(),
+    };
+    println!(
+        "channel={} phy={} sample={} phase={} access_address={:08x} inverted={} aa_errors={} llid={} nesn={} sn={} md={} cp={} cte={} rfu={} carrier_offset_hz={:.1} deviation_hz={:.1} header={} payload={} crc={} plaintext_hint=\"{}\"",
+        data.channel.index(),
+        packet.phy,
+        packet.access_address_sample,
+        packet.symbol_phase,
+        data.access_address,
+        data.inverted,
+        data.access_address_errors,
+        data.llid(),
+        data.next_expected_sequence_number(),
+        data.sequence_number(),
+        data.more_data(),
+        data.constant_tone_extension_present(),
+        cte,
+        data.reserved_header_bits(),
+        packet.estimated_carrier_offset_hz,
+        packet.estimated_deviation_hz,
+        print_hex(&data.header),
+        print_hex(&data.payload),
+        print_hex(&data.crc),
+        plaintext_hint.replace('"', "'"),
+    );
+    description.transpose().map(|_| ())
+}
+
+fn print_decrypted_data_packet(
+    direction: LinkDirection,
+    decryption: &LeAclDecryption,
+    output_format: OutputFormat,
+) -> Result<()> {
+    if output_format == OutputFormat::JsonLines {
+        let (status, packet_counter) = match decryption.status {
+            LeAclDecryptionStatus::New { packet_counter, .. } => ("new", Some(packet_counter)),
+            LeAclDecryptionStatus::Retransmission { packet_counter } => {
+                ("retransmission", Some(packet_counter))
+            }
+            LeAclDecryptionStatus::UnencryptedEmpty => ("unencrypted-empty", None),
+        };
+        let (pdu, result) = json_decode_result(json_data_pdu(&decryption.packet));
+        print_json(
+            json_record("decrypted_data_packet")
+                .field("direction", Json::display(direction))
+                .field("status", status)
+                .field("packet_counter", packet_counter)
+                .field("skipped_counters", decryption.status.skipped_counters())
+                .field("header", Json::hex(&decryption.packet.header))
+                .field("payload", Json::hex(&decryption.packet.payload))
+                .extend(pdu),
+        );
+        return result;
+    }
+    let description = describe_data_pdu(&decryption.packet);
+    let plaintext_hint = match &description {
+        Ok(description) => description.clone(),
+        Err(error) => format!("decode_error={error}"),
+    };
+    let (status, packet_counter) = match decryption.status {
+        LeAclDecryptionStatus::New { packet_counter, .. } => ("new", packet_counter.to_string()),
+        LeAclDecryptionStatus::Retransmission { packet_counter } => {
+            ("retransmission", packet_counter.to_string())
+        }
+        LeAclDecryptionStatus::UnencryptedEmpty => ("unencrypted-empty", "none".to_owned()),
+    };
+    println!(
+        "decrypted_data direction={} status={} packet_counter={} skipped_counters={} header={} payload={} plaintext_hint=\"{}\"",
+        direction,
+        status,
+        packet_counter,
+        decryption.status.skipped_counters(),
+        print_hex(&decryption.packet.header),
+        print_hex(&decryption.packet.payload),
+        plaintext_hint.replace('"', "'"),
+    );
+    description.map(|_| ())
+}
+
+fn describe_incomplete_l2cap(incomplete: IncompleteL2capPdu) -> String {
+    format!(
+        "direction={} cid=0x{:04x} received={} expected={} fragments={}",
+        incomplete.direction,
+        incomplete.channel_id,
+        incomplete.received_payload_length,
+        incomplete.expected_payload_length,
+        incomplete.fragment_count
+    )
+}
+
+fn print_l2cap_channel_ids(channel_ids: &[u16]) -> String {
+    channel_ids
+        .iter()
+        .map(|channel_id| format!("0x{channel_id:04x}"))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn describe_l2cap_signaling(command: L2capSignalingCommand<'_>) -> Result<String> {
+    let prefix = format!(
+        "code=0x{:02x} name={} identifier={}",
+        command.code,
+        command.code_name(),
+        command.identifier
+    );
+    let details = match command.decode()? {
+        DecodedL2capSignalingCommand::CommandReject(reject) => {
+            format!(
+                "reason=0x{:04x} data={}",
+                reject.reason,
+                print_hex(reject.data)
+            )
+        }
+        DecodedL2capSignalingCommand::DisconnectionRequest(disconnection)
+        | DecodedL2capSignalingCommand::DisconnectionResponse(disconnection) => format!(
+            "destination_cid=0x{:04x} source_cid=0x{:04x}",
+            disconnection.destination_channel_id, disconnection.source_channel_id
+        ),
+        DecodedL2capSignalingCommand::ConnectionParameterUpdateRequest(request) => format!(
+            "minimum_interval={} maximum_interval={} latency={} supervision_timeout={}",
+            request.minimum_interval,
+            request.maximum_interval,
+            request.latency,
+            request.supervision_timeout
+        ),
+        DecodedL2capSignalingCommand::ConnectionParameterUpdateResponse(response) => {
+            format!("result=0x{:04x}", response.result)
+        }
+        DecodedL2capSignalingCommand::LeCreditBasedConnectionRequest(request) => format!(
+            "spsm=0x{:04x} source_cid=0x{:04x} mtu={} mps={} initial_credits={}",
+            request.spsm,
+            request.source_channel_id,
+            request.mtu,
+            request.mps,
+            request.initial_credits
         ),
-        DecodedControlPdu::PeriodicSyncInd(value) => format!(
-            "{} opcode=0x{:02x} id=0x{:04x} sync_offset_us={} interval={} channel_map={} access_address={:08x} crc_init={:06x} periodic_event={} connection_event={} last_periodic_event={} sid={} address_type={} sender_sca={} phy=0x{:02x} advertiser={} sync_connection_event={}",
-            control.opcode_name(),
-            control.opcode,
-            value.identifier,
-            value.sync_info.packet_window_offset_us(),
-            value.sync_info.interval,
-            print_hex(&value.sync_info.channel_map.bytes()),
-            value.sync_info.access_address,
-            value.sync_info.crc_init,
-            value.sync_info.periodic_event_counter,
-            value.connection_event_count,
-            value.last_periodic_event_counter,
-            value.advertising_sid,
-            if value.advertiser_address_random {
-                "random"
-            } else {
-                "public"
-            },
-            value.sender_sleep_clock_accuracy.raw(),
-            value.phy,
-            print_hex(&value.advertiser_address),
-            value.sync_connection_event_count
+        DecodedL2capSignalingCommand::LeCreditBasedConnectionResponse(response) => format!(
+            "destination_cid=0x{:04x} mtu={} mps={} initial_credits={} result=0x{:04x}",
+            response.destination_channel_id,
+            response.mtu,
+            response.mps,
+            response.initial_credits,
+            response.result
         ),
-        DecodedControlPdu::ClockAccuracyRequest(value)
-        | DecodedControlPdu::ClockAccuracyResponse(value) => format!(
-            "{} opcode=0x{:02x} sca={} maximum_ppm={}",
-            control.opcode_name(),
-            control.opcode,
-            value.raw(),
-            value.maximum_ppm()
+        DecodedL2capSignalingCommand::FlowControlCredit(credit) => {
+            format!("cid=0x{:04x} credits={}", credit.channel_id, credit.credits)
+        }
+        DecodedL2capSignalingCommand::EnhancedCreditBasedConnectionRequest(request) => format!(
+            "spsm=0x{:04x} mtu={} mps={} initial_credits={} source_cids={}",
+            request.spsm,
+            request.mtu,
+            request.mps,
+            request.initial_credits,
+            print_l2cap_channel_ids(request.source_channel_ids.as_slice())
         ),
-        DecodedControlPdu::CisRequest(value) => format!(
-            "{} opcode=0x{:02x} cig={} cis={} central_phy=0x{:02x} peripheral_phy=0x{:02x} central_max_sdu={} peripheral_max_sdu={} framed={} framing_mode={} central_sdu_interval_us={} peripheral_sdu_interval_us={} central_max_pdu={} peripheral_max_pdu={} nse={} sub_interval_us={} central_bn={} peripheral_bn={} central_ft={} peripheral_ft={} iso_interval={} offset_min_us={} offset_max_us={} connection_event={}",
-            control.opcode_name(),
-            control.opcode,
-            value.cig_identifier,
-            value.cis_identifier,
-            value.central_to_peripheral_phy,
-            value.peripheral_to_central_phy,
-            value.maximum_central_sdu,
-            value.maximum_peripheral_sdu,
-            value.framed,
-            if value.framing_mode_unsegmented {
-                "unsegmented"
-            } else {
-                "segmentable"
-            },
-            value.central_sdu_interval_us,
-            value.peripheral_sdu_interval_us,
-            value.maximum_central_pdu,
-            value.maximum_peripheral_pdu,
-            value.subevents,
-            value.subevent_interval_us,
-            value.central_burst_number,
-            value.peripheral_burst_number,
-            value.central_flush_timeout,
-            value.peripheral_flush_timeout,
-            value.iso_interval,
-            value.cis_offset_min_us,
-            value.cis_offset_max_us,
-            value.connection_event_count
+        DecodedL2capSignalingCommand::EnhancedCreditBasedConnectionResponse(response) => format!(
+            "mtu={} mps={} initial_credits={} result=0x{:04x} destination_cids={}",
+            response.mtu,
+            response.mps,
+            response.initial_credits,
+            response.result,
+            print_l2cap_channel_ids(response.destination_channel_ids.as_slice())
         ),
-        DecodedControlPdu::CisResponse(value) => format!(
-            "{} opcode=0x{:02x} offset_min_us={} offset_max_us={} connection_event={}",
-            control.opcode_name(),
-            control.opcode,
-            value.cis_offset_min_us,
-            value.cis_offset_max_us,
-            value.connection_event_count
+        DecodedL2capSignalingCommand::EnhancedCreditBasedReconfigureRequest(request) => format!(
+            "mtu={} mps={} cids={}",
+            request.mtu,
+            request.mps,
+            print_l2cap_channel_ids(request.channel_ids.as_slice())
         ),
-        DecodedControlPdu::CisInd(value) => format!(
-            "{} opcode=0x{:02x} access_address={:08x} cis_offset_us={} cig_sync_delay_us={} cis_sync_delay_us={} connection_event={}",
-            control.opcode_name(),
-            control.opcode,
-            value.access_address,
-            value.cis_offset_us,
-            value.cig_sync_delay_us,
-            value.cis_sync_delay_us,
-            value.connection_event_count
+        DecodedL2capSignalingCommand::EnhancedCreditBasedReconfigureResponse(response) => {
+            format!("result=0x{:04x}", response.result)
+        }
+        DecodedL2capSignalingCommand::Unknown { parameters, .. } => {
+            format!("parameters={}", print_hex(parameters))
+        }
+    };
+    Ok(format!("{prefix} {details}"))
+}
+
+fn describe_att_uuid(uuid: AttUuid) -> String {
+    match uuid {
+        AttUuid::Uuid16(uuid) => format!("0x{uuid:04x}"),
+        AttUuid::Uuid128(uuid) => print_hex(&uuid),
+    }
+}
+
+fn describe_att_pdu(pdu: AttPdu<'_>) -> Result<String> {
+    let prefix = format!(
+        "opcode=0x{:02x} name={} type={}",
+        pdu.opcode,
+        pdu.opcode_name(),
+        pdu.pdu_type()
+    );
+    let details = match pdu.decode()? {
+        DecodedAttPdu::ErrorResponse(response) => format!(
+            "request_opcode=0x{:02x} handle=0x{:04x} error=0x{:02x} error_name={}",
+            response.request_opcode,
+            response.handle,
+            response.error_code,
+            response.error_name()
         ),
-        DecodedControlPdu::CisTerminateInd(value) => format!(
-            "{} opcode=0x{:02x} cig={} cis={} error_code=0x{:02x}",
-            control.opcode_name(),
-            control.opcode,
-            value.cig_identifier,
-            value.cis_identifier,
-            value.error_code
+        DecodedAttPdu::ExchangeMtuRequest(exchange)
+        | DecodedAttPdu::ExchangeMtuResponse(exchange) => {
+            format!("mtu={}", exchange.mtu)
+        }
+        DecodedAttPdu::FindInformationRequest(range) => format!(
+            "start_handle=0x{:04x} end_handle=0x{:04x}",
+            range.start_handle, range.end_handle
         ),
-        DecodedControlPdu::PowerControlRequest(value) => format!(
-            "{} opcode=0x{:02x} phy=0x{:02x} delta_db={} tx_power_dbm={}",
-            control.opcode_name(),
-            control.opcode,
-            value.phy,
-            value.delta_db,
-            value.transmit_power_dbm
+        DecodedAttPdu::FindInformationResponse(response) => {
+            let entries = response
+                .entries
+                .iter()
+                .map(|entry| format!("0x{:04x}:{}", entry.handle, describe_att_uuid(entry.uuid)))
+                .collect::<Vec<_>>()
+                .join(",");
+            format!("uuid_width={} entries={entries}", response.uuid_width())
+        }
+        DecodedAttPdu::FindByTypeValueRequest(request) => format!(
+            "start_handle=0x{:04x} end_handle=0x{:04x} attribute_type=0x{:04x} value={}",
+            request.range.start_handle,
+            request.range.end_handle,
+            request.attribute_type,
+            print_hex(request.value)
         ),
-        DecodedControlPdu::PowerControlResponse(value) => format!(
-            "{} opcode=0x{:02x} minimum={} maximum={} delta_db={} tx_power_dbm={} acceptable_reduction_db={}",
-            control.opcode_name(),
-            control.opcode,
-            value.at_minimum,
-            value.at_maximum,
-            value.delta_db,
-            value.transmit_power_dbm,
-            value.acceptable_power_reduction_db
+        DecodedAttPdu::FindByTypeValueResponse(ranges) => {
+            let ranges = ranges
+                .iter()
+                .map(|range| format!("0x{:04x}-0x{:04x}", range.start_handle, range.end_handle))
+                .collect::<Vec<_>>()
+                .join(",");
+            format!("ranges={ranges}")
+        }
+        DecodedAttPdu::ReadByTypeRequest(request)
+        | DecodedAttPdu::ReadByGroupTypeRequest(request) => format!(
+            "start_handle=0x{:04x} end_handle=0x{:04x} attribute_type={}",
+            request.range.start_handle,
+            request.range.end_handle,
+            describe_att_uuid(request.attribute_type)
+        ),
+        DecodedAttPdu::ReadByTypeResponse(response) => {
+            let entries = response
+                .entries
+                .iter()
+                .map(|entry| format!("0x{:04x}:{}", entry.handle, print_hex(entry.value)))
+                .collect::<Vec<_>>()
+                .join(",");
+            format!("entry_length={} entries={entries}", response.entry_length)
+        }
+        DecodedAttPdu::ReadRequest(request) => {
+            format!("handle=0x{:04x}", request.handle)
+        }
+        DecodedAttPdu::ReadResponse(value)
+        | DecodedAttPdu::ReadBlobResponse(value)
+        | DecodedAttPdu::ReadMultipleResponse(value) => {
+            format!("value={}", print_hex(value))
+        }
+        DecodedAttPdu::ReadBlobRequest(request) => {
+            format!("handle=0x{:04x} offset={}", request.handle, request.offset)
+        }
+        DecodedAttPdu::ReadMultipleRequest(handles)
+        | DecodedAttPdu::ReadMultipleVariableRequest(handles) => {
+            let handles = handles
+                .iter()
+                .map(|handle| format!("0x{handle:04x}"))
+                .collect::<Vec<_>>()
+                .join(",");
+            format!("handles={handles}")
+        }
+        DecodedAttPdu::ReadByGroupTypeResponse(response) => {
+            let entries = response
+                .entries
+                .iter()
+                .map(|entry| {
+                    format!(
+                        "0x{:04x}-0x{:04x}:{}",
+                        entry.range.start_handle,
+                        entry.range.end_handle,
+                        print_hex(entry.value)
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(",");
+            format!("entry_length={} entries={entries}", response.entry_length)
+        }
+        DecodedAttPdu::WriteRequest(write)
+        | DecodedAttPdu::HandleValueNotification(write)
+        | DecodedAttPdu::HandleValueIndication(write)
+        | DecodedAttPdu::WriteCommand(write) => {
+            format!(
+                "handle=0x{:04x} value={}",
+                write.handle,
+                print_hex(write.value)
+            )
+        }
+        DecodedAttPdu::WriteResponse
+        | DecodedAttPdu::ExecuteWriteResponse
+        | DecodedAttPdu::HandleValueConfirmation => "parameters=none".to_owned(),
+        DecodedAttPdu::PrepareWriteRequest(write) | DecodedAttPdu::PrepareWriteResponse(write) => {
+            format!(
+                "handle=0x{:04x} offset={} value={}",
+                write.handle,
+                write.offset,
+                print_hex(write.value)
+            )
+        }
+        DecodedAttPdu::ExecuteWriteRequest(flags) => format!("flags={flags}"),
+        DecodedAttPdu::ReadMultipleVariableResponse(list) => {
+            let values = list
+                .values
+                .iter()
+                .map(|value| {
+                    format!(
+                        "{}:{}:{}",
+                        value.declared_length,
+                        print_hex(value.value),
+                        value.truncated
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(",");
+            format!("values={values}")
+        }
+        DecodedAttPdu::MultipleHandleValueNotification(values) => {
+            let values = values
+                .iter()
+                .map(|value| format!("0x{:04x}:{}", value.handle, print_hex(value.value)))
+                .collect::<Vec<_>>()
+                .join(",");
+            format!("values={values}")
+        }
+        DecodedAttPdu::SignedWriteCommand(write) => format!(
+            "handle=0x{:04x} value={} signature={}",
+            write.handle,
+            print_hex(write.value),
+            print_hex(&write.signature)
         ),
-        DecodedControlPdu::PowerChangeInd(value) => format!(
-            "{} opcode=0x{:02x} phys=0x{:02x} minimum={} maximum={} delta_db={} tx_power_dbm={}",
-            control.opcode_name(),
-            control.opcode,
-            value.phys,
-            value.at_minimum,
-            value.at_maximum,
-            value.delta_db,
-            value.transmit_power_dbm
+        DecodedAttPdu::Unknown { parameters, .. } => {
+            format!("parameters={}", print_hex(parameters))
+        }
+    };
+    Ok(format!("{prefix} {details}"))
+}
+
+fn describe_smp_authentication(authentication: SmpAuthenticationRequirements) -> String {
+    format!(
+        "auth=0x{:02x} bonding={} mitm={} secure_connections={} keypress={} ct2={}",
+        authentication.raw,
+        authentication.bonding(),
+        authentication.mitm(),
+        authentication.secure_connections(),
+        authentication.keypress(),
+        authentication.ct2()
+    )
+}
+
+fn describe_smp_key_distribution(distribution: SmpKeyDistribution) -> String {
+    format!(
+        "0x{:02x}:enc={}:id={}:sign={}:link={}",
+        distribution.raw,
+        distribution.encryption_key(),
+        distribution.identity_key(),
+        distribution.signing_key(),
+        distribution.link_key()
+    )
+}
+
+fn print_device_address(address: [u8; 6]) -> String {
+    address
+        .iter()
+        .rev()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+fn describe_smp_pdu(pdu: SmpPdu<'_>) -> Result<String> {
+    let prefix = format!("code=0x{:02x} name={}", pdu.code, pdu.code_name());
+    let details = match pdu.decode()? {
+        DecodedSmpPdu::PairingRequest(features) | DecodedSmpPdu::PairingResponse(features) => {
+            format!(
+                "io_capability={} oob={} {} maximum_key_size={} initiator_keys={} responder_keys={}",
+                features.io_capability,
+                features.oob_data_present,
+                describe_smp_authentication(features.authentication),
+                features.maximum_encryption_key_size,
+                describe_smp_key_distribution(features.initiator_key_distribution),
+                describe_smp_key_distribution(features.responder_key_distribution)
+            )
+        }
+        DecodedSmpPdu::PairingConfirm(value) => {
+            format!("confirm={}", print_hex(&value))
+        }
+        DecodedSmpPdu::PairingRandom(value) => {
+            format!("random={}", print_hex(&value))
+        }
+        DecodedSmpPdu::PairingFailed(failure) => format!(
+            "reason=0x{:02x} reason_name={}",
+            failure.reason,
+            failure.reason_name()
         ),
-        DecodedControlPdu::SubrateRequest(value) => format!(
-            "{} opcode=0x{:02x} factor_min={} factor_max={} maximum_latency={} continuation_number={} timeout={}",
-            control.opcode_name(),
-            control.opcode,
-            value.factor_min,
-            value.factor_max,
-            value.maximum_latency,
-            value.continuation_number,
-            value.supervision_timeout
+        DecodedSmpPdu::EncryptionInformation(key) => {
+            format!("ltk={}", print_hex(&key))
+        }
+        DecodedSmpPdu::CentralIdentification(identification) => format!(
+            "ediv=0x{:04x} random={}",
+            identification.encrypted_diversifier,
+            print_hex(&identification.random)
         ),
-        DecodedControlPdu::SubrateInd(value) => format!(
-            "{} opcode=0x{:02x} factor={} base_event={} latency={} continuation_number={} timeout={}",
-            control.opcode_name(),
-            control.opcode,
-            value.factor,
-            value.base_event,
-            value.latency,
-            value.continuation_number,
-            value.supervision_timeout
+        DecodedSmpPdu::IdentityInformation(key) => {
+            format!("irk={}", print_hex(&key))
+        }
+        DecodedSmpPdu::IdentityAddressInformation(identity) => format!(
+            "address_type={} address={}",
+            identity.address_type,
+            print_device_address(identity.address)
         ),
-        DecodedControlPdu::ChannelReportingInd(value) => format!(
-            "{} opcode=0x{:02x} enabled={} minimum_spacing={} maximum_delay={}",
-            control.opcode_name(),
-            control.opcode,
-            value.enabled,
-            value.minimum_spacing,
-            value.maximum_delay
+        DecodedSmpPdu::SigningInformation(key) => {
+            format!("csrk={}", print_hex(&key))
+        }
+        DecodedSmpPdu::SecurityRequest(authentication) => {
+            describe_smp_authentication(authentication)
+        }
+        DecodedSmpPdu::PairingPublicKey(key) => {
+            format!("x={} y={}", print_hex(&key.x), print_hex(&key.y))
+        }
+        DecodedSmpPdu::PairingDhKeyCheck(value) => {
+            format!("dhkey_check={}", print_hex(&value))
+        }
+        DecodedSmpPdu::KeypressNotification(notification_type) => {
+            format!("notification_type={notification_type}")
+        }
+        DecodedSmpPdu::Unknown { parameters, .. } => {
+            format!("parameters={}", print_hex(parameters))
+        }
+    };
+    Ok(format!("{prefix} {details}"))
+}
+
+/// Starts one `--output-format jsonl` record of `record_type`.
+fn json_record(record_type: &str) -> JsonObject {
+    JsonObject::new()
+        .field("schema_version", JSON_SCHEMA_VERSION)
+        .field("type", record_type)
+}
+
+fn print_json(record: JsonObject) {
+    println!("{}", Json::from(record));
+}
+
+/// The `pdu` member of a packet record, with `decode_error` beside a `null`
+/// PDU when the semantic decode failed. The error is handed back so callers
+/// can count it as they do for text output.
+fn json_decode_result(decoded: Result<impl Into<Json>>) -> (JsonObject, Result<()>) {
+    match decoded {
+        Ok(pdu) => (JsonObject::new().field("pdu", pdu), Ok(())),
+        Err(error) => (
+            JsonObject::new()
+                .field("pdu", Json::Null)
+                .field("decode_error", error.to_string()),
+            Err(error),
         ),
-        DecodedControlPdu::ChannelStatusInd(value) => {
-            let good = value
-                .classifications
-                .iter()
-                .filter(|classification| **classification == ChannelClassification::Good)
-                .count();
-            let bad = value
-                .classifications
-                .iter()
-                .filter(|classification| **classification == ChannelClassification::Bad)
-                .count();
-            format!(
-                "{} opcode=0x{:02x} good_channels={} bad_channels={} unknown_channels={}",
-                control.opcode_name(),
-                control.opcode,
-                good,
-                bad,
-                value.classifications.len() - good - bad
+    }
+}
+
+fn json_ad_structures(structures: &[AdStructure]) -> Json {
+    structures
+        .iter()
+        .map(|structure| {
+            JsonObject::new()
+                .field("ad_type", structure.ad_type)
+                .field("name", structure.type_name())
+                .field("data", Json::hex(&structure.data))
+                .field("text", structure.text())
+        })
+        .collect::<Vec<_>>()
+        .into()
+}
+
+fn json_cte_info(info: ConstantToneExtensionInfo) -> JsonObject {
+    JsonObject::new()
+        .field("raw", info.raw())
+        .field("cte_type", info.cte_type_name())
+        .field("duration_us", info.duration_us())
+        .field("rfu", info.rfu())
+        .field("reserved", info.has_reserved_value())
+}
+
+fn json_sync_info(sync_info: &PeriodicAdvertisingSyncInfo) -> JsonObject {
+    JsonObject::new()
+        .field("offset_us", sync_info.packet_offset_us())
+        .field("interval", sync_info.interval)
+        .field("interval_us", sync_info.interval_us())
+        .field("channel_map", Json::hex(&sync_info.channel_map.bytes()))
+        .field("sca_ppm", sync_info.sleep_clock_accuracy.maximum_ppm())
+        .field("access_address", sync_info.access_address)
+        .field("crc_init", sync_info.crc_init)
+        .field("event", sync_info.event_counter)
+}
+
+fn json_extended_advertising(
+    kind: ExtendedAdvertisingPduKind,
+    header: &ExtendedAdvertisingHeader,
+) -> JsonObject {
+    JsonObject::new()
+        .field("name", Json::display(kind))
+        .field("mode", Json::display(header.mode))
+        .field("ext_header_octets", header.extended_header_length)
+        .field("flags", header.flags)
+        .field("advertiser", header.advertiser_address.map(Json::display))
+        .field(
+            "advertiser_type",
+            header.advertiser_address_kind.map(Json::display),
+        )
+        .field("target", header.target_address.map(Json::display))
+        .field("target_type", header.target_address_kind.map(Json::display))
+        .field(
+            "cte_info",
+            header.constant_tone_extension_info.map(json_cte_info),
+        )
+        .field(
+            "adi",
+            header.advertising_data_info.map(|info| {
+                JsonObject::new()
+                    .field("sid", info.advertising_set_id)
+                    .field("did", info.data_id)
+            }),
+        )
+        .field(
+            "aux_pointer",
+            header.auxiliary_pointer.map(|pointer| {
+                JsonObject::new()
+                    .field("channel", pointer.channel.index())
+                    .field("offset_us", pointer.offset_us())
+                    .field("phy", Json::display(pointer.phy))
+                    .field("clock_accuracy_ppm", pointer.clock_accuracy.maximum_ppm())
+            }),
+        )
+        .field("sync_info", header.sync_info.as_ref().map(json_sync_info))
+        .field("tx_power_dbm", header.tx_power_dbm)
+        .field(
+            "acad",
+            Json::hex(&header.additional_controller_advertising_data),
+        )
+        .field("advertising_data", Json::hex(&header.advertising_data))
+}
+
+fn json_advertising_pdu(decoded: &DecodedAdvertisingPdu) -> JsonObject {
+    let advertiser = |name: &str, address: &DeviceAddress, kind: &AddressKind| {
+        JsonObject::new()
+            .field("name", name)
+            .field("advertiser", Json::display(address))
+            .field("advertiser_type", Json::display(kind))
+    };
+    match decoded {
+        DecodedAdvertisingPdu::AdvInd {
+            advertiser: address,
+            advertiser_kind,
+            data,
+        } => advertiser("ADV_IND", address, advertiser_kind)
+            .field("ad_structures", json_ad_structures(data)),
+        DecodedAdvertisingPdu::AdvNonconnInd {
+            advertiser: address,
+            advertiser_kind,
+            data,
+        } => advertiser("ADV_NONCONN_IND", address, advertiser_kind)
+            .field("ad_structures", json_ad_structures(data)),
+        DecodedAdvertisingPdu::ScanRsp {
+            advertiser: address,
+            advertiser_kind,
+            data,
+        } => advertiser("SCAN_RSP", address, advertiser_kind)
+            .field("ad_structures", json_ad_structures(data)),
+        DecodedAdvertisingPdu::AdvScanInd {
+            advertiser: address,
+            advertiser_kind,
+            data,
+        } => advertiser("ADV_SCAN_IND", address, advertiser_kind)
+            .field("ad_structures", json_ad_structures(data)),
+        DecodedAdvertisingPdu::AdvDirectInd {
+            advertiser: address,
+            advertiser_kind,
+            target,
+            target_kind,
+        } => advertiser("ADV_DIRECT_IND", address, advertiser_kind)
+            .field("target", Json::display(target))
+            .field("target_type", Json::display(target_kind)),
+        DecodedAdvertisingPdu::ScanReq {
+            scanner,
+            scanner_kind,
+            advertiser: address,
+            advertiser_kind,
+        } => advertiser("SCAN_REQ", address, advertiser_kind)
+            .field("scanner", Json::display(scanner))
+            .field("scanner_type", Json::display(scanner_kind)),
+        DecodedAdvertisingPdu::ConnectInd {
+            initiator,
+            initiator_kind,
+            advertiser: address,
+            advertiser_kind,
+            request,
+        } => advertiser("CONNECT_IND", address, advertiser_kind)
+            .field("initiator", Json::display(initiator))
+            .field("initiator_type", Json::display(initiator_kind))
+            .field("access_address", request.access_address)
+            .field("crc_init", request.crc_init)
+            .field("window_size", request.window_size)
+            .field("window_offset", request.window_offset)
+            .field("interval", request.interval)
+            .field("interval_us", request.interval_us())
+            .field("latency", request.latency)
+            .field("timeout", request.supervision_timeout)
+            .field("timeout_us", request.supervision_timeout_us())
+            .field("channel_map", Json::hex(&request.channel_map))
+            .field("channels", request.enabled_data_channels().len())
+            .field("hop", request.hop_increment)
+            .field("sca", request.sleep_clock_accuracy)
+            .field(
+                "channel_selection",
+                Json::display(request.channel_selection_algorithm),
+            ),
+        DecodedAdvertisingPdu::AdvExtInd { header } => {
+            json_extended_advertising(ExtendedAdvertisingPduKind::AdvExtInd, header)
+        }
+        DecodedAdvertisingPdu::ExtendedOrReserved { pdu_type, payload } => JsonObject::new()
+            .field("name", format!("PDU_TYPE_{pdu_type}"))
+            .field("undecoded_payload", Json::hex(payload)),
+    }
+}
+
+/// Fields shared by every demodulated-packet record.
+fn json_received(
+    record_type: &str,
+    channel: BleChannel,
+    phy: LeUncodedPhy,
+    sample: u64,
+    packet: &ReceivedLePdu,
+) -> JsonObject {
+    json_record(record_type)
+        .field("channel", channel.index())
+        .field("phy", Json::display(phy))
+        .field("sample", sample)
+        .field("phase", packet.symbol_phase)
+        .field("access_address", packet.pdu.access_address)
+        .field("inverted", packet.pdu.inverted)
+        .field("aa_errors", packet.pdu.access_address_errors)
+        .field("carrier_offset_hz", packet.estimated_carrier_offset_hz)
+        .field("deviation_hz", packet.estimated_deviation_hz)
+}
+
+fn json_advertising_packet(
+    record_type: &str,
+    packet: &ReceivedAdvertisingPdu,
+    decoded: Result<JsonObject>,
+) -> JsonObject {
+    json_record(record_type)
+        .field("channel", packet.pdu.channel.index())
+        .field("phy", Json::display(packet.phy))
+        .field("sample", packet.access_address_sample)
+        .field("phase", packet.symbol_phase)
+        .field("access_address", packet.pdu.access_address)
+        .field("inverted", packet.pdu.inverted)
+        .field("aa_errors", packet.pdu.access_address_errors)
+        .field("pdu_type", packet.pdu.pdu_type())
+        .field("carrier_offset_hz", packet.estimated_carrier_offset_hz)
+        .field("deviation_hz", packet.estimated_deviation_hz)
+        .field("header", Json::hex(&packet.pdu.header))
+        .field("payload", Json::hex(&packet.pdu.payload))
+        .field("crc", Json::hex(&packet.pdu.crc))
+        .extend(json_decode_result(decoded).0)
+}
+
+fn json_control_pdu(control: ControlPdu<'_>) -> Result<JsonObject> {
+    let fields = match control.decode()? {
+        DecodedControlPdu::ConnectionUpdateInd(update) => JsonObject::new()
+            .field("window_offset", update.window_offset)
+            .field("window_size", update.window_size)
+            .field("interval", update.parameters.interval)
+            .field("latency", update.parameters.latency)
+            .field("timeout", update.parameters.supervision_timeout)
+            .field("instant", update.instant),
+        DecodedControlPdu::ChannelMapInd(update) => JsonObject::new()
+            .field("channel_map", Json::hex(&update.channel_map.bytes()))
+            .field("channels", update.channel_map.used_count())
+            .field("instant", update.instant),
+        DecodedControlPdu::TerminateInd(value) | DecodedControlPdu::RejectInd(value) => {
+            JsonObject::new().field("error_code", value.error_code)
+        }
+        DecodedControlPdu::EncryptionRequest(value) => JsonObject::new()
+            .field("rand", Json::hex(&value.random_number))
+            .field("ediv", value.encrypted_diversifier)
+            .field("skd_c", Json::hex(&value.central_session_key_diversifier))
+            .field("iv_c", Json::hex(&value.central_initialization_vector)),
+        DecodedControlPdu::EncryptionResponse(value) => JsonObject::new()
+            .field(
+                "skd_p",
+                Json::hex(&value.peripheral_session_key_diversifier),
+            )
+            .field("iv_p", Json::hex(&value.peripheral_initialization_vector)),
+        DecodedControlPdu::StartEncryptionRequest
+        | DecodedControlPdu::StartEncryptionResponse
+        | DecodedControlPdu::PauseEncryptionRequest
+        | DecodedControlPdu::PauseEncryptionResponse
+        | DecodedControlPdu::PingRequest
+        | DecodedControlPdu::PingResponse
+        | DecodedControlPdu::CteResponse
+        | DecodedControlPdu::CsFaeRequest => JsonObject::new(),
+        DecodedControlPdu::UnknownResponse(value) => JsonObject::new()
+            .field("unknown_type", value.unknown_type)
+            .field(
+                "unknown_name",
+                ControlPdu {
+                    opcode: value.unknown_type,
+                    parameters: &[],
+                }
+                .opcode_name(),
+            ),
+        DecodedControlPdu::FeatureRequest(value)
+        | DecodedControlPdu::FeatureResponse(value)
+        | DecodedControlPdu::PeripheralFeatureRequest(value) => {
+            JsonObject::new().field("features", Json::hex(&value.bytes))
+        }
+        DecodedControlPdu::VersionInd(value) => JsonObject::new()
+            .field("version", value.version)
+            .field("company", value.company_identifier)
+            .field("subversion", value.subversion),
+        DecodedControlPdu::ConnectionParameterRequest(value)
+        | DecodedControlPdu::ConnectionParameterResponse(value) => JsonObject::new()
+            .field("interval_min", value.interval_min)
+            .field("interval_max", value.interval_max)
+            .field("latency", value.latency)
+            .field("timeout", value.supervision_timeout)
+            .field("preferred_periodicity", value.preferred_periodicity)
+            .field("reference_event", value.reference_connection_event_count)
+            .field("offsets", value.offsets.to_vec()),
+        DecodedControlPdu::RejectExtendedInd(value) => JsonObject::new()
+            .field("rejected_opcode", value.rejected_opcode)
+            .field(
+                "rejected_name",
+                ControlPdu {
+                    opcode: value.rejected_opcode,
+                    parameters: &[],
+                }
+                .opcode_name(),
+            )
+            .field("error_code", value.error_code),
+        DecodedControlPdu::LengthRequest(value) | DecodedControlPdu::LengthResponse(value) => {
+            JsonObject::new()
+                .field("max_rx_octets", value.maximum_receive_octets)
+                .field("max_rx_time_us", value.maximum_receive_time_us)
+                .field("max_tx_octets", value.maximum_transmit_octets)
+                .field("max_tx_time_us", value.maximum_transmit_time_us)
+        }
+        DecodedControlPdu::PhyRequest(value) | DecodedControlPdu::PhyResponse(value) => {
+            JsonObject::new()
+                .field("tx_phys", value.transmit_phys)
+                .field("rx_phys", value.receive_phys)
+        }
+        DecodedControlPdu::PhyUpdateInd(value) => JsonObject::new()
+            .field(
+                "central_to_peripheral_phy",
+                value.central_to_peripheral_phy.map(Json::display),
+            )
+            .field(
+                "peripheral_to_central_phy",
+                value.peripheral_to_central_phy.map(Json::display),
+            )
+            .field("instant", value.instant),
+        DecodedControlPdu::MinimumUsedChannelsInd(value) => JsonObject::new()
+            .field("phys", value.phys)
+            .field("minimum_used_channels", value.minimum_used_channels),
+        DecodedControlPdu::CteRequest(value) => JsonObject::new()
+            .field("minimum_cte_length", value.minimum_length_units)
+            .field("minimum_cte_us", value.minimum_duration_us())
+            .field("cte_type", value.cte_type)
+            .field("cte_type_name", value.cte_type_name()),
+        DecodedControlPdu::PeriodicSyncInd(value) => JsonObject::new()
+            .field("id", value.identifier)
+            .field("sync_offset_us", value.sync_info.packet_window_offset_us())
+            .field("interval", value.sync_info.interval)
+            .field(
+                "channel_map",
+                Json::hex(&value.sync_info.channel_map.bytes()),
+            )
+            .field("access_address", value.sync_info.access_address)
+            .field("crc_init", value.sync_info.crc_init)
+            .field("periodic_event", value.sync_info.periodic_event_counter)
+            .field("connection_event", value.connection_event_count)
+            .field("last_periodic_event", value.last_periodic_event_counter)
+            .field("sid", value.advertising_sid)
+            .field(
+                "address_type",
+                if value.advertiser_address_random {
+                    "random"
+                } else {
+                    "public"
+                },
+            )
+            .field("sender_sca", value.sender_sleep_clock_accuracy.raw())
+            .field("phy", value.phy)
+            .field("advertiser", Json::hex(&value.advertiser_address))
+            .field("sync_connection_event", value.sync_connection_event_count),
+        DecodedControlPdu::ClockAccuracyRequest(value)
+        | DecodedControlPdu::ClockAccuracyResponse(value) => JsonObject::new()
+            .field("sca", value.raw())
+            .field("maximum_ppm", value.maximum_ppm()),
+        DecodedControlPdu::CisRequest(value) => JsonObject::new()
+            .field("cig", value.cig_identifier)
+            .field("cis", value.cis_identifier)
+            .field("central_phy", value.central_to_peripheral_phy)
+            .field("peripheral_phy", value.peripheral_to_central_phy)
+            .field("central_max_sdu", value.maximum_central_sdu)
+            .field("peripheral_max_sdu", value.maximum_peripheral_sdu)
+            .field("framed", value.framed)
+            .field(
+                "framing_mode",
+                if value.framing_mode_unsegmented {
+                    "unsegmented"
+                } else {
+                    "segmentable"
+                },
             )
+            .field("central_sdu_interval_us", value.central_sdu_interval_us)
+            .field(
+                "peripheral_sdu_interval_us",
+                value.peripheral_sdu_interval_us,
+            )
+            .field("central_max_pdu", value.maximum_central_pdu)
+            .field("peripheral_max_pdu", value.maximum_peripheral_pdu)
+            .field("nse", value.subevents)
+            .field("sub_interval_us", value.subevent_interval_us)
+            .field("central_bn", value.central_burst_number)
+            .field("peripheral_bn", value.peripheral_burst_number)
+            .field("central_ft", value.central_flush_timeout)
+            .field("peripheral_ft", value.peripheral_flush_timeout)
+            .field("iso_interval", value.iso_interval)
+            .field("offset_min_us", value.cis_offset_min_us)
+            .field("offset_max_us", value.cis_offset_max_us)
+            .field("connection_event", value.connection_event_count),
+        DecodedControlPdu::CisResponse(value) => JsonObject::new()
+            .field("offset_min_us", value.cis_offset_min_us)
+            .field("offset_max_us", value.cis_offset_max_us)
+            .field("connection_event", value.connection_event_count),
+        DecodedControlPdu::CisInd(value) => JsonObject::new()
+            .field("access_address", value.access_address)
+            .field("cis_offset_us", value.cis_offset_us)
+            .field("cig_sync_delay_us", value.cig_sync_delay_us)
+            .field("cis_sync_delay_us", value.cis_sync_delay_us)
+            .field("connection_event", value.connection_event_count),
+        DecodedControlPdu::CisTerminateInd(value) => JsonObject::new()
+            .field("cig", value.cig_identifier)
+            .field("cis", value.cis_identifier)
+            .field("error_code", value.error_code),
+        DecodedControlPdu::PowerControlRequest(value) => JsonObject::new()
+            .field("phy", value.phy)
+            .field("delta_db", value.delta_db)
+            .field("tx_power_dbm", value.transmit_power_dbm),
+        DecodedControlPdu::PowerControlResponse(value) => JsonObject::new()
+            .field("minimum", value.at_minimum)
+            .field("maximum", value.at_maximum)
+            .field("delta_db", value.delta_db)
+            .field("tx_power_dbm", value.transmit_power_dbm)
+            .field(
+                "acceptable_reduction_db",
+                value.acceptable_power_reduction_db,
+            ),
+        DecodedControlPdu::PowerChangeInd(value) => JsonObject::new()
+            .field("phys", value.phys)
+            .field("minimum", value.at_minimum)
+            .field("maximum", value.at_maximum)
+            .field("delta_db", value.delta_db)
+            .field("tx_power_dbm", value.transmit_power_dbm),
+        DecodedControlPdu::SubrateRequest(value) => JsonObject::new()
+            .field("factor_min", value.factor_min)
+            .field("factor_max", value.factor_max)
+            .field("maximum_latency", value.maximum_latency)
+            .field("continuation_number", value.continuation_number)
+            .field("timeout", value.supervision_timeout),
+        DecodedControlPdu::SubrateInd(value) => JsonObject::new()
+            .field("factor", value.factor)
+            .field("base_event", value.base_event)
+            .field("latency", value.latency)
+            .field("continuation_number", value.continuation_number)
+            .field("timeout", value.supervision_timeout),
+        DecodedControlPdu::ChannelReportingInd(value) => JsonObject::new()
+            .field("enabled", value.enabled)
+            .field("minimum_spacing", value.minimum_spacing)
+            .field("maximum_delay", value.maximum_delay),
+        DecodedControlPdu::ChannelStatusInd(value) => {
+            let count = |wanted: ChannelClassification| {
+                value
+                    .classifications
+                    .iter()
+                    .filter(|classification| **classification == wanted)
+                    .count()
+            };
+            let good = count(ChannelClassification::Good);
+            let bad = count(ChannelClassification::Bad);
+            JsonObject::new()
+                .field("good_channels", good)
+                .field("bad_channels", bad)
+                .field("unknown_channels", value.classifications.len() - good - bad)
         }
-        DecodedControlPdu::PeriodicSyncWrInd(value) => format!(
-            "{} opcode=0x{:02x} id=0x{:04x} sync_offset_us={} interval={} channel_map={} periodic_access_address={:08x} response_access_address={:08x} subevents={} subevent_interval={} response_slot_delay={} response_slot_spacing={}",
-            control.opcode_name(),
-            control.opcode,
-            value.periodic_sync.identifier,
-            value.periodic_sync.sync_info.packet_window_offset_us(),
-            value.periodic_sync.sync_info.interval,
-            print_hex(&value.periodic_sync.sync_info.channel_map.bytes()),
-            value.periodic_sync.sync_info.access_address,
-            value.response_access_address,
-            value.subevent_count,
-            value.subevent_interval,
-            value.response_slot_delay,
-            value.response_slot_spacing
-        ),
+        DecodedControlPdu::PeriodicSyncWrInd(value) => JsonObject::new()
+            .field("id", value.periodic_sync.identifier)
+            .field(
+                "sync_offset_us",
+                value.periodic_sync.sync_info.packet_window_offset_us(),
+            )
+            .field("interval", value.periodic_sync.sync_info.interval)
+            .field(
+                "channel_map",
+                Json::hex(&value.periodic_sync.sync_info.channel_map.bytes()),
+            )
+            .field(
+                "periodic_access_address",
+                value.periodic_sync.sync_info.access_address,
+            )
+            .field("response_access_address", value.response_access_address)
+            .field("subevents", value.subevent_count)
+            .field("subevent_interval", value.subevent_interval)
+            .field("response_slot_delay", value.response_slot_delay)
+            .field("response_slot_spacing", value.response_slot_spacing),
         DecodedControlPdu::FeatureExtendedRequest(value)
-        | DecodedControlPdu::FeatureExtendedResponse(value) => format!(
-            "{} opcode=0x{:02x} maximum_page={} page={} features={}",
-            control.opcode_name(),
-            control.opcode,
-            value.maximum_page,
-            value.page_number,
-            print_hex(&value.feature_page)
-        ),
+        | DecodedControlPdu::FeatureExtendedResponse(value) => JsonObject::new()
+            .field("maximum_page", value.maximum_page)
+            .field("page", value.page_number)
+            .field("features", Json::hex(&value.feature_page)),
         DecodedControlPdu::CsSecurityRequest(value)
-        | DecodedControlPdu::CsSecurityResponse(value) => format!(
-            "{} opcode=0x{:02x} iv={} nonce={} personalization_vector={}",
-            control.opcode_name(),
-            control.opcode,
-            print_hex(&value.initialization_vector),
-            print_hex(&value.nonce),
-            print_hex(&value.personalization_vector)
-        ),
+        | DecodedControlPdu::CsSecurityResponse(value) => JsonObject::new()
+            .field("iv", Json::hex(&value.initialization_vector))
+            .field("nonce", Json::hex(&value.nonce))
+            .field(
+                "personalization_vector",
+                Json::hex(&value.personalization_vector),
+            ),
         DecodedControlPdu::CsCapabilitiesRequest(value)
-        | DecodedControlPdu::CsCapabilitiesResponse(value) => format!(
-            "{} opcode=0x{:02x} mode_types=0x{:02x} rtt_capability=0x{:02x} rtt_aa_only_n={} rtt_sounding_n={} rtt_random_sequence_n={} nadm_sounding=0x{:04x} nadm_random=0x{:04x} cs_sync_phys=0x{:02x} antennas={} maximum_antenna_paths={} roles=0x{:02x} no_fae={} channel_selection_3c={} sounding_pct_estimate={} configurations={} maximum_procedures={} t_sw_us={} t_ip1_capability=0x{:04x} t_ip2_capability=0x{:04x} t_fcs_capability=0x{:04x} t_pm_capability=0x{:04x} tx_snr_capability=0x{:02x}",
-            control.opcode_name(),
-            control.opcode,
-            value.mode_types,
-            value.rtt_capability,
-            value.rtt_aa_only_n,
-            value.rtt_sounding_n,
-            value.rtt_random_sequence_n,
-            value.nadm_sounding_capability,
-            value.nadm_random_capability,
-            value.cs_sync_phy_capability,
-            value.antenna_count,
-            value.maximum_antenna_paths,
-            value.roles,
-            value.no_fae,
-            value.channel_selection_3c,
-            value.sounding_pct_estimate,
-            value.configuration_count,
-            value.maximum_procedures_supported,
-            value.antenna_switch_time_us,
-            value.t_ip1_capability,
-            value.t_ip2_capability,
-            value.t_fcs_capability,
-            value.t_pm_capability,
-            value.tx_snr_capability
-        ),
-        DecodedControlPdu::CsConfigRequest(value) => format!(
-            "{} opcode=0x{:02x} config={} action={} channel_map={} channels={} channel_map_repetition={} main_mode={} sub_mode={} main_mode_min_steps={} main_mode_max_steps={} main_mode_repetition={} mode_0_steps={} cs_sync_phy=0x{:02x} rtt_type={} role={} channel_selection={} channel_selection_3c_shape={} channel_selection_3c_jump={} t_ip1={} t_ip2={} t_fcs={} t_pm={}",
-            control.opcode_name(),
-            control.opcode,
-            value.config_id,
-            match value.action {
-                CsConfigAction::Remove => "remove",
-                CsConfigAction::Create => "create",
-            },
-            print_hex(&value.channel_map.bytes()),
-            value.channel_map.used_count(),
-            value.channel_map_repetition,
-            value.main_mode,
-            value.sub_mode,
-            value.main_mode_min_steps,
-            value.main_mode_max_steps,
-            value.main_mode_repetition,
-            value.mode_0_steps,
-            value.cs_sync_phy,
-            value.rtt_type,
-            value.role,
-            value.channel_selection,
-            value.channel_selection_3c_shape,
-            value.channel_selection_3c_jump,
-            value.t_ip1,
-            value.t_ip2,
-            value.t_fcs,
-            value.t_pm
-        ),
-        DecodedControlPdu::CsConfigResponse(value) => format!(
-            "{} opcode=0x{:02x} config={}",
-            control.opcode_name(),
-            control.opcode,
-            value.config_id
-        ),
-        DecodedControlPdu::CsProcedureRequest(value) => format!(
-            "{} opcode=0x{:02x} config={} connection_event={} offset_min_us={} offset_max_us={} maximum_procedure_length_units={} maximum_procedure_length_us={} event_interval={} subevents_per_event={} subevent_interval_units={} subevent_interval_us={} subevent_length_us={} procedure_interval={} procedure_count={} aci={} preferred_peer_antennas=0x{:02x} phy=0x{:02x} power_delta_db={} initiator_snr_index={} reflector_snr_index={}",
-            control.opcode_name(),
-            control.opcode,
-            value.config_id,
-            value.connection_event_count,
-            value.offset_min_us,
-            value.offset_max_us,
-            value.maximum_procedure_length_units,
-            value.maximum_procedure_length_us(),
-            value.event_interval_connection_events,
-            value.subevents_per_event,
-            value.subevent_interval_units,
-            value.subevent_interval_us(),
-            value.subevent_length_us,
-            value.procedure_interval_connection_events,
-            value.procedure_count,
-            value.antenna_configuration_index,
-            value.preferred_peer_antennas,
-            value.phy,
-            value.power_delta_db,
-            value.initiator_snr_index,
-            value.reflector_snr_index
-        ),
-        DecodedControlPdu::CsProcedureResponse(value) => format!(
-            "{} opcode=0x{:02x} config={} connection_event={} offset_min_us={} offset_max_us={} event_interval={} subevents_per_event={} subevent_interval_units={} subevent_interval_us={} subevent_length_us={} aci={} phy=0x{:02x} power_delta_db={}",
-            control.opcode_name(),
-            control.opcode,
-            value.config_id,
-            value.connection_event_count,
-            value.offset_min_us,
-            value.offset_max_us,
-            value.event_interval_connection_events,
-            value.subevents_per_event,
-            value.subevent_interval_units,
-            value.subevent_interval_us(),
-            value.subevent_length_us,
-            value.antenna_configuration_index,
-            value.phy,
-            value.power_delta_db
-        ),
-        DecodedControlPdu::CsProcedureIndication(value) => format!(
-            "{} opcode=0x{:02x} config={} connection_event={} offset_us={} event_interval={} subevents_per_event={} subevent_interval_units={} subevent_interval_us={} subevent_length_us={} aci={} phy=0x{:02x} power_delta_db={}",
-            control.opcode_name(),
-            control.opcode,
-            value.config_id,
-            value.connection_event_count,
-            value.offset_us,
-            value.event_interval_connection_events,
-            value.subevents_per_event,
-            value.subevent_interval_units,
-            value.subevent_interval_us(),
-            value.subevent_length_us,
-            value.antenna_configuration_index,
-            value.phy,
-            value.power_delta_db
-        ),
+        | DecodedControlPdu::CsCapabilitiesResponse(value) => JsonObject::new()
+            .field("mode_types", value.mode_types)
+            .field("rtt_capability", value.rtt_capability)
+            .field("rtt_aa_only_n", value.rtt_aa_only_n)
+            .field("rtt_sounding_n", value.rtt_sounding_n)
+            .field("rtt_random_sequence_n", value.rtt_random_sequence_n)
+            .field("nadm_sounding", value.nadm_sounding_capability)
+            .field("nadm_random", value.nadm_random_capability)
+            .field("cs_sync_phys", value.cs_sync_phy_capability)
+            .field("antennas", value.antenna_count)
+            .field("maximum_antenna_paths", value.maximum_antenna_paths)
+            .field("roles", value.roles)
+            .field("no_fae", value.no_fae)
+            .field("channel_selection_3c", value.channel_selection_3c)
+            .field("sounding_pct_estimate", value.sounding_pct_estimate)
+            .field("configurations", value.configuration_count)
+            .field("maximum_procedures", value.maximum_procedures_supported)
+            .field("t_sw_us", value.antenna_switch_time_us)
+            .field("t_ip1_capability", value.t_ip1_capability)
+            .field("t_ip2_capability", value.t_ip2_capability)
+            .field("t_fcs_capability", value.t_fcs_capability)
+            .field("t_pm_capability", value.t_pm_capability)
+            .field("tx_snr_capability", value.tx_snr_capability),
+        DecodedControlPdu::CsConfigRequest(value) => JsonObject::new()
+            .field("config", value.config_id)
+            .field(
+                "action",
+                match value.action {
+                    CsConfigAction::Remove => "remove",
+                    CsConfigAction::Create => "create",
+                },
+            )
+            .field("channel_map", Json::hex(&value.channel_map.bytes()))
+            .field("channels", value.channel_map.used_count())
+            .field("channel_map_repetition", value.channel_map_repetition)
+            .field("main_mode", value.main_mode)
+            .field("sub_mode", value.sub_mode)
+            .field("main_mode_min_steps", value.main_mode_min_steps)
+            .field("main_mode_max_steps", value.main_mode_max_steps)
+            .field("main_mode_repetition", value.main_mode_repetition)
+            .field("mode_0_steps", value.mode_0_steps)
+            .field("cs_sync_phy", value.cs_sync_phy)
+            .field("rtt_type", value.rtt_type)
+            .field("role", value.role)
+            .field("channel_selection", value.channel_selection)
+            .field(
+                "channel_selection_3c_shape",
+                value.channel_selection_3c_shape,
+            )
+            .field("channel_selection_3c_jump", value.channel_selection_3c_jump)
+            .field("t_ip1", value.t_ip1)
+            .field("t_ip2", value.t_ip2)
+            .field("t_fcs", value.t_fcs)
+            .field("t_pm", value.t_pm),
+        DecodedControlPdu::CsConfigResponse(value) => {
+            JsonObject::new().field("config", value.config_id)
+        }
+        DecodedControlPdu::CsProcedureRequest(value) => JsonObject::new()
+            .field("config", value.config_id)
+            .field("connection_event", value.connection_event_count)
+            .field("offset_min_us", value.offset_min_us)
+            .field("offset_max_us", value.offset_max_us)
+            .field(
+                "maximum_procedure_length_units",
+                value.maximum_procedure_length_units,
+            )
+            .field(
+                "maximum_procedure_length_us",
+                value.maximum_procedure_length_us(),
+            )
+            .field("event_interval", value.event_interval_connection_events)
+            .field("subevents_per_event", value.subevents_per_event)
+            .field("subevent_interval_units", value.subevent_interval_units)
+            .field("subevent_interval_us", value.subevent_interval_us())
+            .field("subevent_length_us", value.subevent_length_us)
+            .field(
+                "procedure_interval",
+                value.procedure_interval_connection_events,
+            )
+            .field("procedure_count", value.procedure_count)
+            .field("aci", value.antenna_configuration_index)
+            .field("preferred_peer_antennas", value.preferred_peer_antennas)
+            .field("phy", value.phy)
+            .field("power_delta_db", value.power_delta_db)
+            .field("initiator_snr_index", value.initiator_snr_index)
+            .field("reflector_snr_index", value.reflector_snr_index),
+        DecodedControlPdu::CsProcedureResponse(value) => JsonObject::new()
+            .field("config", value.config_id)
+            .field("connection_event", value.connection_event_count)
+            .field("offset_min_us", value.offset_min_us)
+            .field("offset_max_us", value.offset_max_us)
+            .field("event_interval", value.event_interval_connection_events)
+            .field("subevents_per_event", value.subevents_per_event)
+            .field("subevent_interval_units", value.subevent_interval_units)
+            .field("subevent_interval_us", value.subevent_interval_us())
+            .field("subevent_length_us", value.subevent_length_us)
+            .field("aci", value.antenna_configuration_index)
+            .field("phy", value.phy)
+            .field("power_delta_db", value.power_delta_db),
+        DecodedControlPdu::CsProcedureIndication(value) => JsonObject::new()
+            .field("config", value.config_id)
+            .field("connection_event", value.connection_event_count)
+            .field("offset_us", value.offset_us)
+            .field("event_interval", value.event_interval_connection_events)
+            .field("subevents_per_event", value.subevents_per_event)
+            .field("subevent_interval_units", value.subevent_interval_units)
+            .field("subevent_interval_us", value.subevent_interval_us())
+            .field("subevent_length_us", value.subevent_length_us)
+            .field("aci", value.antenna_configuration_index)
+            .field("phy", value.phy)
+            .field("power_delta_db", value.power_delta_db),
         DecodedControlPdu::CsTerminateRequest(value)
-        | DecodedControlPdu::CsTerminateResponse(value) => format!(
-            "{} opcode=0x{:02x} config={} procedure_count={} error_code=0x{:02x}",
-            control.opcode_name(),
-            control.opcode,
-            value.config_id,
-            value.procedure_count,
-            value.error_code
-        ),
-        DecodedControlPdu::CsFaeResponse(value) => format!(
-            "{} opcode=0x{:02x} fae={}",
-            control.opcode_name(),
-            control.opcode,
-            print_signed_hex(&value.values)
-        ),
-        DecodedControlPdu::CsChannelMapInd(value) => format!(
-            "{} opcode=0x{:02x} channel_map={} channels={} instant={}",
-            control.opcode_name(),
-            control.opcode,
-            print_hex(&value.channel_map.bytes()),
-            value.channel_map.used_count(),
-            value.instant
-        ),
-        DecodedControlPdu::FrameSpaceRequest(value) => format!(
-            "{} opcode=0x{:02x} minimum_us={} maximum_us={} phys=0x{:02x} spacing_types=0x{:04x}",
-            control.opcode_name(),
-            control.opcode,
-            value.minimum_us,
-            value.maximum_us,
-            value.phys,
-            value.spacing_types
+        | DecodedControlPdu::CsTerminateResponse(value) => JsonObject::new()
+            .field("config", value.config_id)
+            .field("procedure_count", value.procedure_count)
+            .field("error_code", value.error_code),
+        DecodedControlPdu::CsFaeResponse(value) => {
+            JsonObject::new().field("fae", value.values.to_vec())
+        }
+        DecodedControlPdu::CsChannelMapInd(value) => JsonObject::new()
+            .field("channel_map", Json::hex(&value.channel_map.bytes()))
+            .field("channels", value.channel_map.used_count())
+            .field("instant", value.instant),
+        DecodedControlPdu::FrameSpaceRequest(value) => JsonObject::new()
+            .field("minimum_us", value.minimum_us)
+            .field("maximum_us", value.maximum_us)
+            .field("phys", value.phys)
+            .field("spacing_types", value.spacing_types),
+        DecodedControlPdu::FrameSpaceResponse(value) => JsonObject::new()
+            .field("frame_space_us", value.frame_space_us)
+            .field("phys", value.phys)
+            .field("spacing_types", value.spacing_types),
+        DecodedControlPdu::Raw { parameters, .. } => {
+            JsonObject::new().field("raw_parameters", Json::hex(parameters))
+        }
+    };
+    Ok(JsonObject::new()
+        .field("name", control.opcode_name())
+        .field("opcode", control.opcode)
+        .extend(fields))
+}
+
+fn json_data_pdu(packet: &DataChannelPdu) -> Result<JsonObject> {
+    match packet.llid() {
+        LogicalLinkId::StartOrComplete => {
+            let start = packet
+                .l2cap_start()?
+                .expect("LLID checked before L2CAP start decode");
+            Ok(JsonObject::new()
+                .field("layer", "l2cap_start")
+                .field("length", start.payload_length)
+                .field("cid", start.channel_id)
+                .field("fragment_octets", start.fragment.len()))
+        }
+        LogicalLinkId::Control => {
+            Ok(JsonObject::new()
+                .field("layer", "ll_control")
+                .extend(json_control_pdu(
+                    packet
+                        .control()?
+                        .expect("LLID checked before LL control decode"),
+                )?))
+        }
+        LogicalLinkId::ContinuationOrEmpty if packet.payload.is_empty() => {
+            Ok(JsonObject::new().field("layer", "empty"))
+        }
+        LogicalLinkId::ContinuationOrEmpty => Ok(JsonObject::new()
+            .field("layer", "l2cap_continuation")
+            .field("continuation_octets", packet.payload.len())),
+        LogicalLinkId::Reserved => Ok(JsonObject::new()
+            .field("layer", "reserved_llid")
+            .field("payload_octets", packet.payload.len())),
+    }
+}
+
+fn json_l2cap_signaling(command: L2capSignalingCommand<'_>) -> Result<JsonObject> {
+    let fields = match command.decode()? {
+        DecodedL2capSignalingCommand::CommandReject(reject) => JsonObject::new()
+            .field("reason", reject.reason)
+            .field("data", Json::hex(reject.data)),
+        DecodedL2capSignalingCommand::DisconnectionRequest(disconnection)
+        | DecodedL2capSignalingCommand::DisconnectionResponse(disconnection) => JsonObject::new()
+            .field("destination_cid", disconnection.destination_channel_id)
+            .field("source_cid", disconnection.source_channel_id),
+        DecodedL2capSignalingCommand::ConnectionParameterUpdateRequest(request) => {
+            JsonObject::new()
+                .field("minimum_interval", request.minimum_interval)
+                .field("maximum_interval", request.maximum_interval)
+                .field("latency", request.latency)
+                .field("supervision_timeout", request.supervision_timeout)
+        }
+        DecodedL2capSignalingCommand::ConnectionParameterUpdateResponse(response) => {
+            JsonObject::new().field("result", response.result)
+        }
+        DecodedL2capSignalingCommand::LeCreditBasedConnectionRequest(request) => JsonObject::new()
+            .field("spsm", request.spsm)
+            .field("source_cid", request.source_channel_id)
+            .field("mtu", request.mtu)
+            .field("mps", request.mps)
+            .field("initial_credits", request.initial_credits),
+        DecodedL2capSignalingCommand::LeCreditBasedConnectionResponse(response) => {
+            JsonObject::new()
+                .field("destination_cid", response.destination_channel_id)
+                .field("mtu", response.mtu)
+                .field("mps", response.mps)
+                .field("initial_credits", response.initial_credits)
+                .field("result", response.result)
+        }
+        DecodedL2capSignalingCommand::FlowControlCredit(credit) => JsonObject::new()
+            .field("cid", credit.channel_id)
+            .field("credits", credit.credits),
+        DecodedL2capSignalingCommand::EnhancedCreditBasedConnectionRequest(request) => {
+            JsonObject::new()
+                .field("spsm", request.spsm)
+                .field("mtu", request.mtu)
+                .field("mps", request.mps)
+                .field("initial_credits", request.initial_credits)
+                .field(
+                    "source_cids",
+                    request.source_channel_ids.as_slice().to_vec(),
+                )
+        }
+        DecodedL2capSignalingCommand::EnhancedCreditBasedConnectionResponse(response) => {
+            JsonObject::new()
+                .field("mtu", response.mtu)
+                .field("mps", response.mps)
+                .field("initial_credits", response.initial_credits)
+                .field("result", response.result)
+                .field(
+                    "destination_cids",
+                    response.destination_channel_ids.as_slice().to_vec(),
+                )
+        }
+        DecodedL2capSignalingCommand::EnhancedCreditBasedReconfigureRequest(request) => {
+            JsonObject::new()
+                .field("mtu", request.mtu)
+                .field("mps", request.mps)
+                .field("cids", request.channel_ids.as_slice().to_vec())
+        }
+        DecodedL2capSignalingCommand::EnhancedCreditBasedReconfigureResponse(response) => {
+            JsonObject::new().field("result", response.result)
+        }
+        DecodedL2capSignalingCommand::Unknown { parameters, .. } => {
+            JsonObject::new().field("parameters", Json::hex(parameters))
+        }
+    };
+    Ok(JsonObject::new()
+        .field("code", command.code)
+        .field("name", command.code_name())
+        .field("identifier", command.identifier)
+        .extend(fields))
+}
+
+fn json_att_uuid(uuid: AttUuid) -> Json {
+    describe_att_uuid(uuid).into()
+}
+
+fn json_handle_range(start_handle: u16, end_handle: u16) -> JsonObject {
+    JsonObject::new()
+        .field("start_handle", start_handle)
+        .field("end_handle", end_handle)
+}
+
+fn json_att_pdu(pdu: AttPdu<'_>) -> Result<JsonObject> {
+    let fields = match pdu.decode()? {
+        DecodedAttPdu::ErrorResponse(response) => JsonObject::new()
+            .field("request_opcode", response.request_opcode)
+            .field("handle", response.handle)
+            .field("error", response.error_code)
+            .field("error_name", response.error_name()),
+        DecodedAttPdu::ExchangeMtuRequest(exchange)
+        | DecodedAttPdu::ExchangeMtuResponse(exchange) => {
+            JsonObject::new().field("mtu", exchange.mtu)
+        }
+        DecodedAttPdu::FindInformationRequest(range) => {
+            json_handle_range(range.start_handle, range.end_handle)
+        }
+        DecodedAttPdu::FindInformationResponse(response) => JsonObject::new()
+            .field("uuid_width", response.uuid_width())
+            .field(
+                "entries",
+                response
+                    .entries
+                    .iter()
+                    .map(|entry| {
+                        JsonObject::new()
+                            .field("handle", entry.handle)
+                            .field("uuid", json_att_uuid(entry.uuid))
+                    })
+                    .collect::<Vec<_>>(),
+            ),
+        DecodedAttPdu::FindByTypeValueRequest(request) => {
+            json_handle_range(request.range.start_handle, request.range.end_handle)
+                .field("attribute_type", request.attribute_type)
+                .field("value", Json::hex(request.value))
+        }
+        DecodedAttPdu::FindByTypeValueResponse(ranges) => JsonObject::new().field(
+            "ranges",
+            ranges
+                .iter()
+                .map(|range| json_handle_range(range.start_handle, range.end_handle))
+                .collect::<Vec<_>>(),
         ),
-        DecodedControlPdu::FrameSpaceResponse(value) => format!(
-            "{} opcode=0x{:02x} frame_space_us={} phys=0x{:02x} spacing_types=0x{:04x}",
-            control.opcode_name(),
-            control.opcode,
-            value.frame_space_us,
-            value.phys,
-            value.spacing_types
+        DecodedAttPdu::ReadByTypeRequest(request)
+        | DecodedAttPdu::ReadByGroupTypeRequest(request) => {
+            json_handle_range(request.range.start_handle, request.range.end_handle)
+                .field("attribute_type", json_att_uuid(request.attribute_type))
+        }
+        DecodedAttPdu::ReadByTypeResponse(response) => JsonObject::new()
+            .field("entry_length", response.entry_length)
+            .field(
+                "entries",
+                response
+                    .entries
+                    .iter()
+                    .map(|entry| {
+                        JsonObject::new()
+                            .field("handle", entry.handle)
+                            .field("value", Json::hex(entry.value))
+                    })
+                    .collect::<Vec<_>>(),
+            ),
+        DecodedAttPdu::ReadRequest(request) => JsonObject::new().field("handle", request.handle),
+        DecodedAttPdu::ReadResponse(value)
+        | DecodedAttPdu::ReadBlobResponse(value)
+        | DecodedAttPdu::ReadMultipleResponse(value) => {
+            JsonObject::new().field("value", Json::hex(value))
+        }
+        DecodedAttPdu::ReadBlobRequest(request) => JsonObject::new()
+            .field("handle", request.handle)
+            .field("offset", request.offset),
+        DecodedAttPdu::ReadMultipleRequest(handles)
+        | DecodedAttPdu::ReadMultipleVariableRequest(handles) => {
+            JsonObject::new().field("handles", handles.to_vec())
+        }
+        DecodedAttPdu::ReadByGroupTypeResponse(response) => JsonObject::new()
+            .field("entry_length", response.entry_length)
+            .field(
+                "entries",
+                response
+                    .entries
+                    .iter()
+                    .map(|entry| {
+                        json_handle_range(entry.range.start_handle, entry.range.end_handle)
+                            .field("value", Json::hex(entry.value))
+                    })
+                    .collect::<Vec<_>>(),
+            ),
+        DecodedAttPdu::WriteRequest(write)
+        | DecodedAttPdu::HandleValueNotification(write)
+        | DecodedAttPdu::HandleValueIndication(write)
+        | DecodedAttPdu::WriteCommand(write) => JsonObject::new()
+            .field("handle", write.handle)
+            .field("value", Json::hex(write.value)),
+        DecodedAttPdu::WriteResponse
+        | DecodedAttPdu::ExecuteWriteResponse
+        | DecodedAttPdu::HandleValueConfirmation => JsonObject::new(),
+        DecodedAttPdu::PrepareWriteRequest(write) | DecodedAttPdu::PrepareWriteResponse(write) => {
+            JsonObject::new()
+                .field("handle", write.handle)
+                .field("offset", write.offset)
+                .field("value", Json::hex(write.value))
+        }
+        DecodedAttPdu::ExecuteWriteRequest(flags) => {
+            JsonObject::new().field("flags", Json::display(flags))
+        }
+        DecodedAttPdu::ReadMultipleVariableResponse(list) => JsonObject::new().field(
+            "values",
+            list.values
+                .iter()
+                .map(|value| {
+                    JsonObject::new()
+                        .field("declared_length", value.declared_length)
+                        .field("value", Json::hex(value.value))
+                        .field("truncated", value.truncated)
+                })
+                .collect::<Vec<_>>(),
         ),
-        DecodedControlPdu::Raw { parameters, .. } => format!(
-            "{} opcode=0x{:02x} raw_parameters={}",
-            control.opcode_name(),
-            control.opcode,
-            print_hex(parameters)
+        DecodedAttPdu::MultipleHandleValueNotification(values) => JsonObject::new().field(
+            "values",
+            values
+                .iter()
+                .map(|value| {
+                    JsonObject::new()
+                        .field("handle", value.handle)
+                        .field("value", Json::hex(value.value))
+                })
+                .collect::<Vec<_>>(),
         ),
-    })
+        DecodedAttPdu::SignedWriteCommand(write) => JsonObject::new()
+            .field("handle", write.handle)
+            .field("value", Json::hex(write.value))
+            .field("signature", Json::hex(&write.signature)),
+        DecodedAttPdu::Unknown { parameters, .. } => {
+            JsonObject::new().field("parameters", Json::hex(parameters))
+        }
+    };
+    Ok(JsonObject::new()
+        .field("opcode", pdu.opcode)
+        .field("name", pdu.opcode_name())
+        .field("pdu_type", Json::display(pdu.pdu_type()))
+        .extend(fields))
 }
 
-fn describe_data_pdu(packet: &DataChannelPdu) -> Result<String> {
-    match packet.llid() {
-        LogicalLinkId::StartOrComplete => {
-            let start = packet
-                .l2cap_start()?
-                .expect("LLID checked before L2CAP start decode");
-            Ok(format!(
-                "L2CAP length={} cid=0x{:04x} fragment_octets={}",
-                start.payload_length,
-                start.channel_id,
-                start.fragment.len()
-            ))
+fn json_smp_authentication(authentication: SmpAuthenticationRequirements) -> JsonObject {
+    JsonObject::new()
+        .field("raw", authentication.raw)
+        .field("bonding", authentication.bonding())
+        .field("mitm", authentication.mitm())
+        .field("secure_connections", authentication.secure_connections())
+        .field("keypress", authentication.keypress())
+        .field("ct2", authentication.ct2())
+}
+
+fn json_smp_key_distribution(distribution: SmpKeyDistribution) -> JsonObject {
+    JsonObject::new()
+        .field("raw", distribution.raw)
+        .field("enc", distribution.encryption_key())
+        .field("id", distribution.identity_key())
+        .field("sign", distribution.signing_key())
+        .field("link", distribution.link_key())
+}
+
+fn json_smp_pdu(pdu: SmpPdu<'_>) -> Result<JsonObject> {
+    let fields = match pdu.decode()? {
+        DecodedSmpPdu::PairingRequest(features) | DecodedSmpPdu::PairingResponse(features) => {
+            JsonObject::new()
+                .field("io_capability", Json::display(features.io_capability))
+                .field("oob", features.oob_data_present)
+                .field(
+                    "authentication",
+                    json_smp_authentication(features.authentication),
+                )
+                .field("maximum_key_size", features.maximum_encryption_key_size)
+                .field(
+                    "initiator_keys",
+                    json_smp_key_distribution(features.initiator_key_distribution),
+                )
+                .field(
+                    "responder_keys",
+                    json_smp_key_distribution(features.responder_key_distribution),
+                )
         }
-        LogicalLinkId::Control => describe_control_pdu(
-            packet
-                .control()?
-                .expect("LLID checked before LL control decode"),
+        DecodedSmpPdu::PairingConfirm(value) => {
+            JsonObject::new().field("confirm", Json::hex(&value))
+        }
+        DecodedSmpPdu::PairingRandom(value) => JsonObject::new().field("random", Json::hex(&value)),
+        DecodedSmpPdu::PairingFailed(failure) => JsonObject::new()
+            .field("reason", failure.reason)
+            .field("reason_name", failure.reason_name()),
+        DecodedSmpPdu::EncryptionInformation(key) => {
+            JsonObject::new().field("ltk", Json::hex(&key))
+        }
+        DecodedSmpPdu::CentralIdentification(identification) => JsonObject::new()
+            .field("ediv", identification.encrypted_diversifier)
+            .field("random", Json::hex(&identification.random)),
+        DecodedSmpPdu::IdentityInformation(key) => JsonObject::new().field("irk", Json::hex(&key)),
+        DecodedSmpPdu::IdentityAddressInformation(identity) => JsonObject::new()
+            .field("address_type", Json::display(identity.address_type))
+            .field("address", print_device_address(identity.address)),
+        DecodedSmpPdu::SigningInformation(key) => JsonObject::new().field("csrk", Json::hex(&key)),
+        DecodedSmpPdu::SecurityRequest(authentication) => {
+            JsonObject::new().field("authentication", json_smp_authentication(authentication))
+        }
+        DecodedSmpPdu::PairingPublicKey(key) => JsonObject::new()
+            .field("x", Json::hex(&key.x))
+            .field("y", Json::hex(&key.y)),
+        DecodedSmpPdu::PairingDhKeyCheck(value) => {
+            JsonObject::new().field("dhkey_check", Json::hex(&value))
+        }
+        DecodedSmpPdu::KeypressNotification(notification_type) => {
+            JsonObject::new().field("notification_type", Json::display(notification_type))
+        }
+        DecodedSmpPdu::Unknown { parameters, .. } => {
+            JsonObject::new().field("parameters", Json::hex(parameters))
+        }
+    };
+    Ok(JsonObject::new()
+        .field("code", pdu.code)
+        .field("name", pdu.code_name())
+        .extend(fields))
+}
+
+/// Prints one signaling command; `index` is the `l2cap-trace` PDU index.
+fn print_l2cap_signal(
+    index: Option<usize>,
+    direction: LinkDirection,
+    command: L2capSignalingCommand<'_>,
+    output_format: OutputFormat,
+) -> Result<()> {
+    match output_format {
+        OutputFormat::Text => {
+            let description = describe_l2cap_signaling(command)?;
+            match index {
+                Some(index) => {
+                    println!("l2cap_signal index={index} direction={direction} {description}");
+                }
+                None => println!("l2cap_signal direction={direction} {description}"),
+            }
+        }
+        OutputFormat::JsonLines => {
+            let mut record = json_record("l2cap_signal");
+            if let Some(index) = index {
+                record.push("index", index);
+            }
+            print_json(
+                record
+                    .field("direction", Json::display(direction))
+                    .extend(json_l2cap_signaling(command)?),
+            );
+        }
+    }
+    Ok(())
+}
+
+fn print_att_pdu(
+    direction: LinkDirection,
+    pdu: AttPdu<'_>,
+    output_format: OutputFormat,
+) -> Result<()> {
+    match output_format {
+        OutputFormat::Text => println!("att_pdu direction={direction} {}", describe_att_pdu(pdu)?),
+        OutputFormat::JsonLines => print_json(
+            json_record("att_pdu")
+                .field("direction", Json::display(direction))
+                .extend(json_att_pdu(pdu)?),
         ),
-        LogicalLinkId::ContinuationOrEmpty if packet.payload.is_empty() => Ok("empty".to_owned()),
-        LogicalLinkId::ContinuationOrEmpty => Ok(format!(
-            "L2CAP continuation_octets={}",
-            packet.payload.len()
-        )),
-        LogicalLinkId::Reserved => Ok(format!(
-            "reserved_llid payload_octets={}",
-            packet.payload.len()
+    }
+    Ok(())
+}
+
+fn print_smp_pdu(
+    direction: LinkDirection,
+    pdu: SmpPdu<'_>,
+    output_format: OutputFormat,
+) -> Result<()> {
+    match output_format {
+        OutputFormat::Text => println!("smp_pdu direction={direction} {}", describe_smp_pdu(pdu)?),
+        OutputFormat::JsonLines => print_json(
+            json_record("smp_pdu")
+                .field("direction", Json::display(direction))
+                .extend(json_smp_pdu(pdu)?),
+        ),
+    }
+    Ok(())
+}
+
+/// Offline I/Q input from a regular file, FIFO, standard input, or TCP.
+///
+/// Regular files are size-checked before decoding. Streams have no known
+/// length, so `--max-samples` is enforced as samples arrive.
+struct DecodeInput {
+    reader: IqReader<Box<dyn Read + Send>>,
+    max_samples: usize,
+}
+
+impl DecodeInput {
+    const fn next_sample_index(&self) -> u64 {
+        self.reader.next_sample_index()
+    }
+
+    fn read_block(&mut self, maximum_samples: usize) -> Result<Vec<Complex32>> {
+        let samples = self.reader.read_block(maximum_samples)?;
+        if self.reader.next_sample_index() > self.max_samples as u64 {
+            return Err(Error::InvalidInput(format!(
+                "I/Q stream exceeded the configured limit of {} samples",
+                self.max_samples
+            )));
+        }
+        Ok(samples)
+    }
+}
+
+fn open_decode_input(
+    input: &IqStreamLocation,
+    format: IqFormat,
+    framing: IqStreamFraming,
+    sample_rate_hz: u32,
+    max_samples: usize,
+) -> Result<DecodeInput> {
+    if let (IqStreamLocation::Path(path), IqStreamFraming::Raw) = (input, framing)
+        && input.is_regular_file()
+    {
+        let file = File::open(path)?;
+        let sample_count = iq_file_sample_count(&file, format)?;
+        if sample_count > max_samples {
+            return Err(Error::InvalidInput(format!(
+                "I/Q file contains {sample_count} samples, exceeding the configured limit of {max_samples}"
+            )));
+        }
+        return Ok(DecodeInput {
+            reader: IqReader::new(Box::new(BufReader::new(file)), format),
+            max_samples,
+        });
+    }
+    let stream = open_iq_stream(input, framing)?;
+    if let Some(header) = stream.header {
+        header.check(format, sample_rate_hz)?;
+    }
+    Ok(DecodeInput {
+        reader: IqReader::new(stream.reader, format),
+        max_samples,
+    })
+}
+
+/// One PCAPNG interface per receive channel, named after the channel.
+fn pcapng_channel_interface(
+    channel: BleChannel,
+    sample_rate_hz: u32,
+    hardware: &str,
+) -> PcapNgInterface {
+    PcapNgInterface {
+        name: Some(format!("ble-ch{}", channel.index())),
+        description: Some(format!(
+            "LE channel {} at {} Hz, {sample_rate_hz} samples/s",
+            channel.index(),
+            channel.center_frequency_hz()
         )),
+        hardware: Some(hardware.to_owned()),
+        comments: Vec::new(),
     }
 }
 
-fn print_data_packet(
-    packet: &ReceivedLePdu,
-    data: &DataChannelPdu,
-    payload_is_plaintext: bool,
+fn create_pcapng(
+    path: &Path,
+    hardware: &str,
+    parameters: String,
+    interfaces: &[PcapNgInterface],
+) -> Result<PcapNgWriter<BufWriter<File>>> {
+    PcapNgWriter::with_interfaces(
+        BufWriter::new(File::create(path)?),
+        &pcapng_section(hardware, parameters),
+        interfaces,
+    )
+}
+
+fn pcapng_section(hardware: &str, parameters: String) -> PcapNgSection {
+    PcapNgSection {
+        hardware: Some(hardware.to_owned()),
+        os: Some(env::consts::OS.to_owned()),
+        user_application: Some(format!("blueoxide {}", env!("CARGO_PKG_VERSION"))),
+        comments: vec![parameters],
+    }
+}
+
+fn lock_pcap(
+    pcap: &Mutex<Option<RotatingPcapNgWriter>>,
+) -> MutexGuard<'_, Option<RotatingPcapNgWriter>> {
+    pcap.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Flushes `pcap` every `interval` until `stop` disconnects, so packets reach
+/// disk even while the channel is silent.
+fn flush_pcap_periodically(
+    pcap: &Mutex<Option<RotatingPcapNgWriter>>,
+    interval: Duration,
+    stop: Receiver<()>,
 ) -> Result<()> {
-    let cte = data
-        .constant_tone_extension_info()
-        .map(|info| {
-            format!(
-                "0x{:02x}:{}:{}us:rfu={}:reserved={}",
-                info.raw(),
-                info.cte_type_name(),
-                info.duration_us(),
-                info.rfu(),
-                info.has_reserved_value()
-            )
+    while let Err(RecvTimeoutError::Timeout) = stop.recv_timeout(interval) {
+        if let Some(writer) = lock_pcap(pcap).as_mut() {
+            writer.flush_if_due(Instant::now())?;
+        }
+    }
+    Ok(())
+}
+
+fn decode_pcapng(
+    path: &Path,
+    command: &str,
+    channel: BleChannel,
+    format: IqFormat,
+    sample_rate_hz: u32,
+    access_address: Option<(u32, u32)>,
+) -> Result<PcapNgWriter<BufWriter<File>>> {
+    let link = access_address
+        .map(|(access_address, crc_init)| {
+            format!(" access_address=0x{access_address:08x} crc_init=0x{crc_init:06x}")
         })
-        .unwrap_or_else(|| "none".to_owned());
-    let description = payload_is_plaintext.then(|| describe_data_pdu(data));
-    let plaintext_hint = match &description {
-        Some(Ok(description)) => description.clone(),
-        Some(Err(error)) => format!("decode_error={error}"),
-        None => "encrypted".to_owned(),
+        .unwrap_or_default();
+    create_pcapng(
+        path,
+        "I/Q recording",
+        format!(
+            "command={command} channel={} sample_rate_hz={sample_rate_hz} format={}{link}",
+            channel.index(),
+            format.name()
+        ),
+        &[pcapng_channel_interface(
+            channel,
+            sample_rate_hz,
+            "I/Q recording",
+        )],
+    )
+}
+
+fn decode(args: DecodeArgs) -> Result<()> {
+    if args.block_samples == 0 {
+        return Err(Error::InvalidConfiguration(
+            "--block-samples must be greater than zero".to_owned(),
+        ));
+    }
+    let mut reader = open_decode_input(
+        &args.input,
+        args.format,
+        args.framing,
+        args.sample_rate_hz,
+        args.max_samples,
+    )?;
+
+    let config = Le1mDemodConfig {
+        sample_rate_hz: args.sample_rate_hz,
+        max_access_address_errors: args.max_access_address_errors,
     };
-    println!(
-        "channel={} phy={} sample={} phase={} access_address={:08x} inverted={} aa_errors={} llid={} nesn={} sn={} md={} cp={} cte={} rfu={} carrier_offset_hz={:.1} deviation_hz={:.1} header={} payload={} crc={} plaintext_hint=\"{}\"",
-        data.channel.index(),
-        packet.phy,
-        packet.access_address_sample,
-        packet.symbol_phase,
-        data.access_address,
-        data.inverted,
-        data.access_address_errors,
-        data.llid(),
-        data.next_expected_sequence_number(),
-        data.sequence_number(),
-        data.more_data(),
-        data.constant_tone_extension_present(),
-        cte,
-        data.reserved_header_bits(),
-        packet.estimated_carrier_offset_hz,
-        packet.estimated_deviation_hz,
-        print_hex(&data.header),
-        print_hex(&data.payload),
-        print_hex(&data.crc),
-        plaintext_hint.replace('"', "'"),
+    let mut decoder =
+        Le1mStreamDecoder::with_crc_failure_policy(args.channel, config, args.crc_failure_policy)?;
+    let mut pcap = match &args.output_pcap {
+        Some(path) => Some(decode_pcapng(
+            path,
+            "decode",
+            args.channel,
+            args.format,
+            args.sample_rate_hz,
+            None,
+        )?),
+        None => None,
+    };
+    let mut packet_count = 0usize;
+    let mut crc_failure_count = 0usize;
+
+    loop {
+        let first_sample = reader.next_sample_index();
+        let samples = reader.read_block(args.block_samples)?;
+        if samples.is_empty() {
+            break;
+        }
+        let batch = decoder.push(first_sample, &samples)?;
+        if let Some(discontinuity) = batch.discontinuity {
+            eprintln!(
+                "sample discontinuity: expected {}, observed {}",
+                discontinuity.expected_first_sample, discontinuity.observed_first_sample
+            );
+        }
+        write_interleaved_crc_failures(
+            &batch.packets,
+            &batch.crc_failures,
+            &mut pcap,
+            args.capture_start_ns,
+            args.sample_rate_hz,
+            args.output_format,
+            |packet, pcap| {
+                print_packet(packet, args.output_format);
+                if let Some(writer) = pcap {
+                    let timestamp = sample_timestamp_ns(
+                        args.capture_start_ns,
+                        packet.access_address_sample,
+                        args.sample_rate_hz,
+                    )?;
+                    writer.write_advertising_with_options(
+                        packet,
+                        timestamp,
+                        Some(&PcapNgPacketOptions::received_advertising(0, packet)),
+                    )?;
+                }
+                Ok(())
+            },
+        )?;
+        packet_count += batch.packets.len();
+        crc_failure_count += batch.crc_failures.len();
+    }
+
+    if let Some(writer) = pcap {
+        writer.into_inner().flush()?;
+    }
+    eprintln!(
+        "decoded {packet_count} CRC-valid packet(s) from {} sample(s)",
+        reader.next_sample_index()
+    );
+    if args.crc_failure_policy != LeCrcFailurePolicy::Discard {
+        eprintln!("reported {crc_failure_count} CRC-failed frame(s)");
+    }
+    Ok(())
+}
+
+fn decode_secondary(args: DecodeSecondaryArgs) -> Result<()> {
+    let mut reader = open_decode_input(
+        &args.input,
+        args.format,
+        args.framing,
+        args.sample_rate_hz,
+        args.max_samples,
+    )?;
+
+    let config = LeUncodedDemodConfig {
+        phy: args.phy,
+        sample_rate_hz: args.sample_rate_hz,
+        max_access_address_errors: args.max_access_address_errors,
+    };
+    let mut decoder = LeSecondaryAdvertisingStreamDecoder::with_crc_failure_policy(
+        args.channel,
+        config,
+        args.crc_failure_policy,
+    )?;
+    let mut pcap = match &args.output_pcap {
+        Some(path) => Some(decode_pcapng(
+            path,
+            "decode-secondary",
+            args.channel,
+            args.format,
+            args.sample_rate_hz,
+            None,
+        )?),
+        None => None,
+    };
+    let mut packet_count = 0usize;
+    let mut crc_failure_count = 0usize;
+
+    loop {
+        let first_sample = reader.next_sample_index();
+        let samples = reader.read_block(args.block_samples)?;
+        if samples.is_empty() {
+            break;
+        }
+        let batch = decoder.push(first_sample, &samples)?;
+        if let Some(discontinuity) = batch.discontinuity {
+            eprintln!(
+                "sample discontinuity: expected {}, observed {}",
+                discontinuity.expected_first_sample, discontinuity.observed_first_sample
+            );
+        }
+        write_interleaved_crc_failures(
+            &batch.packets,
+            &batch.crc_failures,
+            &mut pcap,
+            args.capture_start_ns,
+            args.sample_rate_hz,
+            args.output_format,
+            |packet, pcap| {
+                print_packet(packet, args.output_format);
+                if let Some(writer) = pcap {
+                    let timestamp = sample_timestamp_ns(
+                        args.capture_start_ns,
+                        packet.access_address_sample,
+                        args.sample_rate_hz,
+                    )?;
+                    writer.write_advertising_with_options(
+                        packet,
+                        timestamp,
+                        Some(&PcapNgPacketOptions::received_advertising(0, packet)),
+                    )?;
+                }
+                Ok(())
+            },
+        )?;
+        packet_count += batch.packets.len();
+        crc_failure_count += batch.crc_failures.len();
+    }
+
+    if let Some(writer) = pcap {
+        writer.into_inner().flush()?;
+    }
+    eprintln!(
+        "decoded {packet_count} CRC-valid packet(s) from {} sample(s)",
+        reader.next_sample_index()
     );
-    description.transpose().map(|_| ())
+    if args.crc_failure_policy != LeCrcFailurePolicy::Discard {
+        eprintln!("reported {crc_failure_count} CRC-failed frame(s)");
+    }
+    Ok(())
 }
 
-fn print_decrypted_data_packet(
-    direction: LinkDirection,
-    decryption: &LeAclDecryption,
-) -> Result<()> {
-    let description = describe_data_pdu(&decryption.packet);
-    let plaintext_hint = match &description {
-        Ok(description) => description.clone(),
-        Err(error) => format!("decode_error={error}"),
+fn decode_periodic(args: DecodePeriodicArgs) -> Result<()> {
+    let mut reader = open_decode_input(
+        &args.input,
+        args.format,
+        args.framing,
+        args.sample_rate_hz,
+        args.max_samples,
+    )?;
+
+    let config = LeUncodedDemodConfig {
+        phy: args.phy,
+        sample_rate_hz: args.sample_rate_hz,
+        max_access_address_errors: args.max_access_address_errors,
     };
-    let (status, packet_counter) = match decryption.status {
-        LeAclDecryptionStatus::New { packet_counter, .. } => ("new", packet_counter.to_string()),
-        LeAclDecryptionStatus::Retransmission { packet_counter } => {
-            ("retransmission", packet_counter.to_string())
-        }
-        LeAclDecryptionStatus::UnencryptedEmpty => ("unencrypted-empty", "none".to_owned()),
+    let mut decoder = LePeriodicAdvertisingStreamDecoder::with_crc_failure_policy(
+        args.channel,
+        args.access_address,
+        args.crc_init,
+        config,
+        args.crc_failure_policy,
+    )?;
+    let mut pcap = match &args.output_pcap {
+        Some(path) => Some(decode_pcapng(
+            path,
+            "decode-periodic",
+            args.channel,
+            args.format,
+            args.sample_rate_hz,
+            Some((args.access_address, args.crc_init)),
+        )?),
+        None => None,
     };
-    println!(
-        "decrypted_data direction={} status={} packet_counter={} skipped_counters={} header={} payload={} plaintext_hint=\"{}\"",
-        direction,
-        status,
-        packet_counter,
-        decryption.status.skipped_counters(),
-        print_hex(&decryption.packet.header),
-        print_hex(&decryption.packet.payload),
-        plaintext_hint.replace('"', "'"),
-    );
-    description.map(|_| ())
-}
+    let mut packet_count = 0usize;
+    let mut crc_failure_count = 0usize;
 
-fn describe_incomplete_l2cap(incomplete: IncompleteL2capPdu) -> String {
-    format!(
-        "direction={} cid=0x{:04x} received={} expected={} fragments={}",
-        incomplete.direction,
-        incomplete.channel_id,
-        incomplete.received_payload_length,
-        incomplete.expected_payload_length,
-        incomplete.fragment_count
-    )
-}
+    loop {
+        let first_sample = reader.next_sample_index();
+        let samples = reader.read_block(args.block_samples)?;
+        if samples.is_empty() {
+            break;
+        }
+        let batch = decoder.push(first_sample, &samples)?;
+        if let Some(discontinuity) = batch.discontinuity {
+            eprintln!(
+                "sample discontinuity: expected {}, observed {}",
+                discontinuity.expected_first_sample, discontinuity.observed_first_sample
+            );
+        }
+        write_interleaved_crc_failures(
+            &batch.packets,
+            &batch.crc_failures,
+            &mut pcap,
+            args.capture_start_ns,
+            args.sample_rate_hz,
+            args.output_format,
+            |packet, pcap| {
+                print_periodic_packet(packet, args.output_format);
+                if let Some(writer) = pcap {
+                    let timestamp = sample_timestamp_ns(
+                        args.capture_start_ns,
+                        packet.access_address_sample,
+                        args.sample_rate_hz,
+                    )?;
+                    writer.write_advertising_with_options(
+                        packet,
+                        timestamp,
+                        Some(&PcapNgPacketOptions::received_advertising(0, packet)),
+                    )?;
+                }
+                Ok(())
+            },
+        )?;
+        packet_count += batch.packets.len();
+        crc_failure_count += batch.crc_failures.len();
+    }
 
-fn print_l2cap_channel_ids(channel_ids: &[u16]) -> String {
-    channel_ids
-        .iter()
-        .map(|channel_id| format!("0x{channel_id:04x}"))
-        .collect::<Vec<_>>()
-        .join(",")
+    if let Some(writer) = pcap {
+        writer.into_inner().flush()?;
+    }
+    eprintln!(
+        "decoded {packet_count} CRC-valid packet(s) from {} sample(s)",
+        reader.next_sample_index()
+    );
+    if args.crc_failure_policy != LeCrcFailurePolicy::Discard {
+        eprintln!("reported {crc_failure_count} CRC-failed frame(s)");
+    }
+    Ok(())
 }
 
-fn describe_l2cap_signaling(command: L2capSignalingCommand<'_>) -> Result<String> {
-    let prefix = format!(
-        "code=0x{:02x} name={} identifier={}",
-        command.code,
-        command.code_name(),
-        command.identifier
+fn decode_data(args: DecodeDataArgs) -> Result<()> {
+    if args.block_samples == 0 {
+        return Err(Error::InvalidConfiguration(
+            "--block-samples must be greater than zero".to_owned(),
+        ));
+    }
+    let mut reader = open_decode_input(
+        &args.input,
+        args.format,
+        args.framing,
+        args.sample_rate_hz,
+        args.max_samples,
+    )?;
+
+    let demod_config = LeUncodedDemodConfig {
+        phy: args.phy,
+        sample_rate_hz: args.sample_rate_hz,
+        max_access_address_errors: args.max_access_address_errors,
+    };
+    let frame_config = LeFrameConfig::data(args.access_address, args.crc_init)?;
+    let mut decoder = LeUncodedPacketStreamDecoder::with_crc_failure_policy(
+        args.channel,
+        frame_config,
+        demod_config,
+        args.crc_failure_policy,
+    )?;
+    let mut decryptor = match &args.decryption {
+        Some(decryption) => Some(LeAclDecryptor::new(
+            decryption.session_key,
+            decryption.initialization_vector,
+            decryption.direction,
+            decryption.initial_packet_counter,
+            decryption.maximum_counter_skip,
+        )?),
+        None => None,
+    };
+    let mut alternative_materials = LeKeyCandidates::new(
+        args.decryption
+            .as_ref()
+            .map(|decryption| decryption.alternatives.clone())
+            .unwrap_or_default(),
     );
-    let details = match command.decode()? {
-        DecodedL2capSignalingCommand::CommandReject(reject) => {
-            format!(
-                "reason=0x{:04x} data={}",
-                reject.reason,
-                print_hex(reject.data)
-            )
-        }
-        DecodedL2capSignalingCommand::DisconnectionRequest(disconnection)
-        | DecodedL2capSignalingCommand::DisconnectionResponse(disconnection) => format!(
-            "destination_cid=0x{:04x} source_cid=0x{:04x}",
-            disconnection.destination_channel_id, disconnection.source_channel_id
-        ),
-        DecodedL2capSignalingCommand::ConnectionParameterUpdateRequest(request) => format!(
-            "minimum_interval={} maximum_interval={} latency={} supervision_timeout={}",
-            request.minimum_interval,
-            request.maximum_interval,
-            request.latency,
-            request.supervision_timeout
-        ),
-        DecodedL2capSignalingCommand::ConnectionParameterUpdateResponse(response) => {
-            format!("result=0x{:04x}", response.result)
-        }
-        DecodedL2capSignalingCommand::LeCreditBasedConnectionRequest(request) => format!(
-            "spsm=0x{:04x} source_cid=0x{:04x} mtu={} mps={} initial_credits={}",
-            request.spsm,
-            request.source_channel_id,
-            request.mtu,
-            request.mps,
-            request.initial_credits
-        ),
-        DecodedL2capSignalingCommand::LeCreditBasedConnectionResponse(response) => format!(
-            "destination_cid=0x{:04x} mtu={} mps={} initial_credits={} result=0x{:04x}",
-            response.destination_channel_id,
-            response.mtu,
-            response.mps,
-            response.initial_credits,
-            response.result
-        ),
-        DecodedL2capSignalingCommand::FlowControlCredit(credit) => {
-            format!("cid=0x{:04x} credits={}", credit.channel_id, credit.credits)
-        }
-        DecodedL2capSignalingCommand::EnhancedCreditBasedConnectionRequest(request) => format!(
-            "spsm=0x{:04x} mtu={} mps={} initial_credits={} source_cids={}",
-            request.spsm,
-            request.mtu,
-            request.mps,
-            request.initial_credits,
-            print_l2cap_channel_ids(request.source_channel_ids.as_slice())
-        ),
-        DecodedL2capSignalingCommand::EnhancedCreditBasedConnectionResponse(response) => format!(
-            "mtu={} mps={} initial_credits={} result=0x{:04x} destination_cids={}",
-            response.mtu,
-            response.mps,
-            response.initial_credits,
-            response.result,
-            print_l2cap_channel_ids(response.destination_channel_ids.as_slice())
-        ),
-        DecodedL2capSignalingCommand::EnhancedCreditBasedReconfigureRequest(request) => format!(
-            "mtu={} mps={} cids={}",
-            request.mtu,
-            request.mps,
-            print_l2cap_channel_ids(request.channel_ids.as_slice())
-        ),
-        DecodedL2capSignalingCommand::EnhancedCreditBasedReconfigureResponse(response) => {
-            format!("result=0x{:04x}", response.result)
+    let mut l2cap_reassembler = match args.plaintext_l2cap_direction {
+        Some(direction) => Some((
+            direction,
+            L2capReassembler::new(args.maximum_l2cap_payload_length)?,
+        )),
+        None => None,
+    };
+    let mut pcap = match &args.output_pcap {
+        Some(path) => Some(decode_pcapng(
+            path,
+            "decode-data",
+            args.channel,
+            args.format,
+            args.sample_rate_hz,
+            Some((args.access_address, args.crc_init)),
+        )?),
+        None => None,
+    };
+    let mut packet_count = 0usize;
+    let mut crc_failure_count = 0usize;
+    let mut l2cap_pdu_count = 0usize;
+    let mut l2cap_duplicate_count = 0usize;
+    let mut l2cap_orphan_count = 0usize;
+    let mut l2cap_discarded_count = 0usize;
+    let mut l2cap_error_count = 0usize;
+    let mut l2cap_signaling_error_count = 0usize;
+    let mut att_error_count = 0usize;
+    let mut smp_error_count = 0usize;
+    let mut ll_control_error_count = 0usize;
+    let mut decrypted_packet_count = 0usize;
+    let mut decryption_retransmission_count = 0usize;
+    let mut unencrypted_empty_count = 0usize;
+    let mut decryption_error_count = 0usize;
+    let mut skipped_packet_counter_count = 0u64;
+    let mut pcap_key_comment = args.decryption.as_ref().map(|decryption| {
+        encryption_material_comment(
+            args.access_address,
+            decryption.session_key,
+            decryption.initialization_vector,
+        )
+    });
+
+    loop {
+        let first_sample = reader.next_sample_index();
+        let samples = reader.read_block(args.block_samples)?;
+        if samples.is_empty() {
+            break;
         }
-        DecodedL2capSignalingCommand::Unknown { parameters, .. } => {
-            format!("parameters={}", print_hex(parameters))
+        let batch = decoder.push(first_sample, &samples)?;
+        if let Some(discontinuity) = batch.discontinuity {
+            eprintln!(
+                "sample discontinuity: expected {}, observed {}",
+                discontinuity.expected_first_sample, discontinuity.observed_first_sample
+            );
+            if let Some((direction, reassembler)) = &mut l2cap_reassembler
+                && let Some(incomplete) = reassembler.reset(*direction)
+            {
+                l2cap_discarded_count += 1;
+                eprintln!(
+                    "discarded incomplete plaintext L2CAP PDU after sample discontinuity: {}",
+                    describe_incomplete_l2cap(incomplete)
+                );
+            }
         }
-    };
-    Ok(format!("{prefix} {details}"))
-}
+        // CRC failures are never decrypted or reassembled: a CRC failure says
+        // nothing about which octets are wrong.
+        write_interleaved_crc_failures(
+            &batch.packets,
+            &batch.crc_failures,
+            &mut pcap,
+            args.capture_start_ns,
+            args.sample_rate_hz,
+            args.output_format,
+            |packet, pcap| {
+                let raw_data = DataChannelPdu::from(packet.pdu.clone());
+                let mut pcap_options = PcapNgPacketOptions::received(0, packet);
+                let mut pcap_plaintext = None;
+                let data = if let Some(decryptor) = &mut decryptor {
+                    print_data_packet(packet, &raw_data, false, args.output_format)?;
+                    let mut result = decryptor.decrypt(&raw_data);
+                    // Until one packet authenticates, another keyring LTK may be
+                    // the right one; a failed attempt leaves its counter unchanged.
+                    if result.is_err()
+                        && let Some(decryption_args) = &args.decryption
+                        && let Some((index, candidate, decryption)) = alternative_materials
+                            .authenticate(
+                                |material| Some(*material),
+                                decryption_args.direction,
+                                decryption_args.initial_packet_counter,
+                                decryption_args.maximum_counter_skip,
+                                &raw_data,
+                            )?
+                    {
+                        let material = alternative_materials.take(index);
+                        *decryptor = candidate;
+                        if pcap_key_comment.is_some() {
+                            pcap_key_comment = Some(encryption_material_comment(
+                                args.access_address,
+                                material.session_key(),
+                                material.initialization_vector(),
+                            ));
+                        }
+                        eprintln!(
+                            "an alternative keyring LTK authenticated the first encrypted packet"
+                        );
+                        result = Ok(decryption);
+                    }
+                    alternative_materials
+                        .settle(result.as_ref().ok().map(|decryption| decryption.status));
+                    match result {
+                        Ok(decryption) => {
+                            pcap_options.decryption = Some(decryption.status);
+                            if decryption.status != LeAclDecryptionStatus::UnencryptedEmpty {
+                                pcap_plaintext =
+                                    Some((decryptor.direction(), decryption.packet.clone()));
+                            }
+                            match decryption.status {
+                                LeAclDecryptionStatus::New {
+                                    skipped_counters, ..
+                                } => {
+                                    decrypted_packet_count += 1;
+                                    skipped_packet_counter_count = skipped_packet_counter_count
+                                        .checked_add(skipped_counters)
+                                        .ok_or_else(|| {
+                                            Error::InvalidState(
+                                                "skipped packet-counter total overflow".to_owned(),
+                                            )
+                                        })?;
+                                    if skipped_counters != 0
+                                        && let Some((direction, reassembler)) =
+                                            &mut l2cap_reassembler
+                                        && let Some(incomplete) = reassembler.reset(*direction)
+                                    {
+                                        l2cap_discarded_count += 1;
+                                        eprintln!(
+                                            "discarded incomplete plaintext L2CAP PDU after {skipped_counters} skipped encrypted packet counter(s): {}",
+                                            describe_incomplete_l2cap(incomplete)
+                                        );
+                                    }
+                                }
+                                LeAclDecryptionStatus::Retransmission { .. } => {
+                                    decryption_retransmission_count += 1;
+                                }
+                                LeAclDecryptionStatus::UnencryptedEmpty => {
+                                    unencrypted_empty_count += 1;
+                                }
+                            }
+                            if let Err(error) = print_decrypted_data_packet(
+                                decryptor.direction(),
+                                &decryption,
+                                args.output_format,
+                            ) && decryption.packet.llid() == LogicalLinkId::Control
+                            {
+                                ll_control_error_count += 1;
+                                eprintln!(
+                                    "decrypted LL control PDU decode error: opcode={} error={error}",
+                                    decryption
+                                        .packet
+                                        .payload
+                                        .first()
+                                        .map(|opcode| format!("0x{opcode:02x}"))
+                                        .unwrap_or_else(|| "missing".to_owned())
+                                );
+                            }
+                            Some(decryption.packet)
+                        }
+                        Err(error) => {
+                            decryption_error_count += 1;
+                            pcap_options
+                                .comments
+                                .push(format!("decryption_error=\"{error}\""));
+                            eprintln!(
+                                "LE ACL decryption error: direction={} error={error}",
+                                decryptor.direction()
+                            );
+                            if let Some((direction, reassembler)) = &mut l2cap_reassembler
+                                && let Some(incomplete) = reassembler.reset(*direction)
+                            {
+                                l2cap_discarded_count += 1;
+                                eprintln!(
+                                    "discarded incomplete plaintext L2CAP PDU after decryption failure: {}",
+                                    describe_incomplete_l2cap(incomplete)
+                                );
+                            }
+                            None
+                        }
+                    }
+                } else {
+                    if let Err(error) =
+                        print_data_packet(packet, &raw_data, true, args.output_format)
+                        && raw_data.llid() == LogicalLinkId::Control
+                    {
+                        ll_control_error_count += 1;
+                        eprintln!(
+                            "LL control PDU decode error: opcode={} error={error}",
+                            raw_data
+                                .payload
+                                .first()
+                                .map(|opcode| format!("0x{opcode:02x}"))
+                                .unwrap_or_else(|| "missing".to_owned())
+                        );
+                    }
+                    Some(raw_data.clone())
+                };
+                if let (Some((direction, reassembler)), Some(data)) =
+                    (&mut l2cap_reassembler, data.as_ref())
+                {
+                    match reassembler.push(*direction, data) {
+                        Ok(update) => {
+                            if let Some(replaced) = update.replaced {
+                                l2cap_discarded_count += 1;
+                                eprintln!(
+                                    "replaced incomplete plaintext L2CAP PDU: {}",
+                                    describe_incomplete_l2cap(replaced)
+                                );
+                            }
+                            match update.outcome {
+                                L2capReassemblyOutcome::Ignored
+                                | L2capReassemblyOutcome::InProgress(_) => {}
+                                L2capReassemblyOutcome::Duplicate => {
+                                    l2cap_duplicate_count += 1;
+                                }
+                                L2capReassemblyOutcome::OrphanedContinuation {
+                                    fragment_octets,
+                                } => {
+                                    l2cap_orphan_count += 1;
+                                    eprintln!(
+                                        "orphaned plaintext L2CAP continuation: direction={} fragment_octets={fragment_octets}",
+                                        direction
+                                    );
+                                }
+                                L2capReassemblyOutcome::Complete(pdu) => {
+                                    l2cap_pdu_count += 1;
+                                    match args.output_format {
+                                        OutputFormat::Text => println!(
+                                            "l2cap_pdu direction={} cid=0x{:04x} length={} fragments={} payload={}",
+                                            pdu.direction,
+                                            pdu.channel_id,
+                                            pdu.payload.len(),
+                                            pdu.fragment_count,
+                                            print_hex(&pdu.payload)
+                                        ),
+                                        OutputFormat::JsonLines => print_json(
+                                            json_record("l2cap_pdu")
+                                                .field("direction", Json::display(pdu.direction))
+                                                .field("cid", pdu.channel_id)
+                                                .field("length", pdu.payload.len())
+                                                .field("fragments", pdu.fragment_count)
+                                                .field("payload", Json::hex(&pdu.payload)),
+                                        ),
+                                    }
+                                    match pdu.le_signaling_command() {
+                                        Ok(Some(command)) => {
+                                            if let Err(error) = print_l2cap_signal(
+                                                None,
+                                                pdu.direction,
+                                                command,
+                                                args.output_format,
+                                            ) {
+                                                l2cap_signaling_error_count += 1;
+                                                eprintln!(
+                                                    "plaintext L2CAP signaling command decode error: direction={} error={error}",
+                                                    pdu.direction
+                                                );
+                                            }
+                                        }
+                                        Ok(None) => {}
+                                        Err(error) => {
+                                            l2cap_signaling_error_count += 1;
+                                            eprintln!(
+                                                "plaintext L2CAP signaling envelope decode error: direction={} error={error}",
+                                                pdu.direction
+                                            );
+                                        }
+                                    }
+                                    match pdu.att_pdu() {
+                                        Ok(Some(att)) => {
+                                            if let Err(error) = print_att_pdu(
+                                                pdu.direction,
+                                                att,
+                                                args.output_format,
+                                            ) {
+                                                att_error_count += 1;
+                                                eprintln!(
+                                                    "plaintext ATT PDU decode error: direction={} error={error}",
+                                                    pdu.direction
+                                                );
+                                            }
+                                        }
+                                        Ok(None) => {}
+                                        Err(error) => {
+                                            att_error_count += 1;
+                                            eprintln!(
+                                                "plaintext ATT PDU envelope decode error: direction={} error={error}",
+                                                pdu.direction
+                                            );
+                                        }
+                                    }
+                                    match pdu.smp_pdu() {
+                                        Ok(Some(smp)) => {
+                                            if let Err(error) = print_smp_pdu(
+                                                pdu.direction,
+                                                smp,
+                                                args.output_format,
+                                            ) {
+                                                smp_error_count += 1;
+                                                eprintln!(
+                                                    "plaintext SMP PDU decode error: direction={} error={error}",
+                                                    pdu.direction
+                                                );
+                                            }
+                                        }
+                                        Ok(None) => {}
+                                        Err(error) => {
+                                            smp_error_count += 1;
+                                            eprintln!(
+                                                "plaintext SMP PDU envelope decode error: direction={} error={error}",
+                                                pdu.direction
+                                            );
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                        Err(error) => {
+                            l2cap_error_count += 1;
+                            eprintln!(
+                                "plaintext L2CAP reassembly error: direction={} error={error}",
+                                direction
+                            );
+                        }
+                    }
+                }
+                if let Some(writer) = pcap {
+                    let timestamp = sample_timestamp_ns(
+                        args.capture_start_ns,
+                        packet.access_address_sample,
+                        args.sample_rate_hz,
+                    )?;
+                    let plaintext =
+                        pcap_plaintext.filter(|_| args.pcap_payload != PcapPayload::Ciphertext);
+                    if plaintext.is_none() || args.pcap_payload == PcapPayload::Both {
+                        writer.write_pdu_with_options(
+                            &packet.pdu,
+                            packet.phy,
+                            None,
+                            timestamp,
+                            Some(&pcap_options),
+                        )?;
+                    }
+                    if let Some((direction, plaintext)) = plaintext {
+                        // The first plaintext record carries the key so the file
+                        // alone is enough to check or extend the decryption.
+                        pcap_options.comments.extend(pcap_key_comment.take());
+                        writer.write_decrypted_pdu(
+                            &plaintext,
+                            packet.phy,
+                            Some(direction),
+                            timestamp,
+                            Some(&pcap_options),
+                        )?;
+                    }
+                }
+                Ok(())
+            },
+        )?;
+        packet_count += batch.packets.len();
+        crc_failure_count += batch.crc_failures.len();
+    }
 
-fn describe_att_uuid(uuid: AttUuid) -> String {
-    match uuid {
-        AttUuid::Uuid16(uuid) => format!("0x{uuid:04x}"),
-        AttUuid::Uuid128(uuid) => print_hex(&uuid),
+    if let Some((direction, reassembler)) = &mut l2cap_reassembler
+        && let Some(incomplete) = reassembler.reset(*direction)
+    {
+        l2cap_discarded_count += 1;
+        eprintln!(
+            "discarded incomplete plaintext L2CAP PDU at end of input: {}",
+            describe_incomplete_l2cap(incomplete)
+        );
     }
-}
 
-fn describe_att_pdu(pdu: AttPdu<'_>) -> Result<String> {
-    let prefix = format!(
-        "opcode=0x{:02x} name={} type={}",
-        pdu.opcode,
-        pdu.opcode_name(),
-        pdu.pdu_type()
+    if let Some(writer) = pcap {
+        writer.into_inner().flush()?;
+    }
+    eprintln!(
+        "decoded {packet_count} CRC-valid data-channel packet(s) from {} sample(s); ll_control_errors={ll_control_error_count}",
+        reader.next_sample_index()
     );
-    let details = match pdu.decode()? {
-        DecodedAttPdu::ErrorResponse(response) => format!(
-            "request_opcode=0x{:02x} handle=0x{:04x} error=0x{:02x} error_name={}",
-            response.request_opcode,
-            response.handle,
-            response.error_code,
-            response.error_name()
-        ),
-        DecodedAttPdu::ExchangeMtuRequest(exchange)
-        | DecodedAttPdu::ExchangeMtuResponse(exchange) => {
-            format!("mtu={}", exchange.mtu)
-        }
-        DecodedAttPdu::FindInformationRequest(range) => format!(
-            "start_handle=0x{:04x} end_handle=0x{:04x}",
-            range.start_handle, range.end_handle
-        ),
-        DecodedAttPdu::FindInformationResponse(response) => {
-            let entries = response
-                .entries
-                .iter()
-                .map(|entry| format!("0x{:04x}:{}", entry.handle, describe_att_uuid(entry.uuid)))
-                .collect::<Vec<_>>()
-                .join(",");
-            format!("uuid_width={} entries={entries}", response.uuid_width())
-        }
-        DecodedAttPdu::FindByTypeValueRequest(request) => format!(
-            "start_handle=0x{:04x} end_handle=0x{:04x} attribute_type=0x{:04x} value={}",
-            request.range.start_handle,
-            request.range.end_handle,
-            request.attribute_type,
-            print_hex(request.value)
-        ),
-        DecodedAttPdu::FindByTypeValueResponse(ranges) => {
-            let ranges = ranges
-                .iter()
-                .map(|range| format!("0x{:04x}-0x{:04x}", range.start_handle, range.end_handle))
-                .collect::<Vec<_>>()
-                .join(",");
-            format!("ranges={ranges}")
-        }
-        DecodedAttPdu::ReadByTypeRequest(request)
-        | DecodedAttPdu::ReadByGroupTypeRequest(request) => format!(
-            "start_handle=0x{:04x} end_handle=0x{:04x} attribute_type={}",
-            request.range.start_handle,
-            request.range.end_handle,
-            describe_att_uuid(request.attribute_type)
-        ),
-        DecodedAttPdu::ReadByTypeResponse(response) => {
-            let entries = response
-                .entries
-                .iter()
-                .map(|entry| format!("0x{:04x}:{}", entry.handle, print_hex(entry.value)))
-                .collect::<Vec<_>>()
-                .join(",");
-            format!("entry_length={} entries={entries}", response.entry_length)
-        }
-        DecodedAttPdu::ReadRequest(request) => {
-            format!("handle=0x{:04x}", request.handle)
-        }
-        DecodedAttPdu::ReadResponse(value)
-        | DecodedAttPdu::ReadBlobResponse(value)
-        | DecodedAttPdu::ReadMultipleResponse(value) => {
-            format!("value={}", print_hex(value))
-        }
-        DecodedAttPdu::ReadBlobRequest(request) => {
-            format!("handle=0x{:04x} offset={}", request.handle, request.offset)
-        }
-        DecodedAttPdu::ReadMultipleRequest(handles)
-        | DecodedAttPdu::ReadMultipleVariableRequest(handles) => {
-            let handles = handles
-                .iter()
-                .map(|handle| format!("0x{handle:04x}"))
-                .collect::<Vec<_>>()
-                .join(",");
-            format!("handles={handles}")
-        }
-        DecodedAttPdu::ReadByGroupTypeResponse(response) => {
-            let entries = response
-                .entries
-                .iter()
-                .map(|entry| {
-                    format!(
-                        "0x{:04x}-0x{:04x}:{}",
-                        entry.range.start_handle,
-                        entry.range.end_handle,
-                        print_hex(entry.value)
-                    )
-                })
-                .collect::<Vec<_>>()
-                .join(",");
-            format!("entry_length={} entries={entries}", response.entry_length)
-        }
-        DecodedAttPdu::WriteRequest(write)
-        | DecodedAttPdu::HandleValueNotification(write)
-        | DecodedAttPdu::HandleValueIndication(write)
-        | DecodedAttPdu::WriteCommand(write) => {
-            format!(
-                "handle=0x{:04x} value={}",
-                write.handle,
-                print_hex(write.value)
-            )
-        }
-        DecodedAttPdu::WriteResponse
-        | DecodedAttPdu::ExecuteWriteResponse
-        | DecodedAttPdu::HandleValueConfirmation => "parameters=none".to_owned(),
-        DecodedAttPdu::PrepareWriteRequest(write) | DecodedAttPdu::PrepareWriteResponse(write) => {
-            format!(
-                "handle=0x{:04x} offset={} value={}",
-                write.handle,
-                write.offset,
-                print_hex(write.value)
-            )
-        }
-        DecodedAttPdu::ExecuteWriteRequest(flags) => format!("flags={flags}"),
-        DecodedAttPdu::ReadMultipleVariableResponse(list) => {
-            let values = list
-                .values
-                .iter()
-                .map(|value| {
-                    format!(
-                        "{}:{}:{}",
-                        value.declared_length,
-                        print_hex(value.value),
-                        value.truncated
-                    )
-                })
-                .collect::<Vec<_>>()
-                .join(",");
-            format!("values={values}")
+    if args.crc_failure_policy != LeCrcFailurePolicy::Discard {
+        eprintln!("reported {crc_failure_count} CRC-failed frame(s)");
+    }
+    if decryptor.is_some() {
+        eprintln!(
+            "authenticated {decrypted_packet_count} new encrypted packet(s); retransmissions={decryption_retransmission_count} unencrypted_empty={unencrypted_empty_count} skipped_counters={skipped_packet_counter_count} errors={decryption_error_count}"
+        );
+    }
+    if l2cap_reassembler.is_some() {
+        eprintln!(
+            "reassembled {l2cap_pdu_count} plaintext L2CAP PDU(s); duplicates={l2cap_duplicate_count} orphan_continuations={l2cap_orphan_count} discarded_incomplete={l2cap_discarded_count} errors={l2cap_error_count} signaling_errors={l2cap_signaling_error_count} att_errors={att_error_count} smp_errors={smp_error_count}"
+        );
+    }
+    Ok(())
+}
+
+fn encryption_trace(args: EncryptionTraceArgs) -> Result<()> {
+    let mut tracker = match args.long_term_key {
+        Some(long_term_key) => {
+            LeEncryptionSessionTracker::new(long_term_key, args.maximum_counter_skip)?
         }
-        DecodedAttPdu::MultipleHandleValueNotification(values) => {
-            let values = values
-                .iter()
-                .map(|value| format!("0x{:04x}:{}", value.handle, print_hex(value.value)))
-                .collect::<Vec<_>>()
-                .join(",");
-            format!("values={values}")
+        // A standalone trace has no CONNECT_IND to name the peers, so the
+        // keyring is searched by EDIV and Rand alone.
+        None => LeEncryptionSessionTracker::with_keyring(
+            args.key_sources.load()?,
+            &[],
+            args.maximum_counter_skip,
+        )?,
+    };
+    let mut accepted = 0usize;
+    let mut errors = 0usize;
+    for (index, directed) in args.packets.iter().enumerate() {
+        match args.output_format {
+            OutputFormat::Text => println!(
+                "raw_encryption_packet index={index} direction={} header={} payload={}",
+                directed.direction,
+                print_hex(&directed.packet.header),
+                print_hex(&directed.packet.payload)
+            ),
+            OutputFormat::JsonLines => print_json(
+                json_record("raw_encryption_packet")
+                    .field("index", index)
+                    .field("direction", Json::display(directed.direction))
+                    .field("header", Json::hex(&directed.packet.header))
+                    .field("payload", Json::hex(&directed.packet.payload)),
+            ),
         }
-        DecodedAttPdu::SignedWriteCommand(write) => format!(
-            "handle=0x{:04x} value={} signature={}",
-            write.handle,
-            print_hex(write.value),
-            print_hex(&write.signature)
-        ),
-        DecodedAttPdu::Unknown { parameters, .. } => {
-            format!("parameters={}", print_hex(parameters))
+        match tracker.observe(directed.direction, &directed.packet) {
+            Ok(observation) => {
+                accepted += 1;
+                let (protection, packet_counter, skipped_counters) = match observation.decryption {
+                    None => ("plaintext", None, 0),
+                    Some(LeAclDecryptionStatus::New {
+                        packet_counter,
+                        skipped_counters,
+                    }) => ("encrypted-new", Some(packet_counter), skipped_counters),
+                    Some(LeAclDecryptionStatus::Retransmission { packet_counter }) => {
+                        ("encrypted-retransmission", Some(packet_counter), 0)
+                    }
+                    Some(LeAclDecryptionStatus::UnencryptedEmpty) => ("unencrypted-empty", None, 0),
+                };
+                let control = observation
+                    .packet
+                    .control()
+                    .ok()
+                    .flatten()
+                    .map(ControlPdu::opcode_name);
+                match args.output_format {
+                    OutputFormat::Text => println!(
+                        "encryption_observation index={index} direction={} protection={protection} packet_counter={} skipped_counters={skipped_counters} state_before={} state_after={} header={} payload={} control={}",
+                        directed.direction,
+                        packet_counter
+                            .map_or_else(|| "none".to_owned(), |counter| counter.to_string()),
+                        observation.state_before,
+                        observation.state_after,
+                        print_hex(&observation.packet.header),
+                        print_hex(&observation.packet.payload),
+                        control.unwrap_or("none"),
+                    ),
+                    OutputFormat::JsonLines => print_json(
+                        json_record("encryption_observation")
+                            .field("index", index)
+                            .field("direction", Json::display(directed.direction))
+                            .field("protection", protection)
+                            .field("packet_counter", packet_counter)
+                            .field("skipped_counters", skipped_counters)
+                            .field("state_before", Json::display(observation.state_before))
+                            .field("state_after", Json::display(observation.state_after))
+                            .field("header", Json::hex(&observation.packet.header))
+                            .field("payload", Json::hex(&observation.packet.payload))
+                            .field("control", control),
+                    ),
+                }
+            }
+            Err(error) => {
+                errors += 1;
+                eprintln!(
+                    "encryption observation error: index={index} direction={} state={} error={error}",
+                    directed.direction,
+                    tracker.state()
+                );
+            }
         }
-    };
-    Ok(format!("{prefix} {details}"))
+    }
+    eprintln!(
+        "processed {} directed encryption packet(s); accepted={accepted} errors={errors} final_state={}",
+        args.packets.len(),
+        tracker.state()
+    );
+    Ok(())
 }
 
-fn describe_smp_authentication(authentication: SmpAuthenticationRequirements) -> String {
+fn describe_credit_channel(channel: &L2capCreditBasedChannel) -> String {
     format!(
-        "auth=0x{:02x} bonding={} mitm={} secure_connections={} keypress={} ct2={}",
-        authentication.raw,
-        authentication.bonding(),
-        authentication.mitm(),
-        authentication.secure_connections(),
-        authentication.keypress(),
-        authentication.ct2()
+        "mode={} spsm=0x{:04x} eatt={} status={} central_cid=0x{:04x} central_mtu={} central_mps={} central_credits={} peripheral_cid=0x{:04x} peripheral_mtu={} peripheral_mps={} peripheral_credits={}",
+        channel.mode,
+        channel.spsm,
+        channel.is_eatt(),
+        channel.status,
+        channel.central.channel_id,
+        channel.central.mtu,
+        channel.central.mps,
+        channel.central.credits,
+        channel.peripheral.channel_id,
+        channel.peripheral.mtu,
+        channel.peripheral.mps,
+        channel.peripheral.credits
     )
 }
 
-fn describe_smp_key_distribution(distribution: SmpKeyDistribution) -> String {
-    format!(
-        "0x{:02x}:enc={}:id={}:sign={}:link={}",
-        distribution.raw,
-        distribution.encryption_key(),
-        distribution.identity_key(),
-        distribution.signing_key(),
-        distribution.link_key()
-    )
+fn print_credit_sdu(
+    index: usize,
+    sdu: &L2capCreditBasedSdu,
+    output_format: OutputFormat,
+) -> Result<()> {
+    if output_format == OutputFormat::JsonLines {
+        print_json(
+            json_record("l2cap_credit_sdu")
+                .field("index", index)
+                .field("direction", Json::display(sdu.direction))
+                .field("cid", sdu.channel_id)
+                .field("spsm", sdu.spsm)
+                .field("eatt", sdu.is_eatt())
+                .field("segments", sdu.segment_count)
+                .field("payload", Json::hex(&sdu.payload)),
+        );
+        if let Some(att) = sdu.att_pdu()? {
+            print_json(
+                json_record("eatt_pdu")
+                    .field("index", index)
+                    .field("direction", Json::display(sdu.direction))
+                    .field("cid", sdu.channel_id)
+                    .extend(json_att_pdu(att)?),
+            );
+        }
+        return Ok(());
+    }
+    println!(
+        "l2cap_credit_sdu index={index} direction={} cid=0x{:04x} spsm=0x{:04x} eatt={} segments={} payload={}",
+        sdu.direction,
+        sdu.channel_id,
+        sdu.spsm,
+        sdu.is_eatt(),
+        sdu.segment_count,
+        print_hex(&sdu.payload)
+    );
+    if let Some(att) = sdu.att_pdu()? {
+        println!(
+            "eatt_pdu index={index} direction={} cid=0x{:04x} {}",
+            sdu.direction,
+            sdu.channel_id,
+            describe_att_pdu(att)?
+        );
+    }
+    Ok(())
 }
 
-fn print_device_address(address: [u8; 6]) -> String {
-    address
-        .iter()
-        .rev()
-        .map(|byte| format!("{byte:02x}"))
-        .collect::<Vec<_>>()
-        .join(":")
+fn json_credit_channel(channel: &L2capCreditBasedChannel) -> JsonObject {
+    JsonObject::new()
+        .field("mode", Json::display(channel.mode))
+        .field("spsm", channel.spsm)
+        .field("eatt", channel.is_eatt())
+        .field("status", Json::display(channel.status))
+        .field("central_cid", channel.central.channel_id)
+        .field("central_mtu", channel.central.mtu)
+        .field("central_mps", channel.central.mps)
+        .field("central_credits", channel.central.credits)
+        .field("peripheral_cid", channel.peripheral.channel_id)
+        .field("peripheral_mtu", channel.peripheral.mtu)
+        .field("peripheral_mps", channel.peripheral.mps)
+        .field("peripheral_credits", channel.peripheral.credits)
 }
 
-fn describe_smp_pdu(pdu: SmpPdu<'_>) -> Result<String> {
-    let prefix = format!("code=0x{:02x} name={}", pdu.code, pdu.code_name());
-    let details = match pdu.decode()? {
-        DecodedSmpPdu::PairingRequest(features) | DecodedSmpPdu::PairingResponse(features) => {
-            format!(
-                "io_capability={} oob={} {} maximum_key_size={} initiator_keys={} responder_keys={}",
-                features.io_capability,
-                features.oob_data_present,
-                describe_smp_authentication(features.authentication),
-                features.maximum_encryption_key_size,
-                describe_smp_key_distribution(features.initiator_key_distribution),
-                describe_smp_key_distribution(features.responder_key_distribution)
-            )
+const fn credit_owner_name(owner: LinkDirection) -> &'static str {
+    match owner {
+        LinkDirection::CentralToPeripheral => "central",
+        LinkDirection::PeripheralToCentral => "peripheral",
+    }
+}
+
+fn print_l2cap_credit_event_json(index: usize, event: L2capCreditBasedEvent) -> Result<()> {
+    let record = |kind: &str| {
+        json_record("l2cap_credit_event")
+            .field("index", index)
+            .field("kind", kind)
+    };
+    match event {
+        L2capCreditBasedEvent::Ignored => print_json(record("ignored")),
+        L2capCreditBasedEvent::ConnectionRequestPending {
+            mode,
+            identifier,
+            spsm,
+            channel_count,
+        } => print_json(
+            record("connection-request-pending")
+                .field("mode", Json::display(mode))
+                .field("identifier", identifier)
+                .field("spsm", spsm)
+                .field("channels", channel_count),
+        ),
+        L2capCreditBasedEvent::ConnectionRejected {
+            mode,
+            identifier,
+            result,
+        } => print_json(
+            record("connection-rejected")
+                .field("mode", Json::display(mode))
+                .field("identifier", identifier)
+                .field("result", result),
+        ),
+        L2capCreditBasedEvent::ChannelsOpened(channels) => {
+            for channel in channels {
+                print_json(record("channel-opened").extend(json_credit_channel(&channel)));
+            }
         }
-        DecodedSmpPdu::PairingConfirm(value) => {
-            format!("confirm={}", print_hex(&value))
+        L2capCreditBasedEvent::CreditsAdded {
+            owner,
+            channel_id,
+            added,
+            total,
+        } => print_json(
+            record("credits-added")
+                .field("owner", credit_owner_name(owner))
+                .field("cid", channel_id)
+                .field("added", added)
+                .field("total", total),
+        ),
+        L2capCreditBasedEvent::SduInProgress(sdu) => print_json(
+            record("sdu-in-progress")
+                .field("direction", Json::display(sdu.direction))
+                .field("cid", sdu.channel_id)
+                .field("spsm", sdu.spsm)
+                .field("received", sdu.received_octets)
+                .field("expected", sdu.expected_octets)
+                .field("segments", sdu.segment_count),
+        ),
+        L2capCreditBasedEvent::SduComplete(sdu) => {
+            print_credit_sdu(index, &sdu, OutputFormat::JsonLines)?;
         }
-        DecodedSmpPdu::PairingRandom(value) => {
-            format!("random={}", print_hex(&value))
+        L2capCreditBasedEvent::ReconfigurePending {
+            identifier,
+            owner,
+            channel_ids,
+            mtu,
+            mps,
+        } => print_json(
+            record("reconfigure-pending")
+                .field("identifier", identifier)
+                .field("owner", credit_owner_name(owner))
+                .field("cids", channel_ids)
+                .field("mtu", mtu)
+                .field("mps", mps),
+        ),
+        L2capCreditBasedEvent::ReconfigureRejected { identifier, result } => print_json(
+            record("reconfigure-rejected")
+                .field("identifier", identifier)
+                .field("result", result),
+        ),
+        L2capCreditBasedEvent::Reconfigured {
+            owner,
+            channel_ids,
+            mtu,
+            mps,
+        } => print_json(
+            record("reconfigured")
+                .field("owner", credit_owner_name(owner))
+                .field("cids", channel_ids)
+                .field("mtu", mtu)
+                .field("mps", mps),
+        ),
+        L2capCreditBasedEvent::DisconnectPending {
+            identifier,
+            central_channel_id,
+            peripheral_channel_id,
+        } => print_json(
+            record("disconnect-pending")
+                .field("identifier", identifier)
+                .field("central_cid", central_channel_id)
+                .field("peripheral_cid", peripheral_channel_id),
+        ),
+        L2capCreditBasedEvent::Disconnected(channel) => {
+            print_json(record("disconnected").extend(json_credit_channel(&channel)));
         }
-        DecodedSmpPdu::PairingFailed(failure) => format!(
-            "reason=0x{:02x} reason_name={}",
-            failure.reason,
-            failure.reason_name()
+        L2capCreditBasedEvent::CommandRejected {
+            identifier,
+            removed_pending_procedure,
+        } => print_json(
+            record("command-rejected")
+                .field("identifier", identifier)
+                .field("removed_pending", removed_pending_procedure),
+        ),
+    }
+    Ok(())
+}
+
+fn print_l2cap_credit_event(
+    index: usize,
+    event: L2capCreditBasedEvent,
+    output_format: OutputFormat,
+) -> Result<()> {
+    if output_format == OutputFormat::JsonLines {
+        return print_l2cap_credit_event_json(index, event);
+    }
+    match event {
+        L2capCreditBasedEvent::Ignored => {
+            println!("l2cap_credit_event index={index} kind=ignored");
+        }
+        L2capCreditBasedEvent::ConnectionRequestPending {
+            mode,
+            identifier,
+            spsm,
+            channel_count,
+        } => println!(
+            "l2cap_credit_event index={index} kind=connection-request-pending mode={mode} identifier={identifier} spsm=0x{spsm:04x} channels={channel_count}"
+        ),
+        L2capCreditBasedEvent::ConnectionRejected {
+            mode,
+            identifier,
+            result,
+        } => println!(
+            "l2cap_credit_event index={index} kind=connection-rejected mode={mode} identifier={identifier} result=0x{result:04x}"
+        ),
+        L2capCreditBasedEvent::ChannelsOpened(channels) => {
+            for channel in channels {
+                println!(
+                    "l2cap_credit_event index={index} kind=channel-opened {}",
+                    describe_credit_channel(&channel)
+                );
+            }
+        }
+        L2capCreditBasedEvent::CreditsAdded {
+            owner,
+            channel_id,
+            added,
+            total,
+        } => println!(
+            "l2cap_credit_event index={index} kind=credits-added owner={} cid=0x{channel_id:04x} added={added} total={total}",
+            match owner {
+                LinkDirection::CentralToPeripheral => "central",
+                LinkDirection::PeripheralToCentral => "peripheral",
+            }
+        ),
+        L2capCreditBasedEvent::SduInProgress(IncompleteL2capCreditBasedSdu {
+            direction,
+            channel_id,
+            spsm,
+            expected_octets,
+            received_octets,
+            segment_count,
+        }) => println!(
+            "l2cap_credit_event index={index} kind=sdu-in-progress direction={direction} cid=0x{channel_id:04x} spsm=0x{spsm:04x} received={received_octets} expected={expected_octets} segments={segment_count}"
+        ),
+        L2capCreditBasedEvent::SduComplete(sdu) => {
+            print_credit_sdu(index, &sdu, OutputFormat::Text)?;
+        }
+        L2capCreditBasedEvent::ReconfigurePending {
+            identifier,
+            owner,
+            channel_ids,
+            mtu,
+            mps,
+        } => println!(
+            "l2cap_credit_event index={index} kind=reconfigure-pending identifier={identifier} owner={} cids={} mtu={mtu} mps={mps}",
+            match owner {
+                LinkDirection::CentralToPeripheral => "central",
+                LinkDirection::PeripheralToCentral => "peripheral",
+            },
+            print_l2cap_channel_ids(&channel_ids)
         ),
-        DecodedSmpPdu::EncryptionInformation(key) => {
-            format!("ltk={}", print_hex(&key))
-        }
-        DecodedSmpPdu::CentralIdentification(identification) => format!(
-            "ediv=0x{:04x} random={}",
-            identification.encrypted_diversifier,
-            print_hex(&identification.random)
+        L2capCreditBasedEvent::ReconfigureRejected { identifier, result } => println!(
+            "l2cap_credit_event index={index} kind=reconfigure-rejected identifier={identifier} result=0x{result:04x}"
         ),
-        DecodedSmpPdu::IdentityInformation(key) => {
-            format!("irk={}", print_hex(&key))
-        }
-        DecodedSmpPdu::IdentityAddressInformation(identity) => format!(
-            "address_type={} address={}",
-            identity.address_type,
-            print_device_address(identity.address)
+        L2capCreditBasedEvent::Reconfigured {
+            owner,
+            channel_ids,
+            mtu,
+            mps,
+        } => println!(
+            "l2cap_credit_event index={index} kind=reconfigured owner={} cids={} mtu={mtu} mps={mps}",
+            match owner {
+                LinkDirection::CentralToPeripheral => "central",
+                LinkDirection::PeripheralToCentral => "peripheral",
+            },
+            print_l2cap_channel_ids(&channel_ids)
+        ),
+        L2capCreditBasedEvent::DisconnectPending {
+            identifier,
+            central_channel_id,
+            peripheral_channel_id,
+        } => println!(
+            "l2cap_credit_event index={index} kind=disconnect-pending identifier={identifier} central_cid=0x{central_channel_id:04x} peripheral_cid=0x{peripheral_channel_id:04x}"
+        ),
+        L2capCreditBasedEvent::Disconnected(channel) => println!(
+            "l2cap_credit_event index={index} kind=disconnected {}",
+            describe_credit_channel(&channel)
+        ),
+        L2capCreditBasedEvent::CommandRejected {
+            identifier,
+            removed_pending_procedure,
+        } => println!(
+            "l2cap_credit_event index={index} kind=command-rejected identifier={identifier} removed_pending={removed_pending_procedure}"
         ),
-        DecodedSmpPdu::SigningInformation(key) => {
-            format!("csrk={}", print_hex(&key))
-        }
-        DecodedSmpPdu::SecurityRequest(authentication) => {
-            describe_smp_authentication(authentication)
-        }
-        DecodedSmpPdu::PairingPublicKey(key) => {
-            format!("x={} y={}", print_hex(&key.x), print_hex(&key.y))
-        }
-        DecodedSmpPdu::PairingDhKeyCheck(value) => {
-            format!("dhkey_check={}", print_hex(&value))
-        }
-        DecodedSmpPdu::KeypressNotification(notification_type) => {
-            format!("notification_type={notification_type}")
-        }
-        DecodedSmpPdu::Unknown { parameters, .. } => {
-            format!("parameters={}", print_hex(parameters))
-        }
-    };
-    Ok(format!("{prefix} {details}"))
-}
-
-fn decode(args: DecodeArgs) -> Result<()> {
-    if args.block_samples == 0 {
-        return Err(Error::InvalidConfiguration(
-            "--block-samples must be greater than zero".to_owned(),
-        ));
-    }
-    let (mut reader, sample_count) = open_iq_file(&args.input, args.format)?;
-    if sample_count > args.max_samples {
-        return Err(Error::InvalidInput(format!(
-            "I/Q file contains {sample_count} samples, exceeding the configured limit of {}",
-            args.max_samples
-        )));
     }
+    Ok(())
+}
 
-    let config = Le1mDemodConfig {
-        sample_rate_hz: args.sample_rate_hz,
-        max_access_address_errors: args.max_access_address_errors,
-    };
-    let mut decoder = Le1mStreamDecoder::new(args.channel, config)?;
-    let mut pcap = match &args.output_pcap {
-        Some(path) => Some(PcapNgWriter::new(BufWriter::new(File::create(path)?))?),
-        None => None,
+fn keyring_command(args: KeyringArgs) -> Result<()> {
+    let stored = || {
+        if args.path.exists() {
+            LeKeyring::load(&args.path)
+        } else {
+            Ok(LeKeyring::new())
+        }
     };
-    let mut packet_count = 0usize;
-
-    loop {
-        let first_sample = reader.next_sample_index();
-        let samples = reader.read_block(args.block_samples)?;
-        if samples.is_empty() {
-            break;
+    match args.action {
+        KeyringAction::Add(entries) => {
+            let mut keyring = stored()?;
+            let added = keyring.merge(&entries);
+            keyring.save(&args.path)?;
+            eprintln!(
+                "added {} to {}",
+                describe_key_import(added),
+                args.path.display()
+            );
         }
-        let batch = decoder.push(first_sample, &samples)?;
-        if let Some(discontinuity) = batch.discontinuity {
+        KeyringAction::Import(sources) => {
+            let mut keyring = stored()?;
+            let added = keyring.merge(&sources.load()?);
+            keyring.save(&args.path)?;
             eprintln!(
-                "sample discontinuity: expected {}, observed {}",
-                discontinuity.expected_first_sample, discontinuity.observed_first_sample
+                "added {} to {}",
+                describe_key_import(added),
+                args.path.display()
             );
         }
-        for packet in &batch.packets {
-            print_packet(packet);
-            if let Some(writer) = &mut pcap {
-                let timestamp = sample_timestamp_ns(
-                    args.capture_start_ns,
-                    packet.access_address_sample,
-                    args.sample_rate_hz,
-                )?;
-                writer.write_advertising(packet, timestamp)?;
+        KeyringAction::Remove { address, kind } => {
+            let mut keyring = LeKeyring::load(&args.path)?;
+            let removed = keyring.remove_device(address, kind);
+            keyring.save(&args.path)?;
+            eprintln!(
+                "removed {removed} entr{} for {address} from {}",
+                if removed == 1 { "y" } else { "ies" },
+                args.path.display()
+            );
+        }
+        KeyringAction::List { show_keys } => {
+            let keyring = LeKeyring::load(&args.path)?;
+            let key = |key: &[u8; 16]| {
+                if show_keys {
+                    print_hex(key)
+                } else {
+                    "redacted".to_owned()
+                }
+            };
+            let mut entries = Vec::new();
+            for entry in keyring.long_term_keys() {
+                entries.push((
+                    KeyringEntryKind::LongTermKey,
+                    entry.address,
+                    Some((entry.encrypted_diversifier, entry.random_number)),
+                    key(&entry.key),
+                    None,
+                ));
+            }
+            for entry in keyring.identity_resolving_keys() {
+                entries.push((
+                    KeyringEntryKind::IdentityResolvingKey,
+                    Some(entry.address),
+                    None,
+                    key(&entry.key),
+                    None,
+                ));
+            }
+            for entry in keyring.signature_keys() {
+                entries.push((
+                    KeyringEntryKind::SignatureKey,
+                    Some(entry.address),
+                    None,
+                    key(&entry.key),
+                    None,
+                ));
+            }
+            for entry in keyring.link_keys() {
+                entries.push((
+                    KeyringEntryKind::LinkKey,
+                    Some((entry.address, AddressKind::Public)),
+                    None,
+                    key(&entry.key),
+                    None,
+                ));
+            }
+            for entry in keyring.encrypted_data_keys() {
+                let material = entry.key_material;
+                entries.push((
+                    KeyringEntryKind::EncryptedDataKey,
+                    entry.address,
+                    None,
+                    key(&material.session_key),
+                    Some(if show_keys {
+                        print_hex(&material.initialization_vector)
+                    } else {
+                        "redacted".to_owned()
+                    }),
+                ));
+            }
+            for (index, (kind, address, diversifier, key, initialization_vector)) in
+                entries.into_iter().enumerate()
+            {
+                match args.output_format {
+                    OutputFormat::Text => println!(
+                        "keyring_entry index={index} type={kind} address={}{} key={key}{}",
+                        describe_optional_address(address),
+                        diversifier.map_or_else(String::new, |(ediv, rand)| format!(
+                            " ediv={ediv} rand={}",
+                            print_hex(&rand)
+                        )),
+                        initialization_vector
+                            .as_ref()
+                            .map_or_else(String::new, |iv| format!(" iv={iv}"))
+                    ),
+                    OutputFormat::JsonLines => print_json(
+                        json_record("keyring_entry")
+                            .field("index", index)
+                            .field("entry_type", Json::display(kind))
+                            .field(
+                                "address",
+                                address.map(|(address, _)| Json::display(address)),
+                            )
+                            .field("address_kind", address.map(|(_, kind)| Json::display(kind)))
+                            .field("ediv", diversifier.map(|(ediv, _)| ediv))
+                            .field("rand", diversifier.map(|(_, rand)| Json::hex(&rand)))
+                            .field("key", show_keys.then_some(key))
+                            .field("iv", initialization_vector.filter(|_| show_keys)),
+                    ),
+                }
             }
         }
-        packet_count += batch.packets.len();
-    }
-
-    if let Some(writer) = pcap {
-        writer.into_inner().flush()?;
     }
-    eprintln!("decoded {packet_count} CRC-valid packet(s) from {sample_count} sample(s)");
     Ok(())
 }
 
-fn decode_secondary(args: DecodeSecondaryArgs) -> Result<()> {
-    let (mut reader, sample_count) = open_iq_file(&args.input, args.format)?;
-    if sample_count > args.max_samples {
-        return Err(Error::InvalidInput(format!(
-            "I/Q file contains {sample_count} samples, exceeding the configured limit of {}",
-            args.max_samples
-        )));
-    }
-
-    let config = LeUncodedDemodConfig {
-        phy: args.phy,
-        sample_rate_hz: args.sample_rate_hz,
-        max_access_address_errors: args.max_access_address_errors,
-    };
-    let mut decoder = LeSecondaryAdvertisingStreamDecoder::new(args.channel, config)?;
-    let mut pcap = match &args.output_pcap {
-        Some(path) => Some(PcapNgWriter::new(BufWriter::new(File::create(path)?))?),
-        None => None,
-    };
-    let mut packet_count = 0usize;
-
-    loop {
-        let first_sample = reader.next_sample_index();
-        let samples = reader.read_block(args.block_samples)?;
-        if samples.is_empty() {
-            break;
+fn l2cap_trace(args: L2capTraceArgs) -> Result<()> {
+    let mut tracker = L2capCreditBasedChannelTracker::default();
+    let mut accepted = 0usize;
+    let mut errors = 0usize;
+    for (index, pdu) in args.pdus.iter().enumerate() {
+        match args.output_format {
+            OutputFormat::Text => println!(
+                "raw_l2cap_pdu index={index} direction={} cid=0x{:04x} payload={}",
+                pdu.direction,
+                pdu.channel_id,
+                print_hex(&pdu.payload)
+            ),
+            OutputFormat::JsonLines => print_json(
+                json_record("raw_l2cap_pdu")
+                    .field("index", index)
+                    .field("direction", Json::display(pdu.direction))
+                    .field("cid", pdu.channel_id)
+                    .field("payload", Json::hex(&pdu.payload)),
+            ),
         }
-        let batch = decoder.push(first_sample, &samples)?;
-        if let Some(discontinuity) = batch.discontinuity {
-            eprintln!(
-                "sample discontinuity: expected {}, observed {}",
-                discontinuity.expected_first_sample, discontinuity.observed_first_sample
-            );
+        if pdu.channel_id == blueoxide::link_layer::LE_SIGNALING_CHANNEL_ID {
+            match pdu.le_signaling_command() {
+                Ok(Some(command)) => {
+                    if let Err(error) =
+                        print_l2cap_signal(Some(index), pdu.direction, command, args.output_format)
+                    {
+                        eprintln!(
+                            "l2cap signaling decode error: index={index} direction={} error={error}",
+                            pdu.direction
+                        );
+                    }
+                }
+                Ok(None) => {}
+                Err(error) => eprintln!(
+                    "l2cap signaling envelope error: index={index} direction={} error={error}",
+                    pdu.direction
+                ),
+            }
         }
-        for packet in &batch.packets {
-            print_packet(packet);
-            if let Some(writer) = &mut pcap {
-                let timestamp = sample_timestamp_ns(
-                    args.capture_start_ns,
-                    packet.access_address_sample,
-                    args.sample_rate_hz,
-                )?;
-                writer.write_advertising(packet, timestamp)?;
+        match tracker.observe(pdu) {
+            Ok(event) => {
+                accepted += 1;
+                print_l2cap_credit_event(index, event, args.output_format)?;
+            }
+            Err(error) => {
+                errors += 1;
+                eprintln!(
+                    "l2cap credit observation error: index={index} direction={} cid=0x{:04x} error={error}",
+                    pdu.direction, pdu.channel_id
+                );
             }
         }
-        packet_count += batch.packets.len();
-    }
-
-    if let Some(writer) = pcap {
-        writer.into_inner().flush()?;
     }
-    eprintln!("decoded {packet_count} CRC-valid packet(s) from {sample_count} sample(s)");
+    eprintln!(
+        "processed {} directed L2CAP PDU(s); accepted={accepted} errors={errors} open_channels={}",
+        args.pdus.len(),
+        tracker.channels().count()
+    );
     Ok(())
 }
 
-fn decode_periodic(args: DecodePeriodicArgs) -> Result<()> {
-    let (mut reader, sample_count) = open_iq_file(&args.input, args.format)?;
-    if sample_count > args.max_samples {
-        return Err(Error::InvalidInput(format!(
-            "I/Q file contains {sample_count} samples, exceeding the configured limit of {}",
-            args.max_samples
+fn current_unix_time_ns() -> Result<u64> {
+    let duration = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|error| {
+            Error::InvalidInput(format!("system clock precedes Unix epoch: {error}"))
+        })?;
+    u64::try_from(duration.as_nanos())
+        .map_err(|_| Error::InvalidInput("current Unix timestamp exceeds u64".to_owned()))
+}
+
+fn capture(args: CaptureArgs) -> Result<()> {
+    let device = args.device.to_ascii_lowercase();
+    if !matches!(
+        device.as_str(),
+        "bladerf" | "limesdr" | "lime" | "xtrx" | "stream" | "remote" | "replay"
+    ) {
+        return Err(Error::InvalidConfiguration(format!(
+            "{} device {:?} is not implemented; currently available: bladerf, limesdr, xtrx, stream, remote, replay",
+            args.frame.command_name(),
+            args.device
         )));
     }
-
-    let config = LeUncodedDemodConfig {
-        phy: args.phy,
+    let radio_config = SdrConfig {
+        center_frequency_hz: args.channel.center_frequency_hz(),
         sample_rate_hz: args.sample_rate_hz,
-        max_access_address_errors: args.max_access_address_errors,
-    };
-    let mut decoder = LePeriodicAdvertisingStreamDecoder::new(
-        args.channel,
-        args.access_address,
-        args.crc_init,
-        config,
-    )?;
-    let mut pcap = match &args.output_pcap {
-        Some(path) => Some(PcapNgWriter::new(BufWriter::new(File::create(path)?))?),
-        None => None,
+        bandwidth_hz: args.bandwidth_hz,
+        gain_db: args.gain_db,
+        channel: args.rx_channel,
     };
-    let mut packet_count = 0usize;
 
-    loop {
-        let first_sample = reader.next_sample_index();
-        let samples = reader.read_block(args.block_samples)?;
-        if samples.is_empty() {
-            break;
+    let stats = match device.as_str() {
+        "bladerf" => {
+            let mut source =
+                BladeRfSource::open(args.identifier.as_deref(), BladeRfOptions::default())?;
+            let stats = capture_from_source(&mut source, &args, &radio_config)?;
+            if let Some(applied) = source.applied_config() {
+                eprintln!(
+                    "bladeRF applied sample_rate={} bandwidth={}",
+                    applied.sample_rate_hz, applied.bandwidth_hz
+                );
+            }
+            stats
+        }
+        "limesdr" | "lime" => {
+            let mut source =
+                LimeSdrSource::open(args.identifier.as_deref(), LimeSdrOptions::default())?;
+            let stats = capture_from_source(&mut source, &args, &radio_config)?;
+            if let Some(applied) = source.applied_config() {
+                eprintln!(
+                    "LimeSDR applied sample_rate={} bandwidth={}",
+                    applied.sample_rate_hz, applied.bandwidth_hz
+                );
+            }
+            stats
+        }
+        "xtrx" => {
+            let mut source = XtrxSource::open(args.identifier.as_deref(), XtrxOptions::default())?;
+            let stats = capture_from_source(&mut source, &args, &radio_config)?;
+            if let Some(applied) = source.applied_config() {
+                eprintln!(
+                    "XTRX applied sample_rate={} bandwidth={}",
+                    applied.sample_rate_hz, applied.bandwidth_hz
+                );
+            }
+            stats
         }
-        let batch = decoder.push(first_sample, &samples)?;
-        if let Some(discontinuity) = batch.discontinuity {
-            eprintln!(
-                "sample discontinuity: expected {}, observed {}",
-                discontinuity.expected_first_sample, discontinuity.observed_first_sample
-            );
+        "stream" => {
+            let identifier = args.identifier.as_deref().ok_or_else(|| {
+                Error::InvalidConfiguration(format!(
+                    "{} --device stream requires --identifier -|PATH|tcp://HOST:PORT",
+                    args.frame.command_name()
+                ))
+            })?;
+            let mut source = StreamSource::open(
+                &IqStreamLocation::parse(identifier)?,
+                args.stream_format,
+                args.stream_framing,
+            )?;
+            capture_from_source(&mut source, &args, &radio_config)?
         }
-        for packet in &batch.packets {
-            print_periodic_packet(packet);
-            if let Some(writer) = &mut pcap {
-                let timestamp = sample_timestamp_ns(
-                    args.capture_start_ns,
-                    packet.access_address_sample,
-                    args.sample_rate_hz,
-                )?;
-                writer.write_advertising(packet, timestamp)?;
+        "remote" => {
+            let identifier = args.identifier.as_deref().ok_or_else(|| {
+                Error::InvalidConfiguration(format!(
+                    "{} --device remote requires --identifier HOST:PORT",
+                    args.frame.command_name()
+                ))
+            })?;
+            let mut source = RemoteSource::connect(identifier)?;
+            let stats = capture_from_source(&mut source, &args, &radio_config)?;
+            if let Some(applied_sample_rate_hz) = source.applied_sample_rate_hz() {
+                eprintln!(
+                    "remote {:?} applied sample_rate={applied_sample_rate_hz}",
+                    source.kind()
+                );
             }
+            stats
         }
-        packet_count += batch.packets.len();
-    }
-
-    if let Some(writer) = pcap {
-        writer.into_inner().flush()?;
-    }
-    eprintln!("decoded {packet_count} CRC-valid packet(s) from {sample_count} sample(s)");
+        "replay" => {
+            let identifier = args.identifier.as_deref().ok_or_else(|| {
+                Error::InvalidConfiguration(format!(
+                    "{} --device replay requires --identifier PATH",
+                    args.frame.command_name()
+                ))
+            })?;
+            let path = Path::new(identifier);
+            let mut source = if is_sigmf_path(path) {
+                ReplaySource::open_sigmf(path, args.replay.clone())?
+            } else {
+                ReplaySource::open(path, args.stream_format, args.replay.clone())?
+            };
+            let stats = capture_from_source(&mut source, &args, &radio_config)?;
+            eprintln!("replay ended at recording sample {}", source.position());
+            stats
+        }
+        _ => unreachable!(),
+    };
+    eprintln!(
+        "capture complete: samples={} packets={} overruns={} dropped={} discontinuities={} crc_failures={}",
+        stats.samples_received,
+        stats.packets_decoded,
+        stats.overruns,
+        stats.dropped_samples,
+        stats.discontinuities,
+        stats.crc_failures
+    );
     Ok(())
 }
 
-fn decode_data(args: DecodeDataArgs) -> Result<()> {
-    if args.block_samples == 0 {
-        return Err(Error::InvalidConfiguration(
-            "--block-samples must be greater than zero".to_owned(),
-        ));
-    }
-    let (mut reader, sample_count) = open_iq_file(&args.input, args.format)?;
-    if sample_count > args.max_samples {
-        return Err(Error::InvalidInput(format!(
-            "I/Q file contains {sample_count} samples, exceeding the configured limit of {}",
-            args.max_samples
-        )));
-    }
-
-    let demod_config = LeUncodedDemodConfig {
-        phy: args.phy,
-        sample_rate_hz: args.sample_rate_hz,
-        max_access_address_errors: args.max_access_address_errors,
-    };
-    let frame_config = LeFrameConfig::data(args.access_address, args.crc_init)?;
-    let mut decoder = LeUncodedPacketStreamDecoder::new(args.channel, frame_config, demod_config)?;
-    let mut decryptor = match &args.decryption {
-        Some(decryption) => Some(LeAclDecryptor::new(
-            decryption.session_key,
-            decryption.initialization_vector,
-            decryption.direction,
-            decryption.initial_packet_counter,
-            decryption.maximum_counter_skip,
+fn capture_from_source<S: IqSource>(
+    source: &mut S,
+    args: &CaptureArgs,
+    radio_config: &SdrConfig,
+) -> Result<CaptureStats> {
+    let capture_start_ns = args
+        .capture_start_ns
+        .map(Ok)
+        .unwrap_or_else(current_unix_time_ns)?;
+    // Shared by the packet and CRC-failure callbacks and the flush thread.
+    let pcap = Mutex::new(match &args.output_pcap {
+        Some(path) => Some(RotatingPcapNgWriter::create(
+            path,
+            pcapng_section(
+                &args.device,
+                format!(
+                    "command=capture device={} channel={} sample_rate_hz={} bandwidth_hz={} gain_db={} rx_channel={}",
+                    args.device,
+                    args.channel.index(),
+                    args.sample_rate_hz,
+                    args.bandwidth_hz,
+                    args.gain_db,
+                    args.rx_channel
+                ),
+            ),
+            vec![pcapng_channel_interface(
+                args.channel,
+                args.sample_rate_hz,
+                &args.device,
+            )],
+            args.pcap_rotation,
+            capture_start_ns,
         )?),
         None => None,
+    });
+    let stats = thread::scope(|scope| {
+        let (stop_flushing, flush_stop) = mpsc::channel();
+        let flusher = match args.pcap_rotation.flush_interval {
+            Some(interval) if args.output_pcap.is_some() => Some(
+                thread::Builder::new()
+                    .name("blueoxide-pcapng-flush".to_owned())
+                    .spawn_scoped(scope, {
+                        let pcap = &pcap;
+                        move || flush_pcap_periodically(pcap, interval, flush_stop)
+                    })?,
+            ),
+            _ => None,
+        };
+        let stats = capture_to_pcap(source, args, radio_config, &pcap, capture_start_ns);
+        drop(stop_flushing);
+        if let Some(flusher) = flusher {
+            flusher
+                .join()
+                .map_err(|_| Error::InvalidState("PCAPNG flush thread panicked".to_owned()))??;
+        }
+        stats
+    })?;
+    if let Some(mut writer) = pcap.into_inner().unwrap_or_else(PoisonError::into_inner) {
+        writer.flush()?;
+    }
+    Ok(stats)
+}
+
+fn capture_to_pcap<S: IqSource>(
+    source: &mut S,
+    args: &CaptureArgs,
+    radio_config: &SdrConfig,
+    pcap: &Mutex<Option<RotatingPcapNgWriter>>,
+    capture_start_ns: u64,
+) -> Result<CaptureStats> {
+    let mut central_observation_tracker = args
+        .central_observation_tracking
+        .clone()
+        .map(|config| FixedChannelCentralObservationTracker::new(args.channel, config))
+        .transpose()?;
+    let mut central_observation_matches = 0u64;
+    let mut central_observation_errors = 0u64;
+
+    let limits = CaptureLimits {
+        maximum_samples: None,
+        maximum_duration: Some(args.duration),
+        read_timeout: Duration::from_millis(args.read_timeout_ms),
+        block_samples: args.block_samples,
     };
-    let mut l2cap_reassembler = match args.plaintext_l2cap_direction {
-        Some(direction) => Some((
-            direction,
-            L2capReassembler::new(args.maximum_l2cap_payload_length)?,
-        )),
-        None => None,
-    };
-    let mut pcap = match &args.output_pcap {
-        Some(path) => Some(PcapNgWriter::new(BufWriter::new(File::create(path)?))?),
-        None => None,
+    let mut report_captured_crc_failure = |failure: &CapturedCrcFailure| {
+        let mut pcap = lock_pcap(pcap);
+        let writer = match pcap.as_mut() {
+            Some(pcap) => Some(pcap.packet_writer(sample_timestamp_ns(
+                capture_start_ns,
+                failure.relative_sample_index,
+                args.sample_rate_hz,
+            )?)?),
+            None => None,
+        };
+        report_crc_failure(
+            &failure.observation,
+            failure.relative_sample_index,
+            writer,
+            capture_start_ns,
+            args.sample_rate_hz,
+            args.output_format,
+        )
     };
-    let mut packet_count = 0usize;
-    let mut l2cap_pdu_count = 0usize;
-    let mut l2cap_duplicate_count = 0usize;
-    let mut l2cap_orphan_count = 0usize;
-    let mut l2cap_discarded_count = 0usize;
-    let mut l2cap_error_count = 0usize;
-    let mut l2cap_signaling_error_count = 0usize;
-    let mut att_error_count = 0usize;
-    let mut smp_error_count = 0usize;
-    let mut ll_control_error_count = 0usize;
-    let mut decrypted_packet_count = 0usize;
-    let mut decryption_retransmission_count = 0usize;
-    let mut unencrypted_empty_count = 0usize;
-    let mut decryption_error_count = 0usize;
-    let mut skipped_packet_counter_count = 0u64;
-
-    loop {
-        let first_sample = reader.next_sample_index();
-        let samples = reader.read_block(args.block_samples)?;
-        if samples.is_empty() {
-            break;
-        }
-        let batch = decoder.push(first_sample, &samples)?;
-        if let Some(discontinuity) = batch.discontinuity {
-            eprintln!(
-                "sample discontinuity: expected {}, observed {}",
-                discontinuity.expected_first_sample, discontinuity.observed_first_sample
-            );
-            if let Some((direction, reassembler)) = &mut l2cap_reassembler
-                && let Some(incomplete) = reassembler.reset(*direction)
-            {
-                l2cap_discarded_count += 1;
-                eprintln!(
-                    "discarded incomplete plaintext L2CAP PDU after sample discontinuity: {}",
-                    describe_incomplete_l2cap(incomplete)
-                );
-            }
-        }
-        for packet in &batch.packets {
-            let raw_data = DataChannelPdu::from(packet.pdu.clone());
-            let data = if let Some(decryptor) = &mut decryptor {
-                print_data_packet(packet, &raw_data, false)?;
-                match decryptor.decrypt(&raw_data) {
-                    Ok(decryption) => {
-                        match decryption.status {
-                            LeAclDecryptionStatus::New {
-                                skipped_counters, ..
-                            } => {
-                                decrypted_packet_count += 1;
-                                skipped_packet_counter_count = skipped_packet_counter_count
-                                    .checked_add(skipped_counters)
-                                    .ok_or_else(|| {
-                                        Error::InvalidState(
-                                            "skipped packet-counter total overflow".to_owned(),
-                                        )
-                                    })?;
-                                if skipped_counters != 0
-                                    && let Some((direction, reassembler)) = &mut l2cap_reassembler
-                                    && let Some(incomplete) = reassembler.reset(*direction)
-                                {
-                                    l2cap_discarded_count += 1;
-                                    eprintln!(
-                                        "discarded incomplete plaintext L2CAP PDU after {skipped_counters} skipped encrypted packet counter(s): {}",
-                                        describe_incomplete_l2cap(incomplete)
-                                    );
-                                }
-                            }
-                            LeAclDecryptionStatus::Retransmission { .. } => {
-                                decryption_retransmission_count += 1;
-                            }
-                            LeAclDecryptionStatus::UnencryptedEmpty => {
-                                unencrypted_empty_count += 1;
-                            }
-                        }
-                        if let Err(error) =
-                            print_decrypted_data_packet(decryptor.direction(), &decryption)
-                            && decryption.packet.llid() == LogicalLinkId::Control
-                        {
-                            ll_control_error_count += 1;
-                            eprintln!(
-                                "decrypted LL control PDU decode error: opcode={} error={error}",
-                                decryption
-                                    .packet
-                                    .payload
-                                    .first()
-                                    .map(|opcode| format!("0x{opcode:02x}"))
-                                    .unwrap_or_else(|| "missing".to_owned())
-                            );
-                        }
-                        Some(decryption.packet)
-                    }
-                    Err(error) => {
-                        decryption_error_count += 1;
-                        eprintln!(
-                            "LE ACL decryption error: direction={} error={error}",
-                            decryptor.direction()
-                        );
-                        if let Some((direction, reassembler)) = &mut l2cap_reassembler
-                            && let Some(incomplete) = reassembler.reset(*direction)
-                        {
-                            l2cap_discarded_count += 1;
-                            eprintln!(
-                                "discarded incomplete plaintext L2CAP PDU after decryption failure: {}",
-                                describe_incomplete_l2cap(incomplete)
-                            );
-                        }
-                        None
-                    }
+    let stats = match args.frame {
+        CaptureFrame::Advertising => capture_primary_advertising_with_crc_failures(
+            source,
+            radio_config,
+            args.channel,
+            Le1mDemodConfig {
+                sample_rate_hz: args.sample_rate_hz,
+                max_access_address_errors: args.max_access_address_errors,
+            },
+            args.crc_failure_policy,
+            limits,
+            |captured: &CapturedAdvertisingPdu| {
+                print_packet(&captured.observation, args.output_format);
+                if let Some(pcap) = lock_pcap(pcap).as_mut() {
+                    let timestamp = sample_timestamp_ns(
+                        capture_start_ns,
+                        captured.relative_sample_index,
+                        args.sample_rate_hz,
+                    )?;
+                    pcap.packet_writer(timestamp)?
+                        .write_advertising_with_options(
+                            &captured.observation,
+                            timestamp,
+                            Some(&PcapNgPacketOptions::received_advertising(
+                                0,
+                                &captured.observation,
+                            )),
+                        )?;
                 }
-            } else {
-                if let Err(error) = print_data_packet(packet, &raw_data, true)
-                    && raw_data.llid() == LogicalLinkId::Control
+                Ok(())
+            },
+            &mut report_captured_crc_failure,
+        )?,
+        CaptureFrame::Data {
+            access_address,
+            crc_init,
+            phy,
+        } => capture_data_channel_with_crc_failures(
+            source,
+            radio_config,
+            args.channel,
+            LeFrameConfig::data(access_address, crc_init)?,
+            LeUncodedDemodConfig {
+                phy,
+                sample_rate_hz: args.sample_rate_hz,
+                max_access_address_errors: args.max_access_address_errors,
+            },
+            args.crc_failure_policy,
+            limits,
+            |captured: &CapturedDataChannelPdu| {
+                let data = DataChannelPdu::from(captured.observation.pdu.clone());
+                let mut pcap_options = PcapNgPacketOptions::received(0, &captured.observation);
+                if let Err(error) =
+                    print_data_packet(&captured.observation, &data, true, args.output_format)
                 {
-                    ll_control_error_count += 1;
                     eprintln!(
-                        "LL control PDU decode error: opcode={} error={error}",
-                        raw_data
-                            .payload
-                            .first()
-                            .map(|opcode| format!("0x{opcode:02x}"))
-                            .unwrap_or_else(|| "missing".to_owned())
+                        "live data-channel plaintext-hint decode error: channel={} sample={} error={error}",
+                        data.channel.index(),
+                        captured.observation.access_address_sample
                     );
                 }
-                Some(raw_data.clone())
-            };
-            if let (Some((direction, reassembler)), Some(data)) =
-                (&mut l2cap_reassembler, data.as_ref())
-            {
-                match reassembler.push(*direction, data) {
-                    Ok(update) => {
-                        if let Some(replaced) = update.replaced {
-                            l2cap_discarded_count += 1;
-                            eprintln!(
-                                "replaced incomplete plaintext L2CAP PDU: {}",
-                                describe_incomplete_l2cap(replaced)
-                            );
-                        }
-                        match update.outcome {
-                            L2capReassemblyOutcome::Ignored
-                            | L2capReassemblyOutcome::InProgress(_) => {}
-                            L2capReassemblyOutcome::Duplicate => {
-                                l2cap_duplicate_count += 1;
-                            }
-                            L2capReassemblyOutcome::OrphanedContinuation { fragment_octets } => {
-                                l2cap_orphan_count += 1;
-                                eprintln!(
-                                    "orphaned plaintext L2CAP continuation: direction={} fragment_octets={fragment_octets}",
-                                    direction
-                                );
-                            }
-                            L2capReassemblyOutcome::Complete(pdu) => {
-                                l2cap_pdu_count += 1;
-                                println!(
-                                    "l2cap_pdu direction={} cid=0x{:04x} length={} fragments={} payload={}",
-                                    pdu.direction,
-                                    pdu.channel_id,
-                                    pdu.payload.len(),
-                                    pdu.fragment_count,
-                                    print_hex(&pdu.payload)
-                                );
-                                match pdu.le_signaling_command() {
-                                    Ok(Some(command)) => match describe_l2cap_signaling(command) {
-                                        Ok(description) => println!(
-                                            "l2cap_signal direction={} {description}",
-                                            pdu.direction
-                                        ),
-                                        Err(error) => {
-                                            l2cap_signaling_error_count += 1;
-                                            eprintln!(
-                                                "plaintext L2CAP signaling command decode error: direction={} error={error}",
-                                                pdu.direction
-                                            );
-                                        }
-                                    },
-                                    Ok(None) => {}
-                                    Err(error) => {
-                                        l2cap_signaling_error_count += 1;
-                                        eprintln!(
-                                            "plaintext L2CAP signaling envelope decode error: direction={} error={error}",
-                                            pdu.direction
-                                        );
-                                    }
-                                }
-                                match pdu.att_pdu() {
-                                    Ok(Some(att)) => match describe_att_pdu(att) {
-                                        Ok(description) => println!(
-                                            "att_pdu direction={} {description}",
-                                            pdu.direction
-                                        ),
-                                        Err(error) => {
-                                            att_error_count += 1;
-                                            eprintln!(
-                                                "plaintext ATT PDU decode error: direction={} error={error}",
-                                                pdu.direction
-                                            );
-                                        }
-                                    },
-                                    Ok(None) => {}
-                                    Err(error) => {
-                                        att_error_count += 1;
-                                        eprintln!(
-                                            "plaintext ATT PDU envelope decode error: direction={} error={error}",
-                                            pdu.direction
-                                        );
-                                    }
-                                }
-                                match pdu.smp_pdu() {
-                                    Ok(Some(smp)) => match describe_smp_pdu(smp) {
-                                        Ok(description) => println!(
-                                            "smp_pdu direction={} {description}",
-                                            pdu.direction
-                                        ),
-                                        Err(error) => {
-                                            smp_error_count += 1;
-                                            eprintln!(
-                                                "plaintext SMP PDU decode error: direction={} error={error}",
-                                                pdu.direction
-                                            );
-                                        }
-                                    },
-                                    Ok(None) => {}
-                                    Err(error) => {
-                                        smp_error_count += 1;
-                                        eprintln!(
-                                            "plaintext SMP PDU envelope decode error: direction={} error={error}",
-                                            pdu.direction
-                                        );
-                                    }
-                                }
-                            }
+                if let Some(tracker) = &mut central_observation_tracker {
+                    match tracker.observe_central(captured.observation.access_address_sample) {
+                        Ok(observation) => {
+                            pcap_options.event_counter = Some(observation.event.event_counter);
+                            central_observation_matches =
+                                central_observation_matches.checked_add(1).ok_or_else(|| {
+                                    Error::InvalidState(
+                                        "central observation match count overflow".to_owned(),
+                                    )
+                                })?;
+                            print_live_central_observation(
+                                captured.observation.access_address_sample,
+                                observation,
+                                args.output_format,
+                            );
+                        }
+                        Err(error) => {
+                            central_observation_errors =
+                                central_observation_errors.checked_add(1).ok_or_else(|| {
+                                    Error::InvalidState(
+                                        "central observation error count overflow".to_owned(),
+                                    )
+                                })?;
+                            eprintln!(
+                                "central connection observation rejected: channel={} sample={} error={error}",
+                                data.channel.index(),
+                                captured.observation.access_address_sample
+                            );
                         }
-                    }
-                    Err(error) => {
-                        l2cap_error_count += 1;
-                        eprintln!(
-                            "plaintext L2CAP reassembly error: direction={} error={error}",
-                            direction
-                        );
                     }
                 }
-            }
-            if let Some(writer) = &mut pcap {
-                let timestamp = sample_timestamp_ns(
-                    args.capture_start_ns,
-                    packet.access_address_sample,
-                    args.sample_rate_hz,
-                )?;
-                writer.write_le(packet, timestamp)?;
-            }
-        }
-        packet_count += batch.packets.len();
-    }
-
-    if let Some((direction, reassembler)) = &mut l2cap_reassembler
-        && let Some(incomplete) = reassembler.reset(*direction)
-    {
-        l2cap_discarded_count += 1;
-        eprintln!(
-            "discarded incomplete plaintext L2CAP PDU at end of input: {}",
-            describe_incomplete_l2cap(incomplete)
-        );
-    }
-
-    if let Some(writer) = pcap {
-        writer.into_inner().flush()?;
-    }
-    eprintln!(
-        "decoded {packet_count} CRC-valid data-channel packet(s) from {sample_count} sample(s); ll_control_errors={ll_control_error_count}"
-    );
-    if decryptor.is_some() {
-        eprintln!(
-            "authenticated {decrypted_packet_count} new encrypted packet(s); retransmissions={decryption_retransmission_count} unencrypted_empty={unencrypted_empty_count} skipped_counters={skipped_packet_counter_count} errors={decryption_error_count}"
-        );
-    }
-    if l2cap_reassembler.is_some() {
+                if let Some(pcap) = lock_pcap(pcap).as_mut() {
+                    let timestamp = sample_timestamp_ns(
+                        capture_start_ns,
+                        captured.relative_sample_index,
+                        args.sample_rate_hz,
+                    )?;
+                    pcap.packet_writer(timestamp)?.write_pdu_with_options(
+                        &captured.observation.pdu,
+                        captured.observation.phy,
+                        None,
+                        timestamp,
+                        Some(&pcap_options),
+                    )?;
+                }
+                Ok(())
+            },
+            &mut report_captured_crc_failure,
+        )?,
+    };
+    if args.central_observation_tracking.is_some() {
         eprintln!(
-            "reassembled {l2cap_pdu_count} plaintext L2CAP PDU(s); duplicates={l2cap_duplicate_count} orphan_continuations={l2cap_orphan_count} discarded_incomplete={l2cap_discarded_count} errors={l2cap_error_count} signaling_errors={l2cap_signaling_error_count} att_errors={att_error_count} smp_errors={smp_error_count}"
+            "central connection observations: matched={central_observation_matches} rejected={central_observation_errors}"
         );
     }
-    Ok(())
+    Ok(stats)
 }
 
-fn encryption_trace(args: EncryptionTraceArgs) -> Result<()> {
-    let mut tracker =
-        LeEncryptionSessionTracker::new(args.long_term_key, args.maximum_counter_skip)?;
-    let mut accepted = 0usize;
-    let mut errors = 0usize;
-    for (index, directed) in args.packets.iter().enumerate() {
-        println!(
-            "raw_encryption_packet index={index} direction={} header={} payload={}",
-            directed.direction,
-            print_hex(&directed.packet.header),
-            print_hex(&directed.packet.payload)
-        );
-        match tracker.observe(directed.direction, &directed.packet) {
-            Ok(observation) => {
-                accepted += 1;
-                let (protection, packet_counter, skipped_counters) = match observation.decryption {
-                    None => ("plaintext", "none".to_owned(), 0),
-                    Some(LeAclDecryptionStatus::New {
-                        packet_counter,
-                        skipped_counters,
-                    }) => (
-                        "encrypted-new",
-                        packet_counter.to_string(),
-                        skipped_counters,
-                    ),
-                    Some(LeAclDecryptionStatus::Retransmission { packet_counter }) => {
-                        ("encrypted-retransmission", packet_counter.to_string(), 0)
-                    }
-                    Some(LeAclDecryptionStatus::UnencryptedEmpty) => {
-                        ("unencrypted-empty", "none".to_owned(), 0)
-                    }
-                };
-                let control = observation
-                    .packet
-                    .control()
-                    .ok()
-                    .flatten()
-                    .map(ControlPdu::opcode_name)
-                    .unwrap_or("none");
-                println!(
-                    "encryption_observation index={index} direction={} protection={protection} packet_counter={packet_counter} skipped_counters={skipped_counters} state_before={} state_after={} header={} payload={} control={control}",
-                    directed.direction,
-                    observation.state_before,
-                    observation.state_after,
-                    print_hex(&observation.packet.header),
-                    print_hex(&observation.packet.payload),
-                );
-            }
-            Err(error) => {
-                errors += 1;
-                eprintln!(
-                    "encryption observation error: index={index} direction={} state={} error={error}",
-                    directed.direction,
-                    tracker.state()
-                );
-            }
+fn synthesize_command(args: SynthesizeArgs) -> Result<()> {
+    let packets = args
+        .packets
+        .iter()
+        .map(|packet| {
+            Ok(SynthesizedPacket {
+                pdu: LePdu::from_pdu_bytes(args.channel, args.frame_config, &packet.pdu)?,
+                phy: packet.phy,
+                start_sample: packet.start_sample,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let total_samples = match args.total_samples {
+        Some(total_samples) => total_samples,
+        None => {
+            let mut last = 0u64;
+            for packet in &packets {
+                last = last
+                    .max(packet.end_sample(args.sample_rate_hz, args.impairments.clock_drift_ppm)?);
+            }
+            // Trailing noise lets the demodulator settle after the final packet.
+            let tail = u64::from(args.sample_rate_hz / 10_000).max(64);
+            usize::try_from(last + tail).map_err(|_| {
+                Error::InvalidConfiguration("synthesized output is too long".to_owned())
+            })?
         }
-    }
-    eprintln!(
-        "processed {} directed encryption packet(s); accepted={accepted} errors={errors} final_state={}",
-        args.packets.len(),
-        tracker.state()
-    );
-    Ok(())
-}
+    };
+    let samples = synthesize(
+        &SynthesisConfig {
+            sample_rate_hz: args.sample_rate_hz,
+            total_samples,
+            shape: args.shape,
+            impairments: args.impairments.clone(),
+        },
+        &packets,
+    )?;
 
-fn describe_credit_channel(channel: &L2capCreditBasedChannel) -> String {
-    format!(
-        "mode={} spsm=0x{:04x} eatt={} status={} central_cid=0x{:04x} central_mtu={} central_mps={} central_credits={} peripheral_cid=0x{:04x} peripheral_mtu={} peripheral_mps={} peripheral_credits={}",
-        channel.mode,
-        channel.spsm,
-        channel.is_eatt(),
-        channel.status,
-        channel.central.channel_id,
-        channel.central.mtu,
-        channel.central.mps,
-        channel.central.credits,
-        channel.peripheral.channel_id,
-        channel.peripheral.mtu,
-        channel.peripheral.mps,
-        channel.peripheral.credits
-    )
-}
+    let data_path = if is_sigmf_path(&args.output) {
+        let (meta_path, data_path) = sigmf_paths(&args.output);
+        std::fs::write(
+            &meta_path,
+            sigmf_metadata_text(
+                args.format,
+                args.sample_rate_hz,
+                args.channel.center_frequency_hz(),
+            ),
+        )?;
+        data_path
+    } else {
+        args.output.clone()
+    };
+    let mut writer = BufWriter::new(File::create(&data_path)?);
+    write_iq_samples(&mut writer, &samples, args.format)?;
+    writer.flush()?;
 
-fn print_credit_sdu(index: usize, sdu: &L2capCreditBasedSdu) -> Result<()> {
-    println!(
-        "l2cap_credit_sdu index={index} direction={} cid=0x{:04x} spsm=0x{:04x} eatt={} segments={} payload={}",
-        sdu.direction,
-        sdu.channel_id,
-        sdu.spsm,
-        sdu.is_eatt(),
-        sdu.segment_count,
-        print_hex(&sdu.payload)
-    );
-    if let Some(att) = sdu.att_pdu()? {
+    for (index, packet) in packets.iter().enumerate() {
         println!(
-            "eatt_pdu index={index} direction={} cid=0x{:04x} {}",
-            sdu.direction,
-            sdu.channel_id,
-            describe_att_pdu(att)?
-        );
-    }
-    Ok(())
-}
-
-fn print_l2cap_credit_event(index: usize, event: L2capCreditBasedEvent) -> Result<()> {
-    match event {
-        L2capCreditBasedEvent::Ignored => {
-            println!("l2cap_credit_event index={index} kind=ignored");
-        }
-        L2capCreditBasedEvent::ConnectionRequestPending {
-            mode,
-            identifier,
-            spsm,
-            channel_count,
-        } => println!(
-            "l2cap_credit_event index={index} kind=connection-request-pending mode={mode} identifier={identifier} spsm=0x{spsm:04x} channels={channel_count}"
-        ),
-        L2capCreditBasedEvent::ConnectionRejected {
-            mode,
-            identifier,
-            result,
-        } => println!(
-            "l2cap_credit_event index={index} kind=connection-rejected mode={mode} identifier={identifier} result=0x{result:04x}"
-        ),
-        L2capCreditBasedEvent::ChannelsOpened(channels) => {
-            for channel in channels {
-                println!(
-                    "l2cap_credit_event index={index} kind=channel-opened {}",
-                    describe_credit_channel(&channel)
-                );
-            }
-        }
-        L2capCreditBasedEvent::CreditsAdded {
-            owner,
-            channel_id,
-            added,
-            total,
-        } => println!(
-            "l2cap_credit_event index={index} kind=credits-added owner={} cid=0x{channel_id:04x} added={added} total={total}",
-            match owner {
-                LinkDirection::CentralToPeripheral => "central",
-                LinkDirection::PeripheralToCentral => "peripheral",
-            }
-        ),
-        L2capCreditBasedEvent::SduInProgress(IncompleteL2capCreditBasedSdu {
-            direction,
-            channel_id,
-            spsm,
-            expected_octets,
-            received_octets,
-            segment_count,
-        }) => println!(
-            "l2cap_credit_event index={index} kind=sdu-in-progress direction={direction} cid=0x{channel_id:04x} spsm=0x{spsm:04x} received={received_octets} expected={expected_octets} segments={segment_count}"
-        ),
-        L2capCreditBasedEvent::SduComplete(sdu) => print_credit_sdu(index, &sdu)?,
-        L2capCreditBasedEvent::ReconfigurePending {
-            identifier,
-            owner,
-            channel_ids,
-            mtu,
-            mps,
-        } => println!(
-            "l2cap_credit_event index={index} kind=reconfigure-pending identifier={identifier} owner={} cids={} mtu={mtu} mps={mps}",
-            match owner {
-                LinkDirection::CentralToPeripheral => "central",
-                LinkDirection::PeripheralToCentral => "peripheral",
-            },
-            print_l2cap_channel_ids(&channel_ids)
-        ),
-        L2capCreditBasedEvent::ReconfigureRejected { identifier, result } => println!(
-            "l2cap_credit_event index={index} kind=reconfigure-rejected identifier={identifier} result=0x{result:04x}"
-        ),
-        L2capCreditBasedEvent::Reconfigured {
-            owner,
-            channel_ids,
-            mtu,
-            mps,
-        } => println!(
-            "l2cap_credit_event index={index} kind=reconfigured owner={} cids={} mtu={mtu} mps={mps}",
-            match owner {
-                LinkDirection::CentralToPeripheral => "central",
-                LinkDirection::PeripheralToCentral => "peripheral",
-            },
-            print_l2cap_channel_ids(&channel_ids)
-        ),
-        L2capCreditBasedEvent::DisconnectPending {
-            identifier,
-            central_channel_id,
-            peripheral_channel_id,
-        } => println!(
-            "l2cap_credit_event index={index} kind=disconnect-pending identifier={identifier} central_cid=0x{central_channel_id:04x} peripheral_cid=0x{peripheral_channel_id:04x}"
-        ),
-        L2capCreditBasedEvent::Disconnected(channel) => println!(
-            "l2cap_credit_event index={index} kind=disconnected {}",
-            describe_credit_channel(&channel)
-        ),
-        L2capCreditBasedEvent::CommandRejected {
-            identifier,
-            removed_pending_procedure,
-        } => println!(
-            "l2cap_credit_event index={index} kind=command-rejected identifier={identifier} removed_pending={removed_pending_procedure}"
-        ),
+            "packet {index}: phy={} channel={} start_sample={} access_address_sample={} pdu={} crc={}",
+            packet.phy,
+            packet.pdu.channel.index(),
+            packet.start_sample,
+            packet.access_address_sample(args.sample_rate_hz)?,
+            print_hex(&args.packets[index].pdu),
+            print_hex(&packet.pdu.crc)
+        );
     }
+    eprintln!(
+        "wrote {total_samples} {} samples at {} Hz to {}",
+        args.format.name(),
+        args.sample_rate_hz,
+        data_path.display()
+    );
     Ok(())
 }
 
-fn l2cap_trace(args: L2capTraceArgs) -> Result<()> {
-    let mut tracker = L2capCreditBasedChannelTracker::default();
-    let mut accepted = 0usize;
-    let mut errors = 0usize;
-    for (index, pdu) in args.pdus.iter().enumerate() {
-        println!(
-            "raw_l2cap_pdu index={index} direction={} cid=0x{:04x} payload={}",
-            pdu.direction,
-            pdu.channel_id,
-            print_hex(&pdu.payload)
-        );
-        if pdu.channel_id == blueoxide::link_layer::LE_SIGNALING_CHANNEL_ID {
-            match pdu.le_signaling_command() {
-                Ok(Some(command)) => match describe_l2cap_signaling(command) {
-                    Ok(description) => println!(
-                        "l2cap_signal index={index} direction={} {description}",
-                        pdu.direction
-                    ),
-                    Err(error) => eprintln!(
-                        "l2cap signaling decode error: index={index} direction={} error={error}",
-                        pdu.direction
-                    ),
-                },
-                Ok(None) => {}
-                Err(error) => eprintln!(
-                    "l2cap signaling envelope error: index={index} direction={} error={error}",
-                    pdu.direction
-                ),
-            }
-        }
-        match tracker.observe(pdu) {
-            Ok(event) => {
-                accepted += 1;
-                print_l2cap_credit_event(index, event)?;
-            }
-            Err(error) => {
-                errors += 1;
-                eprintln!(
-                    "l2cap credit observation error: index={index} direction={} cid=0x{:04x} error={error}",
-                    pdu.direction, pdu.channel_id
+fn scenario_command(args: ScenarioArgs) -> Result<()> {
+    let sample_rate_hz = args.scenario.sample_rate_hz;
+    let packets = args.scenario.generate()?;
+
+    let mut pcap = match &args.output_pcap {
+        Some(path) => Some(create_pcapng(
+            path,
+            "synthetic",
+            format!(
+                "command=scenario sample_rate_hz={sample_rate_hz} access_address=0x{:08x} crc_init=0x{:06x} interval={} events={}",
+                args.scenario.connect_request.access_address,
+                args.scenario.connect_request.crc_init,
+                args.scenario.connect_request.interval,
+                args.scenario.event_count
+            ),
+            &[],
+        )?),
+        None => None,
+    };
+    // Interfaces are declared per channel as the connection first uses them.
+    let mut pcap_interfaces = [None; 40];
+    for (index, packet) in packets.iter().enumerate() {
+        let pdu = &packet.transmission.pdu;
+        match &packet.link {
+            None => println!(
+                "packet {index}: advertising channel={} access_address_sample={} header={} payload={}",
+                pdu.channel.index(),
+                packet.access_address_sample,
+                print_hex(&pdu.header),
+                print_hex(&pdu.payload)
+            ),
+            Some(link) => {
+                let counter = link
+                    .packet_counter
+                    .map(|counter| format!(" packet_counter={counter}"))
+                    .unwrap_or_default();
+                println!(
+                    "packet {index}: event={} {} channel={} access_address_sample={} llid={} header={} payload={}{counter}",
+                    link.event_counter,
+                    link.direction,
+                    pdu.channel.index(),
+                    packet.access_address_sample,
+                    link.plaintext.llid(),
+                    print_hex(&link.plaintext.header),
+                    print_hex(&link.plaintext.payload)
                 );
             }
         }
+        if let Some(writer) = &mut pcap {
+            let timestamp = sample_timestamp_ns(
+                args.capture_start_ns,
+                packet.access_address_sample,
+                sample_rate_hz,
+            )?;
+            let interface_id = match pcap_interfaces[usize::from(pdu.channel.index())] {
+                Some(interface_id) => interface_id,
+                None => {
+                    let interface_id = writer.add_interface(&pcapng_channel_interface(
+                        pdu.channel,
+                        sample_rate_hz,
+                        "synthetic",
+                    ))?;
+                    pcap_interfaces[usize::from(pdu.channel.index())] = Some(interface_id);
+                    interface_id
+                }
+            };
+            let options = PcapNgPacketOptions {
+                interface_id,
+                sample_index: Some(packet.access_address_sample),
+                event_counter: packet.link.as_ref().map(|link| link.event_counter),
+                decryption: packet
+                    .link
+                    .as_ref()
+                    .and_then(|link| link.packet_counter)
+                    .map(|packet_counter| LeAclDecryptionStatus::New {
+                        packet_counter,
+                        skipped_counters: 0,
+                    }),
+                ..PcapNgPacketOptions::default()
+            };
+            writer.write_pdu_with_options(
+                pdu,
+                LeUncodedPhy::Le1M,
+                packet.link.as_ref().map(|link| link.direction),
+                timestamp,
+                Some(&options),
+            )?;
+        }
+    }
+    if let Some(writer) = pcap {
+        writer.into_inner().flush()?;
+    }
+
+    if let (Some(output), Some(channel)) = (&args.output, args.iq_channel) {
+        let mut last = 0u64;
+        for packet in &packets {
+            last = last.max(packet.transmission.end_sample(sample_rate_hz, 0.0)?);
+        }
+        let tail = u64::from(sample_rate_hz / 10_000).max(64);
+        let total_samples = usize::try_from(last + tail).map_err(|_| {
+            Error::InvalidConfiguration("synthesized output is too long".to_owned())
+        })?;
+        // A fixed receiver hears only the transmissions on its own channel.
+        let heard: Vec<SynthesizedPacket> = packets
+            .iter()
+            .filter(|packet| packet.transmission.pdu.channel == channel)
+            .map(|packet| packet.transmission.clone())
+            .collect();
+        let samples = synthesize(
+            &SynthesisConfig {
+                sample_rate_hz,
+                total_samples,
+                shape: GfskShape::default(),
+                impairments: args.impairments.clone(),
+            },
+            &heard,
+        )?;
+        let data_path = if is_sigmf_path(output) {
+            let (meta_path, data_path) = sigmf_paths(output);
+            std::fs::write(
+                &meta_path,
+                sigmf_metadata_text(args.format, sample_rate_hz, channel.center_frequency_hz()),
+            )?;
+            data_path
+        } else {
+            output.clone()
+        };
+        let mut writer = BufWriter::new(File::create(&data_path)?);
+        write_iq_samples(&mut writer, &samples, args.format)?;
+        writer.flush()?;
+        eprintln!(
+            "wrote {total_samples} {} samples with {} of {} packets on channel {} to {}",
+            args.format.name(),
+            heard.len(),
+            packets.len(),
+            channel.index(),
+            data_path.display()
+        );
     }
-    eprintln!(
-        "processed {} directed L2CAP PDU(s); accepted={accepted} errors={errors} open_channels={}",
-        args.pdus.len(),
-        tracker.channels().count()
-    );
     Ok(())
 }
 
-fn current_unix_time_ns() -> Result<u64> {
-    let duration = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .map_err(|error| {
-            Error::InvalidInput(format!("system clock precedes Unix epoch: {error}"))
-        })?;
-    u64::try_from(duration.as_nanos())
-        .map_err(|_| Error::InvalidInput("current Unix timestamp exceeds u64".to_owned()))
+#[derive(Default)]
+struct AnalyzeCounters {
+    packets: usize,
+    decode_errors: usize,
 }
 
-fn capture(args: CaptureArgs) -> Result<()> {
-    let device = args.device.to_ascii_lowercase();
-    if !matches!(device.as_str(), "bladerf" | "limesdr" | "lime" | "xtrx") {
-        return Err(Error::InvalidConfiguration(format!(
-            "{} device {:?} is not implemented; currently available: bladerf, limesdr, xtrx",
-            args.frame.command_name(),
-            args.device
-        )));
+fn describe_event(event: Option<AnalyzedEvent>) -> String {
+    match event {
+        Some(event) => format!(
+            "event={} anchor={} timing={}",
+            event.event_counter,
+            event.anchor,
+            event
+                .timing_error
+                .map(describe_sample_timing)
+                .unwrap_or_else(|| "none".to_owned())
+        ),
+        None => "event=none anchor=false timing=none".to_owned(),
     }
-    let radio_config = SdrConfig {
-        center_frequency_hz: args.channel.center_frequency_hz(),
-        sample_rate_hz: args.sample_rate_hz,
-        bandwidth_hz: args.bandwidth_hz,
-        gain_db: args.gain_db,
-        channel: args.rx_channel,
-    };
+}
 
-    let stats = match device.as_str() {
-        "bladerf" => {
-            let mut source =
-                BladeRfSource::open(args.identifier.as_deref(), BladeRfOptions::default())?;
-            let stats = capture_from_source(&mut source, &args, &radio_config)?;
-            if let Some(applied) = source.applied_config() {
-                eprintln!(
-                    "bladeRF applied sample_rate={} bandwidth={}",
-                    applied.sample_rate_hz, applied.bandwidth_hz
+fn print_analyzed_l2cap(index: usize, pdu: &L2capPdu, counters: &mut AnalyzeCounters) {
+    println!(
+        "l2cap_pdu index={index} direction={} cid=0x{:04x} length={} fragments={} payload={}",
+        pdu.direction,
+        pdu.channel_id,
+        pdu.payload.len(),
+        pdu.fragment_count,
+        print_hex(&pdu.payload)
+    );
+    let signaling = pdu
+        .le_signaling_command()
+        .and_then(|command| command.map(describe_l2cap_signaling).transpose());
+    let att = pdu
+        .att_pdu()
+        .and_then(|att| att.map(describe_att_pdu).transpose());
+    let smp = pdu
+        .smp_pdu()
+        .and_then(|smp| smp.map(describe_smp_pdu).transpose());
+    for (layer, description) in [
+        ("l2cap_signal", signaling),
+        ("att_pdu", att),
+        ("smp_pdu", smp),
+    ] {
+        match description {
+            Ok(Some(description)) => {
+                println!(
+                    "{layer} index={index} direction={} {description}",
+                    pdu.direction
                 );
             }
-            stats
-        }
-        "limesdr" | "lime" => {
-            let mut source =
-                LimeSdrSource::open(args.identifier.as_deref(), LimeSdrOptions::default())?;
-            let stats = capture_from_source(&mut source, &args, &radio_config)?;
-            if let Some(applied) = source.applied_config() {
+            Ok(None) => {}
+            Err(error) => {
+                counters.decode_errors += 1;
                 eprintln!(
-                    "LimeSDR applied sample_rate={} bandwidth={}",
-                    applied.sample_rate_hz, applied.bandwidth_hz
+                    "{layer} decode error: index={index} direction={} error={error}",
+                    pdu.direction
                 );
             }
-            stats
         }
-        "xtrx" => {
-            let mut source = XtrxSource::open(args.identifier.as_deref(), XtrxOptions::default())?;
-            let stats = capture_from_source(&mut source, &args, &radio_config)?;
-            if let Some(applied) = source.applied_config() {
-                eprintln!(
-                    "XTRX applied sample_rate={} bandwidth={}",
-                    applied.sample_rate_hz, applied.bandwidth_hz
+    }
+}
+
+fn print_analyzed_packet(
+    index: usize,
+    (time_label, time): (&str, u64),
+    packet: &AnalyzerPacket,
+    analyzed: AnalyzedPacket,
+    counters: &mut AnalyzeCounters,
+) -> Result<()> {
+    let pdu = &packet.pdu;
+    let (direction, source) = match analyzed.direction {
+        Some(direction) => (
+            direction.direction.to_string(),
+            match direction.source {
+                DirectionSource::Capture => "capture",
+                DirectionSource::Timing => "timing",
+            },
+        ),
+        None => ("none".to_owned(), "none"),
+    };
+    println!(
+        "packet index={index} {time_label}={time} channel={} phy={} access_address={:08x} connection={} direction={direction} direction_source={source} {} header={} payload={}",
+        if packet.channel_known {
+            pdu.channel.index().to_string()
+        } else {
+            "unknown".to_owned()
+        },
+        packet.phy,
+        pdu.access_address,
+        analyzed
+            .connection
+            .map(|connection| connection.to_string())
+            .unwrap_or_else(|| "none".to_owned()),
+        describe_event(analyzed.event),
+        print_hex(&pdu.header),
+        print_hex(&pdu.payload),
+    );
+    for layer in analyzed.layers {
+        match layer {
+            AnalysisLayer::Advertising(advertising) => {
+                let semantic = decode_advertising_pdu(&advertising)
+                    .map(|decoded| decoded.to_string())
+                    .unwrap_or_else(|error| format!("decode_error={error}"));
+                println!(
+                    "advertising index={index} pdu_type={} semantic=\"{}\"",
+                    advertising.pdu_type(),
+                    semantic.replace('"', "'")
+                );
+            }
+            AnalysisLayer::ConnectionRequest {
+                connection,
+                initiator,
+                initiator_kind,
+                advertiser,
+                advertiser_kind,
+                request,
+            } => println!(
+                "connection_request index={index} connection={connection} initiator={initiator}/{initiator_kind} advertiser={advertiser}/{advertiser_kind} access_address={:08x} crc_init=0x{:06x} window_size={} window_offset={} interval={} latency={} timeout={} channel_map={} hop={} sca={} csa={}",
+                request.access_address,
+                request.crc_init,
+                request.window_size,
+                request.window_offset,
+                request.interval,
+                request.latency,
+                request.supervision_timeout,
+                print_hex(&request.channel_map),
+                request.hop_increment,
+                request.sleep_clock_accuracy,
+                request.channel_selection_algorithm,
+            ),
+            AnalysisLayer::DataChannel {
+                plaintext,
+                decryption,
+                retransmission,
+            } => {
+                let description = describe_data_pdu(&plaintext).unwrap_or_else(|error| {
+                    counters.decode_errors += 1;
+                    format!("decode_error={error}")
+                });
+                let (status, packet_counter) = match decryption {
+                    None => ("plaintext", "none".to_owned()),
+                    Some(LeAclDecryptionStatus::New { packet_counter, .. }) => {
+                        ("new", packet_counter.to_string())
+                    }
+                    Some(LeAclDecryptionStatus::Retransmission { packet_counter }) => {
+                        ("retransmission", packet_counter.to_string())
+                    }
+                    Some(LeAclDecryptionStatus::UnencryptedEmpty) => {
+                        ("unencrypted-empty", "none".to_owned())
+                    }
+                };
+                println!(
+                    "ll_pdu index={index} status={status} packet_counter={packet_counter} retransmission={retransmission} llid={} nesn={} sn={} md={} header={} payload={} description=\"{}\"",
+                    plaintext.llid(),
+                    plaintext.next_expected_sequence_number(),
+                    plaintext.sequence_number(),
+                    plaintext.more_data(),
+                    print_hex(&plaintext.header),
+                    print_hex(&plaintext.payload),
+                    description.replace('"', "'"),
+                );
+            }
+            AnalysisLayer::Ciphertext => println!(
+                "ll_ciphertext index={index} payload_octets={}",
+                pdu.payload.len()
+            ),
+            AnalysisLayer::L2cap(l2cap) => print_analyzed_l2cap(index, &l2cap, counters),
+            AnalysisLayer::L2capCredit(event) => {
+                print_l2cap_credit_event(index, event, OutputFormat::Text)?;
+            }
+            AnalysisLayer::LegacyPairing(event) => print_legacy_pairing_event(index, event),
+            AnalysisLayer::SecureConnectionsPairing(event) => {
+                print_secure_connections_pairing_event(index, event);
+            }
+            AnalysisLayer::PairingSession(event) => print_pairing_session_event(index, event),
+            AnalysisLayer::AttSignature {
+                device,
+                sign_counter,
+                status,
+            } => {
+                let replayed = match status {
+                    AttSignatureStatus::Replayed { last_sign_counter } => {
+                        format!(" last_sign_counter={last_sign_counter}")
+                    }
+                    _ => String::new(),
+                };
+                println!(
+                    "att_signature index={index} device={} sign_counter={sign_counter} status={status}{replayed}",
+                    describe_optional_address(device)
+                );
+            }
+            AnalysisLayer::DerivedLinkKey(entry) => println!(
+                "derived_link_key index={index} address={} key={}",
+                entry.address,
+                print_hex(&entry.key)
+            ),
+            AnalysisLayer::EncryptedData { structure, key } => {
+                let status = match structure.status {
+                    EncryptedDataStatus::Decrypted { structures, .. } => format!(
+                        "decrypted structures={}",
+                        structures
+                            .iter()
+                            .map(|structure| format!(
+                                "0x{:02x}:{}",
+                                structure.ad_type,
+                                print_hex(&structure.data)
+                            ))
+                            .collect::<Vec<_>>()
+                            .join(",")
+                    ),
+                    EncryptedDataStatus::MalformedPlaintext { plaintext, .. } => {
+                        format!("malformed-plaintext plaintext={}", print_hex(&plaintext))
+                    }
+                    EncryptedDataStatus::Undecrypted => "undecrypted".to_owned(),
+                    EncryptedDataStatus::Truncated { length } => {
+                        format!("truncated length={length}")
+                    }
+                };
+                let key = key.map_or_else(String::new, |key| {
+                    format!(" key_address={}", describe_optional_address(key.address))
+                });
+                println!(
+                    "encrypted_data index={index} depth={}{key} status={status}",
+                    structure.depth
+                );
+            }
+            AnalysisLayer::EncryptedDataKey(entry) => println!(
+                "encrypted_data_key index={index} address={} key={} iv={}",
+                describe_optional_address(entry.address),
+                print_hex(&entry.key_material.session_key),
+                print_hex(&entry.key_material.initialization_vector)
+            ),
+            AnalysisLayer::ResolvedAddress(resolved) => println!(
+                "resolved_address index={index} role={} address={} identity={}",
+                resolved.role,
+                describe_optional_address(Some(resolved.address)),
+                describe_optional_address(Some(resolved.identity))
+            ),
+            AnalysisLayer::Note(message) => {
+                println!(
+                    "note index={index} message=\"{}\"",
+                    message.replace('"', "'")
                 );
             }
-            stats
         }
-        _ => unreachable!(),
-    };
-    eprintln!(
-        "capture complete: samples={} packets={} overruns={} dropped={} discontinuities={}",
-        stats.samples_received,
-        stats.packets_decoded,
-        stats.overruns,
-        stats.dropped_samples,
-        stats.discontinuities
-    );
+    }
     Ok(())
 }
 
-fn capture_from_source<S: IqSource>(
-    source: &mut S,
-    args: &CaptureArgs,
-    radio_config: &SdrConfig,
-) -> Result<CaptureStats> {
-    let capture_start_ns = args
-        .capture_start_ns
-        .map(Ok)
-        .unwrap_or_else(current_unix_time_ns)?;
-    let mut pcap = match &args.output_pcap {
-        Some(path) => Some(PcapNgWriter::new(BufWriter::new(File::create(path)?))?),
-        None => None,
+fn print_legacy_pairing_event(index: usize, event: LegacyPairingEvent) {
+    match event {
+        LegacyPairingEvent::KeysRecovered(keys) => println!(
+            "legacy_pairing index={index} status=recovered model={} passkey={:06} tk={} stk={}",
+            keys.association_model,
+            keys.passkey,
+            print_hex(&keys.temporary_key),
+            print_hex(&keys.short_term_key)
+        ),
+        LegacyPairingEvent::NotRecovered(reason) => {
+            println!("legacy_pairing index={index} status=not-recovered reason={reason}");
+        }
+        LegacyPairingEvent::LongTermKeyDistributed { direction, key } => println!(
+            "distributed_ltk index={index} direction={direction} address={} ediv={} rand={} key={}",
+            describe_optional_address(key.address),
+            key.encrypted_diversifier,
+            print_hex(&key.random_number),
+            print_hex(&key.key)
+        ),
+    }
+}
+
+fn print_secure_connections_pairing_event(index: usize, event: SecureConnectionsPairingEvent) {
+    let optional = |value: Option<u32>, width: usize| {
+        value
+            .map(|value| format!("{value:0width$}"))
+            .unwrap_or_else(|| "none".to_owned())
     };
-    let mut central_observation_tracker = args
-        .central_observation_tracking
-        .clone()
-        .map(|config| FixedChannelCentralObservationTracker::new(args.channel, config))
-        .transpose()?;
-    let mut central_observation_matches = 0u64;
-    let mut central_observation_errors = 0u64;
+    match event {
+        SecureConnectionsPairingEvent::DebugKeyDetected(direction) => println!(
+            "secure_connections_pairing index={index} status=debug-key direction={direction}"
+        ),
+        SecureConnectionsPairingEvent::KeysDerived(keys) => println!(
+            "secure_connections_pairing index={index} status=derived model={} dhkey_source={} verified={} passkey={} comparison={} dhkey={} mac_key={} ltk={} link_key={}",
+            keys.association_model,
+            keys.diffie_hellman_key_source,
+            keys.verified,
+            optional(keys.passkey, 6),
+            optional(keys.comparison_value, 6),
+            print_hex(&keys.diffie_hellman_key),
+            print_hex(&keys.mac_key),
+            print_hex(&keys.long_term_key),
+            keys.link_key
+                .map(|key| print_hex(&key))
+                .unwrap_or_else(|| "none".to_owned())
+        ),
+        SecureConnectionsPairingEvent::NotDerived(reason) => {
+            println!("secure_connections_pairing index={index} status=not-derived reason={reason}")
+        }
+    }
+}
 
-    let limits = CaptureLimits {
-        maximum_samples: None,
-        maximum_duration: Some(args.duration),
-        read_timeout: Duration::from_millis(args.read_timeout_ms),
-        block_samples: args.block_samples,
+fn print_pairing_session_event(index: usize, event: PairingSessionEvent) {
+    let (status, summary) = match event {
+        PairingSessionEvent::Completed(summary) => ("complete", summary),
+        PairingSessionEvent::Failed(summary) => ("failed", summary),
     };
-    let stats = match args.frame {
-        CaptureFrame::Advertising => capture_primary_advertising(
-            source,
-            radio_config,
-            args.channel,
-            Le1mDemodConfig {
-                sample_rate_hz: args.sample_rate_hz,
-                max_access_address_errors: args.max_access_address_errors,
-            },
-            limits,
-            |captured: &CapturedAdvertisingPdu| {
-                print_packet(&captured.observation);
-                if let Some(writer) = &mut pcap {
-                    let timestamp = sample_timestamp_ns(
-                        capture_start_ns,
-                        captured.relative_sample_index,
-                        args.sample_rate_hz,
+    let weaknesses = if summary.weaknesses.is_empty() {
+        "none".to_owned()
+    } else {
+        summary
+            .weaknesses
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+    println!(
+        "pairing_session index={index} status={status} method={} key_size={} initiator_distributed=0x{:02x} responder_distributed=0x{:02x} failure={} security_level={} weaknesses={weaknesses}",
+        summary
+            .method
+            .map(|method| method.to_string())
+            .unwrap_or_else(|| "unknown".to_owned()),
+        summary
+            .key_size
+            .map(|size| size.to_string())
+            .unwrap_or_else(|| "none".to_owned()),
+        summary.initiator_distributed.raw,
+        summary.responder_distributed.raw,
+        summary
+            .failure
+            .map(|(direction, failure)| format!("{direction}:{}", failure.reason_name()))
+            .unwrap_or_else(|| "none".to_owned()),
+        summary.security_level
+    );
+}
+
+fn describe_optional_address(address: Option<(DeviceAddress, AddressKind)>) -> String {
+    address
+        .map(|(address, kind)| format!("{address}/{kind}"))
+        .unwrap_or_else(|| "unknown".to_owned())
+}
+
+fn print_connection_summary(id: usize, summary: &ConnectionSummary, time_base_hz: u32) {
+    let optional = |value: Option<u64>| {
+        value
+            .map(|value| value.to_string())
+            .unwrap_or_else(|| "none".to_owned())
+    };
+    let (crc_init, interval, csa) = match &summary.request {
+        Some(request) => (
+            format!("0x{:06x}", request.crc_init),
+            request.interval.to_string(),
+            request.channel_selection_algorithm.to_string(),
+        ),
+        None => (
+            "unknown".to_owned(),
+            "unknown".to_owned(),
+            "unknown".to_owned(),
+        ),
+    };
+    let tracking = match summary.tracking {
+        ConnectionTracking::Untracked => "untracked",
+        ConnectionTracking::AwaitingFirstEvent => "awaiting-first-event",
+        ConnectionTracking::Tracked => "tracked",
+        ConnectionTracking::Lost => "lost",
+    };
+    let identity = |address: Option<(DeviceAddress, AddressKind)>| {
+        address
+            .map(|(address, kind)| format!("{address}/{kind}"))
+            .unwrap_or_else(|| "none".to_owned())
+    };
+    let encryption = match (summary.encryption_started, summary.key_index) {
+        (_, Some(key_index)) => format!("decrypted:ltk{key_index}"),
+        (_, None) if summary.keyring_key => "decrypted:keyring".to_owned(),
+        (_, None) if summary.short_term_key => "decrypted:stk".to_owned(),
+        (_, None) if summary.secure_connections_key => "decrypted:sc-ltk".to_owned(),
+        (true, None) => "undecrypted".to_owned(),
+        (false, None) => "none".to_owned(),
+    };
+    println!(
+        "connection id={id} access_address={:08x} crc_init={crc_init} advertiser={} initiator={} interval={interval} csa={csa} duration_us={} central_packets={} peripheral_packets={} first_event={} last_event={} tracking={tracking} encryption={encryption} terminated={} l2cap_pdus={} att_pdus={} smp_pdus={} credit_sdus={} errors={} advertiser_identity={} initiator_identity={}",
+        summary.access_address,
+        describe_optional_address(summary.advertiser),
+        describe_optional_address(summary.initiator),
+        optional(
+            summary
+                .first_time
+                .zip(summary.last_time)
+                .map(|(first, last)| {
+                    (u128::from(last - first) * 1_000_000 / u128::from(time_base_hz)) as u64
+                })
+        ),
+        summary.central_packets,
+        summary.peripheral_packets,
+        optional(summary.first_event.map(u64::from)),
+        optional(summary.last_event.map(u64::from)),
+        summary.terminated,
+        summary.l2cap_pdus,
+        summary.att_pdus,
+        summary.smp_pdus,
+        summary.credit_sdus,
+        summary.errors,
+        identity(summary.advertiser_identity),
+        identity(summary.initiator_identity),
+    );
+}
+
+fn analyze_packet(
+    analyzer: &mut ConnectionAnalyzer,
+    time: (&str, u64),
+    timestamp_ns: u64,
+    packet: AnalyzerPacket,
+    counters: &mut AnalyzeCounters,
+    btsnoop: Option<&mut BtSnoopWriter<BufWriter<File>>>,
+) -> Result<()> {
+    let analyzed = analyzer.analyze(&packet)?;
+    if let Some(btsnoop) = btsnoop {
+        write_analyzed_btsnoop(btsnoop, timestamp_ns, &analyzed)?;
+    }
+    print_analyzed_packet(counters.packets, time, &packet, analyzed, counters)?;
+    counters.packets += 1;
+    Ok(())
+}
+
+/// Writes the HCI traffic the chosen host would have seen for one analyzed
+/// packet. Connection handles are the analyzer's connection indices.
+fn write_analyzed_btsnoop(
+    btsnoop: &mut BtSnoopWriter<BufWriter<File>>,
+    timestamp_ns: u64,
+    analyzed: &AnalyzedPacket,
+) -> Result<()> {
+    let Some(connection) = analyzed.connection else {
+        return Ok(());
+    };
+    let handle = u16::try_from(connection).unwrap_or(u16::MAX);
+    for layer in &analyzed.layers {
+        match layer {
+            AnalysisLayer::ConnectionRequest {
+                initiator,
+                initiator_kind,
+                advertiser,
+                advertiser_kind,
+                request,
+                ..
+            } => btsnoop.write_le_connection_complete(
+                timestamp_ns,
+                handle,
+                (*initiator, *initiator_kind),
+                (*advertiser, *advertiser_kind),
+                request,
+            )?,
+            AnalysisLayer::DataChannel {
+                plaintext,
+                retransmission: false,
+                ..
+            } => {
+                if let Ok(Some(control)) = plaintext.control()
+                    && let Ok(DecodedControlPdu::TerminateInd(termination)) = control.decode()
+                {
+                    btsnoop.write_disconnection_complete(
+                        timestamp_ns,
+                        handle,
+                        termination.error_code,
                     )?;
-                    writer.write_advertising(&captured.observation, timestamp)?;
                 }
-                Ok(())
-            },
-        )?,
-        CaptureFrame::Data {
-            access_address,
-            crc_init,
-            phy,
-        } => capture_data_channel(
-            source,
-            radio_config,
-            args.channel,
-            LeFrameConfig::data(access_address, crc_init)?,
-            LeUncodedDemodConfig {
-                phy,
-                sample_rate_hz: args.sample_rate_hz,
-                max_access_address_errors: args.max_access_address_errors,
-            },
-            limits,
-            |captured: &CapturedDataChannelPdu| {
-                let data = DataChannelPdu::from(captured.observation.pdu.clone());
-                if let Err(error) = print_data_packet(&captured.observation, &data, true) {
-                    eprintln!(
-                        "live data-channel plaintext-hint decode error: channel={} sample={} error={error}",
-                        data.channel.index(),
-                        captured.observation.access_address_sample
-                    );
+            }
+            AnalysisLayer::L2cap(pdu) => {
+                btsnoop.write_l2cap(timestamp_ns, handle, pdu)?;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn analyze_command(args: AnalyzeArgs) -> Result<()> {
+    let mut config = args.config.clone();
+    config.keyring = args.key_sources.load()?;
+    let mut analyzer = ConnectionAnalyzer::new(config)?;
+    let mut counters = AnalyzeCounters::default();
+    let mut btsnoop = match &args.output_btsnoop {
+        Some(path) => Some(BtSnoopWriter::new(
+            BufWriter::new(File::create(path)?),
+            args.btsnoop_host,
+        )?),
+        None => None,
+    };
+    match &args.iq {
+        None => {
+            let mut reader = CaptureFileReader::new(BufReader::new(File::open(&args.input)?))?;
+            let mut crc_invalid = 0u64;
+            let mut decrypted_copies = 0u64;
+            while let Some(captured) = reader.next_packet()? {
+                if captured.crc == CaptureCrcStatus::Invalid {
+                    crc_invalid += 1;
+                    continue;
                 }
-                if let Some(tracker) = &mut central_observation_tracker {
-                    match tracker.observe_central(captured.observation.access_address_sample) {
-                        Ok(observation) => {
-                            central_observation_matches =
-                                central_observation_matches.checked_add(1).ok_or_else(|| {
-                                    Error::InvalidState(
-                                        "central observation match count overflow".to_owned(),
-                                    )
-                                })?;
-                            print_live_central_observation(
-                                captured.observation.access_address_sample,
-                                observation,
-                            );
-                        }
-                        Err(error) => {
-                            central_observation_errors =
-                                central_observation_errors.checked_add(1).ok_or_else(|| {
-                                    Error::InvalidState(
-                                        "central observation error count overflow".to_owned(),
-                                    )
-                                })?;
-                            eprintln!(
-                                "central connection observation rejected: channel={} sample={} error={error}",
-                                data.channel.index(),
-                                captured.observation.access_address_sample
-                            );
-                        }
-                    }
+                // Plaintext copies written by decode-data; the analyzer
+                // decrypts the over-the-air PDUs itself.
+                if captured.decrypted {
+                    decrypted_copies += 1;
+                    continue;
                 }
-                if let Some(writer) = &mut pcap {
-                    let timestamp = sample_timestamp_ns(
-                        capture_start_ns,
-                        captured.relative_sample_index,
-                        args.sample_rate_hz,
+                let timestamp_ns = captured.timestamp_ns;
+                let packet = AnalyzerPacket {
+                    pdu: captured.pdu,
+                    channel_known: captured.channel_known,
+                    phy: captured.phy.unwrap_or(LePhy::Le1M),
+                    time: (u128::from(captured.timestamp_ns)
+                        * u128::from(ANALYZE_CAPTURE_TIME_BASE_HZ)
+                        / 1_000_000_000) as u64,
+                    direction: captured.direction,
+                };
+                analyze_packet(
+                    &mut analyzer,
+                    ("timestamp_ns", timestamp_ns),
+                    timestamp_ns,
+                    packet,
+                    &mut counters,
+                    btsnoop.as_mut(),
+                )?;
+            }
+            eprintln!(
+                "analyzed {} packet(s); crc_invalid={crc_invalid} decrypted_copies={decrypted_copies} unsupported_link_type={} malformed={} decode_errors={}",
+                counters.packets,
+                reader.skipped_packets(),
+                reader.malformed_packets(),
+                counters.decode_errors
+            );
+        }
+        Some(iq) => {
+            let sample_rate_hz = args.config.time_base_hz;
+            let mut reader = open_decode_input(
+                &IqStreamLocation::Path(args.input.clone()),
+                iq.format,
+                IqStreamFraming::Raw,
+                sample_rate_hz,
+                iq.max_samples,
+            )?;
+            let demod_config = LeUncodedDemodConfig {
+                phy: iq.phy,
+                sample_rate_hz,
+                max_access_address_errors: iq.max_access_address_errors,
+            };
+            let mut decoder =
+                LeUncodedPacketStreamDecoder::new(iq.channel, iq.frame_config, demod_config)?;
+            loop {
+                let first_sample = reader.next_sample_index();
+                let samples = reader.read_block(iq.block_samples)?;
+                if samples.is_empty() {
+                    break;
+                }
+                let batch = decoder.push(first_sample, &samples)?;
+                for received in batch.packets {
+                    let packet = AnalyzerPacket {
+                        pdu: received.pdu,
+                        channel_known: true,
+                        phy: match received.phy {
+                            LeUncodedPhy::Le1M => LePhy::Le1M,
+                            LeUncodedPhy::Le2M => LePhy::Le2M,
+                        },
+                        time: received.access_address_sample,
+                        direction: None,
+                    };
+                    let sample = packet.time;
+                    let timestamp_ns =
+                        sample_timestamp_ns(iq.capture_start_ns, sample, sample_rate_hz)?;
+                    analyze_packet(
+                        &mut analyzer,
+                        ("sample", sample),
+                        timestamp_ns,
+                        packet,
+                        &mut counters,
+                        btsnoop.as_mut(),
                     )?;
-                    writer.write_le(&captured.observation, timestamp)?;
                 }
-                Ok(())
-            },
-        )?,
-    };
-    if let Some(writer) = pcap {
-        writer.into_inner().flush()?;
+            }
+            eprintln!(
+                "analyzed {} CRC-valid packet(s) from {} sample(s); decode_errors={}",
+                counters.packets,
+                reader.next_sample_index(),
+                counters.decode_errors
+            );
+        }
     }
-    if args.central_observation_tracking.is_some() {
+    for (id, summary) in analyzer.connections().enumerate() {
+        print_connection_summary(id, summary, args.config.time_base_hz);
+    }
+    if let Some(path) = &args.output_keyring {
+        let mut keyring = if path.exists() {
+            LeKeyring::load(path)?
+        } else {
+            LeKeyring::new()
+        };
+        let mut added = KeyImport::default();
+        for key in analyzer
+            .connections()
+            .flat_map(|summary| &summary.pairing_long_term_keys)
+        {
+            added.long_term_keys += usize::from(keyring.add_long_term_key(*key));
+        }
+        for key in analyzer
+            .connections()
+            .flat_map(|summary| &summary.pairing_identity_resolving_keys)
+        {
+            added.identity_resolving_keys += usize::from(keyring.add_identity_resolving_key(*key));
+        }
+        for key in analyzer
+            .connections()
+            .flat_map(|summary| &summary.pairing_signature_keys)
+        {
+            added.signature_keys += usize::from(keyring.add_signature_key(*key));
+        }
+        for key in analyzer
+            .connections()
+            .flat_map(|summary| &summary.pairing_link_keys)
+        {
+            added.link_keys += usize::from(keyring.add_link_key(*key));
+        }
+        for key in analyzer
+            .connections()
+            .flat_map(|summary| &summary.encrypted_data_keys)
+        {
+            added.encrypted_data_keys += usize::from(keyring.add_encrypted_data_key(*key));
+        }
+        keyring.save(path)?;
+        eprintln!("added {} to {}", describe_key_import(added), path.display());
+    }
+    if let Some(mut btsnoop) = btsnoop {
+        btsnoop.flush()?;
         eprintln!(
-            "central connection observations: matched={central_observation_matches} rejected={central_observation_errors}"
+            "wrote {} btsnoop record(s) from the {} host",
+            btsnoop.records_written(),
+            btsnoop.host()
         );
     }
-    Ok(stats)
+    Ok(())
 }
 
 fn backends() {
@@ -4164,6 +9042,273 @@ fn backends() {
     }
 }
 
+const EXTCAP_REPLAY_INTERFACE: &str = "blueoxide-replay";
+/// How long captured packets may wait before reaching Wireshark's FIFO.
+const EXTCAP_FLUSH_INTERVAL: Duration = Duration::from_millis(100);
+
+struct ExtcapInterface {
+    value: String,
+    display: String,
+    /// `None` for the replay interface.
+    device: Option<SdrDevice>,
+}
+
+/// Lists attached radios and the always-present replay interface. A missing
+/// vendor library or failed enumeration hides that backend's devices;
+/// `blueoxide backends` reports why.
+fn extcap_interfaces() -> Vec<ExtcapInterface> {
+    let mut interfaces = Vec::new();
+    for devices in [
+        BladeRfSource::list_devices(),
+        LimeSdrSource::list_devices(),
+        XtrxSource::list_devices(),
+    ] {
+        for device in devices.unwrap_or_default() {
+            let serial: String = device
+                .serial
+                .chars()
+                .map(|character| {
+                    if character.is_ascii_alphanumeric() || character == '-' {
+                        character
+                    } else {
+                        '_'
+                    }
+                })
+                .collect();
+            interfaces.push(ExtcapInterface {
+                value: format!("blueoxide-{}-{serial}", extcap_device_name(device.kind)),
+                display: format!("blueoxide {}", device.description),
+                device: Some(device),
+            });
+        }
+    }
+    interfaces.push(ExtcapInterface {
+        value: EXTCAP_REPLAY_INTERFACE.to_owned(),
+        display: "blueoxide replay of a SigMF or raw I/Q recording".to_owned(),
+        device: None,
+    });
+    interfaces
+}
+
+fn extcap_device_name(kind: SdrKind) -> &'static str {
+    match kind {
+        SdrKind::BladeRf => "bladerf",
+        SdrKind::LimeSdr => "limesdr",
+        SdrKind::Xtrx => "xtrx",
+        SdrKind::Stream => "stream",
+        SdrKind::Replay => "replay",
+    }
+}
+
+fn extcap(args: ExtcapArgs) -> Result<()> {
+    if args.action == ExtcapAction::Interfaces {
+        println!(
+            "extcap {{version={}}}{{help={}}}",
+            env!("CARGO_PKG_VERSION"),
+            env!("CARGO_PKG_REPOSITORY")
+        );
+        for interface in extcap_interfaces() {
+            println!(
+                "interface {{value={}}}{{display={}}}",
+                interface.value, interface.display
+            );
+        }
+        return Ok(());
+    }
+    let name = args.interface.as_deref().unwrap_or_default();
+    let interface = if name == EXTCAP_REPLAY_INTERFACE {
+        ExtcapInterface {
+            value: name.to_owned(),
+            display: String::new(),
+            device: None,
+        }
+    } else {
+        extcap_interfaces()
+            .into_iter()
+            .find(|interface| interface.value == name)
+            .ok_or_else(|| {
+                Error::InvalidConfiguration(format!("unknown extcap interface {name:?}"))
+            })?
+    };
+    match args.action {
+        ExtcapAction::Interfaces => unreachable!(),
+        ExtcapAction::Dlts => {
+            println!(
+                "dlt {{number=256}}{{name=BLUETOOTH_LE_LL_WITH_PHDR}}{{display=Bluetooth LE Link Layer with pseudo-header}}"
+            );
+            Ok(())
+        }
+        ExtcapAction::Config => {
+            print_extcap_config(interface.device.is_none());
+            Ok(())
+        }
+        ExtcapAction::Capture => {
+            let fifo = args.fifo.clone().unwrap_or_default();
+            match extcap_capture(&interface, &args.options, fifo) {
+                // Wireshark closed the FIFO to stop the capture.
+                Err(Error::Io(error)) if error.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+                result => result,
+            }
+        }
+    }
+}
+
+fn print_extcap_config(replay: bool) {
+    let mut arguments = vec![
+        "{call=--channel}{display=Channel}{type=integer}{range=0,39}{default=37}{tooltip=37-39 for advertising; 0-36 with an access address}".to_owned(),
+        "{call=--access-address}{display=Access address}{type=string}{tooltip=Capture one data channel of this connection instead of advertising, e.g. 0x12345678}".to_owned(),
+        "{call=--crc-init}{display=CRC init}{type=string}{tooltip=24-bit connection CRC initialization value; required with an access address}".to_owned(),
+        "{call=--phy}{display=Data-channel PHY}{type=selector}{tooltip=PHY of the connection; advertising is always LE 1M}".to_owned(),
+        "{call=--sample-rate}{display=Sample rate (Hz)}{type=unsigned}{default=4000000}".to_owned(),
+        "{call=--aa-errors}{display=Access-address bit errors}{type=integer}{range=0,8}{default=1}".to_owned(),
+        "{call=--emit-crc-failures}{display=Report CRC-failed frames}{type=boolflag}{default=false}".to_owned(),
+    ];
+    if replay {
+        arguments.push("{call=--recording}{display=Recording}{type=fileselect}{mustexist=true}{required=true}{tooltip=SigMF metadata or raw I/Q file}".to_owned());
+        arguments.push(
+            "{call=--format}{display=Raw I/Q format}{type=selector}{tooltip=Ignored for SigMF}"
+                .to_owned(),
+        );
+        arguments.push("{call=--replay-speed}{display=Replay speed}{type=string}{default=1}{tooltip=max, or a real-time factor such as 1 or 4}".to_owned());
+    } else {
+        arguments.push(
+            "{call=--bandwidth}{display=Bandwidth (Hz)}{type=unsigned}{default=2000000}".to_owned(),
+        );
+        arguments.push("{call=--gain}{display=Gain (dB)}{type=double}{default=30}".to_owned());
+        arguments.push(
+            "{call=--rx-channel}{display=Hardware RX channel}{type=unsigned}{default=0}".to_owned(),
+        );
+    }
+    for (number, argument) in arguments.iter().enumerate() {
+        println!("arg {{number={number}}}{argument}");
+        if argument.starts_with("{call=--phy}") {
+            println!("value {{arg={number}}}{{value=1m}}{{display=LE 1M}}{{default=true}}");
+            println!("value {{arg={number}}}{{value=2m}}{{display=LE 2M}}");
+        } else if argument.starts_with("{call=--format}") {
+            println!(
+                "value {{arg={number}}}{{value=f32le}}{{display=Complex float32}}{{default=true}}"
+            );
+            println!("value {{arg={number}}}{{value=s16le}}{{display=Complex int16}}");
+        }
+    }
+}
+
+/// Runs `capture` or `capture-data`, chosen by whether an access address was
+/// given, with live PCAPNG written to Wireshark's FIFO until it stops us.
+fn extcap_capture(
+    interface: &ExtcapInterface,
+    options: &[(String, Option<String>)],
+    fifo: PathBuf,
+) -> Result<()> {
+    let data = options.iter().any(|(name, value)| {
+        name == "--access-address" && value.as_ref().is_some_and(|value| !value.is_empty())
+    });
+    let mut argv = vec!["--device".to_owned()];
+    match &interface.device {
+        Some(device) => {
+            argv.push(extcap_device_name(device.kind).to_owned());
+            argv.push("--identifier".to_owned());
+            argv.push(device.identifier.clone());
+        }
+        None => {
+            argv.push("replay".to_owned());
+            if !options.iter().any(|(name, _)| name == "--recording") {
+                return Err(Error::InvalidConfiguration(format!(
+                    "{EXTCAP_REPLAY_INTERFACE} requires --recording FILE"
+                )));
+            }
+        }
+    }
+    for (name, value) in options {
+        if value.as_ref().is_some_and(String::is_empty)
+            || (!data && matches!(name.as_str(), "--phy" | "--crc-init"))
+        {
+            continue;
+        }
+        argv.push(if name == "--recording" {
+            "--identifier".to_owned()
+        } else {
+            name.clone()
+        });
+        argv.extend(value.iter().cloned());
+    }
+    let mut args = parse_capture_args(
+        &argv,
+        if data {
+            CaptureCommand::Data
+        } else {
+            CaptureCommand::Advertising
+        },
+    )?;
+    // Wireshark stops the capture by closing the FIFO or ending the process.
+    args.duration = Duration::MAX;
+    args.output_pcap = Some(fifo);
+    args.pcap_rotation.flush_interval = Some(EXTCAP_FLUSH_INTERVAL);
+    capture(args)
+}
+
+fn serve(args: ServeArgs) -> Result<()> {
+    let device = args.device.to_ascii_lowercase();
+    match device.as_str() {
+        "bladerf" => {
+            let mut source =
+                BladeRfSource::open(args.identifier.as_deref(), BladeRfOptions::default())?;
+            serve_source(&mut source, &args)
+        }
+        "limesdr" | "lime" => {
+            let mut source =
+                LimeSdrSource::open(args.identifier.as_deref(), LimeSdrOptions::default())?;
+            serve_source(&mut source, &args)
+        }
+        "xtrx" => {
+            let mut source = XtrxSource::open(args.identifier.as_deref(), XtrxOptions::default())?;
+            serve_source(&mut source, &args)
+        }
+        "stream" => {
+            let identifier = args.identifier.as_deref().ok_or_else(|| {
+                Error::InvalidConfiguration(
+                    "serve --device stream requires --identifier -|PATH|tcp://HOST:PORT".to_owned(),
+                )
+            })?;
+            let mut source = StreamSource::open(
+                &IqStreamLocation::parse(identifier)?,
+                args.stream_format,
+                args.stream_framing,
+            )?;
+            serve_source(&mut source, &args)
+        }
+        _ => Err(Error::InvalidConfiguration(format!(
+            "serve device {:?} is not implemented; currently available: bladerf, limesdr, xtrx, stream",
+            args.device
+        ))),
+    }
+}
+
+fn serve_source<S: IqSource>(source: &mut S, args: &ServeArgs) -> Result<()> {
+    let listener = TcpListener::bind(args.listen.as_str())?;
+    eprintln!(
+        "serving {:?} I/Q on {}",
+        source.kind(),
+        listener.local_addr()?
+    );
+    for (served, connection) in (1u64..).zip(listener.incoming()) {
+        let connection = connection?;
+        let peer = connection.peer_addr()?;
+        eprintln!("client connected: {peer}");
+        match serve_iq_connection(source, connection) {
+            Ok(()) => eprintln!("client disconnected: {peer}"),
+            Err(error) => eprintln!("client {peer} disconnected with error: {error}"),
+        }
+        if args
+            .max_connections
+            .is_some_and(|maximum| served >= maximum)
+        {
+            break;
+        }
+    }
+    Ok(())
+}
+
 fn connection_tracker(args: &ConnectionPlanArgs) -> Result<ConnectionTracker> {
     let mut tracker = ConnectionTracker::new_with_phy(
         ConnectionTrackerConfig {
@@ -4213,18 +9358,38 @@ fn connection_plan(args: ConnectionPlanArgs) -> Result<()> {
                     "offline connection plan unexpectedly lost its timing anchor".to_owned(),
                 )
             })?;
-        println!(
-            "event={} channel={} frequency_hz={} central_to_peripheral_phy={} peripheral_to_central_phy={} expected_sample={} earliest_sample={} latest_sample={} widening_samples={}",
-            event.event_counter,
-            event.channel.index(),
-            event.channel.center_frequency_hz(),
-            event.phy.central_to_peripheral,
-            event.phy.peripheral_to_central,
-            access_address_sample,
-            timing_window.earliest_sample,
-            timing_window.latest_sample,
-            timing_window.widening_samples
-        );
+        match args.output_format {
+            OutputFormat::Text => println!(
+                "event={} channel={} frequency_hz={} central_to_peripheral_phy={} peripheral_to_central_phy={} expected_sample={} earliest_sample={} latest_sample={} widening_samples={}",
+                event.event_counter,
+                event.channel.index(),
+                event.channel.center_frequency_hz(),
+                event.phy.central_to_peripheral,
+                event.phy.peripheral_to_central,
+                access_address_sample,
+                timing_window.earliest_sample,
+                timing_window.latest_sample,
+                timing_window.widening_samples
+            ),
+            OutputFormat::JsonLines => print_json(
+                json_record("connection_event")
+                    .field("event", event.event_counter)
+                    .field("channel", event.channel.index())
+                    .field("frequency_hz", event.channel.center_frequency_hz())
+                    .field(
+                        "central_to_peripheral_phy",
+                        Json::display(event.phy.central_to_peripheral),
+                    )
+                    .field(
+                        "peripheral_to_central_phy",
+                        Json::display(event.phy.peripheral_to_central),
+                    )
+                    .field("expected_sample", access_address_sample)
+                    .field("earliest_sample", timing_window.earliest_sample)
+                    .field("latest_sample", timing_window.latest_sample)
+                    .field("widening_samples", timing_window.widening_samples),
+            ),
+        }
     }
     Ok(())
 }
@@ -4249,7 +9414,7 @@ fn connection_sync(args: ConnectionPlanArgs) -> Result<()> {
             args.receiver_clock_accuracy_ppm,
             args.maximum_event_advance,
         )?;
-        print_synchronized_observation(observed, observation);
+        print_synchronized_observation(observed, observation, args.output_format);
     }
     Ok(())
 }
@@ -4257,8 +9422,36 @@ fn connection_sync(args: ConnectionPlanArgs) -> Result<()> {
 fn print_synchronized_observation(
     observed: ConnectionObservationArg,
     observation: blueoxide::link_layer::ConnectionObservation,
+    output_format: OutputFormat,
 ) {
     let missed_events = observation.advanced_events.saturating_sub(1);
+    if output_format == OutputFormat::JsonLines {
+        print_json(
+            json_record("connection_observation")
+                .field("event", observation.event.event_counter)
+                .field("channel", observation.event.channel.index())
+                .field(
+                    "central_to_peripheral_phy",
+                    Json::display(observation.event.phy.central_to_peripheral),
+                )
+                .field(
+                    "peripheral_to_central_phy",
+                    Json::display(observation.event.phy.peripheral_to_central),
+                )
+                .field("observed_sample", observed.access_address_sample)
+                .field("advanced_events", observation.advanced_events)
+                .field("missed_events", missed_events)
+                .field("expected_sample", observation.timing_window.expected_sample)
+                .extend(json_sample_timing(observation.timing_error))
+                .field("earliest_sample", observation.timing_window.earliest_sample)
+                .field("latest_sample", observation.timing_window.latest_sample)
+                .field(
+                    "widening_samples",
+                    observation.timing_window.widening_samples,
+                ),
+        );
+        return;
+    }
     let timing = describe_sample_timing(observation.timing_error);
     println!(
         "event={} channel={} central_to_peripheral_phy={} peripheral_to_central_phy={} observed_sample={} advanced_events={} missed_events={} expected_sample={} timing={} earliest_sample={} latest_sample={} widening_samples={}",
@@ -4285,11 +9478,44 @@ fn describe_sample_timing(timing_error: SampleTimingError) -> String {
     }
 }
 
+/// Timing error as `timing` (`early`, `on-time`, or `late`) and its
+/// non-negative magnitude in samples.
+fn json_sample_timing(timing_error: SampleTimingError) -> JsonObject {
+    let (timing, samples) = match timing_error {
+        SampleTimingError::Early(samples) => ("early", samples),
+        SampleTimingError::OnTime => ("on-time", 0),
+        SampleTimingError::Late(samples) => ("late", samples),
+    };
+    JsonObject::new()
+        .field("timing", timing)
+        .field("timing_samples", samples)
+}
+
 fn print_live_central_observation(
     observed_sample: u64,
     observation: blueoxide::link_layer::ConnectionObservation,
+    output_format: OutputFormat,
 ) {
     let missed_events = observation.advanced_events.saturating_sub(1);
+    if output_format == OutputFormat::JsonLines {
+        print_json(
+            json_record("central_connection_event")
+                .field("event", observation.event.event_counter)
+                .field("channel", observation.event.channel.index())
+                .field("observed_sample", observed_sample)
+                .field("advanced_events", observation.advanced_events)
+                .field("missed_events", missed_events)
+                .field("expected_sample", observation.timing_window.expected_sample)
+                .extend(json_sample_timing(observation.timing_error))
+                .field("earliest_sample", observation.timing_window.earliest_sample)
+                .field("latest_sample", observation.timing_window.latest_sample)
+                .field(
+                    "widening_samples",
+                    observation.timing_window.widening_samples,
+                ),
+        );
+        return;
+    }
     println!(
         "central_connection_event event={} channel={} observed_sample={} advanced_events={} missed_events={} expected_sample={} timing={} earliest_sample={} latest_sample={} widening_samples={}",
         observation.event.event_counter,
@@ -4353,19 +9579,44 @@ fn connection_acquire(args: ConnectionPlanArgs) -> Result<()> {
         tracker.schedule_phy_update(update)?;
     }
     let event = tracker.current_event()?;
-    println!(
-        "event=0 channel={} central_to_peripheral_phy={} peripheral_to_central_phy={} central_sample={} connect_ind_sample={} nominal_start_sample={} nominal_end_sample={} earliest_sample={} latest_sample={} widening_samples={}",
-        first_window.channel.index(),
-        event.phy.central_to_peripheral,
-        event.phy.peripheral_to_central,
-        first_central_transmission.access_address_sample,
-        connect_ind_access_address_sample,
-        first_window.nominal_start_sample,
-        first_window.nominal_end_sample,
-        first_window.earliest_sample,
-        first_window.latest_sample,
-        first_window.widening_samples
-    );
+    match args.output_format {
+        OutputFormat::Text => println!(
+            "event=0 channel={} central_to_peripheral_phy={} peripheral_to_central_phy={} central_sample={} connect_ind_sample={} nominal_start_sample={} nominal_end_sample={} earliest_sample={} latest_sample={} widening_samples={}",
+            first_window.channel.index(),
+            event.phy.central_to_peripheral,
+            event.phy.peripheral_to_central,
+            first_central_transmission.access_address_sample,
+            connect_ind_access_address_sample,
+            first_window.nominal_start_sample,
+            first_window.nominal_end_sample,
+            first_window.earliest_sample,
+            first_window.latest_sample,
+            first_window.widening_samples
+        ),
+        OutputFormat::JsonLines => print_json(
+            json_record("connection_acquisition")
+                .field("event", 0u16)
+                .field("channel", first_window.channel.index())
+                .field(
+                    "central_to_peripheral_phy",
+                    Json::display(event.phy.central_to_peripheral),
+                )
+                .field(
+                    "peripheral_to_central_phy",
+                    Json::display(event.phy.peripheral_to_central),
+                )
+                .field(
+                    "central_sample",
+                    first_central_transmission.access_address_sample,
+                )
+                .field("connect_ind_sample", connect_ind_access_address_sample)
+                .field("nominal_start_sample", first_window.nominal_start_sample)
+                .field("nominal_end_sample", first_window.nominal_end_sample)
+                .field("earliest_sample", first_window.earliest_sample)
+                .field("latest_sample", first_window.latest_sample)
+                .field("widening_samples", first_window.widening_samples),
+        ),
+    }
     for observed in args.observations {
         let observation = tracker.synchronize_observation(
             observed.channel,
@@ -4374,7 +9625,7 @@ fn connection_acquire(args: ConnectionPlanArgs) -> Result<()> {
             args.receiver_clock_accuracy_ppm,
             args.maximum_event_advance,
         )?;
-        print_synchronized_observation(observed, observation);
+        print_synchronized_observation(observed, observation, args.output_format);
     }
     Ok(())
 }
@@ -4384,7 +9635,68 @@ fn print_extended_advertising_progress(
     kind: ExtendedAdvertisingPduKind,
     packet: &ExtendedAdvertisingPacketArg,
     progress: &ExtendedAdvertisingChainProgress,
+    output_format: OutputFormat,
 ) {
+    if output_format == OutputFormat::JsonLines {
+        let record = json_record("extended_advertising_progress")
+            .field("packet", packet_index)
+            .field("kind", Json::display(kind))
+            .field("channel", packet.pdu.channel.index())
+            .field("phy", Json::display(packet.phy))
+            .field("sample", packet.access_address_sample);
+        print_json(match progress {
+            ExtendedAdvertisingChainProgress::Awaiting {
+                window,
+                fragment_count,
+                advertising_data_octets,
+            } => record
+                .field("status", "awaiting")
+                .field("fragments", *fragment_count)
+                .field("advertising_data_octets", *advertising_data_octets)
+                .field("next_kind", Json::display(window.expected_kind))
+                .field("next_channel", window.channel.index())
+                .field("next_frequency_hz", window.channel.center_frequency_hz())
+                .field("next_phy", Json::display(window.phy))
+                .field(
+                    "represented_earliest_sample",
+                    window.represented_earliest_sample,
+                )
+                .field(
+                    "represented_latest_sample",
+                    window.represented_latest_sample,
+                )
+                .field("earliest_sample", window.earliest_sample)
+                .field("latest_sample", window.latest_sample)
+                .field("quantization_width_us", window.quantization_width_us)
+                .field(
+                    "quantization_width_samples",
+                    window.quantization_width_samples,
+                )
+                .field("widening_samples", window.widening_samples),
+            ExtendedAdvertisingChainProgress::Complete(chain) => record
+                .field("status", "complete")
+                .field("mode", Json::display(chain.mode))
+                .field("advertiser", chain.advertiser_address.map(Json::display))
+                .field(
+                    "advertiser_type",
+                    chain.advertiser_address_kind.map(Json::display),
+                )
+                .field(
+                    "adi",
+                    chain.advertising_data_info.map(|info| {
+                        JsonObject::new()
+                            .field("sid", info.advertising_set_id)
+                            .field("did", info.data_id)
+                    }),
+                )
+                .field("fragments", chain.fragment_count)
+                .field("first_auxiliary_sample", chain.first_auxiliary_sample)
+                .field("last_auxiliary_sample", chain.last_auxiliary_sample)
+                .field("advertising_data_octets", chain.advertising_data.len())
+                .field("advertising_data", Json::hex(&chain.advertising_data)),
+        });
+        return;
+    }
     match progress {
         ExtendedAdvertisingChainProgress::Awaiting {
             window,
@@ -4463,6 +9775,7 @@ fn extended_advertising_plan(args: ExtendedAdvertisingPlanArgs) -> Result<()> {
         ExtendedAdvertisingPduKind::AdvExtInd,
         &primary,
         &progress,
+        args.output_format,
     );
 
     for (offset, packet) in packets.enumerate() {
@@ -4476,13 +9789,49 @@ fn extended_advertising_plan(args: ExtendedAdvertisingPlanArgs) -> Result<()> {
             }
         };
         progress = tracker.observe(&packet.pdu, packet.phy, packet.access_address_sample)?;
-        print_extended_advertising_progress(offset + 1, kind, &packet, &progress);
+        print_extended_advertising_progress(
+            offset + 1,
+            kind,
+            &packet,
+            &progress,
+            args.output_format,
+        );
     }
     Ok(())
 }
 
-fn print_periodic_advertising_event(index: usize, event: PeriodicAdvertisingEvent) {
+fn print_periodic_advertising_event(
+    index: usize,
+    event: PeriodicAdvertisingEvent,
+    output_format: OutputFormat,
+) {
     let window = event.timing_window;
+    if output_format == OutputFormat::JsonLines {
+        print_json(
+            json_record("periodic_event")
+                .field("plan", index)
+                .field("event", event.event_counter)
+                .field("channel", event.channel.index())
+                .field("frequency_hz", event.channel.center_frequency_hz())
+                .field("phy", Json::display(event.phy))
+                .field(
+                    "represented_earliest_sample",
+                    window.represented_earliest_sample,
+                )
+                .field(
+                    "represented_latest_sample",
+                    window.represented_latest_sample,
+                )
+                .field("earliest_sample", window.earliest_sample)
+                .field("latest_sample", window.latest_sample)
+                .field(
+                    "quantization_width_samples",
+                    window.quantization_width_samples,
+                )
+                .field("widening_samples", window.widening_samples),
+        );
+        return;
+    }
     println!(
         "plan={} event={} channel={} frequency_hz={} phy={} represented_earliest_sample={} represented_latest_sample={} earliest_sample={} latest_sample={} quantization_width_samples={} widening_samples={}",
         index,
@@ -4509,18 +9858,27 @@ fn periodic_advertising_plan(args: PeriodicAdvertisingPlanArgs) -> Result<()> {
             "periodic-advertising-plan --sync-packet does not contain SyncInfo".to_owned(),
         )
     })?;
-    println!(
-        "sync_packet channel={} phy={} sample={} access_address={:08x} crc_init={:06x} interval_us={} event={} channel_map={} advertiser_sca_ppm={}",
-        args.sync_packet.pdu.channel.index(),
-        args.sync_packet.phy,
-        args.sync_packet.access_address_sample,
-        sync_info.access_address,
-        sync_info.crc_init,
-        sync_info.interval_us(),
-        sync_info.event_counter,
-        print_hex(&sync_info.channel_map.bytes()),
-        sync_info.sleep_clock_accuracy.maximum_ppm(),
-    );
+    match args.output_format {
+        OutputFormat::Text => println!(
+            "sync_packet channel={} phy={} sample={} access_address={:08x} crc_init={:06x} interval_us={} event={} channel_map={} advertiser_sca_ppm={}",
+            args.sync_packet.pdu.channel.index(),
+            args.sync_packet.phy,
+            args.sync_packet.access_address_sample,
+            sync_info.access_address,
+            sync_info.crc_init,
+            sync_info.interval_us(),
+            sync_info.event_counter,
+            print_hex(&sync_info.channel_map.bytes()),
+            sync_info.sleep_clock_accuracy.maximum_ppm(),
+        ),
+        OutputFormat::JsonLines => print_json(
+            json_record("periodic_sync_packet")
+                .field("channel", args.sync_packet.pdu.channel.index())
+                .field("phy", Json::display(args.sync_packet.phy))
+                .field("sample", args.sync_packet.access_address_sample)
+                .field("sync_info", json_sync_info(&sync_info)),
+        ),
+    }
     let mut tracker = PeriodicAdvertisingTracker::new(
         sync_info,
         args.sync_packet.phy,
@@ -4538,6 +9896,31 @@ fn periodic_advertising_plan(args: PeriodicAdvertisingPlanArgs) -> Result<()> {
             args.maximum_event_advance,
         )?;
         let window = observation.event.timing_window;
+        if args.output_format == OutputFormat::JsonLines {
+            print_json(
+                json_record("periodic_observation")
+                    .field("observation", index)
+                    .field("event", observation.event.event_counter)
+                    .field("channel", observation.event.channel.index())
+                    .field("phy", Json::display(observation.event.phy))
+                    .field("observed_sample", observed.access_address_sample)
+                    .field("advanced_events", observation.advanced_events)
+                    .field("missed_events", observation.advanced_events)
+                    .extend(json_sample_timing(observation.timing_error))
+                    .field(
+                        "represented_earliest_sample",
+                        window.represented_earliest_sample,
+                    )
+                    .field(
+                        "represented_latest_sample",
+                        window.represented_latest_sample,
+                    )
+                    .field("earliest_sample", window.earliest_sample)
+                    .field("latest_sample", window.latest_sample)
+                    .field("widening_samples", window.widening_samples),
+            );
+            continue;
+        }
         println!(
             "observation={} event={} channel={} phy={} observed_sample={} advanced_events={} missed_events={} timing={} represented_earliest_sample={} represented_latest_sample={} earliest_sample={} latest_sample={} widening_samples={}",
             index,
@@ -4561,7 +9944,7 @@ fn periodic_advertising_plan(args: PeriodicAdvertisingPlanArgs) -> Result<()> {
         } else {
             tracker.advance()?
         };
-        print_periodic_advertising_event(index, event);
+        print_periodic_advertising_event(index, event, args.output_format);
     }
     Ok(())
 }
@@ -4569,12 +9952,17 @@ fn periodic_advertising_plan(args: PeriodicAdvertisingPlanArgs) -> Result<()> {
 fn run() -> Result<()> {
     let args: Vec<String> = env::args().skip(1).collect();
     match args.first().map(String::as_str) {
+        // Wireshark runs extcap programs with protocol options only.
+        Some(first) if first.starts_with("--extcap-") || first == "--capture" => {
+            extcap(parse_extcap_args(&args)?)
+        }
         Some("decode") => decode(parse_decode_args(&args[1..])?),
         Some("decode-secondary") => decode_secondary(parse_decode_secondary_args(&args[1..])?),
         Some("decode-periodic") => decode_periodic(parse_decode_periodic_args(&args[1..])?),
         Some("decode-data") => decode_data(parse_decode_data_args(&args[1..])?),
         Some("encryption-trace") => encryption_trace(parse_encryption_trace_args(&args[1..])?),
         Some("l2cap-trace") => l2cap_trace(parse_l2cap_trace_args(&args[1..])?),
+        Some("keyring") => keyring_command(parse_keyring_args(&args[1..])?),
         Some("connection-plan") => connection_plan(parse_connection_plan_args(&args[1..])?),
         Some("connection-sync") => connection_sync(parse_connection_plan_args(&args[1..])?),
         Some("connection-acquire") => connection_acquire(parse_connection_plan_args(&args[1..])?),
@@ -4586,6 +9974,10 @@ fn run() -> Result<()> {
         }
         Some("capture") => capture(parse_capture_args(&args[1..], CaptureCommand::Advertising)?),
         Some("capture-data") => capture(parse_capture_args(&args[1..], CaptureCommand::Data)?),
+        Some("serve") => serve(parse_serve_args(&args[1..])?),
+        Some("synthesize") => synthesize_command(parse_synthesize_args(&args[1..])?),
+        Some("scenario") => scenario_command(parse_scenario_args(&args[1..])?),
+        Some("analyze") => analyze_command(parse_analyze_args(&args[1..])?),
         Some("backends") => {
             backends();
             Ok(())
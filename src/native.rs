@@ -184,6 +184,17 @@ mod platform {
     }
 }
 
+/// Reads a fixed-size C character array up to its first NUL, or the whole
+/// array when the library filled it without one.
+pub(crate) fn c_char_array_to_string(field: &[c_char]) -> String {
+    let bytes: Vec<u8> = field
+        .iter()
+        .map(|&value| value as u8)
+        .take_while(|&byte| byte != 0)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -223,4 +234,12 @@ mod tests {
         // SAFETY: value is a valid NUL-terminated string.
         assert_eq!(unsafe { function(value.as_ptr()) }, 9);
     }
+
+    #[test]
+    fn reads_fixed_c_character_arrays_with_and_without_terminator() {
+        let terminated = [b'a' as c_char, b'b' as c_char, 0, b'x' as c_char];
+        assert_eq!(c_char_array_to_string(&terminated), "ab");
+        let full = [b'c' as c_char; 3];
+        assert_eq!(c_char_array_to_string(&full), "ccc");
+    }
 }
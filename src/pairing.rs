@@ -0,0 +1,2007 @@
+//! LE Security Manager pairing cryptography and passive key recovery.
+//!
+//! LE legacy pairing protects its confirm values with a TK that is zero for
+//! Just Works and a six-digit passkey for Passkey Entry, so an observer of
+//! the Pairing Confirm and Pairing Random PDUs can search the TK with the
+//! `c1` confirm function. [`LegacyPairingTracker`] follows the SMP PDUs of
+//! one connection, recovers the TK, derives the STK with `s1`, and, once the
+//! caller decrypts the session the STK encrypts, captures the LTK, EDIV, and
+//! Rand distributed in Encryption Information and Central Identification.
+//!
+//! LE Secure Connections agrees on a DHKey by P-256 ECDH, which a passive
+//! observer cannot compute unless one side uses the published debug key or
+//! the DHKey is known from elsewhere. [`SecureConnectionsPairingTracker`]
+//! detects the debug key, derives the MacKey and LTK with `f5`, and checks
+//! them against the DHKey Check values with `f6`.
+//!
+//! [`PairingSessionTracker`] needs no keys: it follows the phases of either
+//! kind of pairing and summarizes the negotiated method, key size, key
+//! distribution, and resulting LE security level, flagging weak outcomes.
+//!
+//! Every 128-bit value uses the octet order of SMP PDU fields, least
+//! significant octet first, as [`crate::keyring`] does.
+
+use crate::advertising::{AddressKind, DeviceAddress};
+use crate::crypto::{AES_BATCH_BLOCKS, Aes128, Aes128Batch};
+use crate::keyring::LongTermKeyEntry;
+use crate::link_layer::{L2capPdu, LinkDirection};
+use crate::smp::{
+    DecodedSmpPdu, SMP_KEY_LENGTH, SMP_PUBLIC_KEY_COORDINATE_LENGTH, SmpIoCapability,
+    SmpKeyDistribution, SmpPairingFailure, SmpPairingFeatures, SmpPdu, SmpPublicKey,
+};
+use crate::{Error, Result};
+use std::fmt::{Display, Formatter};
+
+/// Pairing Request and Pairing Response PDUs, command code included.
+pub const SMP_PAIRING_FEATURES_PDU_LENGTH: usize = 7;
+/// Largest passkey a six-digit display or keyboard can carry.
+pub const MAXIMUM_PASSKEY: u32 = 999_999;
+/// Secure Connections Passkey Entry commits one passkey bit per round.
+const PASSKEY_ENTRY_ROUNDS: usize = 20;
+/// SALT of `f5` and its keyID, "btle", least significant octet first.
+const F5_SALT: [u8; SMP_KEY_LENGTH] = [
+    0xbe, 0x83, 0x60, 0x5a, 0xdb, 0x0b, 0x37, 0x60, 0x38, 0xa5, 0xf5, 0xaa, 0x91, 0x83, 0x88, 0x6c,
+];
+const F5_KEY_ID: [u8; 4] = [0x65, 0x6c, 0x74, 0x62];
+/// Cross-transport key derivation key IDs "tmp1", "lebr", "tmp2", and
+/// "brle", least significant octet first. The `h7` salts are the same
+/// values zero-extended to 128 bits.
+const TMP1_KEY_ID: [u8; 4] = [0x31, 0x70, 0x6d, 0x74];
+const LEBR_KEY_ID: [u8; 4] = [0x72, 0x62, 0x65, 0x6c];
+const TMP2_KEY_ID: [u8; 4] = [0x32, 0x70, 0x6d, 0x74];
+const BRLE_KEY_ID: [u8; 4] = [0x65, 0x6c, 0x72, 0x62];
+/// The published Secure Connections debug key pair, most significant octet
+/// first.
+const DEBUG_PRIVATE_KEY: [u8; SMP_PUBLIC_KEY_COORDINATE_LENGTH] = [
+    0x3f, 0x49, 0xf6, 0xd4, 0xa3, 0xc5, 0x5f, 0x38, 0x74, 0xc9, 0xb3, 0xe3, 0xd2, 0x10, 0x3f, 0x50,
+    0x4a, 0xff, 0x60, 0x7b, 0xeb, 0x40, 0xb7, 0x99, 0x58, 0x99, 0xb8, 0xa6, 0xcd, 0x3c, 0x1a, 0xbd,
+];
+const DEBUG_PUBLIC_KEY_X: [u8; SMP_PUBLIC_KEY_COORDINATE_LENGTH] = [
+    0x20, 0xb0, 0x03, 0xd2, 0xf2, 0x97, 0xbe, 0x2c, 0x5e, 0x2c, 0x83, 0xa7, 0xe9, 0xf9, 0xa5, 0xb9,
+    0xef, 0xf4, 0x91, 0x11, 0xac, 0xf4, 0xfd, 0xdb, 0xcc, 0x03, 0x01, 0x48, 0x0e, 0x35, 0x9d, 0xe6,
+];
+const DEBUG_PUBLIC_KEY_Y: [u8; SMP_PUBLIC_KEY_COORDINATE_LENGTH] = [
+    0xdc, 0x80, 0x9c, 0x49, 0x65, 0x2a, 0xeb, 0x6d, 0x63, 0x32, 0x9a, 0xbf, 0x5a, 0x52, 0x15, 0x5c,
+    0x76, 0x63, 0x45, 0xc2, 0x8f, 0xed, 0x30, 0x24, 0x74, 0x1c, 0x8e, 0xd0, 0x15, 0x89, 0xd2, 0x8b,
+];
+
+/// Confirm value function `c1` of LE legacy pairing.
+///
+/// `pairing_request` and `pairing_response` are the PDUs as transmitted, and
+/// the addresses are those of the initiating central and the responding
+/// peripheral during pairing.
+pub fn c1(
+    key: [u8; SMP_KEY_LENGTH],
+    random: [u8; SMP_KEY_LENGTH],
+    pairing_request: [u8; SMP_PAIRING_FEATURES_PDU_LENGTH],
+    pairing_response: [u8; SMP_PAIRING_FEATURES_PDU_LENGTH],
+    initiator: (DeviceAddress, AddressKind),
+    responder: (DeviceAddress, AddressKind),
+) -> [u8; SMP_KEY_LENGTH] {
+    let (first_input, second_operand) = c1_operands(
+        random,
+        pairing_request,
+        pairing_response,
+        initiator,
+        responder,
+    );
+    let aes = Aes128::new(reversed(key));
+    reversed(aes.encrypt(xor(aes.encrypt(first_input), second_operand)))
+}
+
+/// The key-independent inputs of `c1` in AES octet order: the first
+/// encryption's input, and the operand XORed into its output.
+fn c1_operands(
+    random: [u8; SMP_KEY_LENGTH],
+    pairing_request: [u8; SMP_PAIRING_FEATURES_PDU_LENGTH],
+    pairing_response: [u8; SMP_PAIRING_FEATURES_PDU_LENGTH],
+    initiator: (DeviceAddress, AddressKind),
+    responder: (DeviceAddress, AddressKind),
+) -> ([u8; SMP_KEY_LENGTH], [u8; SMP_KEY_LENGTH]) {
+    // p1 = pres || preq || rat' || iat' and p2 = padding || ia || ra, built
+    // least significant octet first like the other operands.
+    let mut p1 = [0u8; SMP_KEY_LENGTH];
+    p1[0] = address_type(initiator.1);
+    p1[1] = address_type(responder.1);
+    p1[2..9].copy_from_slice(&pairing_request);
+    p1[9..].copy_from_slice(&pairing_response);
+    let mut p2 = [0u8; SMP_KEY_LENGTH];
+    p2[..6].copy_from_slice(&responder.0.0);
+    p2[6..12].copy_from_slice(&initiator.0.0);
+    (reversed(xor(random, p1)), reversed(p2))
+}
+
+/// Key generation function `s1`; the STK is `s1(TK, Srand, Mrand)`.
+pub fn s1(
+    key: [u8; SMP_KEY_LENGTH],
+    first_random: [u8; SMP_KEY_LENGTH],
+    second_random: [u8; SMP_KEY_LENGTH],
+) -> [u8; SMP_KEY_LENGTH] {
+    // r' = r1' || r2', the least significant 64 bits of each random.
+    let mut random = [0u8; SMP_KEY_LENGTH];
+    random[..8].copy_from_slice(&second_random[..8]);
+    random[8..].copy_from_slice(&first_random[..8]);
+    reversed(Aes128::new(reversed(key)).encrypt(reversed(random)))
+}
+
+/// The TK of a Passkey Entry pairing; passkey 0 is also the Just Works TK.
+pub fn passkey_temporary_key(passkey: u32) -> [u8; SMP_KEY_LENGTH] {
+    let mut key = [0u8; SMP_KEY_LENGTH];
+    key[..4].copy_from_slice(&passkey.to_le_bytes());
+    key
+}
+
+/// Confirm value function `f4` of LE Secure Connections, over the x
+/// coordinates `u` and `v` of two public keys.
+pub fn f4(
+    u: [u8; SMP_PUBLIC_KEY_COORDINATE_LENGTH],
+    v: [u8; SMP_PUBLIC_KEY_COORDINATE_LENGTH],
+    x: [u8; SMP_KEY_LENGTH],
+    z: u8,
+) -> [u8; SMP_KEY_LENGTH] {
+    cmac(x, &[&u, &v, &[z]])
+}
+
+/// Key generation function `f5`, returning the MacKey and the LTK derived
+/// from the DHKey `w`. The addresses are those of the initiator and the
+/// responder.
+pub fn f5(
+    w: [u8; SMP_PUBLIC_KEY_COORDINATE_LENGTH],
+    initiator_nonce: [u8; SMP_KEY_LENGTH],
+    responder_nonce: [u8; SMP_KEY_LENGTH],
+    initiator: (DeviceAddress, AddressKind),
+    responder: (DeviceAddress, AddressKind),
+) -> ([u8; SMP_KEY_LENGTH], [u8; SMP_KEY_LENGTH]) {
+    let key = cmac(F5_SALT, &[&w]);
+    let derive = |counter: u8| {
+        cmac(
+            key,
+            &[
+                &[counter],
+                &F5_KEY_ID,
+                &initiator_nonce,
+                &responder_nonce,
+                &smp_address(initiator),
+                &smp_address(responder),
+                &256u16.to_le_bytes(),
+            ],
+        )
+    };
+    (derive(0), derive(1))
+}
+
+/// Check value function `f6` behind the DHKey Check PDUs. `io_capability`
+/// is the IOcap, AuthReq, OOB flag, and IO Capability of the side computing
+/// the check, least significant octet first.
+pub fn f6(
+    w: [u8; SMP_KEY_LENGTH],
+    first_nonce: [u8; SMP_KEY_LENGTH],
+    second_nonce: [u8; SMP_KEY_LENGTH],
+    r: [u8; SMP_KEY_LENGTH],
+    io_capability: [u8; 3],
+    first_address: (DeviceAddress, AddressKind),
+    second_address: (DeviceAddress, AddressKind),
+) -> [u8; SMP_KEY_LENGTH] {
+    cmac(
+        w,
+        &[
+            &first_nonce,
+            &second_nonce,
+            &r,
+            &io_capability,
+            &smp_address(first_address),
+            &smp_address(second_address),
+        ],
+    )
+}
+
+/// Numeric comparison function `g2`; both devices display its value
+/// modulo 1,000,000.
+pub fn g2(
+    u: [u8; SMP_PUBLIC_KEY_COORDINATE_LENGTH],
+    v: [u8; SMP_PUBLIC_KEY_COORDINATE_LENGTH],
+    x: [u8; SMP_KEY_LENGTH],
+    y: [u8; SMP_KEY_LENGTH],
+) -> u32 {
+    let output = cmac(x, &[&u, &v, &y]);
+    u32::from_le_bytes(output[..4].try_into().expect("four octets"))
+}
+
+/// Link key conversion function `h6` over the 32-bit `key_id`.
+pub fn h6(w: [u8; SMP_KEY_LENGTH], key_id: [u8; 4]) -> [u8; SMP_KEY_LENGTH] {
+    cmac(w, &[&key_id])
+}
+
+/// Link key conversion function `h7`, keyed by `salt`, which replaces the
+/// first `h6` step when both devices set CT2 in their AuthReq.
+pub fn h7(salt: [u8; SMP_KEY_LENGTH], w: [u8; SMP_KEY_LENGTH]) -> [u8; SMP_KEY_LENGTH] {
+    cmac(salt, &[&w])
+}
+
+/// The BR/EDR link key that cross-transport key derivation gives an LE
+/// Secure Connections LTK: `h6(ILK, "lebr")`, with the intermediate key from
+/// `h7` when `ct2` is set and from `h6(LTK, "tmp1")` otherwise.
+pub fn link_key_from_long_term_key(
+    long_term_key: [u8; SMP_KEY_LENGTH],
+    ct2: bool,
+) -> [u8; SMP_KEY_LENGTH] {
+    let intermediate = if ct2 {
+        h7(salt(TMP1_KEY_ID), long_term_key)
+    } else {
+        h6(long_term_key, TMP1_KEY_ID)
+    };
+    h6(intermediate, LEBR_KEY_ID)
+}
+
+/// The LE LTK that cross-transport key derivation gives a BR/EDR Secure
+/// Connections link key: `h6(ILTK, "brle")`, the reverse of
+/// [`link_key_from_long_term_key`] with "tmp2".
+pub fn long_term_key_from_link_key(
+    link_key: [u8; SMP_KEY_LENGTH],
+    ct2: bool,
+) -> [u8; SMP_KEY_LENGTH] {
+    let intermediate = if ct2 {
+        h7(salt(TMP2_KEY_ID), link_key)
+    } else {
+        h6(link_key, TMP2_KEY_ID)
+    };
+    h6(intermediate, BRLE_KEY_ID)
+}
+
+/// Whether a public key is the Secure Connections debug key published in
+/// the Core specification, whose private key is known to everyone.
+pub fn is_debug_public_key(key: &SmpPublicKey) -> bool {
+    key.x == reversed_coordinate(DEBUG_PUBLIC_KEY_X)
+        && key.y == reversed_coordinate(DEBUG_PUBLIC_KEY_Y)
+}
+
+/// The P-256 public key of `private_key`, least significant octet first;
+/// `None` for a zero key.
+pub fn public_key(private_key: [u8; SMP_PUBLIC_KEY_COORDINATE_LENGTH]) -> Option<SmpPublicKey> {
+    crate::crypto::p256_public_key(reversed_coordinate(private_key)).map(|(x, y)| SmpPublicKey {
+        x: reversed_coordinate(x),
+        y: reversed_coordinate(y),
+    })
+}
+
+/// The DHKey a device holding `private_key`, least significant octet first,
+/// shares with the owner of `public`; `None` when `public` is not a P-256
+/// point.
+pub fn diffie_hellman_key(
+    private_key: [u8; SMP_PUBLIC_KEY_COORDINATE_LENGTH],
+    public: &SmpPublicKey,
+) -> Option<[u8; SMP_PUBLIC_KEY_COORDINATE_LENGTH]> {
+    crate::crypto::p256_diffie_hellman(
+        reversed_coordinate(private_key),
+        reversed_coordinate(public.x),
+        reversed_coordinate(public.y),
+    )
+    .map(reversed_coordinate)
+}
+
+/// How LE legacy pairing chose its TK.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LegacyAssociationModel {
+    JustWorks,
+    PasskeyEntry,
+    OutOfBand,
+}
+
+impl LegacyAssociationModel {
+    /// Applies the legacy association rules: OOB when both sides have OOB
+    /// data, Just Works when neither requests MITM protection, and otherwise
+    /// Passkey Entry whenever a keyboard faces a device with any IO.
+    pub fn select(initiator: &SmpPairingFeatures, responder: &SmpPairingFeatures) -> Self {
+        if initiator.oob_data_present && responder.oob_data_present {
+            return Self::OutOfBand;
+        }
+        if !initiator.authentication.mitm() && !responder.authentication.mitm() {
+            return Self::JustWorks;
+        }
+        let keyboard = |capability| {
+            matches!(
+                capability,
+                SmpIoCapability::KeyboardOnly | SmpIoCapability::KeyboardDisplay
+            )
+        };
+        let silent = |capability| capability == SmpIoCapability::NoInputNoOutput;
+        if silent(initiator.io_capability) || silent(responder.io_capability) {
+            Self::JustWorks
+        } else if keyboard(initiator.io_capability) || keyboard(responder.io_capability) {
+            Self::PasskeyEntry
+        } else {
+            Self::JustWorks
+        }
+    }
+}
+
+impl Display for LegacyAssociationModel {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::JustWorks => formatter.write_str("just-works"),
+            Self::PasskeyEntry => formatter.write_str("passkey-entry"),
+            Self::OutOfBand => formatter.write_str("out-of-band"),
+        }
+    }
+}
+
+/// The TK and STK recovered from one legacy pairing.
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub struct LegacyPairingKeys {
+    pub association_model: LegacyAssociationModel,
+    /// Passkey whose TK produced both confirm values; 0 for Just Works.
+    pub passkey: u32,
+    pub temporary_key: [u8; SMP_KEY_LENGTH],
+    /// Masked to the negotiated encryption key size. The central starts
+    /// encryption with it under EDIV 0 and Rand 0.
+    pub short_term_key: [u8; SMP_KEY_LENGTH],
+}
+
+impl std::fmt::Debug for LegacyPairingKeys {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        formatter
+            .debug_struct("LegacyPairingKeys")
+            .field("association_model", &self.association_model)
+            .field("passkey", &"<redacted>")
+            .field("temporary_key", &"<redacted>")
+            .field("short_term_key", &"<redacted>")
+            .finish()
+    }
+}
+
+/// Why an observed pairing's TK was not recovered.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LegacyPairingFailure {
+    /// Both sides set the Secure Connections flag, so the confirm values do
+    /// not depend on a TK.
+    SecureConnections,
+    /// The TK came out of band and is a full 128-bit value.
+    OutOfBand,
+    /// No passkey reproduces the confirm values.
+    NoMatchingPasskey,
+}
+
+impl Display for LegacyPairingFailure {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::SecureConnections => formatter.write_str("secure-connections"),
+            Self::OutOfBand => formatter.write_str("out-of-band"),
+            Self::NoMatchingPasskey => formatter.write_str("no-matching-passkey"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LegacyPairingEvent {
+    /// Reported once both Pairing Random PDUs are observed.
+    KeysRecovered(LegacyPairingKeys),
+    NotRecovered(LegacyPairingFailure),
+    /// An LTK completed by the Central Identification that follows its
+    /// Encryption Information. The entry is addressed to the distributing
+    /// device, which asks for it when it is next encrypted as a peripheral.
+    LongTermKeyDistributed {
+        direction: LinkDirection,
+        key: LongTermKeyEntry,
+    },
+}
+
+/// Follows the SMP PDUs of one connection whose central and peripheral
+/// addresses are known from its CONNECT_IND.
+#[derive(Clone)]
+pub struct LegacyPairingTracker {
+    initiator: (DeviceAddress, AddressKind),
+    responder: (DeviceAddress, AddressKind),
+    pairing_request: Option<[u8; SMP_PAIRING_FEATURES_PDU_LENGTH]>,
+    pairing_response: Option<[u8; SMP_PAIRING_FEATURES_PDU_LENGTH]>,
+    /// Pairing Confirm and Pairing Random values, indexed by direction.
+    confirms: [Option<[u8; SMP_KEY_LENGTH]>; 2],
+    randoms: [Option<[u8; SMP_KEY_LENGTH]>; 2],
+    /// Encryption Information awaiting its Central Identification.
+    distributed_keys: [Option<[u8; SMP_KEY_LENGTH]>; 2],
+}
+
+impl std::fmt::Debug for LegacyPairingTracker {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        formatter
+            .debug_struct("LegacyPairingTracker")
+            .field("initiator", &self.initiator)
+            .field("responder", &self.responder)
+            .field("pairing_request", &self.pairing_request)
+            .field("pairing_response", &self.pairing_response)
+            .finish_non_exhaustive()
+    }
+}
+
+impl LegacyPairingTracker {
+    pub fn new(
+        initiator: (DeviceAddress, AddressKind),
+        responder: (DeviceAddress, AddressKind),
+    ) -> Self {
+        Self {
+            initiator,
+            responder,
+            pairing_request: None,
+            pairing_response: None,
+            confirms: [None; 2],
+            randoms: [None; 2],
+            distributed_keys: [None; 2],
+        }
+    }
+
+    /// Observes one reassembled L2CAP PDU; PDUs outside the SMP channel are
+    /// ignored. A Pairing Request restarts the exchange.
+    pub fn observe(&mut self, pdu: &L2capPdu) -> Result<Option<LegacyPairingEvent>> {
+        let Some(smp) = pdu.smp_pdu()? else {
+            return Ok(None);
+        };
+        let slot = direction_slot(pdu.direction);
+        match smp.decode()? {
+            DecodedSmpPdu::PairingRequest(_) => {
+                *self = Self::new(self.initiator, self.responder);
+                self.pairing_request = Some(features_pdu(&pdu.payload)?);
+            }
+            DecodedSmpPdu::PairingResponse(_) => {
+                self.pairing_response = Some(features_pdu(&pdu.payload)?);
+            }
+            DecodedSmpPdu::PairingConfirm(confirm) => self.confirms[slot] = Some(confirm),
+            DecodedSmpPdu::PairingRandom(random) => {
+                self.randoms[slot] = Some(random);
+                if pdu.direction == LinkDirection::PeripheralToCentral {
+                    return self.recover();
+                }
+            }
+            DecodedSmpPdu::PairingFailed(_) => {
+                *self = Self::new(self.initiator, self.responder);
+            }
+            DecodedSmpPdu::EncryptionInformation(key) => {
+                self.distributed_keys[slot] = Some(key);
+            }
+            DecodedSmpPdu::CentralIdentification(identification) => {
+                let Some(key) = self.distributed_keys[slot].take() else {
+                    return Ok(None);
+                };
+                let distributor = match pdu.direction {
+                    LinkDirection::CentralToPeripheral => self.initiator,
+                    LinkDirection::PeripheralToCentral => self.responder,
+                };
+                return Ok(Some(LegacyPairingEvent::LongTermKeyDistributed {
+                    direction: pdu.direction,
+                    key: LongTermKeyEntry {
+                        address: Some(distributor),
+                        encrypted_diversifier: identification.encrypted_diversifier,
+                        random_number: identification.random,
+                        key,
+                    },
+                }));
+            }
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    fn recover(&self) -> Result<Option<LegacyPairingEvent>> {
+        let central = direction_slot(LinkDirection::CentralToPeripheral);
+        let peripheral = direction_slot(LinkDirection::PeripheralToCentral);
+        let (
+            Some(pairing_request),
+            Some(pairing_response),
+            Some(initiator_confirm),
+            Some(initiator_random),
+            Some(responder_random),
+        ) = (
+            self.pairing_request,
+            self.pairing_response,
+            self.confirms[central],
+            self.randoms[central],
+            self.randoms[peripheral],
+        )
+        else {
+            return Ok(None);
+        };
+        let initiator_features = pairing_features(&pairing_request)?;
+        let responder_features = pairing_features(&pairing_response)?;
+        if initiator_features.authentication.secure_connections()
+            && responder_features.authentication.secure_connections()
+        {
+            return Ok(Some(LegacyPairingEvent::NotRecovered(
+                LegacyPairingFailure::SecureConnections,
+            )));
+        }
+        let association_model =
+            LegacyAssociationModel::select(&initiator_features, &responder_features);
+        if association_model == LegacyAssociationModel::OutOfBand {
+            return Ok(Some(LegacyPairingEvent::NotRecovered(
+                LegacyPairingFailure::OutOfBand,
+            )));
+        }
+
+        // Eight passkeys share each AES call. Only a passkey that reproduces
+        // the initiator's confirm is checked against the responder's.
+        let (first_input, second_operand) = c1_operands(
+            initiator_random,
+            pairing_request,
+            pairing_response,
+            self.initiator,
+            self.responder,
+        );
+        let responder_confirms = |passkey| {
+            self.confirms[peripheral].is_none_or(|responder_confirm| {
+                c1(
+                    passkey_temporary_key(passkey),
+                    responder_random,
+                    pairing_request,
+                    pairing_response,
+                    self.initiator,
+                    self.responder,
+                ) == responder_confirm
+            })
+        };
+        let search = (0..=MAXIMUM_PASSKEY)
+            .step_by(AES_BATCH_BLOCKS)
+            .find_map(|first| {
+                let passkeys: [u32; AES_BATCH_BLOCKS] =
+                    std::array::from_fn(|lane| (first + lane as u32).min(MAXIMUM_PASSKEY));
+                let aes = Aes128Batch::new(
+                    passkeys.map(|passkey| reversed(passkey_temporary_key(passkey))),
+                );
+                let confirms = aes.encrypt(
+                    aes.encrypt([first_input; AES_BATCH_BLOCKS])
+                        .map(|output| xor(output, second_operand)),
+                );
+                passkeys
+                    .into_iter()
+                    .zip(confirms)
+                    .find(|(passkey, confirm)| {
+                        reversed(*confirm) == initiator_confirm && responder_confirms(*passkey)
+                    })
+                    .map(|(passkey, _)| passkey)
+            });
+        let Some(passkey) = search else {
+            return Ok(Some(LegacyPairingEvent::NotRecovered(
+                LegacyPairingFailure::NoMatchingPasskey,
+            )));
+        };
+        let temporary_key = passkey_temporary_key(passkey);
+        let mut short_term_key = s1(temporary_key, responder_random, initiator_random);
+        let key_size = initiator_features
+            .maximum_encryption_key_size
+            .min(responder_features.maximum_encryption_key_size);
+        short_term_key[usize::from(key_size)..].fill(0);
+        Ok(Some(LegacyPairingEvent::KeysRecovered(LegacyPairingKeys {
+            association_model,
+            passkey,
+            temporary_key,
+            short_term_key,
+        })))
+    }
+}
+
+/// How LE Secure Connections pairing authenticated its public keys.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SecureConnectionsAssociationModel {
+    JustWorks,
+    NumericComparison,
+    PasskeyEntry,
+    OutOfBand,
+}
+
+impl SecureConnectionsAssociationModel {
+    /// Applies the Secure Connections rules: OOB when either side has OOB
+    /// data, Just Works without MITM protection or with a side lacking IO,
+    /// Numeric Comparison between two yes/no-capable displays, and otherwise
+    /// Passkey Entry whenever a keyboard is involved.
+    pub fn select(initiator: &SmpPairingFeatures, responder: &SmpPairingFeatures) -> Self {
+        if initiator.oob_data_present || responder.oob_data_present {
+            return Self::OutOfBand;
+        }
+        if !initiator.authentication.mitm() && !responder.authentication.mitm() {
+            return Self::JustWorks;
+        }
+        let confirming = |capability| {
+            matches!(
+                capability,
+                SmpIoCapability::DisplayYesNo | SmpIoCapability::KeyboardDisplay
+            )
+        };
+        let keyboard = |capability| {
+            matches!(
+                capability,
+                SmpIoCapability::KeyboardOnly | SmpIoCapability::KeyboardDisplay
+            )
+        };
+        let (initiator, responder) = (initiator.io_capability, responder.io_capability);
+        if initiator == SmpIoCapability::NoInputNoOutput
+            || responder == SmpIoCapability::NoInputNoOutput
+        {
+            Self::JustWorks
+        } else if confirming(initiator) && confirming(responder) {
+            Self::NumericComparison
+        } else if keyboard(initiator) || keyboard(responder) {
+            Self::PasskeyEntry
+        } else {
+            Self::JustWorks
+        }
+    }
+}
+
+impl Display for SecureConnectionsAssociationModel {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::JustWorks => formatter.write_str("just-works"),
+            Self::NumericComparison => formatter.write_str("numeric-comparison"),
+            Self::PasskeyEntry => formatter.write_str("passkey-entry"),
+            Self::OutOfBand => formatter.write_str("out-of-band"),
+        }
+    }
+}
+
+/// Where the DHKey of a Secure Connections pairing came from.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DiffieHellmanKeySource {
+    /// The side sending in this direction used the debug key pair.
+    DebugKey(LinkDirection),
+    /// Index into the DHKeys given to
+    /// [`SecureConnectionsPairingTracker::with_diffie_hellman_keys`].
+    Supplied(usize),
+}
+
+impl Display for DiffieHellmanKeySource {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DebugKey(direction) => write!(formatter, "debug-key:{direction}"),
+            Self::Supplied(index) => write!(formatter, "supplied:{index}"),
+        }
+    }
+}
+
+/// Keys derived from one Secure Connections pairing.
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub struct SecureConnectionsKeys {
+    pub association_model: SecureConnectionsAssociationModel,
+    /// Recovered bit by bit from the 20 Passkey Entry rounds.
+    pub passkey: Option<u32>,
+    /// The six-digit value both devices displayed for Numeric Comparison.
+    pub comparison_value: Option<u32>,
+    pub diffie_hellman_key_source: DiffieHellmanKeySource,
+    pub diffie_hellman_key: [u8; SMP_PUBLIC_KEY_COORDINATE_LENGTH],
+    pub mac_key: [u8; SMP_KEY_LENGTH],
+    /// Masked to the negotiated encryption key size. Secure Connections
+    /// LTKs are used with EDIV 0 and Rand 0.
+    pub long_term_key: [u8; SMP_KEY_LENGTH],
+    /// Whether both DHKey Check values match the derived MacKey. A debug
+    /// key DHKey is certain, but OOB checks depend on values never sent over
+    /// the air.
+    pub verified: bool,
+    /// The BR/EDR link key derived from the LTK when the Pairing Response
+    /// distributes LinkKey, using `h7` when both devices set CT2.
+    pub link_key: Option<[u8; SMP_KEY_LENGTH]>,
+}
+
+impl std::fmt::Debug for SecureConnectionsKeys {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        formatter
+            .debug_struct("SecureConnectionsKeys")
+            .field("association_model", &self.association_model)
+            .field("diffie_hellman_key_source", &self.diffie_hellman_key_source)
+            .field("verified", &self.verified)
+            .field("diffie_hellman_key", &"<redacted>")
+            .field("mac_key", &"<redacted>")
+            .field("long_term_key", &"<redacted>")
+            .field("link_key", &self.link_key.map(|_| "<redacted>"))
+            .finish_non_exhaustive()
+    }
+}
+
+/// Why no LTK was derived from a Secure Connections pairing.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SecureConnectionsFailure {
+    /// Neither side used the debug key and no supplied DHKey matches the
+    /// DHKey Check values.
+    NoDiffieHellmanKey,
+    /// A public key is not a point on P-256.
+    InvalidPublicKey,
+}
+
+impl Display for SecureConnectionsFailure {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoDiffieHellmanKey => formatter.write_str("no-dhkey"),
+            Self::InvalidPublicKey => formatter.write_str("invalid-public-key"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SecureConnectionsPairingEvent {
+    /// The Pairing Public Key sent in this direction is the debug key.
+    DebugKeyDetected(LinkDirection),
+    /// Reported once both DHKey Check PDUs are observed.
+    KeysDerived(SecureConnectionsKeys),
+    NotDerived(SecureConnectionsFailure),
+}
+
+/// Follows the SMP PDUs of one LE Secure Connections pairing whose central
+/// and peripheral addresses are known from its CONNECT_IND.
+#[derive(Clone)]
+pub struct SecureConnectionsPairingTracker {
+    initiator: (DeviceAddress, AddressKind),
+    responder: (DeviceAddress, AddressKind),
+    diffie_hellman_keys: Vec<[u8; SMP_PUBLIC_KEY_COORDINATE_LENGTH]>,
+    pairing_request: Option<[u8; SMP_PAIRING_FEATURES_PDU_LENGTH]>,
+    pairing_response: Option<[u8; SMP_PAIRING_FEATURES_PDU_LENGTH]>,
+    /// Values indexed by direction. Passkey Entry repeats the confirm and
+    /// random exchange for each of its 20 rounds.
+    public_keys: [Option<SmpPublicKey>; 2],
+    confirms: [Vec<[u8; SMP_KEY_LENGTH]>; 2],
+    randoms: [Vec<[u8; SMP_KEY_LENGTH]>; 2],
+    checks: [Option<[u8; SMP_KEY_LENGTH]>; 2],
+}
+
+impl std::fmt::Debug for SecureConnectionsPairingTracker {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        formatter
+            .debug_struct("SecureConnectionsPairingTracker")
+            .field("initiator", &self.initiator)
+            .field("responder", &self.responder)
+            .field("diffie_hellman_keys", &self.diffie_hellman_keys.len())
+            .field("pairing_request", &self.pairing_request)
+            .field("pairing_response", &self.pairing_response)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SecureConnectionsPairingTracker {
+    pub fn new(
+        initiator: (DeviceAddress, AddressKind),
+        responder: (DeviceAddress, AddressKind),
+    ) -> Self {
+        Self::with_diffie_hellman_keys(initiator, responder, Vec::new())
+    }
+
+    /// Also tries DHKeys learned elsewhere, such as from the LE Generate
+    /// DHKey Complete events of a host's HCI log, least significant octet
+    /// first. The one matching the DHKey Check values is used.
+    pub fn with_diffie_hellman_keys(
+        initiator: (DeviceAddress, AddressKind),
+        responder: (DeviceAddress, AddressKind),
+        diffie_hellman_keys: Vec<[u8; SMP_PUBLIC_KEY_COORDINATE_LENGTH]>,
+    ) -> Self {
+        Self {
+            initiator,
+            responder,
+            diffie_hellman_keys,
+            pairing_request: None,
+            pairing_response: None,
+            public_keys: [None; 2],
+            confirms: [Vec::new(), Vec::new()],
+            randoms: [Vec::new(), Vec::new()],
+            checks: [None; 2],
+        }
+    }
+
+    /// Observes one reassembled L2CAP PDU; PDUs outside the SMP channel are
+    /// ignored. A Pairing Request restarts the exchange.
+    pub fn observe(&mut self, pdu: &L2capPdu) -> Result<Option<SecureConnectionsPairingEvent>> {
+        let Some(smp) = pdu.smp_pdu()? else {
+            return Ok(None);
+        };
+        let slot = direction_slot(pdu.direction);
+        match smp.decode()? {
+            DecodedSmpPdu::PairingRequest(_) => {
+                self.restart();
+                self.pairing_request = Some(features_pdu(&pdu.payload)?);
+            }
+            DecodedSmpPdu::PairingResponse(_) => {
+                self.pairing_response = Some(features_pdu(&pdu.payload)?);
+            }
+            DecodedSmpPdu::PairingPublicKey(key) => {
+                self.public_keys[slot] = Some(key);
+                if is_debug_public_key(&key) {
+                    return Ok(Some(SecureConnectionsPairingEvent::DebugKeyDetected(
+                        pdu.direction,
+                    )));
+                }
+            }
+            DecodedSmpPdu::PairingConfirm(confirm) => self.confirms[slot].push(confirm),
+            DecodedSmpPdu::PairingRandom(random) => self.randoms[slot].push(random),
+            DecodedSmpPdu::PairingDhKeyCheck(check) => {
+                self.checks[slot] = Some(check);
+                if self.checks.iter().all(Option::is_some) {
+                    return self.derive();
+                }
+            }
+            DecodedSmpPdu::PairingFailed(_) => self.restart(),
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    fn restart(&mut self) {
+        let diffie_hellman_keys = std::mem::take(&mut self.diffie_hellman_keys);
+        *self = Self::with_diffie_hellman_keys(self.initiator, self.responder, diffie_hellman_keys);
+    }
+
+    fn derive(&self) -> Result<Option<SecureConnectionsPairingEvent>> {
+        let central = direction_slot(LinkDirection::CentralToPeripheral);
+        let peripheral = direction_slot(LinkDirection::PeripheralToCentral);
+        let (
+            Some(pairing_request),
+            Some(pairing_response),
+            Some(initiator_key),
+            Some(responder_key),
+            Some(&initiator_nonce),
+            Some(&responder_nonce),
+            Some(initiator_check),
+            Some(responder_check),
+        ) = (
+            self.pairing_request,
+            self.pairing_response,
+            self.public_keys[central],
+            self.public_keys[peripheral],
+            self.randoms[central].last(),
+            self.randoms[peripheral].last(),
+            self.checks[central],
+            self.checks[peripheral],
+        )
+        else {
+            return Ok(None);
+        };
+        let initiator_features = pairing_features(&pairing_request)?;
+        let responder_features = pairing_features(&pairing_response)?;
+        let association_model =
+            SecureConnectionsAssociationModel::select(&initiator_features, &responder_features);
+
+        let mut candidates = Vec::new();
+        for (direction, own_key, peer_key) in [
+            (
+                LinkDirection::CentralToPeripheral,
+                &initiator_key,
+                &responder_key,
+            ),
+            (
+                LinkDirection::PeripheralToCentral,
+                &responder_key,
+                &initiator_key,
+            ),
+        ] {
+            if is_debug_public_key(own_key) {
+                let Some(key) =
+                    diffie_hellman_key(reversed_coordinate(DEBUG_PRIVATE_KEY), peer_key)
+                else {
+                    return Ok(Some(SecureConnectionsPairingEvent::NotDerived(
+                        SecureConnectionsFailure::InvalidPublicKey,
+                    )));
+                };
+                candidates.push((DiffieHellmanKeySource::DebugKey(direction), key));
+            }
+        }
+        candidates.extend(
+            self.diffie_hellman_keys
+                .iter()
+                .enumerate()
+                .map(|(index, key)| (DiffieHellmanKeySource::Supplied(index), *key)),
+        );
+
+        let passkey = match association_model {
+            SecureConnectionsAssociationModel::PasskeyEntry => {
+                self.recover_passkey(&initiator_key, &responder_key)
+            }
+            _ => None,
+        };
+        // The checks use r = 0 for Just Works and Numeric Comparison, and
+        // the passkey for Passkey Entry. OOB r values never cross the air.
+        let check_random = match association_model {
+            SecureConnectionsAssociationModel::JustWorks
+            | SecureConnectionsAssociationModel::NumericComparison => Some([0; SMP_KEY_LENGTH]),
+            SecureConnectionsAssociationModel::PasskeyEntry => passkey.map(passkey_temporary_key),
+            SecureConnectionsAssociationModel::OutOfBand => None,
+        };
+        let io_capability = |pdu: [u8; SMP_PAIRING_FEATURES_PDU_LENGTH]| [pdu[1], pdu[2], pdu[3]];
+        let derived = candidates.into_iter().find_map(|(source, dhkey)| {
+            let (mac_key, long_term_key) = f5(
+                dhkey,
+                initiator_nonce,
+                responder_nonce,
+                self.initiator,
+                self.responder,
+            );
+            let verified = check_random.is_some_and(|random| {
+                f6(
+                    mac_key,
+                    initiator_nonce,
+                    responder_nonce,
+                    random,
+                    io_capability(pairing_request),
+                    self.initiator,
+                    self.responder,
+                ) == initiator_check
+                    && f6(
+                        mac_key,
+                        responder_nonce,
+                        initiator_nonce,
+                        random,
+                        io_capability(pairing_response),
+                        self.responder,
+                        self.initiator,
+                    ) == responder_check
+            });
+            let certain = matches!(source, DiffieHellmanKeySource::DebugKey(_));
+            (verified || certain || check_random.is_none()).then_some((
+                source,
+                dhkey,
+                mac_key,
+                long_term_key,
+                verified,
+            ))
+        });
+        let Some((source, dhkey, mac_key, mut long_term_key, verified)) = derived else {
+            return Ok(Some(SecureConnectionsPairingEvent::NotDerived(
+                SecureConnectionsFailure::NoDiffieHellmanKey,
+            )));
+        };
+        let key_size = initiator_features
+            .maximum_encryption_key_size
+            .min(responder_features.maximum_encryption_key_size);
+        long_term_key[usize::from(key_size)..].fill(0);
+        let link_key = (responder_features.initiator_key_distribution.link_key()
+            || responder_features.responder_key_distribution.link_key())
+        .then(|| {
+            link_key_from_long_term_key(
+                long_term_key,
+                initiator_features.authentication.ct2() && responder_features.authentication.ct2(),
+            )
+        });
+        Ok(Some(SecureConnectionsPairingEvent::KeysDerived(
+            SecureConnectionsKeys {
+                association_model,
+                passkey,
+                comparison_value: (association_model
+                    == SecureConnectionsAssociationModel::NumericComparison)
+                    .then(|| {
+                        g2(
+                            initiator_key.x,
+                            responder_key.x,
+                            initiator_nonce,
+                            responder_nonce,
+                        ) % 1_000_000
+                    }),
+                diffie_hellman_key_source: source,
+                diffie_hellman_key: dhkey,
+                mac_key,
+                long_term_key,
+                verified,
+                link_key,
+            },
+        )))
+    }
+
+    /// Each Passkey Entry round commits the initiator to one passkey bit
+    /// with `f4(PKax, PKbx, Nai, 0x80 | bit)`, so both guesses are checked.
+    fn recover_passkey(
+        &self,
+        initiator_key: &SmpPublicKey,
+        responder_key: &SmpPublicKey,
+    ) -> Option<u32> {
+        let central = direction_slot(LinkDirection::CentralToPeripheral);
+        let rounds: Vec<_> = self.confirms[central]
+            .iter()
+            .zip(&self.randoms[central])
+            .collect();
+        if rounds.len() != PASSKEY_ENTRY_ROUNDS {
+            return None;
+        }
+        rounds
+            .into_iter()
+            .enumerate()
+            .try_fold(0u32, |passkey, (round, (confirm, random))| {
+                let bit = [0u8, 1].into_iter().find(|bit| {
+                    f4(initiator_key.x, responder_key.x, *random, 0x80 | bit) == *confirm
+                })?;
+                Some(passkey | u32::from(bit) << round)
+            })
+            .filter(|passkey| *passkey <= MAXIMUM_PASSKEY)
+    }
+}
+
+/// The association model of a pairing of either kind.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PairingMethod {
+    Legacy(LegacyAssociationModel),
+    SecureConnections(SecureConnectionsAssociationModel),
+}
+
+impl PairingMethod {
+    /// Whether the model protects against a man in the middle; only Just
+    /// Works does not.
+    pub const fn authenticated(self) -> bool {
+        !matches!(
+            self,
+            Self::Legacy(LegacyAssociationModel::JustWorks)
+                | Self::SecureConnections(SecureConnectionsAssociationModel::JustWorks)
+        )
+    }
+}
+
+impl Display for PairingMethod {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Legacy(model) => write!(formatter, "legacy:{model}"),
+            Self::SecureConnections(model) => write!(formatter, "secure-connections:{model}"),
+        }
+    }
+}
+
+/// The levels of LE security mode 1.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum SecurityLevel {
+    /// Level 1: no pairing completed.
+    NoSecurity = 1,
+    /// Level 2: encryption after Just Works pairing.
+    UnauthenticatedEncryption = 2,
+    /// Level 3: encryption after an authenticated pairing.
+    AuthenticatedEncryption = 3,
+    /// Level 4: encryption with a 128-bit key after an authenticated Secure
+    /// Connections pairing.
+    AuthenticatedSecureConnections = 4,
+}
+
+impl Display for SecurityLevel {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(formatter, "{}", *self as u8)
+    }
+}
+
+/// A weak property of a negotiated pairing.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PairingWeakness {
+    /// The negotiated encryption key is shorter than 16 octets.
+    ShortKey { key_size: u8 },
+    /// Neither device set the MITM flag.
+    MitmNotRequired,
+    /// A device asked for MITM protection, but the IO capabilities left
+    /// only Just Works.
+    MitmNotProvided,
+    /// LE legacy pairing with a Just Works or passkey TK, which an observer
+    /// of the pairing recovers.
+    LegacyPairing,
+    /// The device sending in this direction used the published debug key.
+    DebugKey(LinkDirection),
+}
+
+impl Display for PairingWeakness {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ShortKey { key_size } => write!(formatter, "short-key:{key_size}"),
+            Self::MitmNotRequired => formatter.write_str("mitm-not-required"),
+            Self::MitmNotProvided => formatter.write_str("mitm-not-provided"),
+            Self::LegacyPairing => formatter.write_str("legacy-pairing"),
+            Self::DebugKey(direction) => write!(formatter, "debug-key:{direction}"),
+        }
+    }
+}
+
+/// The phases of one pairing.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PairingSessionState {
+    Idle,
+    /// A Pairing Request awaits its Pairing Response.
+    FeatureExchange,
+    /// Confirm, random, public key, and DHKey Check values are exchanged.
+    Authentication,
+    /// The link is encrypted and negotiated keys are outstanding.
+    KeyDistribution,
+    Complete,
+    Failed,
+}
+
+impl Display for PairingSessionState {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        formatter.write_str(match self {
+            Self::Idle => "idle",
+            Self::FeatureExchange => "feature-exchange",
+            Self::Authentication => "authentication",
+            Self::KeyDistribution => "key-distribution",
+            Self::Complete => "complete",
+            Self::Failed => "failed",
+        })
+    }
+}
+
+/// What one pairing negotiated and reached.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PairingSessionSummary {
+    pub state: PairingSessionState,
+    pub initiator_features: Option<SmpPairingFeatures>,
+    pub responder_features: Option<SmpPairingFeatures>,
+    /// Known once both feature PDUs are observed.
+    pub method: Option<PairingMethod>,
+    pub key_size: Option<u8>,
+    /// Keys each device sent during key distribution, in Key Distribution
+    /// bits. An LTK counts once its Central Identification follows, an IRK
+    /// once its Identity Address Information follows.
+    pub initiator_distributed: SmpKeyDistribution,
+    pub responder_distributed: SmpKeyDistribution,
+    pub failure: Option<(LinkDirection, SmpPairingFailure)>,
+    /// The level the link reaches; level 1 until authentication finishes.
+    pub security_level: SecurityLevel,
+    pub weaknesses: Vec<PairingWeakness>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PairingSessionEvent {
+    /// Authentication finished and every negotiated key was distributed.
+    Completed(PairingSessionSummary),
+    /// A device sent Pairing Failed.
+    Failed(PairingSessionSummary),
+}
+
+/// Follows the phases of one connection's pairings without any key
+/// material. A Pairing Request starts a new session.
+#[derive(Clone, Debug)]
+pub struct PairingSessionTracker {
+    state: PairingSessionState,
+    initiator_features: Option<SmpPairingFeatures>,
+    responder_features: Option<SmpPairingFeatures>,
+    /// Flags indexed by direction.
+    randoms: [bool; 2],
+    checks: [bool; 2],
+    debug_keys: [bool; 2],
+    distributed: [u8; 2],
+    failure: Option<(LinkDirection, SmpPairingFailure)>,
+}
+
+impl Default for PairingSessionTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PairingSessionTracker {
+    pub const fn new() -> Self {
+        Self {
+            state: PairingSessionState::Idle,
+            initiator_features: None,
+            responder_features: None,
+            randoms: [false; 2],
+            checks: [false; 2],
+            debug_keys: [false; 2],
+            distributed: [0; 2],
+            failure: None,
+        }
+    }
+
+    pub const fn state(&self) -> PairingSessionState {
+        self.state
+    }
+
+    /// Observes one reassembled L2CAP PDU; PDUs outside the SMP channel are
+    /// ignored.
+    pub fn observe(&mut self, pdu: &L2capPdu) -> Result<Option<PairingSessionEvent>> {
+        let Some(smp) = pdu.smp_pdu()? else {
+            return Ok(None);
+        };
+        let slot = direction_slot(pdu.direction);
+        match smp.decode()? {
+            DecodedSmpPdu::PairingRequest(features) => {
+                *self = Self::new();
+                self.initiator_features = Some(features);
+                self.state = PairingSessionState::FeatureExchange;
+            }
+            DecodedSmpPdu::PairingResponse(features) => {
+                self.responder_features = Some(features);
+                self.state = PairingSessionState::Authentication;
+            }
+            DecodedSmpPdu::PairingPublicKey(key) => {
+                self.debug_keys[slot] |= is_debug_public_key(&key);
+            }
+            DecodedSmpPdu::PairingRandom(_) => {
+                self.randoms[slot] = true;
+                if !self.secure_connections() && self.randoms == [true; 2] {
+                    return Ok(self.authenticated());
+                }
+            }
+            DecodedSmpPdu::PairingDhKeyCheck(_) => {
+                self.checks[slot] = true;
+                if self.secure_connections() && self.checks == [true; 2] {
+                    return Ok(self.authenticated());
+                }
+            }
+            DecodedSmpPdu::PairingFailed(failure) => {
+                self.failure = Some((pdu.direction, failure));
+                self.state = PairingSessionState::Failed;
+                return Ok(Some(PairingSessionEvent::Failed(self.summary())));
+            }
+            DecodedSmpPdu::CentralIdentification(_) => return Ok(self.distributed(slot, 0x01)),
+            DecodedSmpPdu::IdentityAddressInformation(_) => {
+                return Ok(self.distributed(slot, 0x02));
+            }
+            DecodedSmpPdu::SigningInformation(_) => return Ok(self.distributed(slot, 0x04)),
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    /// The session so far, or as it ended.
+    pub fn summary(&self) -> PairingSessionSummary {
+        let method =
+            self.initiator_features
+                .zip(self.responder_features)
+                .map(|(initiator, responder)| {
+                    if self.secure_connections() {
+                        PairingMethod::SecureConnections(SecureConnectionsAssociationModel::select(
+                            &initiator, &responder,
+                        ))
+                    } else {
+                        PairingMethod::Legacy(LegacyAssociationModel::select(
+                            &initiator, &responder,
+                        ))
+                    }
+                });
+        let key_size =
+            self.initiator_features
+                .zip(self.responder_features)
+                .map(|(initiator, responder)| {
+                    initiator
+                        .maximum_encryption_key_size
+                        .min(responder.maximum_encryption_key_size)
+                });
+        let security_level = match (self.state, method) {
+            (
+                PairingSessionState::KeyDistribution | PairingSessionState::Complete,
+                Some(method),
+            ) => {
+                if !method.authenticated() {
+                    SecurityLevel::UnauthenticatedEncryption
+                } else if matches!(method, PairingMethod::SecureConnections(_))
+                    && key_size == Some(16)
+                {
+                    SecurityLevel::AuthenticatedSecureConnections
+                } else {
+                    SecurityLevel::AuthenticatedEncryption
+                }
+            }
+            _ => SecurityLevel::NoSecurity,
+        };
+
+        let mut weaknesses = Vec::new();
+        if let Some(key_size) = key_size.filter(|key_size| *key_size < 16) {
+            weaknesses.push(PairingWeakness::ShortKey { key_size });
+        }
+        if let (Some(initiator), Some(responder), Some(method)) =
+            (self.initiator_features, self.responder_features, method)
+        {
+            if !initiator.authentication.mitm() && !responder.authentication.mitm() {
+                weaknesses.push(PairingWeakness::MitmNotRequired);
+            } else if !method.authenticated() {
+                weaknesses.push(PairingWeakness::MitmNotProvided);
+            }
+            if matches!(
+                method,
+                PairingMethod::Legacy(
+                    LegacyAssociationModel::JustWorks | LegacyAssociationModel::PasskeyEntry
+                )
+            ) {
+                weaknesses.push(PairingWeakness::LegacyPairing);
+            }
+        }
+        for direction in [
+            LinkDirection::CentralToPeripheral,
+            LinkDirection::PeripheralToCentral,
+        ] {
+            if self.debug_keys[direction_slot(direction)] {
+                weaknesses.push(PairingWeakness::DebugKey(direction));
+            }
+        }
+
+        let central = direction_slot(LinkDirection::CentralToPeripheral);
+        let peripheral = direction_slot(LinkDirection::PeripheralToCentral);
+        PairingSessionSummary {
+            state: self.state,
+            initiator_features: self.initiator_features,
+            responder_features: self.responder_features,
+            method,
+            key_size,
+            initiator_distributed: SmpKeyDistribution {
+                raw: self.distributed[central],
+            },
+            responder_distributed: SmpKeyDistribution {
+                raw: self.distributed[peripheral],
+            },
+            failure: self.failure,
+            security_level,
+            weaknesses,
+        }
+    }
+
+    fn secure_connections(&self) -> bool {
+        self.initiator_features
+            .zip(self.responder_features)
+            .is_some_and(|(initiator, responder)| {
+                initiator.authentication.secure_connections()
+                    && responder.authentication.secure_connections()
+            })
+    }
+
+    fn authenticated(&mut self) -> Option<PairingSessionEvent> {
+        if self.state != PairingSessionState::Authentication {
+            return None;
+        }
+        self.state = PairingSessionState::KeyDistribution;
+        self.complete_if_distributed()
+    }
+
+    fn distributed(&mut self, slot: usize, key: u8) -> Option<PairingSessionEvent> {
+        self.distributed[slot] |= key;
+        self.complete_if_distributed()
+    }
+
+    /// Completes the session once each device sent the keys the Pairing
+    /// Response assigned it. Secure Connections derives the LTK instead of
+    /// distributing it, and link keys are never sent over SMP.
+    fn complete_if_distributed(&mut self) -> Option<PairingSessionEvent> {
+        if self.state != PairingSessionState::KeyDistribution {
+            return None;
+        }
+        let responder = self.responder_features?;
+        let sent = if self.secure_connections() {
+            0x06
+        } else {
+            0x07
+        };
+        let expected = [
+            responder.initiator_key_distribution.raw & sent,
+            responder.responder_key_distribution.raw & sent,
+        ];
+        let central = direction_slot(LinkDirection::CentralToPeripheral);
+        let peripheral = direction_slot(LinkDirection::PeripheralToCentral);
+        if self.distributed[central] & expected[0] != expected[0]
+            || self.distributed[peripheral] & expected[1] != expected[1]
+        {
+            return None;
+        }
+        self.state = PairingSessionState::Complete;
+        Some(PairingSessionEvent::Completed(self.summary()))
+    }
+}
+
+fn features_pdu(payload: &[u8]) -> Result<[u8; SMP_PAIRING_FEATURES_PDU_LENGTH]> {
+    payload.try_into().map_err(|_| {
+        Error::InvalidInput(format!(
+            "SMP pairing features PDU has {} octets, expected {SMP_PAIRING_FEATURES_PDU_LENGTH}",
+            payload.len()
+        ))
+    })
+}
+
+fn pairing_features(pdu: &[u8; SMP_PAIRING_FEATURES_PDU_LENGTH]) -> Result<SmpPairingFeatures> {
+    match SmpPdu::parse(pdu)?.decode()? {
+        DecodedSmpPdu::PairingRequest(features) | DecodedSmpPdu::PairingResponse(features) => {
+            Ok(features)
+        }
+        _ => Err(Error::InvalidInput(
+            "SMP pairing features PDU is not a Pairing Request or Response".to_owned(),
+        )),
+    }
+}
+
+const fn address_type(kind: AddressKind) -> u8 {
+    match kind {
+        AddressKind::Public => 0,
+        AddressKind::Random => 1,
+    }
+}
+
+const fn direction_slot(direction: LinkDirection) -> usize {
+    match direction {
+        LinkDirection::CentralToPeripheral => 0,
+        LinkDirection::PeripheralToCentral => 1,
+    }
+}
+
+/// AES-CMAC over operands given least significant octet first, each placed
+/// most significant octet first in the message as the Core functions define.
+fn cmac(key: [u8; SMP_KEY_LENGTH], operands: &[&[u8]]) -> [u8; SMP_KEY_LENGTH] {
+    let message: Vec<u8> = operands
+        .iter()
+        .flat_map(|operand| operand.iter().rev().copied())
+        .collect();
+    reversed(crate::crypto::aes_cmac(reversed(key), &message))
+}
+
+/// A 56-bit address operand: the address type above the device address.
+fn smp_address((address, kind): (DeviceAddress, AddressKind)) -> [u8; 7] {
+    let mut operand = [0u8; 7];
+    operand[..6].copy_from_slice(&address.0);
+    operand[6] = address_type(kind);
+    operand
+}
+
+fn salt(key_id: [u8; 4]) -> [u8; SMP_KEY_LENGTH] {
+    let mut salt = [0u8; SMP_KEY_LENGTH];
+    salt[..4].copy_from_slice(&key_id);
+    salt
+}
+
+fn reversed_coordinate(
+    mut value: [u8; SMP_PUBLIC_KEY_COORDINATE_LENGTH],
+) -> [u8; SMP_PUBLIC_KEY_COORDINATE_LENGTH] {
+    value.reverse();
+    value
+}
+
+fn reversed(mut value: [u8; SMP_KEY_LENGTH]) -> [u8; SMP_KEY_LENGTH] {
+    value.reverse();
+    value
+}
+
+fn xor(left: [u8; SMP_KEY_LENGTH], right: [u8; SMP_KEY_LENGTH]) -> [u8; SMP_KEY_LENGTH] {
+    std::array::from_fn(|index| left[index] ^ right[index])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes a value given most significant octet first in SMP field order.
+    fn field<const N: usize>(hex: &str) -> [u8; N] {
+        let mut value: [u8; N] = std::array::from_fn(|index| {
+            u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16).unwrap()
+        });
+        value.reverse();
+        value
+    }
+
+    fn initiator() -> (DeviceAddress, AddressKind) {
+        (DeviceAddress(field("a1a2a3a4a5a6")), AddressKind::Random)
+    }
+
+    fn responder() -> (DeviceAddress, AddressKind) {
+        (DeviceAddress(field("b1b2b3b4b5b6")), AddressKind::Public)
+    }
+
+    #[test]
+    fn c1_and_s1_match_the_core_specification_sample_data() {
+        assert_eq!(
+            c1(
+                [0; 16],
+                field("5783d52156ad6f0e6388274ec6702ee0"),
+                field("07071000000101"),
+                field("05000800000302"),
+                initiator(),
+                responder(),
+            ),
+            field("1e1e3fef878988ead2a74dc5bef13b86")
+        );
+        assert_eq!(
+            s1(
+                [0; 16],
+                field("000f0e0d0c0b0a091122334455667788"),
+                field("010203040506070899aabbccddeeff00"),
+            ),
+            field("9a1fe1f0e8b0f49b5b4216ae796da062")
+        );
+    }
+
+    #[test]
+    fn cross_transport_functions_match_the_core_specification_sample_data() {
+        let w = field("ec0234a357c8ad05341010a60a397d9b");
+        assert_eq!(
+            h6(w, LEBR_KEY_ID),
+            field("2d9ae102e76dc91ce8d3a9e280b16399")
+        );
+        assert_eq!(
+            h7(salt(TMP1_KEY_ID), w),
+            field("fb173597c6a3c0ecd2998c2a75a57011")
+        );
+
+        let long_term_key = field("368df9bce3264b58bd066c33334fbf64");
+        assert_eq!(
+            link_key_from_long_term_key(long_term_key, false),
+            field("bc1ca4ef633fc1bd0d8230afee388fb0")
+        );
+        assert_eq!(
+            link_key_from_long_term_key(long_term_key, true),
+            field("287ad379dca402530a39f1f43047b835")
+        );
+        let link_key = field("05040302010009080706050403020100");
+        assert_eq!(
+            long_term_key_from_link_key(link_key, false),
+            field("a813fb72f1a3dfa18a2c9a43f10d0a30")
+        );
+        assert_eq!(
+            long_term_key_from_link_key(link_key, true),
+            field("e85e09eb5eccb3e269418a133211bc79")
+        );
+    }
+
+    #[test]
+    fn secure_connections_functions_match_the_core_specification_sample_data() {
+        let u = field("20b003d2f297be2c5e2c83a7e9f9a5b9eff49111acf4fddbcc0301480e359de6");
+        let v = field("55188b3d32f6bb9a900afcfbeed4e72a59cb9ac2f19d7cfb6b4fdd49f47fc5fd");
+        let initiator_nonce = field("d5cb8454d177733effffb2ec712baeab");
+        let responder_nonce = field("a6e8e7cc25a75f6e216583f7ff3dc4cf");
+        let first = (DeviceAddress(field("56123737bfce")), AddressKind::Public);
+        let second = (DeviceAddress(field("a713702dcfc1")), AddressKind::Public);
+        assert_eq!(
+            f4(u, v, initiator_nonce, 0),
+            field("f2c916f107a9bd1cf1eda1bea974872d")
+        );
+        let (mac_key, long_term_key) = f5(
+            field("ec0234a357c8ad05341010a60a397d9b99796b13b4f866f1868d34f373bfa698"),
+            initiator_nonce,
+            responder_nonce,
+            first,
+            second,
+        );
+        assert_eq!(mac_key, field("2965f176a1084a02fd3f6a20ce636e20"));
+        assert_eq!(long_term_key, field("6986791169d7cd23980522b594750a38"));
+        assert_eq!(
+            f6(
+                mac_key,
+                initiator_nonce,
+                responder_nonce,
+                field("12a3343bb453bb5408da42d20c2d0fc8"),
+                field("010102"),
+                first,
+                second,
+            ),
+            field("e3c473989cd0e8c5d26c0b09da958f61")
+        );
+        assert_eq!(g2(u, v, initiator_nonce, responder_nonce), 0x2f9e_d5ba);
+    }
+
+    fn smp(direction: LinkDirection, payload: &[u8]) -> L2capPdu {
+        L2capPdu {
+            direction,
+            channel_id: crate::smp::LE_SMP_FIXED_CHANNEL_ID,
+            payload: payload.to_vec(),
+            fragment_count: 1,
+        }
+    }
+
+    fn with_code(code: u8, value: [u8; 16]) -> Vec<u8> {
+        std::iter::once(code).chain(value).collect()
+    }
+
+    #[test]
+    fn recovers_a_passkey_and_captures_the_distributed_long_term_key() {
+        // KeyboardOnly central, DisplayOnly peripheral, MITM requested.
+        let request = [0x01, 0x02, 0x00, 0x05, 0x10, 0x00, 0x01];
+        let response = [0x02, 0x00, 0x00, 0x05, 0x07, 0x00, 0x01];
+        let temporary_key = passkey_temporary_key(4_321);
+        let initiator_random = [0x11; 16];
+        let responder_random = [0x22; 16];
+        let confirm = |random| {
+            c1(
+                temporary_key,
+                random,
+                request,
+                response,
+                initiator(),
+                responder(),
+            )
+        };
+        let mut tracker = LegacyPairingTracker::new(initiator(), responder());
+        let central = LinkDirection::CentralToPeripheral;
+        let peripheral = LinkDirection::PeripheralToCentral;
+        for pdu in [
+            smp(central, &request),
+            smp(peripheral, &response),
+            smp(central, &with_code(0x03, confirm(initiator_random))),
+            smp(peripheral, &with_code(0x03, confirm(responder_random))),
+            smp(central, &with_code(0x04, initiator_random)),
+        ] {
+            assert_eq!(tracker.observe(&pdu).unwrap(), None);
+        }
+        let Some(LegacyPairingEvent::KeysRecovered(keys)) = tracker
+            .observe(&smp(peripheral, &with_code(0x04, responder_random)))
+            .unwrap()
+        else {
+            panic!("the passkey must be recovered");
+        };
+        assert_eq!(keys.association_model, LegacyAssociationModel::PasskeyEntry);
+        assert_eq!(keys.passkey, 4_321);
+        let mut expected = s1(temporary_key, responder_random, initiator_random);
+        expected[7..].fill(0);
+        assert_eq!(keys.short_term_key, expected);
+
+        let long_term_key = [0x33; 16];
+        assert_eq!(
+            tracker
+                .observe(&smp(peripheral, &with_code(0x06, long_term_key)))
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            tracker
+                .observe(&smp(
+                    peripheral,
+                    &[0x07, 0x34, 0x12, 1, 2, 3, 4, 5, 6, 7, 8]
+                ))
+                .unwrap(),
+            Some(LegacyPairingEvent::LongTermKeyDistributed {
+                direction: peripheral,
+                key: LongTermKeyEntry {
+                    address: Some(responder()),
+                    encrypted_diversifier: 0x1234,
+                    random_number: [1, 2, 3, 4, 5, 6, 7, 8],
+                    key: long_term_key,
+                },
+            })
+        );
+    }
+
+    /// Builds a Secure Connections exchange between two key pairs, returning
+    /// its SMP PDUs and the unmasked LTK.
+    fn secure_connections_exchange(
+        request: [u8; 7],
+        response: [u8; 7],
+        initiator_private: [u8; 32],
+        responder_private: [u8; 32],
+        passkey: Option<u32>,
+    ) -> (Vec<L2capPdu>, [u8; 16]) {
+        let central = LinkDirection::CentralToPeripheral;
+        let peripheral = LinkDirection::PeripheralToCentral;
+        let initiator_key = public_key(initiator_private).unwrap();
+        let responder_key = public_key(responder_private).unwrap();
+        let public = |key: SmpPublicKey| -> Vec<u8> {
+            std::iter::once(0x0c).chain(key.x).chain(key.y).collect()
+        };
+        let mut pdus = vec![
+            smp(central, &request),
+            smp(peripheral, &response),
+            smp(central, &public(initiator_key)),
+            smp(peripheral, &public(responder_key)),
+        ];
+        let mut nonces = ([0u8; 16], [0u8; 16]);
+        match passkey {
+            Some(passkey) => {
+                for round in 0..20u8 {
+                    let bit = 0x80 | (passkey >> round & 1) as u8;
+                    nonces = ([round; 16], [round | 0x40; 16]);
+                    pdus.extend([
+                        smp(
+                            central,
+                            &with_code(0x03, f4(initiator_key.x, responder_key.x, nonces.0, bit)),
+                        ),
+                        smp(
+                            peripheral,
+                            &with_code(0x03, f4(responder_key.x, initiator_key.x, nonces.1, bit)),
+                        ),
+                        smp(central, &with_code(0x04, nonces.0)),
+                        smp(peripheral, &with_code(0x04, nonces.1)),
+                    ]);
+                }
+            }
+            None => {
+                nonces = ([0x5a; 16], [0xa5; 16]);
+                pdus.extend([
+                    smp(
+                        peripheral,
+                        &with_code(0x03, f4(responder_key.x, initiator_key.x, nonces.1, 0)),
+                    ),
+                    smp(central, &with_code(0x04, nonces.0)),
+                    smp(peripheral, &with_code(0x04, nonces.1)),
+                ]);
+            }
+        }
+        let dhkey = diffie_hellman_key(initiator_private, &responder_key).unwrap();
+        let (mac_key, long_term_key) = f5(dhkey, nonces.0, nonces.1, initiator(), responder());
+        let random = passkey_temporary_key(passkey.unwrap_or(0));
+        pdus.extend([
+            smp(
+                central,
+                &with_code(
+                    0x0d,
+                    f6(
+                        mac_key,
+                        nonces.0,
+                        nonces.1,
+                        random,
+                        [request[1], request[2], request[3]],
+                        initiator(),
+                        responder(),
+                    ),
+                ),
+            ),
+            smp(
+                peripheral,
+                &with_code(
+                    0x0d,
+                    f6(
+                        mac_key,
+                        nonces.1,
+                        nonces.0,
+                        random,
+                        [response[1], response[2], response[3]],
+                        responder(),
+                        initiator(),
+                    ),
+                ),
+            ),
+        ]);
+        (pdus, long_term_key)
+    }
+
+    fn observe_all(
+        tracker: &mut SecureConnectionsPairingTracker,
+        pdus: &[L2capPdu],
+    ) -> Vec<SecureConnectionsPairingEvent> {
+        pdus.iter()
+            .filter_map(|pdu| tracker.observe(pdu).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn derives_the_ltk_of_a_debug_key_pairing_and_verifies_its_dhkey_checks() {
+        let mut debug_private = DEBUG_PRIVATE_KEY;
+        debug_private.reverse();
+        let request = [0x01, 0x03, 0x00, 0x09, 0x10, 0x00, 0x00];
+        let response = [0x02, 0x03, 0x00, 0x09, 0x10, 0x00, 0x00];
+        let (pdus, long_term_key) =
+            secure_connections_exchange(request, response, [0x11; 32], debug_private, None);
+        let mut tracker = SecureConnectionsPairingTracker::new(initiator(), responder());
+        let events = observe_all(&mut tracker, &pdus);
+        assert_eq!(
+            events[0],
+            SecureConnectionsPairingEvent::DebugKeyDetected(LinkDirection::PeripheralToCentral)
+        );
+        let SecureConnectionsPairingEvent::KeysDerived(keys) = events[1] else {
+            panic!("the debug key DHKey must derive the LTK");
+        };
+        assert_eq!(
+            keys.association_model,
+            SecureConnectionsAssociationModel::JustWorks
+        );
+        assert_eq!(
+            keys.diffie_hellman_key_source,
+            DiffieHellmanKeySource::DebugKey(LinkDirection::PeripheralToCentral)
+        );
+        assert!(keys.verified);
+        assert_eq!(keys.long_term_key, long_term_key);
+    }
+
+    #[test]
+    fn selects_the_supplied_dhkey_that_verifies_and_recovers_the_passkey() {
+        let request = [0x01, 0x02, 0x00, 0x0d, 0x10, 0x00, 0x00];
+        let response = [0x02, 0x00, 0x00, 0x0d, 0x07, 0x00, 0x00];
+        let (pdus, long_term_key) =
+            secure_connections_exchange(request, response, [0x11; 32], [0x22; 32], Some(654_321));
+        let dhkey = diffie_hellman_key([0x22; 32], &public_key([0x11; 32]).unwrap()).unwrap();
+
+        let mut without = SecureConnectionsPairingTracker::new(initiator(), responder());
+        assert_eq!(
+            observe_all(&mut without, &pdus),
+            [SecureConnectionsPairingEvent::NotDerived(
+                SecureConnectionsFailure::NoDiffieHellmanKey
+            )]
+        );
+        let mut tracker = SecureConnectionsPairingTracker::with_diffie_hellman_keys(
+            initiator(),
+            responder(),
+            vec![[0x33; 32], dhkey],
+        );
+        let [SecureConnectionsPairingEvent::KeysDerived(keys)] =
+            observe_all(&mut tracker, &pdus)[..]
+        else {
+            panic!("the supplied DHKey must derive the LTK");
+        };
+        assert_eq!(
+            keys.association_model,
+            SecureConnectionsAssociationModel::PasskeyEntry
+        );
+        assert_eq!(keys.passkey, Some(654_321));
+        assert_eq!(
+            keys.diffie_hellman_key_source,
+            DiffieHellmanKeySource::Supplied(1)
+        );
+        assert!(keys.verified);
+        let mut expected = long_term_key;
+        expected[7..].fill(0);
+        assert_eq!(keys.long_term_key, expected);
+    }
+
+    #[test]
+    fn reports_pairings_whose_temporary_key_cannot_be_searched() {
+        let mut tracker = LegacyPairingTracker::new(initiator(), responder());
+        let central = LinkDirection::CentralToPeripheral;
+        for pdu in [
+            smp(central, &[0x01, 0x03, 0x01, 0x01, 0x10, 0x00, 0x00]),
+            smp(
+                LinkDirection::PeripheralToCentral,
+                &[0x02, 0x03, 0x01, 0x01, 0x10, 0x00, 0x00],
+            ),
+            smp(central, &with_code(0x03, [0; 16])),
+            smp(central, &with_code(0x04, [0; 16])),
+        ] {
+            tracker.observe(&pdu).unwrap();
+        }
+        assert_eq!(
+            tracker
+                .observe(&smp(
+                    LinkDirection::PeripheralToCentral,
+                    &with_code(0x04, [0; 16])
+                ))
+                .unwrap(),
+            Some(LegacyPairingEvent::NotRecovered(
+                LegacyPairingFailure::OutOfBand
+            ))
+        );
+    }
+
+    #[test]
+    fn summarizes_a_legacy_pairing_once_every_negotiated_key_is_distributed() {
+        // KeyboardOnly central, DisplayOnly peripheral, MITM requested, a
+        // 7-octet key, the central sending its LTK and the peripheral its LTK
+        // and IRK.
+        let central = LinkDirection::CentralToPeripheral;
+        let peripheral = LinkDirection::PeripheralToCentral;
+        let central_identification = [0x07, 0x34, 0x12, 1, 2, 3, 4, 5, 6, 7, 8];
+        let identity_address = [0x09, 0x00, 1, 2, 3, 4, 5, 6];
+        let mut tracker = PairingSessionTracker::new();
+        for (pdu, state) in [
+            (
+                smp(central, &[0x01, 0x02, 0x00, 0x05, 0x10, 0x01, 0x03]),
+                PairingSessionState::FeatureExchange,
+            ),
+            (
+                smp(peripheral, &[0x02, 0x00, 0x00, 0x05, 0x07, 0x01, 0x03]),
+                PairingSessionState::Authentication,
+            ),
+            (
+                smp(central, &with_code(0x03, [1; 16])),
+                PairingSessionState::Authentication,
+            ),
+            (
+                smp(peripheral, &with_code(0x03, [2; 16])),
+                PairingSessionState::Authentication,
+            ),
+            (
+                smp(central, &with_code(0x04, [3; 16])),
+                PairingSessionState::Authentication,
+            ),
+            (
+                smp(peripheral, &with_code(0x04, [4; 16])),
+                PairingSessionState::KeyDistribution,
+            ),
+            (
+                smp(peripheral, &with_code(0x06, [5; 16])),
+                PairingSessionState::KeyDistribution,
+            ),
+            (
+                smp(peripheral, &central_identification),
+                PairingSessionState::KeyDistribution,
+            ),
+            (
+                smp(peripheral, &with_code(0x08, [6; 16])),
+                PairingSessionState::KeyDistribution,
+            ),
+            (
+                smp(peripheral, &identity_address),
+                PairingSessionState::KeyDistribution,
+            ),
+            (
+                smp(central, &with_code(0x06, [7; 16])),
+                PairingSessionState::KeyDistribution,
+            ),
+        ] {
+            assert_eq!(tracker.observe(&pdu).unwrap(), None);
+            assert_eq!(tracker.state(), state);
+        }
+        let Some(PairingSessionEvent::Completed(summary)) = tracker
+            .observe(&smp(central, &central_identification))
+            .unwrap()
+        else {
+            panic!("the pairing must complete with the central's LTK");
+        };
+        assert_eq!(summary.state, PairingSessionState::Complete);
+        assert_eq!(
+            summary.method,
+            Some(PairingMethod::Legacy(LegacyAssociationModel::PasskeyEntry))
+        );
+        assert_eq!(summary.key_size, Some(7));
+        assert_eq!(summary.initiator_distributed.raw, 0x01);
+        assert_eq!(summary.responder_distributed.raw, 0x03);
+        assert_eq!(summary.failure, None);
+        assert_eq!(
+            summary.security_level,
+            SecurityLevel::AuthenticatedEncryption
+        );
+        assert_eq!(
+            summary.weaknesses,
+            [
+                PairingWeakness::ShortKey { key_size: 7 },
+                PairingWeakness::LegacyPairing
+            ]
+        );
+    }
+
+    #[test]
+    fn rates_secure_connections_pairings_and_flags_the_debug_key() {
+        let mut debug_private = DEBUG_PRIVATE_KEY;
+        debug_private.reverse();
+        let request = [0x01, 0x03, 0x00, 0x09, 0x10, 0x00, 0x00];
+        let response = [0x02, 0x03, 0x00, 0x09, 0x10, 0x00, 0x00];
+        let (pdus, _) =
+            secure_connections_exchange(request, response, [0x11; 32], debug_private, None);
+        let mut tracker = PairingSessionTracker::new();
+        let events: Vec<_> = pdus
+            .iter()
+            .filter_map(|pdu| tracker.observe(pdu).unwrap())
+            .collect();
+        let [PairingSessionEvent::Completed(summary)] = &events[..] else {
+            panic!("a pairing distributing no keys completes with its DHKey checks");
+        };
+        assert_eq!(
+            summary.method,
+            Some(PairingMethod::SecureConnections(
+                SecureConnectionsAssociationModel::JustWorks
+            ))
+        );
+        assert_eq!(
+            summary.security_level,
+            SecurityLevel::UnauthenticatedEncryption
+        );
+        assert_eq!(
+            summary.weaknesses,
+            [
+                PairingWeakness::MitmNotRequired,
+                PairingWeakness::DebugKey(LinkDirection::PeripheralToCentral)
+            ]
+        );
+
+        let request = [0x01, 0x04, 0x00, 0x0d, 0x10, 0x00, 0x00];
+        let response = [0x02, 0x04, 0x00, 0x0d, 0x10, 0x00, 0x00];
+        let (pdus, _) =
+            secure_connections_exchange(request, response, [0x11; 32], [0x22; 32], None);
+        let mut tracker = PairingSessionTracker::new();
+        let events: Vec<_> = pdus
+            .iter()
+            .filter_map(|pdu| tracker.observe(pdu).unwrap())
+            .collect();
+        let [PairingSessionEvent::Completed(summary)] = &events[..] else {
+            panic!("the numeric comparison pairing must complete");
+        };
+        assert_eq!(
+            summary.security_level,
+            SecurityLevel::AuthenticatedSecureConnections
+        );
+        assert!(summary.weaknesses.is_empty());
+    }
+
+    #[test]
+    fn reports_failed_pairings_and_mitm_requests_left_unprotected() {
+        let central = LinkDirection::CentralToPeripheral;
+        let peripheral = LinkDirection::PeripheralToCentral;
+        let mut tracker = PairingSessionTracker::new();
+        tracker
+            .observe(&smp(central, &[0x01, 0x01, 0x00, 0x0d, 0x10, 0x00, 0x00]))
+            .unwrap();
+        tracker
+            .observe(&smp(
+                peripheral,
+                &[0x02, 0x03, 0x00, 0x09, 0x10, 0x00, 0x00],
+            ))
+            .unwrap();
+        let Some(PairingSessionEvent::Failed(summary)) =
+            tracker.observe(&smp(peripheral, &[0x05, 0x0c])).unwrap()
+        else {
+            panic!("Pairing Failed must end the session");
+        };
+        assert_eq!(summary.state, PairingSessionState::Failed);
+        assert_eq!(
+            summary
+                .failure
+                .map(|(direction, failure)| (direction, failure.reason_name())),
+            Some((peripheral, "numeric-comparison-failed"))
+        );
+        assert_eq!(summary.security_level, SecurityLevel::NoSecurity);
+        assert_eq!(summary.weaknesses, [PairingWeakness::MitmNotProvided]);
+
+        tracker
+            .observe(&smp(central, &[0x01, 0x03, 0x00, 0x01, 0x10, 0x00, 0x00]))
+            .unwrap();
+        assert_eq!(tracker.state(), PairingSessionState::FeatureExchange);
+        assert_eq!(tracker.summary().failure, None);
+    }
+}
@@ -1,94 +1,421 @@
+use crate::ble::{
+    BleChannel, LE_ADV_ACCESS_ADDRESS, LE_ADV_CRC_INIT, LePdu, bits_to_bytes_lsb,
+    bytes_to_bits_lsb, crc24_bytes, whiten_bits,
+};
 use crate::demod::{LeUncodedPhy, ReceivedAdvertisingPdu, ReceivedLePdu};
+use crate::link_layer::{DataChannelPdu, LeAclDecryptionStatus, LePhy, LinkDirection};
 use crate::{Error, Result};
-use std::io::Write;
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 const SECTION_HEADER_BLOCK: u32 = 0x0a0d_0d0a;
 const INTERFACE_DESCRIPTION_BLOCK: u32 = 0x0000_0001;
 const ENHANCED_PACKET_BLOCK: u32 = 0x0000_0006;
 const BYTE_ORDER_MAGIC: u32 = 0x1a2b_3c4d;
-const LINKTYPE_BLUETOOTH_LE_LL_WITH_PHDR: u16 = 256;
+const PCAP_MAGIC_MICROSECONDS: u32 = 0xa1b2_c3d4;
+const PCAP_MAGIC_NANOSECONDS: u32 = 0xa1b2_3c4d;
+const LINKTYPE_BLUETOOTH_LE_LL: u32 = 251;
+const LINKTYPE_BLUETOOTH_LE_LL_WITH_PHDR: u32 = 256;
+const LINKTYPE_NORDIC_BLE: u32 = 272;
 const PCAPNG_NANOSECOND_RESOLUTION: u8 = 9;
+const OPTION_COMMENT: u16 = 1;
+const OPTION_SHB_HARDWARE: u16 = 2;
+const OPTION_SHB_OS: u16 = 3;
+const OPTION_SHB_USERAPPL: u16 = 4;
+const OPTION_IF_NAME: u16 = 2;
+const OPTION_IF_DESCRIPTION: u16 = 3;
+const OPTION_IF_TSRESOL: u16 = 9;
+const OPTION_IF_TSOFFSET: u16 = 14;
+const OPTION_IF_HARDWARE: u16 = 15;
+const OPTION_EPB_FLAGS: u16 = 2;
+const EPB_FLAGS_INBOUND: u32 = 0x0000_0001;
+const EPB_FLAGS_FCS_LENGTH_SHIFT: u32 = 5;
+/// Bounds allocations for corrupt length fields.
+const MAXIMUM_BLOCK_LENGTH: usize = 16 * 1024 * 1024;
+const MAXIMUM_CAPTURED_LENGTH: usize = 262_144;
 
 const BLE_DEWHITENED: u16 = 0x0001;
+const BLE_SIGNAL_POWER_VALID: u16 = 0x0002;
+const BLE_DECRYPTED: u16 = 0x0008;
 const BLE_REFERENCE_ACCESS_ADDRESS_VALID: u16 = 0x0010;
 const BLE_ACCESS_ADDRESS_OFFENSES_VALID: u16 = 0x0020;
+const BLE_PDU_TYPE_MASK: u16 = 0x0380;
+const BLE_PDU_TYPE_CENTRAL_TO_PERIPHERAL: u16 = 0x0100;
+const BLE_PDU_TYPE_PERIPHERAL_TO_CENTRAL: u16 = 0x0180;
 const BLE_CRC_CHECKED: u16 = 0x0400;
 const BLE_CRC_VALID: u16 = 0x0800;
+const BLE_MIC_CHECKED: u16 = 0x1000;
+const BLE_MIC_VALID: u16 = 0x2000;
+const BLE_PHY_MASK: u16 = 0xc000;
 const BLE_PHY_LE_2M: u16 = 0x4000;
+const BLE_PHY_LE_CODED: u16 = 0x8000;
+
+const NORDIC_EVENT_PACKET: u8 = 0x02;
+const NORDIC_EVENT_PACKET_DATA_PDU: u8 = 0x06;
+const NORDIC_CRC_OK: u8 = 0x01;
+const NORDIC_CENTRAL_TO_PERIPHERAL: u8 = 0x02;
+
+/// Section Header Block options describing the capture as a whole.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PcapNgSection {
+    /// `shb_hardware`: the receiving hardware.
+    pub hardware: Option<String>,
+    /// `shb_os`: the operating system the capture ran on.
+    pub os: Option<String>,
+    /// `shb_userappl`: the writing application and version.
+    pub user_application: Option<String>,
+    /// One `opt_comment` each, typically the capture parameters.
+    pub comments: Vec<String>,
+}
+
+/// One Interface Description Block, normally one per radio channel. All
+/// interfaces use LINKTYPE_BLUETOOTH_LE_LL_WITH_PHDR and nanosecond
+/// timestamps.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PcapNgInterface {
+    /// `if_name`
+    pub name: Option<String>,
+    /// `if_description`
+    pub description: Option<String>,
+    /// `if_hardware`
+    pub hardware: Option<String>,
+    pub comments: Vec<String>,
+}
+
+/// Per-packet metadata stored as Enhanced Packet Block options.
+///
+/// Receiver measurements and link state are rendered into one `opt_comment`
+/// of space-separated `key=value` fields so Wireshark shows them in the
+/// packet details; `comments` are written as further `opt_comment` options.
+/// Every packet written with options also carries `epb_flags` marking it
+/// inbound with a 3-octet FCS.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PcapNgPacketOptions {
+    pub interface_id: u32,
+    pub sample_index: Option<u64>,
+    pub carrier_offset_hz: Option<f32>,
+    pub deviation_hz: Option<f32>,
+    pub event_counter: Option<u16>,
+    pub decryption: Option<LeAclDecryptionStatus>,
+    pub comments: Vec<String>,
+}
+
+impl PcapNgPacketOptions {
+    /// Fills the receiver measurements of a demodulated packet.
+    pub fn received(interface_id: u32, packet: &ReceivedLePdu) -> Self {
+        Self {
+            interface_id,
+            sample_index: Some(packet.access_address_sample),
+            carrier_offset_hz: Some(packet.estimated_carrier_offset_hz),
+            deviation_hz: Some(packet.estimated_deviation_hz),
+            ..Self::default()
+        }
+    }
+
+    /// Fills the receiver measurements of a demodulated advertising packet.
+    pub fn received_advertising(interface_id: u32, packet: &ReceivedAdvertisingPdu) -> Self {
+        Self {
+            interface_id,
+            sample_index: Some(packet.access_address_sample),
+            carrier_offset_hz: Some(packet.estimated_carrier_offset_hz),
+            deviation_hz: Some(packet.estimated_deviation_hz),
+            ..Self::default()
+        }
+    }
+
+    fn metadata_comment(&self) -> Option<String> {
+        let mut fields = Vec::new();
+        if let Some(sample) = self.sample_index {
+            fields.push(format!("sample={sample}"));
+        }
+        if let Some(offset) = self.carrier_offset_hz {
+            fields.push(format!("cfo_hz={offset:.1}"));
+        }
+        if let Some(deviation) = self.deviation_hz {
+            fields.push(format!("deviation_hz={deviation:.1}"));
+        }
+        if let Some(event) = self.event_counter {
+            fields.push(format!("event={event}"));
+        }
+        match self.decryption {
+            Some(LeAclDecryptionStatus::New { packet_counter, .. }) => {
+                fields.push(format!("decryption=new packet_counter={packet_counter}"));
+            }
+            Some(LeAclDecryptionStatus::Retransmission { packet_counter }) => fields.push(format!(
+                "decryption=retransmission packet_counter={packet_counter}"
+            )),
+            Some(LeAclDecryptionStatus::UnencryptedEmpty) => {
+                fields.push("decryption=unencrypted-empty".to_owned());
+            }
+            None => {}
+        }
+        (!fields.is_empty()).then(|| fields.join(" "))
+    }
+}
 
 pub struct PcapNgWriter<W: Write> {
     writer: W,
+    interface_count: u32,
+    bytes_written: u64,
 }
 
 impl<W: Write> PcapNgWriter<W> {
-    pub fn new(mut writer: W) -> Result<Self> {
-        let mut section = Vec::with_capacity(16);
-        section.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
-        section.extend_from_slice(&1u16.to_le_bytes());
-        section.extend_from_slice(&0u16.to_le_bytes());
-        section.extend_from_slice(&u64::MAX.to_le_bytes());
-        write_block(&mut writer, SECTION_HEADER_BLOCK, &section)?;
-
-        let mut interface = Vec::new();
-        interface.extend_from_slice(&LINKTYPE_BLUETOOTH_LE_LL_WITH_PHDR.to_le_bytes());
-        interface.extend_from_slice(&0u16.to_le_bytes());
-        interface.extend_from_slice(&65_535u32.to_le_bytes());
-        interface.extend_from_slice(&9u16.to_le_bytes());
-        interface.extend_from_slice(&1u16.to_le_bytes());
-        interface.push(PCAPNG_NANOSECOND_RESOLUTION);
-        interface.extend_from_slice(&[0; 3]);
-        interface.extend_from_slice(&0u16.to_le_bytes());
-        interface.extend_from_slice(&0u16.to_le_bytes());
-        write_block(&mut writer, INTERFACE_DESCRIPTION_BLOCK, &interface)?;
-
-        Ok(Self { writer })
+    /// Writes a bare section with one unnamed interface.
+    pub fn new(writer: W) -> Result<Self> {
+        Self::with_interfaces(
+            writer,
+            &PcapNgSection::default(),
+            &[PcapNgInterface::default()],
+        )
+    }
+
+    /// Writes a described section followed by `interfaces`, numbered from 0
+    /// in order. More can be added with [`Self::add_interface`].
+    pub fn with_interfaces(
+        mut writer: W,
+        section: &PcapNgSection,
+        interfaces: &[PcapNgInterface],
+    ) -> Result<Self> {
+        let mut body = Vec::with_capacity(16);
+        body.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+        body.extend_from_slice(&1u16.to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes());
+        body.extend_from_slice(&u64::MAX.to_le_bytes());
+        let mut options = Vec::new();
+        for comment in &section.comments {
+            push_option(&mut options, OPTION_COMMENT, comment.as_bytes())?;
+        }
+        for (code, value) in [
+            (OPTION_SHB_HARDWARE, &section.hardware),
+            (OPTION_SHB_OS, &section.os),
+            (OPTION_SHB_USERAPPL, &section.user_application),
+        ] {
+            if let Some(value) = value {
+                push_option(&mut options, code, value.as_bytes())?;
+            }
+        }
+        if !options.is_empty() {
+            body.extend_from_slice(&options);
+            body.extend_from_slice(&[0; 4]);
+        }
+        let bytes_written = write_block(&mut writer, SECTION_HEADER_BLOCK, &body)?;
+
+        let mut pcapng = Self {
+            writer,
+            interface_count: 0,
+            bytes_written,
+        };
+        for interface in interfaces {
+            pcapng.add_interface(interface)?;
+        }
+        Ok(pcapng)
+    }
+
+    /// Appends an Interface Description Block and returns its interface ID.
+    pub fn add_interface(&mut self, interface: &PcapNgInterface) -> Result<u32> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&(LINKTYPE_BLUETOOTH_LE_LL_WITH_PHDR as u16).to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes());
+        body.extend_from_slice(&65_535u32.to_le_bytes());
+        for comment in &interface.comments {
+            push_option(&mut body, OPTION_COMMENT, comment.as_bytes())?;
+        }
+        for (code, value) in [
+            (OPTION_IF_NAME, &interface.name),
+            (OPTION_IF_DESCRIPTION, &interface.description),
+            (OPTION_IF_HARDWARE, &interface.hardware),
+        ] {
+            if let Some(value) = value {
+                push_option(&mut body, code, value.as_bytes())?;
+            }
+        }
+        push_option(
+            &mut body,
+            OPTION_IF_TSRESOL,
+            &[PCAPNG_NANOSECOND_RESOLUTION],
+        )?;
+        body.extend_from_slice(&[0; 4]);
+        self.bytes_written += write_block(&mut self.writer, INTERFACE_DESCRIPTION_BLOCK, &body)?;
+        let interface_id = self.interface_count;
+        self.interface_count += 1;
+        Ok(interface_id)
+    }
+
+    pub const fn interface_count(&self) -> u32 {
+        self.interface_count
+    }
+
+    /// Octets handed to the underlying writer so far, including the section
+    /// and interface headers.
+    pub const fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
     }
 
     pub fn write_advertising(
         &mut self,
         packet: &ReceivedAdvertisingPdu,
         timestamp_ns: u64,
+    ) -> Result<()> {
+        self.write_advertising_with_options(packet, timestamp_ns, None)
+    }
+
+    pub fn write_advertising_with_options(
+        &mut self,
+        packet: &ReceivedAdvertisingPdu,
+        timestamp_ns: u64,
+        options: Option<&PcapNgPacketOptions>,
     ) -> Result<()> {
         let phy_flags = match packet.phy {
             LeUncodedPhy::Le1M => 0,
             LeUncodedPhy::Le2M => BLE_PHY_LE_2M,
         };
         self.write_packet(
-            packet.pdu.channel.index(),
+            packet.pdu.channel.rf_channel(),
             packet.pdu.access_address_errors,
             packet.pdu.access_address,
             &packet.pdu.link_layer_bytes(),
-            phy_flags,
+            phy_flags | BLE_CRC_VALID,
             timestamp_ns,
+            options,
         )
     }
 
     pub fn write_le(&mut self, packet: &ReceivedLePdu, timestamp_ns: u64) -> Result<()> {
+        self.write_pdu(&packet.pdu, packet.phy, None, timestamp_ns)
+    }
+
+    /// Writes a CRC-valid packet. A known data-channel transmitter direction
+    /// is recorded in the PDU type field; `None` leaves it unspecified.
+    pub fn write_pdu(
+        &mut self,
+        pdu: &LePdu,
+        phy: LeUncodedPhy,
+        direction: Option<LinkDirection>,
+        timestamp_ns: u64,
+    ) -> Result<()> {
+        self.write_pdu_with_options(pdu, phy, direction, timestamp_ns, None)
+    }
+
+    /// Like [`Self::write_pdu`], on the interface and with the metadata named
+    /// by `options`.
+    pub fn write_pdu_with_options(
+        &mut self,
+        pdu: &LePdu,
+        phy: LeUncodedPhy,
+        direction: Option<LinkDirection>,
+        timestamp_ns: u64,
+        options: Option<&PcapNgPacketOptions>,
+    ) -> Result<()> {
+        let phy_flags = match phy {
+            LeUncodedPhy::Le1M => 0,
+            LeUncodedPhy::Le2M => BLE_PHY_LE_2M,
+        };
+        let direction_flags = match direction {
+            Some(LinkDirection::CentralToPeripheral) => BLE_PDU_TYPE_CENTRAL_TO_PERIPHERAL,
+            Some(LinkDirection::PeripheralToCentral) => BLE_PDU_TYPE_PERIPHERAL_TO_CENTRAL,
+            None => 0,
+        };
+        self.write_packet(
+            pdu.channel.rf_channel(),
+            pdu.access_address_errors,
+            pdu.access_address,
+            &pdu.link_layer_bytes(),
+            phy_flags | direction_flags | BLE_CRC_VALID,
+            timestamp_ns,
+            options,
+        )
+    }
+
+    /// Writes a frame whose CRC failed, as reported under
+    /// [`crate::ble::LeCrcFailurePolicy::Emit`]. The pseudo-header marks the
+    /// CRC as checked and not valid, so Wireshark flags the frame instead of
+    /// dissecting it as trusted data.
+    pub fn write_crc_failure(
+        &mut self,
+        packet: &ReceivedLePdu,
+        timestamp_ns: u64,
+        options: Option<&PcapNgPacketOptions>,
+    ) -> Result<()> {
         let phy_flags = match packet.phy {
             LeUncodedPhy::Le1M => 0,
             LeUncodedPhy::Le2M => BLE_PHY_LE_2M,
         };
         self.write_packet(
-            packet.pdu.channel.index(),
+            packet.pdu.channel.rf_channel(),
             packet.pdu.access_address_errors,
             packet.pdu.access_address,
             &packet.pdu.link_layer_bytes(),
             phy_flags,
             timestamp_ns,
+            options,
+        )
+    }
+
+    /// Writes an authenticated plaintext data PDU, as returned by
+    /// [`crate::link_layer::LeAclDecryptor`], with the MIC already stripped.
+    /// The pseudo-header marks it decrypted with a checked, valid MIC so
+    /// Wireshark dissects the payload; the CRC is the one received over the
+    /// ciphertext.
+    pub fn write_decrypted_pdu(
+        &mut self,
+        plaintext: &DataChannelPdu,
+        phy: LeUncodedPhy,
+        direction: Option<LinkDirection>,
+        timestamp_ns: u64,
+        options: Option<&PcapNgPacketOptions>,
+    ) -> Result<()> {
+        let phy_flags = match phy {
+            LeUncodedPhy::Le1M => 0,
+            LeUncodedPhy::Le2M => BLE_PHY_LE_2M,
+        };
+        let direction_flags = match direction {
+            Some(LinkDirection::CentralToPeripheral) => BLE_PDU_TYPE_CENTRAL_TO_PERIPHERAL,
+            Some(LinkDirection::PeripheralToCentral) => BLE_PDU_TYPE_PERIPHERAL_TO_CENTRAL,
+            None => 0,
+        };
+        self.write_packet(
+            plaintext.channel.rf_channel(),
+            plaintext.access_address_errors,
+            plaintext.access_address,
+            &plaintext.link_layer_bytes(),
+            phy_flags
+                | direction_flags
+                | BLE_CRC_VALID
+                | BLE_DECRYPTED
+                | BLE_MIC_CHECKED
+                | BLE_MIC_VALID,
+            timestamp_ns,
+            options,
         )
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn write_packet(
         &mut self,
-        channel: u8,
+        rf_channel: u8,
         access_address_errors: u8,
         access_address: u32,
         link_layer_bytes: &[u8],
-        phy_flags: u16,
+        packet_flags: u16,
         timestamp_ns: u64,
+        options: Option<&PcapNgPacketOptions>,
     ) -> Result<()> {
+        let interface_id = options.map_or(0, |options| options.interface_id);
+        if interface_id >= self.interface_count {
+            return Err(Error::InvalidInput(format!(
+                "PCAPNG packet names undeclared interface {interface_id}"
+            )));
+        }
         let mut captured = Vec::with_capacity(10 + link_layer_bytes.len());
-        captured.push(channel);
+        captured.push(rf_channel);
         captured.push(0);
         captured.push(0);
         captured.push(access_address_errors);
@@ -97,20 +424,31 @@ impl<W: Write> PcapNgWriter<W> {
             | BLE_REFERENCE_ACCESS_ADDRESS_VALID
             | BLE_ACCESS_ADDRESS_OFFENSES_VALID
             | BLE_CRC_CHECKED
-            | BLE_CRC_VALID
-            | phy_flags;
+            | packet_flags;
         captured.extend_from_slice(&flags.to_le_bytes());
         captured.extend_from_slice(link_layer_bytes);
 
         let mut body = Vec::with_capacity(20 + captured.len() + 3);
-        body.extend_from_slice(&0u32.to_le_bytes());
+        body.extend_from_slice(&interface_id.to_le_bytes());
         body.extend_from_slice(&((timestamp_ns >> 32) as u32).to_le_bytes());
         body.extend_from_slice(&(timestamp_ns as u32).to_le_bytes());
         body.extend_from_slice(&(captured.len() as u32).to_le_bytes());
         body.extend_from_slice(&(captured.len() as u32).to_le_bytes());
         body.extend_from_slice(&captured);
         body.resize(body.len().next_multiple_of(4), 0);
-        write_block(&mut self.writer, ENHANCED_PACKET_BLOCK, &body)
+        if let Some(options) = options {
+            if let Some(metadata) = options.metadata_comment() {
+                push_option(&mut body, OPTION_COMMENT, metadata.as_bytes())?;
+            }
+            for comment in &options.comments {
+                push_option(&mut body, OPTION_COMMENT, comment.as_bytes())?;
+            }
+            let flags = EPB_FLAGS_INBOUND | (3 << EPB_FLAGS_FCS_LENGTH_SHIFT);
+            push_option(&mut body, OPTION_EPB_FLAGS, &flags.to_le_bytes())?;
+            body.extend_from_slice(&[0; 4]);
+        }
+        self.bytes_written += write_block(&mut self.writer, ENHANCED_PACKET_BLOCK, &body)?;
+        Ok(())
     }
 
     pub fn into_inner(self) -> W {
@@ -118,6 +456,288 @@ impl<W: Write> PcapNgWriter<W> {
     }
 }
 
+/// File rotation and flushing for [`RotatingPcapNgWriter`]. With no size,
+/// duration or packet limit the writer keeps one file at the given path.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct PcapNgRotation {
+    /// Starts a new file once the current one holds this many octets; the
+    /// packet that crosses the limit is the last one in the file.
+    pub maximum_file_bytes: Option<u64>,
+    /// Starts a new file for the first packet this long after the current
+    /// file began.
+    pub maximum_file_duration: Option<Duration>,
+    /// Starts a new file once the current one holds this many packets.
+    pub maximum_file_packets: Option<u64>,
+    /// Keeps only the newest this-many files, deleting older ones.
+    pub ring_files: Option<usize>,
+    /// Upper bound on how long written packets stay buffered, enforced by
+    /// [`RotatingPcapNgWriter::flush_if_due`].
+    pub flush_interval: Option<Duration>,
+}
+
+impl PcapNgRotation {
+    pub const fn rotates(&self) -> bool {
+        self.maximum_file_bytes.is_some()
+            || self.maximum_file_duration.is_some()
+            || self.maximum_file_packets.is_some()
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.maximum_file_bytes == Some(0)
+            || self.maximum_file_packets == Some(0)
+            || self
+                .maximum_file_duration
+                .is_some_and(|duration| duration.is_zero())
+        {
+            return Err(Error::InvalidConfiguration(
+                "PCAPNG rotation limits must be greater than zero".to_owned(),
+            ));
+        }
+        if self.ring_files == Some(0) {
+            return Err(Error::InvalidConfiguration(
+                "PCAPNG ring buffer must keep at least one file".to_owned(),
+            ));
+        }
+        if self.ring_files.is_some() && !self.rotates() {
+            return Err(Error::InvalidConfiguration(
+                "PCAPNG ring buffer requires a size, duration or packet rotation limit".to_owned(),
+            ));
+        }
+        if self
+            .flush_interval
+            .is_some_and(|interval| interval.is_zero())
+        {
+            return Err(Error::InvalidConfiguration(
+                "PCAPNG flush interval must be greater than zero".to_owned(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Writes PCAPNG to a sequence of files for unattended capture. Every file
+/// begins with the same Section Header and Interface Description Blocks, so
+/// each one opens on its own.
+///
+/// When [`PcapNgRotation::rotates`], files are created beside `path` as
+/// `STEM_NNNNN_YYYYMMDDhhmmss.EXT`: a sequence number from 1 and the UTC
+/// wall-clock start of the file, which is the capture start for the first
+/// file and the timestamp of its first packet afterwards. Otherwise `path`
+/// itself is written.
+pub struct RotatingPcapNgWriter {
+    path: PathBuf,
+    section: PcapNgSection,
+    interfaces: Vec<PcapNgInterface>,
+    rotation: PcapNgRotation,
+    writer: PcapNgWriter<BufWriter<File>>,
+    file_start_ns: u64,
+    file_packets: u64,
+    sequence: u64,
+    files: VecDeque<PathBuf>,
+    last_flush: Instant,
+}
+
+impl RotatingPcapNgWriter {
+    pub fn create(
+        path: impl Into<PathBuf>,
+        section: PcapNgSection,
+        interfaces: Vec<PcapNgInterface>,
+        rotation: PcapNgRotation,
+        capture_start_ns: u64,
+    ) -> Result<Self> {
+        rotation.validate()?;
+        let path = path.into();
+        if rotation.rotates() && path.file_name().is_none() {
+            return Err(Error::InvalidConfiguration(
+                "PCAPNG rotation requires a file path, not a directory".to_owned(),
+            ));
+        }
+        let file_path = if rotation.rotates() {
+            rotated_file_path(&path, 1, capture_start_ns)
+        } else {
+            path.clone()
+        };
+        let writer = PcapNgWriter::with_interfaces(
+            BufWriter::new(File::create(&file_path)?),
+            &section,
+            &interfaces,
+        )?;
+        Ok(Self {
+            path,
+            section,
+            interfaces,
+            rotation,
+            writer,
+            file_start_ns: capture_start_ns,
+            file_packets: 0,
+            sequence: 1,
+            files: VecDeque::from([file_path]),
+            last_flush: Instant::now(),
+        })
+    }
+
+    /// Returns the writer for one packet at `timestamp_ns`, first starting a
+    /// new file if the current one has reached a limit. Call it once per
+    /// packet; every file holds at least one packet.
+    pub fn packet_writer(
+        &mut self,
+        timestamp_ns: u64,
+    ) -> Result<&mut PcapNgWriter<BufWriter<File>>> {
+        if self.file_packets > 0 && self.limit_reached(timestamp_ns) {
+            self.rotate(timestamp_ns)?;
+        }
+        self.file_packets += 1;
+        Ok(&mut self.writer)
+    }
+
+    /// Adds an interface to the current file and to every later one.
+    pub fn add_interface(&mut self, interface: PcapNgInterface) -> Result<u32> {
+        let interface_id = self.writer.add_interface(&interface)?;
+        self.interfaces.push(interface);
+        Ok(interface_id)
+    }
+
+    /// Flushes if [`PcapNgRotation::flush_interval`] has passed since the
+    /// last flush. Call it periodically, including while no packets arrive.
+    pub fn flush_if_due(&mut self, now: Instant) -> Result<()> {
+        match self.rotation.flush_interval {
+            Some(interval) if now.duration_since(self.last_flush) >= interval => self.flush_at(now),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.flush_at(Instant::now())
+    }
+
+    /// The file currently being written.
+    pub fn current_path(&self) -> &Path {
+        self.files.back().expect("a file is always open")
+    }
+
+    /// Files written and not yet removed by the ring buffer, oldest first.
+    pub fn retained_files(&self) -> impl Iterator<Item = &Path> {
+        self.files.iter().map(PathBuf::as_path)
+    }
+
+    fn flush_at(&mut self, now: Instant) -> Result<()> {
+        self.writer.flush()?;
+        self.last_flush = now;
+        Ok(())
+    }
+
+    fn limit_reached(&self, timestamp_ns: u64) -> bool {
+        let rotation = &self.rotation;
+        rotation
+            .maximum_file_packets
+            .is_some_and(|maximum| self.file_packets >= maximum)
+            || rotation
+                .maximum_file_bytes
+                .is_some_and(|maximum| self.writer.bytes_written() >= maximum)
+            || rotation.maximum_file_duration.is_some_and(|maximum| {
+                u128::from(timestamp_ns.saturating_sub(self.file_start_ns)) >= maximum.as_nanos()
+            })
+    }
+
+    fn rotate(&mut self, timestamp_ns: u64) -> Result<()> {
+        self.writer.flush()?;
+        let sequence = self
+            .sequence
+            .checked_add(1)
+            .ok_or_else(|| Error::InvalidState("PCAPNG file sequence overflow".to_owned()))?;
+        let file_path = rotated_file_path(&self.path, sequence, timestamp_ns);
+        self.writer = PcapNgWriter::with_interfaces(
+            BufWriter::new(File::create(&file_path)?),
+            &self.section,
+            &self.interfaces,
+        )?;
+        self.sequence = sequence;
+        self.files.push_back(file_path);
+        self.file_start_ns = timestamp_ns;
+        self.file_packets = 0;
+        if let Some(ring_files) = self.rotation.ring_files {
+            while self.files.len() > ring_files {
+                if let Some(oldest) = self.files.pop_front() {
+                    fs::remove_file(oldest)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn rotated_file_path(path: &Path, sequence: u64, start_ns: u64) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut name = format!("{stem}_{sequence:05}_{}", utc_file_timestamp(start_ns));
+    if let Some(extension) = path.extension() {
+        name.push('.');
+        name.push_str(&extension.to_string_lossy());
+    }
+    path.with_file_name(name)
+}
+
+/// `YYYYMMDDhhmmss` in UTC, using the proleptic Gregorian civil-from-days
+/// conversion.
+fn utc_file_timestamp(unix_ns: u64) -> String {
+    let seconds = unix_ns / 1_000_000_000;
+    let second_of_day = seconds % 86_400;
+    let shifted_days = seconds / 86_400 + 719_468;
+    let era = shifted_days / 146_097;
+    let day_of_era = shifted_days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+    format!(
+        "{year:04}{month:02}{day:02}{:02}{:02}{:02}",
+        second_of_day / 3_600,
+        second_of_day / 60 % 60,
+        second_of_day % 60
+    )
+}
+
+/// Formats LL session key material as a packet comment, so a reader of the
+/// capture can decrypt the connection's other packets. The session key is in
+/// AES input order and the IV in nonce order, as
+/// [`crate::ll_control::LeEncryptionMaterial`] returns them.
+pub fn encryption_material_comment(
+    access_address: u32,
+    session_key: [u8; 16],
+    initialization_vector: [u8; 8],
+) -> String {
+    let hex = |bytes: &[u8]| {
+        bytes
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>()
+    };
+    format!(
+        "ll_encryption access_address=0x{access_address:08x} session_key={} iv={}",
+        hex(&session_key),
+        hex(&initialization_vector)
+    )
+}
+
+fn push_option(body: &mut Vec<u8>, code: u16, value: &[u8]) -> Result<()> {
+    let length = u16::try_from(value.len())
+        .map_err(|_| Error::InvalidInput("PCAPNG option exceeds 65535 octets".to_owned()))?;
+    body.extend_from_slice(&code.to_le_bytes());
+    body.extend_from_slice(&length.to_le_bytes());
+    body.extend_from_slice(value);
+    body.resize(body.len().next_multiple_of(4), 0);
+    Ok(())
+}
+
 pub fn sample_timestamp_ns(
     capture_start_ns: u64,
     sample_index: u64,
@@ -137,7 +757,8 @@ pub fn sample_timestamp_ns(
         .map_err(|_| Error::InvalidInput("sample timestamp overflow".to_owned()))
 }
 
-fn write_block(writer: &mut impl Write, block_type: u32, body: &[u8]) -> Result<()> {
+/// Returns the total block length written.
+fn write_block(writer: &mut impl Write, block_type: u32, body: &[u8]) -> Result<u64> {
     if !body.len().is_multiple_of(4) {
         return Err(Error::InvalidInput(
             "PCAPNG block body is not 32-bit aligned".to_owned(),
@@ -153,7 +774,641 @@ fn write_block(writer: &mut impl Write, block_type: u32, body: &[u8]) -> Result<
     writer.write_all(&total_length.to_le_bytes())?;
     writer.write_all(body)?;
     writer.write_all(&total_length.to_le_bytes())?;
-    Ok(())
+    Ok(u64::from(total_length))
+}
+
+/// CRC state recorded for a packet read from a capture file.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CaptureCrcStatus {
+    Valid,
+    Invalid,
+    /// The capture neither records a check nor carries a CRC initialization
+    /// value that can be recomputed.
+    Unchecked,
+}
+
+/// One LE packet read from a PCAPNG or classic pcap file.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CapturedLePacket {
+    /// Dewhitened packet. When the link type carries no RF channel,
+    /// `channel_known` is false and `pdu.channel` is 37 for the advertising
+    /// access address and 0 otherwise.
+    pub pdu: LePdu,
+    pub channel_known: bool,
+    pub phy: Option<LePhy>,
+    pub direction: Option<LinkDirection>,
+    pub crc: CaptureCrcStatus,
+    pub signal_dbm: Option<i8>,
+    /// Unix time in nanoseconds, after the interface's resolution and offset.
+    pub timestamp_ns: u64,
+    pub link_type: u32,
+    pub interface_id: u32,
+    /// Set when the pseudo-header marks the payload as decrypted, with the MIC
+    /// removed.
+    pub decrypted: bool,
+    /// The Enhanced Packet Block's `opt_comment` options, in file order.
+    pub comments: Vec<String>,
+    /// The Enhanced Packet Block's `epb_flags`, when present.
+    pub flags: Option<u32>,
+}
+
+struct CaptureRecord {
+    interface_id: u32,
+    timestamp_ns: u64,
+    data: Vec<u8>,
+    comments: Vec<String>,
+    flags: Option<u32>,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum CaptureFormat {
+    PcapNg,
+    Pcap { nanosecond: bool },
+}
+
+#[derive(Clone, Copy, Debug)]
+struct CaptureInterface {
+    link_type: u32,
+    /// Timestamp unit as a power of ten or two below one second.
+    resolution: TimestampResolution,
+    offset_seconds: i64,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum TimestampResolution {
+    Decimal(u8),
+    Binary(u8),
+}
+
+impl TimestampResolution {
+    fn nanoseconds(self, timestamp: u64) -> Result<u64> {
+        let nanoseconds = match self {
+            Self::Decimal(exponent) if exponent <= 9 => {
+                u128::from(timestamp) * 10u128.pow(u32::from(9 - exponent))
+            }
+            Self::Decimal(exponent) => {
+                u128::from(timestamp) / 10u128.pow(u32::from(exponent.min(38) - 9))
+            }
+            Self::Binary(exponent) => {
+                (u128::from(timestamp) * 1_000_000_000) >> u32::from(exponent.min(127))
+            }
+        };
+        u64::try_from(nanoseconds)
+            .map_err(|_| Error::InvalidInput("capture timestamp overflow".to_owned()))
+    }
+}
+
+/// Reads LE packets from PCAPNG and classic pcap files.
+///
+/// Supported link types are LINKTYPE_BLUETOOTH_LE_LL_WITH_PHDR (256),
+/// LINKTYPE_BLUETOOTH_LE_LL (251), and LINKTYPE_NORDIC_BLE (272). Packets on
+/// interfaces with other link types are skipped and counted. So are records
+/// whose LE packet is malformed, such as a snaplen-truncated packet or a
+/// corrupted Length octet; only damaged block framing ends the read.
+pub struct CaptureFileReader<R: Read> {
+    reader: R,
+    format: CaptureFormat,
+    big_endian: bool,
+    interfaces: Vec<CaptureInterface>,
+    skipped_packets: u64,
+    malformed_packets: u64,
+}
+
+impl<R: Read> CaptureFileReader<R> {
+    pub fn new(mut reader: R) -> Result<Self> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        let mut capture = Self {
+            reader,
+            format: CaptureFormat::PcapNg,
+            big_endian: false,
+            interfaces: Vec::new(),
+            skipped_packets: 0,
+            malformed_packets: 0,
+        };
+        match (u32::from_le_bytes(magic), u32::from_be_bytes(magic)) {
+            (SECTION_HEADER_BLOCK, _) => capture.read_section_header()?,
+            (PCAP_MAGIC_MICROSECONDS, _) | (_, PCAP_MAGIC_MICROSECONDS) => {
+                capture.read_pcap_header(magic, false)?
+            }
+            (PCAP_MAGIC_NANOSECONDS, _) | (_, PCAP_MAGIC_NANOSECONDS) => {
+                capture.read_pcap_header(magic, true)?
+            }
+            _ => {
+                return Err(Error::InvalidInput(
+                    "capture is neither PCAPNG nor classic pcap".to_owned(),
+                ));
+            }
+        }
+        Ok(capture)
+    }
+
+    /// Packets skipped because their interface uses an unsupported link type.
+    pub const fn skipped_packets(&self) -> u64 {
+        self.skipped_packets
+    }
+
+    /// Records skipped because their LE packet could not be parsed.
+    pub const fn malformed_packets(&self) -> u64 {
+        self.malformed_packets
+    }
+
+    pub fn next_packet(&mut self) -> Result<Option<CapturedLePacket>> {
+        loop {
+            let record = match self.format {
+                CaptureFormat::PcapNg => self.next_pcapng_record()?,
+                CaptureFormat::Pcap { nanosecond } => self.next_pcap_record(nanosecond)?,
+            };
+            let Some(CaptureRecord {
+                interface_id,
+                timestamp_ns,
+                data,
+                comments,
+                flags,
+            }) = record
+            else {
+                return Ok(None);
+            };
+            let interface = self.interfaces[interface_id as usize];
+            let parsed = match interface.link_type {
+                LINKTYPE_BLUETOOTH_LE_LL_WITH_PHDR => parse_phdr_packet(&data),
+                LINKTYPE_BLUETOOTH_LE_LL => parse_link_layer_packet(&data, None, None),
+                LINKTYPE_NORDIC_BLE => parse_nordic_packet(&data),
+                _ => {
+                    self.skipped_packets += 1;
+                    continue;
+                }
+            };
+            let Ok(packet) = parsed else {
+                self.malformed_packets += 1;
+                continue;
+            };
+            return Ok(Some(CapturedLePacket {
+                timestamp_ns,
+                link_type: interface.link_type,
+                interface_id,
+                comments,
+                flags,
+                ..packet
+            }));
+        }
+    }
+
+    fn read_pcap_header(&mut self, magic: [u8; 4], nanosecond: bool) -> Result<()> {
+        self.big_endian = u32::from_le_bytes(magic) != PCAP_MAGIC_MICROSECONDS
+            && u32::from_le_bytes(magic) != PCAP_MAGIC_NANOSECONDS;
+        let mut header = [0u8; 20];
+        self.reader.read_exact(&mut header)?;
+        self.format = CaptureFormat::Pcap { nanosecond };
+        // The FCS and reserved bits share the upper half of the link-type word.
+        let link_type = self.u32_at(&header, 16) & 0x0fff_ffff;
+        self.interfaces.push(CaptureInterface {
+            link_type,
+            resolution: TimestampResolution::Decimal(if nanosecond { 9 } else { 6 }),
+            offset_seconds: 0,
+        });
+        Ok(())
+    }
+
+    fn next_pcap_record(&mut self, nanosecond: bool) -> Result<Option<CaptureRecord>> {
+        let mut header = [0u8; 16];
+        if !read_exact_or_end(&mut self.reader, &mut header)? {
+            return Ok(None);
+        }
+        let seconds = u64::from(self.u32_at(&header, 0));
+        let fraction = u64::from(self.u32_at(&header, 4));
+        let captured_length = self.u32_at(&header, 8) as usize;
+        if captured_length > MAXIMUM_CAPTURED_LENGTH {
+            return Err(Error::InvalidInput(format!(
+                "pcap record length {captured_length} exceeds {MAXIMUM_CAPTURED_LENGTH}"
+            )));
+        }
+        let mut data = vec![0u8; captured_length];
+        self.reader.read_exact(&mut data)?;
+        let fraction_ns = if nanosecond {
+            fraction
+        } else {
+            fraction * 1_000
+        };
+        let timestamp_ns = seconds
+            .checked_mul(1_000_000_000)
+            .and_then(|seconds| seconds.checked_add(fraction_ns))
+            .ok_or_else(|| Error::InvalidInput("capture timestamp overflow".to_owned()))?;
+        Ok(Some(CaptureRecord {
+            interface_id: 0,
+            timestamp_ns,
+            data,
+            comments: Vec::new(),
+            flags: None,
+        }))
+    }
+
+    /// Reads the remainder of a section header block whose type was consumed.
+    fn read_section_header(&mut self) -> Result<()> {
+        let mut length = [0u8; 4];
+        let mut magic = [0u8; 4];
+        self.reader.read_exact(&mut length)?;
+        self.reader.read_exact(&mut magic)?;
+        self.big_endian = match (u32::from_le_bytes(magic), u32::from_be_bytes(magic)) {
+            (BYTE_ORDER_MAGIC, _) => false,
+            (_, BYTE_ORDER_MAGIC) => true,
+            _ => {
+                return Err(Error::InvalidInput(
+                    "PCAPNG section header has an invalid byte-order magic".to_owned(),
+                ));
+            }
+        };
+        let total_length = self.u32_at(&length, 0) as usize;
+        if total_length < 28
+            || !total_length.is_multiple_of(4)
+            || total_length > MAXIMUM_BLOCK_LENGTH
+        {
+            return Err(Error::InvalidInput(format!(
+                "PCAPNG section header length {total_length} is invalid"
+            )));
+        }
+        let mut rest = vec![0u8; total_length - 12];
+        self.reader.read_exact(&mut rest)?;
+        self.check_trailing_length(&rest, total_length)?;
+        if self.u16_at(&rest, 0) != 1 {
+            return Err(Error::InvalidInput(format!(
+                "PCAPNG major version {} is not supported",
+                self.u16_at(&rest, 0)
+            )));
+        }
+        self.interfaces.clear();
+        Ok(())
+    }
+
+    fn next_pcapng_record(&mut self) -> Result<Option<CaptureRecord>> {
+        loop {
+            let mut block_type = [0u8; 4];
+            if !read_exact_or_end(&mut self.reader, &mut block_type)? {
+                return Ok(None);
+            }
+            if u32::from_le_bytes(block_type) == SECTION_HEADER_BLOCK {
+                self.read_section_header()?;
+                continue;
+            }
+            let block_type = self.u32_at(&block_type, 0);
+            let mut length = [0u8; 4];
+            self.reader.read_exact(&mut length)?;
+            let total_length = self.u32_at(&length, 0) as usize;
+            if total_length < 12
+                || !total_length.is_multiple_of(4)
+                || total_length > MAXIMUM_BLOCK_LENGTH
+            {
+                return Err(Error::InvalidInput(format!(
+                    "PCAPNG block length {total_length} is invalid"
+                )));
+            }
+            let mut body = vec![0u8; total_length - 8];
+            self.reader.read_exact(&mut body)?;
+            self.check_trailing_length(&body, total_length)?;
+            let body = &body[..total_length - 12];
+            match block_type {
+                INTERFACE_DESCRIPTION_BLOCK => self.read_interface(body)?,
+                ENHANCED_PACKET_BLOCK => {
+                    if body.len() < 20 {
+                        return Err(Error::InvalidInput(
+                            "PCAPNG enhanced packet block is truncated".to_owned(),
+                        ));
+                    }
+                    let interface_id = self.u32_at(body, 0);
+                    let interface = self
+                        .interfaces
+                        .get(interface_id as usize)
+                        .copied()
+                        .ok_or_else(|| {
+                            Error::InvalidInput(format!(
+                                "PCAPNG packet names undeclared interface {interface_id}"
+                            ))
+                        })?;
+                    let timestamp =
+                        (u64::from(self.u32_at(body, 4)) << 32) | u64::from(self.u32_at(body, 8));
+                    let captured_length = self.u32_at(body, 12) as usize;
+                    let data = body.get(20..20 + captured_length).ok_or_else(|| {
+                        Error::InvalidInput("PCAPNG packet data exceeds its block".to_owned())
+                    })?;
+                    let timestamp_ns = i128::from(interface.resolution.nanoseconds(timestamp)?)
+                        + i128::from(interface.offset_seconds) * 1_000_000_000;
+                    let timestamp_ns = u64::try_from(timestamp_ns).map_err(|_| {
+                        Error::InvalidInput("capture timestamp overflow".to_owned())
+                    })?;
+                    let mut record = CaptureRecord {
+                        interface_id,
+                        timestamp_ns,
+                        data: data.to_vec(),
+                        comments: Vec::new(),
+                        flags: None,
+                    };
+                    self.read_packet_options(
+                        &body[20 + captured_length.next_multiple_of(4).min(body.len() - 20)..],
+                        &mut record,
+                    )?;
+                    return Ok(Some(record));
+                }
+                // Simple packet, name resolution, statistics, and custom
+                // blocks carry nothing the LE decoders use.
+                _ => {}
+            }
+        }
+    }
+
+    fn read_packet_options(&self, options: &[u8], record: &mut CaptureRecord) -> Result<()> {
+        let mut offset = 0;
+        while offset + 4 <= options.len() {
+            let code = self.u16_at(options, offset);
+            let length = usize::from(self.u16_at(options, offset + 2));
+            let value = options
+                .get(offset + 4..offset + 4 + length)
+                .ok_or_else(|| {
+                    Error::InvalidInput("PCAPNG packet option exceeds its block".to_owned())
+                })?;
+            match (code, length) {
+                (0, _) => break,
+                (OPTION_COMMENT, _) => record
+                    .comments
+                    .push(String::from_utf8_lossy(value).into_owned()),
+                (OPTION_EPB_FLAGS, 4) => record.flags = Some(self.u32_at(value, 0)),
+                _ => {}
+            }
+            offset += 4 + length.next_multiple_of(4);
+        }
+        Ok(())
+    }
+
+    fn read_interface(&mut self, body: &[u8]) -> Result<()> {
+        if body.len() < 8 {
+            return Err(Error::InvalidInput(
+                "PCAPNG interface description block is truncated".to_owned(),
+            ));
+        }
+        let mut interface = CaptureInterface {
+            link_type: u32::from(self.u16_at(body, 0)),
+            resolution: TimestampResolution::Decimal(6),
+            offset_seconds: 0,
+        };
+        let mut offset = 8;
+        while offset + 4 <= body.len() {
+            let code = self.u16_at(body, offset);
+            let length = usize::from(self.u16_at(body, offset + 2));
+            let value = body.get(offset + 4..offset + 4 + length).ok_or_else(|| {
+                Error::InvalidInput("PCAPNG interface option exceeds its block".to_owned())
+            })?;
+            match (code, length) {
+                (0, _) => break,
+                (OPTION_IF_TSRESOL, 1) => {
+                    interface.resolution = if value[0] & 0x80 == 0 {
+                        TimestampResolution::Decimal(value[0])
+                    } else {
+                        TimestampResolution::Binary(value[0] & 0x7f)
+                    };
+                }
+                (OPTION_IF_TSOFFSET, 8) => {
+                    let bytes: [u8; 8] = value.try_into().expect("length checked above");
+                    interface.offset_seconds = if self.big_endian {
+                        i64::from_be_bytes(bytes)
+                    } else {
+                        i64::from_le_bytes(bytes)
+                    };
+                }
+                _ => {}
+            }
+            offset += 4 + length.next_multiple_of(4);
+        }
+        self.interfaces.push(interface);
+        Ok(())
+    }
+
+    fn check_trailing_length(&self, rest: &[u8], total_length: usize) -> Result<()> {
+        if self.u32_at(rest, rest.len() - 4) as usize != total_length {
+            return Err(Error::InvalidInput(
+                "PCAPNG block trailing length does not match its header".to_owned(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn u16_at(&self, bytes: &[u8], offset: usize) -> u16 {
+        let value = [bytes[offset], bytes[offset + 1]];
+        if self.big_endian {
+            u16::from_be_bytes(value)
+        } else {
+            u16::from_le_bytes(value)
+        }
+    }
+
+    fn u32_at(&self, bytes: &[u8], offset: usize) -> u32 {
+        let value = bytes[offset..offset + 4]
+            .try_into()
+            .expect("callers bound offsets by the block length");
+        if self.big_endian {
+            u32::from_be_bytes(value)
+        } else {
+            u32::from_le_bytes(value)
+        }
+    }
+}
+
+fn read_exact_or_end(reader: &mut impl Read, buffer: &mut [u8]) -> Result<bool> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) if filled == 0 => return Ok(false),
+            Ok(0) => {
+                return Err(Error::InvalidInput(
+                    "capture file ends inside a record".to_owned(),
+                ));
+            }
+            Ok(read) => filled += read,
+            Err(error) if error.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(error) => return Err(error.into()),
+        }
+    }
+    Ok(true)
+}
+
+fn parse_phdr_packet(data: &[u8]) -> Result<CapturedLePacket> {
+    if data.len() < 10 {
+        return Err(Error::InvalidInput(
+            "BLE pseudo-header is truncated".to_owned(),
+        ));
+    }
+    let channel = BleChannel::from_rf_channel(data[0])?;
+    let flags = u16::from_le_bytes([data[8], data[9]]);
+    let phy = match flags & BLE_PHY_MASK {
+        0 => LePhy::Le1M,
+        BLE_PHY_LE_2M => LePhy::Le2M,
+        BLE_PHY_LE_CODED => LePhy::LeCoded,
+        _ => {
+            return Err(Error::InvalidInput(
+                "BLE pseudo-header names a reserved PHY".to_owned(),
+            ));
+        }
+    };
+    let mut link_layer = data[10..].to_vec();
+    if phy == LePhy::LeCoded {
+        // Coded packets carry the coding indicator between the access
+        // address and the PDU.
+        if link_layer.len() < 5 {
+            return Err(Error::InvalidInput(
+                "LE Coded packet is truncated".to_owned(),
+            ));
+        }
+        link_layer.remove(4);
+    }
+    if flags & BLE_DEWHITENED == 0 && link_layer.len() > 4 {
+        let mut bits = bytes_to_bits_lsb(&link_layer[4..]);
+        whiten_bits(&mut bits, channel);
+        link_layer.splice(4.., bits_to_bytes_lsb(&bits));
+    }
+    let direction = match flags & BLE_PDU_TYPE_MASK {
+        BLE_PDU_TYPE_CENTRAL_TO_PERIPHERAL => Some(LinkDirection::CentralToPeripheral),
+        BLE_PDU_TYPE_PERIPHERAL_TO_CENTRAL => Some(LinkDirection::PeripheralToCentral),
+        _ => None,
+    };
+    let crc = match (flags & BLE_CRC_CHECKED != 0, flags & BLE_CRC_VALID != 0) {
+        (true, true) => Some(CaptureCrcStatus::Valid),
+        (true, false) => Some(CaptureCrcStatus::Invalid),
+        (false, _) => None,
+    };
+    let mut packet = parse_link_layer_packet(&link_layer, Some(channel), crc)?;
+    packet.phy = Some(phy);
+    packet.direction = direction;
+    packet.signal_dbm = (flags & BLE_SIGNAL_POWER_VALID != 0).then_some(data[1] as i8);
+    if flags & BLE_ACCESS_ADDRESS_OFFENSES_VALID != 0 {
+        packet.pdu.access_address_errors = data[3];
+    }
+    packet.decrypted = flags & BLE_DECRYPTED != 0;
+    Ok(packet)
+}
+
+fn parse_nordic_packet(data: &[u8]) -> Result<CapturedLePacket> {
+    // Board identifier, the six-octet transport header, then the packet
+    // header whose first octet is its own length.
+    if data.len() < 8 || !matches!(data[6], NORDIC_EVENT_PACKET | NORDIC_EVENT_PACKET_DATA_PDU) {
+        return Err(Error::InvalidInput(
+            "Nordic BLE record is not a received-packet event".to_owned(),
+        ));
+    }
+    let header_length = usize::from(data[7]);
+    if header_length < 10 || data.len() < 7 + header_length {
+        return Err(Error::InvalidInput(
+            "Nordic BLE packet header is truncated".to_owned(),
+        ));
+    }
+    let flags = data[8];
+    let channel = BleChannel::new(data[9])?;
+    let phy = match (flags >> 4) & 0x07 {
+        0 => LePhy::Le1M,
+        1 => LePhy::Le2M,
+        2 => LePhy::LeCoded,
+        raw => {
+            return Err(Error::InvalidInput(format!(
+                "Nordic BLE header names reserved PHY {raw}"
+            )));
+        }
+    };
+    let mut link_layer = data[7 + header_length..].to_vec();
+    if phy == LePhy::LeCoded && link_layer.len() >= 5 {
+        link_layer.remove(4);
+    }
+    let crc = if flags & NORDIC_CRC_OK != 0 {
+        CaptureCrcStatus::Valid
+    } else {
+        CaptureCrcStatus::Invalid
+    };
+    let mut packet = parse_link_layer_packet(&link_layer, Some(channel), Some(crc))?;
+    packet.phy = Some(phy);
+    if packet.pdu.access_address != LE_ADV_ACCESS_ADDRESS {
+        packet.direction = Some(if flags & NORDIC_CENTRAL_TO_PERIPHERAL != 0 {
+            LinkDirection::CentralToPeripheral
+        } else {
+            LinkDirection::PeripheralToCentral
+        });
+    }
+    // The sniffer reports RSSI as a positive attenuation.
+    packet.signal_dbm = Some(0i8.saturating_sub_unsigned(data[10]));
+    Ok(packet)
+}
+
+/// Splits dewhitened access address, header, payload, and CRC octets.
+///
+/// The advertising access address selects the primary or secondary
+/// advertising layout from the channel; any other access address is parsed as
+/// a data-channel PDU, whose layout also covers periodic advertising without
+/// CTEInfo.
+fn parse_link_layer_packet(
+    bytes: &[u8],
+    channel: Option<BleChannel>,
+    crc: Option<CaptureCrcStatus>,
+) -> Result<CapturedLePacket> {
+    if bytes.len() < 9 {
+        return Err(Error::InvalidInput(format!(
+            "LE packet has {} octets; at least 9 are required",
+            bytes.len()
+        )));
+    }
+    let access_address = u32::from_le_bytes(bytes[..4].try_into().expect("length checked"));
+    let header = [bytes[4], bytes[5]];
+    let advertising = access_address == LE_ADV_ACCESS_ADDRESS;
+    let pdu_channel = channel.unwrap_or_else(|| {
+        BleChannel::new(if advertising { 37 } else { 0 }).expect("placeholder channels are valid")
+    });
+    let primary = advertising && pdu_channel.is_primary_advertising();
+    let cte_info_length = usize::from(!advertising && header[0] & 0x20 != 0);
+    let payload_length = if primary {
+        usize::from(header[1] & 0x3f)
+    } else {
+        usize::from(header[1])
+    };
+    let payload_start = 6 + cte_info_length;
+    if bytes.len() != payload_start + payload_length + 3 {
+        return Err(Error::InvalidInput(format!(
+            "LE packet has {} octets, but its header requires {}",
+            bytes.len(),
+            payload_start + payload_length + 3
+        )));
+    }
+    let crc_bytes: [u8; 3] = bytes[bytes.len() - 3..]
+        .try_into()
+        .expect("length checked above");
+    let crc = crc.unwrap_or_else(|| {
+        if advertising {
+            if crc24_bytes(&bytes[4..bytes.len() - 3], LE_ADV_CRC_INIT) == crc_bytes {
+                CaptureCrcStatus::Valid
+            } else {
+                CaptureCrcStatus::Invalid
+            }
+        } else {
+            CaptureCrcStatus::Unchecked
+        }
+    });
+    Ok(CapturedLePacket {
+        pdu: LePdu {
+            channel: pdu_channel,
+            access_address,
+            bit_offset: 0,
+            inverted: false,
+            access_address_errors: 0,
+            header,
+            cte_info: (cte_info_length == 1).then_some(bytes[6]),
+            payload: bytes[payload_start..bytes.len() - 3].to_vec(),
+            crc: crc_bytes,
+        },
+        channel_known: channel.is_some(),
+        phy: None,
+        direction: None,
+        crc,
+        signal_dbm: None,
+        timestamp_ns: 0,
+        link_type: 0,
+        interface_id: 0,
+        decrypted: false,
+        comments: Vec::new(),
+        flags: None,
+    })
 }
 
 #[cfg(test)]
@@ -204,7 +1459,7 @@ mod tests {
         assert_eq!(read_u32(&bytes, epb + epb_length - 4) as usize, epb_length);
 
         let captured = epb + 28;
-        assert_eq!(bytes[captured], 37);
+        assert_eq!(bytes[captured], 0);
         assert_eq!(read_u32(&bytes, captured + 4), LE_ADV_ACCESS_ADDRESS);
         assert_eq!(
             read_u16(&bytes, captured + 8),
@@ -247,7 +1502,7 @@ mod tests {
         let idb_length = read_u32(&bytes, shb_length + 4) as usize;
         let captured = shb_length + idb_length + 28;
 
-        assert_eq!(bytes[captured], 27);
+        assert_eq!(bytes[captured], 29);
         assert_eq!(read_u32(&bytes, captured + 4), 0x1234_5678);
         assert_eq!(
             &bytes[captured + 10..captured + 14],
@@ -255,9 +1510,618 @@ mod tests {
         );
     }
 
+    #[test]
+    fn records_known_data_channel_direction_in_pdu_type() {
+        let pdu = LePdu {
+            channel: BleChannel::new(9).unwrap(),
+            access_address: 0x1234_5678,
+            bit_offset: 0,
+            inverted: false,
+            access_address_errors: 0,
+            header: [0x01, 0x00],
+            cte_info: None,
+            payload: Vec::new(),
+            crc: [1, 2, 3],
+        };
+        let mut writer = PcapNgWriter::new(Vec::new()).unwrap();
+        writer
+            .write_pdu(
+                &pdu,
+                LeUncodedPhy::Le1M,
+                Some(LinkDirection::PeripheralToCentral),
+                0,
+            )
+            .unwrap();
+        let bytes = writer.into_inner();
+        let shb_length = read_u32(&bytes, 4) as usize;
+        let idb_length = read_u32(&bytes, shb_length + 4) as usize;
+        let captured = shb_length + idb_length + 28;
+        assert_eq!(
+            read_u16(&bytes, captured + 8) & 0x0380,
+            BLE_PDU_TYPE_PERIPHERAL_TO_CENTRAL
+        );
+    }
+
+    fn data_pdu(channel: u8, header: [u8; 2], payload: &[u8]) -> LePdu {
+        let mut pdu = header.to_vec();
+        pdu.extend_from_slice(payload);
+        LePdu::from_pdu_bytes(
+            BleChannel::new(channel).unwrap(),
+            crate::ble::LeFrameConfig::data(0x5065_4b4f, 0x7a_4f21).unwrap(),
+            &pdu,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn reads_back_written_packets_with_channel_phy_and_direction() {
+        let advertising = LePdu::from_pdu_bytes(
+            BleChannel::new(38).unwrap(),
+            crate::ble::LeFrameConfig::advertising(),
+            &[0x00, 0x09, 1, 2, 3, 4, 5, 6, 2, 1, 6],
+        )
+        .unwrap();
+        let data = data_pdu(17, [0x0e, 0x03], &[0xaa, 0xbb, 0xcc]);
+        let mut writer = PcapNgWriter::new(Vec::new()).unwrap();
+        writer
+            .write_pdu(&advertising, LeUncodedPhy::Le1M, None, 1_000)
+            .unwrap();
+        writer
+            .write_pdu(
+                &data,
+                LeUncodedPhy::Le2M,
+                Some(LinkDirection::PeripheralToCentral),
+                5_000_000_123,
+            )
+            .unwrap();
+        let bytes = writer.into_inner();
+
+        let mut reader = CaptureFileReader::new(bytes.as_slice()).unwrap();
+        let first = reader.next_packet().unwrap().unwrap();
+        assert_eq!(first.pdu, advertising);
+        assert!(first.channel_known);
+        assert_eq!(first.phy, Some(LePhy::Le1M));
+        assert_eq!(first.direction, None);
+        assert_eq!(first.crc, CaptureCrcStatus::Valid);
+        assert_eq!(first.timestamp_ns, 1_000);
+        assert_eq!(first.link_type, 256);
+
+        let second = reader.next_packet().unwrap().unwrap();
+        assert_eq!(second.pdu, data);
+        assert_eq!(second.phy, Some(LePhy::Le2M));
+        assert_eq!(second.direction, Some(LinkDirection::PeripheralToCentral));
+        assert_eq!(second.timestamp_ns, 5_000_000_123);
+        assert!(reader.next_packet().unwrap().is_none());
+    }
+
+    #[test]
+    fn writes_interfaces_section_options_and_packet_metadata() {
+        let section = PcapNgSection {
+            hardware: Some("synthetic".to_owned()),
+            os: None,
+            user_application: Some("blueoxide test".to_owned()),
+            comments: vec!["sample_rate_hz=4000000".to_owned()],
+        };
+        let interface = |channel: u8| PcapNgInterface {
+            name: Some(format!("ble{channel}")),
+            description: Some(format!("LE channel {channel}")),
+            hardware: None,
+            comments: Vec::new(),
+        };
+        let mut writer =
+            PcapNgWriter::with_interfaces(Vec::new(), &section, &[interface(37)]).unwrap();
+        assert_eq!(writer.add_interface(&interface(17)).unwrap(), 1);
+        assert_eq!(writer.interface_count(), 2);
+
+        let data = data_pdu(17, [0x0e, 0x03], &[0xaa, 0xbb, 0xcc]);
+        let options = PcapNgPacketOptions {
+            interface_id: 1,
+            sample_index: Some(4_000),
+            carrier_offset_hz: Some(-1_250.0),
+            deviation_hz: Some(250_000.0),
+            event_counter: Some(7),
+            decryption: Some(LeAclDecryptionStatus::New {
+                packet_counter: 3,
+                skipped_counters: 0,
+            }),
+            comments: vec!["retransmission".to_owned()],
+        };
+        writer
+            .write_pdu_with_options(
+                &data,
+                LeUncodedPhy::Le1M,
+                Some(LinkDirection::CentralToPeripheral),
+                9_000,
+                Some(&options),
+            )
+            .unwrap();
+        writer
+            .write_pdu(&data, LeUncodedPhy::Le1M, None, 10_000)
+            .unwrap();
+        assert!(matches!(
+            writer.write_pdu_with_options(
+                &data,
+                LeUncodedPhy::Le1M,
+                None,
+                11_000,
+                Some(&PcapNgPacketOptions {
+                    interface_id: 2,
+                    ..PcapNgPacketOptions::default()
+                }),
+            ),
+            Err(Error::InvalidInput(_))
+        ));
+        let bytes = writer.into_inner();
+
+        let shb_length = read_u32(&bytes, 4) as usize;
+        let shb = &bytes[..shb_length];
+        assert!(shb.windows(9).any(|window| window == b"synthetic"));
+        assert!(shb.windows(14).any(|window| window == b"blueoxide test"));
+        let idb_length = read_u32(&bytes, shb_length + 4) as usize;
+        assert!(
+            bytes[shb_length..shb_length + idb_length]
+                .windows(5)
+                .any(|window| window == b"ble37")
+        );
+
+        let mut reader = CaptureFileReader::new(bytes.as_slice()).unwrap();
+        let annotated = reader.next_packet().unwrap().unwrap();
+        assert_eq!(annotated.pdu, data);
+        assert_eq!(annotated.interface_id, 1);
+        assert_eq!(
+            annotated.comments,
+            [
+                "sample=4000 cfo_hz=-1250.0 deviation_hz=250000.0 event=7 decryption=new packet_counter=3",
+                "retransmission",
+            ]
+        );
+        assert_eq!(annotated.flags, Some(0x0000_0061));
+        let plain = reader.next_packet().unwrap().unwrap();
+        assert_eq!(plain.interface_id, 0);
+        assert!(plain.comments.is_empty());
+        assert_eq!(plain.flags, None);
+    }
+
+    #[test]
+    fn marks_decrypted_pdus_and_formats_session_key_material() {
+        let received = data_pdu(17, [0x06, 0x02], &[0x01, 0x02]);
+        let plaintext = DataChannelPdu::from(received.clone());
+        let material = crate::ll_control::LeEncryptionMaterial::derive(
+            [0x11; 16],
+            crate::ll_control::EncryptionRequest {
+                random_number: [0; 8],
+                encrypted_diversifier: 0,
+                central_session_key_diversifier: [0x22; 8],
+                central_initialization_vector: [0xba, 0xdc, 0xab, 0x24],
+            },
+            crate::ll_control::EncryptionResponse {
+                peripheral_session_key_diversifier: [0x33; 8],
+                peripheral_initialization_vector: [0xde, 0xaf, 0xba, 0xbe],
+            },
+        );
+        let comment = encryption_material_comment(
+            0x5065_4b4f,
+            material.session_key(),
+            material.initialization_vector(),
+        );
+        assert!(comment.starts_with("ll_encryption access_address=0x50654b4f session_key="));
+        assert!(comment.ends_with(" iv=badcab24deafbabe"));
+        let options = PcapNgPacketOptions {
+            comments: vec![comment.clone()],
+            ..PcapNgPacketOptions::default()
+        };
+
+        let mut writer = PcapNgWriter::new(Vec::new()).unwrap();
+        writer
+            .write_decrypted_pdu(
+                &plaintext,
+                LeUncodedPhy::Le2M,
+                Some(LinkDirection::PeripheralToCentral),
+                5_000,
+                Some(&options),
+            )
+            .unwrap();
+        writer
+            .write_pdu(&received, LeUncodedPhy::Le1M, None, 6_000)
+            .unwrap();
+        let bytes = writer.into_inner();
+
+        let mut reader = CaptureFileReader::new(bytes.as_slice()).unwrap();
+        let decrypted = reader.next_packet().unwrap().unwrap();
+        assert!(decrypted.decrypted);
+        assert_eq!(decrypted.pdu.payload, [0x01, 0x02]);
+        assert_eq!(decrypted.phy, Some(LePhy::Le2M));
+        assert_eq!(
+            decrypted.direction,
+            Some(LinkDirection::PeripheralToCentral)
+        );
+        assert_eq!(decrypted.comments, [comment]);
+        let ciphertext = reader.next_packet().unwrap().unwrap();
+        assert!(!ciphertext.decrypted);
+    }
+
+    #[test]
+    fn marks_crc_failures_checked_but_not_valid() {
+        let mut failed = data_pdu(9, [0x02, 0x02], &[0x01, 0x02]);
+        failed.crc[0] ^= 0xff;
+        let received = ReceivedLePdu {
+            pdu: failed.clone(),
+            phy: LeUncodedPhy::Le1M,
+            access_address_sample: 100,
+            symbol_phase: 0,
+            estimated_carrier_offset_hz: 0.0,
+            estimated_deviation_hz: 250_000.0,
+            discriminator_separation: 1.0,
+        };
+        let mut writer = PcapNgWriter::new(Vec::new()).unwrap();
+        writer.write_crc_failure(&received, 2_000, None).unwrap();
+        let bytes = writer.into_inner();
+
+        let mut reader = CaptureFileReader::new(bytes.as_slice()).unwrap();
+        let packet = reader.next_packet().unwrap().unwrap();
+        assert_eq!(packet.crc, CaptureCrcStatus::Invalid);
+        assert_eq!(packet.pdu.crc, failed.crc);
+    }
+
+    #[test]
+    fn reads_big_endian_classic_pcap_without_channel() {
+        let advertising = [0xd6, 0xbe, 0x89, 0x8e, 0x00, 0x06, 1, 2, 3, 4, 5, 6];
+        let crc = crc24_bytes(&advertising[4..], LE_ADV_CRC_INIT);
+        let mut file = Vec::new();
+        file.extend_from_slice(&PCAP_MAGIC_MICROSECONDS.to_be_bytes());
+        file.extend_from_slice(&2u16.to_be_bytes());
+        file.extend_from_slice(&4u16.to_be_bytes());
+        file.extend_from_slice(&[0; 8]);
+        file.extend_from_slice(&65_535u32.to_be_bytes());
+        file.extend_from_slice(&LINKTYPE_BLUETOOTH_LE_LL.to_be_bytes());
+        for (seconds, corrupt) in [(7u32, false), (8, true)] {
+            let mut packet = advertising.to_vec();
+            packet.extend_from_slice(&crc);
+            if corrupt {
+                packet[14] ^= 1;
+            }
+            file.extend_from_slice(&seconds.to_be_bytes());
+            file.extend_from_slice(&250u32.to_be_bytes());
+            file.extend_from_slice(&(packet.len() as u32).to_be_bytes());
+            file.extend_from_slice(&(packet.len() as u32).to_be_bytes());
+            file.extend_from_slice(&packet);
+        }
+
+        let mut reader = CaptureFileReader::new(file.as_slice()).unwrap();
+        let packet = reader.next_packet().unwrap().unwrap();
+        assert!(!packet.channel_known);
+        assert_eq!(packet.pdu.channel.index(), 37);
+        assert_eq!(packet.pdu.payload, [1, 2, 3, 4, 5, 6]);
+        assert_eq!(packet.crc, CaptureCrcStatus::Valid);
+        assert_eq!(packet.timestamp_ns, 7_000_250_000);
+        assert_eq!(packet.phy, None);
+        assert_eq!(
+            reader.next_packet().unwrap().unwrap().crc,
+            CaptureCrcStatus::Invalid
+        );
+        assert!(reader.next_packet().unwrap().is_none());
+    }
+
+    fn pcapng_interface(link_type: u16, options: &[u8]) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&link_type.to_le_bytes());
+        body.extend_from_slice(&[0; 2]);
+        body.extend_from_slice(&0u32.to_le_bytes());
+        body.extend_from_slice(options);
+        let mut block = Vec::new();
+        write_block(&mut block, INTERFACE_DESCRIPTION_BLOCK, &body).unwrap();
+        block
+    }
+
+    fn pcapng_packet(interface_id: u32, timestamp: u64, data: &[u8]) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&interface_id.to_le_bytes());
+        body.extend_from_slice(&((timestamp >> 32) as u32).to_le_bytes());
+        body.extend_from_slice(&(timestamp as u32).to_le_bytes());
+        body.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        body.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        body.extend_from_slice(data);
+        body.resize(body.len().next_multiple_of(4), 0);
+        let mut block = Vec::new();
+        write_block(&mut block, ENHANCED_PACKET_BLOCK, &body).unwrap();
+        block
+    }
+
+    #[test]
+    fn reads_nordic_and_whitened_phdr_interfaces_and_skips_others() {
+        let data = data_pdu(5, [0x03, 0x01], &[0x06]);
+        let link_layer = data.link_layer_bytes();
+
+        let mut file = PcapNgWriter::new(Vec::new()).unwrap().into_inner();
+        file.truncate(read_u32(&file, 4) as usize);
+        // Interface 0: Ethernet, skipped. Interface 1: Nordic with default
+        // microsecond timestamps. Interface 2: PHDR with millisecond
+        // timestamps and a ten-second offset.
+        file.extend(pcapng_interface(1, &[]));
+        file.extend(pcapng_interface(272, &[]));
+        let mut options = Vec::new();
+        options.extend_from_slice(&OPTION_IF_TSRESOL.to_le_bytes());
+        options.extend_from_slice(&1u16.to_le_bytes());
+        options.extend_from_slice(&[3, 0, 0, 0]);
+        options.extend_from_slice(&OPTION_IF_TSOFFSET.to_le_bytes());
+        options.extend_from_slice(&8u16.to_le_bytes());
+        options.extend_from_slice(&10i64.to_le_bytes());
+        options.extend_from_slice(&[0; 4]);
+        file.extend(pcapng_interface(256, &options));
+
+        file.extend(pcapng_packet(0, 1, &[0; 14]));
+        let mut nordic = vec![0x00, 0x00, 0x00, 0x03, 0x01, 0x00, 0x06, 10];
+        nordic.extend_from_slice(&[0x13, 5, 62, 0x34, 0x12, 0, 0, 0, 0]);
+        nordic.extend_from_slice(&link_layer);
+        file.extend(pcapng_packet(1, 2_000_000, &nordic));
+
+        let mut whitened = link_layer.clone();
+        let mut bits = bytes_to_bits_lsb(&whitened[4..]);
+        whiten_bits(&mut bits, data.channel);
+        whitened.splice(4.., bits_to_bytes_lsb(&bits));
+        let mut phdr = vec![data.channel.rf_channel(), (-55i8) as u8, 0, 0];
+        phdr.extend_from_slice(&data.access_address.to_le_bytes());
+        phdr.extend_from_slice(
+            &(BLE_SIGNAL_POWER_VALID | BLE_PDU_TYPE_CENTRAL_TO_PERIPHERAL).to_le_bytes(),
+        );
+        phdr.extend_from_slice(&whitened);
+        file.extend(pcapng_packet(2, 1_500, &phdr));
+
+        let mut reader = CaptureFileReader::new(file.as_slice()).unwrap();
+        let nordic = reader.next_packet().unwrap().unwrap();
+        assert_eq!(nordic.pdu, data);
+        assert_eq!(nordic.link_type, 272);
+        assert_eq!(nordic.interface_id, 1);
+        assert_eq!(nordic.phy, Some(LePhy::Le2M));
+        assert_eq!(nordic.direction, Some(LinkDirection::CentralToPeripheral));
+        assert_eq!(nordic.crc, CaptureCrcStatus::Valid);
+        assert_eq!(nordic.signal_dbm, Some(-62));
+        assert_eq!(nordic.timestamp_ns, 2_000_000_000);
+
+        let phdr = reader.next_packet().unwrap().unwrap();
+        assert_eq!(phdr.pdu, data);
+        assert_eq!(phdr.direction, Some(LinkDirection::CentralToPeripheral));
+        assert_eq!(phdr.crc, CaptureCrcStatus::Unchecked);
+        assert_eq!(phdr.signal_dbm, Some(-55));
+        assert_eq!(phdr.timestamp_ns, 11_500_000_000);
+        assert!(reader.next_packet().unwrap().is_none());
+        assert_eq!(reader.skipped_packets(), 1);
+    }
+
+    #[test]
+    fn skips_malformed_records_between_good_packets() {
+        let first = data_pdu(3, [0x01, 0x02], &[0x11, 0x22]);
+        let last = data_pdu(4, [0x02, 0x01], &[0x33]);
+        let mut writer = PcapNgWriter::new(Vec::new()).unwrap();
+        let header_end = writer.bytes_written() as usize;
+        writer
+            .write_pdu(&first, LeUncodedPhy::Le1M, None, 1_000)
+            .unwrap();
+        let first_end = writer.bytes_written() as usize;
+        writer
+            .write_pdu(&last, LeUncodedPhy::Le1M, None, 3_000)
+            .unwrap();
+        let bytes = writer.into_inner();
+
+        // The first packet's record with a Length octet one larger than the
+        // octets captured, as a snaplen cut or corrupted header leaves it.
+        let captured = read_u32(&bytes, header_end + 20) as usize;
+        let mut malformed = bytes[header_end + 28..header_end + 28 + captured].to_vec();
+        malformed[15] += 1;
+        let mut file = bytes[..first_end].to_vec();
+        file.extend(pcapng_packet(0, 2_000, &malformed));
+        file.extend(pcapng_packet(0, 2_500, &[0; 12]));
+        file.extend_from_slice(&bytes[first_end..]);
+
+        let mut reader = CaptureFileReader::new(file.as_slice()).unwrap();
+        assert_eq!(reader.next_packet().unwrap().unwrap().pdu, first);
+        assert_eq!(reader.next_packet().unwrap().unwrap().pdu, last);
+        assert!(reader.next_packet().unwrap().is_none());
+        assert_eq!(reader.malformed_packets(), 2);
+        assert_eq!(reader.skipped_packets(), 0);
+    }
+
+    #[test]
+    fn rejects_truncated_and_inconsistent_captures() {
+        let mut truncated = PcapNgWriter::new(Vec::new()).unwrap().into_inner();
+        truncated.extend_from_slice(&ENHANCED_PACKET_BLOCK.to_le_bytes());
+        let mut reader = CaptureFileReader::new(truncated.as_slice()).unwrap();
+        assert!(reader.next_packet().is_err());
+
+        assert!(CaptureFileReader::new([0u8; 24].as_slice()).is_err());
+    }
+
     #[test]
     fn converts_sample_index_to_nanoseconds_without_float_rounding() {
         assert_eq!(sample_timestamp_ns(10, 4, 4_000_000).unwrap(), 1_010);
         assert!(sample_timestamp_ns(0, 1, 0).is_err());
     }
+
+    #[test]
+    fn formats_utc_file_timestamps() {
+        assert_eq!(utc_file_timestamp(0), "19700101000000");
+        assert_eq!(
+            utc_file_timestamp(951_827_696_500_000_000),
+            "20000229123456"
+        );
+        assert_eq!(
+            utc_file_timestamp(1_792_454_399_000_000_000),
+            "20261019235959"
+        );
+    }
+
+    #[test]
+    fn rotates_into_independently_readable_ring_files() {
+        let nonce = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let directory = std::env::temp_dir().join(format!("blueoxide-{nonce}-rotation"));
+        fs::create_dir(&directory).unwrap();
+        let packet = ReceivedAdvertisingPdu {
+            pdu: AdvertisingPdu {
+                channel: BleChannel::new(37).unwrap(),
+                access_address: LE_ADV_ACCESS_ADDRESS,
+                bit_offset: 8,
+                inverted: false,
+                access_address_errors: 0,
+                header: [0x00, 0x02],
+                payload: vec![0xaa, 0xbb],
+                crc: [1, 2, 3],
+            },
+            phy: LeUncodedPhy::Le1M,
+            access_address_sample: 400,
+            symbol_phase: 0,
+            estimated_carrier_offset_hz: 0.0,
+            estimated_deviation_hz: 250_000.0,
+            discriminator_separation: 1.0,
+        };
+        let rotation = PcapNgRotation {
+            maximum_file_packets: Some(2),
+            ring_files: Some(2),
+            ..PcapNgRotation::default()
+        };
+        let start_ns = 1_792_454_399_000_000_000;
+        let mut writer = RotatingPcapNgWriter::create(
+            directory.join("capture.pcapng"),
+            PcapNgSection {
+                hardware: Some("synthetic".to_owned()),
+                ..PcapNgSection::default()
+            },
+            vec![PcapNgInterface::default()],
+            rotation,
+            start_ns,
+        )
+        .unwrap();
+        assert_eq!(
+            writer.current_path(),
+            directory.join("capture_00001_20261019235959.pcapng")
+        );
+        for index in 0..5u64 {
+            writer
+                .packet_writer(start_ns + index * 1_000_000_000)
+                .unwrap()
+                .write_advertising(&packet, start_ns + index * 1_000_000_000)
+                .unwrap();
+        }
+        writer.flush().unwrap();
+
+        let retained: Vec<_> = writer.retained_files().map(Path::to_path_buf).collect();
+        assert_eq!(
+            retained,
+            [
+                directory.join("capture_00002_20261020000001.pcapng"),
+                directory.join("capture_00003_20261020000003.pcapng"),
+            ]
+        );
+        assert!(
+            !directory
+                .join("capture_00001_20261019235959.pcapng")
+                .exists()
+        );
+        let mut counts = Vec::new();
+        for path in &retained {
+            let bytes = fs::read(path).unwrap();
+            assert_eq!(read_u32(&bytes, 0), SECTION_HEADER_BLOCK);
+            let mut reader = CaptureFileReader::new(bytes.as_slice()).unwrap();
+            let mut count = 0;
+            while let Some(captured) = reader.next_packet().unwrap() {
+                assert_eq!(captured.pdu.payload, [0xaa, 0xbb]);
+                count += 1;
+            }
+            counts.push(count);
+        }
+        assert_eq!(counts, [2, 1]);
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn rotates_by_size_and_duration_and_flushes_on_interval() {
+        let nonce = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let directory = std::env::temp_dir().join(format!("blueoxide-{nonce}-rotation-limits"));
+        fs::create_dir(&directory).unwrap();
+        let pdu = LePdu {
+            channel: BleChannel::new(37).unwrap(),
+            access_address: LE_ADV_ACCESS_ADDRESS,
+            bit_offset: 0,
+            inverted: false,
+            access_address_errors: 0,
+            header: [0x00, 0x02],
+            cte_info: None,
+            payload: vec![0xaa, 0xbb],
+            crc: [1, 2, 3],
+        };
+        let mut writer = RotatingPcapNgWriter::create(
+            directory.join("size"),
+            PcapNgSection::default(),
+            vec![PcapNgInterface::default()],
+            PcapNgRotation {
+                maximum_file_bytes: Some(1),
+                flush_interval: Some(Duration::from_secs(60)),
+                ..PcapNgRotation::default()
+            },
+            0,
+        )
+        .unwrap();
+        for _ in 0..3 {
+            writer
+                .packet_writer(0)
+                .unwrap()
+                .write_pdu(&pdu, LeUncodedPhy::Le1M, None, 0)
+                .unwrap();
+        }
+        assert_eq!(writer.retained_files().count(), 3);
+        assert_eq!(
+            writer.current_path(),
+            directory.join("size_00003_19700101000000")
+        );
+        let flushed_at = writer.last_flush;
+        let unflushed = fs::metadata(writer.current_path()).unwrap().len();
+        writer
+            .flush_if_due(flushed_at + Duration::from_secs(59))
+            .unwrap();
+        assert_eq!(
+            fs::metadata(writer.current_path()).unwrap().len(),
+            unflushed
+        );
+        writer
+            .flush_if_due(flushed_at + Duration::from_secs(60))
+            .unwrap();
+        assert!(fs::metadata(writer.current_path()).unwrap().len() > unflushed);
+
+        let mut writer = RotatingPcapNgWriter::create(
+            directory.join("duration.pcapng"),
+            PcapNgSection::default(),
+            vec![PcapNgInterface::default()],
+            PcapNgRotation {
+                maximum_file_duration: Some(Duration::from_secs(10)),
+                ..PcapNgRotation::default()
+            },
+            0,
+        )
+        .unwrap();
+        for seconds in [0, 9, 10, 19, 25] {
+            writer.packet_writer(seconds * 1_000_000_000).unwrap();
+        }
+        assert_eq!(writer.retained_files().count(), 3);
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn rejects_unbounded_ring_buffers() {
+        let ring_without_limit = PcapNgRotation {
+            ring_files: Some(4),
+            ..PcapNgRotation::default()
+        };
+        assert!(ring_without_limit.validate().is_err());
+        let zero_limit = PcapNgRotation {
+            maximum_file_packets: Some(0),
+            ..PcapNgRotation::default()
+        };
+        assert!(zero_limit.validate().is_err());
+        assert!(PcapNgRotation::default().validate().is_ok());
+    }
 }
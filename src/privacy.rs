@@ -0,0 +1,330 @@
+//! LE privacy: resolving private addresses to the identities behind them.
+//!
+//! A resolvable private address carries a 24-bit `prand` and a hash of it
+//! computed with the owner's IRK by the `ah` function. Whoever holds the IRK
+//! can recognize every address the device rotates through.
+//! [`IdentityResolver`] keeps a set of IRKs, from the keyring or from captured
+//! Identity Information PDUs, and maps addresses to identity addresses.
+//!
+//! IRKs use the octet order of SMP PDU fields, least significant octet first,
+//! as [`crate::keyring`] does.
+
+use crate::advertising::{AddressKind, DecodedAdvertisingPdu, DeviceAddress};
+use crate::crypto::aes_128_encrypt_block;
+use crate::keyring::{IdentityResolvingKeyEntry, LeKeyring};
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{Display, Formatter};
+
+/// Random address hash function `ah`. `prand` is the three most significant
+/// octets of the address, least significant octet first, and so is the
+/// returned hash.
+pub fn ah(irk: [u8; 16], prand: [u8; 3]) -> [u8; 3] {
+    let mut key = irk;
+    key.reverse();
+    let mut block = [0u8; 16];
+    block[13..].copy_from_slice(&[prand[2], prand[1], prand[0]]);
+    let output = aes_128_encrypt_block(key, block);
+    [output[15], output[14], output[13]]
+}
+
+/// Whether `address` is a resolvable private address: a random address whose
+/// two most significant bits are `0b01`.
+pub const fn is_resolvable_private_address(address: DeviceAddress, kind: AddressKind) -> bool {
+    matches!(kind, AddressKind::Random) && address.0[5] >> 6 == 0b01
+}
+
+/// Whether `address` was generated from `irk`.
+pub fn resolves(irk: [u8; 16], address: DeviceAddress) -> bool {
+    let [hash_0, hash_1, hash_2, prand_0, prand_1, prand_2] = address.0;
+    ah(irk, [prand_0, prand_1, prand_2]) == [hash_0, hash_1, hash_2]
+}
+
+/// The field an annotated address was decoded from.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AddressRole {
+    /// AdvA of an advertising PDU or CONNECT_IND.
+    Advertiser,
+    /// InitA of a CONNECT_IND.
+    Initiator,
+    /// ScanA of a SCAN_REQ.
+    Scanner,
+    /// TargetA of a directed advertising PDU.
+    Target,
+    /// The connection address of the device that sent an SMP Identity
+    /// Address Information PDU, matched to the identity it distributed.
+    SmpIdentityAddress,
+}
+
+impl Display for AddressRole {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        formatter.write_str(match self {
+            Self::Advertiser => "adv-a",
+            Self::Initiator => "init-a",
+            Self::Scanner => "scan-a",
+            Self::Target => "target-a",
+            Self::SmpIdentityAddress => "smp-identity-address",
+        })
+    }
+}
+
+/// An over-the-air address and the identity address it resolved to.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ResolvedAddress {
+    pub role: AddressRole,
+    pub address: (DeviceAddress, AddressKind),
+    pub identity: (DeviceAddress, AddressKind),
+}
+
+/// Addresses whose resolution outcome is cached. Unattended captures see RPAs
+/// rotate for hours, so the oldest outcomes are forgotten past this bound and
+/// recomputed if the address comes back.
+const RESOLVED_ADDRESS_LIMIT: usize = 4096;
+
+/// A set of IRKs with a bounded cache of the addresses already tried.
+#[derive(Clone, Default)]
+pub struct IdentityResolver {
+    keys: Vec<IdentityResolvingKeyEntry>,
+    resolved: HashMap<DeviceAddress, Option<usize>>,
+    /// Cached addresses, oldest first.
+    resolution_order: VecDeque<DeviceAddress>,
+}
+
+impl std::fmt::Debug for IdentityResolver {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        formatter
+            .debug_struct("IdentityResolver")
+            .field("keys", &self.keys.len())
+            .field("resolved", &self.resolved.len())
+            .finish()
+    }
+}
+
+impl IdentityResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_keyring(keyring: &LeKeyring) -> Self {
+        let mut resolver = Self::new();
+        for entry in keyring.identity_resolving_keys() {
+            resolver.add(*entry);
+        }
+        resolver
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Adds an IRK, returning `false` when the set already holds it. Addresses
+    /// that no earlier key resolved are tried again.
+    pub fn add(&mut self, entry: IdentityResolvingKeyEntry) -> bool {
+        if self.keys.contains(&entry) {
+            return false;
+        }
+        self.keys.push(entry);
+        self.resolved.retain(|_, key| key.is_some());
+        let resolved = &self.resolved;
+        self.resolution_order
+            .retain(|address| resolved.contains_key(address));
+        true
+    }
+
+    /// The identity address of a resolvable private address, or `None` for
+    /// other addresses and for RPAs of unknown devices.
+    pub fn resolve(
+        &mut self,
+        address: DeviceAddress,
+        kind: AddressKind,
+    ) -> Option<(DeviceAddress, AddressKind)> {
+        if !is_resolvable_private_address(address, kind) {
+            return None;
+        }
+        let key = match self.resolved.get(&address) {
+            Some(key) => *key,
+            None => {
+                let key = self
+                    .keys
+                    .iter()
+                    .position(|entry| resolves(entry.key, address));
+                if self.resolution_order.len() == RESOLVED_ADDRESS_LIMIT
+                    && let Some(oldest) = self.resolution_order.pop_front()
+                {
+                    self.resolved.remove(&oldest);
+                }
+                self.resolved.insert(address, key);
+                self.resolution_order.push_back(address);
+                key
+            }
+        };
+        key.map(|key| self.keys[key].address)
+    }
+
+    /// Resolves the AdvA, InitA, ScanA, and TargetA fields of a decoded
+    /// advertising PDU.
+    pub fn resolve_advertising(&mut self, pdu: &DecodedAdvertisingPdu) -> Vec<ResolvedAddress> {
+        advertising_addresses(pdu)
+            .into_iter()
+            .filter_map(|(role, address, kind)| {
+                self.resolve(address, kind).map(|identity| ResolvedAddress {
+                    role,
+                    address: (address, kind),
+                    identity,
+                })
+            })
+            .collect()
+    }
+}
+
+fn advertising_addresses(
+    pdu: &DecodedAdvertisingPdu,
+) -> Vec<(AddressRole, DeviceAddress, AddressKind)> {
+    match pdu {
+        DecodedAdvertisingPdu::AdvInd {
+            advertiser,
+            advertiser_kind,
+            ..
+        }
+        | DecodedAdvertisingPdu::AdvNonconnInd {
+            advertiser,
+            advertiser_kind,
+            ..
+        }
+        | DecodedAdvertisingPdu::ScanRsp {
+            advertiser,
+            advertiser_kind,
+            ..
+        }
+        | DecodedAdvertisingPdu::AdvScanInd {
+            advertiser,
+            advertiser_kind,
+            ..
+        } => vec![(AddressRole::Advertiser, *advertiser, *advertiser_kind)],
+        DecodedAdvertisingPdu::AdvDirectInd {
+            advertiser,
+            advertiser_kind,
+            target,
+            target_kind,
+        } => vec![
+            (AddressRole::Advertiser, *advertiser, *advertiser_kind),
+            (AddressRole::Target, *target, *target_kind),
+        ],
+        DecodedAdvertisingPdu::ScanReq {
+            scanner,
+            scanner_kind,
+            advertiser,
+            advertiser_kind,
+        } => vec![
+            (AddressRole::Scanner, *scanner, *scanner_kind),
+            (AddressRole::Advertiser, *advertiser, *advertiser_kind),
+        ],
+        DecodedAdvertisingPdu::ConnectInd {
+            initiator,
+            initiator_kind,
+            advertiser,
+            advertiser_kind,
+            ..
+        } => vec![
+            (AddressRole::Initiator, *initiator, *initiator_kind),
+            (AddressRole::Advertiser, *advertiser, *advertiser_kind),
+        ],
+        DecodedAdvertisingPdu::AdvExtInd { header } => [
+            header
+                .advertiser_address
+                .zip(header.advertiser_address_kind)
+                .map(|(address, kind)| (AddressRole::Advertiser, address, kind)),
+            header
+                .target_address
+                .zip(header.target_address_kind)
+                .map(|(address, kind)| (AddressRole::Target, address, kind)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect(),
+        DecodedAdvertisingPdu::ExtendedOrReserved { .. } => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The IRK of the Core specification's `ah` sample data, in field order.
+    const IRK: [u8; 16] = [
+        0x9b, 0x7d, 0x39, 0x0a, 0xa6, 0x10, 0x10, 0x34, 0x05, 0xad, 0xc8, 0x57, 0xa3, 0x34, 0x02,
+        0xec,
+    ];
+    /// 70:81:94:0D:FB:AA, the RPA built from the sample `prand` and hash.
+    const RPA: DeviceAddress = DeviceAddress([0xaa, 0xfb, 0x0d, 0x94, 0x81, 0x70]);
+
+    fn identity() -> (DeviceAddress, AddressKind) {
+        (DeviceAddress([1, 2, 3, 4, 5, 6]), AddressKind::Public)
+    }
+
+    #[test]
+    fn ah_matches_the_core_specification_sample_data() {
+        assert_eq!(ah(IRK, [0x94, 0x81, 0x70]), [0xaa, 0xfb, 0x0d]);
+        assert!(is_resolvable_private_address(RPA, AddressKind::Random));
+        assert!(!is_resolvable_private_address(RPA, AddressKind::Public));
+        assert!(resolves(IRK, RPA));
+        assert!(!resolves([0; 16], RPA));
+    }
+
+    #[test]
+    fn resolves_advertising_addresses_once_the_irk_is_known() {
+        let pdu = DecodedAdvertisingPdu::AdvDirectInd {
+            advertiser: RPA,
+            advertiser_kind: AddressKind::Random,
+            target: DeviceAddress([0x11, 0x22, 0x33, 0x44, 0x55, 0xc6]),
+            target_kind: AddressKind::Random,
+        };
+        let mut resolver = IdentityResolver::new();
+        assert!(resolver.resolve_advertising(&pdu).is_empty());
+
+        let entry = IdentityResolvingKeyEntry {
+            address: identity(),
+            key: IRK,
+        };
+        assert!(resolver.add(entry));
+        assert!(!resolver.add(entry));
+        assert_eq!(
+            resolver.resolve_advertising(&pdu),
+            [ResolvedAddress {
+                role: AddressRole::Advertiser,
+                address: (RPA, AddressKind::Random),
+                identity: identity(),
+            }]
+        );
+        assert_eq!(resolver.resolve(RPA, AddressKind::Public), None);
+    }
+
+    #[test]
+    fn bounds_the_cache_of_rotating_addresses() {
+        let mut resolver = IdentityResolver::new();
+        resolver.add(IdentityResolvingKeyEntry {
+            address: identity(),
+            key: IRK,
+        });
+        assert_eq!(resolver.resolve(RPA, AddressKind::Random), Some(identity()));
+        for index in 0..RESOLVED_ADDRESS_LIMIT as u32 + 10 {
+            let [low, middle, high, _] = index.to_le_bytes();
+            let address = DeviceAddress([low, middle, high, 0, 0, 0x40]);
+            assert_eq!(resolver.resolve(address, AddressKind::Random), None);
+        }
+        assert_eq!(resolver.resolved.len(), RESOLVED_ADDRESS_LIMIT);
+        assert_eq!(resolver.resolution_order.len(), RESOLVED_ADDRESS_LIMIT);
+        assert!(!resolver.resolved.contains_key(&RPA));
+        assert_eq!(resolver.resolve(RPA, AddressKind::Random), Some(identity()));
+
+        resolver.add(IdentityResolvingKeyEntry {
+            address: (DeviceAddress([6, 5, 4, 3, 2, 1]), AddressKind::Public),
+            key: [0x11; 16],
+        });
+        assert_eq!(resolver.resolved.len(), 1);
+        assert_eq!(resolver.resolution_order, [RPA]);
+    }
+}
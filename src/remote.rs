@@ -0,0 +1,653 @@
+use crate::complex::Complex32;
+use crate::iq::{IqFormat, parse_iq_bytes};
+use crate::sdr::{IqSource, ReadMetadata, SdrCapabilities, SdrConfig, SdrKind};
+use crate::{Error, Result};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+pub const REMOTE_PROTOCOL_MAGIC: [u8; 4] = *b"BORS";
+pub const REMOTE_PROTOCOL_VERSION: u8 = 1;
+/// Largest read a client may request in one round trip.
+pub const REMOTE_MAXIMUM_READ_SAMPLES: usize = 1 << 20;
+
+const FRAME_HEADER_LENGTH: usize = 5;
+const SAMPLES_HEADER_LENGTH: usize = 21;
+const MAXIMUM_FRAME_PAYLOAD: usize = SAMPLES_HEADER_LENGTH + REMOTE_MAXIMUM_READ_SAMPLES * 8;
+/// Added to each read timeout so a slow link is not mistaken for a dead server.
+const RESPONSE_GRACE: Duration = Duration::from_secs(5);
+
+const REQUEST_CONFIGURE: u8 = 0x01;
+const REQUEST_START: u8 = 0x02;
+const REQUEST_READ: u8 = 0x03;
+const REQUEST_STOP: u8 = 0x04;
+const RESPONSE_HELLO: u8 = 0x81;
+const RESPONSE_OK: u8 = 0x82;
+const RESPONSE_SAMPLES: u8 = 0x83;
+const RESPONSE_ERROR: u8 = 0xff;
+
+const SAMPLES_FLAG_OVERRUN: u8 = 0x01;
+const SAMPLES_FLAG_END_OF_STREAM: u8 = 0x02;
+
+const ERROR_CONFIGURATION: u8 = 1;
+const ERROR_INPUT: u8 = 2;
+const ERROR_STATE: u8 = 3;
+const ERROR_OTHER: u8 = 4;
+
+fn kind_code(kind: SdrKind) -> u8 {
+    match kind {
+        SdrKind::LimeSdr => 1,
+        SdrKind::BladeRf => 2,
+        SdrKind::Xtrx => 3,
+        SdrKind::Stream => 4,
+        SdrKind::Replay => 5,
+    }
+}
+
+fn kind_from_code(code: u8) -> Result<SdrKind> {
+    match code {
+        1 => Ok(SdrKind::LimeSdr),
+        2 => Ok(SdrKind::BladeRf),
+        3 => Ok(SdrKind::Xtrx),
+        4 => Ok(SdrKind::Stream),
+        5 => Ok(SdrKind::Replay),
+        _ => Err(Error::InvalidInput(format!(
+            "remote source reported unknown device kind {code}"
+        ))),
+    }
+}
+
+fn write_frame(writer: &mut impl Write, kind: u8, payload: &[u8]) -> Result<()> {
+    let length = u32::try_from(payload.len())
+        .map_err(|_| Error::InvalidInput("remote frame exceeds 4 GiB".to_owned()))?;
+    writer.write_all(&[kind])?;
+    writer.write_all(&length.to_be_bytes())?;
+    writer.write_all(payload)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Reads one frame, returning `None` when the peer closes the connection at a
+/// frame boundary.
+fn read_frame(reader: &mut impl Read) -> Result<Option<(u8, Vec<u8>)>> {
+    let mut header = [0u8; FRAME_HEADER_LENGTH];
+    let mut filled = 0;
+    while filled < header.len() {
+        match reader.read(&mut header[filled..]) {
+            Ok(0) if filled == 0 => return Ok(None),
+            Ok(0) => {
+                return Err(Error::InvalidInput(
+                    "remote connection closed inside a frame header".to_owned(),
+                ));
+            }
+            Ok(count) => filled += count,
+            Err(error) if error.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error.into()),
+        }
+    }
+    let length = u32::from_be_bytes([header[1], header[2], header[3], header[4]]) as usize;
+    if length > MAXIMUM_FRAME_PAYLOAD {
+        return Err(Error::InvalidInput(format!(
+            "remote frame payload of {length} octets exceeds the {MAXIMUM_FRAME_PAYLOAD}-octet limit"
+        )));
+    }
+    let mut payload = vec![0u8; length];
+    reader.read_exact(&mut payload).map_err(|error| {
+        if error.kind() == std::io::ErrorKind::UnexpectedEof {
+            Error::InvalidInput("remote connection closed inside a frame".to_owned())
+        } else {
+            error.into()
+        }
+    })?;
+    Ok(Some((header[0], payload)))
+}
+
+fn expect_length(kind: &str, payload: &[u8], expected: usize) -> Result<()> {
+    if payload.len() != expected {
+        return Err(Error::InvalidInput(format!(
+            "remote {kind} frame has {} octets, expected {expected}",
+            payload.len()
+        )));
+    }
+    Ok(())
+}
+
+fn be_u32(octets: &[u8]) -> u32 {
+    u32::from_be_bytes(octets.try_into().expect("4 octets, length checked above"))
+}
+
+fn be_u64(octets: &[u8]) -> u64 {
+    u64::from_be_bytes(octets.try_into().expect("8 octets, length checked above"))
+}
+
+fn encode_hello(kind: SdrKind, capabilities: SdrCapabilities) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(27);
+    payload.extend_from_slice(&REMOTE_PROTOCOL_MAGIC);
+    payload.push(REMOTE_PROTOCOL_VERSION);
+    payload.push(kind_code(kind));
+    payload.extend_from_slice(&capabilities.minimum_frequency_hz.to_be_bytes());
+    payload.extend_from_slice(&capabilities.maximum_frequency_hz.to_be_bytes());
+    payload.extend_from_slice(&capabilities.maximum_sample_rate_hz.to_be_bytes());
+    payload.push(capabilities.receive_channels);
+    payload
+}
+
+fn decode_hello(payload: &[u8]) -> Result<(SdrKind, SdrCapabilities)> {
+    expect_length("hello", payload, 27)?;
+    if payload[..4] != REMOTE_PROTOCOL_MAGIC {
+        return Err(Error::InvalidInput(
+            "remote server did not identify as a Blueoxide I/Q server".to_owned(),
+        ));
+    }
+    if payload[4] != REMOTE_PROTOCOL_VERSION {
+        return Err(Error::InvalidInput(format!(
+            "unsupported remote protocol version {}",
+            payload[4]
+        )));
+    }
+    Ok((
+        kind_from_code(payload[5])?,
+        SdrCapabilities {
+            minimum_frequency_hz: be_u64(&payload[6..14]),
+            maximum_frequency_hz: be_u64(&payload[14..22]),
+            maximum_sample_rate_hz: be_u32(&payload[22..26]),
+            receive_channels: payload[26],
+        },
+    ))
+}
+
+fn encode_config(config: &SdrConfig) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(21);
+    payload.extend_from_slice(&config.center_frequency_hz.to_be_bytes());
+    payload.extend_from_slice(&config.sample_rate_hz.to_be_bytes());
+    payload.extend_from_slice(&config.bandwidth_hz.to_be_bytes());
+    payload.extend_from_slice(&config.gain_db.to_bits().to_be_bytes());
+    payload.push(config.channel);
+    payload
+}
+
+fn decode_config(payload: &[u8]) -> Result<SdrConfig> {
+    expect_length("configure", payload, 21)?;
+    Ok(SdrConfig {
+        center_frequency_hz: be_u64(&payload[..8]),
+        sample_rate_hz: be_u32(&payload[8..12]),
+        bandwidth_hz: be_u32(&payload[12..16]),
+        gain_db: f32::from_bits(be_u32(&payload[16..20])),
+        channel: payload[20],
+    })
+}
+
+fn encode_error(error: &Error) -> Vec<u8> {
+    let code = match error {
+        Error::InvalidChannel(_) | Error::InvalidConfiguration(_) => ERROR_CONFIGURATION,
+        Error::InvalidInput(_) => ERROR_INPUT,
+        Error::InvalidState(_) => ERROR_STATE,
+        Error::NativeCall { .. } | Error::NativeLibrary { .. } | Error::Io(_) => ERROR_OTHER,
+    };
+    let mut payload = vec![code];
+    payload.extend_from_slice(error.to_string().as_bytes());
+    payload
+}
+
+fn decode_error(payload: &[u8]) -> Error {
+    let Some((&code, message)) = payload.split_first() else {
+        return Error::InvalidInput("remote error frame is empty".to_owned());
+    };
+    let message = String::from_utf8_lossy(message).into_owned();
+    match code {
+        ERROR_CONFIGURATION => Error::InvalidConfiguration(message),
+        ERROR_INPUT => Error::InvalidInput(message),
+        ERROR_STATE => Error::InvalidState(message),
+        _ => Error::InvalidState(format!("remote source: {message}")),
+    }
+}
+
+fn encode_samples(samples: &[Complex32], metadata: ReadMetadata, end_of_stream: bool) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(SAMPLES_HEADER_LENGTH + samples.len() * 8);
+    payload.extend_from_slice(&metadata.first_sample_index.to_be_bytes());
+    payload.extend_from_slice(&metadata.dropped_samples_before.to_be_bytes());
+    let mut flags = 0;
+    if metadata.overrun {
+        flags |= SAMPLES_FLAG_OVERRUN;
+    }
+    if end_of_stream {
+        flags |= SAMPLES_FLAG_END_OF_STREAM;
+    }
+    payload.push(flags);
+    payload.extend_from_slice(&(samples.len() as u32).to_be_bytes());
+    for sample in samples {
+        payload.extend_from_slice(&sample.re.to_le_bytes());
+        payload.extend_from_slice(&sample.im.to_le_bytes());
+    }
+    payload
+}
+
+/// Serves one client connection from `source` until the client disconnects.
+///
+/// The source is stopped when the client disconnects while it is running, so
+/// a lost analyst workstation cannot leave a radio streaming.
+pub fn serve_iq_connection<S: IqSource>(source: &mut S, stream: TcpStream) -> Result<()> {
+    stream.set_nodelay(true)?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = BufWriter::new(stream);
+    write_frame(
+        &mut writer,
+        RESPONSE_HELLO,
+        &encode_hello(source.kind(), source.capabilities()),
+    )?;
+    let mut running = false;
+    let mut buffer = Vec::new();
+    let result = loop {
+        let (kind, payload) = match read_frame(&mut reader) {
+            Ok(Some(frame)) => frame,
+            Ok(None) => break Ok(()),
+            Err(error) => break Err(error),
+        };
+        let response = match kind {
+            REQUEST_CONFIGURE => decode_config(&payload)
+                .and_then(|config| source.configure(&config))
+                .map(|()| {
+                    let mut response = Vec::with_capacity(5);
+                    match source.applied_sample_rate_hz() {
+                        Some(rate) => {
+                            response.push(1);
+                            response.extend_from_slice(&rate.to_be_bytes());
+                        }
+                        None => response.extend_from_slice(&[0; 5]),
+                    }
+                    (RESPONSE_OK, response)
+                }),
+            REQUEST_START => source.start().map(|()| {
+                running = true;
+                (RESPONSE_OK, Vec::new())
+            }),
+            REQUEST_STOP => source.stop().map(|()| {
+                running = false;
+                (RESPONSE_OK, Vec::new())
+            }),
+            REQUEST_READ => expect_length("read", &payload, 8).and_then(|()| {
+                let maximum = be_u32(&payload[..4]) as usize;
+                let timeout_ms = be_u32(&payload[4..8]);
+                if maximum == 0 || maximum > REMOTE_MAXIMUM_READ_SAMPLES {
+                    return Err(Error::InvalidInput(format!(
+                        "remote read of {maximum} samples is outside 1..={REMOTE_MAXIMUM_READ_SAMPLES}"
+                    )));
+                }
+                buffer.resize(maximum, Complex32::ZERO);
+                let (count, metadata) = source.read(
+                    &mut buffer,
+                    Duration::from_millis(u64::from(timeout_ms)),
+                )?;
+                if count > maximum {
+                    return Err(Error::InvalidInput(format!(
+                        "SDR backend returned {count} samples for a {maximum}-sample buffer"
+                    )));
+                }
+                Ok((
+                    RESPONSE_SAMPLES,
+                    encode_samples(&buffer[..count], metadata, source.end_of_stream()),
+                ))
+            }),
+            unknown => Err(Error::InvalidInput(format!(
+                "unknown remote request type 0x{unknown:02x}"
+            ))),
+        };
+        let written = match response {
+            Ok((kind, payload)) => write_frame(&mut writer, kind, &payload),
+            Err(error) => write_frame(&mut writer, RESPONSE_ERROR, &encode_error(&error)),
+        };
+        if let Err(error) = written {
+            break Err(error);
+        }
+    };
+    if running {
+        let stop_result = source.stop();
+        result?;
+        return stop_result;
+    }
+    result
+}
+
+/// Network client for a source exported by `serve_iq_connection`.
+///
+/// The remote device kind, capabilities, applied sample rate, and read
+/// metadata are reported unchanged, so capture code cannot distinguish a
+/// remote radio from a local one.
+pub struct RemoteSource {
+    reader: BufReader<TcpStream>,
+    writer: BufWriter<TcpStream>,
+    kind: SdrKind,
+    capabilities: SdrCapabilities,
+    applied_sample_rate_hz: Option<u32>,
+    end_of_stream: bool,
+}
+
+impl RemoteSource {
+    pub fn connect(address: impl ToSocketAddrs) -> Result<Self> {
+        let stream = TcpStream::connect(address)?;
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(Some(RESPONSE_GRACE))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let (kind, payload) = read_frame(&mut reader)?.ok_or_else(|| {
+            Error::InvalidInput("remote server closed the connection before hello".to_owned())
+        })?;
+        if kind != RESPONSE_HELLO {
+            return Err(Error::InvalidInput(format!(
+                "remote server sent frame type 0x{kind:02x} instead of hello"
+            )));
+        }
+        let (kind, capabilities) = decode_hello(&payload)?;
+        Ok(Self {
+            reader,
+            writer: BufWriter::new(stream),
+            kind,
+            capabilities,
+            applied_sample_rate_hz: None,
+            end_of_stream: false,
+        })
+    }
+
+    fn request(&mut self, kind: u8, payload: &[u8], expected: u8) -> Result<Vec<u8>> {
+        write_frame(&mut self.writer, kind, payload)?;
+        let (kind, payload) = read_frame(&mut self.reader)?
+            .ok_or_else(|| Error::InvalidInput("remote server closed the connection".to_owned()))?;
+        if kind == RESPONSE_ERROR {
+            return Err(decode_error(&payload));
+        }
+        if kind != expected {
+            return Err(Error::InvalidInput(format!(
+                "remote server sent frame type 0x{kind:02x}, expected 0x{expected:02x}"
+            )));
+        }
+        Ok(payload)
+    }
+}
+
+impl IqSource for RemoteSource {
+    fn kind(&self) -> SdrKind {
+        self.kind
+    }
+
+    fn capabilities(&self) -> SdrCapabilities {
+        self.capabilities
+    }
+
+    fn configure(&mut self, config: &SdrConfig) -> Result<()> {
+        let payload = self.request(REQUEST_CONFIGURE, &encode_config(config), RESPONSE_OK)?;
+        expect_length("configure response", &payload, 5)?;
+        self.applied_sample_rate_hz = match payload[0] {
+            0 => None,
+            1 => Some(be_u32(&payload[1..5])),
+            flag => {
+                return Err(Error::InvalidInput(format!(
+                    "remote configure response has invalid applied-rate flag {flag}"
+                )));
+            }
+        };
+        Ok(())
+    }
+
+    fn applied_sample_rate_hz(&self) -> Option<u32> {
+        self.applied_sample_rate_hz
+    }
+
+    fn start(&mut self) -> Result<()> {
+        let payload = self.request(REQUEST_START, &[], RESPONSE_OK)?;
+        expect_length("start response", &payload, 0)
+    }
+
+    fn read(
+        &mut self,
+        output: &mut [Complex32],
+        timeout: Duration,
+    ) -> Result<(usize, ReadMetadata)> {
+        if output.is_empty() {
+            return Ok((0, ReadMetadata::default()));
+        }
+        let maximum = output.len().min(REMOTE_MAXIMUM_READ_SAMPLES) as u32;
+        let timeout_ms = u32::try_from(timeout.as_millis().max(1)).unwrap_or(u32::MAX);
+        let mut request = Vec::with_capacity(8);
+        request.extend_from_slice(&maximum.to_be_bytes());
+        request.extend_from_slice(&timeout_ms.to_be_bytes());
+        self.reader
+            .get_ref()
+            .set_read_timeout(Some(timeout.saturating_add(RESPONSE_GRACE)))?;
+        let payload = self.request(REQUEST_READ, &request, RESPONSE_SAMPLES)?;
+        if payload.len() < SAMPLES_HEADER_LENGTH {
+            return Err(Error::InvalidInput(
+                "remote samples frame is shorter than its header".to_owned(),
+            ));
+        }
+        let flags = payload[16];
+        if flags & !(SAMPLES_FLAG_OVERRUN | SAMPLES_FLAG_END_OF_STREAM) != 0 {
+            return Err(Error::InvalidInput(format!(
+                "remote samples frame has reserved flags 0x{flags:02x}"
+            )));
+        }
+        let count = be_u32(&payload[17..21]) as usize;
+        if count > maximum as usize {
+            return Err(Error::InvalidInput(format!(
+                "remote source returned {count} samples for a {maximum}-sample request"
+            )));
+        }
+        expect_length("samples", &payload, SAMPLES_HEADER_LENGTH + count * 8)?;
+        let samples = parse_iq_bytes(&payload[SAMPLES_HEADER_LENGTH..], IqFormat::F32Le)?;
+        output[..count].copy_from_slice(&samples);
+        self.end_of_stream = flags & SAMPLES_FLAG_END_OF_STREAM != 0;
+        Ok((
+            count,
+            ReadMetadata {
+                first_sample_index: be_u64(&payload[..8]),
+                dropped_samples_before: be_u64(&payload[8..16]),
+                overrun: flags & SAMPLES_FLAG_OVERRUN != 0,
+            },
+        ))
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        let payload = self.request(REQUEST_STOP, &[], RESPONSE_OK)?;
+        expect_length("stop response", &payload, 0)
+    }
+
+    fn end_of_stream(&self) -> bool {
+        self.end_of_stream
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::net::TcpListener;
+    use std::thread;
+
+    struct MockSource {
+        blocks: VecDeque<(Vec<Complex32>, ReadMetadata)>,
+        configured: Option<SdrConfig>,
+        running: bool,
+        stops: usize,
+    }
+
+    impl IqSource for MockSource {
+        fn kind(&self) -> SdrKind {
+            SdrKind::Xtrx
+        }
+
+        fn capabilities(&self) -> SdrCapabilities {
+            SdrCapabilities {
+                minimum_frequency_hz: 30_000_000,
+                maximum_frequency_hz: 3_800_000_000,
+                maximum_sample_rate_hz: 61_440_000,
+                receive_channels: 2,
+            }
+        }
+
+        fn configure(&mut self, config: &SdrConfig) -> Result<()> {
+            config.validate(self.capabilities())?;
+            self.configured = Some(config.clone());
+            Ok(())
+        }
+
+        fn applied_sample_rate_hz(&self) -> Option<u32> {
+            self.configured.as_ref().map(|config| config.sample_rate_hz)
+        }
+
+        fn start(&mut self) -> Result<()> {
+            self.running = true;
+            Ok(())
+        }
+
+        fn read(
+            &mut self,
+            output: &mut [Complex32],
+            _timeout: Duration,
+        ) -> Result<(usize, ReadMetadata)> {
+            if !self.running {
+                return Err(Error::InvalidState("mock source is not running".to_owned()));
+            }
+            match self.blocks.pop_front() {
+                Some((samples, metadata)) => {
+                    output[..samples.len()].copy_from_slice(&samples);
+                    Ok((samples.len(), metadata))
+                }
+                None => Ok((0, ReadMetadata::default())),
+            }
+        }
+
+        fn stop(&mut self) -> Result<()> {
+            self.running = false;
+            self.stops += 1;
+            Ok(())
+        }
+
+        fn end_of_stream(&self) -> bool {
+            self.blocks.is_empty()
+        }
+    }
+
+    fn config() -> SdrConfig {
+        SdrConfig {
+            center_frequency_hz: 2_426_000_000,
+            sample_rate_hz: 4_000_000,
+            bandwidth_hz: 2_000_000,
+            gain_db: 12.5,
+            channel: 1,
+        }
+    }
+
+    #[test]
+    fn loopback_preserves_kind_configuration_metadata_and_errors() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let mut source = MockSource {
+                blocks: VecDeque::from([
+                    (
+                        vec![Complex32::new(0.25, -0.5), Complex32::new(1.0, 0.0)],
+                        ReadMetadata {
+                            first_sample_index: 7_000,
+                            dropped_samples_before: 0,
+                            overrun: false,
+                        },
+                    ),
+                    (
+                        vec![Complex32::new(-1.0, 0.125)],
+                        ReadMetadata {
+                            first_sample_index: 7_050,
+                            dropped_samples_before: 48,
+                            overrun: true,
+                        },
+                    ),
+                ]),
+                configured: None,
+                running: false,
+                stops: 0,
+            };
+            let (connection, _) = listener.accept().unwrap();
+            serve_iq_connection(&mut source, connection).unwrap();
+            source
+        });
+
+        let mut remote = RemoteSource::connect(address).unwrap();
+        assert_eq!(remote.kind(), SdrKind::Xtrx);
+        assert_eq!(remote.capabilities().receive_channels, 2);
+        assert_eq!(remote.applied_sample_rate_hz(), None);
+
+        let mut invalid = config();
+        invalid.channel = 2;
+        let error = remote.configure(&invalid).unwrap_err();
+        assert!(matches!(error, Error::InvalidConfiguration(_)));
+        assert!(
+            error
+                .to_string()
+                .contains("receive channel 2 is unavailable")
+        );
+
+        remote.configure(&config()).unwrap();
+        assert_eq!(remote.applied_sample_rate_hz(), Some(4_000_000));
+        let mut output = [Complex32::ZERO; 16];
+        assert!(matches!(
+            remote.read(&mut output, Duration::from_millis(10)),
+            Err(Error::InvalidState(_))
+        ));
+        remote.start().unwrap();
+
+        let (count, metadata) = remote.read(&mut output, Duration::from_millis(10)).unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(metadata.first_sample_index, 7_000);
+        assert_eq!(metadata.dropped_samples_before, 0);
+        assert!(!metadata.overrun);
+        assert_eq!(output[0], Complex32::new(0.25, -0.5));
+        assert!(!remote.end_of_stream());
+
+        let (count, metadata) = remote.read(&mut output, Duration::from_millis(10)).unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(metadata.first_sample_index, 7_050);
+        assert_eq!(metadata.dropped_samples_before, 48);
+        assert!(metadata.overrun);
+        assert_eq!(output[0], Complex32::new(-1.0, 0.125));
+        assert!(remote.end_of_stream());
+        drop(remote);
+
+        let source = server.join().unwrap();
+        assert_eq!(source.configured.unwrap().gain_db, 12.5);
+        assert!(!source.running);
+        assert_eq!(source.stops, 1);
+    }
+
+    #[test]
+    fn rejects_oversized_frames_and_unknown_requests() {
+        let mut oversized = vec![RESPONSE_SAMPLES];
+        oversized.extend_from_slice(&(MAXIMUM_FRAME_PAYLOAD as u32 + 1).to_be_bytes());
+        assert!(read_frame(&mut oversized.as_slice()).is_err());
+        assert!(read_frame(&mut [0x82u8, 0, 0].as_slice()).is_err());
+        assert!(read_frame(&mut [].as_slice()).unwrap().is_none());
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let mut source = MockSource {
+                blocks: VecDeque::new(),
+                configured: None,
+                running: false,
+                stops: 0,
+            };
+            let (connection, _) = listener.accept().unwrap();
+            serve_iq_connection(&mut source, connection).unwrap();
+            source.stops
+        });
+        let mut remote = RemoteSource::connect(address).unwrap();
+        let error = remote.request(0x42, &[], RESPONSE_OK).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("unknown remote request type 0x42")
+        );
+        let error = remote
+            .request(REQUEST_READ, &0u64.to_be_bytes(), RESPONSE_SAMPLES)
+            .unwrap_err();
+        assert!(error.to_string().contains("outside 1..="));
+        drop(remote);
+        assert_eq!(server.join().unwrap(), 0);
+    }
+}
@@ -0,0 +1,688 @@
+use crate::complex::Complex32;
+use crate::iq::{IqFormat, IqReader, iq_file_sample_count};
+use crate::json::JsonValue;
+use crate::sdr::{IqSource, ReadMetadata, SdrCapabilities, SdrConfig, SdrKind};
+use crate::{Error, Result};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// How quickly a replayed recording is delivered to the reader.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReplayPacing {
+    /// Every read returns as soon as the file can supply the samples.
+    Unpaced,
+    /// Samples become available at the recording rate multiplied by the
+    /// factor, so `1.0` is real time and `4.0` is four times faster.
+    Scaled(f64),
+}
+
+impl ReplayPacing {
+    /// Parses `max` as unpaced playback and any positive number as a speed
+    /// factor.
+    pub fn parse(value: &str) -> Result<Self> {
+        if value == "max" {
+            return Ok(Self::Unpaced);
+        }
+        let speed: f64 = value.parse().map_err(|_| {
+            Error::InvalidConfiguration(format!(
+                "replay speed {value:?} must be max or a positive factor"
+            ))
+        })?;
+        Self::scaled(speed)
+    }
+
+    pub fn scaled(speed: f64) -> Result<Self> {
+        if !speed.is_finite() || speed <= 0.0 {
+            return Err(Error::InvalidConfiguration(format!(
+                "replay speed {speed} must be a positive finite factor"
+            )));
+        }
+        Ok(Self::Scaled(speed))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ReplayImpairmentKind {
+    /// Discards samples and flags the next read as an overrun.
+    Overrun { dropped_samples: u64 },
+    /// Discards samples and reports them as dropped without an overrun flag.
+    Gap { dropped_samples: u64 },
+    /// Returns one empty read, as a radio does when its timeout expires.
+    Timeout,
+}
+
+/// A receive fault injected when playback reaches a recording sample index.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ReplayImpairment {
+    pub at_sample: u64,
+    pub kind: ReplayImpairmentKind,
+}
+
+impl ReplayImpairment {
+    /// Parses `overrun:SAMPLE:COUNT`, `gap:SAMPLE:COUNT`, or `timeout:SAMPLE`.
+    pub fn parse(value: &str) -> Result<Self> {
+        let invalid = || {
+            Error::InvalidConfiguration(format!(
+                "replay impairment {value:?} must be overrun:SAMPLE:COUNT, gap:SAMPLE:COUNT, or timeout:SAMPLE"
+            ))
+        };
+        let mut fields = value.split(':');
+        let name = fields.next().ok_or_else(invalid)?;
+        let at_sample = fields
+            .next()
+            .and_then(|field| field.parse().ok())
+            .ok_or_else(invalid)?;
+        let count = fields.next().map(str::parse::<u64>);
+        if fields.next().is_some() {
+            return Err(invalid());
+        }
+        let kind = match (name, count) {
+            ("overrun", Some(Ok(dropped_samples))) => {
+                ReplayImpairmentKind::Overrun { dropped_samples }
+            }
+            ("gap", Some(Ok(dropped_samples))) => ReplayImpairmentKind::Gap { dropped_samples },
+            ("timeout", None) => ReplayImpairmentKind::Timeout,
+            _ => return Err(invalid()),
+        };
+        let impairment = Self { at_sample, kind };
+        impairment.validate()?;
+        Ok(impairment)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.kind == (ReplayImpairmentKind::Gap { dropped_samples: 0 }) {
+            return Err(Error::InvalidConfiguration(format!(
+                "replay gap at sample {} must drop at least one sample",
+                self.at_sample
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReplayOptions {
+    pub pacing: ReplayPacing,
+    /// Faults applied in sample order; entries at the same sample apply in
+    /// the order given.
+    pub impairments: Vec<ReplayImpairment>,
+}
+
+impl Default for ReplayOptions {
+    fn default() -> Self {
+        Self {
+            pacing: ReplayPacing::Unpaced,
+            impairments: Vec::new(),
+        }
+    }
+}
+
+/// Radio settings stored with a recording, when its container records them.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ReplayRecording {
+    pub format: IqFormat,
+    pub sample_rate_hz: Option<u32>,
+    pub center_frequency_hz: Option<u64>,
+    /// Total sample count when the recording length is known up front.
+    pub sample_count: Option<u64>,
+}
+
+/// Parsed subset of a SigMF metadata file needed for single-channel replay.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SigMfMetadata {
+    pub format: IqFormat,
+    pub sample_rate_hz: u32,
+    pub center_frequency_hz: Option<u64>,
+}
+
+impl SigMfMetadata {
+    pub fn parse(text: &str) -> Result<Self> {
+        let document = JsonValue::parse(text)?;
+        let global = document
+            .get("global")
+            .ok_or_else(|| Error::InvalidInput("SigMF metadata has no global object".to_owned()))?;
+        let datatype = global
+            .get("core:datatype")
+            .and_then(JsonValue::as_str)
+            .ok_or_else(|| {
+                Error::InvalidInput("SigMF metadata has no core:datatype string".to_owned())
+            })?;
+        let format = match datatype {
+            "cf32_le" => IqFormat::F32Le,
+            "ci16_le" => IqFormat::S16Le,
+            _ => {
+                return Err(Error::InvalidInput(format!(
+                    "SigMF datatype {datatype:?} is unsupported; expected cf32_le or ci16_le"
+                )));
+            }
+        };
+        if let Some(channels) = global.get("core:num_channels")
+            && channels.as_u64() != Some(1)
+        {
+            return Err(Error::InvalidInput(
+                "SigMF replay supports only single-channel recordings".to_owned(),
+            ));
+        }
+        let sample_rate_hz = global
+            .get("core:sample_rate")
+            .and_then(JsonValue::as_u64)
+            .and_then(|rate| u32::try_from(rate).ok())
+            .filter(|rate| *rate > 0)
+            .ok_or_else(|| {
+                Error::InvalidInput(
+                    "SigMF core:sample_rate must be a positive whole number of hertz".to_owned(),
+                )
+            })?;
+
+        let captures = document
+            .get("captures")
+            .map(|captures| {
+                captures.as_array().ok_or_else(|| {
+                    Error::InvalidInput("SigMF captures must be an array".to_owned())
+                })
+            })
+            .transpose()?
+            .unwrap_or_default();
+        let mut center_frequency_hz = None;
+        for capture in captures {
+            if capture
+                .get("core:sample_start")
+                .is_some_and(|start| start.as_u64().is_none())
+            {
+                return Err(Error::InvalidInput(
+                    "SigMF core:sample_start must be a non-negative integer".to_owned(),
+                ));
+            }
+            let Some(frequency) = capture.get("core:frequency") else {
+                continue;
+            };
+            let frequency = frequency.as_u64().ok_or_else(|| {
+                Error::InvalidInput(
+                    "SigMF core:frequency must be a non-negative whole number of hertz".to_owned(),
+                )
+            })?;
+            match center_frequency_hz {
+                None => center_frequency_hz = Some(frequency),
+                Some(first) if first != frequency => {
+                    return Err(Error::InvalidInput(
+                        "SigMF replay requires every capture segment to share one center frequency"
+                            .to_owned(),
+                    ));
+                }
+                Some(_) => {}
+            }
+        }
+        Ok(Self {
+            format,
+            sample_rate_hz,
+            center_frequency_hz,
+        })
+    }
+}
+
+/// Returns the `.sigmf-meta` and `.sigmf-data` paths for a recording named
+/// by either file or by their shared base name.
+pub fn sigmf_paths(path: &Path) -> (PathBuf, PathBuf) {
+    let base = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("sigmf-meta" | "sigmf-data") => path.with_extension(""),
+        _ => path.to_path_buf(),
+    };
+    let mut meta = base.clone().into_os_string();
+    meta.push(".sigmf-meta");
+    let mut data = base.into_os_string();
+    data.push(".sigmf-data");
+    (PathBuf::from(meta), PathBuf::from(data))
+}
+
+/// Returns SigMF metadata describing a single-channel recording written by
+/// this crate.
+pub fn sigmf_metadata_text(
+    format: IqFormat,
+    sample_rate_hz: u32,
+    center_frequency_hz: u64,
+) -> String {
+    let datatype = match format {
+        IqFormat::F32Le => "cf32_le",
+        IqFormat::S16Le => "ci16_le",
+    };
+    format!(
+        "{{\"global\":{{\"core:datatype\":\"{datatype}\",\"core:sample_rate\":{sample_rate_hz},\"core:version\":\"1.0.0\",\"core:recorder\":\"blueoxide {}\"}},\"captures\":[{{\"core:sample_start\":0,\"core:frequency\":{center_frequency_hz}}}],\"annotations\":[]}}\n",
+        env!("CARGO_PKG_VERSION")
+    )
+}
+
+/// Returns true when `path` names a SigMF recording file.
+pub fn is_sigmf_path(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|extension| extension.to_str()),
+        Some("sigmf-meta" | "sigmf-data")
+    )
+}
+
+/// File-backed `IqSource` that replays a recording through the receive
+/// contract.
+///
+/// Reported sample indices are recording sample indices, so injected gaps
+/// and overruns skip samples exactly as a radio that lost them would, and
+/// decoded packets keep the positions they have in the file.
+pub struct ReplaySource {
+    reader: IqReader<Box<dyn Read + Send>>,
+    recording: ReplayRecording,
+    pacing: ReplayPacing,
+    impairments: VecDeque<ReplayImpairment>,
+    configured_sample_rate_hz: Option<u32>,
+    started_at: Option<Instant>,
+    position: u64,
+    pending_dropped: u64,
+    pending_overrun: bool,
+    finished: bool,
+}
+
+impl ReplaySource {
+    /// Replays a headerless I/Q file whose radio settings come from the
+    /// capture configuration.
+    pub fn open(path: impl AsRef<Path>, format: IqFormat, options: ReplayOptions) -> Result<Self> {
+        let file = File::open(path.as_ref())?;
+        let sample_count = iq_file_sample_count(&file, format)? as u64;
+        Self::new(
+            Box::new(BufReader::new(file)),
+            ReplayRecording {
+                format,
+                sample_rate_hz: None,
+                center_frequency_hz: None,
+                sample_count: Some(sample_count),
+            },
+            options,
+        )
+    }
+
+    /// Replays a SigMF recording named by its metadata file, data file, or
+    /// base name.
+    pub fn open_sigmf(path: impl AsRef<Path>, options: ReplayOptions) -> Result<Self> {
+        let (meta_path, data_path) = sigmf_paths(path.as_ref());
+        let metadata = SigMfMetadata::parse(&std::fs::read_to_string(&meta_path)?)?;
+        let file = File::open(&data_path)?;
+        let sample_count = iq_file_sample_count(&file, metadata.format)? as u64;
+        Self::new(
+            Box::new(BufReader::new(file)),
+            ReplayRecording {
+                format: metadata.format,
+                sample_rate_hz: Some(metadata.sample_rate_hz),
+                center_frequency_hz: metadata.center_frequency_hz,
+                sample_count: Some(sample_count),
+            },
+            options,
+        )
+    }
+
+    pub fn new(
+        reader: Box<dyn Read + Send>,
+        recording: ReplayRecording,
+        options: ReplayOptions,
+    ) -> Result<Self> {
+        for impairment in &options.impairments {
+            impairment.validate()?;
+        }
+        let mut impairments = options.impairments;
+        impairments.sort_by_key(|impairment| impairment.at_sample);
+        Ok(Self {
+            reader: IqReader::new(reader, recording.format),
+            recording,
+            pacing: options.pacing,
+            impairments: impairments.into(),
+            configured_sample_rate_hz: None,
+            started_at: None,
+            position: 0,
+            pending_dropped: 0,
+            pending_overrun: false,
+            finished: false,
+        })
+    }
+
+    pub const fn recording(&self) -> ReplayRecording {
+        self.recording
+    }
+
+    /// Returns the recording sample index the next read starts at.
+    pub const fn position(&self) -> u64 {
+        self.position
+    }
+
+    fn discard(&mut self, mut samples: u64) -> Result<u64> {
+        let mut discarded = 0u64;
+        while samples > 0 && !self.finished {
+            let block = samples.min(65_536) as usize;
+            let count = self.reader.read_block(block)?.len() as u64;
+            if count < block as u64 {
+                self.finished = true;
+            }
+            discarded += count;
+            samples -= count;
+        }
+        Ok(discarded)
+    }
+
+    /// Waits until the paced clock has released `wanted` samples past the
+    /// current position, giving up after `timeout`, and returns how many are
+    /// available.
+    fn paced_available(&self, wanted: usize, timeout: Duration) -> Result<usize> {
+        let ReplayPacing::Scaled(speed) = self.pacing else {
+            return Ok(wanted);
+        };
+        let started_at = self
+            .started_at
+            .ok_or_else(|| Error::InvalidState("replay source is not started".to_owned()))?;
+        let sample_rate_hz = self
+            .recording
+            .sample_rate_hz
+            .or(self.configured_sample_rate_hz)
+            .ok_or_else(|| {
+                Error::InvalidState("replay source must be configured before start".to_owned())
+            })?;
+        let samples_per_second = sample_rate_hz as f64 * speed;
+        let release_time =
+            |samples: u64| Duration::from_secs_f64(samples as f64 / samples_per_second);
+        let deadline = Instant::now() + timeout;
+        let wanted_release = started_at + release_time(self.position + wanted as u64);
+        std::thread::sleep(
+            wanted_release
+                .min(deadline)
+                .saturating_duration_since(Instant::now()),
+        );
+        if Instant::now() >= wanted_release {
+            return Ok(wanted);
+        }
+        let released = (started_at.elapsed().as_secs_f64() * samples_per_second).floor() as u64;
+        Ok(released.saturating_sub(self.position).min(wanted as u64) as usize)
+    }
+}
+
+impl IqSource for ReplaySource {
+    fn kind(&self) -> SdrKind {
+        SdrKind::Replay
+    }
+
+    fn capabilities(&self) -> SdrCapabilities {
+        SdrCapabilities {
+            minimum_frequency_hz: 0,
+            maximum_frequency_hz: u64::MAX,
+            maximum_sample_rate_hz: u32::MAX,
+            receive_channels: 1,
+        }
+    }
+
+    fn configure(&mut self, config: &SdrConfig) -> Result<()> {
+        config.validate(self.capabilities())?;
+        if let Some(sample_rate_hz) = self.recording.sample_rate_hz
+            && sample_rate_hz != config.sample_rate_hz
+        {
+            return Err(Error::InvalidConfiguration(format!(
+                "replay recording was captured at {sample_rate_hz} Hz, but {} Hz was requested",
+                config.sample_rate_hz
+            )));
+        }
+        if let Some(center_frequency_hz) = self.recording.center_frequency_hz
+            && center_frequency_hz != config.center_frequency_hz
+        {
+            return Err(Error::InvalidConfiguration(format!(
+                "replay recording was captured at {center_frequency_hz} Hz center frequency, but {} Hz was requested",
+                config.center_frequency_hz
+            )));
+        }
+        self.configured_sample_rate_hz = Some(config.sample_rate_hz);
+        Ok(())
+    }
+
+    fn applied_sample_rate_hz(&self) -> Option<u32> {
+        self.recording.sample_rate_hz
+    }
+
+    fn start(&mut self) -> Result<()> {
+        if self.configured_sample_rate_hz.is_none() {
+            return Err(Error::InvalidState(
+                "replay source must be configured before start".to_owned(),
+            ));
+        }
+        if self.position != 0 || self.finished {
+            return Err(Error::InvalidState(
+                "replay source cannot be restarted".to_owned(),
+            ));
+        }
+        self.started_at = Some(Instant::now());
+        Ok(())
+    }
+
+    fn read(
+        &mut self,
+        output: &mut [Complex32],
+        timeout: Duration,
+    ) -> Result<(usize, ReadMetadata)> {
+        if self.started_at.is_none() {
+            return Err(Error::InvalidState(
+                "replay source is not started".to_owned(),
+            ));
+        }
+        while let Some(impairment) = self.impairments.front().copied() {
+            if impairment.at_sample > self.position || self.finished {
+                break;
+            }
+            self.impairments.pop_front();
+            match impairment.kind {
+                ReplayImpairmentKind::Timeout => {
+                    if self.pacing != ReplayPacing::Unpaced {
+                        std::thread::sleep(timeout);
+                    }
+                    return Ok((0, ReadMetadata::default()));
+                }
+                ReplayImpairmentKind::Overrun { dropped_samples }
+                | ReplayImpairmentKind::Gap { dropped_samples } => {
+                    let discarded = self.discard(dropped_samples)?;
+                    self.position += discarded;
+                    self.pending_dropped += discarded;
+                    self.pending_overrun |=
+                        matches!(impairment.kind, ReplayImpairmentKind::Overrun { .. });
+                }
+            }
+        }
+        if self.finished || output.is_empty() {
+            return Ok((0, ReadMetadata::default()));
+        }
+
+        let mut wanted = output.len();
+        if let Some(impairment) = self.impairments.front() {
+            wanted = wanted.min((impairment.at_sample - self.position) as usize);
+        }
+        let available = self.paced_available(wanted, timeout)?;
+        if available == 0 {
+            return Ok((0, ReadMetadata::default()));
+        }
+        let samples = self.reader.read_block(available)?;
+        if samples.len() < available {
+            self.finished = true;
+        }
+        if samples.is_empty() {
+            return Ok((0, ReadMetadata::default()));
+        }
+        output[..samples.len()].copy_from_slice(&samples);
+        let metadata = ReadMetadata {
+            first_sample_index: self.position,
+            dropped_samples_before: std::mem::take(&mut self.pending_dropped),
+            overrun: std::mem::take(&mut self.pending_overrun),
+        };
+        self.position += samples.len() as u64;
+        if self
+            .recording
+            .sample_count
+            .is_some_and(|count| self.position >= count)
+        {
+            self.finished = true;
+        }
+        Ok((samples.len(), metadata))
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.started_at = None;
+        Ok(())
+    }
+
+    fn end_of_stream(&self) -> bool {
+        self.finished
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn started_source(samples: usize, options: ReplayOptions) -> ReplaySource {
+        let mut bytes = Vec::new();
+        for index in 0..samples {
+            bytes.extend_from_slice(&(index as f32).to_le_bytes());
+            bytes.extend_from_slice(&0.0f32.to_le_bytes());
+        }
+        let mut source = ReplaySource::new(
+            Box::new(Cursor::new(bytes)),
+            ReplayRecording {
+                format: IqFormat::F32Le,
+                sample_rate_hz: None,
+                center_frequency_hz: None,
+                sample_count: Some(samples as u64),
+            },
+            options,
+        )
+        .unwrap();
+        source
+            .configure(&SdrConfig {
+                center_frequency_hz: 2_402_000_000,
+                sample_rate_hz: 4_000_000,
+                bandwidth_hz: 2_000_000,
+                gain_db: 0.0,
+                channel: 0,
+            })
+            .unwrap();
+        source.start().unwrap();
+        source
+    }
+
+    #[test]
+    fn injected_faults_keep_recording_sample_indices() {
+        let mut source = started_source(
+            100,
+            ReplayOptions {
+                pacing: ReplayPacing::Unpaced,
+                impairments: vec![
+                    ReplayImpairment::parse("gap:40:5").unwrap(),
+                    ReplayImpairment::parse("timeout:10").unwrap(),
+                    ReplayImpairment::parse("overrun:70:0").unwrap(),
+                ],
+            },
+        );
+        let mut output = vec![Complex32::new(0.0, 0.0); 32];
+        let timeout = Duration::from_millis(1);
+
+        let (count, metadata) = source.read(&mut output, timeout).unwrap();
+        assert_eq!((count, metadata.first_sample_index), (10, 0));
+        assert_eq!(source.read(&mut output, timeout).unwrap().0, 0);
+        let (count, metadata) = source.read(&mut output, timeout).unwrap();
+        assert_eq!((count, metadata.first_sample_index), (30, 10));
+
+        let (count, metadata) = source.read(&mut output, timeout).unwrap();
+        assert_eq!(count, 25);
+        assert_eq!(metadata.first_sample_index, 45);
+        assert_eq!(metadata.dropped_samples_before, 5);
+        assert!(!metadata.overrun);
+        assert_eq!(output[0].re, 45.0);
+
+        let (count, metadata) = source.read(&mut output, timeout).unwrap();
+        assert_eq!((count, metadata.first_sample_index), (30, 70));
+        assert!(metadata.overrun);
+        assert_eq!(metadata.dropped_samples_before, 0);
+        assert!(source.end_of_stream());
+        assert_eq!(source.read(&mut output, timeout).unwrap().0, 0);
+    }
+
+    #[test]
+    fn scaled_pacing_releases_samples_at_the_recording_rate() {
+        let mut source = started_source(
+            40_000,
+            ReplayOptions {
+                pacing: ReplayPacing::parse("2").unwrap(),
+                impairments: Vec::new(),
+            },
+        );
+        let started = Instant::now();
+        let mut output = vec![Complex32::new(0.0, 0.0); 40_000];
+        let mut received = 0;
+        while received < 40_000 {
+            received += source
+                .read(&mut output[received..], Duration::from_secs(1))
+                .unwrap()
+                .0;
+        }
+        // 40,000 samples at 4 Msps and twice real time take 5 ms.
+        assert!(started.elapsed() >= Duration::from_millis(5));
+
+        let mut source = started_source(
+            40_000,
+            ReplayOptions {
+                pacing: ReplayPacing::Scaled(0.01),
+                impairments: Vec::new(),
+            },
+        );
+        let (count, _) = source.read(&mut output, Duration::from_millis(2)).unwrap();
+        assert!(count < 40_000);
+    }
+
+    #[test]
+    fn parses_sigmf_metadata_and_rejects_unsupported_recordings() {
+        let metadata = SigMfMetadata::parse(
+            r#"{"global":{"core:datatype":"ci16_le","core:sample_rate":4000000,"core:version":"1.0.0"},
+                "captures":[{"core:sample_start":0,"core:frequency":2402000000}],"annotations":[]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            metadata,
+            SigMfMetadata {
+                format: IqFormat::S16Le,
+                sample_rate_hz: 4_000_000,
+                center_frequency_hz: Some(2_402_000_000),
+            }
+        );
+        for text in [
+            r#"{"global":{"core:datatype":"cu8","core:sample_rate":4000000}}"#,
+            r#"{"global":{"core:datatype":"cf32_le","core:sample_rate":0}}"#,
+            r#"{"global":{"core:datatype":"cf32_le","core:sample_rate":4000000,"core:num_channels":2}}"#,
+            r#"{"global":{"core:datatype":"cf32_le","core:sample_rate":4000000},
+                "captures":[{"core:frequency":2402000000},{"core:frequency":2426000000}]}"#,
+        ] {
+            assert!(SigMfMetadata::parse(text).is_err(), "{text}");
+        }
+        assert_eq!(
+            sigmf_paths(Path::new("/tmp/capture.sigmf-data")),
+            (
+                PathBuf::from("/tmp/capture.sigmf-meta"),
+                PathBuf::from("/tmp/capture.sigmf-data")
+            )
+        );
+        assert_eq!(
+            SigMfMetadata::parse(&sigmf_metadata_text(
+                IqFormat::F32Le,
+                8_000_000,
+                2_480_000_000
+            ))
+            .unwrap(),
+            SigMfMetadata {
+                format: IqFormat::F32Le,
+                sample_rate_hz: 8_000_000,
+                center_frequency_hz: Some(2_480_000_000),
+            }
+        );
+        assert!(ReplayImpairment::parse("gap:10:0").is_err());
+        assert!(ReplayImpairment::parse("timeout:10:1").is_err());
+        assert!(ReplayPacing::parse("0").is_err());
+    }
+}
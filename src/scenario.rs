@@ -0,0 +1,975 @@
+//! Synthetic connections with known ground truth.
+//!
+//! A [`ConnectionScenario`] describes an advertiser, the initiator's
+//! CONNECT_IND, and actions queued at chosen connection events. Generation
+//! schedules every packet at exact sample positions with the same timing
+//! arithmetic as the connection tracker, and returns each transmission with
+//! the plaintext and counters that produced it.
+
+use crate::advertising::{AddressKind, ConnectRequest, DeviceAddress};
+use crate::ble::{BleChannel, LE_ADV_ACCESS_ADDRESS, LeFrameConfig, LePdu};
+use crate::link_layer::{
+    ChannelMapInd, ChannelSelectionAlgorithm, ConnectionChannelSelector, ConnectionUpdateInd,
+    DataChannelPdu, InstantRelation, LeAclEncryptor, LePhy, LinkDirection, LogicalLinkId,
+    instant_relation,
+};
+use crate::ll_control::{EncryptionRequest, EncryptionResponse, LeEncryptionMaterial};
+use crate::synth::SynthesizedPacket;
+use crate::{Error, Result};
+use std::collections::VecDeque;
+
+const INTER_FRAME_SPACE_US: u64 = 150;
+/// Plaintext data payload limit without a Data Length Update procedure.
+const MAXIMUM_DATA_PAYLOAD_OCTETS: usize = 27;
+/// Airtime of a central and peripheral packet pair of at most 31
+/// Length-counted octets each, including both inter-frame spaces.
+const MAXIMUM_EXCHANGE_US: u64 = 2 * ((1 + 4 + 2 + 31 + 3) * 8 + INTER_FRAME_SPACE_US);
+/// CONNECT_IND access address, header, payload, and CRC bits.
+const CONNECT_IND_BITS_FROM_ACCESS_ADDRESS: u64 = (4 + 2 + 34 + 3) * 8;
+
+/// Material for one LL encryption start procedure.
+///
+/// The LTK uses HCI/SMP field order, as accepted by
+/// [`LeEncryptionMaterial::derive`].
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub struct ScenarioEncryption {
+    pub long_term_key: [u8; 16],
+    pub request: EncryptionRequest,
+    pub response: EncryptionResponse,
+}
+
+impl std::fmt::Debug for ScenarioEncryption {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        formatter
+            .debug_struct("ScenarioEncryption")
+            .field("long_term_key", &"<redacted>")
+            .field("request", &self.request)
+            .field("response", &self.response)
+            .finish()
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ScenarioActionKind {
+    /// The central sends LL_CHANNEL_MAP_IND.
+    ChannelMapUpdate(ChannelMapInd),
+    /// The central sends LL_CONNECTION_UPDATE_IND.
+    ConnectionUpdate(ConnectionUpdateInd),
+    /// The central starts encryption with LL_ENC_REQ.
+    StartEncryption(ScenarioEncryption),
+    /// One complete L2CAP PDU, fragmented into 27-octet LL data PDUs.
+    L2cap {
+        direction: LinkDirection,
+        channel_id: u16,
+        payload: Vec<u8>,
+    },
+}
+
+/// An action queued at the start of connection event `event_counter`.
+///
+/// Queued PDUs are sent as the link allows: LL control PDUs before data, and
+/// data held while an encryption start procedure is in progress. An update
+/// whose instant has passed by the time its PDU is sent is an error.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ScenarioAction {
+    pub event_counter: u16,
+    pub kind: ScenarioActionKind,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ConnectionScenario {
+    /// Sample rate of the generated sample coordinates; a whole multiple of
+    /// 1 MHz so every LE 1M symbol spans an integer number of samples.
+    pub sample_rate_hz: u32,
+    pub advertising_channel: BleChannel,
+    pub advertiser: DeviceAddress,
+    pub advertiser_kind: AddressKind,
+    pub initiator: DeviceAddress,
+    pub initiator_kind: AddressKind,
+    pub advertising_data: Vec<u8>,
+    /// First preamble sample of the ADV_IND that the initiator answers.
+    pub advertising_sample: u64,
+    pub connect_request: ConnectRequest,
+    /// Number of connection events generated, starting at event 0.
+    pub event_count: u16,
+    pub actions: Vec<ScenarioAction>,
+}
+
+/// Connection context of a generated data-channel transmission.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ScenarioLinkPacket {
+    pub direction: LinkDirection,
+    pub event_counter: u16,
+    /// The PDU before encryption, with a CRC calculated over the plaintext.
+    /// Equal to the transmitted PDU when the direction is not encrypted.
+    pub plaintext: DataChannelPdu,
+    /// AES-CCM packet counter of an encrypted transmission.
+    pub packet_counter: Option<u64>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScenarioPacket {
+    pub transmission: SynthesizedPacket,
+    pub access_address_sample: u64,
+    /// `None` for advertising-channel packets.
+    pub link: Option<ScenarioLinkPacket>,
+}
+
+impl ConnectionScenario {
+    pub fn validate(&self) -> Result<()> {
+        if self.sample_rate_hz < 2_000_000 || !self.sample_rate_hz.is_multiple_of(1_000_000) {
+            return Err(Error::InvalidConfiguration(format!(
+                "scenario sample rate {} Hz must be a whole multiple of 1 MHz of at least 2 MHz",
+                self.sample_rate_hz
+            )));
+        }
+        if !self.advertising_channel.is_primary_advertising() {
+            return Err(Error::InvalidConfiguration(format!(
+                "scenario advertising channel {} is not 37, 38, or 39",
+                self.advertising_channel.index()
+            )));
+        }
+        if self.advertising_data.len() > 31 {
+            return Err(Error::InvalidConfiguration(format!(
+                "ADV_IND advertising data has {} octets; maximum is 31",
+                self.advertising_data.len()
+            )));
+        }
+        let request = &self.connect_request;
+        if request.access_address == LE_ADV_ACCESS_ADDRESS {
+            return Err(Error::InvalidConfiguration(
+                "connection access address must differ from the advertising access address"
+                    .to_owned(),
+            ));
+        }
+        LeFrameConfig::data(request.access_address, request.crc_init)?;
+        if !(5..=16).contains(&request.hop_increment) {
+            return Err(Error::InvalidConfiguration(format!(
+                "CONNECT_IND hop increment {} is outside 5..=16",
+                request.hop_increment
+            )));
+        }
+        ConnectionUpdateInd::new(
+            request.window_size,
+            request.window_offset,
+            request.interval,
+            request.latency,
+            request.supervision_timeout,
+            0,
+        )?;
+        request.channel_selector()?;
+        request.peer_clock_accuracy()?;
+        for action in &self.actions {
+            if action.event_counter >= self.event_count {
+                return Err(Error::InvalidConfiguration(format!(
+                    "scenario action at event {} is beyond the {} generated event(s)",
+                    action.event_counter, self.event_count
+                )));
+            }
+            match &action.kind {
+                ScenarioActionKind::ChannelMapUpdate(update) => {
+                    require_future_instant(action.event_counter, update.instant)?;
+                }
+                ScenarioActionKind::ConnectionUpdate(update) => {
+                    update.validate()?;
+                    require_future_instant(action.event_counter, update.instant)?;
+                }
+                ScenarioActionKind::StartEncryption(_) => {}
+                ScenarioActionKind::L2cap { payload, .. } => {
+                    if payload.len() > usize::from(u16::MAX) {
+                        return Err(Error::InvalidConfiguration(format!(
+                            "L2CAP payload has {} octets; maximum is 65535",
+                            payload.len()
+                        )));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Generates the ADV_IND, CONNECT_IND, and every connection-event packet
+    /// in transmission order.
+    ///
+    /// Every packet is acknowledged by its peer, so sequence numbers advance
+    /// without retransmissions. Each event carries central and peripheral
+    /// packet pairs while either side sets MD and another pair fits before
+    /// the next anchor.
+    pub fn generate(&self) -> Result<Vec<ScenarioPacket>> {
+        self.validate()?;
+        let mut generator = Generator::new(self)?;
+        generator.advertise()?;
+        for event_index in 0..self.event_count {
+            generator.connection_event(event_index)?;
+        }
+        Ok(generator.packets)
+    }
+}
+
+fn require_future_instant(event_counter: u16, instant: u16) -> Result<()> {
+    match instant_relation(event_counter, instant) {
+        InstantRelation::Future(_) => Ok(()),
+        _ => Err(Error::InvalidConfiguration(format!(
+            "instant {instant} is not in the future of event {event_counter}"
+        ))),
+    }
+}
+
+#[derive(Clone, Debug)]
+enum PendingUpdate {
+    ChannelMap(ChannelMapInd),
+    Connection(ConnectionUpdateInd),
+}
+
+impl PendingUpdate {
+    const fn instant(&self) -> u16 {
+        match self {
+            Self::ChannelMap(update) => update.instant,
+            Self::Connection(update) => update.instant,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Transmitter {
+    sequence_number: bool,
+    next_expected_sequence_number: bool,
+    control: VecDeque<Vec<u8>>,
+    data: VecDeque<(LogicalLinkId, Vec<u8>)>,
+    data_paused: bool,
+    /// Set once this side has sent or received its LL_START_ENC_REQ.
+    encryptor: Option<LeAclEncryptor>,
+}
+
+impl Transmitter {
+    /// Position of the next eligible control PDU; only encryption procedure
+    /// PDUs may be sent while data is paused.
+    fn next_control(&self) -> Option<usize> {
+        if self.data_paused {
+            self.control
+                .iter()
+                .position(|pdu| matches!(pdu[0], 0x03..=0x06))
+        } else {
+            (!self.control.is_empty()).then_some(0)
+        }
+    }
+
+    fn has_pending(&self) -> bool {
+        self.next_control().is_some() || (!self.data_paused && !self.data.is_empty())
+    }
+
+    fn next_pdu(&mut self) -> (LogicalLinkId, Vec<u8>) {
+        if let Some(index) = self.next_control() {
+            let pdu = self
+                .control
+                .remove(index)
+                .expect("control index is in range");
+            return (LogicalLinkId::Control, pdu);
+        }
+        if !self.data_paused
+            && let Some(data) = self.data.pop_front()
+        {
+            return data;
+        }
+        (LogicalLinkId::ContinuationOrEmpty, Vec::new())
+    }
+}
+
+struct Generator<'a> {
+    scenario: &'a ConnectionScenario,
+    frame_config: LeFrameConfig,
+    samples_per_symbol: u64,
+    inter_frame_space_samples: u64,
+    selector: ConnectionChannelSelector,
+    interval_us: u64,
+    anchor_event_index: u64,
+    anchor_sample: u64,
+    pending_update: Option<(PendingUpdate, bool)>,
+    encryption: Option<ScenarioEncryption>,
+    material: Option<LeEncryptionMaterial>,
+    central: Transmitter,
+    peripheral: Transmitter,
+    packets: Vec<ScenarioPacket>,
+}
+
+impl<'a> Generator<'a> {
+    fn new(scenario: &'a ConnectionScenario) -> Result<Self> {
+        let request = &scenario.connect_request;
+        Ok(Self {
+            scenario,
+            frame_config: LeFrameConfig::data(request.access_address, request.crc_init)?,
+            samples_per_symbol: u64::from(scenario.sample_rate_hz / 1_000_000),
+            inter_frame_space_samples: INTER_FRAME_SPACE_US
+                * u64::from(scenario.sample_rate_hz / 1_000_000),
+            selector: request.channel_selector()?,
+            interval_us: u64::from(request.interval_us()),
+            anchor_event_index: 0,
+            anchor_sample: 0,
+            pending_update: None,
+            encryption: None,
+            material: None,
+            central: Transmitter::default(),
+            peripheral: Transmitter::default(),
+            packets: Vec::new(),
+        })
+    }
+
+    fn microseconds_to_samples(&self, microseconds: u64) -> u64 {
+        microseconds * u64::from(self.scenario.sample_rate_hz) / 1_000_000
+    }
+
+    /// Anchor arithmetic matches `ConnectionTracker`: the offset from the
+    /// last anchor is rounded to the nearest sample.
+    fn anchor_for(&self, event_index: u64) -> u64 {
+        let elapsed = u128::from(event_index - self.anchor_event_index);
+        let offset =
+            (elapsed * u128::from(self.interval_us) * u128::from(self.scenario.sample_rate_hz)
+                + 500_000)
+                / 1_000_000;
+        self.anchor_sample + offset as u64
+    }
+
+    fn push_packet(&mut self, pdu: LePdu, start_sample: u64, link: Option<ScenarioLinkPacket>) {
+        let access_address_sample = start_sample + 8 * self.samples_per_symbol;
+        self.packets.push(ScenarioPacket {
+            transmission: SynthesizedPacket {
+                pdu,
+                phy: LePhy::Le1M,
+                start_sample,
+            },
+            access_address_sample,
+            link,
+        });
+    }
+
+    fn last_end_sample(&self) -> Result<u64> {
+        let packet = self.packets.last().expect("a packet was generated");
+        packet
+            .transmission
+            .end_sample(self.scenario.sample_rate_hz, 0.0)
+    }
+
+    fn advertise(&mut self) -> Result<()> {
+        let scenario = self.scenario;
+        let request = &scenario.connect_request;
+        let csa2 = request.channel_selection_algorithm == ChannelSelectionAlgorithm::Csa2;
+
+        let mut adv_ind = vec![
+            address_flag(scenario.advertiser_kind, 0x40) | if csa2 { 0x20 } else { 0x00 },
+            (6 + scenario.advertising_data.len()) as u8,
+        ];
+        adv_ind.extend_from_slice(&scenario.advertiser.0);
+        adv_ind.extend_from_slice(&scenario.advertising_data);
+        let adv_ind = LePdu::from_pdu_bytes(
+            scenario.advertising_channel,
+            LeFrameConfig::advertising(),
+            &adv_ind,
+        )?;
+        self.push_packet(adv_ind, scenario.advertising_sample, None);
+
+        let mut connect_ind = vec![
+            0x05 | if csa2 { 0x20 } else { 0x00 }
+                | address_flag(scenario.initiator_kind, 0x40)
+                | address_flag(scenario.advertiser_kind, 0x80),
+            34,
+        ];
+        connect_ind.extend_from_slice(&scenario.initiator.0);
+        connect_ind.extend_from_slice(&scenario.advertiser.0);
+        connect_ind.extend_from_slice(&request.ll_data());
+        let connect_ind = LePdu::from_pdu_bytes(
+            scenario.advertising_channel,
+            LeFrameConfig::advertising(),
+            &connect_ind,
+        )?;
+        let connect_start = self.last_end_sample()? + self.inter_frame_space_samples;
+        self.push_packet(connect_ind, connect_start, None);
+
+        // The first central packet's access address is placed at the start of
+        // the transmit window, measured as `ConnectRequest::first_event_window`
+        // measures it.
+        let connect_access_address_sample = connect_start + 8 * self.samples_per_symbol;
+        self.anchor_sample = connect_access_address_sample
+            + CONNECT_IND_BITS_FROM_ACCESS_ADDRESS * self.samples_per_symbol
+            + self.microseconds_to_samples(u64::from(request.transmit_window_start_us()));
+        Ok(())
+    }
+
+    fn connection_event(&mut self, event_index: u16) -> Result<()> {
+        let event_counter = event_index;
+        let event_index = u64::from(event_index);
+        if let Some((update, true)) = &self.pending_update
+            && update.instant() == event_counter
+        {
+            match update.clone() {
+                PendingUpdate::ChannelMap(update) => {
+                    let request = &self.scenario.connect_request;
+                    self.selector = ConnectionChannelSelector::new(
+                        request.channel_selection_algorithm,
+                        update.channel_map,
+                        request.access_address,
+                        request.hop_increment,
+                    )?;
+                }
+                PendingUpdate::Connection(update) => {
+                    let anchor = self.anchor_for(event_index)
+                        + self.microseconds_to_samples(u64::from(update.window_offset_us()));
+                    self.anchor_event_index = event_index;
+                    self.anchor_sample = anchor;
+                    self.interval_us = u64::from(update.parameters.interval_us());
+                }
+            }
+            self.pending_update = None;
+        }
+        for action in self
+            .scenario
+            .actions
+            .iter()
+            .filter(|action| action.event_counter == event_counter)
+        {
+            self.queue(action)?;
+        }
+
+        let channel = self.selector.channel_for_event(event_counter);
+        let anchor = self.anchor_for(event_index);
+        let next_anchor = self.anchor_for(event_index + 1);
+        let preamble_samples = 8 * self.samples_per_symbol;
+        let mut start = anchor - preamble_samples;
+        loop {
+            let central_more = self.transmit(
+                LinkDirection::CentralToPeripheral,
+                channel,
+                event_counter,
+                start,
+            )?;
+            let peripheral_start = self.last_end_sample()? + self.inter_frame_space_samples;
+            let peripheral_more = self.transmit(
+                LinkDirection::PeripheralToCentral,
+                channel,
+                event_counter,
+                peripheral_start,
+            )?;
+            start = self.last_end_sample()? + self.inter_frame_space_samples;
+            let exchange_end = start
+                + self.microseconds_to_samples(MAXIMUM_EXCHANGE_US)
+                + self.inter_frame_space_samples;
+            if !(central_more || peripheral_more) || exchange_end >= next_anchor - preamble_samples
+            {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn queue(&mut self, action: &ScenarioAction) -> Result<()> {
+        let instant_update = match &action.kind {
+            ScenarioActionKind::ChannelMapUpdate(update) => {
+                let mut pdu = vec![0x01];
+                pdu.extend_from_slice(&update.channel_map.bytes());
+                pdu.extend_from_slice(&update.instant.to_le_bytes());
+                self.central.control.push_back(pdu);
+                Some(PendingUpdate::ChannelMap(update.clone()))
+            }
+            ScenarioActionKind::ConnectionUpdate(update) => {
+                let mut pdu = vec![0x00, update.window_size];
+                pdu.extend_from_slice(&update.window_offset.to_le_bytes());
+                pdu.extend_from_slice(&update.parameters.interval.to_le_bytes());
+                pdu.extend_from_slice(&update.parameters.latency.to_le_bytes());
+                pdu.extend_from_slice(&update.parameters.supervision_timeout.to_le_bytes());
+                pdu.extend_from_slice(&update.instant.to_le_bytes());
+                self.central.control.push_back(pdu);
+                Some(PendingUpdate::Connection(*update))
+            }
+            ScenarioActionKind::StartEncryption(encryption) => {
+                if self.encryption.is_some() {
+                    return Err(Error::InvalidConfiguration(format!(
+                        "scenario starts encryption again at event {}; pause and refresh are not generated",
+                        action.event_counter
+                    )));
+                }
+                let request = encryption.request;
+                let mut pdu = vec![0x03];
+                pdu.extend_from_slice(&request.random_number);
+                pdu.extend_from_slice(&request.encrypted_diversifier.to_le_bytes());
+                pdu.extend_from_slice(&request.central_session_key_diversifier);
+                pdu.extend_from_slice(&request.central_initialization_vector);
+                self.central.control.push_back(pdu);
+                self.central.data_paused = true;
+                self.encryption = Some(*encryption);
+                None
+            }
+            ScenarioActionKind::L2cap {
+                direction,
+                channel_id,
+                payload,
+            } => {
+                let mut pdu = Vec::with_capacity(4 + payload.len());
+                pdu.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+                pdu.extend_from_slice(&channel_id.to_le_bytes());
+                pdu.extend_from_slice(payload);
+                let transmitter = self.transmitter(*direction);
+                for (index, fragment) in pdu.chunks(MAXIMUM_DATA_PAYLOAD_OCTETS).enumerate() {
+                    let llid = if index == 0 {
+                        LogicalLinkId::StartOrComplete
+                    } else {
+                        LogicalLinkId::ContinuationOrEmpty
+                    };
+                    transmitter.data.push_back((llid, fragment.to_vec()));
+                }
+                None
+            }
+        };
+        if let Some(update) = instant_update {
+            if let Some((pending, _)) = &self.pending_update {
+                return Err(Error::InvalidConfiguration(format!(
+                    "scenario update at event {} overlaps the update pending for instant {}",
+                    action.event_counter,
+                    pending.instant()
+                )));
+            }
+            self.pending_update = Some((update, false));
+        }
+        Ok(())
+    }
+
+    fn transmitter(&mut self, direction: LinkDirection) -> &mut Transmitter {
+        match direction {
+            LinkDirection::CentralToPeripheral => &mut self.central,
+            LinkDirection::PeripheralToCentral => &mut self.peripheral,
+        }
+    }
+
+    /// Encrypts every later nonempty PDU `direction` sends, starting at
+    /// packet counter zero.
+    fn start_encryption(&mut self, direction: LinkDirection) -> Result<()> {
+        let material = self.material.ok_or_else(|| {
+            Error::InvalidState("scenario encryption started without material".to_owned())
+        })?;
+        self.transmitter(direction).encryptor = Some(LeAclEncryptor::new(
+            material.session_key(),
+            material.initialization_vector(),
+            direction,
+            0,
+        )?);
+        Ok(())
+    }
+
+    /// Transmits the next PDU in `direction` and delivers it to the peer.
+    /// Returns the transmitted MD bit.
+    fn transmit(
+        &mut self,
+        direction: LinkDirection,
+        channel: BleChannel,
+        event_counter: u16,
+        start_sample: u64,
+    ) -> Result<bool> {
+        let frame_config = self.frame_config;
+        let transmitter = self.transmitter(direction);
+        let (llid, payload) = transmitter.next_pdu();
+        let more_data = transmitter.has_pending();
+        let header = llid as u8
+            | u8::from(transmitter.next_expected_sequence_number) << 2
+            | u8::from(transmitter.sequence_number) << 3
+            | u8::from(more_data) << 4;
+
+        let mut plaintext = vec![header, payload.len() as u8];
+        plaintext.extend_from_slice(&payload);
+        let plaintext = LePdu::from_pdu_bytes(channel, frame_config, &plaintext)?;
+        let (transmitted, packet_counter) = match &mut transmitter.encryptor {
+            Some(encryptor) => {
+                let encryption = encryptor.encrypt(&plaintext.clone().into())?;
+                let mut encrypted = encryption.packet.header.to_vec();
+                encrypted.extend_from_slice(&encryption.packet.payload);
+                (
+                    LePdu::from_pdu_bytes(channel, frame_config, &encrypted)?,
+                    encryption.packet_counter,
+                )
+            }
+            None => (plaintext.clone(), None),
+        };
+        self.push_packet(
+            transmitted,
+            start_sample,
+            Some(ScenarioLinkPacket {
+                direction,
+                event_counter,
+                plaintext: plaintext.into(),
+                packet_counter,
+            }),
+        );
+
+        if llid == LogicalLinkId::Control {
+            self.transmitted_control(direction, event_counter, &payload)?;
+        }
+        self.deliver(direction, header, llid, &payload)?;
+        Ok(more_data)
+    }
+
+    fn transmitted_control(
+        &mut self,
+        direction: LinkDirection,
+        event_counter: u16,
+        payload: &[u8],
+    ) -> Result<()> {
+        match (direction, payload[0]) {
+            (LinkDirection::CentralToPeripheral, 0x00 | 0x01) => {
+                let (update, sent) = self
+                    .pending_update
+                    .as_mut()
+                    .expect("queued update PDUs are pending");
+                if !matches!(
+                    instant_relation(event_counter, update.instant()),
+                    InstantRelation::Future(_)
+                ) {
+                    return Err(Error::InvalidConfiguration(format!(
+                        "update for instant {} could not be sent before event {event_counter}",
+                        update.instant()
+                    )));
+                }
+                *sent = true;
+            }
+            (LinkDirection::PeripheralToCentral, 0x06) => self.peripheral.data_paused = false,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn deliver(
+        &mut self,
+        direction: LinkDirection,
+        header: u8,
+        llid: LogicalLinkId,
+        payload: &[u8],
+    ) -> Result<()> {
+        let receiver = match direction {
+            LinkDirection::CentralToPeripheral => &mut self.peripheral,
+            LinkDirection::PeripheralToCentral => &mut self.central,
+        };
+        if (header & 0x08 != 0) == receiver.next_expected_sequence_number {
+            receiver.next_expected_sequence_number = !receiver.next_expected_sequence_number;
+        }
+        if (header & 0x04 != 0) != receiver.sequence_number {
+            receiver.sequence_number = !receiver.sequence_number;
+        }
+        if llid != LogicalLinkId::Control {
+            return Ok(());
+        }
+
+        match (direction, payload[0]) {
+            (LinkDirection::CentralToPeripheral, 0x03) => {
+                let encryption = self
+                    .encryption
+                    .expect("LL_ENC_REQ is queued with scenario encryption");
+                let response = encryption.response;
+                let mut pdu = vec![0x04];
+                pdu.extend_from_slice(&response.peripheral_session_key_diversifier);
+                pdu.extend_from_slice(&response.peripheral_initialization_vector);
+                self.peripheral.data_paused = true;
+                self.peripheral.control.push_back(pdu);
+                self.peripheral.control.push_back(vec![0x05]);
+                self.material = Some(LeEncryptionMaterial::derive(
+                    encryption.long_term_key,
+                    encryption.request,
+                    response,
+                ));
+            }
+            (LinkDirection::PeripheralToCentral, 0x05) => {
+                self.start_encryption(LinkDirection::CentralToPeripheral)?;
+                self.central.control.push_back(vec![0x06]);
+            }
+            (LinkDirection::CentralToPeripheral, 0x06) => {
+                self.start_encryption(LinkDirection::PeripheralToCentral)?;
+                self.peripheral.control.push_back(vec![0x06]);
+            }
+            (LinkDirection::PeripheralToCentral, 0x06) => self.central.data_paused = false,
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+const fn address_flag(kind: AddressKind, flag: u8) -> u8 {
+    match kind {
+        AddressKind::Public => 0,
+        AddressKind::Random => flag,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advertising::{
+        DecodedAdvertisingPdu, FirstCentralTransmission, decode_advertising_pdu,
+    };
+    use crate::att::{ATT_FIXED_CHANNEL_ID, AttPdu};
+    use crate::ble::AdvertisingPdu;
+    use crate::link_layer::{
+        ConnectionEventTiming, DataChannelMap, L2capReassembler, L2capReassemblyOutcome,
+    };
+    use crate::ll_control::LeEncryptionSessionTracker;
+
+    fn core_encryption() -> ScenarioEncryption {
+        ScenarioEncryption {
+            long_term_key: [
+                0xbf, 0x01, 0xfb, 0x9d, 0x4e, 0xf3, 0xbc, 0x36, 0xd8, 0x74, 0xf5, 0x39, 0x41, 0x38,
+                0x68, 0x4c,
+            ],
+            request: EncryptionRequest {
+                random_number: [0x90, 0x78, 0x56, 0x34, 0x12, 0xef, 0xcd, 0xab],
+                encrypted_diversifier: 0x2474,
+                central_session_key_diversifier: [0x13, 0x02, 0xf1, 0xe0, 0xdf, 0xce, 0xbd, 0xac],
+                central_initialization_vector: [0x24, 0xab, 0xdc, 0xba],
+            },
+            response: EncryptionResponse {
+                peripheral_session_key_diversifier: [
+                    0x79, 0x68, 0x57, 0x46, 0x35, 0x24, 0x13, 0x02,
+                ],
+                peripheral_initialization_vector: [0xbe, 0xba, 0xaf, 0xde],
+            },
+        }
+    }
+
+    fn scenario(channel_selection_algorithm: ChannelSelectionAlgorithm) -> ConnectionScenario {
+        let notification: Vec<u8> = [0x1b, 0x2a, 0x00]
+            .into_iter()
+            .chain((0..40).map(|value| value as u8))
+            .collect();
+        ConnectionScenario {
+            sample_rate_hz: 4_000_000,
+            advertising_channel: BleChannel::new(37).unwrap(),
+            advertiser: DeviceAddress([0x06, 0x05, 0x04, 0x03, 0x02, 0x01]),
+            advertiser_kind: AddressKind::Public,
+            initiator: DeviceAddress([0x11, 0x22, 0x33, 0x44, 0x55, 0xc6]),
+            initiator_kind: AddressKind::Random,
+            advertising_data: vec![0x02, 0x01, 0x06],
+            advertising_sample: 1_000,
+            connect_request: ConnectRequest {
+                access_address: 0x5065_4b4f,
+                crc_init: 0x7a_4f21,
+                window_size: 2,
+                window_offset: 1,
+                interval: 24,
+                latency: 0,
+                supervision_timeout: 100,
+                channel_map: [0xff, 0xff, 0xff, 0xff, 0x1f],
+                hop_increment: 7,
+                sleep_clock_accuracy: 5,
+                channel_selection_algorithm,
+            },
+            event_count: 18,
+            actions: vec![
+                ScenarioAction {
+                    event_counter: 1,
+                    kind: ScenarioActionKind::L2cap {
+                        direction: LinkDirection::CentralToPeripheral,
+                        channel_id: ATT_FIXED_CHANNEL_ID,
+                        payload: vec![0x0a, 0x2a, 0x00],
+                    },
+                },
+                ScenarioAction {
+                    event_counter: 2,
+                    kind: ScenarioActionKind::StartEncryption(core_encryption()),
+                },
+                ScenarioAction {
+                    event_counter: 2,
+                    kind: ScenarioActionKind::L2cap {
+                        direction: LinkDirection::PeripheralToCentral,
+                        channel_id: ATT_FIXED_CHANNEL_ID,
+                        payload: notification,
+                    },
+                },
+                ScenarioAction {
+                    event_counter: 4,
+                    kind: ScenarioActionKind::ChannelMapUpdate(ChannelMapInd {
+                        channel_map: DataChannelMap::new([0x00, 0x00, 0xf0, 0x0f, 0x00]).unwrap(),
+                        instant: 9,
+                    }),
+                },
+                ScenarioAction {
+                    event_counter: 10,
+                    kind: ScenarioActionKind::ConnectionUpdate(
+                        ConnectionUpdateInd::new(1, 3, 12, 0, 100, 14).unwrap(),
+                    ),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn generated_connection_is_reconstructed_by_the_receive_stack() {
+        for algorithm in [
+            ChannelSelectionAlgorithm::Csa1,
+            ChannelSelectionAlgorithm::Csa2,
+        ] {
+            let scenario = scenario(algorithm);
+            let packets = scenario.generate().unwrap();
+
+            let adv_ind = decode_advertising_pdu(
+                &AdvertisingPdu::try_from(packets[0].transmission.pdu.clone()).unwrap(),
+            )
+            .unwrap();
+            assert!(matches!(
+                adv_ind,
+                DecodedAdvertisingPdu::AdvInd { advertiser, .. } if advertiser == scenario.advertiser
+            ));
+            let DecodedAdvertisingPdu::ConnectInd {
+                initiator,
+                initiator_kind,
+                request,
+                ..
+            } = decode_advertising_pdu(
+                &AdvertisingPdu::try_from(packets[1].transmission.pdu.clone()).unwrap(),
+            )
+            .unwrap()
+            else {
+                panic!("expected CONNECT_IND");
+            };
+            assert_eq!(initiator, scenario.initiator);
+            assert_eq!(initiator_kind, AddressKind::Random);
+            assert_eq!(request, scenario.connect_request);
+
+            let first = &packets[2];
+            let mut tracker = request
+                .acquire_first_event_anchor(
+                    packets[1].access_address_sample,
+                    scenario.sample_rate_hz,
+                    20,
+                    FirstCentralTransmission::new(
+                        first.transmission.pdu.channel,
+                        first.access_address_sample,
+                    )
+                    .unwrap(),
+                )
+                .unwrap();
+            let mut session =
+                LeEncryptionSessionTracker::new(core_encryption().long_term_key, 0).unwrap();
+            let mut reassembler = L2capReassembler::default();
+            let mut attribute_pdus = Vec::new();
+            let mut anchored_event = None;
+            let mut encrypted_packets = 0;
+            for packet in &packets[2..] {
+                let link = packet.link.as_ref().unwrap();
+                let received = DataChannelPdu::from(packet.transmission.pdu.clone());
+                if link.direction == LinkDirection::CentralToPeripheral
+                    && anchored_event != Some(link.event_counter)
+                {
+                    while tracker.event_counter() != link.event_counter {
+                        tracker.advance().unwrap();
+                    }
+                    match tracker.current_event().unwrap().timing {
+                        ConnectionEventTiming::Expected {
+                            access_address_sample,
+                        } => assert_eq!(access_address_sample, packet.access_address_sample),
+                        ConnectionEventTiming::AnchorObservationRequired { .. } => {
+                            tracker
+                                .observe_anchor(packet.access_address_sample)
+                                .unwrap();
+                        }
+                    }
+                    anchored_event = Some(link.event_counter);
+                }
+                assert_eq!(
+                    tracker.current_event().unwrap().channel,
+                    packet.transmission.pdu.channel
+                );
+
+                let observation = session.observe(link.direction, &received).unwrap();
+                assert_eq!(observation.packet.header, link.plaintext.header);
+                assert_eq!(observation.packet.payload, link.plaintext.payload);
+                assert_eq!(
+                    observation
+                        .decryption
+                        .and_then(|status| status.packet_counter()),
+                    link.packet_counter
+                );
+                encrypted_packets += usize::from(link.packet_counter.is_some());
+                if let Some(control) = observation.packet.control().unwrap() {
+                    tracker.schedule_control(control).unwrap();
+                }
+                if let L2capReassemblyOutcome::Complete(pdu) = reassembler
+                    .push(link.direction, &observation.packet)
+                    .unwrap()
+                    .outcome
+                {
+                    attribute_pdus.push((pdu.direction, pdu.payload));
+                }
+            }
+
+            assert_eq!(tracker.event_counter(), scenario.event_count - 1);
+            assert_eq!(tracker.parameters().interval, 12);
+            assert_eq!(tracker.channel_map().used_count(), 8);
+            assert!(session.direction_encrypted(LinkDirection::PeripheralToCentral));
+            assert!(encrypted_packets >= 4);
+            assert_eq!(attribute_pdus.len(), 2);
+            assert_eq!(attribute_pdus[0].0, LinkDirection::CentralToPeripheral);
+            assert_eq!(
+                AttPdu::parse(&attribute_pdus[0].1).unwrap().opcode_name(),
+                "read-request"
+            );
+            assert_eq!(attribute_pdus[1].0, LinkDirection::PeripheralToCentral);
+            assert_eq!(attribute_pdus[1].1.len(), 43);
+        }
+    }
+
+    #[test]
+    fn notification_waits_for_encryption_and_spans_fragments() {
+        let packets = scenario(ChannelSelectionAlgorithm::Csa2)
+            .generate()
+            .unwrap();
+        let peripheral_data: Vec<_> = packets
+            .iter()
+            .filter_map(|packet| packet.link.as_ref())
+            .filter(|link| {
+                link.direction == LinkDirection::PeripheralToCentral
+                    && !link.plaintext.payload.is_empty()
+                    && link.plaintext.llid() != LogicalLinkId::Control
+            })
+            .collect();
+        assert_eq!(peripheral_data.len(), 2);
+        assert!(
+            peripheral_data
+                .iter()
+                .all(|link| link.packet_counter.is_some())
+        );
+        assert_eq!(
+            peripheral_data[0].plaintext.llid(),
+            LogicalLinkId::StartOrComplete
+        );
+        assert!(peripheral_data[0].plaintext.more_data());
+        assert_eq!(
+            peripheral_data[1].plaintext.llid(),
+            LogicalLinkId::ContinuationOrEmpty
+        );
+    }
+
+    #[test]
+    fn rejects_updates_that_cannot_meet_their_instant() {
+        let mut late = scenario(ChannelSelectionAlgorithm::Csa2);
+        late.actions[3] = ScenarioAction {
+            event_counter: 2,
+            kind: ScenarioActionKind::ChannelMapUpdate(ChannelMapInd {
+                channel_map: DataChannelMap::new([0x00, 0x00, 0xf0, 0x0f, 0x00]).unwrap(),
+                instant: 3,
+            }),
+        };
+        assert!(
+            late.generate()
+                .unwrap_err()
+                .to_string()
+                .contains("could not be sent before event")
+        );
+
+        let mut overlapping = scenario(ChannelSelectionAlgorithm::Csa2);
+        overlapping.actions[4].event_counter = 5;
+        assert!(
+            overlapping
+                .generate()
+                .unwrap_err()
+                .to_string()
+                .contains("overlaps the update pending for instant 9")
+        );
+
+        let mut beyond = scenario(ChannelSelectionAlgorithm::Csa2);
+        beyond.event_count = 10;
+        assert!(beyond.generate().is_err());
+    }
+}
@@ -7,6 +7,19 @@ pub enum SdrKind {
     LimeSdr,
     BladeRf,
     Xtrx,
+    Stream,
+    Replay,
+}
+
+/// A receiver reported by a backend's native device enumeration.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SdrDevice {
+    pub kind: SdrKind,
+    /// Passed to the backend's `open` to select this device.
+    pub identifier: String,
+    /// Serial number, or the native device name when the library reports none.
+    pub serial: String,
+    pub description: String,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -93,6 +106,12 @@ pub trait IqSource {
         timeout: Duration,
     ) -> Result<(usize, ReadMetadata)>;
     fn stop(&mut self) -> Result<()>;
+    /// Returns true after a finite source has delivered its final sample.
+    ///
+    /// Radio backends never end; a zero-sample read from them is a timeout.
+    fn end_of_stream(&self) -> bool {
+        false
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,585 @@
+use crate::complex::Complex32;
+use crate::iq::{IqFormat, parse_iq_bytes};
+use crate::sdr::{IqSource, ReadMetadata, SdrCapabilities, SdrConfig, SdrKind};
+use crate::{Error, Result};
+use std::fs::File;
+use std::io::Read;
+use std::net::{Shutdown, TcpStream};
+use std::path::PathBuf;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, SyncSender, TryRecvError, sync_channel};
+use std::thread;
+use std::time::Duration;
+
+pub const IQ_STREAM_HEADER_MAGIC: [u8; 4] = *b"BOIQ";
+pub const IQ_STREAM_HEADER_VERSION: u8 = 1;
+pub const IQ_STREAM_HEADER_LENGTH: usize = 20;
+
+const STREAM_CHUNK_BYTES: usize = 65_536;
+const STREAM_QUEUE_CHUNKS: usize = 64;
+
+/// Location of a byte stream carrying interleaved I/Q samples.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum IqStreamLocation {
+    Stdin,
+    /// A regular file or a named pipe.
+    Path(PathBuf),
+    /// A TCP server address in `HOST:PORT` form.
+    Tcp(String),
+}
+
+impl IqStreamLocation {
+    /// Parses `-` as standard input, `tcp://HOST:PORT` as a TCP client
+    /// connection, and any other value as a filesystem path.
+    pub fn parse(value: &str) -> Result<Self> {
+        if value == "-" {
+            return Ok(Self::Stdin);
+        }
+        if let Some(address) = value.strip_prefix("tcp://") {
+            let (host, port) = address.rsplit_once(':').ok_or_else(|| {
+                Error::InvalidConfiguration(format!(
+                    "TCP I/Q location {value:?} must have the form tcp://HOST:PORT"
+                ))
+            })?;
+            if host.is_empty() || port.parse::<u16>().is_err() {
+                return Err(Error::InvalidConfiguration(format!(
+                    "TCP I/Q location {value:?} must have the form tcp://HOST:PORT"
+                )));
+            }
+            return Ok(Self::Tcp(address.to_owned()));
+        }
+        if value.is_empty() {
+            return Err(Error::InvalidConfiguration(
+                "I/Q location must not be empty".to_owned(),
+            ));
+        }
+        Ok(Self::Path(PathBuf::from(value)))
+    }
+
+    /// Returns true when the location is a regular file whose length is known
+    /// before reading.
+    pub fn is_regular_file(&self) -> bool {
+        match self {
+            Self::Path(path) => std::fs::metadata(path).is_ok_and(|metadata| metadata.is_file()),
+            Self::Stdin | Self::Tcp(_) => false,
+        }
+    }
+}
+
+/// Framing applied to the start of an I/Q byte stream.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum IqStreamFraming {
+    /// Interleaved samples from the first octet.
+    Raw,
+    /// An [`IqStreamHeader`] followed by interleaved samples.
+    Header,
+}
+
+impl IqStreamFraming {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "raw" => Ok(Self::Raw),
+            "header" => Ok(Self::Header),
+            _ => Err(Error::InvalidConfiguration(format!(
+                "unsupported I/Q stream framing {value:?}; expected raw or header"
+            ))),
+        }
+    }
+}
+
+/// Fixed 20-octet stream header, modeled on the rtl_tcp dongle-info block.
+///
+/// Multi-octet fields are big-endian: magic `BOIQ`, version, sample format
+/// (1 = `f32le`, 2 = `s16le`), two reserved zero octets, the complex sample
+/// rate in Hz, and the RF center frequency in Hz.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct IqStreamHeader {
+    pub format: IqFormat,
+    pub sample_rate_hz: u32,
+    pub center_frequency_hz: u64,
+}
+
+impl IqStreamHeader {
+    pub fn encode(self) -> [u8; IQ_STREAM_HEADER_LENGTH] {
+        let mut bytes = [0u8; IQ_STREAM_HEADER_LENGTH];
+        bytes[..4].copy_from_slice(&IQ_STREAM_HEADER_MAGIC);
+        bytes[4] = IQ_STREAM_HEADER_VERSION;
+        bytes[5] = match self.format {
+            IqFormat::F32Le => 1,
+            IqFormat::S16Le => 2,
+        };
+        bytes[8..12].copy_from_slice(&self.sample_rate_hz.to_be_bytes());
+        bytes[12..20].copy_from_slice(&self.center_frequency_hz.to_be_bytes());
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8; IQ_STREAM_HEADER_LENGTH]) -> Result<Self> {
+        if bytes[..4] != IQ_STREAM_HEADER_MAGIC {
+            return Err(Error::InvalidInput(format!(
+                "I/Q stream header magic {:02x?} is not BOIQ",
+                &bytes[..4]
+            )));
+        }
+        if bytes[4] != IQ_STREAM_HEADER_VERSION {
+            return Err(Error::InvalidInput(format!(
+                "unsupported I/Q stream header version {}",
+                bytes[4]
+            )));
+        }
+        let format = match bytes[5] {
+            1 => IqFormat::F32Le,
+            2 => IqFormat::S16Le,
+            code => {
+                return Err(Error::InvalidInput(format!(
+                    "unsupported I/Q stream header sample format {code}"
+                )));
+            }
+        };
+        if bytes[6..8] != [0, 0] {
+            return Err(Error::InvalidInput(
+                "I/Q stream header reserved octets are nonzero".to_owned(),
+            ));
+        }
+        let sample_rate_hz = u32::from_be_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]);
+        if sample_rate_hz == 0 {
+            return Err(Error::InvalidInput(
+                "I/Q stream header sample rate is zero".to_owned(),
+            ));
+        }
+        let mut frequency = [0u8; 8];
+        frequency.copy_from_slice(&bytes[12..20]);
+        Ok(Self {
+            format,
+            sample_rate_hz,
+            center_frequency_hz: u64::from_be_bytes(frequency),
+        })
+    }
+
+    /// Checks the header against the caller's asserted sample format and rate.
+    pub fn check(self, format: IqFormat, sample_rate_hz: u32) -> Result<()> {
+        if self.format != format {
+            return Err(Error::InvalidConfiguration(format!(
+                "I/Q stream header reports {} samples, but {} was requested",
+                self.format.name(),
+                format.name()
+            )));
+        }
+        if self.sample_rate_hz != sample_rate_hz {
+            return Err(Error::InvalidConfiguration(format!(
+                "I/Q stream header reports {} Hz sample rate, but {sample_rate_hz} Hz was requested",
+                self.sample_rate_hz
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// An opened I/Q byte stream positioned at its first sample.
+pub struct IqStream {
+    pub reader: Box<dyn Read + Send>,
+    pub header: Option<IqStreamHeader>,
+    shutdown: Option<TcpStream>,
+}
+
+/// Opens standard input, a file or FIFO, or a TCP connection and consumes the
+/// stream header when `framing` requires one.
+pub fn open_iq_stream(location: &IqStreamLocation, framing: IqStreamFraming) -> Result<IqStream> {
+    let (mut reader, shutdown): (Box<dyn Read + Send>, Option<TcpStream>) = match location {
+        IqStreamLocation::Stdin => (Box::new(std::io::stdin()), None),
+        IqStreamLocation::Path(path) => (Box::new(File::open(path)?), None),
+        IqStreamLocation::Tcp(address) => {
+            let stream = TcpStream::connect(address.as_str())?;
+            stream.set_nodelay(true)?;
+            let shutdown = stream.try_clone()?;
+            (Box::new(stream), Some(shutdown))
+        }
+    };
+    let header = match framing {
+        IqStreamFraming::Raw => None,
+        IqStreamFraming::Header => {
+            let mut bytes = [0u8; IQ_STREAM_HEADER_LENGTH];
+            reader.read_exact(&mut bytes).map_err(|error| {
+                if error.kind() == std::io::ErrorKind::UnexpectedEof {
+                    Error::InvalidInput("I/Q stream ended before its 20-octet header".to_owned())
+                } else {
+                    error.into()
+                }
+            })?;
+            Some(IqStreamHeader::decode(&bytes)?)
+        }
+    };
+    Ok(IqStream {
+        reader,
+        header,
+        shutdown,
+    })
+}
+
+/// Receive-only `IqSource` over standard input, a FIFO, or a TCP stream.
+///
+/// A raw stream carries no loss information, so every read is contiguous and
+/// sample indices count delivered samples. A background reader thread keeps
+/// `read` timeouts effective on blocking pipes and sockets.
+pub struct StreamSource {
+    format: IqFormat,
+    header: Option<IqStreamHeader>,
+    reader: Option<Box<dyn Read + Send>>,
+    shutdown: Option<TcpStream>,
+    receiver: Option<Receiver<std::io::Result<Vec<u8>>>>,
+    configured: bool,
+    pending: Vec<u8>,
+    next_sample_index: u64,
+    finished: bool,
+}
+
+impl StreamSource {
+    pub fn open(
+        location: &IqStreamLocation,
+        format: IqFormat,
+        framing: IqStreamFraming,
+    ) -> Result<Self> {
+        let stream = open_iq_stream(location, framing)?;
+        let mut source = Self::from_reader(stream.reader, format, stream.header)?;
+        source.shutdown = stream.shutdown;
+        Ok(source)
+    }
+
+    /// Wraps an already positioned reader. A supplied header must agree with
+    /// `format`.
+    pub fn from_reader(
+        reader: Box<dyn Read + Send>,
+        format: IqFormat,
+        header: Option<IqStreamHeader>,
+    ) -> Result<Self> {
+        if let Some(header) = header
+            && header.format != format
+        {
+            return Err(Error::InvalidConfiguration(format!(
+                "I/Q stream header reports {} samples, but {} was requested",
+                header.format.name(),
+                format.name()
+            )));
+        }
+        Ok(Self {
+            format,
+            header,
+            reader: Some(reader),
+            shutdown: None,
+            receiver: None,
+            configured: false,
+            pending: Vec::new(),
+            next_sample_index: 0,
+            finished: false,
+        })
+    }
+
+    pub const fn header(&self) -> Option<IqStreamHeader> {
+        self.header
+    }
+
+    fn receive_chunk(&mut self, timeout: Option<Duration>) -> Result<bool> {
+        let receiver = self
+            .receiver
+            .as_ref()
+            .ok_or_else(|| Error::InvalidState("I/Q stream source is not started".to_owned()))?;
+        let received = match timeout {
+            Some(timeout) => match receiver.recv_timeout(timeout) {
+                Ok(chunk) => Some(chunk),
+                Err(RecvTimeoutError::Timeout) => return Ok(false),
+                Err(RecvTimeoutError::Disconnected) => None,
+            },
+            None => match receiver.try_recv() {
+                Ok(chunk) => Some(chunk),
+                Err(TryRecvError::Empty) => return Ok(false),
+                Err(TryRecvError::Disconnected) => None,
+            },
+        };
+        match received {
+            Some(chunk) => {
+                self.pending.extend_from_slice(&chunk?);
+                Ok(true)
+            }
+            None => {
+                self.finished = true;
+                Ok(false)
+            }
+        }
+    }
+}
+
+impl IqSource for StreamSource {
+    fn kind(&self) -> SdrKind {
+        SdrKind::Stream
+    }
+
+    fn capabilities(&self) -> SdrCapabilities {
+        SdrCapabilities {
+            minimum_frequency_hz: 0,
+            maximum_frequency_hz: u64::MAX,
+            maximum_sample_rate_hz: u32::MAX,
+            receive_channels: 1,
+        }
+    }
+
+    fn configure(&mut self, config: &SdrConfig) -> Result<()> {
+        config.validate(self.capabilities())?;
+        if let Some(header) = self.header {
+            header.check(self.format, config.sample_rate_hz)?;
+            if header.center_frequency_hz != config.center_frequency_hz {
+                return Err(Error::InvalidConfiguration(format!(
+                    "I/Q stream header reports {} Hz center frequency, but {} Hz was requested",
+                    header.center_frequency_hz, config.center_frequency_hz
+                )));
+            }
+        }
+        self.configured = true;
+        Ok(())
+    }
+
+    fn applied_sample_rate_hz(&self) -> Option<u32> {
+        self.header.map(|header| header.sample_rate_hz)
+    }
+
+    fn start(&mut self) -> Result<()> {
+        if !self.configured {
+            return Err(Error::InvalidState(
+                "I/Q stream source must be configured before start".to_owned(),
+            ));
+        }
+        let reader = self.reader.take().ok_or_else(|| {
+            Error::InvalidState("I/Q stream source cannot be restarted".to_owned())
+        })?;
+        let (sender, receiver) = sync_channel(STREAM_QUEUE_CHUNKS);
+        thread::Builder::new()
+            .name("blueoxide-iq-stream".to_owned())
+            .spawn(move || forward_stream(reader, sender))?;
+        self.receiver = Some(receiver);
+        Ok(())
+    }
+
+    fn read(
+        &mut self,
+        output: &mut [Complex32],
+        timeout: Duration,
+    ) -> Result<(usize, ReadMetadata)> {
+        let bytes_per_sample = self.format.bytes_per_sample();
+        let wanted_bytes = output.len().saturating_mul(bytes_per_sample);
+        if self.pending.len() < bytes_per_sample && !self.finished {
+            self.receive_chunk(Some(timeout))?;
+        }
+        while self.pending.len() < wanted_bytes && !self.finished {
+            if !self.receive_chunk(None)? {
+                break;
+            }
+        }
+
+        let count = output.len().min(self.pending.len() / bytes_per_sample);
+        if count == 0 {
+            if self.finished && !self.pending.is_empty() {
+                return Err(Error::InvalidInput(format!(
+                    "I/Q stream ended with {} trailing bytes, which is not a complete {bytes_per_sample}-byte sample",
+                    self.pending.len()
+                )));
+            }
+            return Ok((0, ReadMetadata::default()));
+        }
+        let byte_count = count * bytes_per_sample;
+        let samples = parse_iq_bytes(&self.pending[..byte_count], self.format)?;
+        output[..count].copy_from_slice(&samples);
+        self.pending.drain(..byte_count);
+        let metadata = ReadMetadata {
+            first_sample_index: self.next_sample_index,
+            dropped_samples_before: 0,
+            overrun: false,
+        };
+        self.next_sample_index = self
+            .next_sample_index
+            .checked_add(count as u64)
+            .ok_or_else(|| Error::InvalidInput("I/Q stream sample index overflow".to_owned()))?;
+        Ok((count, metadata))
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.receiver = None;
+        if let Some(stream) = self.shutdown.take() {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+        Ok(())
+    }
+
+    fn end_of_stream(&self) -> bool {
+        self.finished && self.pending.is_empty()
+    }
+}
+
+fn forward_stream(mut reader: Box<dyn Read + Send>, sender: SyncSender<std::io::Result<Vec<u8>>>) {
+    let mut buffer = vec![0u8; STREAM_CHUNK_BYTES];
+    loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => return,
+            Ok(count) => {
+                if sender.send(Ok(buffer[..count].to_vec())).is_err() {
+                    return;
+                }
+            }
+            Err(error) if error.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(error) => {
+                let _ = sender.send(Err(error));
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use std::net::TcpListener;
+
+    fn s16_bytes(values: &[i16]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect()
+    }
+
+    fn config(sample_rate_hz: u32) -> SdrConfig {
+        SdrConfig {
+            center_frequency_hz: 2_402_000_000,
+            sample_rate_hz,
+            bandwidth_hz: 2_000_000,
+            gain_db: 0.0,
+            channel: 0,
+        }
+    }
+
+    #[test]
+    fn parses_stream_locations() {
+        assert_eq!(
+            IqStreamLocation::parse("-").unwrap(),
+            IqStreamLocation::Stdin
+        );
+        assert_eq!(
+            IqStreamLocation::parse("tcp://127.0.0.1:1234").unwrap(),
+            IqStreamLocation::Tcp("127.0.0.1:1234".to_owned())
+        );
+        assert_eq!(
+            IqStreamLocation::parse("capture.fifo").unwrap(),
+            IqStreamLocation::Path(PathBuf::from("capture.fifo"))
+        );
+        assert!(IqStreamLocation::parse("tcp://host").is_err());
+        assert!(IqStreamLocation::parse("tcp://:99").is_err());
+        assert!(IqStreamLocation::parse("").is_err());
+    }
+
+    #[test]
+    fn header_round_trips_and_rejects_malformed_fields() {
+        let header = IqStreamHeader {
+            format: IqFormat::S16Le,
+            sample_rate_hz: 4_000_000,
+            center_frequency_hz: 2_402_000_000,
+        };
+        let bytes = header.encode();
+        assert_eq!(&bytes[..4], b"BOIQ");
+        assert_eq!(&bytes[8..12], &4_000_000u32.to_be_bytes());
+        assert_eq!(IqStreamHeader::decode(&bytes).unwrap(), header);
+
+        let mut bad_magic = bytes;
+        bad_magic[0] = b'X';
+        assert!(IqStreamHeader::decode(&bad_magic).is_err());
+        let mut bad_format = bytes;
+        bad_format[5] = 9;
+        assert!(IqStreamHeader::decode(&bad_format).is_err());
+        let mut reserved = bytes;
+        reserved[7] = 1;
+        assert!(IqStreamHeader::decode(&reserved).is_err());
+        assert!(header.check(IqFormat::F32Le, 4_000_000).is_err());
+        assert!(header.check(IqFormat::S16Le, 8_000_000).is_err());
+    }
+
+    #[test]
+    fn stream_source_counts_contiguous_samples_and_reports_end() {
+        let bytes = s16_bytes(&[1, -1, 2, -2, 3, -3, 4, -4, 5, -5]);
+        let mut source =
+            StreamSource::from_reader(Box::new(Cursor::new(bytes)), IqFormat::S16Le, None).unwrap();
+        assert!(source.start().is_err());
+        source.configure(&config(4_000_000)).unwrap();
+        source.start().unwrap();
+
+        let mut output = [Complex32::ZERO; 3];
+        let mut indices = Vec::new();
+        let mut total = 0;
+        while !source.end_of_stream() {
+            let (count, metadata) = source.read(&mut output, Duration::from_secs(1)).unwrap();
+            if count > 0 {
+                indices.push(metadata.first_sample_index);
+                total += count;
+            }
+        }
+        assert_eq!(total, 5);
+        assert_eq!(indices, [0, 3]);
+        source.stop().unwrap();
+        assert!(source.start().is_err());
+    }
+
+    #[test]
+    fn stream_source_rejects_trailing_partial_sample() {
+        let mut source =
+            StreamSource::from_reader(Box::new(Cursor::new(vec![0u8; 6])), IqFormat::S16Le, None)
+                .unwrap();
+        source.configure(&config(4_000_000)).unwrap();
+        source.start().unwrap();
+        let mut output = [Complex32::ZERO; 4];
+        let mut result = Ok((0, ReadMetadata::default()));
+        for _ in 0..4 {
+            result = source.read(&mut output, Duration::from_secs(1));
+            if result.is_err() {
+                break;
+            }
+        }
+        assert!(result.unwrap_err().to_string().contains("2 trailing bytes"));
+    }
+
+    #[test]
+    fn tcp_stream_header_is_checked_against_capture_configuration() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (mut connection, _) = listener.accept().unwrap();
+            let header = IqStreamHeader {
+                format: IqFormat::S16Le,
+                sample_rate_hz: 4_000_000,
+                center_frequency_hz: 2_402_000_000,
+            };
+            connection.write_all(&header.encode()).unwrap();
+            connection
+                .write_all(&s16_bytes(&[100, 200, 300, 400]))
+                .unwrap();
+        });
+
+        let location = IqStreamLocation::parse(&format!("tcp://{address}")).unwrap();
+        let mut source =
+            StreamSource::open(&location, IqFormat::S16Le, IqStreamFraming::Header).unwrap();
+        assert_eq!(source.applied_sample_rate_hz(), Some(4_000_000));
+        assert!(source.configure(&config(8_000_000)).is_err());
+        let mut wrong_frequency = config(4_000_000);
+        wrong_frequency.center_frequency_hz = 2_480_000_000;
+        assert!(source.configure(&wrong_frequency).is_err());
+        source.configure(&config(4_000_000)).unwrap();
+        source.start().unwrap();
+        server.join().unwrap();
+
+        let mut output = [Complex32::ZERO; 8];
+        let mut received = Vec::new();
+        while !source.end_of_stream() {
+            let (count, _) = source.read(&mut output, Duration::from_secs(1)).unwrap();
+            received.extend_from_slice(&output[..count]);
+        }
+        source.stop().unwrap();
+        assert_eq!(received.len(), 2);
+        assert_eq!(
+            received[0],
+            Complex32::new(100.0 / 32767.0, 200.0 / 32767.0)
+        );
+    }
+}
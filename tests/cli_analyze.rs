@@ -0,0 +1,441 @@
+use std::fs;
+use std::process::{Command, Output};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const LTK: &str = "bf01fb9d4ef3bc36d874f5394138684c";
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_blueoxide"))
+        .args(args)
+        .output()
+        .expect("run blueoxide")
+}
+
+fn temporary_path(suffix: &str) -> std::path::PathBuf {
+    let nonce = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time must follow the Unix epoch")
+        .as_nanos();
+    std::env::temp_dir().join(format!("blueoxide-analyze-{nonce}-{suffix}"))
+}
+
+fn assert_success(output: &Output) {
+    assert!(
+        output.status.success(),
+        "stdout: {}\nstderr: {}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+fn generate_scenario(extra: &[&str]) -> std::path::PathBuf {
+    let pcap = temporary_path("connection.pcapng");
+    let mut args = vec![
+        "scenario",
+        "--output-pcap",
+        pcap.to_str().expect("UTF-8 path"),
+        "--interval",
+        "8",
+        "--events",
+        "12",
+        "--att",
+        "1:central:0a2a00",
+        "--encrypt",
+        "2",
+        "--ltk",
+        LTK,
+        "--att",
+        "5:peripheral:1b2a0068656c6c6f",
+        "--connection-update",
+        "4:8:1:0:10:0:100",
+    ];
+    args.extend_from_slice(extra);
+    assert_success(&run(&args));
+    pcap
+}
+
+#[test]
+fn analyze_decrypts_a_capture_and_summarizes_the_connection() {
+    let pcap = generate_scenario(&[]);
+    let output = run(&[
+        "analyze",
+        "--input",
+        pcap.to_str().expect("UTF-8 path"),
+        "--ltk",
+        "00112233445566778899aabbccddeeff",
+        "--ltk",
+        LTK,
+    ]);
+    fs::remove_file(&pcap).ok();
+    assert_success(&output);
+    let stdout = String::from_utf8(output.stdout).expect("UTF-8 stdout");
+    let stderr = String::from_utf8(output.stderr).expect("UTF-8 stderr");
+
+    assert!(stdout.contains(
+        "connection_request index=1 connection=0 initiator=C6:55:44:33:22:11/random advertiser=01:02:03:04:05:06/public access_address=50654b4f crc_init=0x7a4f21"
+    ));
+    assert!(stdout.contains(
+        "connection=0 direction=central-to-peripheral direction_source=capture event=0 anchor=true timing=none"
+    ));
+    assert!(stdout.contains("att_pdu index=4 direction=central-to-peripheral opcode=0x0a"));
+    assert!(stdout.contains("description=\"LL_CONNECTION_UPDATE_IND"));
+    assert!(stdout.contains("status=new packet_counter=0"));
+    assert!(
+        stdout
+            .contains("direction=peripheral-to-central opcode=0x1b name=handle-value-notification")
+    );
+    assert!(!stdout.contains("note index="));
+    assert!(stdout.contains(
+        "connection id=0 access_address=50654b4f crc_init=0x7a4f21 advertiser=01:02:03:04:05:06/public initiator=C6:55:44:33:22:11/random interval=8 csa=CSA#2"
+    ));
+    assert!(stdout.contains(
+        "first_event=0 last_event=11 tracking=tracked encryption=decrypted:ltk1 terminated=false l2cap_pdus=2 att_pdus=2 smp_pdus=0 credit_sdus=0 errors=0"
+    ));
+    assert!(stderr.contains("analyzed 28 packet(s); crc_invalid=0"));
+}
+
+#[test]
+fn analyze_without_a_key_reports_ciphertext() {
+    let pcap = generate_scenario(&[]);
+    let output = run(&["analyze", "--input", pcap.to_str().expect("UTF-8 path")]);
+    fs::remove_file(&pcap).ok();
+    assert_success(&output);
+    let stdout = String::from_utf8(output.stdout).expect("UTF-8 stdout");
+
+    assert!(stdout.contains("ll_ciphertext index="));
+    assert!(stdout.contains("encryption=undecrypted"));
+    assert!(stdout.contains("att_pdus=1 "));
+}
+
+#[test]
+fn analyze_selects_the_ltk_from_a_bluez_key_store_by_ediv_and_rand() {
+    let pcap = generate_scenario(&[]);
+    let store = temporary_path("bluez");
+    let device = store.join("AA:BB:CC:DD:EE:FF").join("01:02:03:04:05:06");
+    fs::create_dir_all(&device).expect("create BlueZ device directory");
+    fs::write(
+        device.join("info"),
+        format!(
+            "[General]\nName=scenario\nAddressType=public\n\n\
+             [LongTermKey]\nKey=00112233445566778899AABBCCDDEEFF\nAuthenticated=0\n\
+             EncSize=16\nEDiv=1\nRand=2\n\n\
+             [PeripheralLongTermKey]\nKey={}\nAuthenticated=0\nEncSize=16\n\
+             EDiv=9332\nRand=12379813812177893520\n",
+            LTK.to_uppercase()
+        ),
+    )
+    .expect("write BlueZ info file");
+    let output = run(&[
+        "analyze",
+        "--input",
+        pcap.to_str().expect("UTF-8 path"),
+        "--keys-bluez",
+        store.to_str().expect("UTF-8 path"),
+    ]);
+    fs::remove_file(&pcap).ok();
+    fs::remove_dir_all(&store).ok();
+    assert_success(&output);
+    let stdout = String::from_utf8(output.stdout).expect("UTF-8 stdout");
+    let stderr = String::from_utf8(output.stderr).expect("UTF-8 stderr");
+
+    assert!(
+        stderr.contains(
+            "imported 2 LTK(s), 0 IRK(s), 0 CSRK(s), 0 link key(s), and 0 EAD key(s) from"
+        )
+    );
+    assert!(stdout.contains("encryption=decrypted:keyring"));
+    assert!(stdout.contains("att_pdus=2 "));
+    assert!(!stdout.contains("ll_ciphertext index="));
+}
+
+#[test]
+fn analyze_cracks_a_legacy_passkey_pairing_and_captures_the_distributed_keys() {
+    let pcap = temporary_path("pairing.pcapng");
+    let keyring = temporary_path("captured.keyring");
+    // Passkey 000412 between a KeyboardDisplay central and a DisplayOnly
+    // peripheral; the central then encrypts with the STK under EDIV 0/Rand 0.
+    assert_success(&run(&[
+        "scenario",
+        "--output-pcap",
+        pcap.to_str().expect("UTF-8 path"),
+        "--events",
+        "14",
+        "--l2cap",
+        "1:central:6:01040005100101",
+        "--l2cap",
+        "2:peripheral:6:02000005100101",
+        "--l2cap",
+        "3:central:6:03eb06d6c0f480cd91df9d63716e1aab12",
+        "--l2cap",
+        "4:peripheral:6:035ccdbc3efea192dee2254d6ea3aaa54c",
+        "--l2cap",
+        "5:central:6:04a0a1a2a3a4a5a6a7a8a9aaabacadaeaf",
+        "--l2cap",
+        "6:peripheral:6:04505152535455565758595a5b5c5d5e5f",
+        "--encrypt",
+        "7",
+        "--ltk",
+        "2440ad4dc8ed530624b7ced3d6d793bb",
+        "--enc-req",
+        "03000000000000000000001302f1e0dfcebdac24abdcba",
+        "--l2cap",
+        "10:peripheral:6:0600112233445566778899aabbccddeeff",
+        "--l2cap",
+        "10:peripheral:6:0734120102030405060708",
+        "--l2cap",
+        "11:peripheral:6:089b7d390aa610103405adc857a33402ec",
+        "--l2cap",
+        "11:peripheral:6:0900060504030201",
+    ]));
+
+    let output = run(&[
+        "analyze",
+        "--input",
+        pcap.to_str().expect("UTF-8 path"),
+        "--crack-legacy-pairing",
+        "--output-keyring",
+        keyring.to_str().expect("UTF-8 path"),
+    ]);
+    fs::remove_file(&pcap).ok();
+    assert_success(&output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(
+        "legacy_pairing index=15 status=recovered model=passkey-entry passkey=000412 tk=9c010000000000000000000000000000 stk=2440ad4dc8ed530624b7ced3d6d793bb"
+    ));
+    assert!(stdout.contains(
+        "distributed_ltk index=27 direction=peripheral-to-central address=01:02:03:04:05:06/public ediv=4660 rand=0102030405060708 key=00112233445566778899aabbccddeeff"
+    ));
+    assert!(stdout.contains(
+        "resolved_address index=31 role=smp-identity-address address=01:02:03:04:05:06/public identity=01:02:03:04:05:06/public"
+    ));
+    assert!(stdout.contains("encryption=decrypted:stk"));
+    assert!(
+        stdout.contains("advertiser_identity=01:02:03:04:05:06/public initiator_identity=none")
+    );
+    let stored = fs::read_to_string(&keyring).expect("read captured keyring");
+    fs::remove_file(&keyring).ok();
+    assert!(stored.contains(
+        "ltk address=01:02:03:04:05:06/public ediv=4660 rand=0102030405060708 key=00112233445566778899aabbccddeeff"
+    ));
+    assert!(
+        stored
+            .contains("irk address=01:02:03:04:05:06/public key=9b7d390aa610103405adc857a33402ec")
+    );
+}
+
+#[test]
+fn analyze_derives_the_ltk_of_a_secure_connections_pairing_with_the_debug_key() {
+    let pcap = temporary_path("secure-pairing.pcapng");
+    let keyring = temporary_path("derived.keyring");
+    // Just Works between NoInputNoOutput devices that both distribute link
+    // keys; the central uses the debug key, then encrypts with the derived
+    // LTK under EDIV 0/Rand 0.
+    assert_success(&run(&[
+        "scenario",
+        "--output-pcap",
+        pcap.to_str().expect("UTF-8 path"),
+        "--events",
+        "16",
+        "--l2cap",
+        "1:central:6:01030009100909",
+        "--l2cap",
+        "2:peripheral:6:02030009100909",
+        "--l2cap",
+        "3:central:6:0ce69d350e480103ccdbfdf4ac1191f4efb9a5f9e9a7832c5e2cbe97f2d203b0208bd28915d08e1c742430ed8fc24563765c15525abf9a32636deb2a65499c80dc",
+        "--l2cap",
+        "4:peripheral:6:0cedd48202b8e566df6c6ddf2b152c4f3aa2699e99968f27283944b6f017e6170294576e30657c89f252965958c75ff4565a76a85aa83cda2d2d7197cbeb7d4a19",
+        "--l2cap",
+        "5:peripheral:6:0334eb89b819bb35ec22593da727095f86",
+        "--l2cap",
+        "6:central:6:045a5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a",
+        "--l2cap",
+        "7:peripheral:6:04a5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a5",
+        "--l2cap",
+        "8:central:6:0d2d07c5e9dfc68f390e5705c6223d21ef",
+        "--l2cap",
+        "9:peripheral:6:0dc71c833f7608f239f4b21da1bb69a66d",
+        "--encrypt",
+        "10",
+        "--ltk",
+        "5c8ebfa7e743e6d7f93e780bff388af9",
+        "--enc-req",
+        "03000000000000000000001302f1e0dfcebdac24abdcba",
+        "--att",
+        "14:central:0a2a00",
+    ]));
+
+    let output = run(&[
+        "analyze",
+        "--input",
+        pcap.to_str().expect("UTF-8 path"),
+        "--output-keyring",
+        keyring.to_str().expect("UTF-8 path"),
+    ]);
+    fs::remove_file(&pcap).ok();
+    assert_success(&output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(
+        "secure_connections_pairing index=12 status=debug-key direction=central-to-peripheral"
+    ));
+    assert!(stdout.contains(
+        "secure_connections_pairing index=29 status=derived model=just-works dhkey_source=debug-key:central-to-peripheral verified=true passkey=none comparison=none dhkey=9db0b3e6d7da85bdaedd82dc34f0ca66cef80ada988fcaef649a4c91df55b991 mac_key=219853be27a59317e568f62778187770 ltk=5c8ebfa7e743e6d7f93e780bff388af9 link_key=510e00ee042d3fc3bae61627c1a1326e"
+    ));
+    assert!(stdout.contains(
+        "derived_link_key index=29 address=01:02:03:04:05:06 key=510e00ee042d3fc3bae61627c1a1326e"
+    ));
+    assert!(stdout.contains(
+        "pairing_session index=29 status=complete method=secure-connections:just-works key_size=16 initiator_distributed=0x00 responder_distributed=0x00 failure=none security_level=2 weaknesses=mitm-not-required,debug-key:central-to-peripheral"
+    ));
+    assert!(stdout.contains("encryption=decrypted:sc-ltk"));
+    assert!(stdout.contains("att_pdus=1 "));
+    let stored = fs::read_to_string(&keyring).expect("read derived keyring");
+    fs::remove_file(&keyring).ok();
+    assert!(stored.contains(
+        "ltk address=01:02:03:04:05:06/public ediv=0 rand=0000000000000000 key=5c8ebfa7e743e6d7f93e780bff388af9"
+    ));
+    assert!(stored.contains(
+        "link-key address=01:02:03:04:05:06/public key=510e00ee042d3fc3bae61627c1a1326e"
+    ));
+}
+
+#[test]
+fn analyze_decrypts_encrypted_advertising_data_with_key_material_read_over_gatt() {
+    let pcap = temporary_path("encrypted-advertising.pcapng");
+    let keyring = temporary_path("ead.keyring");
+    // The ADV_IND carries Flags and an Encrypted Data structure holding the
+    // Complete Local Name "Hidden". The central later raises the ATT MTU and
+    // reads the Encrypted Data Key Material characteristic by type.
+    assert_success(&run(&[
+        "scenario",
+        "--output-pcap",
+        pcap.to_str().expect("UTF-8 path"),
+        "--events",
+        "8",
+        "--adv-data",
+        "020106123111223344d51fee841e5de6c0b6abef6dda",
+        "--att",
+        "2:central:024000",
+        "--att",
+        "3:peripheral:034000",
+        "--att",
+        "4:central:080100ffff882b",
+        "--att",
+        "5:peripheral:091a2100101112131415161718191a1b1c1d1e1fa0a1a2a3a4a5a6a7",
+    ]));
+
+    let output = run(&[
+        "analyze",
+        "--input",
+        pcap.to_str().expect("UTF-8 path"),
+        "--output-keyring",
+        keyring.to_str().expect("UTF-8 path"),
+    ]);
+    assert_success(&output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("encrypted_data index=0 depth=0 status=undecrypted"));
+    assert!(stdout.contains(
+        "encrypted_data_key index=15 address=01:02:03:04:05:06/public key=101112131415161718191a1b1c1d1e1f iv=a0a1a2a3a4a5a6a7"
+    ));
+
+    let output = run(&[
+        "analyze",
+        "--input",
+        pcap.to_str().expect("UTF-8 path"),
+        "--keyring",
+        keyring.to_str().expect("UTF-8 path"),
+    ]);
+    fs::remove_file(&pcap).ok();
+    fs::remove_file(&keyring).ok();
+    assert_success(&output);
+    assert!(String::from_utf8_lossy(&output.stdout).contains(
+        "encrypted_data index=0 depth=0 key_address=01:02:03:04:05:06/public status=decrypted structures=0x09:48696464656e"
+    ));
+}
+
+#[test]
+fn analyze_rejects_iq_options_without_a_channel() {
+    let output = run(&[
+        "analyze",
+        "--input",
+        "capture.cf32",
+        "--sample-rate",
+        "4000000",
+    ]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("I/Q options require --channel"));
+}
+
+#[test]
+fn analyze_exports_reassembled_l2cap_as_btsnoop_from_one_host() {
+    let pcap = generate_scenario(&[]);
+    let btsnoop = temporary_path("host.btsnoop");
+    let output = run(&[
+        "analyze",
+        "--input",
+        pcap.to_str().expect("UTF-8 path"),
+        "--ltk",
+        LTK,
+        "--output-btsnoop",
+        btsnoop.to_str().expect("UTF-8 path"),
+        "--btsnoop-host",
+        "peripheral",
+    ]);
+    fs::remove_file(&pcap).ok();
+    assert_success(&output);
+    let log = fs::read(&btsnoop).expect("read btsnoop");
+    fs::remove_file(&btsnoop).ok();
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("wrote 3 btsnoop record(s) from the peripheral host")
+    );
+
+    assert_eq!(&log[..16], b"btsnoop\0\0\0\0\x01\0\0\x03\xea");
+    let mut records = Vec::new();
+    let mut offset = 16;
+    while offset < log.len() {
+        let length = u32::from_be_bytes(log[offset..offset + 4].try_into().expect("length"));
+        let flags = u32::from_be_bytes(log[offset + 8..offset + 12].try_into().expect("flags"));
+        let end = offset + 24 + length as usize;
+        records.push((flags, log[offset + 24..end].to_vec()));
+        offset = end;
+    }
+    assert_eq!(
+        records,
+        [
+            (
+                3,
+                vec![
+                    0x04, 0x3e, 0x13, 0x01, 0x00, 0x00, 0x00, 0x01, 0x01, 0x11, 0x22, 0x33, 0x44,
+                    0x55, 0xc6, 0x08, 0x00, 0x00, 0x00, 0x64, 0x00, 0x05,
+                ],
+            ),
+            (
+                1,
+                vec![
+                    0x02, 0x00, 0x20, 0x07, 0x00, 0x03, 0x00, 0x04, 0x00, 0x0a, 0x2a, 0x00,
+                ],
+            ),
+            (
+                0,
+                vec![
+                    0x02, 0x00, 0x00, 0x0c, 0x00, 0x08, 0x00, 0x04, 0x00, 0x1b, 0x2a, 0x00, b'h',
+                    b'e', b'l', b'l', b'o',
+                ],
+            ),
+        ]
+    );
+
+    let output = run(&[
+        "analyze",
+        "--input",
+        "capture.pcapng",
+        "--btsnoop-host",
+        "observer",
+    ]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains(
+            "invalid value \"observer\" for --btsnoop-host; expected central or peripheral"
+        )
+    );
+}
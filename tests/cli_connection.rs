@@ -41,6 +41,66 @@ fn cli_prints_core_csa2_connection_plan_with_sample_timing() {
     );
 }
 
+#[test]
+fn cli_prints_connection_plan_as_json_lines() {
+    let output = Command::new(env!("CARGO_BIN_EXE_blueoxide"))
+        .args([
+            "connection-plan",
+            "--access-address",
+            "0x8e89bed6",
+            "--channel-map",
+            "ffffffff1f",
+            "--csa",
+            "2",
+            "--interval",
+            "24",
+            "--sample-rate",
+            "4000000",
+            "--anchor-sample",
+            "1000",
+            "--events",
+            "2",
+            "--output-format",
+            "jsonl",
+        ])
+        .output()
+        .expect("run blueoxide");
+
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8(output.stdout).expect("UTF-8 stdout");
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        [
+            r#"{"schema_version":1,"type":"connection_event","event":0,"channel":25,"frequency_hz":2456000000,"central_to_peripheral_phy":"LE-1M","peripheral_to_central_phy":"LE-1M","expected_sample":1000,"earliest_sample":1000,"latest_sample":1000,"widening_samples":0}"#,
+            r#"{"schema_version":1,"type":"connection_event","event":1,"channel":20,"frequency_hz":2446000000,"central_to_peripheral_phy":"LE-1M","peripheral_to_central_phy":"LE-1M","expected_sample":121000,"earliest_sample":120937,"latest_sample":121063,"widening_samples":63}"#,
+        ]
+    );
+
+    let output = Command::new(env!("CARGO_BIN_EXE_blueoxide"))
+        .args([
+            "connection-plan",
+            "--access-address",
+            "0x8e89bed6",
+            "--channel-map",
+            "ffffffff1f",
+            "--interval",
+            "24",
+            "--output-format",
+            "xml",
+        ])
+        .output()
+        .expect("run blueoxide");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("invalid value \"xml\" for --output-format; expected text or jsonl")
+    );
+}
+
 #[test]
 fn cli_connection_plan_applies_directional_phy_update_at_instant() {
     let output = Command::new(env!("CARGO_BIN_EXE_blueoxide"))
@@ -1,5 +1,7 @@
 use blueoxide::ble::{BleChannel, bytes_to_bits_lsb, crc24_bytes, whiten_bits};
 use blueoxide::demod::LeUncodedPhy;
+use blueoxide::link_layer::LinkDirection;
+use blueoxide::pcapng::{CaptureCrcStatus, CaptureFileReader};
 use std::f32::consts::TAU;
 use std::fs;
 use std::process::Command;
@@ -242,7 +244,7 @@ fn cli_decodes_le_2m_data_channel_waveform() {
     let access_address_bytes = access_address.to_le_bytes();
     let phy_flags = 0x4c31u16.to_le_bytes();
     let expected_pcap_packet = [
-        [channel.index(), 0, 0, 0].as_slice(),
+        [channel.rf_channel(), 0, 0, 0].as_slice(),
         access_address_bytes.as_slice(),
         phy_flags.as_slice(),
         access_address_bytes.as_slice(),
@@ -630,8 +632,10 @@ fn cli_reassembles_asserted_plaintext_l2cap_fragments() {
     ));
 }
 
-#[test]
-fn cli_authenticates_decrypts_and_reassembles_encrypted_waveforms() {
+/// Four encrypted central-to-peripheral packets on channel 12: a new PDU at
+/// packet counter 7, its retransmission, a new PDU at counter 8, and a copy
+/// with a damaged payload whose MIC fails.
+fn encrypted_waveform_fixture() -> Vec<u8> {
     let channel = BleChannel::new(12).expect("valid channel");
     let access_address = 0x1234_5678u32;
     let crc_init = 0x00ab_cdef;
@@ -690,8 +694,13 @@ fn cli_authenticates_decrypts_and_reassembles_encrypted_waveforms() {
         iq_bytes.extend_from_slice(&i.to_le_bytes());
         iq_bytes.extend_from_slice(&q.to_le_bytes());
     }
+    iq_bytes
+}
+
+#[test]
+fn cli_authenticates_decrypts_and_reassembles_encrypted_waveforms() {
     let iq_path = temporary_path("encrypted.cf32");
-    fs::write(&iq_path, iq_bytes).expect("write fixture");
+    fs::write(&iq_path, encrypted_waveform_fixture()).expect("write fixture");
     let output = Command::new(env!("CARGO_BIN_EXE_blueoxide"))
         .args([
             "decode-data",
@@ -805,6 +814,239 @@ fn cli_authenticates_decrypts_and_reassembles_encrypted_waveforms() {
     ));
 }
 
+#[test]
+fn cli_writes_decrypted_pdus_and_session_key_to_pcapng() {
+    let iq_path = temporary_path("encrypted-pcap.cf32");
+    fs::write(&iq_path, encrypted_waveform_fixture()).expect("write fixture");
+    let run = |payload: &str, pcap_path: &std::path::Path| {
+        Command::new(env!("CARGO_BIN_EXE_blueoxide"))
+            .args([
+                "decode-data",
+                "--input",
+                iq_path.to_str().expect("UTF-8 temporary path"),
+                "--channel",
+                "12",
+                "--sample-rate",
+                "4000000",
+                "--access-address",
+                "0x12345678",
+                "--crc-init",
+                "0xabcdef",
+                "--aa-errors",
+                "0",
+                "--session-key",
+                "99ad1b5226a37e3e058e3b8e27c2c666",
+                "--iv",
+                "24abdcbabebaafde",
+                "--decrypt-direction",
+                "central-to-peripheral",
+                "--packet-counter",
+                "5",
+                "--max-counter-skip",
+                "2",
+                "--output-pcap",
+                pcap_path.to_str().expect("UTF-8 temporary path"),
+                "--pcap-payload",
+                payload,
+            ])
+            .output()
+            .expect("run blueoxide")
+    };
+    let read = |pcap_path: &std::path::Path| {
+        let bytes = fs::read(pcap_path).expect("read PCAPNG");
+        let _ = fs::remove_file(pcap_path);
+        let mut reader = CaptureFileReader::new(bytes.as_slice()).expect("PCAPNG header");
+        let mut packets = Vec::new();
+        while let Some(packet) = reader.next_packet().expect("PCAPNG packet") {
+            packets.push(packet);
+        }
+        packets
+    };
+    let both_path = temporary_path("both.pcapng");
+    let plaintext_path = temporary_path("plaintext.pcapng");
+    let both = run("both", &both_path);
+    let plaintext = run("plaintext", &plaintext_path);
+    let _ = fs::remove_file(&iq_path);
+    for output in [&both, &plaintext] {
+        assert!(
+            output.status.success(),
+            "stderr: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let key_comment = "ll_encryption access_address=0x12345678 session_key=99ad1b5226a37e3e058e3b8e27c2c666 iv=24abdcbabebaafde";
+    let both = read(&both_path);
+    assert_eq!(
+        both.iter()
+            .map(|packet| packet.decrypted)
+            .collect::<Vec<_>>(),
+        [false, true, false, true, false, true, false]
+    );
+    assert_eq!(both[0].pdu.payload.len(), 13);
+    assert_eq!(both[1].pdu.payload, [5, 0, 4, 0, 0x0c, 1, 0, 2, 0]);
+    assert_eq!(both[1].direction, Some(LinkDirection::CentralToPeripheral));
+    assert!(
+        both[1]
+            .comments
+            .iter()
+            .any(|comment| comment == key_comment)
+    );
+    assert!(
+        both[3]
+            .comments
+            .iter()
+            .all(|comment| comment != key_comment)
+    );
+    assert_eq!(both[5].pdu.payload, [3, 0, 4, 0, 0x0a, 1, 0]);
+    assert!(
+        both[6]
+            .comments
+            .iter()
+            .any(|comment| comment.starts_with("decryption_error="))
+    );
+
+    let plaintext = read(&plaintext_path);
+    assert_eq!(
+        plaintext
+            .iter()
+            .map(|packet| packet.decrypted)
+            .collect::<Vec<_>>(),
+        [true, true, true, false]
+    );
+
+    let rejected = Command::new(env!("CARGO_BIN_EXE_blueoxide"))
+        .args([
+            "decode-data",
+            "--input",
+            "unused.cf32",
+            "--channel",
+            "12",
+            "--sample-rate",
+            "4000000",
+            "--access-address",
+            "0x12345678",
+            "--crc-init",
+            "0xabcdef",
+            "--output-pcap",
+            "unused.pcapng",
+            "--pcap-payload",
+            "plaintext",
+        ])
+        .output()
+        .expect("run blueoxide");
+    assert_eq!(rejected.status.code(), Some(2));
+    assert!(
+        String::from_utf8_lossy(&rejected.stderr).contains(
+            "--pcap-payload plaintext or both requires the complete decryption option set"
+        )
+    );
+}
+
+#[test]
+fn cli_reports_crc_failures_only_on_request_and_keeps_them_out_of_l2cap() {
+    let channel = BleChannel::new(12).expect("valid channel");
+    let access_address = 0x1234_5678u32;
+    let l2cap = [5, 0, 4, 0, 0x0a, 1, 0, 2, 0];
+    let mut phase = 0.0f32;
+    let mut samples = vec![(1.0f32, 0.0f32); 11];
+    // A CRC computed with the wrong initialization value fails on receive.
+    append_packet_samples(
+        &mut samples,
+        &mut phase,
+        channel,
+        access_address,
+        0x0012_3456,
+        [0x02, l2cap.len() as u8],
+        &l2cap,
+    );
+    samples.extend(std::iter::repeat_n((phase.cos(), phase.sin()), 160));
+    append_packet_samples(
+        &mut samples,
+        &mut phase,
+        channel,
+        access_address,
+        0x00ab_cdef,
+        [0x02, l2cap.len() as u8],
+        &l2cap,
+    );
+    samples.extend(std::iter::repeat_n((phase.cos(), phase.sin()), 160));
+    let mut iq_bytes = Vec::with_capacity(samples.len() * 8);
+    for (i, q) in samples {
+        iq_bytes.extend_from_slice(&i.to_le_bytes());
+        iq_bytes.extend_from_slice(&q.to_le_bytes());
+    }
+    let iq_path = temporary_path("crc-failure.cf32");
+    let pcap_path = temporary_path("crc-failure.pcapng");
+    fs::write(&iq_path, iq_bytes).expect("write fixture");
+    let run = |extra: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_blueoxide"))
+            .args([
+                "decode-data",
+                "--input",
+                iq_path.to_str().expect("UTF-8 temporary path"),
+                "--channel",
+                "12",
+                "--sample-rate",
+                "4000000",
+                "--access-address",
+                "0x12345678",
+                "--crc-init",
+                "0xabcdef",
+                "--aa-errors",
+                "0",
+                "--plaintext-l2cap-direction",
+                "central-to-peripheral",
+            ])
+            .args(extra)
+            .output()
+            .expect("run blueoxide")
+    };
+    let default = run(&[]);
+    let emitted = run(&[
+        "--emit-crc-failures",
+        "--output-pcap",
+        pcap_path.to_str().expect("UTF-8 temporary path"),
+    ]);
+    let _ = fs::remove_file(&iq_path);
+    for output in [&default, &emitted] {
+        assert!(
+            output.status.success(),
+            "stderr: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let default_stdout = String::from_utf8(default.stdout).expect("UTF-8 stdout");
+    let default_stderr = String::from_utf8(default.stderr).expect("UTF-8 stderr");
+    assert!(!default_stdout.contains("crc_failure"));
+    assert!(!default_stderr.contains("CRC-failed"));
+
+    let stdout = String::from_utf8(emitted.stdout).expect("UTF-8 stdout");
+    let stderr = String::from_utf8(emitted.stderr).expect("UTF-8 stderr");
+    assert_eq!(
+        stdout.matches("crc_failure channel=12 phy=LE-1M").count(),
+        1
+    );
+    assert!(stdout.contains("header=0209 cte_info=none payload=050004000a01000200"));
+    assert_eq!(stdout.matches("l2cap_pdu direction=").count(), 1);
+    assert!(stderr.contains("decoded 1 CRC-valid data-channel packet(s)"));
+    assert!(stderr.contains("reported 1 CRC-failed frame(s)"));
+    assert!(stderr.contains("reassembled 1 plaintext L2CAP PDU(s)"));
+
+    let bytes = fs::read(&pcap_path).expect("read PCAPNG");
+    let _ = fs::remove_file(&pcap_path);
+    let mut reader = CaptureFileReader::new(bytes.as_slice()).expect("PCAPNG header");
+    let mut statuses = Vec::new();
+    while let Some(packet) = reader.next_packet().expect("PCAPNG packet") {
+        statuses.push(packet.crc);
+    }
+    assert_eq!(
+        statuses,
+        [CaptureCrcStatus::Invalid, CaptureCrcStatus::Valid]
+    );
+}
+
 #[test]
 fn cli_validates_direction_tagged_encryption_trace_arguments() {
     let output = Command::new(env!("CARGO_BIN_EXE_blueoxide"))
@@ -1021,6 +1263,54 @@ fn cli_tracks_eatt_channels_credits_reconfiguration_and_disconnection() {
     );
 }
 
+#[test]
+fn cli_prints_l2cap_trace_as_json_lines() {
+    let output = Command::new(env!("CARGO_BIN_EXE_blueoxide"))
+        .args([
+            "l2cap-trace",
+            "--output-format",
+            "jsonl",
+            "--pdu",
+            "c2p:0x0005:17010c00270040004000030040004100",
+            "--pdu",
+            "p2c:0x0005:18010c00400040000200000042004300",
+            "--pdu",
+            "c2p:0x0042:05001b01",
+            "--pdu",
+            "c2p:0x0042:00aabb",
+        ])
+        .output()
+        .expect("run blueoxide");
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8(output.stdout).expect("UTF-8 stdout");
+    let lines = stdout.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 12, "stdout: {stdout}");
+    assert!(
+        lines
+            .iter()
+            .all(|line| line.starts_with(r#"{"schema_version":1,"type":""#))
+    );
+    assert_eq!(
+        lines[1],
+        r#"{"schema_version":1,"type":"l2cap_signal","index":0,"direction":"central-to-peripheral","code":23,"name":"enhanced-credit-based-connection-request","identifier":1,"spsm":39,"mtu":64,"mps":64,"initial_credits":3,"source_cids":[64,65]}"#
+    );
+    assert_eq!(
+        lines[10..],
+        [
+            r#"{"schema_version":1,"type":"l2cap_credit_sdu","index":3,"direction":"central-to-peripheral","cid":66,"spsm":39,"eatt":true,"segments":2,"payload":"1b0100aabb"}"#,
+            r#"{"schema_version":1,"type":"eatt_pdu","index":3,"direction":"central-to-peripheral","cid":66,"opcode":27,"name":"handle-value-notification","pdu_type":"notification","handle":1,"value":"aabb"}"#,
+        ]
+    );
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("processed 4 directed L2CAP PDU(s); accepted=4 errors=0 open_channels=2")
+    );
+}
+
 #[test]
 fn cli_l2cap_trace_validates_arguments_and_continues_after_credit_error() {
     let output = Command::new(env!("CARGO_BIN_EXE_blueoxide"))
@@ -3,7 +3,8 @@ use blueoxide::ble::{
 };
 use std::f32::consts::TAU;
 use std::fs;
-use std::process::Command;
+use std::io::Write;
+use std::process::{Command, Stdio};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 fn temporary_path(suffix: &str) -> std::path::PathBuf {
@@ -578,3 +579,124 @@ fn cli_plans_and_reanchors_periodic_advertising() {
         );
     }
 }
+
+#[test]
+fn cli_decodes_advertising_from_standard_input() {
+    let channel = BleChannel::new(38).expect("valid channel");
+    let payload = [1, 2, 3, 4, 5, 6, 2, 0x01, 0x06];
+    let mut pdu = vec![0x00, payload.len() as u8];
+    pdu.extend_from_slice(&payload);
+    pdu.extend_from_slice(&crc24_bytes(&pdu, LE_ADV_CRC_INIT));
+    let mut body = bytes_to_bits_lsb(&pdu);
+    whiten_bits(&mut body, channel);
+    let mut bits = bytes_to_bits_lsb(&[0xaa]);
+    bits.extend(bytes_to_bits_lsb(&LE_ADV_ACCESS_ADDRESS.to_le_bytes()));
+    bits.extend(body);
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_blueoxide"))
+        .args([
+            "decode",
+            "--input",
+            "-",
+            "--channel",
+            "38",
+            "--sample-rate",
+            "4000000",
+            "--block-samples",
+            "64",
+            "--aa-errors",
+            "0",
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("run blueoxide");
+    let samples = modulate_bits(bits, 4, 250_000.0, 4_000_000.0);
+    let sample_count = samples.len() / 8;
+    child
+        .stdin
+        .take()
+        .expect("piped stdin")
+        .write_all(&samples)
+        .expect("write samples");
+    let output = child.wait_with_output().expect("wait for blueoxide");
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8(output.stdout).expect("UTF-8 stdout");
+    let stderr = String::from_utf8(output.stderr).expect("UTF-8 stderr");
+    assert!(stdout.contains("ADV_IND advertiser=06:05:04:03:02:01"));
+    assert!(stderr.contains(&format!(
+        "decoded 1 CRC-valid packet(s) from {sample_count} sample(s)"
+    )));
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_blueoxide"))
+        .args([
+            "decode",
+            "--input",
+            "-",
+            "--channel",
+            "38",
+            "--sample-rate",
+            "4000000",
+            "--aa-errors",
+            "0",
+            "--output-format",
+            "jsonl",
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("run blueoxide");
+    child
+        .stdin
+        .take()
+        .expect("piped stdin")
+        .write_all(&samples)
+        .expect("write samples");
+    let output = child.wait_with_output().expect("wait for blueoxide");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("UTF-8 stdout");
+    let lines = stdout.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 1, "stdout: {stdout}");
+    for expected in [
+        r#"{"schema_version":1,"type":"advertising_packet","channel":38,"#,
+        r#""pdu":{"name":"ADV_IND","advertiser":"06:05:04:03:02:01","#,
+        r#""ad_structures":[{"ad_type":1,"name":"Flags","data":"06","#,
+    ] {
+        assert!(
+            lines[0].contains(expected),
+            "missing {expected} in {stdout}"
+        );
+    }
+    assert!(lines[0].ends_with('}'));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_blueoxide"))
+        .args([
+            "decode",
+            "--input",
+            "-",
+            "--channel",
+            "38",
+            "--sample-rate",
+            "4000000",
+            "--format",
+            "s16le",
+            "--max-samples",
+            "10",
+        ])
+        .stdin(Stdio::from(
+            fs::File::open(env!("CARGO_BIN_EXE_blueoxide")).expect("open binary as input"),
+        ))
+        .output()
+        .expect("run blueoxide");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("I/Q stream exceeded the configured limit of 10 samples")
+    );
+}
@@ -0,0 +1,142 @@
+use blueoxide::pcapng::CaptureFileReader;
+use std::fs;
+use std::process::{Command, Output};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Runs blueoxide as Wireshark would, with every vendor library missing so
+/// only the replay interface is present.
+fn run_extcap(args: &[&str]) -> Output {
+    let missing = std::env::temp_dir().join("blueoxide-extcap-library-that-does-not-exist");
+    Command::new(env!("CARGO_BIN_EXE_blueoxide"))
+        .args(args)
+        .env("BLUEOXIDE_BLADERF_LIBRARY", &missing)
+        .env("BLUEOXIDE_LIMESUITE_LIBRARY", &missing)
+        .env("BLUEOXIDE_XTRX_LIBRARY", &missing)
+        .output()
+        .expect("run blueoxide")
+}
+
+fn temporary_path(suffix: &str) -> std::path::PathBuf {
+    let nonce = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time must follow the Unix epoch")
+        .as_nanos();
+    std::env::temp_dir().join(format!("blueoxide-extcap-{nonce}-{suffix}"))
+}
+
+fn assert_success(output: &Output) {
+    assert!(
+        output.status.success(),
+        "stdout: {}\nstderr: {}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn extcap_handshake_lists_interface_dlt_and_config() {
+    let interfaces = run_extcap(&["--extcap-interfaces", "--extcap-version=4.2.5"]);
+    assert_success(&interfaces);
+    let stdout = String::from_utf8_lossy(&interfaces.stdout);
+    let lines: Vec<_> = stdout.lines().collect();
+    assert!(lines[0].starts_with("extcap {version="), "{stdout}");
+    assert_eq!(
+        &lines[1..],
+        [
+            "interface {value=blueoxide-replay}{display=blueoxide replay of a SigMF or raw I/Q recording}"
+        ]
+    );
+
+    let dlts = run_extcap(&["--extcap-dlts", "--extcap-interface", "blueoxide-replay"]);
+    assert_success(&dlts);
+    assert_eq!(
+        String::from_utf8_lossy(&dlts.stdout).trim(),
+        "dlt {number=256}{name=BLUETOOTH_LE_LL_WITH_PHDR}{display=Bluetooth LE Link Layer with pseudo-header}"
+    );
+
+    let config = run_extcap(&["--extcap-config", "--extcap-interface", "blueoxide-replay"]);
+    assert_success(&config);
+    let stdout = String::from_utf8_lossy(&config.stdout);
+    assert!(stdout.contains(
+        "arg {number=0}{call=--channel}{display=Channel}{type=integer}{range=0,39}{default=37}"
+    ));
+    assert!(stdout.contains("{call=--access-address}"));
+    assert!(stdout.contains("{call=--recording}{display=Recording}{type=fileselect}"));
+    assert!(stdout.contains("{value=2m}{display=LE 2M}"));
+    assert!(!stdout.contains("{call=--gain}"));
+
+    let unknown = run_extcap(&[
+        "--extcap-config",
+        "--extcap-interface",
+        "blueoxide-bladerf-missing",
+    ]);
+    assert!(!unknown.status.success());
+    assert!(String::from_utf8_lossy(&unknown.stderr).contains("unknown extcap interface"));
+}
+
+#[test]
+fn extcap_capture_streams_replayed_packets_into_the_fifo() {
+    let data = temporary_path("adv.sigmf-data");
+    let meta = data.with_extension("sigmf-meta");
+    let fifo = temporary_path("fifo.pcapng");
+    let synthesize = run_extcap(&[
+        "synthesize",
+        "--output",
+        data.to_str().expect("UTF-8 path"),
+        "--channel",
+        "38",
+        "--packet",
+        "1m:1000:0009010203040506020106",
+        "--packet",
+        "1m:3001:0009060504030201020106",
+        "--snr",
+        "25",
+    ]);
+    assert_success(&synthesize);
+
+    let capture = run_extcap(&[
+        "--capture",
+        "--extcap-interface",
+        "blueoxide-replay",
+        "--fifo",
+        fifo.to_str().expect("UTF-8 path"),
+        "--extcap-capture-filter",
+        "",
+        "--channel",
+        "38",
+        "--phy",
+        "1m",
+        "--aa-errors=0",
+        "--format",
+        "f32le",
+        "--replay-speed",
+        "max",
+        "--recording",
+        meta.to_str().expect("UTF-8 path"),
+    ]);
+    fs::remove_file(&data).expect("remove SigMF data");
+    fs::remove_file(&meta).expect("remove SigMF metadata");
+    assert_success(&capture);
+
+    let bytes = fs::read(&fifo).expect("read FIFO output");
+    fs::remove_file(&fifo).expect("remove FIFO output");
+    let mut reader = CaptureFileReader::new(bytes.as_slice()).expect("section header");
+    let mut payloads = Vec::new();
+    while let Some(packet) = reader.next_packet().expect("packet") {
+        assert_eq!(packet.pdu.channel.index(), 38);
+        payloads.push(packet.pdu.payload[..6].to_vec());
+    }
+    assert_eq!(payloads, [[1, 2, 3, 4, 5, 6], [6, 5, 4, 3, 2, 1]]);
+
+    let filtered = run_extcap(&[
+        "--capture",
+        "--extcap-interface",
+        "blueoxide-replay",
+        "--fifo",
+        fifo.to_str().expect("UTF-8 path"),
+        "--extcap-capture-filter",
+        "btle",
+    ]);
+    assert!(!filtered.status.success());
+    assert!(String::from_utf8_lossy(&filtered.stderr).contains("capture filters"));
+}
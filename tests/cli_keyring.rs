@@ -0,0 +1,392 @@
+use std::fs;
+use std::process::{Command, Output};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const LTK: &str = "bf01fb9d4ef3bc36d874f5394138684c";
+const WRONG_LTK: &str = "55555555555555555555555555555555";
+/// EDIV and Rand of the scenario's default LL_ENC_REQ.
+const EDIV: &str = "9332";
+const RAND: &str = "9078563412efcdab";
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_blueoxide"))
+        .args(args)
+        .output()
+        .expect("run blueoxide")
+}
+
+fn temporary_path(suffix: &str) -> std::path::PathBuf {
+    let nonce = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time must follow the Unix epoch")
+        .as_nanos();
+    std::env::temp_dir().join(format!("blueoxide-keyring-{nonce}-{suffix}"))
+}
+
+fn assert_success(output: &Output) {
+    assert!(
+        output.status.success(),
+        "stdout: {}\nstderr: {}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+/// Stores the scenario LTK for an unrelated address, then an unaddressed
+/// wrong key with the same EDIV/Rand, which is therefore tried first.
+fn keyring_with_a_decoy(path: &str) {
+    assert_success(&run(&[
+        "keyring",
+        "add",
+        "--keyring",
+        path,
+        "--address",
+        "AA:BB:CC:DD:EE:FF/random",
+        "--ltk",
+        LTK,
+        "--ediv",
+        EDIV,
+        "--rand",
+        RAND,
+    ]));
+    assert_success(&run(&[
+        "keyring",
+        "add",
+        "--keyring",
+        path,
+        "--ltk",
+        WRONG_LTK,
+        "--ediv",
+        EDIV,
+        "--rand",
+        RAND,
+    ]));
+}
+
+#[test]
+fn keyring_adds_lists_and_removes_entries() {
+    let path = temporary_path("store.keyring");
+    let path = path.to_str().expect("UTF-8 path");
+    let added = run(&[
+        "keyring",
+        "add",
+        "--keyring",
+        path,
+        "--address",
+        "C6:55:44:33:22:11/random",
+        "--ltk",
+        LTK,
+        "--ediv",
+        EDIV,
+        "--rand",
+        RAND,
+        "--irk",
+        "00112233445566778899aabbccddeeff",
+        "--csrk",
+        "0f0e0d0c0b0a09080706050403020100",
+    ]);
+    assert_success(&added);
+    assert!(
+        String::from_utf8_lossy(&added.stderr)
+            .contains("added 1 LTK(s), 1 IRK(s), 1 CSRK(s), 0 link key(s), and 0 EAD key(s) to")
+    );
+
+    let listed = run(&["keyring", "list", "--keyring", path]);
+    assert_success(&listed);
+    let stdout = String::from_utf8_lossy(&listed.stdout);
+    assert!(stdout.contains(
+        "keyring_entry index=0 type=ltk address=C6:55:44:33:22:11/random ediv=9332 rand=9078563412efcdab key=redacted"
+    ));
+    assert!(stdout.contains("type=irk address=C6:55:44:33:22:11/random key=redacted"));
+    assert!(stdout.contains("type=csrk"));
+    assert!(!stdout.contains(LTK));
+    let shown = run(&["keyring", "list", "--keyring", path, "--show-keys"]);
+    assert!(String::from_utf8_lossy(&shown.stdout).contains(LTK));
+
+    let removed = run(&[
+        "keyring",
+        "remove",
+        "--keyring",
+        path,
+        "--address",
+        "C6:55:44:33:22:11",
+        "--type",
+        "irk",
+    ]);
+    assert_success(&removed);
+    assert!(String::from_utf8_lossy(&removed.stderr).contains("removed 1 entry"));
+    let listed = run(&["keyring", "list", "--keyring", path]);
+    let stdout = String::from_utf8_lossy(&listed.stdout);
+    assert!(!stdout.contains("type=irk"));
+    assert_eq!(stdout.lines().count(), 2);
+
+    let rejected = run(&["keyring", "add", "--keyring", path, "--irk", LTK]);
+    assert!(!rejected.status.success());
+    assert!(String::from_utf8_lossy(&rejected.stderr).contains("require --address"));
+    let rejected = run(&["keyring", "list", "--keyring", path, "--type", "ltk"]);
+    assert!(!rejected.status.success());
+    assert!(String::from_utf8_lossy(&rejected.stderr).contains("does not accept --type"));
+    fs::remove_file(path).ok();
+}
+
+#[test]
+fn analyze_and_decode_data_try_keyring_candidates_until_one_authenticates() {
+    let iq = temporary_path("connection.cf32");
+    let pcap = temporary_path("connection.pcapng");
+    let keyring = temporary_path("candidates.keyring");
+    let keyring = keyring.to_str().expect("UTF-8 path");
+    assert_success(&run(&[
+        "scenario",
+        "--output",
+        iq.to_str().expect("UTF-8 path"),
+        "--iq-channel",
+        "16",
+        "--output-pcap",
+        pcap.to_str().expect("UTF-8 path"),
+        "--interval",
+        "8",
+        "--att",
+        "1:central:0a2a00",
+        "--encrypt",
+        "2",
+        "--ltk",
+        LTK,
+        "--att",
+        "5:peripheral:1b2a0068656c6c6f",
+        "--snr",
+        "25",
+    ]));
+    keyring_with_a_decoy(keyring);
+
+    let analyzed = run(&[
+        "analyze",
+        "--input",
+        pcap.to_str().expect("UTF-8 path"),
+        "--keyring",
+        keyring,
+    ]);
+    fs::remove_file(&pcap).ok();
+    assert_success(&analyzed);
+    let stdout = String::from_utf8_lossy(&analyzed.stdout);
+    assert!(
+        String::from_utf8_lossy(&analyzed.stderr).contains(
+            "imported 2 LTK(s), 0 IRK(s), 0 CSRK(s), 0 link key(s), and 0 EAD key(s) from"
+        )
+    );
+    assert!(stdout.contains("encryption=decrypted:keyring"), "{stdout}");
+    assert!(stdout.contains("att_pdus=2 "));
+
+    let decoded = run(&[
+        "decode-data",
+        "--input",
+        iq.to_str().expect("UTF-8 path"),
+        "--channel",
+        "16",
+        "--sample-rate",
+        "4000000",
+        "--access-address",
+        "0x50654b4f",
+        "--crc-init",
+        "0x7a4f21",
+        "--keyring",
+        keyring,
+        "--enc-req",
+        "039078563412efcdab74241302f1e0dfcebdac24abdcba",
+        "--enc-rsp",
+        "047968574635241302bebaafde",
+        "--decrypt-direction",
+        "central-to-peripheral",
+        "--packet-counter",
+        "0",
+    ]);
+    fs::remove_file(&iq).ok();
+    fs::remove_file(keyring).ok();
+    assert_success(&decoded);
+    assert!(
+        String::from_utf8_lossy(&decoded.stderr)
+            .contains("an alternative keyring LTK authenticated the first encrypted packet")
+    );
+    // LL_START_ENC_RSP, the first packet encrypted under the session key.
+    assert!(
+        String::from_utf8_lossy(&decoded.stdout)
+            .contains("status=new packet_counter=0 skipped_counters=0 header=0301 payload=06")
+    );
+}
+
+#[test]
+fn analyze_verifies_signed_writes_with_a_keyring_csrk() {
+    let pcap = temporary_path("signed.pcapng");
+    let keyring = temporary_path("signing.keyring");
+    let keyring = keyring.to_str().expect("UTF-8 path");
+    // Signed writes of aabb to handle 3 with SignCounters 1, 1 again, a
+    // tampered copy of 2, and 2.
+    assert_success(&run(&[
+        "scenario",
+        "--output-pcap",
+        pcap.to_str().expect("UTF-8 path"),
+        "--events",
+        "6",
+        "--att",
+        "1:central:d20300aabb01000000c94912931154ee48",
+        "--att",
+        "2:central:d20300aabb01000000c94912931154ee48",
+        "--att",
+        "3:central:d20300aabc02000000fd98612de5abd274",
+        "--att",
+        "4:central:d20300aabb02000000fd98612de5abd274",
+    ]));
+    assert_success(&run(&[
+        "keyring",
+        "add",
+        "--keyring",
+        keyring,
+        "--address",
+        "C6:55:44:33:22:11/random",
+        "--csrk",
+        "c0c1c2c3c4c5c6c7c8c9cacbcccdcecf",
+    ]));
+
+    let analyzed = run(&[
+        "analyze",
+        "--input",
+        pcap.to_str().expect("UTF-8 path"),
+        "--keyring",
+        keyring,
+    ]);
+    fs::remove_file(&pcap).ok();
+    fs::remove_file(keyring).ok();
+    assert_success(&analyzed);
+    let statuses: Vec<_> = String::from_utf8_lossy(&analyzed.stdout)
+        .lines()
+        .filter_map(|line| line.split_once("att_signature index="))
+        .map(|(_, rest)| rest.split_once(' ').expect("fields").1.to_owned())
+        .collect();
+    assert_eq!(
+        statuses,
+        [
+            "device=C6:55:44:33:22:11/random sign_counter=1 status=valid",
+            "device=C6:55:44:33:22:11/random sign_counter=1 status=replayed last_sign_counter=1",
+            "device=C6:55:44:33:22:11/random sign_counter=2 status=invalid",
+            "device=C6:55:44:33:22:11/random sign_counter=2 status=valid",
+        ]
+    );
+}
+
+#[test]
+fn keyring_add_stores_the_cross_transport_counterpart_of_a_key() {
+    let path = temporary_path("ctkd.keyring");
+    let path = path.to_str().expect("UTF-8 path");
+    // The Core specification's CTKD sample keys, in HCI/SMP field order.
+    let added = run(&[
+        "keyring",
+        "add",
+        "--keyring",
+        path,
+        "--address",
+        "01:02:03:04:05:06/public",
+        "--ltk",
+        "64bf4f33336c06bd584b26e3bcf98d36",
+        "--ctkd",
+        "h7",
+    ]);
+    assert_success(&added);
+    assert!(
+        String::from_utf8_lossy(&added.stderr)
+            .contains("added 1 LTK(s), 0 IRK(s), 0 CSRK(s), 1 link key(s), and 0 EAD key(s) to")
+    );
+    let added = run(&[
+        "keyring",
+        "add",
+        "--keyring",
+        path,
+        "--address",
+        "0A:0B:0C:0D:0E:0F",
+        "--link-key",
+        "00010203040506070809000102030405",
+        "--ctkd",
+        "h6",
+    ]);
+    assert_success(&added);
+
+    let listed = run(&["keyring", "list", "--keyring", path, "--show-keys"]);
+    assert_success(&listed);
+    let stdout = String::from_utf8_lossy(&listed.stdout);
+    assert!(stdout.contains(
+        "type=link-key address=01:02:03:04:05:06/public key=35b84730f4f1390a5302a4dc79d37a28"
+    ));
+    assert!(stdout.contains(
+        "type=ltk address=0A:0B:0C:0D:0E:0F/public ediv=0 rand=0000000000000000 key=300a0df1439a2c8aa1dfa3f172fb13a8"
+    ));
+    assert_eq!(stdout.lines().count(), 4);
+
+    let rejected = run(&[
+        "keyring",
+        "add",
+        "--keyring",
+        path,
+        "--ltk",
+        LTK,
+        "--ctkd",
+        "h6",
+    ]);
+    assert!(!rejected.status.success());
+    assert!(String::from_utf8_lossy(&rejected.stderr).contains("--ctkd requires --address"));
+    fs::remove_file(path).ok();
+}
+
+#[test]
+fn keyring_add_stores_encrypted_advertising_data_key_material() {
+    let path = temporary_path("ead.keyring");
+    let path = path.to_str().expect("UTF-8 path");
+    let added = run(&[
+        "keyring",
+        "add",
+        "--keyring",
+        path,
+        "--address",
+        "01:02:03:04:05:06/public",
+        "--ead-key",
+        "101112131415161718191a1b1c1d1e1f",
+        "--ead-iv",
+        "a0a1a2a3a4a5a6a7",
+    ]);
+    assert_success(&added);
+    assert!(
+        String::from_utf8_lossy(&added.stderr)
+            .contains("added 0 LTK(s), 0 IRK(s), 0 CSRK(s), 0 link key(s), and 1 EAD key(s) to")
+    );
+
+    let listed = run(&["keyring", "list", "--keyring", path]);
+    assert_success(&listed);
+    assert!(String::from_utf8_lossy(&listed.stdout).contains(
+        "keyring_entry index=0 type=ead-key address=01:02:03:04:05:06/public key=redacted iv=redacted"
+    ));
+    let shown = run(&["keyring", "list", "--keyring", path, "--show-keys"]);
+    assert!(String::from_utf8_lossy(&shown.stdout).contains(
+        "type=ead-key address=01:02:03:04:05:06/public key=101112131415161718191a1b1c1d1e1f iv=a0a1a2a3a4a5a6a7"
+    ));
+
+    let rejected = run(&[
+        "keyring",
+        "add",
+        "--keyring",
+        path,
+        "--ead-key",
+        "101112131415161718191a1b1c1d1e1f",
+    ]);
+    assert!(!rejected.status.success());
+    assert!(String::from_utf8_lossy(&rejected.stderr).contains("must be given together"));
+    let removed = run(&[
+        "keyring",
+        "remove",
+        "--keyring",
+        path,
+        "--address",
+        "01:02:03:04:05:06",
+        "--type",
+        "ead-key",
+    ]);
+    assert_success(&removed);
+    assert!(String::from_utf8_lossy(&removed.stderr).contains("removed 1 entry"));
+    fs::remove_file(path).ok();
+}
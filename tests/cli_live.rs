@@ -298,3 +298,277 @@ fn xtrx_capture_missing_library_is_reported_as_an_error() {
     assert!(stderr.contains("failed to load native library"));
     assert!(stderr.contains(&missing.to_string_lossy().to_string()));
 }
+
+fn advertisement_s16_samples() -> Vec<(i16, i16)> {
+    use blueoxide::ble::{
+        BleChannel, LE_ADV_ACCESS_ADDRESS, LE_ADV_CRC_INIT, bytes_to_bits_lsb, crc24_bytes,
+        whiten_bits,
+    };
+
+    let channel = BleChannel::new(37).expect("valid channel");
+    let payload = [1, 2, 3, 4, 5, 6, 2, 0x01, 0x06];
+    let mut pdu = vec![0x00, payload.len() as u8];
+    pdu.extend_from_slice(&payload);
+    pdu.extend_from_slice(&crc24_bytes(&pdu, LE_ADV_CRC_INIT));
+    let mut body = bytes_to_bits_lsb(&pdu);
+    whiten_bits(&mut body, channel);
+    let mut bits = bytes_to_bits_lsb(&[0xaa]);
+    bits.extend(bytes_to_bits_lsb(&LE_ADV_ACCESS_ADDRESS.to_le_bytes()));
+    bits.extend(body);
+    let mut phase = 0.0f32;
+    let mut samples = vec![(i16::MAX, 0i16); 11];
+    for bit in bits {
+        let step = std::f32::consts::TAU * if bit { 250_000.0 } else { -250_000.0 } / 4_000_000.0;
+        for _ in 0..4 {
+            phase += step;
+            samples.push((
+                (phase.cos() * 30_000.0) as i16,
+                (phase.sin() * 30_000.0) as i16,
+            ));
+        }
+    }
+    samples
+}
+
+#[test]
+fn capture_stream_device_decodes_tcp_samples_until_end_of_stream() {
+    use blueoxide::iq::IqFormat;
+    use blueoxide::stream::IqStreamHeader;
+    use std::io::Write;
+    use std::net::TcpListener;
+
+    let samples = advertisement_s16_samples();
+    let listener = TcpListener::bind("127.0.0.1:0").expect("bind loopback listener");
+    let address = listener.local_addr().expect("listener address");
+    let sample_count = samples.len();
+    let server = std::thread::spawn(move || {
+        let (mut connection, _) = listener.accept().expect("accept client");
+        let header = IqStreamHeader {
+            format: IqFormat::S16Le,
+            sample_rate_hz: 4_000_000,
+            center_frequency_hz: 2_402_000_000,
+        };
+        connection
+            .write_all(&header.encode())
+            .expect("write header");
+        for (i, q) in samples {
+            connection.write_all(&i.to_le_bytes()).expect("write I");
+            connection.write_all(&q.to_le_bytes()).expect("write Q");
+        }
+    });
+
+    let output = run(&[
+        "capture",
+        "--device",
+        "stream",
+        "--identifier",
+        &format!("tcp://{address}"),
+        "--format",
+        "s16le",
+        "--framing",
+        "header",
+        "--channel",
+        "37",
+        "--seconds",
+        "60",
+        "--aa-errors",
+        "0",
+    ]);
+    server.join().expect("server thread");
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        String::from_utf8_lossy(&output.stdout).contains("ADV_IND advertiser=06:05:04:03:02:01")
+    );
+    assert!(String::from_utf8_lossy(&output.stderr).contains(&format!(
+        "capture complete: samples={sample_count} packets=1 overruns=0 dropped=0 discontinuities=0"
+    )));
+
+    let output = run(&[
+        "capture",
+        "--device",
+        "bladerf",
+        "--channel",
+        "37",
+        "--framing",
+        "header",
+    ]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("--framing applies only to --device stream")
+    );
+}
+
+#[test]
+fn capture_replays_sigmf_recording_with_injected_faults() {
+    let base = std::env::temp_dir().join(format!(
+        "blueoxide-replay-{}-{}",
+        std::process::id(),
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .expect("clock after epoch")
+            .as_nanos()
+    ));
+    let meta = base.with_extension("sigmf-meta");
+    let data = base.with_extension("sigmf-data");
+    std::fs::write(
+        &meta,
+        r#"{"global":{"core:datatype":"ci16_le","core:sample_rate":4000000,"core:version":"1.0.0"},
+            "captures":[{"core:sample_start":0,"core:frequency":2402000000}],"annotations":[]}"#,
+    )
+    .expect("write SigMF metadata");
+    let samples = advertisement_s16_samples();
+    let sample_count = samples.len();
+    let mut bytes = Vec::new();
+    for (i, q) in samples {
+        bytes.extend_from_slice(&i.to_le_bytes());
+        bytes.extend_from_slice(&q.to_le_bytes());
+    }
+    std::fs::write(&data, bytes).expect("write SigMF data");
+
+    let output = run(&[
+        "capture",
+        "--device",
+        "replay",
+        "--identifier",
+        meta.to_str().expect("UTF-8 path"),
+        "--channel",
+        "37",
+        "--replay-speed",
+        "10",
+        "--impair",
+        "timeout:4",
+        "--impair",
+        "gap:6:3",
+        "--aa-errors",
+        "0",
+    ]);
+    let wrong_channel = run(&[
+        "capture",
+        "--device",
+        "replay",
+        "--identifier",
+        data.to_str().expect("UTF-8 path"),
+        "--channel",
+        "38",
+    ]);
+    std::fs::remove_file(&meta).expect("remove SigMF metadata");
+    std::fs::remove_file(&data).expect("remove SigMF data");
+
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        String::from_utf8_lossy(&output.stdout).contains("ADV_IND advertiser=06:05:04:03:02:01")
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains(&format!("replay ended at recording sample {sample_count}")));
+    assert!(stderr.contains(&format!(
+        "capture complete: samples={} packets=1 overruns=0 dropped=3 discontinuities=1",
+        sample_count - 3
+    )));
+
+    assert_eq!(wrong_channel.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&wrong_channel.stderr).contains(
+        "replay recording was captured at 2402000000 Hz center frequency, but 2426000000 Hz was requested"
+    ));
+
+    let output = run(&[
+        "capture",
+        "--device",
+        "bladerf",
+        "--channel",
+        "37",
+        "--impair",
+        "timeout:4",
+    ]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("--replay-speed and --impair apply only to --device replay")
+    );
+}
+
+#[test]
+fn serve_exports_stream_source_to_remote_capture_client() {
+    use std::io::{BufRead, BufReader};
+    use std::process::Stdio;
+
+    let samples = advertisement_s16_samples();
+    let sample_count = samples.len();
+    let nonce = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .expect("system time must follow the Unix epoch")
+        .as_nanos();
+    let iq_path = std::env::temp_dir().join(format!("blueoxide-cli-{nonce}-served.cs16"));
+    let bytes: Vec<u8> = samples
+        .iter()
+        .flat_map(|(i, q)| i.to_le_bytes().into_iter().chain(q.to_le_bytes()))
+        .collect();
+    std::fs::write(&iq_path, bytes).expect("write fixture");
+
+    let mut server = Command::new(env!("CARGO_BIN_EXE_blueoxide"))
+        .args([
+            "serve",
+            "--device",
+            "stream",
+            "--identifier",
+            iq_path.to_str().expect("UTF-8 temporary path"),
+            "--format",
+            "s16le",
+            "--listen",
+            "127.0.0.1:0",
+            "--max-connections",
+            "1",
+        ])
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("run blueoxide serve");
+    let mut server_stderr = BufReader::new(server.stderr.take().expect("piped stderr"));
+    let mut line = String::new();
+    server_stderr
+        .read_line(&mut line)
+        .expect("read serve banner");
+    let address = line
+        .trim()
+        .strip_prefix("serving Stream I/Q on ")
+        .unwrap_or_else(|| panic!("unexpected serve banner {line:?}"))
+        .to_owned();
+
+    let output = run(&[
+        "capture",
+        "--device",
+        "remote",
+        "--identifier",
+        &address,
+        "--channel",
+        "37",
+        "--seconds",
+        "60",
+        "--aa-errors",
+        "0",
+    ]);
+    let status = server.wait().expect("wait for serve");
+    let _ = std::fs::remove_file(&iq_path);
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stdout).contains("ADV_IND advertiser=06:05:04:03:02:01")
+    );
+    assert!(String::from_utf8_lossy(&output.stderr).contains(&format!(
+        "capture complete: samples={sample_count} packets=1 overruns=0 dropped=0 discontinuities=0"
+    )));
+    let mut rest = String::new();
+    std::io::Read::read_to_string(&mut server_stderr, &mut rest).expect("read serve log");
+    assert!(rest.contains("client disconnected"));
+}
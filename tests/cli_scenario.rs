@@ -0,0 +1,163 @@
+use std::fs;
+use std::process::{Command, Output};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const LTK: &str = "bf01fb9d4ef3bc36d874f5394138684c";
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_blueoxide"))
+        .args(args)
+        .output()
+        .expect("run blueoxide")
+}
+
+fn temporary_path(suffix: &str) -> std::path::PathBuf {
+    let nonce = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time must follow the Unix epoch")
+        .as_nanos();
+    std::env::temp_dir().join(format!("blueoxide-scenario-{nonce}-{suffix}"))
+}
+
+fn assert_success(output: &Output) {
+    assert!(
+        output.status.success(),
+        "stdout: {}\nstderr: {}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+fn pcapng_block_types(bytes: &[u8]) -> Vec<u32> {
+    let mut types = Vec::new();
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let word = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+        types.push(word(offset));
+        offset += word(offset + 4) as usize;
+    }
+    types
+}
+
+#[test]
+fn scenario_writes_every_packet_to_pcapng_and_one_channel_to_iq() {
+    let iq = temporary_path("connection.cf32");
+    let pcap = temporary_path("connection.pcapng");
+    let output = run(&[
+        "scenario",
+        "--output",
+        iq.to_str().expect("UTF-8 path"),
+        "--iq-channel",
+        "28",
+        "--interval",
+        "8",
+        "--output-pcap",
+        pcap.to_str().expect("UTF-8 path"),
+        "--att",
+        "1:central:0a2a00",
+        "--encrypt",
+        "2",
+        "--ltk",
+        LTK,
+        "--channel-map-update",
+        "5:9:000000f01f",
+        "--connection-update",
+        "10:14:1:3:12:0:100",
+        "--snr",
+        "25",
+    ]);
+    assert_success(&output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.lines().count(), 36, "{stdout}");
+    assert!(stdout.contains(
+        "event=1 central-to-peripheral channel=18 access_address_sample=53616 llid=start-or-complete header=0e07 payload=030004000a2a00"
+    ));
+    assert!(stdout.contains(
+        "event=10 central-to-peripheral channel=28 access_address_sample=413616 llid=control header=0f0c payload=000103000c00000064000e00 packet_counter=2"
+    ));
+
+    let bytes = fs::read(&pcap).expect("PCAPNG output");
+    fs::remove_file(&pcap).expect("remove PCAPNG");
+    let blocks = pcapng_block_types(&bytes);
+    assert_eq!(blocks[0], 0x0a0d_0d0a);
+    assert_eq!(blocks.iter().filter(|&&block| block == 6).count(), 36);
+    // One interface per channel the scenario used: 37 and twelve data channels.
+    assert_eq!(blocks.iter().filter(|&&block| block == 1).count(), 13);
+    let contains = |needle: &[u8]| bytes.windows(needle.len()).any(|window| window == needle);
+    assert!(contains(b"command=scenario sample_rate_hz=4000000"));
+    assert!(contains(b"ble-ch28"));
+    assert!(contains(
+        b"sample=413616 event=10 decryption=new packet_counter=2"
+    ));
+
+    let decode = run(&[
+        "decode-data",
+        "--input",
+        iq.to_str().expect("UTF-8 path"),
+        "--channel",
+        "28",
+        "--sample-rate",
+        "4000000",
+        "--access-address",
+        "0x50654b4f",
+        "--crc-init",
+        "0x7a4f21",
+        "--ltk",
+        LTK,
+        "--enc-req",
+        "039078563412efcdab74241302f1e0dfcebdac24abdcba",
+        "--enc-rsp",
+        "047968574635241302bebaafde",
+        "--decrypt-direction",
+        "central-to-peripheral",
+        "--packet-counter",
+        "2",
+    ]);
+    fs::remove_file(&iq).expect("remove I/Q file");
+    assert_success(&decode);
+    let stdout = String::from_utf8_lossy(&decode.stdout);
+    assert!(stdout.contains("sample=413616 "), "{stdout}");
+    assert!(
+        stdout.contains("status=new packet_counter=2 skipped_counters=0 header=0f0c payload=000103000c00000064000e00"),
+        "{stdout}"
+    );
+    assert!(
+        String::from_utf8_lossy(&decode.stderr)
+            .contains("decoded 4 CRC-valid data-channel packet(s)")
+    );
+}
+
+#[test]
+fn scenario_rejects_incomplete_and_unschedulable_requests() {
+    let path = temporary_path("rejected.pcapng");
+    let path = path.to_str().expect("UTF-8 path");
+    for (args, message) in [
+        (
+            vec!["--events", "4"],
+            "requires --output FILE --iq-channel N",
+        ),
+        (
+            vec!["--output-pcap", path, "--encrypt", "2"],
+            "--encrypt and --ltk must be supplied together",
+        ),
+        (
+            vec!["--output-pcap", path, "--att", "20:central:0a0100"],
+            "event 20",
+        ),
+        (
+            vec!["--output-pcap", path, "--hop", "4", "--csa", "1"],
+            "hop increment",
+        ),
+    ] {
+        let mut command = vec!["scenario"];
+        command.extend(args);
+        let output = run(&command);
+        assert_eq!(output.status.code(), Some(2));
+        assert!(
+            String::from_utf8_lossy(&output.stderr).contains(message),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    assert!(!std::path::Path::new(path).exists());
+}
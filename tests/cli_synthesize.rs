@@ -0,0 +1,280 @@
+use blueoxide::pcapng::CaptureFileReader;
+use std::fs;
+use std::process::{Command, Output};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_blueoxide"))
+        .args(args)
+        .output()
+        .expect("run blueoxide")
+}
+
+fn temporary_path(suffix: &str) -> std::path::PathBuf {
+    let nonce = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time must follow the Unix epoch")
+        .as_nanos();
+    std::env::temp_dir().join(format!("blueoxide-synth-{nonce}-{suffix}"))
+}
+
+fn assert_success(output: &Output) {
+    assert!(
+        output.status.success(),
+        "stdout: {}\nstderr: {}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn synthesized_sigmf_recording_replays_through_live_capture() {
+    let data = temporary_path("adv.sigmf-data");
+    let meta = data.with_extension("sigmf-meta");
+    let output = run(&[
+        "synthesize",
+        "--output",
+        data.to_str().expect("UTF-8 path"),
+        "--channel",
+        "38",
+        "--packet",
+        "1m:1000:0009010203040506020106",
+        "--packet",
+        "1m:3001:0009060504030201020106",
+        "--snr",
+        "25",
+        "--cfo",
+        "20000",
+    ]);
+    assert_success(&output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains(
+            "packet 0: phy=LE-1M channel=38 start_sample=1000 access_address_sample=1032"
+        )
+    );
+    assert!(
+        stdout.contains(
+            "packet 1: phy=LE-1M channel=38 start_sample=3001 access_address_sample=3033"
+        )
+    );
+    assert!(
+        fs::read_to_string(&meta)
+            .expect("SigMF metadata")
+            .contains("\"core:frequency\":2426000000")
+    );
+
+    let capture = run(&[
+        "capture",
+        "--device",
+        "replay",
+        "--identifier",
+        meta.to_str().expect("UTF-8 path"),
+        "--channel",
+        "38",
+        "--aa-errors",
+        "0",
+    ]);
+    fs::remove_file(&data).expect("remove SigMF data");
+    fs::remove_file(&meta).expect("remove SigMF metadata");
+    assert_success(&capture);
+    let stdout = String::from_utf8_lossy(&capture.stdout);
+    assert!(stdout.contains("sample=1032 "), "{stdout}");
+    assert!(stdout.contains("sample=3033 "), "{stdout}");
+    assert!(stdout.contains("advertiser=06:05:04:03:02:01"));
+    assert!(stdout.contains("advertiser=01:02:03:04:05:06"));
+}
+
+#[test]
+fn synthesized_le_2m_data_packet_decodes_from_s16_file() {
+    let path = temporary_path("data.cs16");
+    let output = run(&[
+        "synthesize",
+        "--output",
+        path.to_str().expect("UTF-8 path"),
+        "--format",
+        "s16le",
+        "--channel",
+        "12",
+        "--sample-rate",
+        "8000000",
+        "--access-address",
+        "0x12345678",
+        "--crc-init",
+        "0xabcdef",
+        "--packet",
+        "2m:400:0207030004000a0100",
+        "--snr",
+        "30",
+        "--drift-ppm",
+        "40",
+        "--multipath",
+        "2:0.2:90",
+    ]);
+    assert_success(&output);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("crc=f2838c"));
+
+    let decode = run(&[
+        "decode-data",
+        "--input",
+        path.to_str().expect("UTF-8 path"),
+        "--format",
+        "s16le",
+        "--channel",
+        "12",
+        "--phy",
+        "2m",
+        "--sample-rate",
+        "8000000",
+        "--access-address",
+        "0x12345678",
+        "--crc-init",
+        "0xabcdef",
+    ]);
+    fs::remove_file(&path).expect("remove I/Q file");
+    assert_success(&decode);
+    let stdout = String::from_utf8_lossy(&decode.stdout);
+    assert!(stdout.contains("sample=464 "), "{stdout}");
+    assert!(stdout.contains("payload=030004000a0100"), "{stdout}");
+}
+
+#[test]
+fn synthesize_rejects_unsupported_phys_and_mismatched_lengths() {
+    let path = temporary_path("rejected.cf32");
+    let path = path.to_str().expect("UTF-8 path");
+    for (args, message) in [
+        (
+            vec!["--channel", "37", "--packet", "coded:0:0000"],
+            "LE Coded synthesis is not implemented",
+        ),
+        (
+            vec!["--channel", "37", "--packet", "1m:0:000901"],
+            "LE PDU has 3 octets, but its header requires 11",
+        ),
+        (
+            vec![
+                "--channel",
+                "12",
+                "--access-address",
+                "0x12345678",
+                "--packet",
+                "1m:0:0100",
+            ],
+            "--access-address and --crc-init must be supplied together",
+        ),
+        (
+            vec![
+                "--channel",
+                "37",
+                "--samples",
+                "100",
+                "--packet",
+                "1m:0:0000",
+            ],
+            "beyond the 100-sample output",
+        ),
+    ] {
+        let mut command = vec!["synthesize", "--output", path];
+        command.extend(args);
+        let output = run(&command);
+        assert_eq!(output.status.code(), Some(2));
+        assert!(
+            String::from_utf8_lossy(&output.stderr).contains(message),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    assert!(!std::path::Path::new(path).exists());
+}
+
+#[test]
+fn live_capture_rotates_pcapng_into_a_ring_of_readable_files() {
+    let data = temporary_path("rotate.sigmf-data");
+    let meta = data.with_extension("sigmf-meta");
+    let directory = temporary_path("rotate");
+    fs::create_dir(&directory).expect("create output directory");
+    let output = run(&[
+        "synthesize",
+        "--output",
+        data.to_str().expect("UTF-8 path"),
+        "--channel",
+        "37",
+        "--packet",
+        "1m:1000:0009010203040506020106",
+        "--packet",
+        "1m:3001:0009060504030201020106",
+        "--packet",
+        "1m:5002:0009a1a2a3a4a5a6020106",
+        "--snr",
+        "25",
+    ]);
+    assert_success(&output);
+
+    let capture = run(&[
+        "capture",
+        "--device",
+        "replay",
+        "--identifier",
+        meta.to_str().expect("UTF-8 path"),
+        "--channel",
+        "37",
+        "--aa-errors",
+        "0",
+        "--output-pcap",
+        directory.join("ble.pcapng").to_str().expect("UTF-8 path"),
+        "--pcap-max-packets",
+        "1",
+        "--pcap-ring-files",
+        "2",
+        "--pcap-flush-ms",
+        "50",
+        "--capture-start-ns",
+        "1792454399000000000",
+    ]);
+    fs::remove_file(&data).expect("remove SigMF data");
+    fs::remove_file(&meta).expect("remove SigMF metadata");
+    assert_success(&capture);
+
+    let mut names: Vec<_> = fs::read_dir(&directory)
+        .expect("list output directory")
+        .map(|entry| entry.expect("directory entry").file_name())
+        .collect();
+    names.sort();
+    assert_eq!(
+        names,
+        [
+            "ble_00002_20261019235959.pcapng",
+            "ble_00003_20261019235959.pcapng"
+        ]
+    );
+    let mut advertisers = Vec::new();
+    for name in &names {
+        let bytes = fs::read(directory.join(name)).expect("read rotated PCAPNG");
+        let mut reader = CaptureFileReader::new(bytes.as_slice()).expect("section header");
+        let packet = reader
+            .next_packet()
+            .expect("packet")
+            .expect("one packet per file");
+        assert!(reader.next_packet().expect("end of file").is_none());
+        advertisers.push(packet.pdu.payload[0..6].to_vec());
+    }
+    fs::remove_dir_all(&directory).expect("remove output directory");
+    assert_eq!(
+        advertisers,
+        [[6, 5, 4, 3, 2, 1], [0xa1, 0xa2, 0xa3, 0xa4, 0xa5, 0xa6]]
+    );
+
+    let rejected = run(&[
+        "capture",
+        "--device",
+        "replay",
+        "--identifier",
+        "unused.sigmf-meta",
+        "--channel",
+        "37",
+        "--pcap-ring-files",
+        "2",
+    ]);
+    assert!(!rejected.status.success());
+    assert!(String::from_utf8_lossy(&rejected.stderr).contains("require --output-pcap"));
+}